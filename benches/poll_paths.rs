@@ -0,0 +1,59 @@
+//! Benchmarks of the read and write poll paths over a pseudo terminal pair
+//!
+//! Run with `cargo bench --features bench`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokio::runtime::Runtime;
+
+#[cfg(unix)]
+fn pty_pair() -> (tokio_serial::SerialStream, tokio_serial::SerialStream) {
+    tokio_serial::SerialStream::pair().expect("unable to create pty pair")
+}
+
+#[cfg(unix)]
+fn latency(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (mut tx, mut rx) = rt.block_on(async { pty_pair() });
+
+    c.bench_function("pty single byte latency", |b| {
+        b.iter(|| {
+            rt.block_on(tokio_serial::bench::latency(&mut tx, &mut rx, 1))
+                .unwrap()
+        })
+    });
+}
+
+#[cfg(unix)]
+fn throughput(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (mut tx, mut rx) = rt.block_on(async { pty_pair() });
+
+    let mut group = c.benchmark_group("pty throughput");
+    let bytes = 64 * 1024;
+    group.throughput(Throughput::Bytes(bytes as u64));
+    for chunk_size in [64, 1024, 4096].iter() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(chunk_size),
+            chunk_size,
+            |b, &chunk_size| {
+                b.iter(|| {
+                    rt.block_on(tokio_serial::bench::throughput(
+                        &mut tx, &mut rx, bytes, chunk_size,
+                    ))
+                    .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+#[cfg(unix)]
+criterion_group!(benches, latency, throughput);
+
+#[cfg(not(unix))]
+fn unsupported(_: &mut Criterion) {}
+
+#[cfg(not(unix))]
+criterion_group!(benches, unsupported);
+
+criterion_main!(benches);
@@ -18,6 +18,8 @@ impl Decoder for LineCodec {
     type Item = String;
     type Error = io::Error;
 
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         let newline = src.as_ref().iter().position(|b| *b == b'\n');
         if let Some(n) = newline {
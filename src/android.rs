@@ -0,0 +1,442 @@
+//! Ports whose descriptor and settings are managed by someone else, like Android apps
+//!
+//! Android apps reach USB serial adapters through the Java `UsbManager`, not through a tty:
+//! the app opens a `UsbDeviceConnection`, sets the line up with the vendor control requests
+//! of the adapter (usually through a library like usb-serial-for-android) and moves the bytes
+//! itself.  Handing the Rust side a descriptor carrying those bytes, typically one end of a
+//! `ParcelFileDescriptor.createSocketPair()` fed by the Java side or a `/dev/ttyACM*` node on
+//! rooted devices, lets protocol stacks written against this crate run unchanged in the app.
+//!
+//! [`ExternalSerialStream`] wraps such a descriptor as it is: termios is never touched, the
+//! settings and control lines go through a [`LineControl`] instead, implemented by the app
+//! with calls back into Java over JNI.  [`Preconfigured`] is the control for a line already
+//! set up by the Java side.
+//!
+//! The module builds on every unix so the wiring can be tested off the device.
+//!
+//! ```no_run
+//! use std::os::unix::io::{FromRawFd, OwnedFd, RawFd};
+//! use tokio_serial::android::{ExternalSerialStream, Preconfigured};
+//!
+//! // `fd` came from `ParcelFileDescriptor.detachFd()` through JNI
+//! fn wrap(fd: RawFd) -> tokio_serial::Result<ExternalSerialStream<Preconfigured>> {
+//!     // SAFETY: the descriptor was detached, it is owned by nothing else
+//!     let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+//!     ExternalSerialStream::new(fd, Preconfigured, &tokio_serial::new("", 115200))
+//! }
+//! ```
+use crate::settings::Settings;
+use crate::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use futures::ready;
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use std::fmt;
+use std::io::{self, Read, Result as IoResult, Write};
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Settings and control lines of a port configured outside of the tty layer
+///
+/// Only [`set_parameters`](Self::set_parameters) is required, the other methods fail with
+/// `Unsupported` unless implemented.
+pub trait LineControl: Send {
+    /// Apply the framing of the line
+    fn set_parameters(
+        &mut self,
+        baud_rate: u32,
+        data_bits: DataBits,
+        parity: Parity,
+        stop_bits: StopBits,
+    ) -> crate::Result<()>;
+
+    /// Apply the flow control of the line
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> crate::Result<()> {
+        let _ = flow_control;
+        Err(unsupported("flow control"))
+    }
+
+    /// Set the RTS line
+    fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
+        let _ = level;
+        Err(unsupported("RTS"))
+    }
+
+    /// Set the DTR line
+    fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
+        let _ = level;
+        Err(unsupported("DTR"))
+    }
+
+    /// Returns the level of the CTS line
+    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+        Err(unsupported("CTS"))
+    }
+
+    /// Returns the level of the DSR line
+    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+        Err(unsupported("DSR"))
+    }
+
+    /// Returns the level of the RI line
+    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+        Err(unsupported("RI"))
+    }
+
+    /// Returns the level of the DCD line
+    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+        Err(unsupported("DCD"))
+    }
+
+    /// Send a break while `on` is true
+    fn set_break(&mut self, on: bool) -> crate::Result<()> {
+        let _ = on;
+        Err(unsupported("break"))
+    }
+
+    /// Drop the buffered bytes of the adapter
+    fn clear(&mut self, buffer_to_clear: ClearBuffer) -> crate::Result<()> {
+        let _ = buffer_to_clear;
+        Err(unsupported("clearing buffers"))
+    }
+}
+
+fn unsupported(what: &str) -> crate::Error {
+    crate::Error::new(
+        crate::ErrorKind::Io(io::ErrorKind::Unsupported),
+        format!("{} is not supported by this port", what),
+    )
+}
+
+/// The control of a line set up before the descriptor was handed over
+///
+/// Settings changes are only recorded and reported back by the getters.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Preconfigured;
+
+impl LineControl for Preconfigured {
+    fn set_parameters(&mut self, _: u32, _: DataBits, _: Parity, _: StopBits) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, _: FlowControl) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// A port on a descriptor opened and configured elsewhere, see the [module](self) docs
+pub struct ExternalSerialStream<C> {
+    inner: AsyncFd<OwnedFd>,
+    control: Mutex<C>,
+    name: Option<String>,
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+}
+
+impl<C: LineControl> ExternalSerialStream<C> {
+    /// Wrap `fd`, applying the settings of `builder` through `control`
+    ///
+    /// The descriptor is switched to non-blocking mode and otherwise left alone.  A non-empty
+    /// builder path is reported as the port's name.  Must be called inside a tokio runtime.
+    pub fn new(
+        fd: OwnedFd,
+        mut control: C,
+        builder: &crate::SerialPortBuilder,
+    ) -> crate::Result<Self> {
+        let settings = Settings::from_builder(builder);
+        control.set_parameters(
+            settings.baud_rate,
+            settings.data_bits,
+            settings.parity,
+            settings.stop_bits,
+        )?;
+        if settings.flow_control != FlowControl::None {
+            control.set_flow_control(settings.flow_control)?;
+        }
+        if let Some(level) = settings.dtr_on_open {
+            control.write_data_terminal_ready(level)?;
+        }
+
+        set_nonblocking(fd.as_raw_fd())?;
+        Ok(Self {
+            inner: AsyncFd::new(fd)?,
+            control: Mutex::new(control),
+            name: Some(settings.path).filter(|path| !path.is_empty()),
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            flow_control: settings.flow_control,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+        })
+    }
+
+    /// Returns the line control
+    pub fn control(&self) -> MutexGuard<'_, C> {
+        lock(&self.control)
+    }
+
+    /// Consumes the port, returning the descriptor and the line control
+    pub fn into_parts(self) -> (OwnedFd, C) {
+        let control = self
+            .control
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        (self.inner.into_inner(), control)
+    }
+
+    fn set_parameters(
+        &mut self,
+        baud_rate: u32,
+        data_bits: DataBits,
+        parity: Parity,
+        stop_bits: StopBits,
+    ) -> crate::Result<()> {
+        lock(&self.control).set_parameters(baud_rate, data_bits, parity, stop_bits)?;
+        self.baud_rate = baud_rate;
+        self.data_bits = data_bits;
+        self.parity = parity;
+        self.stop_bits = stop_bits;
+        Ok(())
+    }
+}
+
+fn lock<C>(control: &Mutex<C>) -> MutexGuard<'_, C> {
+    control
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    // SAFETY: plain calls, the results are checked
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn read_fd(fd: RawFd, buf: &mut [u8]) -> IoResult<usize> {
+    // SAFETY: the pointer and length describe writable memory owned by `buf`
+    let n = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+fn write_fd(fd: RawFd, buf: &[u8]) -> IoResult<usize> {
+    // SAFETY: the pointer and length describe memory owned by `buf`
+    let n = unsafe { libc::write(fd, buf.as_ptr().cast(), buf.len()) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+fn queued(fd: RawFd, request: libc::c_ulong) -> crate::Result<u32> {
+    let mut count: libc::c_int = 0;
+    // SAFETY: the ioctl writes an int
+    if unsafe { libc::ioctl(fd, request as _, &mut count) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(count as u32)
+}
+
+impl<C: LineControl> AsyncRead for ExternalSerialStream<C> {
+    /// Attempts to read bytes from the descriptor
+    ///
+    /// End of file means the other side closed the descriptor or the device went away.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|inner| read_fd(inner.as_raw_fd(), unfilled)) {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) if crate::is_hangup(&err) => return Poll::Ready(Ok(())),
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl<C: LineControl> AsyncWrite for ExternalSerialStream<C> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_write_ready(cx))?;
+            match guard.try_io(|inner| write_fd(inner.as_raw_fd(), buf)) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<C: LineControl> Read for ExternalSerialStream<C> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        read_fd(self.as_raw_fd(), buf)
+    }
+}
+
+impl<C: LineControl> Write for ExternalSerialStream<C> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        write_fd(self.as_raw_fd(), buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+impl<C: LineControl> SerialPort for ExternalSerialStream<C> {
+    fn name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    fn baud_rate(&self) -> crate::Result<u32> {
+        Ok(self.baud_rate)
+    }
+
+    fn data_bits(&self) -> crate::Result<DataBits> {
+        Ok(self.data_bits)
+    }
+
+    fn flow_control(&self) -> crate::Result<FlowControl> {
+        Ok(self.flow_control)
+    }
+
+    fn parity(&self) -> crate::Result<Parity> {
+        Ok(self.parity)
+    }
+
+    fn stop_bits(&self) -> crate::Result<StopBits> {
+        Ok(self.stop_bits)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
+        self.set_parameters(baud_rate, self.data_bits, self.parity, self.stop_bits)
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> crate::Result<()> {
+        self.set_parameters(self.baud_rate, data_bits, self.parity, self.stop_bits)
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> crate::Result<()> {
+        lock(&self.control).set_flow_control(flow_control)?;
+        self.flow_control = flow_control;
+        Ok(())
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> crate::Result<()> {
+        self.set_parameters(self.baud_rate, self.data_bits, parity, self.stop_bits)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> crate::Result<()> {
+        self.set_parameters(self.baud_rate, self.data_bits, self.parity, stop_bits)
+    }
+
+    fn set_timeout(&mut self, _: Duration) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
+        lock(&self.control).write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
+        lock(&self.control).write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+        lock(&self.control).read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+        lock(&self.control).read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+        lock(&self.control).read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+        lock(&self.control).read_carrier_detect()
+    }
+
+    /// Bytes waiting in the descriptor, not counting those still buffered by the adapter
+    fn bytes_to_read(&self) -> crate::Result<u32> {
+        queued(self.as_raw_fd(), libc::FIONREAD as libc::c_ulong)
+    }
+
+    /// Bytes waiting in the descriptor, not counting those still buffered by the adapter
+    fn bytes_to_write(&self) -> crate::Result<u32> {
+        queued(self.as_raw_fd(), libc::TIOCOUTQ as libc::c_ulong)
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> crate::Result<()> {
+        lock(&self.control).clear(buffer_to_clear)
+    }
+
+    /// Cloning ExternalSerialStream is not supported.
+    ///
+    /// # Errors
+    /// Always returns `ErrorKind::Other` with a message.
+    fn try_clone(&self) -> crate::Result<Box<dyn SerialPort>> {
+        Err(crate::Error::new(
+            crate::ErrorKind::Io(io::ErrorKind::Other),
+            "Cannot clone Tokio handles",
+        ))
+    }
+
+    fn set_break(&self) -> crate::Result<()> {
+        lock(&self.control).set_break(true)
+    }
+
+    fn clear_break(&self) -> crate::Result<()> {
+        lock(&self.control).set_break(false)
+    }
+}
+
+impl<C> AsRawFd for ExternalSerialStream<C> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl<C> fmt::Debug for ExternalSerialStream<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalSerialStream")
+            .field("fd", &self.inner.as_raw_fd())
+            .field("name", &self.name)
+            .field("baud_rate", &self.baud_rate)
+            .finish_non_exhaustive()
+    }
+}
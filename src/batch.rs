@@ -0,0 +1,146 @@
+//! Opening many ports at once
+use crate::settings::Settings;
+use crate::{AsyncSerialPortBuilder, SerialStream};
+
+use futures::channel::oneshot;
+use futures::future::join_all;
+
+use std::fmt;
+
+/// The outcome of opening one port with [`open_all`]
+#[derive(Debug)]
+pub struct PortOutcome {
+    /// Path of the port
+    pub path: String,
+    /// The port, or why it couldn't be opened
+    pub result: crate::Result<SerialStream>,
+}
+
+/// Which ports [`open_all`] opened and which it failed to
+///
+/// The ports that did open are kept, to be used or dropped.
+#[derive(Debug)]
+pub struct OpenReport {
+    /// One outcome per configuration, in their order
+    pub outcomes: Vec<PortOutcome>,
+}
+
+impl OpenReport {
+    /// Returns the paths and errors of the ports that failed to open
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &crate::Error)> + '_ {
+        self.outcomes
+            .iter()
+            .filter_map(|outcome| match &outcome.result {
+                Ok(_) => None,
+                Err(e) => Some((outcome.path.as_str(), e)),
+            })
+    }
+
+    /// Returns the ports that did open, in the order of their configurations
+    pub fn into_opened(self) -> Vec<SerialStream> {
+        self.outcomes
+            .into_iter()
+            .filter_map(|outcome| outcome.result.ok())
+            .collect()
+    }
+}
+
+impl fmt::Display for OpenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} ports failed to open",
+            self.failures().count(),
+            self.outcomes.len()
+        )?;
+        for (i, (path, e)) in self.failures().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{}: {}", separator, path, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OpenReport {}
+
+/// Open the ports of `configs` concurrently
+///
+/// Each port is opened on a thread of its own, so a driver slow to open one doesn't hold
+/// up the others, and registered with the reactor of the current runtime once open.
+/// Returns the ports in the order of `configs`, or the report of every outcome when any
+/// failed.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), tokio_serial::OpenReport> {
+/// let ports = tokio_serial::open_all(vec![
+///     tokio_serial::new("/dev/ttyUSB0", 9600),
+///     tokio_serial::new("/dev/ttyUSB1", 115200),
+/// ])
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn open_all<I>(configs: I) -> Result<Vec<SerialStream>, OpenReport>
+where
+    I: IntoIterator,
+    I::Item: Into<AsyncSerialPortBuilder>,
+{
+    let pending = configs.into_iter().map(|config| {
+        let config = config.into();
+        let path = Settings::from_builder(config.builder()).path;
+        let (tx, rx) = oneshot::channel();
+        let detached = config.clone();
+        let spawned = std::thread::Builder::new()
+            .name("tokio-serial-open".into())
+            .spawn(move || {
+                let _ = tx.send(open_detached(detached));
+            });
+        async move {
+            let result = match spawned {
+                Ok(_) => match rx.await {
+                    Ok(opened) => opened.and_then(|port| attach(port, &config)),
+                    Err(_) => Err(crate::Error::new(
+                        crate::ErrorKind::Unknown,
+                        "opening thread panicked",
+                    )),
+                },
+                Err(e) => Err(e.into()),
+            };
+            PortOutcome { path, result }
+        }
+    });
+    let outcomes = join_all(pending).await;
+
+    if outcomes.iter().any(|outcome| outcome.result.is_err()) {
+        return Err(OpenReport { outcomes });
+    }
+    Ok(outcomes
+        .into_iter()
+        .filter_map(|outcome| outcome.result.ok())
+        .collect())
+}
+
+#[cfg(unix)]
+fn open_detached(config: AsyncSerialPortBuilder) -> crate::Result<crate::Detached> {
+    SerialStream::open_detached(config.builder(), config.shared(), config.restore_settings())
+}
+
+#[cfg(unix)]
+fn attach(port: crate::Detached, config: &AsyncSerialPortBuilder) -> crate::Result<SerialStream> {
+    let mut port = port.attach()?;
+    config.finish(&mut port)?;
+    Ok(port)
+}
+
+// Windows ports don't depend on the reactor, they are opened completely on the thread
+#[cfg(windows)]
+fn open_detached(config: AsyncSerialPortBuilder) -> crate::Result<SerialStream> {
+    use crate::SerialPortBuilderExt;
+
+    config.open_native_async()
+}
+
+#[cfg(windows)]
+fn attach(port: SerialStream, _config: &AsyncSerialPortBuilder) -> crate::Result<SerialStream> {
+    Ok(port)
+}
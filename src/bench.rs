@@ -0,0 +1,281 @@
+//! Latency and throughput measurements for serial ports
+//!
+//! The functions in this module drive a transmitting and a receiving port (the two ends of
+//! `SerialStream::pair`, two ports linked by a null-modem cable, ...) and report how long
+//! data took to cross, how fast it flowed and how often the receiver's `poll_read` had to be
+//! woken up to get it.  They are meant for catching performance regressions in the poll
+//! paths, either from the criterion benchmarks under `benches/` or from an application
+//! checking a particular setup.
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! #[tokio::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let (mut tx, mut rx) = tokio_serial::SerialStream::pair()?;
+//!     let report = tokio_serial::bench::run(&mut tx, &mut rx, &Default::default()).await?;
+//!     println!("{:#?}", report);
+//!     Ok(())
+//! }
+//! # #[cfg(windows)]
+//! # fn main() {}
+//! ```
+use futures::future::try_join;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Parameters of a [`run`]
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Number of single byte round trips timed by the latency measurement
+    pub latency_samples: usize,
+    /// Number of bytes transferred by the throughput measurement
+    pub throughput_bytes: usize,
+    /// Size of the writes issued by the throughput measurement
+    pub chunk_size: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            latency_samples: 100,
+            throughput_bytes: 64 * 1024,
+            chunk_size: 1024,
+        }
+    }
+}
+
+/// Results of a [`run`]
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Per-byte latency
+    pub latency: Latency,
+    /// Sustained throughput
+    pub throughput: Throughput,
+}
+
+/// Time between a single byte being written and it being read back
+#[derive(Debug, Clone)]
+pub struct Latency {
+    /// Number of samples taken
+    pub samples: usize,
+    /// Fastest sample
+    pub min: Duration,
+    /// Median sample
+    pub median: Duration,
+    /// Mean of all samples
+    pub mean: Duration,
+    /// Slowest sample
+    pub max: Duration,
+}
+
+/// Bulk transfer rate
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    /// Number of bytes transferred
+    pub bytes: usize,
+    /// Time taken to transfer them
+    pub elapsed: Duration,
+    /// Counters of the receiving port
+    pub reads: Counts,
+}
+
+impl Throughput {
+    /// Returns the transfer rate in bytes per second
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Poll counters collected by [`Counted`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// Number of calls to `poll_read`/`poll_write`
+    pub polls: u64,
+    /// Number of calls that returned `Poll::Pending`, i.e. waited for a wakeup
+    pub pending: u64,
+    /// Number of calls that returned data
+    pub ready: u64,
+}
+
+/// Wrapper counting the `poll_read` and `poll_write` calls made on an I/O object
+#[derive(Debug)]
+pub struct Counted<T> {
+    inner: T,
+    reads: Counts,
+    writes: Counts,
+}
+
+impl<T> Counted<T> {
+    /// Wrap `inner`
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            reads: Counts::default(),
+            writes: Counts::default(),
+        }
+    }
+
+    /// Returns the read counters
+    pub fn reads(&self) -> Counts {
+        self.reads
+    }
+
+    /// Returns the write counters
+    pub fn writes(&self) -> Counts {
+        self.writes
+    }
+
+    /// Consumes the wrapper, returning the wrapped object
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl Counts {
+    fn record<R>(&mut self, poll: &Poll<R>) {
+        self.polls += 1;
+        match poll {
+            Poll::Pending => self.pending += 1,
+            Poll::Ready(_) => self.ready += 1,
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Counted<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.reads.record(&poll);
+        poll
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Counted<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.writes.record(&poll);
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Measure latency and throughput from `tx` to `rx`
+pub async fn run<W, R>(tx: &mut W, rx: &mut R, options: &Options) -> io::Result<Report>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    let latency = latency(tx, rx, options.latency_samples).await?;
+    let throughput = throughput(tx, rx, options.throughput_bytes, options.chunk_size).await?;
+    Ok(Report {
+        latency,
+        throughput,
+    })
+}
+
+/// Time `samples` single bytes written to `tx` until they are read from `rx`
+pub async fn latency<W, R>(tx: &mut W, rx: &mut R, samples: usize) -> io::Result<Latency>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    if samples == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "at least one sample is required",
+        ));
+    }
+
+    let mut times = Vec::with_capacity(samples);
+    let mut byte = [0u8; 1];
+    for n in 0..samples {
+        let start = Instant::now();
+        tx.write_all(&[n as u8]).await?;
+        tx.flush().await?;
+        rx.read_exact(&mut byte).await?;
+        times.push(start.elapsed());
+        if byte[0] != n as u8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "received byte does not match the one sent",
+            ));
+        }
+    }
+
+    times.sort();
+    let total: Duration = times.iter().sum();
+    Ok(Latency {
+        samples,
+        min: times[0],
+        median: times[samples / 2],
+        mean: total / samples as u32,
+        max: times[samples - 1],
+    })
+}
+
+/// Time the transfer of `bytes` bytes from `tx` to `rx`, written in `chunk_size` chunks
+///
+/// Writing and reading run concurrently so the transfer is not limited by buffer sizes.
+pub async fn throughput<W, R>(
+    tx: &mut W,
+    rx: &mut R,
+    bytes: usize,
+    chunk_size: usize,
+) -> io::Result<Throughput>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+{
+    let chunk: Vec<u8> = (0..chunk_size.max(1)).map(|n| n as u8).collect();
+    let mut rx = Counted::new(rx);
+
+    let start = Instant::now();
+    let write = async {
+        let mut remaining = bytes;
+        while remaining > 0 {
+            let n = remaining.min(chunk.len());
+            tx.write_all(&chunk[..n]).await?;
+            remaining -= n;
+        }
+        tx.flush().await
+    };
+    let read = async {
+        let mut buf = vec![0u8; chunk.len().max(4096)];
+        let mut remaining = bytes;
+        while remaining > 0 {
+            let n = remaining.min(buf.len());
+            let n = rx.read(&mut buf[..n]).await?;
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+            remaining -= n;
+        }
+        Ok(())
+    };
+    try_join(write, read).await?;
+    let elapsed = start.elapsed();
+
+    Ok(Throughput {
+        bytes,
+        elapsed,
+        reads: rx.reads(),
+    })
+}
@@ -0,0 +1,257 @@
+//! Using async ports from synchronous code
+//!
+//! Vendor SDKs and older protocol crates often want an `std::io::Read + Write` port, and
+//! call it from a callback that can't await.  The port is still best opened and configured
+//! with the rest of the application, so this module lends it out for a while:
+//!
+//! * [`BlockingPort`], from [`SerialStream::into_blocking`] (unix only), is the port as a
+//!   plain blocking `serialport::TTYPort`, with its read timeout.  It doesn't need a
+//!   runtime at all and turns back into a `SerialStream` with
+//!   [`into_async`](BlockingPort::into_async).
+//! * [`BlockingBridge`] wraps the `SerialStream` itself and blocks the calling thread on its
+//!   async reads and writes, which the reactor of the runtime keeps driving.
+//!
+//! Both block: move them into `tokio::task::spawn_blocking` or a thread of their own,
+//! never use them on a thread running async tasks.
+//!
+//! ```no_run
+//! use std::io::{Read, Write};
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> tokio_serial::Result<()> {
+//! let mut port = port.into_blocking()?;
+//! let port = tokio::task::spawn_blocking(move || {
+//!     // Whatever the synchronous library does with the port
+//!     port.write_all(b"ATI\r")?;
+//!     let mut reply = [0; 64];
+//!     let n = port.read(&mut reply)?;
+//!     println!("{:?}", &reply[..n]);
+//!     Ok::<_, std::io::Error>(port)
+//! })
+//! .await
+//! .expect("blocking task panicked")?;
+//! let port = port.into_async()?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::SerialStream;
+
+use futures::executor::block_on;
+use futures::future::poll_fn;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+
+#[cfg(unix)]
+use crate::termios;
+#[cfg(unix)]
+use std::convert::TryFrom;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+
+/// A port read and written with blocking calls, see [`SerialStream::into_blocking`]
+///
+/// Reads wait for at most the [timeout](crate::SerialPort::set_timeout) of the port, 100ms unless
+/// changed, and fail with `TimedOut` after it.  Bytes the async port had buffered are read
+/// first.  If the port was set to [restore its
+/// settings](SerialStream::set_restore_settings_on_close), they are restored when it is
+/// dropped.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct BlockingPort {
+    port: serialport::TTYPort,
+    buffered: Vec<u8>,
+    original: Option<termios::Original>,
+    claim: Option<crate::claim::Claim>,
+}
+
+#[cfg(unix)]
+impl BlockingPort {
+    pub(crate) fn new(
+        fd: OwnedFd,
+        buffered: Vec<u8>,
+        original: Option<termios::Original>,
+        exclusive: bool,
+        claim: Option<crate::claim::Claim>,
+    ) -> crate::Result<Self> {
+        // SAFETY: the descriptor is owned and handed over to the port
+        let mut port = unsafe { serialport::TTYPort::from_raw_fd(fd.into_raw_fd()) };
+        // Writes would fail with `WouldBlock` once the driver's queue is full
+        clear_nonblocking(port.as_raw_fd())?;
+        // Taking the descriptor over makes the port exclusive
+        if !exclusive {
+            port.set_exclusive(false)?;
+        }
+        Ok(Self {
+            port,
+            buffered,
+            original,
+            claim,
+        })
+    }
+
+    /// Returns a reference to the blocking port
+    pub fn get_ref(&self) -> &serialport::TTYPort {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the blocking port, to change its timeout or settings
+    ///
+    /// Reading it directly skips the bytes buffered by the async port.
+    pub fn get_mut(&mut self) -> &mut serialport::TTYPort {
+        &mut self.port
+    }
+
+    /// Turn the port back into a `SerialStream` registered with the current runtime
+    ///
+    /// Bytes still buffered are read first again.
+    ///
+    /// ## Panics
+    ///
+    /// This function panics if it is not called from within a runtime with IO enabled.
+    pub fn into_async(mut self) -> crate::Result<SerialStream> {
+        let original = self.original.take();
+        let buffered = std::mem::take(&mut self.buffered);
+        let claim = self.claim.take();
+        // SAFETY: the descriptor is duplicated from the port, which is dropped right after
+        let fd = unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.port.as_raw_fd()) }
+            .try_clone_to_owned()?;
+        let exclusive = self.port.exclusive();
+        drop(self);
+
+        // SAFETY: the descriptor is owned and handed over to the port
+        let mut port = unsafe { serialport::TTYPort::from_raw_fd(fd.into_raw_fd()) };
+        if !exclusive {
+            port.set_exclusive(false)?;
+        }
+        let mut stream = SerialStream::try_from(port)?;
+        stream.read_buf.unread(&buffered);
+        stream.claim = claim;
+        if original.is_some() {
+            stream.original_settings = original;
+            stream.restore_settings = true;
+        }
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for BlockingPort {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take() {
+            if let Err(e) = original.restore(self.port.as_raw_fd()) {
+                log::debug!("failed to restore settings on drop: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Read for BlockingPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.buffered.is_empty() {
+            return self.port.read(buf);
+        }
+        let n = self.buffered.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.buffered[..n]);
+        self.buffered.drain(..n);
+        Ok(n)
+    }
+}
+
+#[cfg(unix)]
+impl Write for BlockingPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+#[cfg(unix)]
+fn clear_nonblocking(fd: std::os::unix::io::RawFd) -> io::Result<()> {
+    // SAFETY: fcntl only reads and sets the flags of the descriptor
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A `SerialStream` read and written with blocking calls
+///
+/// Each call blocks the thread until the async operation completes, driven by the reactor
+/// of the runtime the port was opened in, which must keep running meanwhile.  Reads wait
+/// without a timeout.  Unlike [`BlockingPort`] the port stays registered with the runtime and
+/// is available on every platform.
+///
+/// ```no_run
+/// use std::io::Write;
+/// use tokio_serial::blocking::BlockingBridge;
+///
+/// # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+/// let mut bridge = BlockingBridge::new(port);
+/// let port = tokio::task::spawn_blocking(move || {
+///     bridge.write_all(b"ATZ\r")?;
+///     Ok::<_, std::io::Error>(bridge.into_inner())
+/// })
+/// .await
+/// .expect("blocking task panicked")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BlockingBridge {
+    port: SerialStream,
+}
+
+impl BlockingBridge {
+    /// Wrap `port`
+    pub fn new(port: SerialStream) -> Self {
+        Self { port }
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Returns the port, to be used asynchronously again
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+}
+
+impl Read for BlockingBridge {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let port = &mut self.port;
+        block_on(poll_fn(|cx| {
+            let mut buf = ReadBuf::new(buf);
+            Pin::new(&mut *port)
+                .poll_read(cx, &mut buf)
+                .map_ok(|()| buf.filled().len())
+        }))
+    }
+}
+
+impl Write for BlockingBridge {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let port = &mut self.port;
+        block_on(poll_fn(|cx| Pin::new(&mut *port).poll_write(cx, buf)))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let port = &mut self.port;
+        block_on(poll_fn(|cx| Pin::new(&mut *port).poll_flush(cx)))
+    }
+}
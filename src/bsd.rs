@@ -0,0 +1,45 @@
+//! Device naming on the BSDs
+//!
+//! The BSDs create two nodes per serial line: a call-in device (`/dev/ttyu0`, `/dev/tty00`),
+//! whose `open` waits for carrier detect and which hangs up when carrier drops, and a
+//! call-out device (`/dev/cuau0`, `/dev/cua00`) that ignores the modem lines.  Data loggers
+//! and other programs talking to a device rather than a modem want the call-out node.
+use std::path::{Path, PathBuf};
+
+/// Prefix of the call-out devices
+#[cfg(not(target_os = "netbsd"))]
+const CALLOUT_PREFIX: &str = "cua";
+#[cfg(target_os = "netbsd")]
+const CALLOUT_PREFIX: &str = "dty";
+
+/// Returns the call-out device of the call-in device `path`
+///
+/// `/dev/ttyu0` gives `/dev/cuau0` on FreeBSD and DragonFly, `/dev/tty00` gives `/dev/cua00`
+/// on OpenBSD and `/dev/dty00` on NetBSD.  Returns `None` if `path` isn't a call-in device or
+/// its call-out device doesn't exist.
+///
+/// ```no_run
+/// use tokio_serial::SerialPortBuilderExt;
+///
+/// # fn main() -> tokio_serial::Result<()> {
+/// let path = tokio_serial::callout_path("/dev/ttyU0").unwrap_or_else(|| "/dev/ttyU0".into());
+/// let port = tokio_serial::new(path.to_string_lossy(), 9600).open_native_async()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn callout_path(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let path = path.as_ref();
+    let name = path.file_name()?.to_str()?;
+    let line = name.strip_prefix("tty")?;
+    // `/dev/tty` itself is the controlling terminal, the `.init` and `.lock` nodes hold the
+    // initial and locked settings of the line
+    if line.is_empty() || line.contains('.') {
+        return None;
+    }
+    let callout = path.with_file_name(format!("{}{}", CALLOUT_PREFIX, line));
+    if callout.exists() {
+        Some(callout)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,305 @@
+//! Options applied when opening a `SerialStream` beyond those of `SerialPortBuilder`
+use crate::{
+    AsyncSerialPort, ClearBuffer, PendingOutput, SerialPort, SerialPortBuilder,
+    SerialPortBuilderExt, SerialStream,
+};
+#[cfg(windows)]
+use crate::{CommTimeouts, DtrControl, RtsControl};
+
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(windows)]
+use windows_sys::Win32::Devices::Communication::DCB;
+
+/// A `SerialPortBuilder` with the options only `tokio-serial` knows about
+///
+/// Returned by the option methods of [`SerialPortBuilderExt`], which it implements as well so
+/// the options can be chained:
+///
+/// ```no_run
+/// # #[cfg(windows)]
+/// # fn main() -> tokio_serial::Result<()> {
+/// use tokio_serial::{DtrControl, RtsControl, SerialPortBuilderExt};
+///
+/// let port = tokio_serial::new("COM7", 115200)
+///     .rts_control(RtsControl::Toggle)
+///     .dtr_control(DtrControl::Disable)
+///     .open_native_async()?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(windows))]
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncSerialPortBuilder {
+    builder: SerialPortBuilder,
+    #[cfg(windows)]
+    rts_control: Option<RtsControl>,
+    #[cfg(windows)]
+    dtr_control: Option<DtrControl>,
+    #[cfg(windows)]
+    exclusive: bool,
+    platform: PlatformOptions,
+    clear_on_open: Option<ClearBuffer>,
+    shared: bool,
+    drop_output: PendingOutput,
+    restore_settings: bool,
+}
+
+impl From<SerialPortBuilder> for AsyncSerialPortBuilder {
+    fn from(builder: SerialPortBuilder) -> Self {
+        Self {
+            builder,
+            #[cfg(windows)]
+            rts_control: None,
+            #[cfg(windows)]
+            dtr_control: None,
+            #[cfg(windows)]
+            exclusive: true,
+            platform: PlatformOptions::default(),
+            clear_on_open: None,
+            shared: false,
+            drop_output: PendingOutput::Keep,
+            restore_settings: false,
+        }
+    }
+}
+
+impl AsyncSerialPortBuilder {
+    /// Returns the wrapped `SerialPortBuilder`
+    pub fn builder(&self) -> &SerialPortBuilder {
+        &self.builder
+    }
+
+    /// Returns the platform specific options
+    pub fn platform_options(&self) -> &PlatformOptions {
+        &self.platform
+    }
+
+    /// Returns `true` if the port is opened without claiming its device within the process
+    #[cfg(unix)]
+    pub(crate) fn shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Returns `true` if the settings the device had are restored when the port is closed
+    #[cfg(unix)]
+    pub(crate) fn restore_settings(&self) -> bool {
+        self.restore_settings
+    }
+
+    /// Apply the options left once the port is open
+    pub(crate) fn finish(&self, port: &mut SerialStream) -> crate::Result<()> {
+        self.platform.apply(port)?;
+        if let Some(buffer) = self.clear_on_open {
+            port.clear(buffer)?;
+        }
+        port.set_drop_output(self.drop_output);
+        Ok(())
+    }
+}
+
+impl SerialPortBuilderExt for AsyncSerialPortBuilder {
+    fn open_native_async(self) -> crate::Result<SerialStream> {
+        #[cfg(unix)]
+        let mut port =
+            SerialStream::open_detached(&self.builder, self.shared, self.restore_settings)?
+                .attach()?;
+        #[cfg(windows)]
+        let mut port = {
+            let path = crate::settings::Settings::from_builder(&self.builder).path;
+            let mut port = SerialStream::open_com(
+                &self.builder,
+                &path,
+                self.exclusive,
+                self.shared,
+                self.restore_settings,
+            )?;
+            if let Some(control) = self.rts_control {
+                port.set_rts_control(control)?;
+            }
+            if let Some(control) = self.dtr_control {
+                port.set_dtr_control(control)?;
+            }
+            port
+        };
+        self.finish(&mut port)?;
+        Ok(port)
+    }
+
+    fn open_async(self) -> crate::Result<Box<dyn AsyncSerialPort>> {
+        Ok(Box::new(self.open_native_async()?))
+    }
+
+    #[cfg(windows)]
+    fn rts_control(mut self, control: RtsControl) -> AsyncSerialPortBuilder {
+        self.rts_control = Some(control);
+        self
+    }
+
+    #[cfg(windows)]
+    fn dtr_control(mut self, control: DtrControl) -> AsyncSerialPortBuilder {
+        self.dtr_control = Some(control);
+        self
+    }
+
+    #[cfg(windows)]
+    fn exclusive(mut self, exclusive: bool) -> AsyncSerialPortBuilder {
+        self.exclusive = exclusive;
+        self
+    }
+
+    fn with_platform_options(
+        mut self,
+        f: impl FnOnce(&mut PlatformOptions),
+    ) -> AsyncSerialPortBuilder {
+        f(&mut self.platform);
+        self
+    }
+
+    fn clear_buffers_on_open(mut self, buffer: ClearBuffer) -> AsyncSerialPortBuilder {
+        self.clear_on_open = Some(buffer);
+        self
+    }
+
+    fn allow_shared(mut self) -> AsyncSerialPortBuilder {
+        self.shared = true;
+        self
+    }
+
+    fn drop_output(mut self, output: PendingOutput) -> AsyncSerialPortBuilder {
+        self.drop_output = output;
+        self
+    }
+
+    fn restore_settings_on_close(mut self, restore: bool) -> AsyncSerialPortBuilder {
+        self.restore_settings = restore;
+        self
+    }
+}
+
+#[cfg(unix)]
+type TermiosFn = Arc<dyn Fn(&mut libc::termios) + Send + Sync>;
+#[cfg(windows)]
+type DcbFn = Arc<dyn Fn(&mut DCB) + Send + Sync>;
+
+/// Platform specific settings applied when a port is opened, see
+/// [`SerialPortBuilderExt::with_platform_options`]
+///
+/// The settings are applied before the port is handed out, so no read or write sees the
+/// port without them.  On unix the terminal settings are changed with a single `tcsetattr`,
+/// after those of the builder.  Ports are always opened with `O_NOCTTY`.
+#[derive(Clone, Default)]
+pub struct PlatformOptions {
+    #[cfg(unix)]
+    termios: Vec<TermiosFn>,
+    #[cfg(unix)]
+    vmin: Option<u8>,
+    #[cfg(unix)]
+    vtime: Option<u8>,
+    #[cfg(windows)]
+    dcb: Vec<DcbFn>,
+    #[cfg(windows)]
+    timeouts: Option<CommTimeouts>,
+}
+
+impl PlatformOptions {
+    /// Change the terminal settings with `f`, after the settings of the builder
+    #[cfg(unix)]
+    pub fn modify_termios<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&mut libc::termios) + Send + Sync + 'static,
+    {
+        self.termios.push(Arc::new(f));
+        self
+    }
+
+    /// Set `VMIN`, the bytes a read waits for, see
+    /// [`set_read_low_watermark`](SerialStream::set_read_low_watermark)
+    #[cfg(unix)]
+    pub fn vmin(&mut self, vmin: u8) -> &mut Self {
+        self.vmin = Some(vmin);
+        self
+    }
+
+    /// Set `VTIME`, in tenths of a second, the silence ending a read waiting for `VMIN`
+    /// bytes
+    #[cfg(unix)]
+    pub fn vtime(&mut self, vtime: u8) -> &mut Self {
+        self.vtime = Some(vtime);
+        self
+    }
+
+    /// Change the device control block with `f`, for the fields the builder doesn't set
+    #[cfg(windows)]
+    pub fn modify_dcb<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(&mut DCB) + Send + Sync + 'static,
+    {
+        self.dcb.push(Arc::new(f));
+        self
+    }
+
+    /// Open the port with `timeouts` instead of [`CommTimeouts::default`], see
+    /// [`set_comm_timeouts`](SerialStream::set_comm_timeouts)
+    #[cfg(windows)]
+    pub fn comm_timeouts(&mut self, timeouts: CommTimeouts) -> &mut Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    /// Apply the settings to a port just opened
+    pub(crate) fn apply(&self, port: &mut SerialStream) -> crate::Result<()> {
+        #[cfg(unix)]
+        {
+            if self.termios.is_empty() && self.vmin.is_none() && self.vtime.is_none() {
+                return Ok(());
+            }
+            port.with_raw_fd(|fd| {
+                crate::config::modify_termios(&fd, |termios| {
+                    for f in &self.termios {
+                        f(termios);
+                    }
+                    if let Some(vmin) = self.vmin {
+                        termios.c_cc[libc::VMIN] = vmin;
+                    }
+                    if let Some(vtime) = self.vtime {
+                        termios.c_cc[libc::VTIME] = vtime;
+                    }
+                })
+            })?;
+        }
+
+        #[cfg(windows)]
+        {
+            if !self.dcb.is_empty() {
+                port.with_raw_handle(|handle| {
+                    crate::config::modify_dcb(&handle, |dcb| {
+                        for f in &self.dcb {
+                            f(dcb);
+                        }
+                    })
+                })?;
+            }
+            if let Some(timeouts) = self.timeouts {
+                port.set_comm_timeouts(timeouts)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PlatformOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("PlatformOptions");
+        #[cfg(unix)]
+        f.field("termios", &self.termios.len())
+            .field("vmin", &self.vmin)
+            .field("vtime", &self.vtime);
+        #[cfg(windows)]
+        f.field("dcb", &self.dcb.len())
+            .field("timeouts", &self.timeouts);
+        f.finish()
+    }
+}
@@ -0,0 +1,147 @@
+//! Explaining why a port couldn't be opened because it is in use
+//!
+//! The OS only answers "Device or resource busy" or "Access is denied", which doesn't say
+//! what to close.  On Linux the processes holding the device are looked up in `/proc`, on
+//! Windows the port is opened once more to tell a sharing violation from other failures.
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+
+/// A process holding a device open, see [`port_holders`]
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortHolder {
+    /// Process id
+    pub pid: u32,
+    /// Command name of the process, empty if it exited meanwhile
+    pub command: String,
+}
+
+/// Returns the processes with a descriptor of the device at `path`
+///
+/// Only the processes whose descriptors can be listed are found: the ones of the same user,
+/// or all of them with `CAP_SYS_PTRACE`.  The current process is included.
+///
+/// ## Errors
+///
+/// * `Io` if `path` doesn't exist or `/proc` can't be read.
+#[cfg(target_os = "linux")]
+pub fn port_holders(path: impl AsRef<Path>) -> io::Result<Vec<PortHolder>> {
+    let device = fs::canonicalize(path)?;
+    let mut holders = Vec::new();
+    for entry in fs::read_dir("/proc")?.filter_map(Result::ok) {
+        let pid = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+        // Processes of other users can't be inspected, or exited meanwhile
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let holds = fds
+            .filter_map(Result::ok)
+            .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target == device));
+        if holds {
+            let command = fs::read_to_string(entry.path().join("comm"))
+                .map(|command| command.trim_end().to_owned())
+                .unwrap_or_default();
+            holders.push(PortHolder { pid, command });
+        }
+    }
+    Ok(holders)
+}
+
+/// Add who holds the port at `path` to `err`, if it says the port is busy
+pub(crate) fn explain(path: &str, err: crate::Error) -> crate::Error {
+    if err.kind != crate::ErrorKind::NoDevice {
+        return err;
+    }
+    match in_use_by(path) {
+        Some(detail) => crate::Error::new(
+            err.kind,
+            format!("{}: {} {}", err.description, path, detail),
+        ),
+        None => err,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn in_use_by(path: &str) -> Option<String> {
+    let holders = match port_holders(path) {
+        Ok(holders) => holders,
+        // A path that doesn't exist isn't busy
+        Err(_) => return None,
+    };
+    if holders.is_empty() {
+        return Some("is in use by a process of another user".into());
+    }
+    let own = std::process::id();
+    let holders = holders
+        .iter()
+        .map(|holder| match holder.pid {
+            pid if pid == own => format!("pid {} (this process)", pid),
+            pid => format!("pid {} ({})", pid, holder.command),
+        })
+        .collect::<Vec<_>>();
+    Some(format!("is in use by {}", holders.join(", ")))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn in_use_by(_path: &str) -> Option<String> {
+    Some("is in use, `lsof` lists the processes holding it".into())
+}
+
+#[cfg(windows)]
+fn in_use_by(path: &str) -> Option<String> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, GetLastError, ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION, GENERIC_READ,
+        GENERIC_WRITE, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, FILE_ATTRIBUTE_NORMAL, OPEN_EXISTING,
+    };
+
+    let path = crate::com::device_path(path);
+    let wide: Vec<u16> = OsStr::new(&path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    // SAFETY: the path is NUL terminated, the result is checked
+    let (handle, error) = unsafe {
+        let handle = CreateFileW(
+            wide.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            0,
+            ptr::null(),
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            ptr::null_mut(),
+        );
+        (handle, GetLastError())
+    };
+    if handle != INVALID_HANDLE_VALUE {
+        // SAFETY: the handle was just opened
+        unsafe { CloseHandle(handle) };
+        return None;
+    }
+    match error {
+        // COM ports only ever have a single handle open
+        ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION => Some(
+            "is open in another program (sharing violation), close the terminal, modem \
+             manager or other application using it"
+                .into(),
+        ),
+        _ => None,
+    }
+}
@@ -0,0 +1,216 @@
+//! Decoupling slow consumers from the line rate
+//!
+//! A port must be read as fast as the line delivers or the driver's buffer overruns, yet the
+//! consumer may stall on a database write or a network peer.  A [`ChannelReader`] reads the
+//! port in a task of its own and queues the chunks for a [`ChunkReceiver`], keeping at most
+//! `capacity` of them; what happens beyond that is chosen with an [`OverflowPolicy`].
+//!
+//! ```no_run
+//! use tokio_serial::channel_reader::{ChannelReader, OverflowPolicy};
+//!
+//! # async fn run(port: tokio_serial::SerialStream) {
+//! let (reader, mut chunks) = ChannelReader::new(64, OverflowPolicy::DropOldest);
+//! tokio::spawn(reader.run(port));
+//! while let Some(chunk) = chunks.recv().await {
+//!     // Slow processing only costs the oldest chunks
+//!     println!("{:02x?}", chunk);
+//! }
+//! println!("{} chunks dropped", chunks.dropped());
+//! # }
+//! ```
+use futures::future::poll_fn;
+use futures::task::AtomicWaker;
+use futures::{ready, Stream};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+/// Bytes read from the port at once, by default
+pub const DEFAULT_READ_LEN: usize = 1024;
+
+/// What a [`ChannelReader`] does with a chunk read while the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued chunk to make room, the consumer sees the latest data
+    DropOldest,
+    /// Drop the chunk just read, the consumer sees the data up to the overflow
+    DropNewest,
+    /// Stop reading the port until there is room
+    ///
+    /// The bytes wait in the driver instead, where flow control may hold the sender off, or
+    /// are lost when its buffer overruns.
+    #[default]
+    Backpressure,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    queue: VecDeque<Vec<u8>>,
+    dropped: u64,
+    reader_done: bool,
+    receiver_closed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    // Woken when a chunk is queued or the reader is done
+    receiver: AtomicWaker,
+    // Woken when room is made or the receiver is dropped
+    reader: AtomicWaker,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The queue stays consistent even if the other side panicked
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Reads a port into a bounded queue, see the [module](self) docs
+#[derive(Debug)]
+pub struct ChannelReader {
+    shared: Arc<Shared>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    read_len: usize,
+}
+
+impl ChannelReader {
+    /// Create a reader queueing up to `capacity` chunks and the receiver of the chunks
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> (Self, ChunkReceiver) {
+        let shared = Arc::new(Shared::default());
+        let reader = Self {
+            shared: shared.clone(),
+            capacity: capacity.max(1),
+            policy,
+            read_len: DEFAULT_READ_LEN,
+        };
+        (reader, ChunkReceiver { shared })
+    }
+
+    /// Read up to `len` bytes at once, [`DEFAULT_READ_LEN`] by default
+    pub fn set_read_len(&mut self, len: usize) {
+        self.read_len = len.max(1);
+    }
+
+    /// Read `port` into the queue until it hangs up, fails or the receiver is dropped
+    ///
+    /// The receiver ends once the queued chunks are consumed.  Errors reading the port are
+    /// returned here, not to the receiver.
+    pub async fn run<R: AsyncRead + Unpin>(self, mut port: R) -> io::Result<()> {
+        let mut chunk = vec![0; self.read_len];
+        poll_fn(|cx| loop {
+            self.shared.reader.register(cx.waker());
+            {
+                let state = self.shared.lock();
+                if state.receiver_closed {
+                    return Poll::Ready(Ok(()));
+                }
+                if self.policy == OverflowPolicy::Backpressure && state.queue.len() >= self.capacity
+                {
+                    return Poll::Pending;
+                }
+            }
+
+            let mut buf = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut port).poll_read(cx, &mut buf))?;
+            if buf.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            let bytes = buf.filled().to_vec();
+            self.push(bytes);
+        })
+        .await
+    }
+
+    fn push(&self, bytes: Vec<u8>) {
+        let mut state = self.shared.lock();
+        if state.queue.len() >= self.capacity {
+            state.dropped += 1;
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                }
+                OverflowPolicy::DropNewest => return,
+                OverflowPolicy::Backpressure => unreachable!("reading waits for room"),
+            }
+        }
+        state.queue.push_back(bytes);
+        drop(state);
+        self.shared.receiver.wake();
+    }
+}
+
+impl Drop for ChannelReader {
+    fn drop(&mut self) {
+        self.shared.lock().reader_done = true;
+        self.shared.receiver.wake();
+    }
+}
+
+/// The chunks queued by a [`ChannelReader`]
+///
+/// Ends once the reader is done and every queued chunk was received.
+#[derive(Debug)]
+pub struct ChunkReceiver {
+    shared: Arc<Shared>,
+}
+
+impl ChunkReceiver {
+    /// Wait for the next chunk, `None` once the reader is done
+    pub async fn recv(&mut self) -> Option<Vec<u8>> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Attempts to receive the next chunk
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Vec<u8>>> {
+        self.shared.receiver.register(cx.waker());
+        let mut state = self.shared.lock();
+        if let Some(chunk) = state.queue.pop_front() {
+            drop(state);
+            self.shared.reader.wake();
+            return Poll::Ready(Some(chunk));
+        }
+        if state.reader_done {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+
+    /// Returns the number of chunks dropped by the overflow policy so far
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Returns the number of chunks waiting to be received
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Returns `true` if no chunk is waiting to be received
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Stream for ChunkReceiver {
+    type Item = Vec<u8>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl Drop for ChunkReceiver {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_closed = true;
+        self.shared.reader.wake();
+    }
+}
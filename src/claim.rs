@@ -0,0 +1,92 @@
+//! Keeping two parts of a process from opening the same port
+//!
+//! The OS locks only stop other processes, or not at all for root or shared Windows ports.
+//! Every port opened by path claims its device here until it is closed, so a second open
+//! within the process fails up front with a clear error instead of two tasks interleaving
+//! their frames on one line.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Keys of the devices claimed by open ports, with the paths they were opened by
+static CLAIMS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// The claim of an open port on its device, released when dropped
+#[derive(Debug)]
+pub(crate) struct Claim {
+    key: String,
+}
+
+impl Claim {
+    /// Claim the device at `path`
+    ///
+    /// ## Errors
+    ///
+    /// * `Io(AddrInUse)` if a port of this process already holds the device.
+    pub(crate) fn acquire(path: &str) -> crate::Result<Self> {
+        let key = key(path);
+        let mut claims = claims();
+        if claims.contains_key(&key) {
+            return Err(already_open(path));
+        }
+        claims.insert(key.clone(), path.to_owned());
+        Ok(Self { key })
+    }
+
+    /// Fail like [`acquire`](Self::acquire) if the device at `path` is claimed, before it is
+    /// opened again
+    #[cfg(unix)]
+    pub(crate) fn check(path: &str) -> crate::Result<()> {
+        match claims().contains_key(&key(path)) {
+            true => Err(already_open(path)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The error of opening a device claimed by another port, classified as
+/// [`ErrorClass::AlreadyOpen`](crate::ErrorClass::AlreadyOpen)
+pub(crate) fn already_open(path: &str) -> crate::Error {
+    crate::Error::new(
+        crate::ErrorKind::Io(std::io::ErrorKind::AddrInUse),
+        format!(
+            "{} is already open in this process, open it with `allow_shared` to share it",
+            path
+        ),
+    )
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        claims().remove(&self.key);
+    }
+}
+
+/// Returns `true` if a port of this process has the device at `path` open
+///
+/// Ports are counted from the moment they are opened by path until they are closed, except
+/// those opened with [`allow_shared`](crate::SerialPortBuilderExt::allow_shared).  Every
+/// spelling of the device counts, like a symlink under `/dev/serial/by-id` and its target.
+pub fn is_open_in_process(path: &str) -> bool {
+    claims().contains_key(&key(path))
+}
+
+fn claims() -> std::sync::MutexGuard<'static, BTreeMap<String, String>> {
+    // The set stays consistent even if a holder panicked
+    CLAIMS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns the same key for every path of a device
+#[cfg(unix)]
+fn key(path: &str) -> String {
+    match std::fs::canonicalize(path) {
+        Ok(device) => device.to_string_lossy().into_owned(),
+        Err(_) => path.to_owned(),
+    }
+}
+
+#[cfg(windows)]
+fn key(path: &str) -> String {
+    crate::com::device_path(path).to_uppercase()
+}
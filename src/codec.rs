@@ -0,0 +1,273 @@
+//! Codecs for protocols commonly found on serial ports
+//!
+//! Each codec implements the `tokio_util` [`Decoder`](tokio_util::codec::Decoder) and
+//! [`Encoder`](tokio_util::codec::Encoder) traits and can be used with either
+//! [`SerialFramed`](crate::frame::SerialFramed) or `tokio_util::codec::Framed`.
+//!
+//! The decoders skip line noise and corrupt frames to find the next frame.  What they threw
+//! away is counted in [`ResyncStats`], taken from the codec before handing it to the framed
+//! port, to tell a clean line from a noisy one.
+//!
+//! # Checksum failures
+//!
+//! A frame failing its checksum is reported as an `InvalidData` error by default, which
+//! suits control loops that must know a reply was lost.  A [`ChecksumPolicy`] drops such
+//! frames silently instead, and wrapping a codec in [`Checked`] hands them over as
+//! [`Frame::Corrupt`] with their raw bytes, for loggers keeping everything the line carried.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::nmea::NmeaCodec;
+//! use tokio_serial::codec::{Checked, Frame};
+//! use tokio_util::codec::Decoder;
+//!
+//! let mut codec = Checked::new(NmeaCodec::new());
+//! let mut src = BytesMut::from(&b"$GPGLL,5300.97914,N,00259.98174,E,125926,A*00\r\n"[..]);
+//! assert!(matches!(codec.decode(&mut src), Ok(Some(Frame::Corrupt(_)))));
+//! ```
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod ais;
+#[cfg(feature = "defmt")]
+pub mod defmt;
+pub mod demux;
+pub mod dsmr;
+pub mod escpos;
+pub mod ft12;
+pub mod handshake;
+pub mod log_line;
+pub mod modbus;
+pub mod mstp;
+pub mod nmea;
+pub mod slcan;
+pub mod ubx;
+pub mod xbee;
+
+/// Counters of the bytes a decoder skipped, shared with the decoder
+///
+/// A handle stays live once its codec moved into a framed port, and clones of a codec count
+/// into the same handle.
+#[derive(Debug, Clone, Default)]
+pub struct ResyncStats {
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    skipped_bytes: AtomicU64,
+    resyncs: AtomicU64,
+    corrupt_frames: AtomicU64,
+}
+
+impl ResyncStats {
+    /// Bytes thrown away: noise between frames and corrupt frames
+    pub fn skipped_bytes(&self) -> u64 {
+        self.counters.skipped_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Times the decoder lost track of the frames and skipped bytes to find the next one
+    ///
+    /// A run of skipped bytes counts once, however many reads it spans.
+    pub fn resyncs(&self) -> u64 {
+        self.counters.resyncs.load(Ordering::Relaxed)
+    }
+
+    /// Frames dropped for a bad checksum, an impossible length or a missing end
+    pub fn corrupt_frames(&self) -> u64 {
+        self.counters.corrupt_frames.load(Ordering::Relaxed)
+    }
+
+    /// Set all counters back to zero
+    pub fn reset(&self) {
+        self.counters.skipped_bytes.store(0, Ordering::Relaxed);
+        self.counters.resyncs.store(0, Ordering::Relaxed);
+        self.counters.corrupt_frames.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Decoder side of [`ResyncStats`], telling runs of skipped bytes apart
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SkipTracker {
+    stats: ResyncStats,
+    skipping: bool,
+}
+
+impl SkipTracker {
+    pub(crate) fn stats(&self) -> ResyncStats {
+        self.stats.clone()
+    }
+
+    /// `bytes` were thrown away looking for a frame
+    pub(crate) fn skip(&mut self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        let counters = &self.stats.counters;
+        counters
+            .skipped_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if !self.skipping {
+            counters.resyncs.fetch_add(1, Ordering::Relaxed);
+            self.skipping = true;
+        }
+    }
+
+    /// A corrupt frame of `bytes` was thrown away
+    pub(crate) fn corrupt(&mut self, bytes: usize) {
+        self.stats
+            .counters
+            .corrupt_frames
+            .fetch_add(1, Ordering::Relaxed);
+        self.skip(bytes);
+    }
+
+    /// A frame was decoded, ending any run of skipped bytes
+    pub(crate) fn frame(&mut self) {
+        self.skipping = false;
+    }
+}
+
+/// What a decoder does with a frame failing its checksum, see the [module](self) docs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumPolicy {
+    /// Report it as an `InvalidData` error and go on with the next frame on the next call
+    #[default]
+    Error,
+    /// Drop it silently and go on with the next frame
+    Drop,
+}
+
+/// A frame decoded by [`Checked`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame<T> {
+    /// A frame passing its checksum
+    Valid(T),
+    /// The raw bytes of a frame failing its checksum
+    Corrupt(Bytes),
+}
+
+/// A decoder validating its frames with a checksum
+pub trait Checksummed: Decoder<Error = io::Error> {
+    /// Decode the next frame, handing a corrupt one back whatever the [`ChecksumPolicy`]
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<Self::Item>>>;
+}
+
+/// When the newest bytes handed to a [`TimedDecoder`] arrived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// Time to transmit one character with the current settings of the port
+    pub char_time: Duration,
+    /// Silence before the newest bytes, `None` for the first bytes read from the port
+    ///
+    /// When the decoder is called because the line went idle, the silence since the last
+    /// byte.
+    pub gap: Option<Duration>,
+    /// Bytes at the end of the buffer that arrived after the gap, zero when the line went
+    /// idle
+    pub new_bytes: usize,
+}
+
+/// A decoder delimiting frames by the silences between them, like Modbus RTU
+///
+/// [`TimedFramed`](crate::frame::TimedFramed) calls [`decode_timed`](Self::decode_timed)
+/// instead of `decode`, with the gap before the bytes it just read.  Bytes read in one
+/// chunk arrived too close together to tell their gaps apart.
+pub trait TimedDecoder: Decoder {
+    /// Decode the next frame of `src`, whose last `timing.new_bytes` followed `timing.gap`
+    fn decode_timed(
+        &mut self,
+        src: &mut BytesMut,
+        timing: &Timing,
+    ) -> Result<Option<Self::Item>, Self::Error>;
+
+    /// Silence after the last byte after which to be called again without new bytes
+    ///
+    /// Lets a frame end at its trailing silence rather than when the next frame starts.
+    /// `None`, the default, waits for more bytes.
+    fn idle_timeout(&self, _char_time: Duration) -> Option<Duration> {
+        None
+    }
+}
+
+/// Decoder emitting corrupt frames as [`Frame::Corrupt`] instead of errors
+#[derive(Debug, Clone, Default)]
+pub struct Checked<C> {
+    codec: C,
+}
+
+impl<C> Checked<C> {
+    /// Wrap `codec`
+    pub fn new(codec: C) -> Self {
+        Self { codec }
+    }
+
+    /// Returns a reference to the wrapped codec
+    pub fn get_ref(&self) -> &C {
+        &self.codec
+    }
+
+    /// Returns a mutable reference to the wrapped codec
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Consumes the wrapper, returning the codec
+    pub fn into_inner(self) -> C {
+        self.codec
+    }
+}
+
+impl<C: Checksummed> Decoder for Checked<C> {
+    type Item = Frame<C::Item>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.codec.decode_frame(src)
+    }
+}
+
+impl<I, C: Encoder<I>> Encoder<I> for Checked<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.codec.encode(item, dst)
+    }
+}
+
+/// A frame failing validation, with the reason to report under [`ChecksumPolicy::Error`]
+pub(crate) struct Corrupt {
+    pub(crate) bytes: Bytes,
+    pub(crate) error: io::Error,
+}
+
+impl<T> From<Result<T, Corrupt>> for Frame<T> {
+    fn from(frame: Result<T, Corrupt>) -> Self {
+        match frame {
+            Ok(frame) => Frame::Valid(frame),
+            Err(corrupt) => Frame::Corrupt(corrupt.bytes),
+        }
+    }
+}
+
+/// Decode with `next` until a frame passes `policy`
+pub(crate) fn decode_with_policy<T>(
+    policy: ChecksumPolicy,
+    mut next: impl FnMut() -> Option<Result<T, Corrupt>>,
+) -> io::Result<Option<T>> {
+    loop {
+        return match next() {
+            Some(Ok(frame)) => Ok(Some(frame)),
+            Some(Err(corrupt)) => match policy {
+                ChecksumPolicy::Error => Err(corrupt.error),
+                ChecksumPolicy::Drop => continue,
+            },
+            None => Ok(None),
+        };
+    }
+}
@@ -0,0 +1,427 @@
+//! AIS payloads of AIVDM and AIVDO sentences
+//!
+//! AIS receivers and transponders relay the messages of ships around as `!AIVDM` sentences,
+//! and those of their own ship as `!AIVDO`.  The binary message is armored into six bit
+//! characters, and split over up to nine fragment sentences when too long for one:
+//!
+//! ```text
+//! !AIVDM,2,1,3,B,55P5TL01VIaAL@7WKO@mBplU@<PDhh000000001S;AJ::4A80?4i@E53,0*3E
+//! !AIVDM,2,2,3,B,1@0000000000000,2*55
+//! ```
+//!
+//! [`AisAssembler`] collects the fragments of each message from [`Sentence`]s and
+//! de-armors them into an [`AisPayload`], a bit buffer with accessors for the fields of AIS
+//! messages.  [`AisCodec`] does both on the raw bytes of a port.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::ais::AisCodec;
+//! use tokio_util::codec::Decoder;
+//!
+//! let mut codec = AisCodec::new();
+//! let mut src = BytesMut::from(&b"!AIVDM,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5C\r\n"[..]);
+//! let payload = codec.decode(&mut src).unwrap().unwrap();
+//! assert_eq!(payload.message_type(), 1);
+//! assert_eq!(payload.mmsi(), 477553000);
+//! ```
+use super::nmea::{NmeaCodec, Sentence};
+use super::{ChecksumPolicy, ResyncStats};
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+
+/// Most armored characters put in one sentence by [`AisCodec`]'s encoder
+pub const MAX_FRAGMENT_CHARS: usize = 60;
+
+/// The bits of an AIS message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AisPayload {
+    // Packed most significant bit first
+    bits: Vec<u8>,
+    len: usize,
+    channel: Option<char>,
+    own_vessel: bool,
+}
+
+impl AisPayload {
+    /// Create a payload from the first `len` bits of `bits`, packed most significant bit
+    /// first
+    ///
+    /// ## Panics
+    ///
+    /// This function panics if `bits` holds fewer than `len` bits.
+    // `usize::div_ceil` needs a newer compiler than the MSRV
+    #[allow(clippy::manual_div_ceil)]
+    pub fn from_bits(bits: &[u8], len: usize) -> Self {
+        assert!(bits.len() * 8 >= len, "AIS payload shorter than its length");
+        let mut bits = bits[..(len + 7) / 8].to_vec();
+        let spare = len % 8;
+        if spare != 0 {
+            *bits.last_mut().unwrap() &= 0xff << (8 - spare);
+        }
+        Self {
+            bits,
+            len,
+            channel: None,
+            own_vessel: false,
+        }
+    }
+
+    /// De-armor the payload field of a sentence, dropping `fill_bits` from its end
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if `armored` holds characters outside the armoring alphabet or
+    ///   `fill_bits` is above 5.
+    // `usize::div_ceil` needs a newer compiler than the MSRV
+    #[allow(clippy::manual_div_ceil)]
+    pub fn dearmor(armored: &str, fill_bits: u8) -> io::Result<Self> {
+        if fill_bits > 5 || (armored.is_empty() && fill_bits > 0) {
+            return Err(invalid("AIS fill bits out of range"));
+        }
+        let mut bits = vec![0; (armored.len() * 6 + 7) / 8];
+        for (i, c) in armored.bytes().enumerate() {
+            let value = match c {
+                b'0'..=b'W' => c - b'0',
+                b'`'..=b'w' => c - b'0' - 8,
+                _ => return Err(invalid("invalid AIS armoring character")),
+            };
+            for bit in 0..6 {
+                if value & (0x20 >> bit) != 0 {
+                    let at = i * 6 + bit;
+                    bits[at / 8] |= 0x80 >> (at % 8);
+                }
+            }
+        }
+        Ok(Self::from_bits(
+            &bits,
+            armored.len() * 6 - usize::from(fill_bits),
+        ))
+    }
+
+    /// Armor the payload, returning the characters and the number of fill bits
+    // `usize::div_ceil` needs a newer compiler than the MSRV
+    #[allow(clippy::manual_div_ceil)]
+    pub fn armor(&self) -> (String, u8) {
+        let chars = (self.len + 5) / 6;
+        let armored = (0..chars)
+            .map(|i| {
+                let value = self.bits_at(i * 6, 6) as u8;
+                char::from(if value < 40 { value + 48 } else { value + 56 })
+            })
+            .collect();
+        (armored, (chars * 6 - self.len) as u8)
+    }
+
+    /// Returns the number of bits
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the payload has no bits
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the bits packed most significant bit first, the last byte padded with zeros
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// Returns the radio channel, `A` or `B`, if the sentence named one
+    pub fn channel(&self) -> Option<char> {
+        self.channel
+    }
+
+    /// Returns `true` for a message of the own ship, from an `AIVDO` sentence
+    pub fn is_own_vessel(&self) -> bool {
+        self.own_vessel
+    }
+
+    /// Returns the unsigned field of `width` bits, up to 64, starting at bit `start`
+    pub fn unsigned(&self, start: usize, width: usize) -> Option<u64> {
+        if width > 64 || start.checked_add(width)? > self.len {
+            return None;
+        }
+        Some(self.bits_at(start, width))
+    }
+
+    /// Returns the two's complement field of `width` bits, 1 to 64, starting at bit `start`
+    pub fn signed(&self, start: usize, width: usize) -> Option<i64> {
+        if width == 0 {
+            return None;
+        }
+        let value = self.unsigned(start, width)?;
+        let shift = 64 - width as u32;
+        Some(((value << shift) as i64) >> shift)
+    }
+
+    /// Returns the text field of `width` bits starting at bit `start`, in six bit ASCII,
+    /// without the trailing `@` padding and spaces
+    pub fn text(&self, start: usize, width: usize) -> Option<String> {
+        if start.checked_add(width)? > self.len {
+            return None;
+        }
+        let text: String = (0..width / 6)
+            .map(|i| {
+                let value = self.bits_at(start + i * 6, 6) as u8;
+                char::from(if value < 32 { value + 64 } else { value })
+            })
+            .collect();
+        Some(text.trim_end_matches(['@', ' ']).to_owned())
+    }
+
+    /// Returns the message type, from its first six bits, 0 for an empty payload
+    pub fn message_type(&self) -> u8 {
+        self.unsigned(0, 6).unwrap_or(0) as u8
+    }
+
+    /// Returns the MMSI of the sending station, 0 for a payload too short to have one
+    pub fn mmsi(&self) -> u32 {
+        self.unsigned(8, 30).unwrap_or(0) as u32
+    }
+
+    /// Read `width` bits at `start`, zeros past the end
+    fn bits_at(&self, start: usize, width: usize) -> u64 {
+        (start..start + width).fold(0, |acc, at| {
+            let bit = self
+                .bits
+                .get(at / 8)
+                .is_some_and(|byte| byte & (0x80 >> (at % 8)) != 0);
+            acc << 1 | u64::from(at < self.len && bit)
+        })
+    }
+}
+
+struct Partial {
+    count: u8,
+    received: u8,
+    armored: String,
+}
+
+/// Collects the fragments of AIVDM and AIVDO sentences into [`AisPayload`]s
+///
+/// Fragments of a message must arrive in order.  A message missing a fragment is dropped
+/// when the next one with its sequential message ID starts, and counted in
+/// [`dropped`](Self::dropped).
+#[derive(Default)]
+pub struct AisAssembler {
+    partials: HashMap<(bool, u8), Partial>,
+    dropped: u64,
+}
+
+impl AisAssembler {
+    /// Create an assembler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of incomplete messages dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Add `sentence`, returning the payload it completes
+    ///
+    /// Sentences other than VDM and VDO are ignored.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if the fields of the sentence are malformed, its partial message
+    ///   being dropped.
+    pub fn push(&mut self, sentence: &Sentence) -> io::Result<Option<AisPayload>> {
+        let own_vessel = match sentence.sentence_type() {
+            "VDM" => false,
+            "VDO" => true,
+            _ => return Ok(None),
+        };
+        let mut fields = sentence.fields();
+        let mut field = || {
+            fields
+                .next()
+                .ok_or_else(|| invalid("AIS sentence too short"))
+        };
+        let count: u8 = field()?
+            .parse()
+            .map_err(|_| invalid("bad AIS fragment count"))?;
+        let number: u8 = field()?
+            .parse()
+            .map_err(|_| invalid("bad AIS fragment number"))?;
+        let sequence = field()?;
+        let channel = field()?.chars().next();
+        let armored = field()?;
+        let fill_bits: u8 = match field()? {
+            "" => 0,
+            fill => fill.parse().map_err(|_| invalid("bad AIS fill bits"))?,
+        };
+        if !(1..=9).contains(&count) || !(1..=count).contains(&number) {
+            return Err(invalid("AIS fragment number out of range"));
+        }
+        if count == 1 {
+            return self.complete(armored, fill_bits, channel, own_vessel);
+        }
+
+        let sequence: u8 = sequence
+            .parse()
+            .map_err(|_| invalid("bad AIS sequential message ID"))?;
+        let key = (own_vessel, sequence);
+        if number == 1 {
+            let previous = self.partials.insert(
+                key,
+                Partial {
+                    count,
+                    received: 1,
+                    armored: armored.to_owned(),
+                },
+            );
+            if previous.is_some() {
+                self.dropped += 1;
+            }
+            return Ok(None);
+        }
+        match self.partials.get_mut(&key) {
+            Some(partial) if partial.count == count && partial.received + 1 == number => {
+                partial.received = number;
+                partial.armored.push_str(armored);
+            }
+            Some(_) => {
+                self.partials.remove(&key);
+                self.dropped += 1;
+                return Ok(None);
+            }
+            // The start of the message was missed
+            None => return Ok(None),
+        }
+        if number < count {
+            return Ok(None);
+        }
+        let partial = self.partials.remove(&key).unwrap();
+        self.complete(&partial.armored, fill_bits, channel, own_vessel)
+    }
+
+    fn complete(
+        &self,
+        armored: &str,
+        fill_bits: u8,
+        channel: Option<char>,
+        own_vessel: bool,
+    ) -> io::Result<Option<AisPayload>> {
+        let mut payload = AisPayload::dearmor(armored, fill_bits)?;
+        payload.channel = channel;
+        payload.own_vessel = own_vessel;
+        Ok(Some(payload))
+    }
+}
+
+impl std::fmt::Debug for AisAssembler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AisAssembler")
+            .field("partial", &self.partials.len())
+            .field("dropped", &self.dropped)
+            .finish()
+    }
+}
+
+/// Decoder of [`AisPayload`]s from AIVDM and AIVDO sentences, see the [module](self) docs
+///
+/// Other sentences are skipped.  Sentences failing their checksum are handled according to
+/// the [`ChecksumPolicy`], and malformed AIS sentences reported as
+/// `io::ErrorKind::InvalidData` errors.
+///
+/// The encoder armors payloads into `!AIVDM` sentences, or `!AIVDO` for the own ship,
+/// fragmented with sequential message IDs cycling from 0 to 9.
+#[derive(Debug, Default)]
+pub struct AisCodec {
+    nmea: NmeaCodec,
+    assembler: AisAssembler,
+    sequence: u8,
+}
+
+impl AisCodec {
+    /// Create a codec
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what happens to sentences failing their checksum
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.nmea.set_checksum_policy(policy);
+    }
+
+    /// Returns what happens to sentences failing their checksum
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.nmea.checksum_policy()
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between sentences
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.nmea.resync_stats()
+    }
+
+    /// Returns the number of incomplete messages dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.assembler.dropped()
+    }
+}
+
+impl Decoder for AisCodec {
+    type Item = AisPayload;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        while let Some(sentence) = self.nmea.decode(src)? {
+            if let Some(payload) = self.assembler.push(&sentence)? {
+                return Ok(Some(payload));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Encoder<AisPayload> for AisCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: AisPayload, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (armored, fill_bits) = item.armor();
+        let chunks: Vec<&str> = armored
+            .as_bytes()
+            .chunks(MAX_FRAGMENT_CHARS)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap())
+            .collect();
+        let count = chunks.len().max(1);
+        if count > 9 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "AIS payload too long for nine sentences",
+            ));
+        }
+        let sequence = if count > 1 {
+            self.sequence = (self.sequence + 1) % 10;
+            self.sequence.to_string()
+        } else {
+            String::new()
+        };
+        let address = if item.own_vessel { "AIVDO" } else { "AIVDM" };
+        let channel = item.channel.map(String::from).unwrap_or_default();
+        for number in 1..=count {
+            let chunk = chunks.get(number - 1).copied().unwrap_or("");
+            let fill = if number == count { fill_bits } else { 0 };
+            let mut body = String::new();
+            let _ = write!(
+                body,
+                "{},{},{},{},{},{},{}",
+                address, count, number, sequence, channel, chunk, fill
+            );
+            self.nmea.encode(Sentence::encapsulated(&body)?, dst)?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,247 @@
+//! defmt over UART framing
+//!
+//! [defmt](https://defmt.ferrous-systems.com) logs are compact binary frames that a host
+//! decodes against the firmware's ELF file.  Sent over a UART, as by `defmt-serial`, each
+//! frame is rzCOBS encoded, which leaves no zero byte in it, and followed by a zero.
+//! [`DefmtCodec`] undoes that framing and yields the raw defmt frames, to hand to an
+//! external decoder like `defmt-decoder` along with the ELF file.
+//!
+//! rzCOBS pads the end of a frame with up to seven zero bytes, which a frame can't be told
+//! apart from, so they are kept.  The defmt decoder ignores what follows a frame.
+//!
+//! ```
+//! use bytes::{Bytes, BytesMut};
+//! use tokio_serial::codec::defmt::DefmtCodec;
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = DefmtCodec::new();
+//! let mut wire = BytesMut::new();
+//! codec.encode(Bytes::from_static(&[0x01, 0x00, 0x2a, 0x00]), &mut wire).unwrap();
+//! assert!(!wire[..wire.len() - 1].contains(&0));
+//! let frame = codec.decode(&mut wire).unwrap().unwrap();
+//! assert!(frame.starts_with(&[0x01, 0x00, 0x2a, 0x00]));
+//! ```
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::io;
+
+/// Frames longer than this once encoded are dropped, by default
+pub const DEFAULT_MAX_LENGTH: usize = 4096;
+
+/// Encode `data` with rzCOBS, without the trailing zero delimiter
+pub fn rzcobs_encode(data: &[u8], dst: &mut BytesMut) {
+    // Bytes since the last header, and which of the first seven were zeros
+    let mut run = 0u8;
+    let mut zeros = 0u8;
+    for &byte in data {
+        if run < 7 {
+            if byte == 0 {
+                zeros |= 1 << run;
+            } else {
+                dst.put_u8(byte);
+            }
+            run += 1;
+            // Seven bytes without a zero go on as a run of non-zero bytes
+            if run == 7 && zeros != 0 {
+                dst.put_u8(zeros);
+                run = 0;
+                zeros = 0;
+            }
+        } else if byte == 0 {
+            // The run ends with an implicit zero
+            dst.put_u8(0x80 | (run - 7));
+            run = 0;
+            zeros = 0;
+        } else {
+            dst.put_u8(byte);
+            run += 1;
+            if run == 134 {
+                dst.put_u8(0xff);
+                run = 0;
+                zeros = 0;
+            }
+        }
+    }
+    match run {
+        0 => {}
+        // The missing bytes of the group are zeros
+        1..=6 => dst.put_u8((zeros | (0x7f << run)) & 0x7f),
+        _ => dst.put_u8(0x80 | (run - 7)),
+    }
+}
+
+/// Decode an rzCOBS encoded frame, without its zero delimiter
+///
+/// Returns `None` if `data` is malformed.  Headers follow their bytes, so decoding runs
+/// from the end.
+pub fn rzcobs_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut data = data.iter().rev().copied();
+    while let Some(header) = data.next() {
+        match header {
+            0 => return None,
+            0x01..=0x7f => {
+                for bit in (0..7).rev() {
+                    if header & (1 << bit) == 0 {
+                        out.push(data.next()?);
+                    } else {
+                        out.push(0);
+                    }
+                }
+            }
+            0x80..=0xfe => {
+                out.push(0);
+                for _ in 0..(header & 0x7f) + 7 {
+                    out.push(data.next()?);
+                }
+            }
+            0xff => {
+                for _ in 0..134 {
+                    out.push(data.next()?);
+                }
+            }
+        }
+    }
+    out.reverse();
+    Some(out)
+}
+
+/// Decoder and encoder for rzCOBS framed defmt frames, see the [module](self) docs
+///
+/// A partial first frame, from opening the port while the device was sending, is skipped.
+/// Malformed and overlong frames are handled according to the [`ChecksumPolicy`], reported
+/// as `io::ErrorKind::InvalidData` errors by default.
+#[derive(Debug, Clone)]
+pub struct DefmtCodec {
+    max_length: usize,
+    // No delimiter seen yet, the bytes may start mid frame
+    first: bool,
+    // Dropping an overlong frame up to its delimiter
+    discarding: bool,
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl DefmtCodec {
+    /// Create a codec using [`DEFAULT_MAX_LENGTH`]
+    pub fn new() -> Self {
+        Self::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Create a codec dropping frames longer than `max_length` bytes once encoded
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            max_length,
+            first: true,
+            discarding: false,
+            checksum_policy: ChecksumPolicy::default(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Returns the longest encoded frame
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Set what happens to malformed frames
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to malformed frames
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between frames
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Default for DefmtCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DefmtCodec {
+    fn next_frame(&mut self, src: &mut BytesMut) -> Option<Result<Bytes, Corrupt>> {
+        loop {
+            let end = match src.iter().position(|&b| b == 0) {
+                Some(end) => end,
+                None if self.discarding => {
+                    self.skips.skip(src.len());
+                    src.clear();
+                    return None;
+                }
+                None if src.len() > self.max_length => {
+                    self.discarding = true;
+                    return Some(Err(self.corrupt(src.split(), "defmt frame too long")));
+                }
+                None => return None,
+            };
+            let encoded = src.split_to(end);
+            src.advance(1);
+            let first = std::mem::replace(&mut self.first, false);
+            if std::mem::replace(&mut self.discarding, false) {
+                self.skips.skip(end + 1);
+                continue;
+            }
+            if encoded.is_empty() {
+                continue;
+            }
+            if encoded.len() > self.max_length {
+                return Some(Err(self.corrupt(encoded, "defmt frame too long")));
+            }
+            match rzcobs_decode(&encoded) {
+                Some(frame) => {
+                    self.skips.frame();
+                    return Some(Ok(Bytes::from(frame)));
+                }
+                // Likely the tail of a frame sent before the port was opened
+                None if first => self.skips.skip(end + 1),
+                None => return Some(Err(self.corrupt(encoded, "malformed rzCOBS frame"))),
+            }
+        }
+    }
+
+    fn corrupt(&mut self, bytes: BytesMut, message: &str) -> Corrupt {
+        self.skips.corrupt(bytes.len());
+        Corrupt {
+            bytes: bytes.freeze(),
+            error: io::Error::new(io::ErrorKind::InvalidData, message),
+        }
+    }
+}
+
+impl Decoder for DefmtCodec {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_frame(src))
+    }
+}
+
+impl Checksummed for DefmtCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<Bytes>>> {
+        Ok(self.next_frame(src).map(Frame::from))
+    }
+}
+
+impl Encoder<Bytes> for DefmtCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(item.len() + item.len() / 7 + 2);
+        rzcobs_encode(&item, dst);
+        dst.put_u8(0);
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Decoding several protocols interleaved on one port
+//!
+//! GNSS receivers commonly send NMEA sentences, UBX frames and RTCM corrections on the same
+//! UART.  [`DemuxCodec`] hosts one decoder per protocol and emits their frames as a single
+//! tagged type.  Each protocol is routed by its sync bytes: at every frame boundary the
+//! earliest sync sequence of any protocol picks the decoder for the next frame.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tokio_serial::codec::demux::{DemuxCodec, GnssFrame};
+//! use tokio_serial::frame::SerialFramed;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut frames = SerialFramed::new(port, DemuxCodec::gnss());
+//! while let Some(frame) = frames.next().await {
+//!     match frame {
+//!         Ok(GnssFrame::Nmea(sentence)) => println!("{}", sentence),
+//!         Ok(GnssFrame::Ubx(frame)) => println!("UBX {:02x}/{:02x}", frame.class, frame.id),
+//!         Err(e) => eprintln!("corrupt frame: {}", e),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Resynchronization
+//!
+//! A corrupt frame must not take the frames of other protocols with it, like an NMEA
+//! sentence that lost its line end swallowing the UBX frame after it, or a UBX frame with a
+//! corrupt length waiting for bytes that belong to the following frames.  A decoder is
+//! handed the buffer starting at its sync bytes and must either return a frame or wait for
+//! more bytes without consuming any.  When it fails, or consumes bytes without returning a
+//! frame, its attempt is undone and the search for a sync sequence resumes one byte after
+//! the one it started at.  The failure itself is reported as an error.  The decoder of a
+//! [text protocol](DemuxCodec::route_text) doesn't see past the next sync of another
+//! protocol.
+use super::nmea::{NmeaCodec, Sentence};
+use super::ubx::{self, UbxCodec, UbxFrame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::Decoder;
+
+use std::fmt;
+use std::io;
+
+/// A frame of a GNSS receiver, see [`DemuxCodec::gnss`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GnssFrame {
+    /// NMEA 0183 sentence
+    Nmea(Sentence),
+    /// u-blox UBX frame
+    Ubx(UbxFrame),
+}
+
+type Decode<T> = Box<dyn FnMut(&mut BytesMut) -> io::Result<Option<T>> + Send>;
+
+struct Route<T> {
+    syncs: Vec<&'static [u8]>,
+    decode: Decode<T>,
+    // Frames never hold the sync bytes of another protocol
+    text: bool,
+}
+
+/// Decoder of interleaved protocols, see the [module](self) docs
+pub struct DemuxCodec<T> {
+    routes: Vec<Route<T>>,
+    skips: SkipTracker,
+}
+
+impl<T> DemuxCodec<T> {
+    /// Create a codec without any protocol, add them with [`route`](Self::route)
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between frames
+    ///
+    /// A failed attempt of a sub-decoder counts as a corrupt frame, and the bytes up to the
+    /// next sync sequence as skipped.  The counters of the sub-decoders themselves also see
+    /// the attempts undone.
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+
+    /// Decode the frames starting with one of `syncs` with `decoder`, tagging them with `tag`
+    ///
+    /// When the sync sequences of several protocols match at the same position, the
+    /// protocol routed first wins.
+    pub fn route<D, F>(self, syncs: &[&'static [u8]], decoder: D, tag: F) -> Self
+    where
+        D: Decoder<Error = io::Error> + Send + 'static,
+        F: Fn(D::Item) -> T + Send + 'static,
+    {
+        self.add_route(syncs, decoder, tag, false)
+    }
+
+    /// Like [`route`](Self::route), for a text protocol like NMEA
+    ///
+    /// The frames of a text protocol can't hold the sync bytes of the binary protocols
+    /// routed alongside, so its decoder doesn't see past the next sync sequence of another
+    /// protocol.  A frame still incomplete there was cut short and is reported as an
+    /// `InvalidData` error.
+    pub fn route_text<D, F>(self, syncs: &[&'static [u8]], decoder: D, tag: F) -> Self
+    where
+        D: Decoder<Error = io::Error> + Send + 'static,
+        F: Fn(D::Item) -> T + Send + 'static,
+    {
+        self.add_route(syncs, decoder, tag, true)
+    }
+
+    fn add_route<D, F>(
+        mut self,
+        syncs: &[&'static [u8]],
+        mut decoder: D,
+        tag: F,
+        text: bool,
+    ) -> Self
+    where
+        D: Decoder<Error = io::Error> + Send + 'static,
+        F: Fn(D::Item) -> T + Send + 'static,
+    {
+        self.routes.push(Route {
+            syncs: syncs.to_vec(),
+            decode: Box::new(move |src| Ok(decoder.decode(src)?.map(&tag))),
+            text,
+        });
+        self
+    }
+
+    /// Find the earliest frame start, returning its position and route
+    ///
+    /// `Err` holds the position of a sync sequence cut short by the end of the buffer.
+    fn find_start(&self, src: &[u8]) -> Result<Option<(usize, usize)>, usize> {
+        for pos in 0..src.len() {
+            let rest = &src[pos..];
+            let mut partial = false;
+            for (index, route) in self.routes.iter().enumerate() {
+                for sync in &route.syncs {
+                    if rest.starts_with(sync) {
+                        return Ok(Some((pos, index)));
+                    }
+                    partial |= sync.starts_with(rest);
+                }
+            }
+            if partial {
+                return Err(pos);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Position of the first complete sync sequence of a route other than `route`
+    fn next_foreign_sync(&self, src: &[u8], route: usize) -> Option<usize> {
+        (0..src.len()).find(|&pos| {
+            self.routes.iter().enumerate().any(|(index, other)| {
+                index != route && other.syncs.iter().any(|sync| src[pos..].starts_with(sync))
+            })
+        })
+    }
+
+    /// Run the decoder of `route` on `src`, which starts with one of its sync sequences
+    fn decode_route(&mut self, route: usize, src: &mut BytesMut) -> io::Result<Option<T>> {
+        let bound = if self.routes[route].text {
+            self.next_foreign_sync(&src[1..], route).map(|pos| pos + 1)
+        } else {
+            None
+        };
+        let end = match bound {
+            Some(end) => end,
+            None => return (self.routes[route].decode)(src),
+        };
+
+        let rest = src.split_off(end);
+        let len = src.len();
+        let result = (self.routes[route].decode)(src);
+        let cut_short = matches!(result, Ok(None)) && src.len() == len;
+        src.unsplit(rest);
+        if cut_short {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame cut short by another protocol",
+            ));
+        }
+        result
+    }
+}
+
+impl DemuxCodec<GnssFrame> {
+    /// Create a codec for the NMEA sentences and UBX frames of a u-blox receiver
+    pub fn gnss() -> Self {
+        Self::new()
+            .route_text(&[b"$", b"!"], NmeaCodec::new(), GnssFrame::Nmea)
+            .route(
+                &[&[ubx::SYNC_1, ubx::SYNC_2]],
+                UbxCodec::new(),
+                GnssFrame::Ubx,
+            )
+    }
+}
+
+impl<T> Default for DemuxCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for DemuxCodec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let syncs: Vec<_> = self.routes.iter().map(|route| &route.syncs).collect();
+        f.debug_struct("DemuxCodec")
+            .field("syncs", &syncs)
+            .field("skips", &self.skips)
+            .finish()
+    }
+}
+
+impl<T> Decoder for DemuxCodec<T> {
+    type Item = T;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let (start, index) = match self.find_start(src) {
+                Ok(Some(found)) => found,
+                Ok(None) => {
+                    self.skips.skip(src.len());
+                    src.clear();
+                    return Ok(None);
+                }
+                Err(partial) => {
+                    self.skips.skip(partial);
+                    src.advance(partial);
+                    return Ok(None);
+                }
+            };
+            self.skips.skip(start);
+            src.advance(start);
+
+            let snapshot = src.clone();
+            let result = self.decode_route(index, src);
+            match result {
+                Ok(Some(frame)) => {
+                    self.skips.frame();
+                    return Ok(Some(frame));
+                }
+                Ok(None) if src.len() == snapshot.len() => return Ok(None),
+                _ => {
+                    // Undo the attempt and look for the next frame after its first byte
+                    *src = snapshot;
+                    self.skips.corrupt(1);
+                    src.advance(1);
+                    result?;
+                }
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let frame = self.decode(buf)?;
+        if frame.is_none() {
+            // A frame cut short by the hangup
+            self.skips.skip(buf.len());
+            buf.clear();
+        }
+        Ok(frame)
+    }
+}
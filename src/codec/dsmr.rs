@@ -0,0 +1,301 @@
+//! DSMR P1 smart meter telegram codec
+//!
+//! Dutch, Belgian and other European smart meters send a telegram every second or ten on
+//! their P1 port: `/` and the meter identification, an empty line, one COSEM object per
+//! line like `1-0:1.8.1(001234.567*kWh)`, and `!` followed by the CRC-16 of everything
+//! from the `/` through the `!`.  Meters older than DSMR 4 send no CRC.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::dsmr::DsmrCodec;
+//! use tokio_util::codec::Decoder;
+//!
+//! let mut src = BytesMut::from(&b"/KFM5KAIFA-METER\r\n\r\n1-0:1.8.1(001234.567*kWh)\r\n!\r\n"[..]);
+//! let telegram = DsmrCodec::new().decode(&mut src).unwrap().unwrap();
+//! assert_eq!(telegram.get("1-0:1.8.1").unwrap().value(), Some("001234.567*kWh"));
+//! ```
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::{fmt, io};
+
+/// Telegrams longer than this are discarded
+pub const DEFAULT_MAX_LENGTH: usize = 8 * 1024;
+
+/// Compute the CRC-16/ARC of `data`, the telegram CRC
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ u16::from(byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xa001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// A COSEM object of a [`Telegram`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosemObject {
+    /// OBIS reference, like `1-0:1.8.1`
+    pub obis: String,
+    /// The values in parentheses, with their units, like `001234.567*kWh`
+    pub values: Vec<String>,
+}
+
+impl CosemObject {
+    /// Returns the last value, the reading of objects carrying a timestamp first
+    pub fn value(&self) -> Option<&str> {
+        self.values.last().map(String::as_str)
+    }
+
+    /// Returns the number and unit of the last value, like `(1234.567, "kWh")`
+    pub fn quantity(&self) -> Option<(f64, &str)> {
+        let value = self.value()?;
+        let (number, unit) = value.split_once('*').unwrap_or((value, ""));
+        Some((number.parse().ok()?, unit))
+    }
+}
+
+/// A single P1 telegram
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Telegram {
+    text: String,
+}
+
+impl Telegram {
+    /// Parse a telegram from its text, with or without the trailing `\r\n`
+    ///
+    /// The CRC is verified if present.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let text = text.trim_end_matches(['\r', '\n']);
+        if !text.starts_with('/') {
+            return Err(invalid("DSMR telegram must start with '/'"));
+        }
+        let end = text
+            .rfind('!')
+            .ok_or_else(|| invalid("DSMR telegram must end with '!'"))?;
+        let crc = &text[end + 1..];
+        if !crc.is_empty() {
+            let expected = u16::from_str_radix(crc, 16)
+                .ok()
+                .filter(|_| crc.len() == 4)
+                .ok_or_else(|| invalid("malformed DSMR CRC"))?;
+            if crc16(&text.as_bytes()[..=end]) != expected {
+                return Err(invalid("DSMR CRC mismatch"));
+            }
+        }
+        Ok(Self {
+            text: text.to_owned(),
+        })
+    }
+
+    /// Returns the full text of the telegram, without the trailing `\r\n`
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the meter identification following the `/`
+    pub fn header(&self) -> &str {
+        self.text[1..].lines().next().unwrap_or("").trim_end()
+    }
+
+    /// Returns the CRC transmitted with the telegram, `None` before DSMR 4
+    pub fn crc(&self) -> Option<u16> {
+        let end = self.text.rfind('!')?;
+        u16::from_str_radix(&self.text[end + 1..], 16).ok()
+    }
+
+    /// Returns the COSEM objects of the telegram
+    ///
+    /// A line starting with `(` continues the object of the previous line, as DSMR 2 and 3
+    /// meters send the gas reading.
+    pub fn objects(&self) -> Vec<CosemObject> {
+        let end = self.text.rfind('!').unwrap_or(self.text.len());
+        let mut objects: Vec<CosemObject> = Vec::new();
+        for line in self.text[..end].lines().skip(1).map(str::trim) {
+            let (obis, values) = match line.find('(') {
+                Some(0) => match objects.last_mut() {
+                    Some(object) => {
+                        object.values.extend(parenthesized(line));
+                        continue;
+                    }
+                    None => continue,
+                },
+                Some(start) => (&line[..start], &line[start..]),
+                None => continue,
+            };
+            objects.push(CosemObject {
+                obis: obis.to_owned(),
+                values: parenthesized(values).collect(),
+            });
+        }
+        objects
+    }
+
+    /// Returns the object with the OBIS reference `obis`, like `1-0:1.8.1`
+    pub fn get(&self, obis: &str) -> Option<CosemObject> {
+        self.objects()
+            .into_iter()
+            .find(|object| object.obis == obis)
+    }
+}
+
+impl fmt::Display for Telegram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Returns the values in parentheses of `fields`
+fn parenthesized(fields: &str) -> impl Iterator<Item = String> + '_ {
+    fields
+        .split(')')
+        .filter_map(|field| field.trim().strip_prefix('('))
+        .map(str::to_owned)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Decoder and encoder for P1 telegrams
+///
+/// Bytes before a `/` are skipped.  Telegrams failing their CRC are handled according to
+/// the [`ChecksumPolicy`], reported as `io::ErrorKind::InvalidData` errors by default.
+/// Encoding writes a [`Telegram`] verbatim followed by `\r\n`.
+#[derive(Debug, Clone)]
+pub struct DsmrCodec {
+    max_length: usize,
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl DsmrCodec {
+    /// Create a codec using [`DEFAULT_MAX_LENGTH`]
+    pub fn new() -> Self {
+        Self::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Create a codec discarding telegrams longer than `max_length` bytes
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            max_length,
+            checksum_policy: ChecksumPolicy::default(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Set what happens to telegrams failing their CRC
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to telegrams failing their CRC
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between telegrams
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Default for DsmrCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DsmrCodec {
+    fn next_telegram(&mut self, src: &mut BytesMut) -> Option<Result<Telegram, Corrupt>> {
+        loop {
+            match src.iter().position(|&b| b == b'/') {
+                Some(start) => {
+                    self.skips.skip(start);
+                    src.advance(start);
+                }
+                None => {
+                    self.skips.skip(src.len());
+                    src.clear();
+                    return None;
+                }
+            }
+
+            // The line of the `!` ends the telegram
+            let end = src.iter().position(|&b| b == b'!').and_then(|bang| {
+                src[bang..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map(|newline| bang + newline)
+            });
+            let end = match end {
+                Some(end) => end,
+                None => {
+                    if src.len() > self.max_length {
+                        // Runaway telegram, look for the next start
+                        self.skips.corrupt(1);
+                        src.advance(1);
+                        continue;
+                    }
+                    return None;
+                }
+            };
+
+            let mut telegram = src.split_to(end + 1);
+            // A new start before the end means the previous telegram was cut short
+            if let Some(restart) = telegram[1..].iter().rposition(|&b| b == b'/') {
+                let bang = telegram.iter().position(|&b| b == b'!').unwrap_or(0);
+                if restart + 1 < bang {
+                    self.skips.corrupt(restart + 1);
+                    telegram.advance(restart + 1);
+                }
+            }
+            let text = String::from_utf8_lossy(&telegram);
+            return Some(match Telegram::parse(&text) {
+                Ok(telegram) => {
+                    self.skips.frame();
+                    Ok(telegram)
+                }
+                Err(error) => {
+                    self.skips.corrupt(telegram.len());
+                    Err(Corrupt {
+                        bytes: telegram.freeze(),
+                        error,
+                    })
+                }
+            });
+        }
+    }
+}
+
+impl Decoder for DsmrCodec {
+    type Item = Telegram;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_telegram(src))
+    }
+}
+
+impl Checksummed for DsmrCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<Telegram>>> {
+        Ok(self.next_telegram(src).map(Frame::from))
+    }
+}
+
+impl Encoder<Telegram> for DsmrCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Telegram, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(item.text.len() + 2);
+        dst.extend_from_slice(item.text.as_bytes());
+        dst.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+}
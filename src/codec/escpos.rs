@@ -0,0 +1,409 @@
+//! ESC/POS receipt printer codec
+//!
+//! Receipt printers on serial ports take text interleaved with `ESC` and `GS` command
+//! sequences, and answer `DLE EOT n` real-time status requests with a single status byte.
+//! [`EscPosCodec`] encodes [`Command`]s and decodes the answers to the status requests it
+//! sent as [`Status`], so a [`SerialFramed`](crate::frame::SerialFramed) over it is both
+//! the printer's sink and its status stream:
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::escpos::{Command, Cut, EscPosCodec, StatusKind};
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = EscPosCodec::new();
+//! let mut dst = BytesMut::new();
+//! codec.encode(Command::Text("Total: 4.20\n".into()), &mut dst).unwrap();
+//! codec.encode(Command::Cut(Cut::Partial), &mut dst).unwrap();
+//! codec.encode(Command::Status(StatusKind::Paper), &mut dst).unwrap();
+//!
+//! let status = codec.decode(&mut BytesMut::from(&[0x12][..])).unwrap().unwrap();
+//! assert!(!status.paper_near_end());
+//! ```
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io;
+
+/// Escape
+pub const ESC: u8 = 0x1b;
+/// Group separator, starting the newer commands
+pub const GS: u8 = 0x1d;
+/// Data link escape, starting the real-time commands
+pub const DLE: u8 = 0x10;
+/// End of transmission, `DLE EOT n` requests a status
+pub const EOT: u8 = 0x04;
+
+/// Horizontal alignment of the following lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    /// Left aligned, the default
+    Left,
+    /// Centered
+    Center,
+    /// Right aligned
+    Right,
+}
+
+/// How the paper is cut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cut {
+    /// Cut the paper through
+    Full,
+    /// Leave a point uncut, so the receipt hangs until torn off
+    Partial,
+}
+
+/// Barcode symbology, as numbered for `GS k` in its second form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbology {
+    /// UPC-A, 11 or 12 digits
+    UpcA,
+    /// UPC-E
+    UpcE,
+    /// EAN-13, 12 or 13 digits
+    Ean13,
+    /// EAN-8, 7 or 8 digits
+    Ean8,
+    /// Code 39
+    Code39,
+    /// Interleaved 2 of 5
+    Itf,
+    /// Codabar
+    Codabar,
+    /// Code 93
+    Code93,
+    /// Code 128, the data starting with the code set, like `{B`
+    Code128,
+}
+
+impl From<Symbology> for u8 {
+    fn from(symbology: Symbology) -> Self {
+        match symbology {
+            Symbology::UpcA => 65,
+            Symbology::UpcE => 66,
+            Symbology::Ean13 => 67,
+            Symbology::Ean8 => 68,
+            Symbology::Code39 => 69,
+            Symbology::Itf => 70,
+            Symbology::Codabar => 71,
+            Symbology::Code93 => 72,
+            Symbology::Code128 => 73,
+        }
+    }
+}
+
+/// The status answered to a `DLE EOT n` request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusKind {
+    /// `n = 1`: printer status, online or not
+    Printer,
+    /// `n = 2`: why the printer is offline
+    Offline,
+    /// `n = 3`: error causes
+    Error,
+    /// `n = 4`: paper roll sensors
+    Paper,
+}
+
+impl From<StatusKind> for u8 {
+    fn from(kind: StatusKind) -> Self {
+        match kind {
+            StatusKind::Printer => 1,
+            StatusKind::Offline => 2,
+            StatusKind::Error => 3,
+            StatusKind::Paper => 4,
+        }
+    }
+}
+
+/// A status byte received from the printer
+///
+/// Which bits mean what depends on the [`StatusKind`] requested, the methods return `false`
+/// for bits the kind doesn't carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    /// What was requested
+    pub kind: StatusKind,
+    /// The status byte as received
+    pub byte: u8,
+}
+
+impl Status {
+    fn bit(&self, kind: StatusKind, bit: u8) -> bool {
+        self.kind == kind && self.byte & (1 << bit) != 0
+    }
+
+    /// Returns `true` if the printer is offline
+    pub fn is_offline(&self) -> bool {
+        self.bit(StatusKind::Printer, 3)
+    }
+
+    /// Returns `true` if the cover is open
+    pub fn cover_open(&self) -> bool {
+        self.bit(StatusKind::Offline, 2)
+    }
+
+    /// Returns `true` if printing stopped for lack of paper
+    pub fn paper_end(&self) -> bool {
+        self.bit(StatusKind::Offline, 5) || self.bit(StatusKind::Paper, 5)
+    }
+
+    /// Returns `true` if the paper roll is nearly used up
+    pub fn paper_near_end(&self) -> bool {
+        self.bit(StatusKind::Paper, 2)
+    }
+
+    /// Returns `true` if the printer stopped on an error
+    pub fn has_error(&self) -> bool {
+        self.bit(StatusKind::Offline, 6)
+    }
+
+    /// Returns `true` if the cutter failed
+    pub fn cutter_error(&self) -> bool {
+        self.bit(StatusKind::Error, 3)
+    }
+
+    /// Returns `true` if the error needs the printer to be power cycled
+    pub fn unrecoverable_error(&self) -> bool {
+        self.bit(StatusKind::Error, 5)
+    }
+}
+
+/// A monochrome raster image, printed with `GS v 0`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    width: u16,
+    height: u16,
+    bits: Bytes,
+}
+
+impl Raster {
+    /// Create an image from packed rows of `(width + 7) / 8` bytes, most significant bit
+    /// leftmost, set bits printed black
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `bits` doesn't hold `height` rows.
+    pub fn new(width: u16, height: u16, bits: impl Into<Bytes>) -> io::Result<Self> {
+        let bits = bits.into();
+        if bits.len() != row_len(width) * usize::from(height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "raster size doesn't match its dimensions",
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            bits,
+        })
+    }
+
+    /// Create an image from one 8 bit luminance per pixel, row by row, printing pixels
+    /// darker than `threshold` black
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `pixels` doesn't hold `width * height` pixels.
+    pub fn from_luma(width: u16, height: u16, pixels: &[u8], threshold: u8) -> io::Result<Self> {
+        if pixels.len() != usize::from(width) * usize::from(height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "pixel count doesn't match the dimensions",
+            ));
+        }
+        let mut bits = BytesMut::zeroed(row_len(width) * usize::from(height));
+        for (row, pixels) in pixels.chunks(usize::from(width).max(1)).enumerate() {
+            for (column, &luma) in pixels.iter().enumerate() {
+                if luma < threshold {
+                    bits[row * row_len(width) + column / 8] |= 0x80 >> (column % 8);
+                }
+            }
+        }
+        Self::new(width, height, bits.freeze())
+    }
+
+    /// Returns the width in dots
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Returns the height in dots
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+}
+
+// `usize::div_ceil` needs a newer compiler than the MSRV
+#[allow(clippy::manual_div_ceil)]
+fn row_len(width: u16) -> usize {
+    (usize::from(width) + 7) / 8
+}
+
+/// A printer command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `ESC @`: reset the printer to its default settings
+    Init,
+    /// Text to print, `\n` ending lines.  Characters outside of ASCII are sent as `?`.
+    Text(String),
+    /// `ESC E`: switch emphasized printing on or off
+    Bold(bool),
+    /// `ESC -`: underline with 0 to 2 dots, 0 switching it off
+    Underline(u8),
+    /// `ESC a`: align the following lines
+    Align(Align),
+    /// `GS !`: character width and height multipliers, 1 to 8
+    Size {
+        /// Width multiplier
+        width: u8,
+        /// Height multiplier
+        height: u8,
+    },
+    /// `ESC d`: print the buffer and feed this many lines
+    Feed(u8),
+    /// `GS h`: height of the following barcodes in dots
+    BarcodeHeight(u8),
+    /// `GS k`: print a barcode
+    Barcode {
+        /// Symbology
+        symbology: Symbology,
+        /// Data, in the character set of the symbology
+        data: String,
+    },
+    /// `GS v 0`: print a raster image
+    Image(Raster),
+    /// `GS V`: cut the paper
+    Cut(Cut),
+    /// `DLE EOT n`: request a status, answered as the next [`Status`] decoded
+    Status(StatusKind),
+    /// Bytes sent as they are, for commands not covered here
+    Raw(Bytes),
+}
+
+/// Encoder of printer [`Command`]s and decoder of the [`Status`] they request
+///
+/// Each decoded status is matched to the oldest request still unanswered.  Bytes received
+/// without a request pending, or not shaped like a status, are skipped and counted in the
+/// [`ResyncStats`].
+#[derive(Debug, Clone, Default)]
+pub struct EscPosCodec {
+    pending: VecDeque<StatusKind>,
+    skips: SkipTracker,
+}
+
+impl EscPosCodec {
+    /// Create a new codec
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of status requests not answered yet
+    pub fn pending_status(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Forget the unanswered status requests, as after a timeout
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Returns a handle on the counters of the bytes skipped
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Decoder for EscPosCodec {
+    type Item = Status;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        while !src.is_empty() {
+            let byte = src.get_u8();
+            // Status bytes have bits 1 and 4 set, 0 and 7 cleared
+            match self.pending.front() {
+                Some(&kind) if byte & 0x93 == 0x12 => {
+                    self.pending.pop_front();
+                    self.skips.frame();
+                    return Ok(Some(Status { kind, byte }));
+                }
+                _ => self.skips.skip(1),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Encoder<Command> for EscPosCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Command, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match item {
+            Command::Init => dst.extend_from_slice(&[ESC, b'@']),
+            Command::Text(text) => {
+                dst.reserve(text.len());
+                dst.extend(
+                    text.chars()
+                        .map(|c| if c.is_ascii() { c as u8 } else { b'?' }),
+                );
+            }
+            Command::Bold(on) => dst.extend_from_slice(&[ESC, b'E', u8::from(on)]),
+            Command::Underline(dots) => dst.extend_from_slice(&[ESC, b'-', dots.min(2)]),
+            Command::Align(align) => {
+                let n = match align {
+                    Align::Left => 0,
+                    Align::Center => 1,
+                    Align::Right => 2,
+                };
+                dst.extend_from_slice(&[ESC, b'a', n]);
+            }
+            Command::Size { width, height } => {
+                if !(1..=8).contains(&width) || !(1..=8).contains(&height) {
+                    return Err(invalid_input("character size multipliers are 1 to 8"));
+                }
+                dst.extend_from_slice(&[GS, b'!', (width - 1) << 4 | (height - 1)]);
+            }
+            Command::Feed(lines) => dst.extend_from_slice(&[ESC, b'd', lines]),
+            Command::BarcodeHeight(dots) => dst.extend_from_slice(&[GS, b'h', dots.max(1)]),
+            Command::Barcode { symbology, data } => {
+                let len = u8::try_from(data.len())
+                    .ok()
+                    .filter(|_| !data.is_empty() && data.is_ascii())
+                    .ok_or_else(|| invalid_input("barcode data must be 1 to 255 ASCII bytes"))?;
+                dst.extend_from_slice(&[GS, b'k', symbology.into(), len]);
+                dst.extend_from_slice(data.as_bytes());
+            }
+            Command::Image(raster) => {
+                dst.reserve(8 + raster.bits.len());
+                dst.extend_from_slice(&[GS, b'v', b'0', 0]);
+                // A u16 width fits in a u16 count of bytes
+                dst.put_u16_le(row_len(raster.width) as u16);
+                dst.put_u16_le(raster.height);
+                dst.extend_from_slice(&raster.bits);
+            }
+            Command::Cut(cut) => {
+                let m = match cut {
+                    Cut::Full => 0,
+                    Cut::Partial => 1,
+                };
+                dst.extend_from_slice(&[GS, b'V', m]);
+            }
+            Command::Status(kind) => {
+                dst.extend_from_slice(&[DLE, EOT, kind.into()]);
+                self.pending.push_back(kind);
+            }
+            Command::Raw(bytes) => dst.extend_from_slice(&bytes),
+        }
+        Ok(())
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
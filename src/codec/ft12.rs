@@ -0,0 +1,297 @@
+//! IEC 60870-5 FT1.2 link frame codec
+//!
+//! FT1.2 is the link layer of IEC 60870-5-101 and of many utility RTUs on serial lines.
+//! Frames are one of:
+//!
+//! * the single character `0xE5`, confirming without data,
+//! * fixed length: `0x10`, the control field, the link address, a checksum and `0x16`,
+//! * variable length: `0x68`, the length twice, `0x68`, the control field, the link address,
+//!   the user data, a checksum and `0x16`.
+//!
+//! The checksum is the sum modulo 256 of the control field, address and user data.  The
+//! link address is 0 to 2 bytes long depending on the system, see
+//! [`Ft12Codec::with_address_len`].
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::convert::TryFrom;
+use std::io;
+
+/// The single control character
+pub const SINGLE_CHAR: u8 = 0xe5;
+/// Start of fixed length frames
+pub const START_FIXED: u8 = 0x10;
+/// Start of variable length frames
+pub const START_VARIABLE: u8 = 0x68;
+/// End of fixed and variable length frames
+pub const END: u8 = 0x16;
+
+/// Set in the control field of frames from the primary station
+pub const PRM: u8 = 0x40;
+
+/// Compute the FT1.2 checksum, the sum modulo 256 of `data`
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// A single FT1.2 link frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkFrame {
+    /// The single character `0xE5`, a positive confirmation
+    SingleChar,
+    /// A fixed length frame, without user data
+    Fixed {
+        /// Control field
+        control: u8,
+        /// Link address
+        address: u16,
+    },
+    /// A variable length frame carrying user data
+    Variable {
+        /// Control field
+        control: u8,
+        /// Link address
+        address: u16,
+        /// User data, the ASDU for IEC 60870-5-101
+        data: Bytes,
+    },
+}
+
+impl LinkFrame {
+    /// Returns the control field, `None` for the single character
+    pub fn control(&self) -> Option<u8> {
+        match *self {
+            LinkFrame::SingleChar => None,
+            LinkFrame::Fixed { control, .. } | LinkFrame::Variable { control, .. } => Some(control),
+        }
+    }
+
+    /// Returns the link address, `None` for the single character
+    pub fn address(&self) -> Option<u16> {
+        match *self {
+            LinkFrame::SingleChar => None,
+            LinkFrame::Fixed { address, .. } | LinkFrame::Variable { address, .. } => Some(address),
+        }
+    }
+
+    /// Returns the function code of the control field
+    pub fn function(&self) -> Option<u8> {
+        self.control().map(|control| control & 0x0f)
+    }
+
+    /// Returns `true` if a primary station sent the frame
+    pub fn is_primary(&self) -> bool {
+        self.control().is_some_and(|control| control & PRM != 0)
+    }
+
+    /// Returns `true` for the confirmations of a secondary station: the single character and
+    /// the `ACK` and `NACK` functions
+    pub fn is_confirm(&self) -> bool {
+        match self {
+            LinkFrame::SingleChar => true,
+            frame => !frame.is_primary() && matches!(frame.function(), Some(0) | Some(1)),
+        }
+    }
+}
+
+/// Decoder and encoder for FT1.2 link frames
+///
+/// Bytes before a start character are skipped.  Frames with a bad checksum, mismatched
+/// length fields or a missing end character are handled according to the
+/// [`ChecksumPolicy`], reported as `io::ErrorKind::InvalidData` errors by default.
+#[derive(Debug, Clone)]
+pub struct Ft12Codec {
+    address_len: usize,
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl Ft12Codec {
+    /// Create a codec for one byte link addresses, the most common
+    pub fn new() -> Self {
+        Self::with_address_len(1)
+    }
+
+    /// Create a codec for link addresses of `len` bytes
+    ///
+    /// ## Panics
+    ///
+    /// This function panics if `len` is above 2.
+    pub fn with_address_len(len: usize) -> Self {
+        assert!(len <= 2, "FT1.2 link addresses are at most 2 bytes");
+        Self {
+            address_len: len,
+            checksum_policy: ChecksumPolicy::default(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Returns the length of link addresses
+    pub fn address_len(&self) -> usize {
+        self.address_len
+    }
+
+    /// Set what happens to frames failing their checksum
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to frames failing their checksum
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between frames
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Default for Ft12Codec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ft12Codec {
+    fn next_frame(&mut self, src: &mut BytesMut) -> Option<Result<LinkFrame, Corrupt>> {
+        let start = src
+            .iter()
+            .position(|&b| matches!(b, SINGLE_CHAR | START_FIXED | START_VARIABLE));
+        let start = match start {
+            Some(start) => start,
+            None => {
+                self.skips.skip(src.len());
+                src.clear();
+                return None;
+            }
+        };
+        self.skips.skip(start);
+        src.advance(start);
+
+        let (len, body) = match src[0] {
+            SINGLE_CHAR => {
+                src.advance(1);
+                self.skips.frame();
+                return Some(Ok(LinkFrame::SingleChar));
+            }
+            START_FIXED => (4 + self.address_len, 1..2 + self.address_len),
+            _ => {
+                if src.len() < 4 {
+                    return None;
+                }
+                let user_len = usize::from(src[1]);
+                if src[1] != src[2] || src[3] != START_VARIABLE || user_len < 1 + self.address_len {
+                    return Some(Err(self.corrupt(src.split_to(1), "FT1.2 length mismatch")));
+                }
+                (6 + user_len, 4..4 + user_len)
+            }
+        };
+        if src.len() < len {
+            src.reserve(len - src.len());
+            return None;
+        }
+
+        if src[len - 1] != END {
+            // Not a frame after all, look for the next start after this one
+            return Some(Err(
+                self.corrupt(src.split_to(1), "FT1.2 end character missing")
+            ));
+        }
+        let frame = src.split_to(len);
+        if checksum(&frame[body.clone()]) != frame[len - 2] {
+            return Some(Err(self.corrupt(frame, "FT1.2 checksum mismatch")));
+        }
+
+        let mut fields = &frame[body];
+        let control = fields.get_u8();
+        let address = match self.address_len {
+            0 => 0,
+            1 => u16::from(fields.get_u8()),
+            _ => fields.get_u16_le(),
+        };
+        self.skips.frame();
+        Some(Ok(match frame[0] {
+            START_FIXED => LinkFrame::Fixed { control, address },
+            _ => LinkFrame::Variable {
+                control,
+                address,
+                data: Bytes::copy_from_slice(fields),
+            },
+        }))
+    }
+
+    fn corrupt(&mut self, bytes: BytesMut, message: &str) -> Corrupt {
+        self.skips.corrupt(bytes.len());
+        Corrupt {
+            bytes: bytes.freeze(),
+            error: io::Error::new(io::ErrorKind::InvalidData, message),
+        }
+    }
+}
+
+impl Decoder for Ft12Codec {
+    type Item = LinkFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_frame(src))
+    }
+}
+
+impl Checksummed for Ft12Codec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<LinkFrame>>> {
+        Ok(self.next_frame(src).map(Frame::from))
+    }
+}
+
+impl Encoder<LinkFrame> for Ft12Codec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: LinkFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (control, address, data) = match item {
+            LinkFrame::SingleChar => {
+                dst.put_u8(SINGLE_CHAR);
+                return Ok(());
+            }
+            LinkFrame::Fixed { control, address } => (control, address, None),
+            LinkFrame::Variable {
+                control,
+                address,
+                data,
+            } => (control, address, Some(data)),
+        };
+        let max_address = match self.address_len {
+            0 => 0,
+            1 => 0xff,
+            _ => 0xffff,
+        };
+        if address > max_address {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FT1.2 link address too long",
+            ));
+        }
+
+        let mut body = Vec::with_capacity(3 + data.as_ref().map_or(0, |data| data.len()));
+        body.push(control);
+        body.extend_from_slice(&address.to_le_bytes()[..self.address_len]);
+        match data {
+            None => dst.put_u8(START_FIXED),
+            Some(data) => {
+                body.extend_from_slice(&data);
+                let len = u8::try_from(body.len()).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "FT1.2 user data too long")
+                })?;
+                dst.extend_from_slice(&[START_VARIABLE, len, len, START_VARIABLE]);
+            }
+        }
+        dst.extend_from_slice(&body);
+        dst.put_u8(checksum(&body));
+        dst.put_u8(END);
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+//! ASCII handshake protocol codec
+//!
+//! Paging terminals speaking TAP, and many lab instruments, exchange blocks of text between
+//! `STX` and `ETX` followed by a checksum, each answered by `ACK` to accept it, `NAK` to have
+//! it sent again, or another control character to give up.  [`HandshakeCodec`] encodes
+//! [`Outgoing`] blocks, control characters and text lines, and decodes what comes back into
+//! [`Reply`]s: the control characters of its [`Controls`], and text lines in between.  The
+//! [`Handshake`](crate::handshake::Handshake) layer sends blocks until they are
+//! acknowledged.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::handshake::{HandshakeCodec, Outgoing, Reply};
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = HandshakeCodec::tap();
+//! let mut dst = BytesMut::new();
+//! let block = Outgoing::Block(vec!["123".into(), "ABC".into()]);
+//! codec.encode(block, &mut dst).unwrap();
+//! assert_eq!(&dst[..], b"\x02123\rABC\r\x0317;\r");
+//!
+//! let mut src = BytesMut::from(&b"211 Page(s) Sent OK\r\x06\r"[..]);
+//! assert_eq!(codec.decode(&mut src).unwrap(), Some(Reply::Text("211 Page(s) Sent OK".into())));
+//! assert_eq!(codec.decode(&mut src).unwrap(), Some(Reply::Ack));
+//! ```
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::io;
+
+/// Start of text
+pub const STX: u8 = 0x02;
+/// End of text
+pub const ETX: u8 = 0x03;
+/// End of transmission
+pub const EOT: u8 = 0x04;
+/// Enquiry
+pub const ENQ: u8 = 0x05;
+/// Acknowledge
+pub const ACK: u8 = 0x06;
+/// Negative acknowledge
+pub const NAK: u8 = 0x15;
+/// Record separator, used by TAP to abandon a block
+pub const RS: u8 = 0x1e;
+/// Escape, followed by `EOT` to disconnect in TAP
+pub const ESC: u8 = 0x1b;
+
+/// Lines longer than this are split, by default
+pub const DEFAULT_MAX_LENGTH: usize = 1024;
+
+/// The control characters of a protocol, the ASCII ones by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Controls {
+    /// Starts a block
+    pub stx: u8,
+    /// Ends a block, before its checksum
+    pub etx: u8,
+    /// Accepts a block
+    pub ack: u8,
+    /// Asks for a block again
+    pub nak: u8,
+    /// Asks whether the other end is ready
+    pub enq: u8,
+    /// Ends the exchange
+    pub eot: u8,
+    /// Gives up on a block without ending the exchange
+    pub abandon: u8,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            stx: STX,
+            etx: ETX,
+            ack: ACK,
+            nak: NAK,
+            enq: ENQ,
+            eot: EOT,
+            abandon: RS,
+        }
+    }
+}
+
+/// How the checksum following a block's `ETX` is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumStyle {
+    /// No checksum
+    None,
+    /// TAP: the low 12 bits of the sum from `STX` through `ETX`, as three characters
+    /// `0x30` plus each nibble
+    Tap,
+    /// The low byte of the sum from `STX` through `ETX`, as two hex digits
+    Sum8,
+    /// The XOR of the bytes after `STX` through `ETX`, as two hex digits, like ASTM
+    /// instruments' LRC
+    Xor,
+}
+
+impl ChecksumStyle {
+    /// Compute the checksum characters of `block`, from its `STX` through its `ETX`
+    pub fn checksum(&self, block: &[u8]) -> Vec<u8> {
+        match self {
+            ChecksumStyle::None => Vec::new(),
+            ChecksumStyle::Tap => {
+                let sum = block.iter().map(|&b| u32::from(b)).sum::<u32>() & 0xfff;
+                [8, 4, 0]
+                    .iter()
+                    .map(|shift| 0x30 + ((sum >> shift) & 0xf) as u8)
+                    .collect()
+            }
+            ChecksumStyle::Sum8 => {
+                let sum = block.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+                format!("{:02X}", sum).into_bytes()
+            }
+            ChecksumStyle::Xor => {
+                let lrc = block.iter().skip(1).fold(0, |lrc, &b| lrc ^ b);
+                format!("{:02X}", lrc).into_bytes()
+            }
+        }
+    }
+}
+
+/// What is sent to the other end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outgoing {
+    /// A block of fields, each followed by `\r`, between `STX` and `ETX`, then the checksum
+    /// and `\r`
+    Block(Vec<String>),
+    /// A control character alone
+    Control(u8),
+    /// A line of text, followed by `\r`
+    Text(String),
+}
+
+/// What the other end sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// The block was accepted
+    Ack,
+    /// The block should be sent again
+    Nak,
+    /// The other end asks whether this one is ready
+    Enq,
+    /// The other end ended the exchange
+    Eot,
+    /// The block was given up on
+    Abandon,
+    /// `ESC EOT`, the other end is disconnecting
+    Disconnect,
+    /// A line of text, or a prompt
+    Text(String),
+}
+
+/// Decoder and encoder for ASCII handshake protocols, see the [module](self) docs
+///
+/// Replies are split at `\r` and `\n` and at control characters, blank lines being
+/// skipped.  Prompts, like TAP's `ID=`, come without a line end and are recognized by their
+/// text.
+#[derive(Debug, Clone)]
+pub struct HandshakeCodec {
+    controls: Controls,
+    checksum: ChecksumStyle,
+    prompts: Vec<String>,
+    max_length: usize,
+}
+
+impl HandshakeCodec {
+    /// Create a codec with the given control characters and checksum style
+    pub fn new(controls: Controls, checksum: ChecksumStyle) -> Self {
+        Self {
+            controls,
+            checksum,
+            prompts: Vec::new(),
+            max_length: DEFAULT_MAX_LENGTH,
+        }
+    }
+
+    /// Create a codec for the Telocator Alphanumeric Protocol, recognizing its `ID=` prompt
+    pub fn tap() -> Self {
+        let mut codec = Self::new(Controls::default(), ChecksumStyle::Tap);
+        codec.add_prompt("ID=");
+        codec
+    }
+
+    /// Emit `prompt` as a [`Reply::Text`] as soon as it arrives, without waiting for a line
+    /// end
+    pub fn add_prompt(&mut self, prompt: impl Into<String>) {
+        self.prompts.push(prompt.into());
+    }
+
+    /// Split lines longer than `max_length` bytes, [`DEFAULT_MAX_LENGTH`] by default
+    pub fn set_max_length(&mut self, max_length: usize) {
+        self.max_length = max_length.max(1);
+    }
+
+    /// Returns the control characters
+    pub fn controls(&self) -> Controls {
+        self.controls
+    }
+
+    /// Returns the checksum style
+    pub fn checksum_style(&self) -> ChecksumStyle {
+        self.checksum
+    }
+
+    fn control(&self, byte: u8) -> Option<Reply> {
+        let c = &self.controls;
+        Some(match byte {
+            _ if byte == c.ack => Reply::Ack,
+            _ if byte == c.nak => Reply::Nak,
+            _ if byte == c.enq => Reply::Enq,
+            _ if byte == c.eot => Reply::Eot,
+            _ if byte == c.abandon => Reply::Abandon,
+            _ => return None,
+        })
+    }
+}
+
+impl Default for HandshakeCodec {
+    fn default() -> Self {
+        Self::new(Controls::default(), ChecksumStyle::None)
+    }
+}
+
+fn text(bytes: &[u8]) -> Reply {
+    Reply::Text(String::from_utf8_lossy(bytes).into_owned())
+}
+
+impl Decoder for HandshakeCodec {
+    type Item = Reply;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let mut end = None;
+            for (i, &byte) in src.iter().enumerate() {
+                if byte == b'\r' || byte == b'\n' || self.control(byte).is_some() {
+                    end = Some(i);
+                    break;
+                }
+                if byte == ESC {
+                    match src.get(i + 1) {
+                        Some(&next) if next == self.controls.eot => {
+                            end = Some(i);
+                            break;
+                        }
+                        Some(_) => {}
+                        None => return Ok(None),
+                    }
+                }
+            }
+            let end = match end {
+                Some(end) => end,
+                None if src.len() >= self.max_length => {
+                    let line = src.split_to(self.max_length);
+                    return Ok(Some(text(&line)));
+                }
+                None if self.prompts.iter().any(|p| p.as_bytes() == &src[..]) => {
+                    let prompt = src.split();
+                    return Ok(Some(text(&prompt)));
+                }
+                None => return Ok(None),
+            };
+
+            // The text before a control character goes first
+            if end > 0 {
+                let line = src.split_to(end);
+                return Ok(Some(text(&line)));
+            }
+            let byte = src[0];
+            if byte == ESC {
+                src.advance(2);
+                return Ok(Some(Reply::Disconnect));
+            }
+            src.advance(1);
+            if let Some(reply) = self.control(byte) {
+                return Ok(Some(reply));
+            }
+        }
+    }
+}
+
+impl Encoder<Outgoing> for HandshakeCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Outgoing, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match item {
+            Outgoing::Block(fields) => {
+                let mut block = vec![self.controls.stx];
+                for field in fields {
+                    block.extend_from_slice(field.as_bytes());
+                    block.push(b'\r');
+                }
+                block.push(self.controls.etx);
+                let checksum = self.checksum.checksum(&block);
+                dst.reserve(block.len() + checksum.len() + 1);
+                dst.put_slice(&block);
+                dst.put_slice(&checksum);
+                dst.put_u8(b'\r');
+            }
+            Outgoing::Control(byte) => dst.put_u8(byte),
+            Outgoing::Text(line) => {
+                dst.reserve(line.len() + 1);
+                dst.put_slice(line.as_bytes());
+                dst.put_u8(b'\r');
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+//! Embedded log line codec
+//!
+//! Firmware logs over a console port in a handful of formats.  [`LogLineCodec`] splits the
+//! lines and parses them into [`LogRecord`]s, so a collector can filter by severity or
+//! module without matching strings itself.  Recognized are:
+//!
+//! * Zephyr: `[00:00:01.234,567] <inf> module: message`, the timestamp being optional,
+//! * ESP-IDF: `I (1234) tag: message`,
+//! * syslog: `<13>Oct 16 12:00:00 host app[42]: message` and its RFC 5424 form,
+//!   `<13>1 2024-10-16T12:00:00Z host app 42 - - message`.
+//!
+//! ANSI color sequences are stripped first.  Other lines are kept as records with the whole
+//! line as message and no level.  Logs only flow from the device, so there is no encoder.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::log_line::{Level, LogLineCodec};
+//! use tokio_util::codec::Decoder;
+//!
+//! let mut src = BytesMut::from(&b"\x1b[0;33mW (5123) wifi: beacon timeout\x1b[0m\r\n"[..]);
+//! let record = LogLineCodec::new().decode(&mut src).unwrap().unwrap();
+//! assert_eq!(record.level, Some(Level::Warning));
+//! assert_eq!(record.module.as_deref(), Some("wifi"));
+//! assert_eq!(record.message, "beacon timeout");
+//! ```
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::Decoder;
+
+use std::{fmt, io};
+
+/// Lines longer than this are split, by default
+pub const DEFAULT_MAX_LENGTH: usize = 1024;
+
+/// Severity of a log record, ordered from the least to the most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// ESP-IDF verbose
+    Verbose,
+    /// Debug
+    Debug,
+    /// Informational, syslog notices included
+    Info,
+    /// Warning
+    Warning,
+    /// Error, the syslog critical, alert and emergency severities included
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Verbose => "verbose",
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        })
+    }
+}
+
+/// Format a [`LogRecord`] was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Zephyr logging subsystem
+    Zephyr,
+    /// ESP-IDF `esp_log`
+    EspIdf,
+    /// syslog, RFC 3164 or 5424
+    Syslog,
+    /// Not recognized
+    Unknown,
+}
+
+/// A parsed log line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// Format of the line
+    pub format: LogFormat,
+    /// Severity, `None` for unrecognized lines
+    pub level: Option<Level>,
+    /// Timestamp as written by the device, which may be an uptime
+    pub timestamp: Option<String>,
+    /// Module, tag or application name
+    pub module: Option<String>,
+    /// The message
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Parse a single line, without its line ending
+    pub fn parse(line: &str) -> Self {
+        let line = strip_ansi(line);
+        let line = line.trim_end();
+        parse_zephyr(line)
+            .or_else(|| parse_esp_idf(line))
+            .or_else(|| parse_syslog(line))
+            .unwrap_or_else(|| LogRecord {
+                format: LogFormat::Unknown,
+                level: None,
+                timestamp: None,
+                module: None,
+                message: line.to_owned(),
+            })
+    }
+
+    /// Returns `true` if the record is at least as severe as `level`
+    pub fn is_at_least(&self, level: Level) -> bool {
+        self.level.is_some_and(|own| own >= level)
+    }
+}
+
+/// Remove the `ESC [ ... letter` sequences coloring the output
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.clone().next() == Some('[') {
+                for c in chars.by_ref().skip(1) {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Split `module: message`, the module being a single word
+fn split_module(rest: &str) -> (Option<String>, String) {
+    match rest.find(": ") {
+        Some(end) if !rest[..end].contains(' ') && end > 0 => {
+            (Some(rest[..end].to_owned()), rest[end + 2..].to_owned())
+        }
+        _ => (None, rest.to_owned()),
+    }
+}
+
+fn parse_zephyr(line: &str) -> Option<LogRecord> {
+    let (timestamp, rest) = match line.strip_prefix('[') {
+        Some(rest) => {
+            let end = rest.find("] ")?;
+            (Some(rest[..end].to_owned()), &rest[end + 2..])
+        }
+        None => (None, line),
+    };
+    let level = match rest.get(..6)? {
+        "<err> " => Level::Error,
+        "<wrn> " => Level::Warning,
+        "<inf> " => Level::Info,
+        "<dbg> " => Level::Debug,
+        _ => return None,
+    };
+    let (module, message) = split_module(&rest[6..]);
+    Some(LogRecord {
+        format: LogFormat::Zephyr,
+        level: Some(level),
+        timestamp,
+        module,
+        message,
+    })
+}
+
+fn parse_esp_idf(line: &str) -> Option<LogRecord> {
+    let mut chars = line.chars();
+    let level = match chars.next()? {
+        'E' => Level::Error,
+        'W' => Level::Warning,
+        'I' => Level::Info,
+        'D' => Level::Debug,
+        'V' => Level::Verbose,
+        _ => return None,
+    };
+    let rest = line[1..].strip_prefix(" (")?;
+    let end = rest.find(") ")?;
+    let timestamp = &rest[..end];
+    // Milliseconds since boot, or the system time with CONFIG_LOG_TIMESTAMP_SOURCE_SYSTEM
+    if timestamp.is_empty()
+        || !timestamp
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ':' || c == '.')
+    {
+        return None;
+    }
+    let (module, message) = split_module(&rest[end + 2..]);
+    Some(LogRecord {
+        format: LogFormat::EspIdf,
+        level: Some(level),
+        timestamp: Some(timestamp.to_owned()),
+        module,
+        message,
+    })
+}
+
+fn parse_syslog(line: &str) -> Option<LogRecord> {
+    let rest = line.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let priority: u8 = rest[..end].parse().ok()?;
+    let level = match priority & 7 {
+        0..=3 => Level::Error,
+        4 => Level::Warning,
+        5 | 6 => Level::Info,
+        _ => Level::Debug,
+    };
+    let rest = &rest[end + 1..];
+
+    let (timestamp, module, message) = match rest.strip_prefix("1 ") {
+        // VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+        Some(rest) => {
+            let mut fields = rest.splitn(6, ' ');
+            let timestamp = fields.next()?;
+            let _host = fields.next()?;
+            let app = fields.next()?;
+            let _procid = fields.next()?;
+            let _msgid = fields.next()?;
+            let rest = fields.next().unwrap_or("");
+            // Structured data is `-` or bracketed elements
+            let message = match rest.strip_prefix("- ") {
+                Some(message) => message,
+                None if rest == "-" => "",
+                None => rest.rfind("] ").map_or(rest, |end| &rest[end + 2..]),
+            };
+            let nil = |field: &str| Some(field.to_owned()).filter(|field| field != "-");
+            (nil(timestamp), nil(app), message.to_owned())
+        }
+        // Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG
+        None => {
+            let timestamp = rest
+                .get(..15)
+                .filter(|t| t.as_bytes().get(12) == Some(&b':'));
+            match timestamp {
+                Some(timestamp) => {
+                    let rest = rest[15..].trim_start();
+                    let rest = rest.split_once(' ').map_or(rest, |(_host, rest)| rest);
+                    let (tag, message) = split_module(rest);
+                    let tag = tag.map(|tag| tag.split('[').next().unwrap_or("").to_owned());
+                    (Some(timestamp.to_owned()), tag, message)
+                }
+                None => {
+                    let (tag, message) = split_module(rest);
+                    (None, tag, message)
+                }
+            }
+        }
+    };
+    Some(LogRecord {
+        format: LogFormat::Syslog,
+        level: Some(level),
+        timestamp,
+        module,
+        message,
+    })
+}
+
+/// Decoder of [`LogRecord`]s, one per line
+///
+/// Lines end with `\n`, a preceding `\r` being dropped.  Invalid UTF-8 is replaced, and
+/// lines longer than the maximum are split.
+#[derive(Debug, Clone)]
+pub struct LogLineCodec {
+    max_length: usize,
+}
+
+impl LogLineCodec {
+    /// Create a codec using [`DEFAULT_MAX_LENGTH`]
+    pub fn new() -> Self {
+        Self::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Create a codec splitting lines longer than `max_length` bytes
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            max_length: max_length.max(1),
+        }
+    }
+
+    /// Returns the longest line
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+}
+
+impl Default for LogLineCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for LogLineCodec {
+    type Item = LogRecord;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let (len, skip) = match src.iter().position(|&b| b == b'\n') {
+                Some(end) => (end, 1),
+                None if src.len() >= self.max_length => (self.max_length, 0),
+                None => return Ok(None),
+            };
+            let line = src.split_to(len.min(self.max_length));
+            if len <= self.max_length {
+                src.advance(skip);
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\r');
+            // Blank lines between records carry nothing
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Ok(Some(LogRecord::parse(line)));
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(record) => Ok(Some(record)),
+            None if src.iter().all(|b| b.is_ascii_whitespace()) => {
+                src.clear();
+                Ok(None)
+            }
+            None => {
+                let line = src.split();
+                Ok(Some(LogRecord::parse(&String::from_utf8_lossy(&line))))
+            }
+        }
+    }
+}
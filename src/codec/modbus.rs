@@ -0,0 +1,443 @@
+//! Modbus RTU and ASCII frame codecs
+//!
+//! RTU frames are the slave address, the function code, the data and a little-endian
+//! CRC-16.  They carry no length: a frame ends with a silence of 3.5 character times, so the
+//! codec is a [`TimedDecoder`] meant for [`TimedFramed`](crate::frame::TimedFramed).
+//! [`ModbusRtuMaster`](crate::modbus::ModbusRtuMaster) builds on it.
+//!
+//! ASCII frames carry the same fields and an LRC instead of the CRC, all written as
+//! uppercase hex digits between a `:` and `\r\n`, so [`ModbusAsciiCodec`] is a plain
+//! decoder.  Older PLCs and some converters only speak ASCII.
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame, TimedDecoder};
+use super::{ResyncStats, SkipTracker, Timing};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::time::Duration;
+use std::{error, fmt, io};
+
+/// Address, function code and CRC
+const MIN_LEN: usize = 4;
+/// Longest frame allowed by the specification
+pub const MAX_LEN: usize = 256;
+
+/// Silence ending a frame at any baud rate above 19200, fixed by the specification
+const MIN_SILENCE: Duration = Duration::from_micros(1750);
+
+/// Returns the silence of 3.5 character times ending a frame
+pub fn frame_silence(char_time: Duration) -> Duration {
+    (char_time * 7 / 2).max(MIN_SILENCE)
+}
+
+/// Longest ASCII frame, `:` and `\r\n` included
+pub const MAX_ASCII_LEN: usize = 513;
+
+/// Compute the CRC-16/MODBUS of `data`
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xffff, |crc, &byte| {
+        (0..8).fold(crc ^ u16::from(byte), |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xa001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Compute the LRC of an ASCII frame, the two's complement of the sum of `data`
+pub fn lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+        .wrapping_neg()
+}
+
+/// A single RTU or ASCII frame, request or response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtuFrame {
+    /// Slave address, 0 for broadcasts
+    pub address: u8,
+    /// Function code, with the high bit set in exception responses
+    pub function: u8,
+    /// Data following the function code
+    pub data: Bytes,
+}
+
+impl RtuFrame {
+    /// Create a new frame
+    pub fn new(address: u8, function: u8, data: impl Into<Bytes>) -> Self {
+        Self {
+            address,
+            function,
+            data: data.into(),
+        }
+    }
+
+    /// Returns the exception of an exception response
+    pub fn exception(&self) -> Option<Exception> {
+        if self.function & 0x80 == 0 {
+            return None;
+        }
+        self.data.first().map(|&code| Exception::from(code))
+    }
+
+    /// Returns the length of the encoded frame
+    pub fn encoded_len(&self) -> usize {
+        MIN_LEN + self.data.len()
+    }
+}
+
+/// Exception code of a Modbus exception response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    /// The function code isn't supported
+    IllegalFunction,
+    /// The register or coil address isn't available
+    IllegalDataAddress,
+    /// A value in the request isn't allowed
+    IllegalDataValue,
+    /// The slave failed performing the request
+    ServerDeviceFailure,
+    /// The request was accepted but takes long to process
+    Acknowledge,
+    /// The slave is busy with a long running request
+    ServerDeviceBusy,
+    /// The slave found a parity error in its memory
+    MemoryParityError,
+    /// A gateway couldn't reach the target
+    GatewayPathUnavailable,
+    /// The target behind a gateway didn't respond
+    GatewayTargetFailedToRespond,
+    /// Any other code
+    Other(u8),
+}
+
+impl Exception {
+    /// Returns the exception code
+    pub fn code(self) -> u8 {
+        match self {
+            Exception::IllegalFunction => 0x01,
+            Exception::IllegalDataAddress => 0x02,
+            Exception::IllegalDataValue => 0x03,
+            Exception::ServerDeviceFailure => 0x04,
+            Exception::Acknowledge => 0x05,
+            Exception::ServerDeviceBusy => 0x06,
+            Exception::MemoryParityError => 0x08,
+            Exception::GatewayPathUnavailable => 0x0a,
+            Exception::GatewayTargetFailedToRespond => 0x0b,
+            Exception::Other(code) => code,
+        }
+    }
+}
+
+impl From<u8> for Exception {
+    fn from(code: u8) -> Self {
+        match code {
+            0x01 => Exception::IllegalFunction,
+            0x02 => Exception::IllegalDataAddress,
+            0x03 => Exception::IllegalDataValue,
+            0x04 => Exception::ServerDeviceFailure,
+            0x05 => Exception::Acknowledge,
+            0x06 => Exception::ServerDeviceBusy,
+            0x08 => Exception::MemoryParityError,
+            0x0a => Exception::GatewayPathUnavailable,
+            0x0b => Exception::GatewayTargetFailedToRespond,
+            code => Exception::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Exception::IllegalFunction => "illegal function",
+            Exception::IllegalDataAddress => "illegal data address",
+            Exception::IllegalDataValue => "illegal data value",
+            Exception::ServerDeviceFailure => "server device failure",
+            Exception::Acknowledge => "acknowledge",
+            Exception::ServerDeviceBusy => "server device busy",
+            Exception::MemoryParityError => "memory parity error",
+            Exception::GatewayPathUnavailable => "gateway path unavailable",
+            Exception::GatewayTargetFailedToRespond => "gateway target failed to respond",
+            Exception::Other(code) => return write!(f, "Modbus exception {:#04x}", code),
+        };
+        f.write_str(text)
+    }
+}
+
+impl error::Error for Exception {}
+
+/// Decoder and encoder for RTU frames
+///
+/// Frames are cut at silences of [3.5 character times](frame_silence), or at the end of the
+/// stream.  Frames failing their CRC or too short are handled according to the
+/// [`ChecksumPolicy`], reported as `io::ErrorKind::InvalidData` errors by default.  Without
+/// timing, plain `decode` never completes a frame.
+#[derive(Debug, Clone, Default)]
+pub struct ModbusRtuCodec {
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl ModbusRtuCodec {
+    /// Create a codec
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what happens to frames failing their CRC
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to frames failing their CRC
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the corrupt frames
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+
+    /// Returns the length of the complete frame at the start of `src`, if any
+    fn frame_len(src: &BytesMut, timing: &Timing) -> Option<usize> {
+        let gap = timing.gap?;
+        if gap < frame_silence(timing.char_time) {
+            return None;
+        }
+        // The bytes before the silence form a frame, the new ones start the next
+        let end = src.len() - timing.new_bytes.min(src.len());
+        Some(end).filter(|&end| end > 0)
+    }
+
+    fn parse(&mut self, mut frame: BytesMut) -> Result<RtuFrame, Corrupt> {
+        let len = frame.len();
+        let valid = (MIN_LEN..=MAX_LEN).contains(&len)
+            && crc16(&frame[..len - 2]).to_le_bytes() == frame[len - 2..];
+        if !valid {
+            self.skips.corrupt(len);
+            return Err(Corrupt {
+                bytes: frame.freeze(),
+                error: io::Error::new(io::ErrorKind::InvalidData, "Modbus RTU CRC mismatch"),
+            });
+        }
+        self.skips.frame();
+        let address = frame.get_u8();
+        let function = frame.get_u8();
+        frame.truncate(len - MIN_LEN);
+        Ok(RtuFrame {
+            address,
+            function,
+            data: frame.freeze(),
+        })
+    }
+}
+
+impl Decoder for ModbusRtuCodec {
+    type Item = RtuFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, _src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(None)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let mut frame = Some(src.split());
+        decode_with_policy(self.checksum_policy, || {
+            let frame = frame.take().filter(|frame| !frame.is_empty())?;
+            Some(self.parse(frame))
+        })
+    }
+}
+
+impl TimedDecoder for ModbusRtuCodec {
+    fn decode_timed(
+        &mut self,
+        src: &mut BytesMut,
+        timing: &Timing,
+    ) -> Result<Option<Self::Item>, Self::Error> {
+        let mut len = Self::frame_len(src, timing);
+        decode_with_policy(self.checksum_policy, || {
+            let frame = src.split_to(len.take()?);
+            Some(self.parse(frame))
+        })
+    }
+
+    fn idle_timeout(&self, char_time: Duration) -> Option<Duration> {
+        Some(frame_silence(char_time))
+    }
+}
+
+impl Encoder<RtuFrame> for ModbusRtuCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: RtuFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.encoded_len() > MAX_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Modbus RTU frame too long",
+            ));
+        }
+        dst.reserve(item.encoded_len());
+        let start = dst.len();
+        dst.put_u8(item.address);
+        dst.put_u8(item.function);
+        dst.extend_from_slice(&item.data);
+        let crc = crc16(&dst[start..]);
+        dst.put_u16_le(crc);
+        Ok(())
+    }
+}
+
+/// Decoder and encoder for ASCII frames
+///
+/// Bytes before a `:` are skipped, and a `:` within a frame starts a new one.  Frames with
+/// an LRC mismatch, bad hex digits or too long are handled according to the
+/// [`ChecksumPolicy`], reported as `io::ErrorKind::InvalidData` errors by default.
+#[derive(Debug, Clone, Default)]
+pub struct ModbusAsciiCodec {
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl ModbusAsciiCodec {
+    /// Create a codec
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what happens to frames failing their LRC
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to frames failing their LRC
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between frames
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+
+    fn next_frame(&mut self, src: &mut BytesMut) -> Option<Result<RtuFrame, Corrupt>> {
+        loop {
+            match src.iter().position(|&b| b == b':') {
+                Some(start) => {
+                    self.skips.skip(start);
+                    src.advance(start);
+                }
+                None => {
+                    self.skips.skip(src.len());
+                    src.clear();
+                    return None;
+                }
+            }
+
+            let end = src[1..]
+                .iter()
+                .position(|&b| b == b':' || b == b'\n')
+                .map(|end| end + 1);
+            let end = match end {
+                // Cut short by the start of the next frame
+                Some(end) if src[end] == b':' => {
+                    self.skips.corrupt(end);
+                    src.advance(end);
+                    continue;
+                }
+                Some(end) if end < MAX_ASCII_LEN => end,
+                Some(end) => return Some(Err(self.corrupt(src.split_to(end + 1), "too long"))),
+                None if src.len() >= MAX_ASCII_LEN => {
+                    return Some(Err(self.corrupt(src.split_to(1), "too long")))
+                }
+                None => return None,
+            };
+
+            let line = src.split_to(end + 1);
+            let hex = line[1..end].strip_suffix(b"\r").unwrap_or(&line[1..end]);
+            let bytes = match decode_hex(hex) {
+                Some(bytes) if bytes.len() >= 3 => bytes,
+                _ => return Some(Err(self.corrupt(line, "malformed"))),
+            };
+            let (frame, check) = bytes.split_at(bytes.len() - 1);
+            if lrc(frame) != check[0] {
+                return Some(Err(self.corrupt(line, "LRC mismatch")));
+            }
+            self.skips.frame();
+            return Some(Ok(RtuFrame {
+                address: frame[0],
+                function: frame[1],
+                data: Bytes::copy_from_slice(&frame[2..]),
+            }));
+        }
+    }
+
+    fn corrupt(&mut self, bytes: BytesMut, problem: &str) -> Corrupt {
+        self.skips.corrupt(bytes.len());
+        Corrupt {
+            bytes: bytes.freeze(),
+            error: io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Modbus ASCII frame {}", problem),
+            ),
+        }
+    }
+}
+
+/// Decode pairs of hex digits, `None` if there's an odd count or another character
+fn decode_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.len() % 2 == 1 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| {
+            let digits = std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(digits, 16).ok()
+        })
+        .collect()
+}
+
+impl Decoder for ModbusAsciiCodec {
+    type Item = RtuFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_frame(src))
+    }
+}
+
+impl Checksummed for ModbusAsciiCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<RtuFrame>>> {
+        Ok(self.next_frame(src).map(Frame::from))
+    }
+}
+
+impl Encoder<RtuFrame> for ModbusAsciiCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: RtuFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let len = 1 + 2 * (item.data.len() + 3) + 2;
+        if len > MAX_ASCII_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Modbus ASCII frame too long",
+            ));
+        }
+        let mut bytes = Vec::with_capacity(item.data.len() + 3);
+        bytes.push(item.address);
+        bytes.push(item.function);
+        bytes.extend_from_slice(&item.data);
+        bytes.push(lrc(&bytes));
+
+        dst.reserve(len);
+        dst.put_u8(b':');
+        for byte in bytes {
+            dst.extend_from_slice(format!("{:02X}", byte).as_bytes());
+        }
+        dst.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+//! BACnet MS/TP frame codec
+//!
+//! MS/TP frames are the preamble `0x55 0xFF`, the frame type, the destination and source
+//! MAC addresses, a big-endian `u16` data length and a CRC-8 of the header, followed by the
+//! data and its CRC-16 when the length isn't zero.  Both CRCs are sent inverted, the CRC-16
+//! least significant byte first.
+//!
+//! The token passing itself is left to the application, [`MacTiming`] has the timeouts it
+//! is built on.
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::io;
+use std::time::Duration;
+
+/// Preamble starting every frame
+pub const PREAMBLE: [u8; 2] = [0x55, 0xff];
+/// Destination address of broadcasts
+pub const BROADCAST: u8 = 0xff;
+
+/// Preamble, type, addresses, length and header CRC
+const HEADER_LEN: usize = 8;
+/// Data CRC
+const CRC_LEN: usize = 2;
+
+/// Longest data of a frame without extended encoding
+pub const DEFAULT_MAX_DATA: usize = 501;
+
+/// Update the header CRC-8 `crc` with `byte`
+fn header_crc_step(crc: u8, byte: u8) -> u8 {
+    let crc = u16::from(crc ^ byte);
+    let crc = crc
+        ^ (crc << 1)
+        ^ (crc << 2)
+        ^ (crc << 3)
+        ^ (crc << 4)
+        ^ (crc << 5)
+        ^ (crc << 6)
+        ^ (crc << 7);
+    ((crc & 0xfe) ^ ((crc >> 8) & 1)) as u8
+}
+
+/// Update the data CRC-16 `crc` with `byte`
+fn data_crc_step(crc: u16, byte: u8) -> u16 {
+    let low = (crc & 0xff) ^ u16::from(byte);
+    (crc >> 8)
+        ^ (low << 8)
+        ^ (low << 3)
+        ^ (low << 12)
+        ^ (low >> 4)
+        ^ (low & 0x0f)
+        ^ ((low & 0x0f) << 7)
+}
+
+/// Compute the header CRC of the frame type, addresses and length, as sent
+pub fn header_crc(header: &[u8]) -> u8 {
+    !header
+        .iter()
+        .fold(0xff, |crc, &byte| header_crc_step(crc, byte))
+}
+
+/// Compute the data CRC, as sent
+pub fn data_crc(data: &[u8]) -> u16 {
+    !data
+        .iter()
+        .fold(0xffff, |crc, &byte| data_crc_step(crc, byte))
+}
+
+/// Type of an MS/TP frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    /// Passes the token to the destination
+    Token,
+    /// Looks for a master at the destination
+    PollForMaster,
+    /// Answers a poll for master
+    ReplyToPollForMaster,
+    /// Asks for the data to be echoed
+    TestRequest,
+    /// Echoes the data of a test request
+    TestResponse,
+    /// BACnet data expecting a reply
+    DataExpectingReply,
+    /// BACnet data not expecting a reply
+    DataNotExpectingReply,
+    /// The reply will come later
+    ReplyPostponed,
+    /// Any other type, including the extended ones from 32 on
+    Other(u8),
+}
+
+impl From<u8> for FrameType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => FrameType::Token,
+            1 => FrameType::PollForMaster,
+            2 => FrameType::ReplyToPollForMaster,
+            3 => FrameType::TestRequest,
+            4 => FrameType::TestResponse,
+            5 => FrameType::DataExpectingReply,
+            6 => FrameType::DataNotExpectingReply,
+            7 => FrameType::ReplyPostponed,
+            value => FrameType::Other(value),
+        }
+    }
+}
+
+impl From<FrameType> for u8 {
+    fn from(frame_type: FrameType) -> Self {
+        match frame_type {
+            FrameType::Token => 0,
+            FrameType::PollForMaster => 1,
+            FrameType::ReplyToPollForMaster => 2,
+            FrameType::TestRequest => 3,
+            FrameType::TestResponse => 4,
+            FrameType::DataExpectingReply => 5,
+            FrameType::DataNotExpectingReply => 6,
+            FrameType::ReplyPostponed => 7,
+            FrameType::Other(value) => value,
+        }
+    }
+}
+
+/// A single MS/TP frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MstpFrame {
+    /// Frame type
+    pub frame_type: FrameType,
+    /// Destination MAC address, [`BROADCAST`] for all stations
+    pub destination: u8,
+    /// Source MAC address
+    pub source: u8,
+    /// Data, empty for the frames of the token passing
+    pub data: Bytes,
+}
+
+impl MstpFrame {
+    /// Create a new frame
+    pub fn new(frame_type: FrameType, destination: u8, source: u8, data: impl Into<Bytes>) -> Self {
+        Self {
+            frame_type,
+            destination,
+            source,
+            data: data.into(),
+        }
+    }
+
+    /// Create a token frame passing the token from `source` to `destination`
+    pub fn token(destination: u8, source: u8) -> Self {
+        Self::new(FrameType::Token, destination, source, Bytes::new())
+    }
+
+    /// Returns the length of the encoded frame
+    pub fn encoded_len(&self) -> usize {
+        match self.data.len() {
+            0 => HEADER_LEN,
+            len => HEADER_LEN + len + CRC_LEN,
+        }
+    }
+}
+
+/// Decoder and encoder for MS/TP frames
+///
+/// Bytes before a preamble are skipped.  Frames failing either CRC or longer than the
+/// maximum are handled according to the [`ChecksumPolicy`], reported as
+/// `io::ErrorKind::InvalidData` errors by default.
+#[derive(Debug, Clone)]
+pub struct MstpCodec {
+    max_data: usize,
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl MstpCodec {
+    /// Create a codec using [`DEFAULT_MAX_DATA`]
+    pub fn new() -> Self {
+        Self::with_max_data(DEFAULT_MAX_DATA)
+    }
+
+    /// Create a codec treating frames with more than `max_data` bytes of data as corrupt
+    pub fn with_max_data(max_data: usize) -> Self {
+        Self {
+            max_data,
+            checksum_policy: ChecksumPolicy::default(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Set what happens to frames failing their CRCs
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to frames failing their CRCs
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between frames
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Default for MstpCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MstpCodec {
+    fn next_frame(&mut self, src: &mut BytesMut) -> Option<Result<MstpFrame, Corrupt>> {
+        match src.windows(2).position(|w| w == PREAMBLE) {
+            Some(start) => {
+                self.skips.skip(start);
+                src.advance(start);
+            }
+            None => {
+                // Keep a trailing first preamble byte, the second may be on its way
+                let keep = usize::from(src.last() == Some(&PREAMBLE[0]));
+                let len = src.len();
+                self.skips.skip(len - keep);
+                src.advance(len - keep);
+                return None;
+            }
+        }
+
+        if src.len() < HEADER_LEN {
+            return None;
+        }
+        let data_len = usize::from(u16::from_be_bytes([src[5], src[6]]));
+        if header_crc(&src[2..7]) != src[7] {
+            // A header can't be trusted, look for the next preamble right after this one
+            return Some(Err(
+                self.corrupt(src.split_to(2), "MS/TP header CRC mismatch")
+            ));
+        }
+        if data_len > self.max_data {
+            return Some(Err(
+                self.corrupt(src.split_to(HEADER_LEN), "MS/TP frame too long")
+            ));
+        }
+
+        let frame_len = match data_len {
+            0 => HEADER_LEN,
+            len => HEADER_LEN + len + CRC_LEN,
+        };
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return None;
+        }
+        let mut frame = src.split_to(frame_len);
+        if data_len > 0 {
+            let data = &frame[HEADER_LEN..HEADER_LEN + data_len];
+            let crc = u16::from_le_bytes([frame[frame_len - 2], frame[frame_len - 1]]);
+            if data_crc(data) != crc {
+                return Some(Err(self.corrupt(frame, "MS/TP data CRC mismatch")));
+            }
+        }
+
+        let frame_type = FrameType::from(frame[2]);
+        let destination = frame[3];
+        let source = frame[4];
+        frame.advance(HEADER_LEN);
+        frame.truncate(data_len);
+        self.skips.frame();
+        Some(Ok(MstpFrame {
+            frame_type,
+            destination,
+            source,
+            data: frame.freeze(),
+        }))
+    }
+
+    fn corrupt(&mut self, bytes: BytesMut, message: &str) -> Corrupt {
+        self.skips.corrupt(bytes.len());
+        Corrupt {
+            bytes: bytes.freeze(),
+            error: io::Error::new(io::ErrorKind::InvalidData, message),
+        }
+    }
+}
+
+impl Decoder for MstpCodec {
+    type Item = MstpFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_frame(src))
+    }
+}
+
+impl Checksummed for MstpCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<MstpFrame>>> {
+        Ok(self.next_frame(src).map(Frame::from))
+    }
+}
+
+impl Encoder<MstpFrame> for MstpCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: MstpFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.data.len() > self.max_data {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "MS/TP frame too long",
+            ));
+        }
+
+        dst.reserve(item.encoded_len());
+        dst.extend_from_slice(&PREAMBLE);
+        let header = dst.len();
+        dst.put_u8(item.frame_type.into());
+        dst.put_u8(item.destination);
+        dst.put_u8(item.source);
+        dst.put_u16(item.data.len() as u16);
+        let crc = header_crc(&dst[header..]);
+        dst.put_u8(crc);
+        if !item.data.is_empty() {
+            dst.extend_from_slice(&item.data);
+            dst.put_u16_le(data_crc(&item.data));
+        }
+        Ok(())
+    }
+}
+
+/// Timing parameters of the MS/TP token passing at a baud rate
+///
+/// The fixed timeouts are the limits of ASHRAE 135 clause 9, the ones counted in bit times
+/// follow the baud rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MacTiming {
+    /// Time to transmit one bit
+    pub bit_time: Duration,
+    /// Silence after which a partially received frame is dropped, 60 bit times
+    pub frame_abort: Duration,
+    /// Least silence after receiving a frame before transmitting, 40 bit times
+    pub turnaround: Duration,
+    /// Silence after which the token is considered lost
+    pub no_token: Duration,
+    /// Longest wait for a reply to a frame expecting one
+    pub reply_timeout: Duration,
+    /// Longest time a node may take to reply to a frame expecting one
+    pub reply_delay: Duration,
+    /// Time a node waits after the token loss per lower station address before claiming it
+    pub slot: Duration,
+    /// Longest wait for a station to use the token passed to it
+    pub usage_timeout: Duration,
+}
+
+impl MacTiming {
+    /// Returns the timing at `baud_rate`, usually 9600, 19200, 38400, 57600, 76800 or 115200
+    pub fn new(baud_rate: u32) -> Self {
+        let bit_time = Duration::from_secs(1) / baud_rate.max(1);
+        Self {
+            bit_time,
+            frame_abort: bit_time * 60,
+            turnaround: bit_time * 40,
+            no_token: Duration::from_millis(500),
+            reply_timeout: Duration::from_millis(255),
+            reply_delay: Duration::from_millis(250),
+            slot: Duration::from_millis(10),
+            usage_timeout: Duration::from_millis(20),
+        }
+    }
+
+    /// Returns how long the station `address` waits after losing the token before claiming it
+    pub fn token_regeneration(&self, address: u8) -> Duration {
+        self.no_token + self.slot * u32::from(address)
+    }
+}
@@ -0,0 +1,394 @@
+//! NMEA 0183 sentence codec
+//!
+//! Sentences start with `$` (or `!` for encapsulated sentences such as AIS), end with
+//! `\r\n` and optionally carry a `*hh` checksum.  Bytes outside of a sentence are skipped,
+//! so the codec can be used on a GNSS receiver that interleaves NMEA with binary protocols.
+//!
+//! To send sentences, [`NmeaEncoder`] takes their bodies and appends the checksum and
+//! `\r\n`, optionally limiting how often proprietary configuration sentences go out:
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::nmea::NmeaEncoder;
+//! use tokio_util::codec::Encoder;
+//!
+//! let mut encoder = NmeaEncoder::new();
+//! let mut dst = BytesMut::new();
+//! encoder.encode("PUBX,40,GSV,0,0,0,0,0,0", &mut dst).unwrap();
+//! assert_eq!(&dst[..], b"$PUBX,40,GSV,0,0,0,0,0,0*59\r\n");
+//! ```
+//!
+//! The AIS messages carried by `!AIVDM` sentences are reassembled and de-armored by the
+//! [`ais`](super::ais) codec.
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+/// Sentences longer than this are discarded.  The standard limit is 82 characters, but a
+/// number of receivers exceed it with proprietary sentences.
+pub const DEFAULT_MAX_LENGTH: usize = 1024;
+
+/// A single NMEA 0183 sentence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sentence {
+    text: String,
+}
+
+impl Sentence {
+    /// Parse a sentence from its text, with or without the trailing `\r\n`
+    ///
+    /// The checksum is verified if present.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let text = text.trim_end_matches(['\r', '\n']);
+        if !(text.starts_with('$') || text.starts_with('!')) {
+            return Err(invalid("NMEA sentence must start with '$' or '!'"));
+        }
+        if !text.is_ascii() {
+            return Err(invalid("NMEA sentence is not ASCII"));
+        }
+
+        let sentence = Self {
+            text: text.to_owned(),
+        };
+        if let Some(expected) = sentence.checksum() {
+            if expected != checksum(sentence.body().as_bytes()) {
+                return Err(invalid("NMEA checksum mismatch"));
+            }
+        }
+        Ok(sentence)
+    }
+
+    /// Build a `$` sentence from its body, e.g. `GPGGA,...`, appending the checksum
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if the body isn't ASCII or contains a start, checksum or line end
+    ///   character.
+    pub fn from_body(body: &str) -> io::Result<Self> {
+        Self::build('$', body)
+    }
+
+    /// Build an encapsulated `!` sentence, like AIS `AIVDM`, from its body
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` as for [`from_body`](Self::from_body).
+    pub fn encapsulated(body: &str) -> io::Result<Self> {
+        Self::build('!', body)
+    }
+
+    fn build(start: char, body: &str) -> io::Result<Self> {
+        let body = body.trim_start_matches(['$', '!']);
+        if !body.is_ascii() || body.contains(['$', '!', '*', '\r', '\n']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "NMEA sentence body must be ASCII without '$', '!', '*' or line ends",
+            ));
+        }
+        Ok(Self {
+            text: format!("{}{}*{:02X}", start, body, checksum(body.as_bytes())),
+        })
+    }
+
+    /// Returns `true` for proprietary sentences, whose address starts with `P`
+    pub fn is_proprietary(&self) -> bool {
+        self.talker() == "P"
+    }
+
+    /// Returns the full text of the sentence, without the trailing `\r\n`
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the sentence between the start character and the checksum delimiter
+    pub fn body(&self) -> &str {
+        let end = self.text.rfind('*').unwrap_or(self.text.len());
+        &self.text[1..end]
+    }
+
+    /// Returns the address field, e.g. `GPGGA`
+    pub fn address(&self) -> &str {
+        self.body().split(',').next().unwrap_or("")
+    }
+
+    /// Returns the talker ID, e.g. `GP`, or `P` for proprietary sentences
+    pub fn talker(&self) -> &str {
+        let address = self.address();
+        if address.starts_with('P') {
+            &address[..1]
+        } else {
+            &address[..address.len().min(2)]
+        }
+    }
+
+    /// Returns the sentence formatter, e.g. `GGA`
+    pub fn sentence_type(&self) -> &str {
+        &self.address()[self.talker().len()..]
+    }
+
+    /// Returns an iterator over the data fields following the address field
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.body().split(',').skip(1)
+    }
+
+    /// Returns the checksum transmitted with the sentence, if any
+    pub fn checksum(&self) -> Option<u8> {
+        let star = self.text.rfind('*')?;
+        u8::from_str_radix(&self.text[star + 1..], 16).ok()
+    }
+}
+
+impl fmt::Display for Sentence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Compute the NMEA checksum (XOR of all bytes) of a sentence body
+pub fn checksum(body: &[u8]) -> u8 {
+    body.iter().fold(0, |acc, b| acc ^ b)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Decoder and encoder for NMEA 0183 sentences
+///
+/// Sentences failing their checksum or not ASCII are handled according to the
+/// [`ChecksumPolicy`], reported as `io::ErrorKind::InvalidData` errors by default; decoding
+/// continues with the next sentence on the following call.  Encoding writes a
+/// [`Sentence`] verbatim followed by `\r\n`.
+#[derive(Debug, Clone)]
+pub struct NmeaCodec {
+    max_length: usize,
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl NmeaCodec {
+    /// Create a codec using [`DEFAULT_MAX_LENGTH`]
+    pub fn new() -> Self {
+        Self::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    /// Create a codec discarding sentences longer than `max_length` bytes
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            max_length,
+            checksum_policy: ChecksumPolicy::default(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Returns the maximum sentence length
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Set what happens to sentences failing their checksum
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to sentences failing their checksum
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between sentences
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Default for NmeaCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NmeaCodec {
+    fn next_sentence(&mut self, src: &mut BytesMut) -> Option<Result<Sentence, Corrupt>> {
+        loop {
+            // Skip to the start of a sentence
+            match src.iter().position(|&b| b == b'$' || b == b'!') {
+                Some(start) => {
+                    self.skips.skip(start);
+                    src.advance(start);
+                }
+                None => {
+                    self.skips.skip(src.len());
+                    src.clear();
+                    return None;
+                }
+            }
+
+            let end = match src.iter().position(|&b| b == b'\n') {
+                Some(end) => end,
+                None => {
+                    if src.len() > self.max_length {
+                        // Runaway sentence, drop the start character and look for the next one
+                        self.skips.corrupt(1);
+                        src.advance(1);
+                        continue;
+                    }
+                    return None;
+                }
+            };
+
+            let mut line = src.split_to(end + 1);
+            if line.len() > self.max_length + 2 {
+                self.skips.corrupt(line.len());
+                continue;
+            }
+            // A new start character before the end means the previous sentence was cut short
+            if let Some(restart) = line[1..].iter().rposition(|&b| b == b'$' || b == b'!') {
+                self.skips.corrupt(restart + 1);
+                line.advance(restart + 1);
+            }
+            let text = String::from_utf8_lossy(&line);
+            return Some(match Sentence::parse(&text) {
+                Ok(sentence) => {
+                    self.skips.frame();
+                    Ok(sentence)
+                }
+                Err(error) => {
+                    self.skips.corrupt(line.len());
+                    Err(Corrupt {
+                        bytes: line.freeze(),
+                        error,
+                    })
+                }
+            });
+        }
+    }
+}
+
+impl Decoder for NmeaCodec {
+    type Item = Sentence;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_sentence(src))
+    }
+}
+
+impl Checksummed for NmeaCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<Sentence>>> {
+        Ok(self.next_sentence(src).map(Frame::from))
+    }
+}
+
+impl Encoder<Sentence> for NmeaCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Sentence, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(item.text.len() + 2);
+        dst.extend_from_slice(item.text.as_bytes());
+        dst.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+}
+
+/// Encoder for NMEA 0183 sentences given as bodies or [`Sentence`]s
+///
+/// Bodies, like `GPGGA,...` or `PUBX,40,...`, get the `$`, the checksum and `\r\n`.  A
+/// [`Sentence`] lacking a checksum gets one, one that has it is written verbatim.
+///
+/// With [`limit_proprietary`](Self::limit_proprietary), a proprietary sentence sent sooner
+/// than the interval after the last one of the same address is dropped and counted in
+/// [`suppressed`](Self::suppressed), so a configuration loop can't flood a receiver that
+/// is busy applying it.
+#[derive(Debug, Clone, Default)]
+pub struct NmeaEncoder {
+    proprietary_interval: Option<Duration>,
+    last_sent: HashMap<String, Instant>,
+    suppressed: u64,
+}
+
+impl NmeaEncoder {
+    /// Create an encoder without rate limiting
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send each proprietary sentence address at most once per `interval`, `None` for no
+    /// limit, the default
+    pub fn limit_proprietary(&mut self, interval: Option<Duration>) {
+        self.proprietary_interval = interval;
+        if interval.is_none() {
+            self.last_sent.clear();
+        }
+    }
+
+    /// Returns the interval proprietary sentences of an address are limited to
+    pub fn proprietary_interval(&self) -> Option<Duration> {
+        self.proprietary_interval
+    }
+
+    /// Returns the number of proprietary sentences dropped by the rate limit so far
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// Returns `true` if `sentence` may be sent now, recording it if so
+    fn admit(&mut self, sentence: &Sentence) -> bool {
+        let interval = match self.proprietary_interval {
+            Some(interval) if sentence.is_proprietary() => interval,
+            _ => return true,
+        };
+        let now = Instant::now();
+        match self.last_sent.get_mut(sentence.address()) {
+            Some(last) if now.duration_since(*last) < interval => {
+                self.suppressed += 1;
+                false
+            }
+            Some(last) => {
+                *last = now;
+                true
+            }
+            None => {
+                self.last_sent.insert(sentence.address().to_owned(), now);
+                true
+            }
+        }
+    }
+}
+
+impl Encoder<Sentence> for NmeaEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Sentence, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let item = match item.checksum() {
+            Some(_) => item,
+            None => Sentence::build(item.text.as_bytes()[0] as char, &item.text[1..])?,
+        };
+        if self.admit(&item) {
+            NmeaCodec::new().encode(item, dst)?;
+        }
+        Ok(())
+    }
+}
+
+impl Encoder<&str> for NmeaEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &str, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(Sentence::from_body(item)?, dst)
+    }
+}
+
+impl Encoder<String> for NmeaEncoder {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: String, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(item.as_str(), dst)
+    }
+}
@@ -0,0 +1,407 @@
+//! SLCAN (LAWICEL) serial line CAN codec
+//!
+//! Many USB-CAN adapters, the CANable and CANUSB among them, speak the ASCII protocol
+//! Lawicel defined for its CAN232 and CANUSB: commands are letters with hex arguments ended
+//! by `\r`, answered by `\r` for success and `\a` (BEL) for failure.  Received frames come
+//! as `tIIILDD..` for 11 bit identifiers and `TIIIIIIIILDD..` for 29 bit ones, `r` and `R`
+//! for remote frames, followed by four hex digits of milliseconds when timestamps are on.
+//!
+//! [`SlcanCodec`] encodes [`Command`]s and decodes [`Response`]s.  The
+//! [`Slcan`](crate::slcan::Slcan) interface drives an adapter with it.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_serial::codec::slcan::{CanFrame, CanId, Command, Response, SlcanCodec};
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = SlcanCodec::new();
+//! let mut dst = BytesMut::new();
+//! let frame = CanFrame::new(CanId::Standard(0x123), &[0xde, 0xad]).unwrap();
+//! codec.encode(Command::Transmit(frame), &mut dst).unwrap();
+//! assert_eq!(&dst[..], b"t1232DEAD\r");
+//!
+//! let mut src = BytesMut::from(&b"T1ABCDEF00\r"[..]);
+//! match codec.decode(&mut src).unwrap() {
+//!     Some(Response::Frame(received)) => assert_eq!(received.frame.id(), CanId::Extended(0x1abcdef0)),
+//!     other => panic!("{:?}", other),
+//! }
+//! ```
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::fmt::{self, Write};
+use std::io;
+
+/// Answer of the adapter to a failed command
+pub const BELL: u8 = 0x07;
+
+/// Longest line: an extended frame with eight bytes and a timestamp
+const MAX_LINE: usize = 1 + 8 + 1 + 16 + 4;
+
+/// Identifier of a CAN frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CanId {
+    /// 11 bit identifier
+    Standard(u16),
+    /// 29 bit identifier
+    Extended(u32),
+}
+
+impl CanId {
+    /// Returns the identifier's value
+    pub fn raw(&self) -> u32 {
+        match *self {
+            CanId::Standard(id) => u32::from(id),
+            CanId::Extended(id) => id,
+        }
+    }
+
+    /// Returns `true` for a 29 bit identifier
+    pub fn is_extended(&self) -> bool {
+        matches!(self, CanId::Extended(_))
+    }
+
+    fn is_valid(&self) -> bool {
+        match *self {
+            CanId::Standard(id) => id <= 0x7ff,
+            CanId::Extended(id) => id <= 0x1fff_ffff,
+        }
+    }
+}
+
+impl fmt::Display for CanId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CanId::Standard(id) => write!(f, "{:03X}", id),
+            CanId::Extended(id) => write!(f, "{:08X}", id),
+        }
+    }
+}
+
+/// A classic CAN frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CanFrame {
+    id: CanId,
+    data: [u8; 8],
+    len: u8,
+    remote: bool,
+}
+
+impl CanFrame {
+    /// Create a data frame
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `id` is out of range or `data` longer than 8 bytes.
+    pub fn new(id: CanId, data: &[u8]) -> io::Result<Self> {
+        if data.len() > 8 {
+            return Err(invalid_input("CAN frames carry at most 8 bytes"));
+        }
+        let mut frame = Self::remote(id, data.len() as u8)?;
+        frame.data[..data.len()].copy_from_slice(data);
+        frame.remote = false;
+        Ok(frame)
+    }
+
+    /// Create a remote frame requesting `len` bytes
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `id` is out of range or `len` above 8.
+    pub fn remote(id: CanId, len: u8) -> io::Result<Self> {
+        if !id.is_valid() {
+            return Err(invalid_input("CAN identifier out of range"));
+        }
+        if len > 8 {
+            return Err(invalid_input("CAN frames carry at most 8 bytes"));
+        }
+        Ok(Self {
+            id,
+            data: [0; 8],
+            len,
+            remote: true,
+        })
+    }
+
+    /// Returns the identifier
+    pub fn id(&self) -> CanId {
+        self.id
+    }
+
+    /// Returns the data, empty for a remote frame
+    pub fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..usize::from(self.len)]
+        }
+    }
+
+    /// Returns the data length code, the length requested by a remote frame
+    pub fn dlc(&self) -> u8 {
+        self.len
+    }
+
+    /// Returns `true` for a remote frame
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+}
+
+/// Standard bit rates, set with the `S` command, in the order of its digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bitrate {
+    /// 10 kbit/s
+    Kbps10,
+    /// 20 kbit/s
+    Kbps20,
+    /// 50 kbit/s
+    Kbps50,
+    /// 100 kbit/s
+    Kbps100,
+    /// 125 kbit/s
+    Kbps125,
+    /// 250 kbit/s
+    Kbps250,
+    /// 500 kbit/s
+    Kbps500,
+    /// 800 kbit/s
+    Kbps800,
+    /// 1 Mbit/s
+    Mbps1,
+}
+
+impl Bitrate {
+    /// Returns the bit rate for `bits_per_second`, if it is a standard one
+    pub fn from_bps(bits_per_second: u32) -> Option<Self> {
+        Some(match bits_per_second {
+            10_000 => Bitrate::Kbps10,
+            20_000 => Bitrate::Kbps20,
+            50_000 => Bitrate::Kbps50,
+            100_000 => Bitrate::Kbps100,
+            125_000 => Bitrate::Kbps125,
+            250_000 => Bitrate::Kbps250,
+            500_000 => Bitrate::Kbps500,
+            800_000 => Bitrate::Kbps800,
+            1_000_000 => Bitrate::Mbps1,
+            _ => return None,
+        })
+    }
+
+    /// Returns the digit selecting the bit rate in the `S` command
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// A command to the adapter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Open the channel, `O`
+    Open,
+    /// Open the channel without acknowledging or sending frames, `L`
+    ListenOnly,
+    /// Close the channel, `C`
+    Close,
+    /// Set the bit rate of the closed channel, `Sn`
+    Bitrate(Bitrate),
+    /// Turn the timestamps of received frames on or off, `Zn`, while the channel is closed
+    Timestamps(bool),
+    /// Read the hardware and firmware versions, `V`
+    Version,
+    /// Read the status flags, `F`
+    Status,
+    /// Send a frame, `t`, `T`, `r` or `R`
+    Transmit(CanFrame),
+}
+
+/// A frame received from the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Received {
+    /// The frame
+    pub frame: CanFrame,
+    /// Milliseconds from 0 to 59999, when timestamps are on
+    pub timestamp: Option<u16>,
+}
+
+/// What the adapter sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// A command succeeded, `\r`
+    Ok,
+    /// A command failed, `\a`
+    Error,
+    /// A frame was queued for transmission, `z` or `Z`
+    Sent,
+    /// A frame received from the bus
+    Frame(Received),
+    /// The reply to a query like [`Command::Version`], `V1013` for instance
+    Reply(String),
+}
+
+/// Decoder and encoder for SLCAN, see the [module](self) docs
+///
+/// Malformed frame lines are handled according to the [`ChecksumPolicy`], reported as
+/// `io::ErrorKind::InvalidData` errors by default.
+#[derive(Debug, Clone, Default)]
+pub struct SlcanCodec {
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl SlcanCodec {
+    /// Create a codec
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what happens to malformed frames
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to malformed frames
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between responses
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+
+    fn next_frame(&mut self, src: &mut BytesMut) -> Option<Result<Response, Corrupt>> {
+        let end = match src.iter().position(|&b| b == b'\r' || b == BELL) {
+            Some(end) => end,
+            None if src.len() > MAX_LINE => {
+                let line = src.split();
+                return Some(Err(self.corrupt(line, "SLCAN line too long")));
+            }
+            None => return None,
+        };
+        let line = src.split_to(end);
+        let bell = src[0] == BELL;
+        src.advance(1);
+        if bell {
+            self.skips.skip(line.len());
+            self.skips.frame();
+            return Some(Ok(Response::Error));
+        }
+
+        let response = match line.first() {
+            None => Response::Ok,
+            Some(b'z' | b'Z') if line.len() == 1 => Response::Sent,
+            Some(b't' | b'T' | b'r' | b'R') => match parse_frame(&line) {
+                Some(received) => Response::Frame(received),
+                None => return Some(Err(self.corrupt(line, "malformed SLCAN frame"))),
+            },
+            Some(_) => Response::Reply(String::from_utf8_lossy(&line).into_owned()),
+        };
+        self.skips.frame();
+        Some(Ok(response))
+    }
+
+    fn corrupt(&mut self, bytes: BytesMut, message: &str) -> Corrupt {
+        self.skips.corrupt(bytes.len());
+        Corrupt {
+            bytes: bytes.freeze(),
+            error: io::Error::new(io::ErrorKind::InvalidData, message),
+        }
+    }
+}
+
+fn hex(digits: &[u8]) -> Option<u32> {
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+fn parse_frame(line: &[u8]) -> Option<Received> {
+    let extended = line[0].is_ascii_uppercase();
+    let remote = line[0].eq_ignore_ascii_case(&b'r');
+    let id_len = if extended { 8 } else { 3 };
+    let id = hex(line.get(1..1 + id_len)?)?;
+    let id = match extended {
+        true => CanId::Extended(id),
+        false => CanId::Standard(id as u16),
+    };
+    let len = hex(line.get(1 + id_len..2 + id_len)?)? as u8;
+    let mut rest = &line[2 + id_len..];
+
+    let frame = if remote {
+        CanFrame::remote(id, len).ok()?
+    } else {
+        let data_len = 2 * usize::from(len);
+        let data = rest.get(..data_len)?;
+        let mut bytes = [0; 8];
+        for (byte, digits) in bytes.iter_mut().zip(data.chunks(2)) {
+            *byte = hex(digits)? as u8;
+        }
+        rest = &rest[data_len..];
+        CanFrame::new(id, bytes.get(..usize::from(len))?).ok()?
+    };
+    let timestamp = match rest.len() {
+        0 => None,
+        4 => Some(hex(rest)? as u16),
+        _ => return None,
+    };
+    Some(Received { frame, timestamp })
+}
+
+impl Decoder for SlcanCodec {
+    type Item = Response;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_frame(src))
+    }
+}
+
+impl Checksummed for SlcanCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<Response>>> {
+        Ok(self.next_frame(src).map(Frame::from))
+    }
+}
+
+impl Encoder<Command> for SlcanCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Command, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut line = String::with_capacity(MAX_LINE);
+        // Writing to a `String` doesn't fail
+        let _ = match item {
+            Command::Open => write!(line, "O"),
+            Command::ListenOnly => write!(line, "L"),
+            Command::Close => write!(line, "C"),
+            Command::Bitrate(bitrate) => write!(line, "S{}", bitrate.code()),
+            Command::Timestamps(on) => write!(line, "Z{}", u8::from(on)),
+            Command::Version => write!(line, "V"),
+            Command::Status => write!(line, "F"),
+            Command::Transmit(frame) => {
+                let kind = match (frame.remote, frame.id.is_extended()) {
+                    (false, false) => 't',
+                    (false, true) => 'T',
+                    (true, false) => 'r',
+                    (true, true) => 'R',
+                };
+                let _ = write!(line, "{}{}{}", kind, frame.id, frame.len);
+                frame
+                    .data()
+                    .iter()
+                    .try_for_each(|byte| write!(line, "{:02X}", byte))
+            }
+        };
+        dst.reserve(line.len() + 1);
+        dst.put_slice(line.as_bytes());
+        dst.put_u8(b'\r');
+        Ok(())
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,483 @@
+//! u-blox UBX binary protocol codec
+//!
+//! UBX frames are `0xB5 0x62`, message class, message ID, a little-endian `u16` payload
+//! length, the payload and a two byte Fletcher checksum over everything after the sync
+//! characters.
+//!
+//! Receivers are configured with `CFG` messages, each answered by an `ACK-ACK` or `ACK-NAK`
+//! within a second.  [`Configurator`] sends the common ones and awaits their answer:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tokio_serial::codec::ubx::{Configurator, NmeaMessage, UbxCodec};
+//! use tokio_serial::frame::SerialFramed;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut gnss = Configurator::new(SerialFramed::new(port, UbxCodec::new()));
+//! gnss.set_rate(Duration::from_millis(200)).await?;
+//! gnss.set_nmea_rate(NmeaMessage::Gsv, 0).await?;
+//! gnss.save().await?;
+//! # Ok(())
+//! # }
+//! ```
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use tokio::time::{timeout_at, Instant};
+
+use std::convert::TryFrom;
+use std::io;
+use std::time::Duration;
+
+/// First UBX sync character
+pub const SYNC_1: u8 = 0xb5;
+/// Second UBX sync character
+pub const SYNC_2: u8 = 0x62;
+
+/// Sync characters, class, ID and length
+const HEADER_LEN: usize = 6;
+/// Fletcher checksum
+const CHECKSUM_LEN: usize = 2;
+
+/// Class of the `ACK` messages
+pub const CLASS_ACK: u8 = 0x05;
+/// Class of the `CFG` messages
+pub const CLASS_CFG: u8 = 0x06;
+/// Class of the standard NMEA sentences, when configured with `CFG-MSG`
+pub const CLASS_NMEA: u8 = 0xf0;
+
+const ID_ACK_NAK: u8 = 0x00;
+const ID_ACK_ACK: u8 = 0x01;
+const ID_CFG_MSG: u8 = 0x01;
+const ID_CFG_RATE: u8 = 0x08;
+const ID_CFG_CFG: u8 = 0x09;
+
+/// Payloads longer than this are treated as a corrupt length field
+pub const DEFAULT_MAX_PAYLOAD: usize = 8 * 1024;
+
+/// A single UBX frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UbxFrame {
+    /// Message class
+    pub class: u8,
+    /// Message ID
+    pub id: u8,
+    /// Message payload
+    pub payload: Bytes,
+}
+
+impl UbxFrame {
+    /// Create a new frame
+    pub fn new(class: u8, id: u8, payload: impl Into<Bytes>) -> Self {
+        Self {
+            class,
+            id,
+            payload: payload.into(),
+        }
+    }
+
+    /// Returns the length of the encoded frame
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.payload.len() + CHECKSUM_LEN
+    }
+
+    /// `CFG-RATE`: one navigation solution every `measurement_ms`, aligned to GPS time
+    pub fn cfg_rate(measurement_ms: u16) -> Self {
+        let mut payload = Vec::with_capacity(6);
+        payload.extend_from_slice(&measurement_ms.to_le_bytes());
+        // One measurement per navigation solution, aligned to GPS time
+        payload.extend_from_slice(&1u16.to_le_bytes());
+        payload.extend_from_slice(&1u16.to_le_bytes());
+        Self::new(CLASS_CFG, ID_CFG_RATE, payload)
+    }
+
+    /// `CFG-MSG`: output message `class`/`id` on the current port every `rate` navigation
+    /// solutions, 0 to disable it
+    pub fn cfg_msg(class: u8, id: u8, rate: u8) -> Self {
+        Self::new(CLASS_CFG, ID_CFG_MSG, vec![class, id, rate])
+    }
+
+    /// `CFG-CFG`: save the current configuration to every non-volatile memory
+    pub fn cfg_save() -> Self {
+        let mut payload = Vec::with_capacity(13);
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&0x0000_1f1fu32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        // Battery backed RAM, flash, EEPROM and SPI flash
+        payload.push(0x17);
+        Self::new(CLASS_CFG, ID_CFG_CFG, payload)
+    }
+
+    /// Returns the answer if the frame is an `ACK-ACK` or `ACK-NAK`
+    pub fn ack(&self) -> Option<Ack> {
+        if self.class != CLASS_ACK || self.payload.len() < 2 {
+            return None;
+        }
+        let (class, id) = (self.payload[0], self.payload[1]);
+        match self.id {
+            ID_ACK_ACK => Some(Ack::Ack { class, id }),
+            ID_ACK_NAK => Some(Ack::Nak { class, id }),
+            _ => None,
+        }
+    }
+}
+
+/// Answer of a receiver to a `CFG` message, see [`UbxFrame::ack`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ack {
+    /// The message of `class`/`id` was applied
+    Ack {
+        /// Class of the acknowledged message
+        class: u8,
+        /// ID of the acknowledged message
+        id: u8,
+    },
+    /// The message of `class`/`id` was rejected
+    Nak {
+        /// Class of the rejected message
+        class: u8,
+        /// ID of the rejected message
+        id: u8,
+    },
+}
+
+impl Ack {
+    /// Returns `true` if this answers the message `class`/`id`
+    pub fn answers(&self, class: u8, id: u8) -> bool {
+        match *self {
+            Ack::Ack { class: c, id: i } | Ack::Nak { class: c, id: i } => (c, i) == (class, id),
+        }
+    }
+}
+
+/// Standard NMEA sentences a u-blox receiver outputs, see [`Configurator::set_nmea_rate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NmeaMessage {
+    /// Fix data
+    Gga,
+    /// Position
+    Gll,
+    /// Satellites used
+    Gsa,
+    /// Satellites in view
+    Gsv,
+    /// Recommended minimum data
+    Rmc,
+    /// Course and speed
+    Vtg,
+    /// Time and date
+    Zda,
+}
+
+impl NmeaMessage {
+    /// Returns the UBX message ID of the sentence in class [`CLASS_NMEA`]
+    pub fn id(self) -> u8 {
+        match self {
+            NmeaMessage::Gga => 0x00,
+            NmeaMessage::Gll => 0x01,
+            NmeaMessage::Gsa => 0x02,
+            NmeaMessage::Gsv => 0x03,
+            NmeaMessage::Rmc => 0x04,
+            NmeaMessage::Vtg => 0x05,
+            NmeaMessage::Zda => 0x08,
+        }
+    }
+}
+
+/// Compute the 8-bit Fletcher checksum used by UBX
+pub fn checksum(data: &[u8]) -> [u8; 2] {
+    let (a, b) = data.iter().fold((0u8, 0u8), |(a, b), &byte| {
+        let a = a.wrapping_add(byte);
+        (a, b.wrapping_add(a))
+    });
+    [a, b]
+}
+
+/// Decoder and encoder for UBX frames
+///
+/// Bytes before a sync sequence are skipped.  Frames failing their checksum are removed from
+/// the buffer and handled according to the [`ChecksumPolicy`], reported as
+/// `io::ErrorKind::InvalidData` errors by default.
+#[derive(Debug, Clone)]
+pub struct UbxCodec {
+    max_payload: usize,
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl UbxCodec {
+    /// Create a codec using [`DEFAULT_MAX_PAYLOAD`]
+    pub fn new() -> Self {
+        Self::with_max_payload(DEFAULT_MAX_PAYLOAD)
+    }
+
+    /// Create a codec treating payloads longer than `max_payload` as corrupt
+    pub fn with_max_payload(max_payload: usize) -> Self {
+        Self {
+            max_payload,
+            checksum_policy: ChecksumPolicy::default(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Set what happens to frames failing their checksum
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to frames failing their checksum
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between frames
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Default for UbxCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UbxCodec {
+    fn next_frame(&mut self, src: &mut BytesMut) -> Option<Result<UbxFrame, Corrupt>> {
+        loop {
+            match src.windows(2).position(|w| w == [SYNC_1, SYNC_2]) {
+                Some(start) => {
+                    self.skips.skip(start);
+                    src.advance(start);
+                }
+                None => {
+                    // Keep a trailing first sync character, the second may be on its way
+                    let keep = usize::from(src.last() == Some(&SYNC_1));
+                    let len = src.len();
+                    self.skips.skip(len - keep);
+                    src.advance(len - keep);
+                    return None;
+                }
+            }
+
+            if src.len() < HEADER_LEN {
+                return None;
+            }
+            let payload_len = usize::from(u16::from_le_bytes([src[4], src[5]]));
+            if payload_len > self.max_payload {
+                self.skips.corrupt(2);
+                src.advance(2);
+                continue;
+            }
+
+            let frame_len = HEADER_LEN + payload_len + CHECKSUM_LEN;
+            if src.len() < frame_len {
+                src.reserve(frame_len - src.len());
+                return None;
+            }
+
+            let mut frame = src.split_to(frame_len);
+            let expected = checksum(&frame[2..HEADER_LEN + payload_len]);
+            if frame[HEADER_LEN + payload_len..] != expected {
+                self.skips.corrupt(frame_len);
+                return Some(Err(Corrupt {
+                    bytes: frame.freeze(),
+                    error: io::Error::new(io::ErrorKind::InvalidData, "UBX checksum mismatch"),
+                }));
+            }
+
+            let class = frame[2];
+            let id = frame[3];
+            frame.advance(HEADER_LEN);
+            frame.truncate(payload_len);
+            self.skips.frame();
+            return Some(Ok(UbxFrame {
+                class,
+                id,
+                payload: frame.freeze(),
+            }));
+        }
+    }
+}
+
+impl Decoder for UbxCodec {
+    type Item = UbxFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_frame(src))
+    }
+}
+
+impl Checksummed for UbxCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<UbxFrame>>> {
+        Ok(self.next_frame(src).map(Frame::from))
+    }
+}
+
+impl Encoder<UbxFrame> for UbxCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: UbxFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.payload.len() > usize::from(u16::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "UBX payload too long",
+            ));
+        }
+
+        dst.reserve(item.encoded_len());
+        let start = dst.len();
+        dst.put_u8(SYNC_1);
+        dst.put_u8(SYNC_2);
+        dst.put_u8(item.class);
+        dst.put_u8(item.id);
+        dst.put_u16_le(item.payload.len() as u16);
+        dst.extend_from_slice(&item.payload);
+        let ck = checksum(&dst[start + 2..]);
+        dst.extend_from_slice(&ck);
+        Ok(())
+    }
+}
+
+/// Answers of a receiver are due within a second
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends configuration messages to a u-blox receiver and awaits their acknowledgement
+///
+/// Works over anything that is a `Sink` and `Stream` of [`UbxFrame`]s, typically a
+/// [`SerialFramed`](crate::frame::SerialFramed) with a [`UbxCodec`].  Frames other than the
+/// answer, like navigation output, are dropped while waiting.  A message that isn't
+/// answered within the [timeout](Self::set_timeout) is sent again, up to
+/// [`retries`](Self::set_retries) times, since receivers busy with output at a low baud rate
+/// regularly miss one.
+#[derive(Debug)]
+pub struct Configurator<F> {
+    framed: F,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl<F> Configurator<F> {
+    /// Configure the receiver behind `framed`, with [`DEFAULT_ACK_TIMEOUT`] and 2 retries
+    pub fn new(framed: F) -> Self {
+        Self {
+            framed,
+            timeout: DEFAULT_ACK_TIMEOUT,
+            retries: 2,
+        }
+    }
+
+    /// Wait up to `timeout` for each answer
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns how long each answer is waited for
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Send an unanswered message up to `retries` more times
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// Returns how often an unanswered message is sent again
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Returns a reference to the framed port
+    pub fn get_ref(&self) -> &F {
+        &self.framed
+    }
+
+    /// Returns a mutable reference to the framed port
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.framed
+    }
+
+    /// Consumes the configurator, returning the framed port
+    pub fn into_inner(self) -> F {
+        self.framed
+    }
+}
+
+impl<F> Configurator<F>
+where
+    F: Sink<UbxFrame, Error = io::Error> + Stream<Item = io::Result<UbxFrame>> + Unpin,
+{
+    /// Send `frame` and wait for its acknowledgement
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if the receiver answered with `ACK-NAK`.
+    /// * `TimedOut` if no answer came after the last retry.
+    /// * `UnexpectedEof` if the stream ended, and the errors of the framed port.
+    pub async fn send(&mut self, frame: UbxFrame) -> io::Result<()> {
+        let (class, id) = (frame.class, frame.id);
+        for _ in 0..=self.retries {
+            self.framed.send(frame.clone()).await?;
+            let deadline = Instant::now() + self.timeout;
+            loop {
+                let next = match timeout_at(deadline, self.framed.next()).await {
+                    Ok(next) => next,
+                    Err(_) => break,
+                };
+                let answer = match next {
+                    Some(frame) => frame?.ack(),
+                    None => return Err(io::ErrorKind::UnexpectedEof.into()),
+                };
+                match answer {
+                    Some(Ack::Ack { class: c, id: i }) if (c, i) == (class, id) => return Ok(()),
+                    Some(Ack::Nak { class: c, id: i }) if (c, i) == (class, id) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("receiver rejected UBX message {:#04x}/{:#04x}", class, id),
+                        ));
+                    }
+                    // Output of the receiver, or the answer to an earlier message
+                    _ => continue,
+                }
+            }
+            log::debug!("no answer to UBX message {:#04x}/{:#04x}", class, id);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no answer to UBX message {:#04x}/{:#04x}", class, id),
+        ))
+    }
+
+    /// Compute a navigation solution every `interval`, see [`UbxFrame::cfg_rate`]
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `interval` doesn't fit in 16 bits of milliseconds, or as for
+    ///   [`send`](Self::send).
+    pub async fn set_rate(&mut self, interval: Duration) -> io::Result<()> {
+        let ms = u16::try_from(interval.as_millis()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "UBX rate interval too long")
+        })?;
+        self.send(UbxFrame::cfg_rate(ms)).await
+    }
+
+    /// Output message `class`/`id` every `rate` solutions, 0 to disable it
+    pub async fn set_message_rate(&mut self, class: u8, id: u8, rate: u8) -> io::Result<()> {
+        self.send(UbxFrame::cfg_msg(class, id, rate)).await
+    }
+
+    /// Output the NMEA `sentence` every `rate` solutions, 0 to disable it
+    pub async fn set_nmea_rate(&mut self, sentence: NmeaMessage, rate: u8) -> io::Result<()> {
+        self.set_message_rate(CLASS_NMEA, sentence.id(), rate).await
+    }
+
+    /// Save the current configuration so it survives a power cycle
+    pub async fn save(&mut self) -> io::Result<()> {
+        self.send(UbxFrame::cfg_save()).await
+    }
+}
@@ -0,0 +1,489 @@
+//! Digi XBee API frame codec
+//!
+//! XBee radios in API mode exchange frames of `0x7E`, a two byte big endian length, the
+//! frame data starting with the frame type, and a checksum making the sum of the frame data
+//! `0xFF`.  In escaped mode, `AP=2`, the bytes `0x7E`, `0x7D`, `0x11` and `0x13` after the
+//! start delimiter are sent as `0x7D` followed by the byte XORed with `0x20`, so a start
+//! delimiter always starts a frame and the software flow control characters never appear.
+//!
+//! [`XBeeCodec`] decodes and encodes [`ApiFrame`]s, typed for AT commands, transmit
+//! requests, received packets and their responses.
+//!
+//! ```
+//! use bytes::{Bytes, BytesMut};
+//! use tokio_serial::codec::xbee::{ApiFrame, TransmitRequest, XBeeCodec};
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = XBeeCodec::escaped();
+//! let mut dst = BytesMut::new();
+//! let request = TransmitRequest::new(1, 0x0013_a200_4155_2b7d, Bytes::from_static(b"\x11"));
+//! codec.encode(ApiFrame::Transmit(request.clone()), &mut dst).unwrap();
+//! assert!(dst[1..].iter().all(|&b| b != 0x11 && b != 0x7e));
+//! assert_eq!(codec.decode(&mut dst).unwrap(), Some(ApiFrame::Transmit(request)));
+//! ```
+use super::{decode_with_policy, ChecksumPolicy, Checksummed, Corrupt, Frame};
+use super::{ResyncStats, SkipTracker};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::convert::TryFrom;
+use std::io;
+
+/// Start delimiter of API frames
+pub const START: u8 = 0x7e;
+/// Escape character of escaped mode
+pub const ESCAPE: u8 = 0x7d;
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+
+/// 64 bit address of the coordinator
+pub const COORDINATOR: u64 = 0;
+/// 64 bit broadcast address
+pub const BROADCAST: u64 = 0xffff;
+/// 16 bit address to use when the destination's isn't known
+pub const UNKNOWN_ADDRESS: u16 = 0xfffe;
+
+/// Frame type of AT commands
+pub const AT_COMMAND: u8 = 0x08;
+/// Frame type of queued AT commands, applied on the next `AC` or non-queued command
+pub const AT_COMMAND_QUEUED: u8 = 0x09;
+/// Frame type of transmit requests
+pub const TRANSMIT_REQUEST: u8 = 0x10;
+/// Frame type of AT command responses
+pub const AT_RESPONSE: u8 = 0x88;
+/// Frame type of modem status notifications
+pub const MODEM_STATUS: u8 = 0x8a;
+/// Frame type of transmit statuses
+pub const TRANSMIT_STATUS: u8 = 0x8b;
+/// Frame type of received packets
+pub const RECEIVE_PACKET: u8 = 0x90;
+
+/// Compute the checksum of frame data, `0xFF` minus the low byte of its sum
+pub fn checksum(data: &[u8]) -> u8 {
+    0xff - data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// An AT command, reading a parameter when sent without value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtCommand {
+    /// Identifies the response, 0 for no response
+    pub frame_id: u8,
+    /// The two letters of the command, like `*b"NI"`
+    pub command: [u8; 2],
+    /// The value to set, empty to read the parameter
+    pub parameter: Bytes,
+    /// Queue the command rather than apply it at once
+    pub queued: bool,
+}
+
+impl AtCommand {
+    /// Create a command applied at once
+    pub fn new(frame_id: u8, command: [u8; 2], parameter: impl Into<Bytes>) -> Self {
+        Self {
+            frame_id,
+            command,
+            parameter: parameter.into(),
+            queued: false,
+        }
+    }
+}
+
+/// The response to an [`AtCommand`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtResponse {
+    /// The frame ID of the command
+    pub frame_id: u8,
+    /// The two letters of the command
+    pub command: [u8; 2],
+    /// 0 for success, 1 error, 2 invalid command, 3 invalid parameter
+    pub status: u8,
+    /// The value read
+    pub data: Bytes,
+}
+
+/// A request to send data to another radio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransmitRequest {
+    /// Identifies the [`TransmitStatus`], 0 for none
+    pub frame_id: u8,
+    /// 64 bit address of the destination, [`BROADCAST`] to broadcast
+    pub destination: u64,
+    /// 16 bit network address of the destination, [`UNKNOWN_ADDRESS`] if not known
+    pub network_address: u16,
+    /// Most hops of a broadcast, 0 for the maximum
+    pub broadcast_radius: u8,
+    /// Transmit options
+    pub options: u8,
+    /// The data
+    pub data: Bytes,
+}
+
+impl TransmitRequest {
+    /// Create a request to send `data` to `destination`, with default options
+    pub fn new(frame_id: u8, destination: u64, data: impl Into<Bytes>) -> Self {
+        Self {
+            frame_id,
+            destination,
+            network_address: UNKNOWN_ADDRESS,
+            broadcast_radius: 0,
+            options: 0,
+            data: data.into(),
+        }
+    }
+}
+
+/// The outcome of a [`TransmitRequest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransmitStatus {
+    /// The frame ID of the request
+    pub frame_id: u8,
+    /// 16 bit network address the data was sent to
+    pub network_address: u16,
+    /// Retries it took
+    pub retries: u8,
+    /// 0 for success, a failure reason otherwise
+    pub delivery_status: u8,
+    /// How the route was discovered
+    pub discovery_status: u8,
+}
+
+impl TransmitStatus {
+    /// Returns `true` if the data was delivered
+    pub fn is_delivered(&self) -> bool {
+        self.delivery_status == 0
+    }
+}
+
+/// Data received from another radio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivePacket {
+    /// 64 bit address of the sender
+    pub source: u64,
+    /// 16 bit network address of the sender
+    pub network_address: u16,
+    /// Receive options, `0x01` acknowledged, `0x02` broadcast
+    pub options: u8,
+    /// The data
+    pub data: Bytes,
+}
+
+/// A single API frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiFrame {
+    /// AT command, frame type `0x08` or `0x09`
+    AtCommand(AtCommand),
+    /// AT command response, frame type `0x88`
+    AtResponse(AtResponse),
+    /// Transmit request, frame type `0x10`
+    Transmit(TransmitRequest),
+    /// Transmit status, frame type `0x8B`
+    TransmitStatus(TransmitStatus),
+    /// Received packet, frame type `0x90`
+    Receive(ReceivePacket),
+    /// Modem status, frame type `0x8A`, like `0x00` for a hardware reset
+    ModemStatus(u8),
+    /// Any other frame, with its type and the data following it
+    Other {
+        /// The frame type
+        frame_type: u8,
+        /// The frame data after the type
+        data: Bytes,
+    },
+}
+
+impl ApiFrame {
+    /// Returns the frame type
+    pub fn frame_type(&self) -> u8 {
+        match self {
+            ApiFrame::AtCommand(command) if command.queued => AT_COMMAND_QUEUED,
+            ApiFrame::AtCommand(_) => AT_COMMAND,
+            ApiFrame::AtResponse(_) => AT_RESPONSE,
+            ApiFrame::Transmit(_) => TRANSMIT_REQUEST,
+            ApiFrame::TransmitStatus(_) => TRANSMIT_STATUS,
+            ApiFrame::Receive(_) => RECEIVE_PACKET,
+            ApiFrame::ModemStatus(_) => MODEM_STATUS,
+            ApiFrame::Other { frame_type, .. } => *frame_type,
+        }
+    }
+
+    /// Parse the frame data, type included
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if the data is too short for its frame type.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let (&frame_type, mut data) = data
+            .split_first()
+            .ok_or_else(|| invalid("empty XBee frame"))?;
+        let min_len = match frame_type {
+            AT_COMMAND | AT_COMMAND_QUEUED => 3,
+            AT_RESPONSE => 4,
+            TRANSMIT_REQUEST => 13,
+            TRANSMIT_STATUS => 6,
+            RECEIVE_PACKET => 11,
+            MODEM_STATUS => 1,
+            _ => 0,
+        };
+        if data.len() < min_len {
+            return Err(invalid("XBee frame too short for its type"));
+        }
+        let rest = |data: &[u8]| Bytes::copy_from_slice(data);
+        Ok(match frame_type {
+            AT_COMMAND | AT_COMMAND_QUEUED => ApiFrame::AtCommand(AtCommand {
+                frame_id: data.get_u8(),
+                command: [data.get_u8(), data.get_u8()],
+                parameter: rest(data),
+                queued: frame_type == AT_COMMAND_QUEUED,
+            }),
+            AT_RESPONSE => ApiFrame::AtResponse(AtResponse {
+                frame_id: data.get_u8(),
+                command: [data.get_u8(), data.get_u8()],
+                status: data.get_u8(),
+                data: rest(data),
+            }),
+            TRANSMIT_REQUEST => ApiFrame::Transmit(TransmitRequest {
+                frame_id: data.get_u8(),
+                destination: data.get_u64(),
+                network_address: data.get_u16(),
+                broadcast_radius: data.get_u8(),
+                options: data.get_u8(),
+                data: rest(data),
+            }),
+            TRANSMIT_STATUS => ApiFrame::TransmitStatus(TransmitStatus {
+                frame_id: data.get_u8(),
+                network_address: data.get_u16(),
+                retries: data.get_u8(),
+                delivery_status: data.get_u8(),
+                discovery_status: data.get_u8(),
+            }),
+            RECEIVE_PACKET => ApiFrame::Receive(ReceivePacket {
+                source: data.get_u64(),
+                network_address: data.get_u16(),
+                options: data.get_u8(),
+                data: rest(data),
+            }),
+            MODEM_STATUS => ApiFrame::ModemStatus(data[0]),
+            _ => ApiFrame::Other {
+                frame_type,
+                data: rest(data),
+            },
+        })
+    }
+
+    /// Append the frame data, type included, to `dst`
+    fn write(&self, dst: &mut Vec<u8>) {
+        dst.push(self.frame_type());
+        match self {
+            ApiFrame::AtCommand(command) => {
+                dst.push(command.frame_id);
+                dst.extend_from_slice(&command.command);
+                dst.extend_from_slice(&command.parameter);
+            }
+            ApiFrame::AtResponse(response) => {
+                dst.push(response.frame_id);
+                dst.extend_from_slice(&response.command);
+                dst.push(response.status);
+                dst.extend_from_slice(&response.data);
+            }
+            ApiFrame::Transmit(request) => {
+                dst.push(request.frame_id);
+                dst.put_u64(request.destination);
+                dst.put_u16(request.network_address);
+                dst.push(request.broadcast_radius);
+                dst.push(request.options);
+                dst.extend_from_slice(&request.data);
+            }
+            ApiFrame::TransmitStatus(status) => {
+                dst.push(status.frame_id);
+                dst.put_u16(status.network_address);
+                dst.push(status.retries);
+                dst.push(status.delivery_status);
+                dst.push(status.discovery_status);
+            }
+            ApiFrame::Receive(packet) => {
+                dst.put_u64(packet.source);
+                dst.put_u16(packet.network_address);
+                dst.push(packet.options);
+                dst.extend_from_slice(&packet.data);
+            }
+            ApiFrame::ModemStatus(status) => dst.push(*status),
+            ApiFrame::Other { data, .. } => dst.extend_from_slice(data),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Decoder and encoder for XBee API frames, see the [module](self) docs
+///
+/// Bytes before a start delimiter are skipped.  Frames with a bad checksum, or in escaped
+/// mode cut short by the next start delimiter, are handled according to the
+/// [`ChecksumPolicy`], reported as `io::ErrorKind::InvalidData` errors by default.
+#[derive(Debug, Clone)]
+pub struct XBeeCodec {
+    escaped: bool,
+    checksum_policy: ChecksumPolicy,
+    skips: SkipTracker,
+}
+
+impl XBeeCodec {
+    /// Create a codec for API mode without escapes, `AP=1`
+    pub fn new() -> Self {
+        Self {
+            escaped: false,
+            checksum_policy: ChecksumPolicy::default(),
+            skips: SkipTracker::default(),
+        }
+    }
+
+    /// Create a codec for API mode with escapes, `AP=2`
+    pub fn escaped() -> Self {
+        Self {
+            escaped: true,
+            ..Self::new()
+        }
+    }
+
+    /// Returns `true` in escaped mode
+    pub fn is_escaped(&self) -> bool {
+        self.escaped
+    }
+
+    /// Set what happens to frames failing their checksum
+    pub fn set_checksum_policy(&mut self, policy: ChecksumPolicy) {
+        self.checksum_policy = policy;
+    }
+
+    /// Returns what happens to frames failing their checksum
+    pub fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
+    /// Returns a handle on the counters of the bytes skipped between frames
+    pub fn resync_stats(&self) -> ResyncStats {
+        self.skips.stats()
+    }
+}
+
+impl Default for XBeeCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XBeeCodec {
+    fn next_frame(&mut self, src: &mut BytesMut) -> Option<Result<ApiFrame, Corrupt>> {
+        let start = match src.iter().position(|&b| b == START) {
+            Some(start) => start,
+            None => {
+                self.skips.skip(src.len());
+                src.clear();
+                return None;
+            }
+        };
+        self.skips.skip(start);
+        src.advance(start);
+
+        // Unescape the length, frame data and checksum, as far as they arrived
+        let mut frame = Vec::new();
+        let mut raw_len = 1;
+        // Frame bytes up to the checksum, known once the length arrived
+        let mut total = usize::MAX;
+        while frame.len() < total {
+            let byte = match src.get(raw_len) {
+                Some(&byte) => byte,
+                None => return None,
+            };
+            let byte = match byte {
+                START if self.escaped => {
+                    let bytes = src.split_to(raw_len);
+                    return Some(Err(self.corrupt(bytes, "XBee frame cut short")));
+                }
+                ESCAPE if self.escaped => match src.get(raw_len + 1) {
+                    Some(&escaped) => {
+                        raw_len += 1;
+                        escaped ^ 0x20
+                    }
+                    None => return None,
+                },
+                byte => byte,
+            };
+            raw_len += 1;
+            frame.push(byte);
+            if frame.len() == 2 {
+                total = 2 + usize::from(u16::from_be_bytes([frame[0], frame[1]])) + 1;
+            }
+        }
+
+        let bytes = src.split_to(raw_len);
+        let data = &frame[2..frame.len() - 1];
+        if data.is_empty() || checksum(data) != frame[frame.len() - 1] {
+            return Some(Err(self.corrupt(bytes, "XBee checksum mismatch")));
+        }
+        match ApiFrame::parse(data) {
+            Ok(frame) => {
+                self.skips.frame();
+                Some(Ok(frame))
+            }
+            Err(e) => {
+                self.skips.corrupt(bytes.len());
+                Some(Err(Corrupt {
+                    bytes: bytes.freeze(),
+                    error: e,
+                }))
+            }
+        }
+    }
+
+    fn corrupt(&mut self, bytes: BytesMut, message: &str) -> Corrupt {
+        self.skips.corrupt(bytes.len());
+        Corrupt {
+            bytes: bytes.freeze(),
+            error: invalid(message),
+        }
+    }
+}
+
+impl Decoder for XBeeCodec {
+    type Item = ApiFrame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_with_policy(self.checksum_policy, || self.next_frame(src))
+    }
+}
+
+impl Checksummed for XBeeCodec {
+    fn decode_frame(&mut self, src: &mut BytesMut) -> io::Result<Option<Frame<ApiFrame>>> {
+        Ok(self.next_frame(src).map(Frame::from))
+    }
+}
+
+impl Encoder<ApiFrame> for XBeeCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: ApiFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut data = Vec::new();
+        item.write(&mut data);
+        let len = u16::try_from(data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "XBee frame data too long"))?;
+        let mut frame = len.to_be_bytes().to_vec();
+        frame.extend_from_slice(&data);
+        frame.push(checksum(&data));
+
+        dst.reserve(1 + frame.len() * 2);
+        dst.put_u8(START);
+        for byte in frame {
+            if self.escaped && matches!(byte, START | ESCAPE | XON | XOFF) {
+                dst.put_u8(ESCAPE);
+                dst.put_u8(byte ^ 0x20);
+            } else {
+                dst.put_u8(byte);
+            }
+        }
+        Ok(())
+    }
+}
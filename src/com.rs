@@ -0,0 +1,349 @@
+//! Opening Windows COM ports by name or device path
+//!
+//! `CreateFileW` only finds `COM1` to `COM9` by their bare name, higher numbers need the
+//! `\\.\` device namespace prefix.  Ports are therefore always opened through their device
+//! path, which is also what device interface paths like `\\?\USB#VID_0403&PID_6001#...` are.
+//!
+//! The driver modes of the RTS and DTR lines, which `serialport` doesn't expose, are set
+//! through the device control block (DCB) of the open port.  The driver queues are sized
+//! with `SetupComm` and purged with `PurgeComm`.
+use crate::config::{get_dcb, put_dcb, update_dcb};
+use crate::settings::Settings;
+use crate::SerialPort;
+
+use windows_sys::Win32::Devices::Communication::{
+    ClearCommError, PurgeComm, SetupComm, DCB, PURGE_RXABORT, PURGE_RXCLEAR, PURGE_TXABORT,
+    PURGE_TXCLEAR,
+};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, GENERIC_READ, GENERIC_WRITE, HANDLE,
+    INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, FILE_ATTRIBUTE_NORMAL, FILE_FLAG_OVERLAPPED, FILE_SHARE_READ, FILE_SHARE_WRITE,
+    OPEN_EXISTING,
+};
+use windows_sys::Win32::System::Threading::CreateMutexW;
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::{FromRawHandle, RawHandle};
+use std::ptr;
+
+/// Driver control of the RTS line, the `fRtsControl` field of the DCB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtsControl {
+    /// RTS is low and only changes with `write_request_to_send`
+    Disable,
+    /// RTS is high and only changes with `write_request_to_send`
+    Enable,
+    /// The driver raises RTS while there is room in its input buffer (hardware flow control)
+    Handshake,
+    /// The driver raises RTS while there are bytes to transmit, switching the direction of
+    /// RS-485 transceivers wired to it
+    Toggle,
+}
+
+/// Driver control of the DTR line, the `fDtrControl` field of the DCB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtrControl {
+    /// DTR is low and only changes with `write_data_terminal_ready`
+    Disable,
+    /// DTR is high and only changes with `write_data_terminal_ready`
+    Enable,
+    /// The driver raises DTR while there is room in its input buffer
+    Handshake,
+}
+
+// Offsets of the two bit wide fields in `DCB::_bitfield`
+const DTR_CONTROL_SHIFT: u32 = 4;
+pub(crate) const RTS_CONTROL_SHIFT: u32 = 12;
+
+impl RtsControl {
+    pub(crate) fn bits(self) -> u32 {
+        match self {
+            RtsControl::Disable => 0,
+            RtsControl::Enable => 1,
+            RtsControl::Handshake => 2,
+            RtsControl::Toggle => 3,
+        }
+    }
+}
+
+impl DtrControl {
+    pub(crate) fn bits(self) -> u32 {
+        match self {
+            DtrControl::Disable => 0,
+            DtrControl::Enable => 1,
+            DtrControl::Handshake => 2,
+        }
+    }
+}
+
+pub(crate) fn set_rts_control(handle: RawHandle, control: RtsControl) -> io::Result<()> {
+    update_dcb(handle, |dcb| {
+        set_field(dcb, RTS_CONTROL_SHIFT, control.bits())
+    })
+}
+
+pub(crate) fn set_dtr_control(handle: RawHandle, control: DtrControl) -> io::Result<()> {
+    update_dcb(handle, |dcb| {
+        set_field(dcb, DTR_CONTROL_SHIFT, control.bits())
+    })
+}
+
+pub(crate) fn set_field(dcb: &mut DCB, shift: u32, value: u32) {
+    dcb._bitfield = (dcb._bitfield & !(0b11 << shift)) | (value << shift);
+}
+
+/// What [`SerialStream::purge`](crate::SerialStream::purge) throws away
+///
+/// The fields map to the flags of `PurgeComm`, plus `ClearCommError` for `reset_errors`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Purge {
+    /// Abort the read in flight (`PURGE_RXABORT`), it is reissued by the next read
+    pub abort_read: bool,
+    /// Abort the write in flight (`PURGE_TXABORT`), its unsent bytes are dropped
+    pub abort_write: bool,
+    /// Drop the received bytes not read yet (`PURGE_RXCLEAR`)
+    pub clear_input: bool,
+    /// Drop the bytes waiting to be transmitted (`PURGE_TXCLEAR`)
+    pub clear_output: bool,
+    /// Clear the error state of the driver, which blocks every operation after an error when
+    /// the port aborts on errors
+    pub reset_errors: bool,
+}
+
+impl Purge {
+    /// Everything: abort both directions, drop both buffers and reset the errors
+    pub const ALL: Purge = Purge {
+        abort_read: true,
+        abort_write: true,
+        clear_input: true,
+        clear_output: true,
+        reset_errors: true,
+    };
+
+    fn flags(self) -> u32 {
+        let flag = |set: bool, flag: u32| if set { flag } else { 0 };
+        flag(self.abort_read, PURGE_RXABORT)
+            | flag(self.abort_write, PURGE_TXABORT)
+            | flag(self.clear_input, PURGE_RXCLEAR)
+            | flag(self.clear_output, PURGE_TXCLEAR)
+    }
+}
+
+pub(crate) fn purge(handle: RawHandle, purge: Purge) -> io::Result<()> {
+    let handle = handle as HANDLE;
+    let flags = purge.flags();
+    // SAFETY: plain calls, the statistics aren't asked for
+    unsafe {
+        if flags != 0 && PurgeComm(handle, flags) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut errors = 0;
+        if purge.reset_errors && ClearCommError(handle, &mut errors, ptr::null_mut()) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn set_buffer_sizes(handle: RawHandle, input: u32, output: u32) -> io::Result<()> {
+    // SAFETY: plain call
+    if unsafe { SetupComm(handle as HANDLE, input, output) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns the device path of the port `path`
+///
+/// `COM10`, `COM10:` and `\\.\COM10` all give `\\.\COM10`.  Paths already in a device
+/// namespace are kept as they are.
+pub(crate) fn device_path(path: &str) -> String {
+    if path.starts_with(r"\\") {
+        return path.to_owned();
+    }
+    format!(r"\\.\{}", path.trim_end_matches(':'))
+}
+
+/// Device control block of a port before it was opened
+#[derive(Clone, Copy)]
+pub(crate) struct Original(DCB);
+
+impl Original {
+    /// Apply the saved device control block to `handle`
+    pub(crate) fn restore(&self, handle: RawHandle) -> io::Result<()> {
+        put_dcb(handle, &self.0)
+    }
+}
+
+impl fmt::Debug for Original {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Original").finish()
+    }
+}
+
+/// Open the port at the device `path` for overlapped I/O, with the settings of `builder`
+///
+/// `serialport` applies the settings of the builder on a blocking handle, which is then
+/// replaced by an overlapped one carrying the same settings.  A port that isn't `exclusive`
+/// is opened with read and write sharing, which only some virtual port drivers allow, and
+/// configured on the shared handle directly since `serialport` can't open it.
+///
+/// With `restore`, the device control block is read from the overlapped handle and returned
+/// before the settings are applied to it, and the blocking handle isn't opened at all since
+/// it would change them first.
+pub(crate) fn open(
+    builder: &crate::SerialPortBuilder,
+    path: &str,
+    exclusive: bool,
+    restore: bool,
+) -> crate::Result<(mio_serial::SerialStream, Option<Original>)> {
+    let settings = Settings::from_builder(builder);
+    let share_mode = if exclusive {
+        0
+    } else {
+        FILE_SHARE_READ | FILE_SHARE_WRITE
+    };
+    let settings = if exclusive && !restore {
+        let blocking = serialport::COMPort::open(&builder.clone().path(path))?;
+        LineSettings::read(&blocking)?
+    } else {
+        LineSettings::from(&settings)
+    };
+
+    let wide: Vec<u16> = OsStr::new(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    // SAFETY: the path is NUL terminated, the result is checked
+    let handle = unsafe {
+        CreateFileW(
+            wide.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            share_mode,
+            ptr::null(),
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL | FILE_FLAG_OVERLAPPED,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    // SAFETY: the handle was just opened and is owned by the port from now on
+    let mut port = unsafe { mio_serial::SerialStream::from_raw_handle(handle as RawHandle) };
+    let original = match restore {
+        true => Some(Original(get_dcb(handle as RawHandle)?)),
+        false => None,
+    };
+    settings.apply(&mut port)?;
+    Ok((port, original))
+}
+
+/// The settings carried over to the overlapped handle
+struct LineSettings {
+    baud_rate: u32,
+    data_bits: crate::DataBits,
+    parity: crate::Parity,
+    stop_bits: crate::StopBits,
+    flow_control: crate::FlowControl,
+}
+
+impl LineSettings {
+    fn read(port: &serialport::COMPort) -> crate::Result<Self> {
+        Ok(Self {
+            baud_rate: port.baud_rate()?,
+            data_bits: port.data_bits()?,
+            parity: port.parity()?,
+            stop_bits: port.stop_bits()?,
+            flow_control: port.flow_control()?,
+        })
+    }
+
+    fn apply(&self, port: &mut mio_serial::SerialStream) -> crate::Result<()> {
+        port.set_baud_rate(self.baud_rate)?;
+        port.set_data_bits(self.data_bits)?;
+        port.set_parity(self.parity)?;
+        port.set_stop_bits(self.stop_bits)?;
+        port.set_flow_control(self.flow_control)
+    }
+}
+
+impl From<&Settings> for LineSettings {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            flow_control: settings.flow_control,
+        }
+    }
+}
+
+/// A named object marking a port as used by this process, see
+/// [`SerialStream::set_exclusive`](crate::SerialStream::set_exclusive)
+///
+/// The object lives as long as a handle to it is open, so the lock is released when the port
+/// is closed or the process exits, however it exits.
+#[derive(Debug)]
+pub(crate) struct PortLock(HANDLE);
+
+// SAFETY: the handle is only closed
+unsafe impl Send for PortLock {}
+unsafe impl Sync for PortLock {}
+
+impl PortLock {
+    /// Take the lock of the port at `path`
+    ///
+    /// Returns `None` if the lock can't be created at all, like in a sandbox without access to
+    /// the global namespace: the lock is best effort.
+    pub(crate) fn acquire(path: &str) -> crate::Result<Option<Self>> {
+        let name = lock_name(path);
+        let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        // SAFETY: the name is NUL terminated, the result is checked
+        let (handle, error) = unsafe {
+            let handle = CreateMutexW(ptr::null(), 0, wide.as_ptr());
+            (handle, GetLastError())
+        };
+        if handle.is_null() {
+            log::debug!(
+                "unable to create {}: {}",
+                name,
+                io::Error::from_raw_os_error(error as i32)
+            );
+            return Ok(None);
+        }
+        let lock = Self(handle);
+        if error == ERROR_ALREADY_EXISTS {
+            return Err(crate::Error::new(
+                crate::ErrorKind::NoDevice,
+                format!("{} is used exclusively by another port", path),
+            ));
+        }
+        Ok(Some(lock))
+    }
+}
+
+impl Drop for PortLock {
+    fn drop(&mut self) {
+        // SAFETY: the handle is owned
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// Returns the name of the lock of the port at `path`, the same for every spelling of it
+fn lock_name(path: &str) -> String {
+    let device = device_path(path).to_uppercase();
+    // Backslashes separate the namespace from the name
+    let device = device
+        .trim_start_matches(&['\\', '.', '?'][..])
+        .replace('\\', "_");
+    format!(r"Global\tokio-serial-{}", device)
+}
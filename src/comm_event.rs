@@ -0,0 +1,229 @@
+//! Communication events of Windows COM ports
+//!
+//! [`CommEvents`] is a stream of the events selected with `SetCommMask`, each reported by an
+//! overlapped `WaitCommEvent` on a duplicate of the port handle so the port can be read and
+//! written while the events are watched.
+use crate::overlapped::{poll_overlapped, wait_overlapped, Channel};
+
+use futures::Stream;
+use windows_sys::Win32::Devices::Communication::{
+    SetCommMask, WaitCommEvent, COMM_EVENT_MASK, EV_BREAK, EV_CTS, EV_DSR, EV_ERR, EV_RING,
+    EV_RLSD, EV_RXCHAR, EV_RXFLAG,
+};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, DuplicateHandle, GetLastError, DUPLICATE_SAME_ACCESS, ERROR_IO_PENDING, HANDLE,
+};
+use windows_sys::Win32::System::Threading::GetCurrentProcess;
+use windows_sys::Win32::System::IO::OVERLAPPED;
+
+use std::fmt;
+use std::io;
+use std::os::windows::io::RawHandle;
+use std::pin::Pin;
+use std::ptr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A communication event of a Windows COM port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommEvent {
+    /// A character was received (`EV_RXCHAR`)
+    RxChar,
+    /// The event character was received (`EV_RXFLAG`)
+    RxFlag,
+    /// A break was detected on input (`EV_BREAK`)
+    Break,
+    /// A framing, overrun or parity error occurred (`EV_ERR`)
+    Error,
+    /// A ring indicator was detected (`EV_RING`)
+    Ring,
+    /// The receive line signal detect (carrier detect) changed state (`EV_RLSD`)
+    Rlsd,
+    /// The clear to send signal changed state (`EV_CTS`)
+    Cts,
+    /// The data set ready signal changed state (`EV_DSR`)
+    Dsr,
+}
+
+impl CommEvent {
+    /// Every event, in the order the events of a single wait are reported
+    pub const ALL: [CommEvent; 8] = [
+        CommEvent::Error,
+        CommEvent::Break,
+        CommEvent::Rlsd,
+        CommEvent::Cts,
+        CommEvent::Dsr,
+        CommEvent::Ring,
+        CommEvent::RxFlag,
+        CommEvent::RxChar,
+    ];
+
+    fn mask(self) -> COMM_EVENT_MASK {
+        match self {
+            CommEvent::RxChar => EV_RXCHAR,
+            CommEvent::RxFlag => EV_RXFLAG,
+            CommEvent::Break => EV_BREAK,
+            CommEvent::Error => EV_ERR,
+            CommEvent::Ring => EV_RING,
+            CommEvent::Rlsd => EV_RLSD,
+            CommEvent::Cts => EV_CTS,
+            CommEvent::Dsr => EV_DSR,
+        }
+    }
+}
+
+/// Stream of the communication events of a port, see
+/// [`SerialStream::comm_events`](crate::SerialStream::comm_events)
+///
+/// The stream ends when the event mask of the port is changed, by another call to
+/// `comm_events` for instance.
+pub struct CommEvents {
+    handle: HANDLE,
+    mask: COMM_EVENT_MASK,
+    wait: Option<Box<Wait>>,
+    /// Events of the last completed wait not yet yielded
+    pending: COMM_EVENT_MASK,
+    done: bool,
+    channel: Channel,
+}
+
+// SAFETY: the handle may be used from any thread, the wait is only touched through `&mut self`
+unsafe impl Send for CommEvents {}
+unsafe impl Sync for CommEvents {}
+
+/// A `WaitCommEvent` in flight, boxed so the kernel's pointers to it stay valid
+struct Wait {
+    overlapped: OVERLAPPED,
+    events: COMM_EVENT_MASK,
+}
+
+impl CommEvents {
+    /// Watch `events` on a duplicate of the port handle `port`
+    pub(crate) fn new(port: RawHandle, events: &[CommEvent]) -> io::Result<Self> {
+        let mask = events.iter().fold(0, |mask, event| mask | event.mask());
+        let channel = Channel::new()?;
+        let mut handle = ptr::null_mut();
+        // SAFETY: plain handle duplication, the result is checked
+        unsafe {
+            let process = GetCurrentProcess();
+            if DuplicateHandle(
+                process,
+                port as HANDLE,
+                process,
+                &mut handle,
+                0,
+                0,
+                DUPLICATE_SAME_ACCESS,
+            ) == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        let events = Self {
+            handle,
+            mask,
+            wait: None,
+            pending: 0,
+            done: false,
+            channel,
+        };
+        // SAFETY: plain call on an owned handle
+        if unsafe { SetCommMask(events.handle, mask) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(events)
+    }
+
+    /// Returns the duplicate of the port handle the events are watched on
+    #[cfg(feature = "events")]
+    pub(crate) fn raw_handle(&self) -> RawHandle {
+        self.handle as RawHandle
+    }
+
+    fn next_pending(&mut self) -> Option<CommEvent> {
+        let event = CommEvent::ALL
+            .iter()
+            .copied()
+            .find(|event| self.pending & event.mask() != 0)?;
+        self.pending &= !event.mask();
+        Some(event)
+    }
+
+    fn start_wait(&mut self) -> io::Result<()> {
+        let mut wait = Box::new(Wait {
+            overlapped: OVERLAPPED {
+                hEvent: self.channel.event(),
+                ..Default::default()
+            },
+            events: 0,
+        });
+        // SAFETY: the wait is kept in `self` until the operation completes
+        let started = unsafe { WaitCommEvent(self.handle, &mut wait.events, &mut wait.overlapped) };
+        if started == 0 {
+            match unsafe { GetLastError() } {
+                ERROR_IO_PENDING => {}
+                e => return Err(io::Error::from_raw_os_error(e as i32)),
+            }
+        }
+        self.wait = Some(wait);
+        Ok(())
+    }
+}
+
+impl Stream for CommEvents {
+    type Item = io::Result<CommEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.next_pending() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            let wait = match &this.wait {
+                Some(wait) => wait,
+                None => {
+                    if let Err(e) = this.start_wait() {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    continue;
+                }
+            };
+
+            // Register before checking so a completion in between still wakes the task
+            this.channel.register(cx.waker());
+            let result = futures::ready!(poll_overlapped(this.handle, &wait.overlapped));
+            let wait = this.wait.take().expect("wait in flight");
+            if let Err(e) = result {
+                return Poll::Ready(Some(Err(e)));
+            }
+            // A wait completing without events means the mask was changed
+            this.done = wait.events == 0;
+            this.pending = wait.events & this.mask;
+        }
+    }
+}
+
+impl Drop for CommEvents {
+    fn drop(&mut self) {
+        // The kernel may write to the wait until the operation completes
+        if let Some(wait) = self.wait.take() {
+            wait_overlapped(self.handle, &wait.overlapped, Duration::ZERO);
+        }
+        // SAFETY: the duplicate handle is owned and nothing uses it anymore
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+impl fmt::Debug for CommEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommEvents")
+            .field("mask", &self.mask)
+            .field("pending", &self.pending)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
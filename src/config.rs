@@ -0,0 +1,762 @@
+//! Port configuration without the I/O
+//!
+//! The functions of this module change the settings and control lines of a port given only
+//! its file descriptor (unix) or handle (Windows), so tools whose bytes travel some other way
+//! (their own event loop, a vendor library, a descriptor handed over by another process) can
+//! reuse the configuration code of `tokio-serial`.  Nothing here reads or writes data, waits
+//! or needs a runtime.
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn main() -> std::io::Result<()> {
+//! use std::fs::OpenOptions;
+//! use tokio_serial::config;
+//!
+//! let file = OpenOptions::new().read(true).write(true).open("/dev/ttyUSB0")?;
+//! config::configure(&file, &tokio_serial::new("", 250_000))?;
+//! config::write_data_terminal_ready(&file, false)?;
+//! # Ok(())
+//! # }
+//! # #[cfg(windows)]
+//! # fn main() {}
+//! ```
+use crate::settings::Settings;
+use crate::{DataBits, FlowControl, Parity, SerialPortBuilder, StopBits};
+
+use std::io;
+
+/// Levels of the modem input lines, see `read_modem_lines`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModemLines {
+    /// Clear to send
+    pub cts: bool,
+    /// Data set ready
+    pub dsr: bool,
+    /// Ring indicator
+    pub ri: bool,
+    /// Data carrier detect
+    pub cd: bool,
+}
+
+#[cfg(unix)]
+pub use self::unix::*;
+#[cfg(windows)]
+pub use self::windows::*;
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    use std::convert::TryFrom;
+    use std::mem::MaybeUninit;
+    use std::os::unix::io::{AsRawFd, RawFd};
+    #[cfg(target_os = "linux")]
+    use std::time::Duration;
+
+    /// Returns the terminal settings of `fd`
+    pub fn termios(fd: &impl AsRawFd) -> io::Result<libc::termios> {
+        get_termios(fd.as_raw_fd())
+    }
+
+    /// Replace the terminal settings of `fd`, effective immediately
+    pub fn set_termios(fd: &impl AsRawFd, termios: &libc::termios) -> io::Result<()> {
+        put_termios(fd.as_raw_fd(), termios)
+    }
+
+    /// Read the terminal settings of `fd`, change them with `f` and write them back
+    ///
+    /// For the flags `configure` doesn't know about, like `IGNBRK` or `PARMRK`.
+    pub fn modify_termios(fd: &impl AsRawFd, f: impl FnOnce(&mut libc::termios)) -> io::Result<()> {
+        let mut termios = termios(fd)?;
+        f(&mut termios);
+        set_termios(fd, &termios)
+    }
+
+    pub(crate) fn get_termios(fd: RawFd) -> io::Result<libc::termios> {
+        let mut termios = MaybeUninit::uninit();
+        // SAFETY: tcgetattr fills the whole struct on success
+        unsafe {
+            if libc::tcgetattr(fd, termios.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(termios.assume_init())
+        }
+    }
+
+    pub(crate) fn put_termios(fd: RawFd, termios: &libc::termios) -> io::Result<()> {
+        // SAFETY: the struct is initialized
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Put `fd` in raw mode with the line settings of `builder`
+    ///
+    /// Reads return whatever is available without waiting (`VMIN` and `VTIME` are zero), the
+    /// path and timeout of the builder are ignored.
+    pub fn configure(fd: &impl AsRawFd, builder: &SerialPortBuilder) -> io::Result<()> {
+        let settings = Settings::from_builder(builder);
+        modify_termios(fd, |t| {
+            // SAFETY: the struct is initialized
+            unsafe { libc::cfmakeraw(t) };
+            t.c_cflag |= libc::CLOCAL | libc::CREAD;
+            t.c_cc[libc::VMIN] = 0;
+            t.c_cc[libc::VTIME] = 0;
+            set_data_bits(t, settings.data_bits);
+            set_parity(t, settings.parity);
+            set_stop_bits(t, settings.stop_bits);
+            set_flow_control(t, settings.flow_control);
+        })?;
+        set_baud_rate(fd, settings.baud_rate)?;
+        if let Some(level) = settings.dtr_on_open {
+            write_data_terminal_ready(fd, level)?;
+        }
+        Ok(())
+    }
+
+    fn set_data_bits(t: &mut libc::termios, data_bits: DataBits) {
+        t.c_cflag &= !libc::CSIZE;
+        t.c_cflag |= match data_bits {
+            DataBits::Five => libc::CS5,
+            DataBits::Six => libc::CS6,
+            DataBits::Seven => libc::CS7,
+            DataBits::Eight => libc::CS8,
+        };
+    }
+
+    fn set_parity(t: &mut libc::termios, parity: Parity) {
+        t.c_cflag &= !(libc::PARENB | libc::PARODD);
+        t.c_iflag &= !libc::INPCK;
+        match parity {
+            Parity::None => {}
+            Parity::Odd => {
+                t.c_cflag |= libc::PARENB | libc::PARODD;
+                t.c_iflag |= libc::INPCK;
+            }
+            Parity::Even => {
+                t.c_cflag |= libc::PARENB;
+                t.c_iflag |= libc::INPCK;
+            }
+        }
+    }
+
+    fn set_stop_bits(t: &mut libc::termios, stop_bits: StopBits) {
+        match stop_bits {
+            StopBits::One => t.c_cflag &= !libc::CSTOPB,
+            StopBits::Two => t.c_cflag |= libc::CSTOPB,
+        }
+    }
+
+    fn set_flow_control(t: &mut libc::termios, flow_control: FlowControl) {
+        t.c_cflag &= !libc::CRTSCTS;
+        t.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+        match flow_control {
+            FlowControl::None => {}
+            FlowControl::Software => t.c_iflag |= libc::IXON | libc::IXOFF,
+            FlowControl::Hardware => t.c_cflag |= libc::CRTSCTS,
+        }
+    }
+
+    /// Set the baud rate of `fd`, in both directions
+    ///
+    /// Linux takes any rate the driver can approximate through `termios2`, the BSDs and macOS
+    /// any rate their driver accepts.  Elsewhere only the rates with a `Bxxx` constant work.
+    /// For the legacy custom divisors of old Linux drivers, see [`set_baud_rate_with`].
+    pub fn set_baud_rate(fd: &impl AsRawFd, baud_rate: u32) -> io::Result<()> {
+        set_speed(fd.as_raw_fd(), baud_rate)
+    }
+
+    /// How [`set_baud_rate_with`] programs a rate
+    #[cfg(target_os = "linux")]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum BaudMethod {
+        /// `termios2` with `BOTHER`, the driver approximating the rate, like `set_baud_rate`
+        ///
+        /// Also clears a custom divisor left by [`Divisor`](Self::Divisor).
+        Termios2,
+        /// A custom divisor of the UART's base clock, the `setserial spd_cust` way
+        ///
+        /// For old 16550 drivers without `BOTHER`: the divisor closest to the rate is set
+        /// with `TIOCSSERIAL` and `ASYNC_SPD_CUST`, and 38400 baud selects it.  Needs the
+        /// `CAP_SYS_ADMIN` capability on most kernels.
+        Divisor,
+    }
+
+    /// Set the baud rate of `fd` with `method`
+    #[cfg(target_os = "linux")]
+    pub fn set_baud_rate_with(
+        fd: &impl AsRawFd,
+        baud_rate: u32,
+        method: BaudMethod,
+    ) -> io::Result<()> {
+        let fd = fd.as_raw_fd();
+        match method {
+            BaudMethod::Termios2 => {
+                // Best effort, most drivers don't support the legacy interface at all
+                if let Ok(mut serial) = get_serial(fd) {
+                    if serial.flags & ASYNC_SPD_MASK != 0 {
+                        serial.flags &= !ASYNC_SPD_MASK;
+                        serial.custom_divisor = 0;
+                        put_serial(fd, &serial)?;
+                    }
+                }
+                set_speed(fd, baud_rate)
+            }
+            BaudMethod::Divisor => {
+                if baud_rate == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "baud rate must not be zero",
+                    ));
+                }
+                let mut serial = get_serial(fd)?;
+                let base = match u32::try_from(serial.baud_base) {
+                    Ok(base) if base > 0 => base,
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "driver reports no base clock",
+                        ))
+                    }
+                };
+                let divisor = ((base + baud_rate / 2) / baud_rate).max(1);
+                serial.flags = (serial.flags & !ASYNC_SPD_MASK) | ASYNC_SPD_CUST;
+                serial.custom_divisor = divisor as libc::c_int;
+                put_serial(fd, &serial)?;
+
+                let mut t = get_termios(fd)?;
+                // SAFETY: the struct is initialized
+                if unsafe { libc::cfsetspeed(&mut t, libc::B38400) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                put_termios(fd, &t)
+            }
+        }
+    }
+
+    /// `struct serial_struct` of `<linux/serial.h>`
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    struct SerialStruct {
+        kind: libc::c_int,
+        line: libc::c_int,
+        port: libc::c_uint,
+        irq: libc::c_int,
+        flags: libc::c_int,
+        xmit_fifo_size: libc::c_int,
+        custom_divisor: libc::c_int,
+        baud_base: libc::c_int,
+        close_delay: libc::c_ushort,
+        io_type: libc::c_char,
+        reserved_char: [libc::c_char; 1],
+        hub6: libc::c_int,
+        closing_wait: libc::c_ushort,
+        closing_wait2: libc::c_ushort,
+        iomem_base: *mut libc::c_uchar,
+        iomem_reg_shift: libc::c_ushort,
+        port_high: libc::c_uint,
+        iomap_base: libc::c_ulong,
+    }
+
+    #[cfg(target_os = "linux")]
+    const ASYNC_SPD_MASK: libc::c_int = 0x1030;
+    #[cfg(target_os = "linux")]
+    const ASYNC_SPD_CUST: libc::c_int = 0x0030;
+
+    #[cfg(target_os = "linux")]
+    fn get_serial(fd: RawFd) -> io::Result<SerialStruct> {
+        let mut serial = MaybeUninit::<SerialStruct>::uninit();
+        // SAFETY: TIOCGSERIAL fills the whole struct on success
+        unsafe {
+            if libc::ioctl(fd, libc::TIOCGSERIAL, serial.as_mut_ptr()) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(serial.assume_init())
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn put_serial(fd: RawFd, serial: &SerialStruct) -> io::Result<()> {
+        // SAFETY: the ioctl reads a `struct serial_struct`
+        if unsafe { libc::ioctl(fd, libc::TIOCSSERIAL, serial) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(all(
+        target_os = "linux",
+        not(any(target_arch = "powerpc", target_arch = "powerpc64"))
+    ))]
+    fn set_speed(fd: RawFd, baud_rate: u32) -> io::Result<()> {
+        // Not in `libc` for glibc, the same on every architecture with `termios2`
+        const CBAUD: libc::tcflag_t = 0o010017;
+
+        let mut t = MaybeUninit::<libc::termios2>::uninit();
+        // SAFETY: TCGETS2 fills the whole struct on success
+        let mut t = unsafe {
+            if libc::ioctl(fd, libc::TCGETS2, t.as_mut_ptr()) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            t.assume_init()
+        };
+        t.c_cflag &= !(CBAUD | CBAUD << libc::IBSHIFT);
+        t.c_cflag |= libc::BOTHER | libc::BOTHER << libc::IBSHIFT;
+        t.c_ispeed = baud_rate;
+        t.c_ospeed = baud_rate;
+        // SAFETY: the struct is initialized
+        if unsafe { libc::ioctl(fd, libc::TCSETS2, &t) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(all(
+        target_os = "linux",
+        not(any(target_arch = "powerpc", target_arch = "powerpc64"))
+    )))]
+    fn set_speed(fd: RawFd, baud_rate: u32) -> io::Result<()> {
+        let mut t = get_termios(fd)?;
+        // SAFETY: the struct is initialized
+        if unsafe { libc::cfsetspeed(&mut t, speed(baud_rate)?) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        put_termios(fd, &t)
+    }
+
+    /// The BSDs and macOS take the rate itself as the speed
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    fn speed(baud_rate: u32) -> io::Result<libc::speed_t> {
+        Ok(baud_rate as libc::speed_t)
+    }
+
+    /// Elsewhere the speed is one of the `Bxxx` constants
+    #[cfg(not(any(
+        all(
+            target_os = "linux",
+            not(any(target_arch = "powerpc", target_arch = "powerpc64"))
+        ),
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )))]
+    fn speed(baud_rate: u32) -> io::Result<libc::speed_t> {
+        Ok(match baud_rate {
+            50 => libc::B50,
+            75 => libc::B75,
+            110 => libc::B110,
+            134 => libc::B134,
+            150 => libc::B150,
+            200 => libc::B200,
+            300 => libc::B300,
+            600 => libc::B600,
+            1200 => libc::B1200,
+            1800 => libc::B1800,
+            2400 => libc::B2400,
+            4800 => libc::B4800,
+            9600 => libc::B9600,
+            19200 => libc::B19200,
+            38400 => libc::B38400,
+            57600 => libc::B57600,
+            115_200 => libc::B115200,
+            230_400 => libc::B230400,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            460_800 => libc::B460800,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            921_600 => libc::B921600,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            1_000_000 => libc::B1000000,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            2_000_000 => libc::B2000000,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            4_000_000 => libc::B4000000,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unsupported baud rate {}", baud_rate),
+                ))
+            }
+        })
+    }
+
+    /// Set the RTS line of `fd`
+    pub fn write_request_to_send(fd: &impl AsRawFd, level: bool) -> io::Result<()> {
+        write_line(fd.as_raw_fd(), libc::TIOCM_RTS, level)
+    }
+
+    /// Set the DTR line of `fd`
+    pub fn write_data_terminal_ready(fd: &impl AsRawFd, level: bool) -> io::Result<()> {
+        write_line(fd.as_raw_fd(), libc::TIOCM_DTR, level)
+    }
+
+    /// Set the DTR and RTS lines of `fd` together, leaving a line given `None` as it is
+    ///
+    /// Both lines change with a single `TIOCMSET`.
+    pub fn write_modem_lines(
+        fd: &impl AsRawFd,
+        dtr: Option<bool>,
+        rts: Option<bool>,
+    ) -> io::Result<()> {
+        let fd = fd.as_raw_fd();
+        let mut lines = read_lines(fd)?;
+        for (line, level) in [(libc::TIOCM_DTR, dtr), (libc::TIOCM_RTS, rts)].iter() {
+            match level {
+                Some(true) => lines |= line,
+                Some(false) => lines &= !line,
+                None => (),
+            }
+        }
+        // SAFETY: the ioctl reads an int
+        if unsafe { libc::ioctl(fd, libc::TIOCMSET, &lines) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Returns the level of the CTS line of `fd`
+    pub fn read_clear_to_send(fd: &impl AsRawFd) -> io::Result<bool> {
+        Ok(read_lines(fd.as_raw_fd())? & libc::TIOCM_CTS != 0)
+    }
+
+    /// Returns the level of the DSR line of `fd`
+    pub fn read_data_set_ready(fd: &impl AsRawFd) -> io::Result<bool> {
+        Ok(read_lines(fd.as_raw_fd())? & libc::TIOCM_DSR != 0)
+    }
+
+    /// Returns the level of the RI line of `fd`
+    pub fn read_ring_indicator(fd: &impl AsRawFd) -> io::Result<bool> {
+        Ok(read_lines(fd.as_raw_fd())? & libc::TIOCM_RI != 0)
+    }
+
+    /// Returns the level of the DCD line of `fd`
+    pub fn read_carrier_detect(fd: &impl AsRawFd) -> io::Result<bool> {
+        Ok(read_lines(fd.as_raw_fd())? & libc::TIOCM_CD != 0)
+    }
+
+    /// Returns the levels of all modem input lines of `fd` at once
+    pub fn read_modem_lines(fd: &impl AsRawFd) -> io::Result<ModemLines> {
+        let lines = read_lines(fd.as_raw_fd())?;
+        Ok(ModemLines {
+            cts: lines & libc::TIOCM_CTS != 0,
+            dsr: lines & libc::TIOCM_DSR != 0,
+            ri: lines & libc::TIOCM_RI != 0,
+            cd: lines & libc::TIOCM_CD != 0,
+        })
+    }
+
+    fn write_line(fd: RawFd, line: libc::c_int, level: bool) -> io::Result<()> {
+        let request = if level {
+            libc::TIOCMBIS
+        } else {
+            libc::TIOCMBIC
+        };
+        // SAFETY: the ioctl reads an int
+        if unsafe { libc::ioctl(fd, request, &line) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn read_lines(fd: RawFd) -> io::Result<libc::c_int> {
+        let mut lines: libc::c_int = 0;
+        // SAFETY: the ioctl writes an int
+        if unsafe { libc::ioctl(fd, libc::TIOCMGET, &mut lines) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(lines)
+    }
+
+    /// RS-485 mode of the Linux serial drivers, see `set_rs485`
+    ///
+    /// In RS-485 mode the driver drives RTS to switch the transceiver between transmitting
+    /// and receiving.
+    #[cfg(target_os = "linux")]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Rs485 {
+        /// Level of RTS while transmitting
+        pub rts_on_send: bool,
+        /// Level of RTS after transmitting
+        pub rts_after_send: bool,
+        /// Keep receiving while transmitting, to read back the local echo
+        pub rx_during_tx: bool,
+        /// Time between raising RTS and the first byte, rounded to milliseconds
+        pub delay_before_send: Duration,
+        /// Time between the last byte and dropping RTS, rounded to milliseconds
+        pub delay_after_send: Duration,
+    }
+
+    #[cfg(target_os = "linux")]
+    impl Default for Rs485 {
+        /// RTS high while transmitting, without delays
+        fn default() -> Self {
+            Self {
+                rts_on_send: true,
+                rts_after_send: false,
+                rx_during_tx: false,
+                delay_before_send: Duration::from_millis(0),
+                delay_after_send: Duration::from_millis(0),
+            }
+        }
+    }
+
+    /// `struct serial_rs485` of `<linux/serial.h>`
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    #[derive(Default)]
+    struct SerialRs485 {
+        flags: u32,
+        delay_rts_before_send: u32,
+        delay_rts_after_send: u32,
+        padding: [u32; 5],
+    }
+
+    #[cfg(target_os = "linux")]
+    const SER_RS485_ENABLED: u32 = 1 << 0;
+    #[cfg(target_os = "linux")]
+    const SER_RS485_RTS_ON_SEND: u32 = 1 << 1;
+    #[cfg(target_os = "linux")]
+    const SER_RS485_RTS_AFTER_SEND: u32 = 1 << 2;
+    #[cfg(target_os = "linux")]
+    const SER_RS485_RX_DURING_TX: u32 = 1 << 4;
+
+    /// Turn the RS-485 mode of the driver of `fd` on with `Some` or off with `None`
+    ///
+    /// Fails with `ENOTTY` on drivers without RS-485 support, which includes USB adapters
+    /// doing the direction switching in hardware.
+    #[cfg(target_os = "linux")]
+    pub fn set_rs485(fd: &impl AsRawFd, rs485: Option<Rs485>) -> io::Result<()> {
+        let millis = |delay: Duration| delay.as_millis().min(u32::MAX as u128) as u32;
+        let config = match rs485 {
+            None => SerialRs485::default(),
+            Some(rs485) => {
+                let flag = |set: bool, flag: u32| if set { flag } else { 0 };
+                SerialRs485 {
+                    flags: SER_RS485_ENABLED
+                        | flag(rs485.rts_on_send, SER_RS485_RTS_ON_SEND)
+                        | flag(rs485.rts_after_send, SER_RS485_RTS_AFTER_SEND)
+                        | flag(rs485.rx_during_tx, SER_RS485_RX_DURING_TX),
+                    delay_rts_before_send: millis(rs485.delay_before_send),
+                    delay_rts_after_send: millis(rs485.delay_after_send),
+                    ..Default::default()
+                }
+            }
+        };
+        // SAFETY: the ioctl reads a `struct serial_rs485`
+        if unsafe { libc::ioctl(fd.as_raw_fd(), libc::TIOCSRS485, &config) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::*;
+
+    pub use crate::com::{DtrControl, RtsControl};
+
+    use windows_sys::Win32::Devices::Communication::{
+        EscapeCommFunction, GetCommModemStatus, GetCommState, SetCommState, CLRDTR, CLRRTS, DCB,
+        EVENPARITY, MS_CTS_ON, MS_DSR_ON, MS_RING_ON, MS_RLSD_ON, NOPARITY, ODDPARITY, ONESTOPBIT,
+        SETDTR, SETRTS, TWOSTOPBITS,
+    };
+    use windows_sys::Win32::Foundation::HANDLE;
+
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+
+    // Flags in `DCB::_bitfield`
+    const F_BINARY: u32 = 1 << 0;
+    const F_PARITY: u32 = 1 << 1;
+    const F_OUTX_CTS_FLOW: u32 = 1 << 2;
+    const F_OUTX: u32 = 1 << 8;
+    const F_INX: u32 = 1 << 9;
+    const XON: i8 = 0x11;
+    const XOFF: i8 = 0x13;
+
+    /// Read the device control block of `handle`, change it with `f` and write it back
+    ///
+    /// For the fields `configure` doesn't know about, like the error character or the
+    /// XON/XOFF limits.
+    pub fn modify_dcb(handle: &impl AsRawHandle, f: impl FnOnce(&mut DCB)) -> io::Result<()> {
+        update_dcb(handle.as_raw_handle(), f)
+    }
+
+    pub(crate) fn update_dcb(handle: RawHandle, f: impl FnOnce(&mut DCB)) -> io::Result<()> {
+        let mut dcb = get_dcb(handle)?;
+        f(&mut dcb);
+        put_dcb(handle, &dcb)
+    }
+
+    pub(crate) fn get_dcb(handle: RawHandle) -> io::Result<DCB> {
+        let mut dcb = DCB {
+            DCBlength: std::mem::size_of::<DCB>() as u32,
+            ..Default::default()
+        };
+        // SAFETY: `DCBlength` is initialized, GetCommState fills the rest
+        if unsafe { GetCommState(handle as HANDLE, &mut dcb) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(dcb)
+    }
+
+    pub(crate) fn put_dcb(handle: RawHandle, dcb: &DCB) -> io::Result<()> {
+        // SAFETY: plain call with a DCB filled by GetCommState
+        if unsafe { SetCommState(handle as HANDLE, dcb) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Apply the line settings of `builder` to `handle`
+    ///
+    /// The path and timeout of the builder are ignored.
+    pub fn configure(handle: &impl AsRawHandle, builder: &SerialPortBuilder) -> io::Result<()> {
+        let settings = Settings::from_builder(builder);
+        modify_dcb(handle, |dcb| {
+            dcb.BaudRate = settings.baud_rate;
+            dcb.ByteSize = match settings.data_bits {
+                DataBits::Five => 5,
+                DataBits::Six => 6,
+                DataBits::Seven => 7,
+                DataBits::Eight => 8,
+            };
+            dcb.Parity = match settings.parity {
+                Parity::None => NOPARITY,
+                Parity::Odd => ODDPARITY,
+                Parity::Even => EVENPARITY,
+            };
+            dcb.StopBits = match settings.stop_bits {
+                StopBits::One => ONESTOPBIT,
+                StopBits::Two => TWOSTOPBITS,
+            };
+            let mut flags = dcb._bitfield & !(F_PARITY | F_OUTX_CTS_FLOW | F_OUTX | F_INX);
+            flags |= F_BINARY;
+            if settings.parity != Parity::None {
+                flags |= F_PARITY;
+            }
+            match settings.flow_control {
+                FlowControl::None => {}
+                FlowControl::Software => {
+                    flags |= F_OUTX | F_INX;
+                    dcb.XonChar = XON;
+                    dcb.XoffChar = XOFF;
+                }
+                FlowControl::Hardware => flags |= F_OUTX_CTS_FLOW,
+            }
+            dcb._bitfield = flags;
+            let rts = if settings.flow_control == FlowControl::Hardware {
+                RtsControl::Handshake
+            } else {
+                RtsControl::Enable
+            };
+            crate::com::set_field(dcb, crate::com::RTS_CONTROL_SHIFT, rts.bits());
+        })?;
+        if let Some(level) = settings.dtr_on_open {
+            write_data_terminal_ready(handle, level)?;
+        }
+        Ok(())
+    }
+
+    /// Set the baud rate of `handle`, any rate the driver accepts
+    pub fn set_baud_rate(handle: &impl AsRawHandle, baud_rate: u32) -> io::Result<()> {
+        modify_dcb(handle, |dcb| dcb.BaudRate = baud_rate)
+    }
+
+    /// Set the driver control of the RTS line of `handle`
+    pub fn set_rts_control(handle: &impl AsRawHandle, control: RtsControl) -> io::Result<()> {
+        crate::com::set_rts_control(handle.as_raw_handle(), control)
+    }
+
+    /// Set the driver control of the DTR line of `handle`
+    pub fn set_dtr_control(handle: &impl AsRawHandle, control: DtrControl) -> io::Result<()> {
+        crate::com::set_dtr_control(handle.as_raw_handle(), control)
+    }
+
+    /// Set the RTS line of `handle`
+    pub fn write_request_to_send(handle: &impl AsRawHandle, level: bool) -> io::Result<()> {
+        escape(handle.as_raw_handle(), if level { SETRTS } else { CLRRTS })
+    }
+
+    /// Set the DTR line of `handle`
+    pub fn write_data_terminal_ready(handle: &impl AsRawHandle, level: bool) -> io::Result<()> {
+        escape(handle.as_raw_handle(), if level { SETDTR } else { CLRDTR })
+    }
+
+    /// Set the DTR and RTS lines of `handle`, DTR first, leaving a line given `None` as it is
+    ///
+    /// Windows has no call changing both lines at once, RTS follows DTR within
+    /// microseconds.
+    pub fn write_modem_lines(
+        handle: &impl AsRawHandle,
+        dtr: Option<bool>,
+        rts: Option<bool>,
+    ) -> io::Result<()> {
+        if let Some(level) = dtr {
+            write_data_terminal_ready(handle, level)?;
+        }
+        if let Some(level) = rts {
+            write_request_to_send(handle, level)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the level of the CTS line of `handle`
+    pub fn read_clear_to_send(handle: &impl AsRawHandle) -> io::Result<bool> {
+        Ok(modem_status(handle.as_raw_handle())? & MS_CTS_ON != 0)
+    }
+
+    /// Returns the level of the DSR line of `handle`
+    pub fn read_data_set_ready(handle: &impl AsRawHandle) -> io::Result<bool> {
+        Ok(modem_status(handle.as_raw_handle())? & MS_DSR_ON != 0)
+    }
+
+    /// Returns the level of the RI line of `handle`
+    pub fn read_ring_indicator(handle: &impl AsRawHandle) -> io::Result<bool> {
+        Ok(modem_status(handle.as_raw_handle())? & MS_RING_ON != 0)
+    }
+
+    /// Returns the level of the DCD line of `handle`
+    pub fn read_carrier_detect(handle: &impl AsRawHandle) -> io::Result<bool> {
+        Ok(modem_status(handle.as_raw_handle())? & MS_RLSD_ON != 0)
+    }
+
+    /// Returns the levels of all modem input lines of `handle` at once
+    pub fn read_modem_lines(handle: &impl AsRawHandle) -> io::Result<ModemLines> {
+        let status = modem_status(handle.as_raw_handle())?;
+        Ok(ModemLines {
+            cts: status & MS_CTS_ON != 0,
+            dsr: status & MS_DSR_ON != 0,
+            ri: status & MS_RING_ON != 0,
+            cd: status & MS_RLSD_ON != 0,
+        })
+    }
+
+    fn escape(handle: RawHandle, function: u32) -> io::Result<()> {
+        // SAFETY: plain call
+        if unsafe { EscapeCommFunction(handle as HANDLE, function) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn modem_status(handle: RawHandle) -> io::Result<u32> {
+        let mut status = 0;
+        // SAFETY: plain call writing a u32
+        if unsafe { GetCommModemStatus(handle as HANDLE, &mut status) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,410 @@
+//! Interactive terminal sessions with a port
+//!
+//! [`Console`] bridges the terminal the program runs in with a port, like minicom or
+//! `screen`: keys go to the port as they are typed and what the port sends is shown.  The
+//! terminal is put in raw mode for the session and restored afterwards, so line editing,
+//! `Ctrl-C` and the other special keys reach the device too.  The session ends with the
+//! escape key, `Ctrl-]` by default.
+//!
+//! ```no_run
+//! use tokio_serial::console::{Console, Newline};
+//! use tokio_serial::SerialPortBuilderExt;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let mut port = tokio_serial::new("/dev/ttyUSB0", 115200).open_native_async()?;
+//! let mut console = Console::new();
+//! console.set_send_newline(Newline::CrLf);
+//! eprintln!("Connected, Ctrl-] to quit\r");
+//! console.run(&mut port).await?;
+//! # Ok(())
+//! # }
+//! ```
+use futures::future::poll_fn;
+use futures::ready;
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// `Ctrl-]`, the default escape key
+pub const DEFAULT_ESCAPE: u8 = 0x1d;
+
+/// A line end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Newline {
+    /// `\r`
+    Cr,
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl Newline {
+    /// Returns the bytes of the line end
+    pub fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Newline::Cr => b"\r",
+            Newline::Lf => b"\n",
+            Newline::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// Why a session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The escape key was typed
+    Escape,
+    /// The input ended, like a pipe feeding the session
+    InputClosed,
+    /// The port was closed or went away
+    PortClosed,
+}
+
+/// Bridges a terminal with a port, see the [module](self) docs
+#[derive(Debug, Clone)]
+pub struct Console {
+    escape: Option<u8>,
+    local_echo: bool,
+    send_newline: Newline,
+    receive_newline: Option<Newline>,
+}
+
+impl Console {
+    /// Create a console ending on `Ctrl-]`, sending `\r` for the enter key and showing the
+    /// port's `\n` as a new line, without local echo
+    pub fn new() -> Self {
+        Self {
+            escape: Some(DEFAULT_ESCAPE),
+            local_echo: false,
+            send_newline: Newline::Cr,
+            receive_newline: Some(Newline::Lf),
+        }
+    }
+
+    /// End the session when the key `escape` is typed, or only when the input or the port
+    /// closes with `None`
+    pub fn set_escape(&mut self, escape: Option<u8>) {
+        self.escape = escape;
+    }
+
+    /// Returns the escape key
+    pub fn escape(&self) -> Option<u8> {
+        self.escape
+    }
+
+    /// Show the keys typed, for devices that don't echo them
+    pub fn set_local_echo(&mut self, local_echo: bool) {
+        self.local_echo = local_echo;
+    }
+
+    /// Returns `true` if the keys typed are shown
+    pub fn local_echo(&self) -> bool {
+        self.local_echo
+    }
+
+    /// Send `newline` for the enter key, `\r` by default
+    pub fn set_send_newline(&mut self, newline: Newline) {
+        self.send_newline = newline;
+    }
+
+    /// Returns what is sent for the enter key
+    pub fn send_newline(&self) -> Newline {
+        self.send_newline
+    }
+
+    /// Start a new line on the terminal when the port sends `newline`, `\n` by default, or
+    /// show the port's bytes unchanged with `None`
+    ///
+    /// The terminal's output processing is off in raw mode, so a bare `\n` only moves down
+    /// a line.
+    pub fn set_receive_newline(&mut self, newline: Option<Newline>) {
+        self.receive_newline = newline;
+    }
+
+    /// Returns the line end of the port starting a new line on the terminal
+    pub fn receive_newline(&self) -> Option<Newline> {
+        self.receive_newline
+    }
+
+    /// Bridge the process' standard input and output with `port` until the session ends
+    ///
+    /// Standard input is put in raw mode if it is a terminal, and both are made nonblocking;
+    /// their settings are restored when the session ends, even on error.
+    ///
+    /// ## Errors
+    ///
+    /// * The errors of the terminal settings and of the reads and writes.
+    pub async fn run<P>(&self, port: &mut P) -> io::Result<Exit>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        let _restore = Restore::new()?;
+        let mut input = Stdio::new(libc::STDIN_FILENO)?;
+        let mut output = Stdio::new(libc::STDOUT_FILENO)?;
+        self.bridge(&mut input, &mut output, port).await
+    }
+
+    /// Bridge `input` and `output` with `port` until the session ends, without touching any
+    /// terminal settings
+    ///
+    /// ## Errors
+    ///
+    /// * The errors of the reads and writes.
+    pub async fn bridge<I, O, P>(
+        &self,
+        input: &mut I,
+        output: &mut O,
+        port: &mut P,
+    ) -> io::Result<Exit>
+    where
+        I: AsyncRead + Unpin,
+        O: AsyncWrite + Unpin,
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut to_port = Vec::new();
+        let mut to_screen = Vec::new();
+        let mut buf = [0; 1024];
+        let exit = poll_fn(|cx| -> Poll<io::Result<Exit>> {
+            loop {
+                let mut progress = false;
+                if let Poll::Ready(result) = poll_drain(cx, &mut *port, &mut to_port) {
+                    result?;
+                }
+                if let Poll::Ready(result) = poll_drain(cx, &mut *output, &mut to_screen) {
+                    result?;
+                }
+
+                if to_port.is_empty() {
+                    if let Poll::Ready(keys) = poll_read(cx, &mut *input, &mut buf) {
+                        let keys = keys?;
+                        if keys.is_empty() {
+                            return Poll::Ready(Ok(Exit::InputClosed));
+                        }
+                        if self.keys(keys, &mut to_port, &mut to_screen) {
+                            return Poll::Ready(Ok(Exit::Escape));
+                        }
+                        progress = true;
+                    }
+                }
+                if to_screen.is_empty() {
+                    if let Poll::Ready(received) = poll_read(cx, &mut *port, &mut buf) {
+                        let received = received?;
+                        if received.is_empty() {
+                            return Poll::Ready(Ok(Exit::PortClosed));
+                        }
+                        self.show(received, &mut to_screen);
+                        progress = true;
+                    }
+                }
+                if !progress {
+                    return Poll::Pending;
+                }
+            }
+        })
+        .await?;
+
+        // What was typed before the escape key still goes out
+        poll_fn(|cx| poll_drain(cx, &mut *port, &mut to_port)).await?;
+        poll_fn(|cx| poll_drain(cx, &mut *output, &mut to_screen)).await?;
+        Ok(exit)
+    }
+
+    /// Map the keys typed, returning `true` on the escape key
+    fn keys(&self, keys: &[u8], to_port: &mut Vec<u8>, to_screen: &mut Vec<u8>) -> bool {
+        for &key in keys {
+            if Some(key) == self.escape {
+                return true;
+            }
+            if key == b'\r' {
+                to_port.extend_from_slice(self.send_newline.as_bytes());
+                if self.local_echo {
+                    to_screen.extend_from_slice(b"\r\n");
+                }
+            } else {
+                to_port.push(key);
+                if self.local_echo {
+                    to_screen.push(key);
+                }
+            }
+        }
+        false
+    }
+
+    fn show(&self, received: &[u8], to_screen: &mut Vec<u8>) {
+        let newline = match self.receive_newline {
+            Some(Newline::Cr) => b'\r',
+            Some(Newline::Lf) => b'\n',
+            // Already starts a new line
+            Some(Newline::CrLf) | None => {
+                to_screen.extend_from_slice(received);
+                return;
+            }
+        };
+        for &byte in received {
+            if byte == newline {
+                to_screen.extend_from_slice(b"\r\n");
+            } else {
+                to_screen.push(byte);
+            }
+        }
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn poll_read<'a, R: AsyncRead + Unpin>(
+    cx: &mut Context<'_>,
+    reader: &mut R,
+    buf: &'a mut [u8],
+) -> Poll<io::Result<&'a [u8]>> {
+    let mut read_buf = ReadBuf::new(buf);
+    ready!(Pin::new(reader).poll_read(cx, &mut read_buf))?;
+    let n = read_buf.filled().len();
+    Poll::Ready(Ok(&buf[..n]))
+}
+
+/// Write all of `buf`, emptying it
+fn poll_drain<W: AsyncWrite + Unpin>(
+    cx: &mut Context<'_>,
+    writer: &mut W,
+    buf: &mut Vec<u8>,
+) -> Poll<io::Result<()>> {
+    while !buf.is_empty() {
+        match ready!(Pin::new(&mut *writer).poll_write(cx, buf))? {
+            0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+            n => drop(buf.drain(..n)),
+        }
+    }
+    Pin::new(writer).poll_flush(cx)
+}
+
+/// Standard input or output, nonblocking
+struct Stdio(AsyncFd<RawFd>);
+
+impl Stdio {
+    fn new(fd: RawFd) -> io::Result<Self> {
+        let flags = fcntl(fd, libc::F_GETFL, 0)?;
+        fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)?;
+        Ok(Self(AsyncFd::new(fd)?))
+    }
+}
+
+impl AsyncRead for Stdio {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.0.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            let result = guard.try_io(|fd| {
+                // SAFETY: the pointer and length describe writable memory owned by `unfilled`
+                let n = unsafe {
+                    libc::read(fd.as_raw_fd(), unfilled.as_mut_ptr().cast(), unfilled.len())
+                };
+                if n < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(n as usize)
+            });
+            match result {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) if crate::is_hangup(&err) => return Poll::Ready(Ok(())),
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Stdio {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.0.poll_write_ready(cx))?;
+            let result = guard.try_io(|fd| {
+                // SAFETY: the pointer and length describe memory owned by `buf`
+                let n = unsafe { libc::write(fd.as_raw_fd(), buf.as_ptr().cast(), buf.len()) };
+                if n < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(n as usize)
+            });
+            match result {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn fcntl(fd: RawFd, command: libc::c_int, arg: libc::c_int) -> io::Result<libc::c_int> {
+    // SAFETY: plain call, the result is checked
+    let result = unsafe { libc::fcntl(fd, command, arg) };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result)
+}
+
+/// Puts standard input in raw mode, restoring it and the flags of standard input and output
+/// on drop
+struct Restore {
+    termios: Option<libc::termios>,
+    flags: [libc::c_int; 2],
+}
+
+impl Restore {
+    fn new() -> io::Result<Self> {
+        let flags = [
+            fcntl(libc::STDIN_FILENO, libc::F_GETFL, 0)?,
+            fcntl(libc::STDOUT_FILENO, libc::F_GETFL, 0)?,
+        ];
+        // SAFETY: plain call
+        let termios = if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+            let termios = crate::config::get_termios(libc::STDIN_FILENO)?;
+            let mut raw = termios;
+            // SAFETY: the struct is initialized
+            unsafe { libc::cfmakeraw(&mut raw) };
+            crate::config::put_termios(libc::STDIN_FILENO, &raw)?;
+            Some(termios)
+        } else {
+            None
+        };
+        Ok(Self { termios, flags })
+    }
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(termios) = &self.termios {
+            let _ = crate::config::put_termios(libc::STDIN_FILENO, termios);
+        }
+        let _ = fcntl(libc::STDIN_FILENO, libc::F_SETFL, self.flags[0]);
+        let _ = fcntl(libc::STDOUT_FILENO, libc::F_SETFL, self.flags[1]);
+    }
+}
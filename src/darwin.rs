@@ -0,0 +1,31 @@
+//! ioctls of the macOS serial driver family (IOSerialFamily)
+use std::io;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+/// `_IOW(group, num, type)` of `<sys/ioccom.h>`
+const fn iow(group: u8, num: u8, size: usize) -> libc::c_ulong {
+    const IOC_IN: libc::c_ulong = 0x8000_0000;
+    const IOCPARM_MASK: libc::c_ulong = 0x1fff;
+    IOC_IN
+        | ((size as libc::c_ulong & IOCPARM_MASK) << 16)
+        | ((group as libc::c_ulong) << 8)
+        | num as libc::c_ulong
+}
+
+/// `IOSSDATALAT` of `<IOKit/serial/ioss.h>`: `_IOW('T', 0, unsigned long)`
+const IOSSDATALAT: libc::c_ulong = iow(b'T', 0, std::mem::size_of::<libc::c_ulong>());
+
+/// Set how long the driver holds received bytes before handing them to readers
+///
+/// The driver batches received data to save wakeups, by default for as long as a few
+/// character times at the current baud rate.  A latency of zero hands every byte over as
+/// soon as it arrives.
+pub(crate) fn set_data_latency(fd: RawFd, latency: Duration) -> io::Result<()> {
+    let micros = latency.as_micros().min(libc::c_ulong::MAX as u128) as libc::c_ulong;
+    // SAFETY: the ioctl reads an unsigned long
+    if unsafe { libc::ioctl(fd, IOSSDATALAT, &micros) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
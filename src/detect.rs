@@ -0,0 +1,220 @@
+//! Recognize the protocol spoken on a port from its first bytes
+//!
+//! A "universal listener" doesn't know up front what is connected: a GNSS receiver talking
+//! NMEA or UBX, a Modbus RTU master, something else entirely.  [`Detector`]
+//! [peeks](crate::SerialStream::peek) at the incoming bytes, without consuming them, until one
+//! of its signatures matches, and [`dispatch`](Detector::dispatch) hands the port back framed
+//! with the matching codec.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tokio_serial::detect::{Detected, Detector};
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! match Detector::new().dispatch(port).await? {
+//!     Detected::Nmea(mut sentences) => {
+//!         while let Some(sentence) = sentences.next().await {
+//!             println!("{}", sentence?);
+//!         }
+//!     }
+//!     Detected::Ubx(mut frames) => {
+//!         while let Some(frame) = frames.next().await {
+//!             println!("{:?}", frame?);
+//!         }
+//!     }
+//!     Detected::ModbusRtu(port) | Detected::Custom(_, port) => drop(port),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::codec::modbus::crc16;
+use crate::codec::nmea::NmeaCodec;
+use crate::codec::ubx::{self, UbxCodec};
+use crate::frame::SerialFramed;
+use crate::SerialStream;
+
+use std::fmt;
+use std::io;
+
+/// Bytes looked at before giving up, by default
+pub const DEFAULT_MAX_PEEK: usize = 256;
+
+/// A protocol recognized by a [`Detector`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// NMEA 0183 sentences
+    Nmea,
+    /// u-blox UBX frames
+    Ubx,
+    /// Modbus RTU requests or responses
+    ModbusRtu,
+    /// A signature added with [`Detector::register`]
+    Custom(&'static str),
+}
+
+/// A port handed back by [`Detector::dispatch`], framed when the crate has a codec for it
+///
+/// The bytes looked at are still to be read.
+#[derive(Debug)]
+pub enum Detected {
+    /// NMEA 0183 sentences
+    Nmea(SerialFramed<NmeaCodec>),
+    /// u-blox UBX frames
+    Ubx(SerialFramed<UbxCodec>),
+    /// Modbus RTU requests or responses
+    ModbusRtu(SerialStream),
+    /// A signature added with [`Detector::register`]
+    Custom(&'static str, SerialStream),
+}
+
+type Signature = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+/// Matches the first bytes of a port against signatures, see the [module](self) docs
+pub struct Detector {
+    signatures: Vec<(Protocol, Signature)>,
+    max_peek: usize,
+}
+
+impl Detector {
+    /// Create a detector for NMEA, UBX and Modbus RTU
+    pub fn new() -> Self {
+        Self {
+            signatures: vec![
+                (Protocol::Nmea, Box::new(is_nmea)),
+                (Protocol::Ubx, Box::new(is_ubx)),
+                (Protocol::ModbusRtu, Box::new(is_modbus_rtu)),
+            ],
+            max_peek: DEFAULT_MAX_PEEK,
+        }
+    }
+
+    /// Add a signature, tried after the ones registered before
+    ///
+    /// `signature` gets all bytes peeked so far and tells whether they hold the protocol.
+    /// It is called again with more bytes until it or another signature matches.
+    pub fn register<F>(mut self, name: &'static str, signature: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.signatures
+            .push((Protocol::Custom(name), Box::new(signature)));
+        self
+    }
+
+    /// Give up once `bytes` were peeked without a match, [`DEFAULT_MAX_PEEK`] by default
+    pub fn max_peek(mut self, bytes: usize) -> Self {
+        self.max_peek = bytes;
+        self
+    }
+
+    /// Returns the first protocol whose signature matches `bytes`
+    pub fn classify(&self, bytes: &[u8]) -> Option<Protocol> {
+        self.signatures
+            .iter()
+            .find(|(_, signature)| signature(bytes))
+            .map(|(protocol, _)| *protocol)
+    }
+
+    /// Peek at the bytes arriving on `port` until a signature matches
+    ///
+    /// Nothing is consumed.  Waits for as long as bytes keep arriving without a match, wrap
+    /// the call in a timeout for a line that may stay silent.
+    ///
+    /// # Errors
+    ///
+    /// `InvalidData` when [`max_peek`](Self::max_peek) bytes matched nothing and
+    /// `UnexpectedEof` when the line hung up first.
+    pub async fn detect(&self, port: &mut SerialStream) -> io::Result<Protocol> {
+        let mut window = vec![0u8; self.max_peek];
+        let mut seen = 0;
+        loop {
+            let n = port.peek_beyond(&mut window, seen).await?;
+            seen = n;
+            if let Some(protocol) = self.classify(&window[..n]) {
+                return Ok(protocol);
+            }
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if n == window.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no known protocol recognized",
+                ));
+            }
+        }
+    }
+
+    /// [Detect](Self::detect) the protocol of `port` and frame it with the matching codec
+    pub async fn dispatch(&self, mut port: SerialStream) -> io::Result<Detected> {
+        Ok(match self.detect(&mut port).await? {
+            Protocol::Nmea => Detected::Nmea(SerialFramed::new(port, NmeaCodec::new())),
+            Protocol::Ubx => Detected::Ubx(SerialFramed::new(port, UbxCodec::new())),
+            Protocol::ModbusRtu => Detected::ModbusRtu(port),
+            Protocol::Custom(name) => Detected::Custom(name, port),
+        })
+    }
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Detector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let protocols: Vec<_> = self.signatures.iter().map(|(p, _)| p).collect();
+        f.debug_struct("Detector")
+            .field("protocols", &protocols)
+            .field("max_peek", &self.max_peek)
+            .finish()
+    }
+}
+
+/// A start character, a 5 character address and the first field delimiter
+fn is_nmea(bytes: &[u8]) -> bool {
+    bytes.windows(7).any(|w| {
+        (w[0] == b'$' || w[0] == b'!')
+            && w[1..6]
+                .iter()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+            && w[6] == b','
+    })
+}
+
+/// Sync characters and a complete frame with a valid checksum
+fn is_ubx(bytes: &[u8]) -> bool {
+    (0..bytes.len().saturating_sub(1)).any(|start| {
+        let frame = &bytes[start..];
+        if frame[..2] != [ubx::SYNC_1, ubx::SYNC_2] || frame.len() < 8 {
+            return false;
+        }
+        let end = 6 + usize::from(u16::from_le_bytes([frame[4], frame[5]]));
+        frame.len() >= end + 2 && frame[end..end + 2] == ubx::checksum(&frame[2..end])
+    })
+}
+
+/// A complete request or response of a common function with a valid CRC
+///
+/// Without the silent intervals delimiting RTU frames every offset is tried, the CRC keeps
+/// random matches unlikely.
+fn is_modbus_rtu(bytes: &[u8]) -> bool {
+    (0..bytes.len()).any(|start| {
+        let frame = &bytes[start..];
+        if frame.len() < 5 || frame[0] > 247 {
+            return false;
+        }
+        let lengths = match frame[1] {
+            // Read requests and single writes are 8 bytes, read responses carry a byte count
+            1..=6 => [Some(8), Some(5 + usize::from(frame[2]))],
+            // Multiple writes: byte count in the request, fixed size response
+            15 | 16 => [frame.get(6).map(|&count| 9 + usize::from(count)), Some(8)],
+            0x81..=0x86 | 0x8f | 0x90 => [Some(5), None],
+            _ => return false,
+        };
+        lengths.iter().flatten().any(|&len| {
+            frame.len() >= len && crc16(&frame[..len - 2]).to_le_bytes() == frame[len - 2..len]
+        })
+    })
+}
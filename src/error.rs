@@ -0,0 +1,181 @@
+//! I/O errors carrying the port and operation they occurred on
+//!
+//! `SerialStream` reports its I/O errors through `std::io::Error`, as required by the
+//! `AsyncRead` and `AsyncWrite` traits.  Errors other than `WouldBlock` wrap a [`PortError`]
+//! recording the port name and the failed [`Operation`], recovered with
+//! [`PortError::from_io`].  The `io::ErrorKind` of the original error is kept, its OS error
+//! code is only available from [`PortError::io_error`].
+//!
+//! [`ErrorClass`] sorts errors into the broad groups a retry policy cares about:
+//!
+//! ```no_run
+//! use tokio::io::AsyncReadExt;
+//! use tokio_serial::{ErrorClass, SerialPortBuilderExt};
+//!
+//! #[tokio::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let mut port = tokio_serial::new("/dev/ttyUSB0", 9600).open_native_async()?;
+//!     let mut buf = [0u8; 64];
+//!     loop {
+//!         match port.read(&mut buf).await {
+//!             Ok(0) => break,
+//!             Ok(n) => println!("{:?}", &buf[..n]),
+//!             Err(e) if ErrorClass::of_io(&e) == ErrorClass::Transient => continue,
+//!             Err(e) => return Err(e),
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+/// Operation an error occurred on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Reading data
+    Read,
+    /// Writing data
+    Write,
+    /// Waiting for written data to be transmitted
+    Flush,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Read => "read",
+            Operation::Write => "write",
+            Operation::Flush => "flush",
+        })
+    }
+}
+
+/// Broad classification of an error, for deciding whether and how to retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Retrying the same operation may succeed: `WouldBlock`, `Interrupted`, `TimedOut`
+    Transient,
+    /// The device went away or is held by someone else: reopening it later may succeed
+    Disconnected,
+    /// Access to the device was denied
+    Permission,
+    /// Another port of this process has the device open: opening it succeeds once that port
+    /// is closed, see [`is_open_in_process`](crate::is_open_in_process)
+    AlreadyOpen,
+    /// Anything else, like invalid settings
+    Other,
+}
+
+impl ErrorClass {
+    /// Classify an I/O error, looking through a wrapped [`PortError`]
+    ///
+    /// The OS errors of a device going away count as `Disconnected`: `EIO`, `ENXIO` and
+    /// `ENODEV` on unix, `ERROR_ACCESS_DENIED`, `ERROR_BAD_COMMAND`,
+    /// `ERROR_DEVICE_NOT_CONNECTED`, `ERROR_DEVICE_REMOVED` and `ERROR_OPERATION_ABORTED` on
+    /// Windows.
+    pub fn of_io(err: &io::Error) -> Self {
+        if let Some(port_error) = PortError::from_io(err) {
+            return port_error.class();
+        }
+
+        if crate::is_hangup(err) {
+            return ErrorClass::Disconnected;
+        }
+
+        Self::of_kind(err.kind())
+    }
+
+    /// Classify a serial port error
+    pub fn of(err: &crate::Error) -> Self {
+        match err.kind {
+            crate::ErrorKind::NoDevice => ErrorClass::Disconnected,
+            crate::ErrorKind::Io(kind) => Self::of_kind(kind),
+            crate::ErrorKind::InvalidInput | crate::ErrorKind::Unknown => ErrorClass::Other,
+        }
+    }
+
+    fn of_kind(kind: io::ErrorKind) -> Self {
+        use io::ErrorKind::*;
+
+        match kind {
+            WouldBlock | Interrupted | TimedOut => ErrorClass::Transient,
+            NotFound | BrokenPipe | NotConnected | ConnectionReset | ConnectionAborted
+            | UnexpectedEof => ErrorClass::Disconnected,
+            PermissionDenied => ErrorClass::Permission,
+            AddrInUse => ErrorClass::AlreadyOpen,
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+/// An I/O error with the port and the operation it occurred on
+#[derive(Debug)]
+pub struct PortError {
+    port: Option<String>,
+    operation: Operation,
+    source: io::Error,
+}
+
+impl PortError {
+    /// Create an error for `operation` on `port` caused by `source`
+    pub fn new(port: Option<String>, operation: Operation, source: io::Error) -> Self {
+        Self {
+            port,
+            operation,
+            source,
+        }
+    }
+
+    /// Returns the `PortError` wrapped by `err`, if any
+    pub fn from_io(err: &io::Error) -> Option<&PortError> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    /// Returns the name of the port, if it has one
+    pub fn port(&self) -> Option<&str> {
+        self.port.as_deref()
+    }
+
+    /// Returns the operation that failed
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Returns the underlying I/O error
+    pub fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+
+    /// Returns the classification of the underlying I/O error
+    pub fn class(&self) -> ErrorClass {
+        ErrorClass::of_io(&self.source)
+    }
+
+    /// Whether retrying the same operation may succeed
+    pub fn is_transient(&self) -> bool {
+        self.class() == ErrorClass::Transient
+    }
+}
+
+impl fmt::Display for PortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.port {
+            Some(port) => write!(f, "{} on {} failed: {}", self.operation, port, self.source),
+            None => write!(f, "{} failed: {}", self.operation, self.source),
+        }
+    }
+}
+
+impl StdError for PortError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<PortError> for io::Error {
+    fn from(err: PortError) -> Self {
+        io::Error::new(err.source.kind(), err)
+    }
+}
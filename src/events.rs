@@ -0,0 +1,404 @@
+//! Everything happening on a port as one stream
+//!
+//! A device state machine usually waits for several things at once: incoming bytes, room
+//! for outgoing ones, a modem line toggling, the device going away.  [`PortEvents`], from
+//! [`SerialStream::events`](crate::SerialStream::events), merges them into a single
+//! `Stream<Item = PortEvent>` that fits one `select!` arm, while the port itself stays free
+//! for reading and writing.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tokio::io::AsyncReadExt;
+//! use tokio_serial::events::PortEvent;
+//!
+//! # async fn run(mut port: tokio_serial::SerialStream) -> tokio_serial::Result<()> {
+//! let mut events = port.events()?;
+//! let mut buf = [0u8; 256];
+//! while let Some(event) = events.next().await {
+//!     match event {
+//!         PortEvent::Readable => {
+//!             let n = port.read(&mut buf).await?;
+//!             println!("{:?}", &buf[..n]);
+//!         }
+//!         PortEvent::ModemLines(lines) if !lines.cd => println!("carrier lost"),
+//!         PortEvent::Disconnected => break,
+//!         _ => {}
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! On unix the readiness events come from a duplicate of the port's descriptor and the modem
+//! lines are polled, every [`DEFAULT_POLL_INTERVAL`] by default.  Breaks and line errors are
+//! only reported on Linux, from the interrupt counters of the driver; pseudo terminals have
+//! neither modem lines nor counters.  On Windows the events come from `WaitCommEvent`, see
+//! [`SerialStream::comm_events`](crate::SerialStream::comm_events), which means the port
+//! can't have both streams at once, and `Writable` is never reported.
+use crate::config::ModemLines;
+
+use futures::Stream;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::future::Future;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, BorrowedFd, OwnedFd, RawFd};
+#[cfg(unix)]
+use tokio::io::unix::AsyncFd;
+#[cfg(unix)]
+use tokio::time::{sleep, Instant, Sleep};
+
+#[cfg(windows)]
+use crate::{CommEvent, CommEvents};
+#[cfg(windows)]
+use std::os::windows::io::{BorrowedHandle, RawHandle};
+
+/// How often the modem lines are polled on unix, by default
+#[cfg(unix)]
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Something that happened on a port, see the [module](self) docs
+#[derive(Debug)]
+pub enum PortEvent {
+    /// Bytes arrived
+    ///
+    /// Reported again when more bytes arrive, whether or not the earlier ones were read.
+    Readable,
+    /// The driver made room for more output
+    ///
+    /// Some drivers also signal it while there was room all along, after a read for instance.
+    Writable,
+    /// A modem input line changed, carrying the levels of all of them
+    ///
+    /// A pulse shorter than the poll interval is reported with the levels after it.
+    ModemLines(ModemLines),
+    /// A break condition was received
+    Break,
+    /// A framing, parity or overrun error was detected on input
+    LineError,
+    /// Watching the port failed, the stream ends after this
+    Error(io::Error),
+    /// The device went away or the other end hung up, the stream ends after this
+    Disconnected,
+}
+
+/// Stream of the [events](PortEvent) of a port, see the [module](self) docs
+pub struct PortEvents {
+    #[cfg(unix)]
+    fd: AsyncFd<OwnedFd>,
+    #[cfg(unix)]
+    poll_interval: Duration,
+    #[cfg(unix)]
+    timer: Pin<Box<Sleep>>,
+    #[cfg(target_os = "linux")]
+    counts: Option<linux::Counts>,
+    #[cfg(windows)]
+    comm: CommEvents,
+    #[cfg(windows)]
+    handle: RawHandle,
+    // `None` when the driver has no modem lines
+    lines: Option<ModemLines>,
+    pending: VecDeque<PortEvent>,
+    done: bool,
+}
+
+// SAFETY: the raw handle is owned by `comm`, which is `Send` and `Sync`
+#[cfg(windows)]
+unsafe impl Send for PortEvents {}
+#[cfg(windows)]
+unsafe impl Sync for PortEvents {}
+
+#[cfg(unix)]
+impl PortEvents {
+    /// Watch a duplicate of `fd`
+    pub(crate) fn new(fd: RawFd) -> io::Result<Self> {
+        // SAFETY: `fd` is the open descriptor of the port, borrowed only to duplicate it
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+        let lines = crate::config::read_modem_lines(&fd).ok();
+        Ok(Self {
+            #[cfg(target_os = "linux")]
+            counts: linux::Counts::read(fd.as_raw_fd()).ok(),
+            fd: AsyncFd::new(fd)?,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            timer: Box::pin(sleep(DEFAULT_POLL_INTERVAL)),
+            lines,
+            pending: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    /// Poll the modem lines every `interval`, [`DEFAULT_POLL_INTERVAL`] by default
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+        self.timer.as_mut().reset(Instant::now() + interval);
+    }
+
+    /// Returns how often the modem lines are polled
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// Wait for the port to become readable or writable
+    fn poll_ready(&mut self, cx: &mut Context<'_>, readable: bool) -> Poll<PortEvent> {
+        loop {
+            let guard = if readable {
+                self.fd.poll_read_ready(cx)
+            } else {
+                self.fd.poll_write_ready(cx)
+            };
+            let mut guard = match futures::ready!(guard) {
+                Ok(guard) => guard,
+                Err(e) => return Poll::Ready(PortEvent::Error(e)),
+            };
+            // The readiness of the duplicate doesn't see the reads and writes made through
+            // the port itself, so ask the driver where things stand
+            let revents = poll_now(self.fd.as_raw_fd());
+            guard.clear_ready();
+            if revents & libc::POLLHUP != 0 {
+                return Poll::Ready(PortEvent::Disconnected);
+            }
+            if readable && revents & libc::POLLIN != 0 {
+                return Poll::Ready(PortEvent::Readable);
+            }
+            if !readable && revents & libc::POLLOUT != 0 {
+                return Poll::Ready(PortEvent::Writable);
+            }
+        }
+    }
+
+    /// Queue the events of the modem lines and counters that changed since the last poll
+    fn poll_lines(&mut self) {
+        #[cfg(target_os = "linux")]
+        let mut pulsed = false;
+        #[cfg(target_os = "linux")]
+        if let Some(before) = self.counts {
+            match linux::Counts::read(self.fd.as_raw_fd()) {
+                Ok(now) => {
+                    if now.brk != before.brk {
+                        self.pending.push_back(PortEvent::Break);
+                    }
+                    if now.line_errors() != before.line_errors() {
+                        self.pending.push_back(PortEvent::LineError);
+                    }
+                    pulsed = now.modem_changes() != before.modem_changes();
+                    self.counts = Some(now);
+                }
+                Err(e) => return self.fail(e),
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let pulsed = false;
+
+        if let Some(before) = self.lines {
+            match crate::config::read_modem_lines(self.fd.get_ref()) {
+                Ok(now) => {
+                    if now != before || pulsed {
+                        self.pending.push_back(PortEvent::ModemLines(now));
+                    }
+                    self.lines = Some(now);
+                }
+                Err(e) => self.fail(e),
+            }
+        }
+    }
+
+    fn fail(&mut self, e: io::Error) {
+        self.pending.push_back(if crate::is_hangup(&e) {
+            PortEvent::Disconnected
+        } else {
+            PortEvent::Error(e)
+        });
+    }
+
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<PortEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(event);
+            }
+            if let Poll::Ready(event) = self.poll_ready(cx, true) {
+                return Poll::Ready(event);
+            }
+            if let Poll::Ready(event) = self.poll_ready(cx, false) {
+                return Poll::Ready(event);
+            }
+            if !self.polls_lines() {
+                return Poll::Pending;
+            }
+            futures::ready!(self.timer.as_mut().poll(cx));
+            let next = Instant::now() + self.poll_interval;
+            self.timer.as_mut().reset(next);
+            self.poll_lines();
+        }
+    }
+
+    fn polls_lines(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        if self.counts.is_some() {
+            return true;
+        }
+        self.lines.is_some()
+    }
+}
+
+/// Returns the `poll(2)` events of `fd` right now
+#[cfg(unix)]
+fn poll_now(fd: RawFd) -> libc::c_short {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN | libc::POLLOUT,
+        revents: 0,
+    };
+    // SAFETY: a single valid pollfd and no timeout
+    match unsafe { libc::poll(&mut pollfd, 1, 0) } {
+        1 => pollfd.revents,
+        _ => 0,
+    }
+}
+
+#[cfg(windows)]
+impl PortEvents {
+    /// Watch the events of the port handle `handle`
+    pub(crate) fn new(handle: RawHandle) -> io::Result<Self> {
+        let comm = CommEvents::new(
+            handle,
+            &[
+                CommEvent::RxChar,
+                CommEvent::Break,
+                CommEvent::Error,
+                CommEvent::Cts,
+                CommEvent::Dsr,
+                CommEvent::Rlsd,
+                CommEvent::Ring,
+            ],
+        )?;
+        let handle = comm.raw_handle();
+        Ok(Self {
+            lines: read_lines(handle).ok(),
+            comm,
+            handle,
+            pending: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<PortEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(event);
+            }
+            let event = match futures::ready!(Pin::new(&mut self.comm).poll_next(cx)) {
+                Some(Ok(event)) => event,
+                Some(Err(e)) if crate::is_hangup(&e) => {
+                    return Poll::Ready(PortEvent::Disconnected)
+                }
+                Some(Err(e)) => return Poll::Ready(PortEvent::Error(e)),
+                None => {
+                    return Poll::Ready(PortEvent::Error(io::Error::new(
+                        io::ErrorKind::Other,
+                        "event mask of the port replaced",
+                    )))
+                }
+            };
+            match event {
+                CommEvent::RxChar => return Poll::Ready(PortEvent::Readable),
+                CommEvent::Break => return Poll::Ready(PortEvent::Break),
+                CommEvent::Error => return Poll::Ready(PortEvent::LineError),
+                CommEvent::RxFlag => {}
+                // The ring indicator is reported on its trailing edge, when its level is back
+                CommEvent::Cts | CommEvent::Dsr | CommEvent::Rlsd | CommEvent::Ring => {
+                    match read_lines(self.handle) {
+                        Ok(now) if Some(now) != self.lines || event == CommEvent::Ring => {
+                            self.lines = Some(now);
+                            return Poll::Ready(PortEvent::ModemLines(now));
+                        }
+                        Ok(_) => {}
+                        Err(e) => return Poll::Ready(PortEvent::Error(e)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn read_lines(handle: RawHandle) -> io::Result<ModemLines> {
+    // SAFETY: the handle stays open for the duration of the call
+    crate::config::read_modem_lines(&unsafe { BorrowedHandle::borrow_raw(handle) })
+}
+
+impl Stream for PortEvents {
+    type Item = PortEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let event = futures::ready!(this.poll_event(cx));
+        this.done = matches!(event, PortEvent::Error(_) | PortEvent::Disconnected);
+        Poll::Ready(Some(event))
+    }
+}
+
+impl fmt::Debug for PortEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PortEvents")
+            .field("lines", &self.lines)
+            .field("pending", &self.pending)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::io;
+    use std::os::unix::io::RawFd;
+
+    /// `struct serial_icounter_struct`, interrupt counters of a serial driver
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, Default)]
+    pub(super) struct Counts {
+        cts: libc::c_int,
+        dsr: libc::c_int,
+        rng: libc::c_int,
+        dcd: libc::c_int,
+        _rx: libc::c_int,
+        _tx: libc::c_int,
+        frame: libc::c_int,
+        overrun: libc::c_int,
+        parity: libc::c_int,
+        pub(super) brk: libc::c_int,
+        buf_overrun: libc::c_int,
+        _reserved: [libc::c_int; 9],
+    }
+
+    impl Counts {
+        pub(super) fn read(fd: RawFd) -> io::Result<Self> {
+            let mut counts = Self::default();
+            // SAFETY: the ioctl writes a `struct serial_icounter_struct`
+            if unsafe { libc::ioctl(fd, libc::TIOCGICOUNT, &mut counts) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(counts)
+        }
+
+        pub(super) fn line_errors(&self) -> [libc::c_int; 4] {
+            [self.frame, self.overrun, self.parity, self.buf_overrun]
+        }
+
+        pub(super) fn modem_changes(&self) -> [libc::c_int; 4] {
+            [self.cts, self.dsr, self.rng, self.dcd]
+        }
+    }
+}
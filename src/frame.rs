@@ -1,17 +1,24 @@
 //! A unified [`Stream`] and [`Sink`] interface to an underlying `SerialStream`, using
 //! the `Encoder` and `Decoder` traits to encode and decode frames.
-use super::SerialStream;
+use super::codec::{TimedDecoder, Timing};
+use super::{SerialPort, SerialStream};
 
 use tokio_util::codec::{Decoder, Encoder};
 
 use futures::{Sink, Stream};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-use bytes::{BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::future::poll_fn;
 use futures::ready;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::IoSlice;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use std::{io, mem::MaybeUninit};
+use tokio::time::{sleep, sleep_until, Sleep};
 
 /// A unified [`Stream`] and [`Sink`] interface to an underlying `SerialStream`, using
 /// the `Encoder` and `Decoder` traits to encode and decode frames.
@@ -22,6 +29,9 @@ use std::{io, mem::MaybeUninit};
 /// handle encoding and decoding of messages frames. Note that the incoming and
 /// outgoing frame types may be distinct.
 ///
+/// Frames are decoded with `Decoder::decode` as data arrives.  When the port hangs up, the
+/// bytes left in the read buffer are handed to `Decoder::decode_eof` and the stream ends.
+///
 /// This function returns a *single* object that is both [`Stream`] and [`Sink`];
 /// grouping this into a single object is often useful for layering things which
 /// require both read and write access to the underlying object.
@@ -30,6 +40,12 @@ use std::{io, mem::MaybeUninit};
 /// calling [`split`] on the `SerialFramed` returned by this method, which will break
 /// them into separate objects, allowing them to interact more easily.
 ///
+/// Each frame sent is written out before the next one is accepted, a port accepting only
+/// part of it gets the rest on the following writes.  Frames can instead be buffered up
+/// to a budget of bytes in flight, see [`set_max_in_flight`](Self::set_max_in_flight), or
+/// slowed down for devices that can't keep up with frames sent back to back, see
+/// [`set_pacing`](Self::set_pacing).
+///
 /// [`Stream`]: futures_core::Stream
 /// [`Sink`]: futures_sink::Sink
 /// [`split`]: https://docs.rs/futures/0.3/futures/stream/trait.StreamExt.html#method.split
@@ -40,13 +56,63 @@ pub struct SerialFramed<C> {
     codec: C,
     rd: BytesMut,
     wr: BytesMut,
-    flushed: bool,
+    max_in_flight: Option<usize>,
     is_readable: bool,
+    eof: bool,
+    pacing: Option<Pacing>,
+    pace: Pace,
+    timer: Option<Pin<Box<Sleep>>>,
 }
 
 const INITIAL_RD_CAPACITY: usize = 64 * 1024;
 const INITIAL_WR_CAPACITY: usize = 8 * 1024;
 
+/// Shortest wait between two looks at the output queue while it drains
+const MIN_DRAIN_POLL: Duration = Duration::from_millis(1);
+
+/// Pacing of the frames sent through a [`SerialFramed`]
+///
+/// Slow devices (a meter at 9600 baud, a PLC servicing its UART between scan cycles, ...)
+/// drop bytes when frames arrive back to back.  A paced sink only accepts the next frame once
+/// the driver's output queue went down to `max_pending` bytes and `gap` elapsed after that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pacing {
+    gap: Duration,
+    max_pending: u32,
+}
+
+impl Pacing {
+    /// Leave at least `gap` of silence between frames
+    pub fn new(gap: Duration) -> Self {
+        Self {
+            gap,
+            max_pending: 0,
+        }
+    }
+
+    /// Accept the next frame while up to `bytes` of the previous one are still queued
+    ///
+    /// Defaults to 0, waiting for the queue to drain.  The gap is then counted from the
+    /// moment the queue went down to `bytes`.
+    pub fn max_pending(mut self, bytes: u32) -> Self {
+        self.max_pending = bytes;
+        self
+    }
+
+    /// Returns the gap between frames
+    pub fn gap(&self) -> Duration {
+        self.gap
+    }
+}
+
+/// Where the sink is in pacing the last frame sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pace {
+    Idle,
+    Draining,
+    Gap,
+}
+
 impl<C: Decoder + Unpin> Stream for SerialFramed<C> {
     type Item = Result<C::Item, C::Error>;
 
@@ -58,17 +124,29 @@ impl<C: Decoder + Unpin> Stream for SerialFramed<C> {
         loop {
             // Are there still bytes left in the read buffer to decode?
             if pin.is_readable {
-                if let Some(frame) = pin.codec.decode_eof(&mut pin.rd)? {
+                if pin.eof {
+                    let frame = pin.codec.decode_eof(&mut pin.rd)?;
+                    if frame.is_none() {
+                        pin.is_readable = false;
+                    }
+                    return Poll::Ready(frame.map(Ok));
+                }
+
+                if let Some(frame) = pin.codec.decode(&mut pin.rd)? {
                     return Poll::Ready(Some(Ok(frame)));
                 }
 
                 // if this line has been reached then decode has returned `None`.
                 pin.is_readable = false;
-                pin.rd.clear();
+            }
+
+            // The port hung up and every frame has been decoded
+            if pin.eof {
+                return Poll::Ready(None);
             }
 
             // We're out of data. Try and fetch more data to decode
-            unsafe {
+            let read = unsafe {
                 // Convert `&mut [MaybeUnit<u8>]` to `&mut [u8]` because we will be
                 // writing to it via `poll_recv_from` and therefore initializing the memory.
                 let buf = &mut *(pin.rd.chunk_mut() as *mut _ as *mut [MaybeUninit<u8>]);
@@ -77,9 +155,12 @@ impl<C: Decoder + Unpin> Stream for SerialFramed<C> {
                 ready!(Pin::new(&mut pin.port).poll_read(cx, &mut read))?;
 
                 assert_eq!(ptr, read.filled().as_ptr());
-                pin.rd.advance_mut(read.filled().len());
+                let n = read.filled().len();
+                pin.rd.advance_mut(n);
+                n
             };
 
+            pin.eof = read == 0;
             pin.is_readable = true;
         }
     }
@@ -89,13 +170,15 @@ impl<I, C: Encoder<I> + Unpin> Sink<I> for SerialFramed<C> {
     type Error = C::Error;
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        if !self.flushed {
-            match self.poll_flush(cx)? {
-                Poll::Ready(()) => {}
-                Poll::Pending => return Poll::Pending,
-            }
+        let pin = self.get_mut();
+        if pin.pacing.is_some() {
+            ready!(pin.poll_write_buffered(cx))?;
+            ready!(pin.poll_pace(cx))?;
+        } else if let Some(max) = pin.max_in_flight {
+            ready!(pin.poll_budget(cx, max))?;
+        } else {
+            ready!(pin.poll_write_buffered(cx))?;
         }
-
         Poll::Ready(Ok(()))
     }
 
@@ -103,40 +186,16 @@ impl<I, C: Encoder<I> + Unpin> Sink<I> for SerialFramed<C> {
         let pin = self.get_mut();
 
         pin.codec.encode(item, &mut pin.wr)?;
-        pin.flushed = false;
+        if pin.pacing.is_some() {
+            pin.pace = Pace::Draining;
+        }
 
         Ok(())
     }
 
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        if self.flushed {
-            return Poll::Ready(Ok(()));
-        }
-
-        let Self {
-            ref mut port,
-            ref mut wr,
-            ..
-        } = *self;
-
-        let pinned = Pin::new(port);
-        let n = ready!(pinned.poll_write(cx, &wr))?;
-
-        let wrote_all = n == self.wr.len();
-        self.wr.clear();
-        self.flushed = true;
-
-        let res = if wrote_all {
-            Ok(())
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to write entire datagram to socket",
-            )
-            .into())
-        };
-
-        Poll::Ready(res)
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.get_mut().poll_write_buffered(cx))?;
+        Poll::Ready(Ok(()))
     }
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -156,8 +215,151 @@ impl<C> SerialFramed<C> {
             codec,
             rd: BytesMut::with_capacity(INITIAL_RD_CAPACITY),
             wr: BytesMut::with_capacity(INITIAL_WR_CAPACITY),
-            flushed: true,
+            max_in_flight: None,
             is_readable: false,
+            eof: false,
+            pacing: None,
+            pace: Pace::Idle,
+            timer: None,
+        }
+    }
+
+    /// Pace the frames sent, or send them back to back with `None`
+    ///
+    /// The wait happens in `poll_ready`, so `SinkExt::send` and `send_all` honour it.  Pacing
+    /// asks the driver for its queue length and therefore needs a port reporting
+    /// `bytes_to_write`.
+    ///
+    /// ```no_run
+    /// use futures::SinkExt;
+    /// use std::time::Duration;
+    /// use tokio_serial::frame::{Pacing, SerialFramed};
+    /// use tokio_util::codec::BytesCodec;
+    ///
+    /// # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+    /// let mut framed = SerialFramed::new(port, BytesCodec::new());
+    /// framed.set_pacing(Some(Pacing::new(Duration::from_millis(20))));
+    /// framed.send(bytes::Bytes::from_static(b"first")).await?;
+    /// framed.send(bytes::Bytes::from_static(b"second")).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_pacing(&mut self, pacing: Option<Pacing>) {
+        self.pacing = pacing;
+        if pacing.is_none() {
+            self.pace = Pace::Idle;
+            self.timer = None;
+        }
+    }
+
+    /// Returns the pacing of the frames sent
+    pub fn pacing(&self) -> Option<Pacing> {
+        self.pacing
+    }
+
+    /// Buffer frames until `bytes` are sent but not transmitted yet, or write each frame out
+    /// before accepting the next with `None`, the default
+    ///
+    /// The sink accepts the next frame while its own buffer and the driver's output queue
+    /// hold less than `bytes` together, so `SinkExt::send_all` from a fast source neither
+    /// waits on every frame nor piles frames up in memory.  A frame longer than the budget
+    /// is still accepted, once everything before it went out.  Ports not reporting
+    /// `bytes_to_write` have each frame written out as without a budget.
+    ///
+    /// Ignored while frames are [paced](Self::set_pacing), paced frames are sent one by one.
+    pub fn set_max_in_flight(&mut self, bytes: Option<usize>) {
+        self.max_in_flight = bytes;
+    }
+
+    /// Returns the budget of bytes sent but not transmitted yet
+    pub fn max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+
+    /// Returns a reference to the write buffer.
+    pub fn write_buffer(&self) -> &BytesMut {
+        &self.wr
+    }
+
+    /// Write out the frames accepted so far
+    fn poll_write_buffered(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.wr.is_empty() {
+            let n = ready!(Pin::new(&mut self.port).poll_write(cx, &self.wr))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.wr.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Wait for the bytes in flight to fall below `max`
+    fn poll_budget(&mut self, cx: &mut Context<'_>, max: usize) -> Poll<io::Result<()>> {
+        loop {
+            if let Some(timer) = self.timer.as_mut() {
+                ready!(timer.as_mut().poll(cx));
+                self.timer = None;
+            }
+            let queued = match self.bytes_queued() {
+                Some(queued) => queued,
+                None => return self.poll_write_buffered(cx),
+            };
+            if self.wr.len().saturating_add(queued) < max {
+                return Poll::Ready(Ok(()));
+            }
+
+            // Over budget, hand everything to the driver and let it transmit the excess
+            ready!(self.poll_write_buffered(cx))?;
+            let queued = match self.bytes_queued() {
+                Some(queued) if queued >= max => queued,
+                _ => return Poll::Ready(Ok(())),
+            };
+            let excess = (queued - max + 1) as u32;
+            self.timer = Some(Box::pin(sleep(self.drain_time(excess)?)));
+        }
+    }
+
+    /// Returns the bytes in the driver's output queue, or `None` if the port doesn't tell
+    fn bytes_queued(&self) -> Option<usize> {
+        match self.port.bytes_to_write() {
+            Ok(queued) => Some(queued as usize),
+            Err(e) => {
+                log::debug!("unable to tell the bytes to write, not buffering: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Time the driver needs to transmit `bytes`, but at least [`MIN_DRAIN_POLL`]
+    fn drain_time(&self, bytes: u32) -> io::Result<Duration> {
+        Ok((char_time(&self.port)? * bytes).max(MIN_DRAIN_POLL))
+    }
+
+    /// Wait for the last frame to drain and the gap after it to pass
+    fn poll_pace(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let pacing = match self.pacing {
+            Some(pacing) => pacing,
+            None => return Poll::Ready(Ok(())),
+        };
+        loop {
+            if let Some(timer) = self.timer.as_mut() {
+                ready!(timer.as_mut().poll(cx));
+                self.timer = None;
+            }
+            match self.pace {
+                Pace::Idle => return Poll::Ready(Ok(())),
+                Pace::Draining => {
+                    let pending = self.port.bytes_to_write()?;
+                    let wait = if pending <= pacing.max_pending {
+                        self.pace = Pace::Gap;
+                        pacing.gap
+                    } else {
+                        self.drain_time(pending - pacing.max_pending)?
+                    };
+                    self.timer = Some(Box::pin(sleep(wait)));
+                }
+                Pace::Gap => self.pace = Pace::Idle,
+            }
         }
     }
 
@@ -187,11 +389,53 @@ impl<C> SerialFramed<C> {
     }
 
     /// Consumes the `Framed`, returning its underlying I/O stream.
+    ///
+    /// Bytes read but not decoded yet and frames not sent yet are lost, use
+    /// [`get_mut`](Self::get_mut) or [`map_port`](Self::map_port) to work on the port
+    /// mid-session.
     #[allow(dead_code)]
     pub fn into_inner(self) -> SerialStream {
         self.port
     }
 
+    /// Replace the port with the one `f` makes of it, keeping the codec and both buffers
+    ///
+    /// For changes that take the port by value, like wrapping it or reopening the device
+    /// with other settings, without losing a partially received frame.
+    ///
+    /// ```no_run
+    /// use tokio_serial::frame::SerialFramed;
+    /// use tokio_serial::{SerialPort, SerialPortBuilderExt};
+    /// use tokio_util::codec::LinesCodec;
+    ///
+    /// # fn run(framed: SerialFramed<LinesCodec>) -> tokio_serial::Result<()> {
+    /// let framed = framed.try_map_port(|port| {
+    ///     let path = port.name().unwrap_or_default();
+    ///     drop(port);
+    ///     tokio_serial::new(path, 115_200).open_native_async()
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn map_port<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(SerialStream) -> SerialStream,
+    {
+        self.port = f(self.port);
+        self
+    }
+
+    /// Like [`map_port`](Self::map_port), with an `f` that may fail
+    ///
+    /// The framed port is dropped along with its buffers when `f` fails.
+    pub fn try_map_port<F, E>(mut self, f: F) -> Result<Self, E>
+    where
+        F: FnOnce(SerialStream) -> Result<SerialStream, E>,
+    {
+        self.port = f(self.port)?;
+        Ok(self)
+    }
+
     /// Returns a reference to the underlying codec wrapped by
     /// `Framed`.
     ///
@@ -224,3 +468,248 @@ impl<C> SerialFramed<C> {
         &mut self.rd
     }
 }
+
+/// Encodes frames into several buffers, written with a single vectored write
+///
+/// A frame carrying a large payload, like a chunk of a firmware image, is usually a small
+/// header, the payload and a checksum.  Handing out the payload as a shared [`Bytes`] rather
+/// than copying it into the write buffer spares the copy.  See
+/// [`SerialFramed::send_vectored`].
+///
+/// ```
+/// use bytes::Bytes;
+/// use tokio_serial::frame::EncoderVectored;
+///
+/// struct Chunk {
+///     offset: u32,
+///     data: Bytes,
+/// }
+///
+/// struct ChunkEncoder;
+///
+/// impl EncoderVectored<Chunk> for ChunkEncoder {
+///     type Error = std::io::Error;
+///
+///     fn encode_vectored(&mut self, chunk: Chunk, dst: &mut Vec<Bytes>) -> std::io::Result<()> {
+///         let sum = chunk.data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+///         dst.push(Bytes::copy_from_slice(&chunk.offset.to_be_bytes()));
+///         dst.push(chunk.data);
+///         dst.push(Bytes::copy_from_slice(&[sum]));
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait EncoderVectored<Item> {
+    /// The type of encoding errors, I/O errors included
+    type Error: From<io::Error>;
+
+    /// Encode `item` as buffers appended to `dst`, written in order
+    fn encode_vectored(&mut self, item: Item, dst: &mut Vec<Bytes>) -> Result<(), Self::Error>;
+}
+
+impl<C: Unpin> SerialFramed<C> {
+    /// Send `item`, encoded by an [`EncoderVectored`] and written with vectored writes
+    ///
+    /// The frames buffered by the `Sink` are written first.  The buffers of the frame skip
+    /// the write buffer, the in-flight budget and the pacing, and are written out before
+    /// returning.  Ports without vectored writes, like Windows ones, get the buffers one
+    /// write at a time.
+    pub async fn send_vectored<I>(
+        &mut self,
+        item: I,
+    ) -> Result<(), <C as EncoderVectored<I>>::Error>
+    where
+        C: EncoderVectored<I>,
+    {
+        let mut parts = Vec::new();
+        self.codec.encode_vectored(item, &mut parts)?;
+        poll_fn(|cx| self.poll_write_buffered(cx)).await?;
+
+        let mut parts = parts
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<VecDeque<_>>();
+        while !parts.is_empty() {
+            let port = &mut self.port;
+            let slices = parts
+                .iter()
+                .map(|part| IoSlice::new(part))
+                .collect::<Vec<_>>();
+            let mut n = poll_fn(|cx| Pin::new(&mut *port).poll_write_vectored(cx, &slices)).await?;
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+            }
+            while n > 0 {
+                let part = parts.front_mut().expect("no more written than handed out");
+                if n < part.len() {
+                    part.advance(n);
+                    break;
+                }
+                n -= part.len();
+                parts.pop_front();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Time to transmit one character with the current settings of `port`
+fn char_time(port: &SerialStream) -> io::Result<Duration> {
+    Ok(crate::settings::char_time(
+        port.baud_rate()?,
+        port.data_bits()?,
+        port.parity()?,
+        port.stop_bits()?,
+    ))
+}
+
+/// A [`SerialFramed`] for [`TimedDecoder`]s, handing them the silences between the bytes
+///
+/// Reads are timestamped as they complete, see
+/// [`read_timestamped`](SerialStream::read_timestamped), and the decoder gets the gap
+/// before each chunk along with the character time of the port.  When the decoder asks for
+/// an [idle timeout](TimedDecoder::idle_timeout), it is called again once the line stayed
+/// silent that long.
+///
+/// Sending goes through the wrapped [`SerialFramed`], with its budget and pacing.
+#[must_use = "streams do nothing unless polled"]
+#[derive(Debug)]
+pub struct TimedFramed<C> {
+    framed: SerialFramed<C>,
+    last_byte: Option<Instant>,
+    timing: Timing,
+    idle: Option<Pin<Box<Sleep>>>,
+}
+
+impl<C> TimedFramed<C> {
+    /// Frame `port` with the timed `codec`
+    pub fn new(port: SerialStream, codec: C) -> Self {
+        Self {
+            framed: SerialFramed::new(port, codec),
+            last_byte: None,
+            timing: Timing {
+                char_time: Duration::from_secs(0),
+                gap: None,
+                new_bytes: 0,
+            },
+            idle: None,
+        }
+    }
+
+    /// Returns a reference to the wrapped `SerialFramed`
+    pub fn get_ref(&self) -> &SerialFramed<C> {
+        &self.framed
+    }
+
+    /// Returns a mutable reference to the wrapped `SerialFramed`
+    ///
+    /// The character time is read back from the port with every read, so settings changed
+    /// through it apply to the next bytes.
+    pub fn get_mut(&mut self) -> &mut SerialFramed<C> {
+        &mut self.framed
+    }
+
+    /// Consumes the `TimedFramed`, returning the wrapped `SerialFramed`
+    pub fn into_inner(self) -> SerialFramed<C> {
+        self.framed
+    }
+}
+
+impl<C: TimedDecoder + Unpin> Stream for TimedFramed<C> {
+    type Item = Result<C::Item, C::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let framed = &mut this.framed;
+        framed.rd.reserve(INITIAL_RD_CAPACITY);
+
+        loop {
+            if framed.is_readable {
+                if framed.eof {
+                    let frame = framed.codec.decode_eof(&mut framed.rd)?;
+                    if frame.is_none() {
+                        framed.is_readable = false;
+                    }
+                    return Poll::Ready(frame.map(Ok));
+                }
+                if let Some(frame) = framed.codec.decode_timed(&mut framed.rd, &this.timing)? {
+                    // Frames decoded after this one come out of the same bytes
+                    this.timing.new_bytes = this.timing.new_bytes.min(framed.rd.len());
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                framed.is_readable = false;
+            }
+            if framed.eof {
+                return Poll::Ready(None);
+            }
+
+            let read = unsafe {
+                // Same as `SerialFramed`, the port initializes what it reads
+                let buf = &mut *(framed.rd.chunk_mut() as *mut _ as *mut [MaybeUninit<u8>]);
+                let mut read = ReadBuf::uninit(buf);
+                let ptr = read.filled().as_ptr();
+                match framed.port.poll_read_timestamped(cx, &mut read) {
+                    Poll::Ready(result) => {
+                        let at = result?;
+                        assert_eq!(ptr, read.filled().as_ptr());
+                        let n = read.filled().len();
+                        framed.rd.advance_mut(n);
+                        Some((n, at))
+                    }
+                    Poll::Pending => None,
+                }
+            };
+
+            match read {
+                Some((0, _)) => framed.eof = true,
+                Some((n, at)) => {
+                    this.timing = Timing {
+                        char_time: char_time(&framed.port)?,
+                        gap: this
+                            .last_byte
+                            .map(|last| at.saturating_duration_since(last)),
+                        new_bytes: n,
+                    };
+                    this.last_byte = Some(at);
+                    this.idle = framed
+                        .codec
+                        .idle_timeout(this.timing.char_time)
+                        .map(|timeout| {
+                            Box::pin(sleep_until(tokio::time::Instant::from_std(at + timeout)))
+                        });
+                }
+                None => {
+                    let idle = match this.idle.as_mut() {
+                        Some(idle) if !framed.rd.is_empty() => idle,
+                        _ => return Poll::Pending,
+                    };
+                    ready!(idle.as_mut().poll(cx));
+                    this.idle = None;
+                    this.timing.gap = this.last_byte.map(|last| last.elapsed());
+                    this.timing.new_bytes = 0;
+                }
+            }
+            framed.is_readable = true;
+        }
+    }
+}
+
+impl<I, C: Encoder<I> + Unpin> Sink<I> for TimedFramed<C> {
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().framed).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        Pin::new(&mut self.get_mut().framed).start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().framed).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().framed).poll_close(cx)
+    }
+}
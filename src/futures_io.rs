@@ -0,0 +1,354 @@
+//! Serial ports for runtimes other than tokio
+//!
+//! [`SerialStream`](crate::SerialStream) waits for readiness through the tokio reactor, so it
+//! has to be used from inside a tokio runtime.  The [`SerialStream`] of this module implements
+//! the `AsyncRead` and `AsyncWrite` traits of the `futures` crate instead and doesn't need
+//! tokio at all:
+//!
+//! * on unix it waits for readiness with `async-io`, the reactor behind smol and async-std, so
+//!   applications built on those don't start a second reactor for their serial ports.
+//! * on Windows reads and writes are overlapped operations completing on the system thread
+//!   pool, which wake the task directly and work with any executor.
+//!
+//! ```no_run
+//! use futures::io::{AsyncReadExt, AsyncWriteExt};
+//! use tokio_serial::futures_io::SerialStream;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let mut port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//! port.write_all(b"AT\r").await?;
+//! let mut buf = [0u8; 64];
+//! let n = port.read(&mut buf).await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{SerialPort, SerialPortBuilder};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::ready;
+
+use std::io::{Read, Result as IoResult, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::{Operation, PortError};
+#[cfg(unix)]
+use async_io::Async;
+#[cfg(unix)]
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+
+/// Serial port I/O through the traits of the `futures` crate, see the [module](self) docs
+#[derive(Debug)]
+pub struct SerialStream {
+    #[cfg(unix)]
+    inner: Async<Port>,
+    // Overlapped I/O doesn't depend on the runtime, the tokio traits are only a calling
+    // convention there
+    #[cfg(windows)]
+    inner: crate::SerialStream,
+}
+
+/// `async-io` registers anything with a file descriptor
+#[cfg(unix)]
+#[derive(Debug)]
+struct Port(mio_serial::SerialStream);
+
+#[cfg(unix)]
+impl AsFd for Port {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: the descriptor is owned by the port and lives as long as the borrow
+        unsafe { BorrowedFd::borrow_raw(self.0.as_raw_fd()) }
+    }
+}
+
+impl SerialStream {
+    /// Open the port described by `builder`
+    ///
+    /// Unlike [`crate::SerialStream::open`] this needs no runtime to be running.
+    pub fn open(builder: &SerialPortBuilder) -> crate::Result<Self> {
+        #[cfg(unix)]
+        let inner = Async::new(Port(mio_serial::SerialStream::open(builder)?))?;
+        #[cfg(windows)]
+        let inner = crate::SerialStream::open(builder)?;
+        Ok(Self { inner })
+    }
+
+    /// Create a pair of pseudo terminals, see [`crate::SerialStream::pair`]
+    #[cfg(unix)]
+    pub fn pair() -> crate::Result<(Self, Self)> {
+        let (master, slave) = mio_serial::SerialStream::pair()?;
+        let master = Self {
+            inner: Async::new(Port(master))?,
+        };
+        let slave = Self {
+            inner: Async::new(Port(slave))?,
+        };
+        Ok((master, slave))
+    }
+
+    fn port(&self) -> &dyn SerialPort {
+        #[cfg(unix)]
+        return &self.inner.get_ref().0;
+        #[cfg(windows)]
+        return &self.inner;
+    }
+
+    fn port_mut(&mut self) -> &mut dyn SerialPort {
+        // SAFETY: changing the settings of the port leaves its descriptor alone
+        #[cfg(unix)]
+        return unsafe { &mut self.inner.get_mut().0 };
+        #[cfg(windows)]
+        return &mut self.inner;
+    }
+
+    #[cfg(unix)]
+    fn port_error(&self, operation: Operation, err: std::io::Error) -> std::io::Error {
+        PortError::new(self.port().name(), operation, err).into()
+    }
+}
+
+#[cfg(unix)]
+impl AsyncRead for SerialStream {
+    /// Attempts to read bytes on the serial port
+    ///
+    /// End of file means the line hung up, as for the reads of [`crate::SerialStream`].
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<IoResult<usize>> {
+        loop {
+            match (&self.inner.get_ref().0).read(buf) {
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(err) if crate::is_hangup(&err) => {
+                    log::debug!("serial port hung up: {}", err);
+                    return Poll::Ready(Ok(0));
+                }
+                result => {
+                    return Poll::Ready(result.map_err(|e| self.port_error(Operation::Read, e)))
+                }
+            }
+            ready!(self.inner.poll_readable(cx))?;
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsyncWrite for SerialStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        loop {
+            match (&self.inner.get_ref().0).write(buf) {
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+                result => {
+                    return Poll::Ready(result.map_err(|e| self.port_error(Operation::Write, e)))
+                }
+            }
+            ready!(self.inner.poll_writable(cx))?;
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        // Writes go straight to the driver, there is nothing to push out
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(windows)]
+impl AsyncRead for SerialStream {
+    /// Attempts to read bytes on the serial port
+    ///
+    /// End of file means the line hung up, as for the reads of [`crate::SerialStream`].
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<IoResult<usize>> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        ready!(tokio::io::AsyncRead::poll_read(
+            Pin::new(&mut self.get_mut().inner),
+            cx,
+            &mut buf
+        ))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+#[cfg(windows)]
+impl AsyncWrite for SerialStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        tokio::io::AsyncWrite::poll_write(Pin::new(&mut self.get_mut().inner), cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        tokio::io::AsyncWrite::poll_flush(Pin::new(&mut self.get_mut().inner), cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(Pin::new(&mut self.get_mut().inner), cx)
+    }
+}
+
+impl SerialPort for SerialStream {
+    #[inline(always)]
+    fn name(&self) -> Option<String> {
+        self.port().name()
+    }
+
+    #[inline(always)]
+    fn baud_rate(&self) -> crate::Result<u32> {
+        self.port().baud_rate()
+    }
+
+    #[inline(always)]
+    fn data_bits(&self) -> crate::Result<crate::DataBits> {
+        self.port().data_bits()
+    }
+
+    #[inline(always)]
+    fn flow_control(&self) -> crate::Result<crate::FlowControl> {
+        self.port().flow_control()
+    }
+
+    #[inline(always)]
+    fn parity(&self) -> crate::Result<crate::Parity> {
+        self.port().parity()
+    }
+
+    #[inline(always)]
+    fn stop_bits(&self) -> crate::Result<crate::StopBits> {
+        self.port().stop_bits()
+    }
+
+    #[inline(always)]
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    #[inline(always)]
+    fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
+        self.port_mut().set_baud_rate(baud_rate)
+    }
+
+    #[inline(always)]
+    fn set_data_bits(&mut self, data_bits: crate::DataBits) -> crate::Result<()> {
+        self.port_mut().set_data_bits(data_bits)
+    }
+
+    #[inline(always)]
+    fn set_flow_control(&mut self, flow_control: crate::FlowControl) -> crate::Result<()> {
+        self.port_mut().set_flow_control(flow_control)
+    }
+
+    #[inline(always)]
+    fn set_parity(&mut self, parity: crate::Parity) -> crate::Result<()> {
+        self.port_mut().set_parity(parity)
+    }
+
+    #[inline(always)]
+    fn set_stop_bits(&mut self, stop_bits: crate::StopBits) -> crate::Result<()> {
+        self.port_mut().set_stop_bits(stop_bits)
+    }
+
+    #[inline(always)]
+    fn set_timeout(&mut self, _: Duration) -> crate::Result<()> {
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
+        self.port_mut().write_request_to_send(level)
+    }
+
+    #[inline(always)]
+    fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
+        self.port_mut().write_data_terminal_ready(level)
+    }
+
+    #[inline(always)]
+    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+        self.port_mut().read_clear_to_send()
+    }
+
+    #[inline(always)]
+    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+        self.port_mut().read_data_set_ready()
+    }
+
+    #[inline(always)]
+    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+        self.port_mut().read_ring_indicator()
+    }
+
+    #[inline(always)]
+    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+        self.port_mut().read_carrier_detect()
+    }
+
+    #[inline(always)]
+    fn bytes_to_read(&self) -> crate::Result<u32> {
+        self.port().bytes_to_read()
+    }
+
+    #[inline(always)]
+    fn bytes_to_write(&self) -> crate::Result<u32> {
+        self.port().bytes_to_write()
+    }
+
+    #[inline(always)]
+    fn clear(&self, buffer_to_clear: crate::ClearBuffer) -> crate::Result<()> {
+        self.port().clear(buffer_to_clear)
+    }
+
+    /// Cloning SerialStream is not supported.
+    ///
+    /// # Errors
+    /// Always returns `ErrorKind::Other` with a message.
+    #[inline(always)]
+    fn try_clone(&self) -> crate::Result<Box<dyn SerialPort>> {
+        Err(crate::Error::new(
+            crate::ErrorKind::Io(std::io::ErrorKind::Other),
+            "Cannot clone async handles",
+        ))
+    }
+
+    #[inline(always)]
+    fn set_break(&self) -> crate::Result<()> {
+        self.port().set_break()
+    }
+
+    #[inline(always)]
+    fn clear_break(&self) -> crate::Result<()> {
+        self.port().clear_break()
+    }
+}
+
+/// Reads without waiting, failing with `WouldBlock` when there is no data
+impl Read for SerialStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.port_mut().read(buf)
+    }
+}
+
+/// Writes without waiting, failing with `WouldBlock` when the driver's buffer is full
+impl Write for SerialStream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.port_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.port_mut().flush()
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for SerialStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.get_ref().0.as_raw_fd()
+    }
+}
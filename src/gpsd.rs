@@ -0,0 +1,493 @@
+//! gpsd-compatible feed of GNSS data read from a serial port
+//!
+//! [`Server`] speaks enough of the [gpsd JSON protocol](https://gpsd.gitlab.io/gpsd/gpsd_json.html)
+//! for existing clients (`gpspipe`, `cgps`, OpenCPN, libgps based tools, ...) to connect to a
+//! tokio-serial based collector instead of a gpsd instance:
+//!
+//! * `?VERSION;`, `?DEVICES;`, `?WATCH;` and `?POLL;` requests are answered
+//! * `"nmea":true` and `"raw":1` watchers receive NMEA sentences verbatim, binary UBX frames
+//!   are hex dumped for `"raw":1`
+//! * `"raw":2` watchers receive all frames verbatim
+//! * `"json":true` watchers receive `TPV` reports built from `RMC` and `GGA` sentences
+//!
+//! ```no_run
+//! use tokio_serial::{gpsd, SerialPortBuilderExt};
+//!
+//! #[tokio::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let port = tokio_serial::new("/dev/ttyACM0", 9600).open_native_async()?;
+//!     gpsd::Server::bind("127.0.0.1:2947", "/dev/ttyACM0")
+//!         .await?
+//!         .run(port)
+//!         .await
+//! }
+//! ```
+use crate::codec::nmea::{NmeaCodec, Sentence};
+use crate::codec::ubx::{UbxCodec, UbxFrame, SYNC_1, SYNC_2};
+
+use bytes::{Buf, BytesMut};
+use futures::future::poll_fn;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::fmt::Write as _;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Output queued for a client beyond this size is dropped until the client catches up
+const MAX_CLIENT_BACKLOG: usize = 64 * 1024;
+
+/// Size of the chunks read from the port and from clients
+const READ_CHUNK: usize = 4096;
+
+/// gpsd protocol revision implemented
+const PROTO_MAJOR: u32 = 3;
+const PROTO_MINOR: u32 = 14;
+
+/// TCP server relaying GNSS frames from a port in gpsd's formats
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    device: String,
+}
+
+impl Server {
+    /// Bind a server to `addr`, reporting the served port to clients as `device`
+    ///
+    /// gpsd listens on port 2947 by default.
+    pub async fn bind<A: ToSocketAddrs>(addr: A, device: impl Into<String>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self {
+            listener,
+            device: device.into(),
+        })
+    }
+
+    /// Returns the local address the server is listening on
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Relay data read from `port` to connected clients until the port reaches EOF or fails
+    pub async fn run<R: AsyncRead + Unpin>(self, port: R) -> io::Result<()> {
+        let mut feed = Feed {
+            listener: self.listener,
+            device: self.device,
+            port,
+            buf: vec![0; READ_CHUNK],
+            rd: BytesMut::new(),
+            nmea: NmeaCodec::new(),
+            ubx: UbxCodec::new(),
+            fix: Fix::default(),
+            clients: Vec::new(),
+        };
+        poll_fn(|cx| feed.poll(cx)).await
+    }
+}
+
+/// Frames extracted from the port
+enum Frame {
+    Nmea(Sentence),
+    Ubx(UbxFrame),
+}
+
+struct Feed<R> {
+    listener: TcpListener,
+    device: String,
+    port: R,
+    buf: Vec<u8>,
+    rd: BytesMut,
+    nmea: NmeaCodec,
+    ubx: UbxCodec,
+    fix: Fix,
+    clients: Vec<Client>,
+}
+
+impl<R: AsyncRead + Unpin> Feed<R> {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            let mut progress = false;
+
+            while let Poll::Ready(result) = self.listener.poll_accept(cx) {
+                match result {
+                    Ok((socket, peer)) => {
+                        log::debug!("gpsd client connected: {}", peer);
+                        self.clients.push(Client::new(socket));
+                        progress = true;
+                    }
+                    Err(e) => {
+                        log::warn!("gpsd accept failed: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            let mut buf = ReadBuf::new(&mut self.buf);
+            if let Poll::Ready(result) = Pin::new(&mut self.port).poll_read(cx, &mut buf) {
+                result?;
+                if buf.filled().is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                self.rd.extend_from_slice(buf.filled());
+                while let Some(frame) = self.next_frame() {
+                    self.dispatch(frame);
+                }
+                progress = true;
+            }
+
+            let Self {
+                clients,
+                device,
+                fix,
+                ..
+            } = self;
+            for client in clients.iter_mut() {
+                progress |= client.poll(cx, device, fix);
+            }
+            clients.retain(|client| !client.closed);
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// Extract the next NMEA sentence or UBX frame from the read buffer
+    fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            let start = self
+                .rd
+                .iter()
+                .position(|&b| b == b'$' || b == b'!' || b == SYNC_1);
+            match start {
+                Some(start) => self.rd.advance(start),
+                None => {
+                    self.rd.clear();
+                    return None;
+                }
+            }
+
+            let result = if self.rd[0] == SYNC_1 {
+                match self.rd.get(1) {
+                    None => return None,
+                    Some(&SYNC_2) => self.ubx.decode(&mut self.rd).map(|f| f.map(Frame::Ubx)),
+                    Some(_) => {
+                        self.rd.advance(1);
+                        continue;
+                    }
+                }
+            } else {
+                self.nmea.decode(&mut self.rd).map(|s| s.map(Frame::Nmea))
+            };
+
+            match result {
+                Ok(frame) => return frame,
+                Err(e) => log::debug!("gpsd dropping frame: {}", e),
+            }
+        }
+    }
+
+    fn dispatch(&mut self, frame: Frame) {
+        let tpv = match frame {
+            Frame::Nmea(ref sentence) => self.fix.update(sentence, &self.device),
+            Frame::Ubx(_) => None,
+        };
+
+        let mut raw = BytesMut::new();
+        let mut text = String::new();
+        match frame {
+            Frame::Nmea(ref sentence) => {
+                text.push_str(sentence.as_str());
+                text.push_str("\r\n");
+                raw.extend_from_slice(text.as_bytes());
+            }
+            Frame::Ubx(ref ubx) => {
+                let _ = self.ubx.encode(ubx.clone(), &mut raw);
+                for byte in raw.iter() {
+                    let _ = write!(text, "{:02x}", byte);
+                }
+                text.push('\n');
+            }
+        }
+
+        for client in self.clients.iter_mut() {
+            let watch = client.watch;
+            if !watch.enable {
+                continue;
+            }
+            if watch.raw >= 2 {
+                client.queue(&raw);
+            } else if watch.raw == 1 || (watch.nmea && matches!(frame, Frame::Nmea(_))) {
+                client.queue(text.as_bytes());
+            }
+            if watch.json {
+                if let Some(ref tpv) = tpv {
+                    client.queue(tpv.as_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Watcher settings of a client
+#[derive(Debug, Default, Clone, Copy)]
+struct Watch {
+    enable: bool,
+    json: bool,
+    nmea: bool,
+    raw: u8,
+}
+
+impl Watch {
+    fn to_json(self) -> String {
+        format!(
+            "{{\"class\":\"WATCH\",\"enable\":{},\"json\":{},\"nmea\":{},\"raw\":{}}}\r\n",
+            self.enable, self.json, self.nmea, self.raw
+        )
+    }
+}
+
+struct Client {
+    socket: TcpStream,
+    watch: Watch,
+    rx: Vec<u8>,
+    tx: Vec<u8>,
+    closed: bool,
+}
+
+impl Client {
+    fn new(socket: TcpStream) -> Self {
+        let tx = Client::banner().into_bytes();
+        Self {
+            socket,
+            watch: Watch::default(),
+            rx: Vec::new(),
+            tx,
+            closed: false,
+        }
+    }
+
+    fn queue(&mut self, data: &[u8]) {
+        if self.tx.len() + data.len() <= MAX_CLIENT_BACKLOG {
+            self.tx.extend_from_slice(data);
+        }
+    }
+
+    /// Service the client socket, returns `true` if any I/O was performed
+    fn poll(&mut self, cx: &mut Context<'_>, device: &str, fix: &Fix) -> bool {
+        let mut progress = false;
+
+        let mut chunk = [0u8; 512];
+        let mut buf = ReadBuf::new(&mut chunk);
+        if let Poll::Ready(result) = Pin::new(&mut self.socket).poll_read(cx, &mut buf) {
+            progress = true;
+            match result {
+                Ok(()) if buf.filled().is_empty() => {
+                    self.closed = true;
+                    return true;
+                }
+                Ok(()) => {
+                    self.rx.extend_from_slice(buf.filled());
+                    self.handle_requests(device, fix);
+                }
+                Err(_) => {
+                    self.closed = true;
+                    return true;
+                }
+            }
+        }
+
+        if !self.tx.is_empty() {
+            if let Poll::Ready(result) = Pin::new(&mut self.socket).poll_write(cx, &self.tx) {
+                progress = true;
+                match result {
+                    Ok(n) => {
+                        self.tx.drain(..n);
+                    }
+                    Err(_) => self.closed = true,
+                }
+            }
+        }
+        progress
+    }
+
+    fn handle_requests(&mut self, device: &str, fix: &Fix) {
+        while let Some(end) = self.rx.iter().position(|&b| b == b';' || b == b'\n') {
+            let request: Vec<u8> = self.rx.drain(..=end).collect();
+            let request = String::from_utf8_lossy(&request);
+            let request = request.trim_matches(|c: char| c == ';' || c.is_whitespace());
+            if !request.is_empty() {
+                self.handle_request(request, device, fix);
+            }
+        }
+        if self.rx.len() > READ_CHUNK {
+            self.rx.clear();
+        }
+    }
+
+    fn handle_request(&mut self, request: &str, device: &str, fix: &Fix) {
+        let (command, args) = match request.find('=') {
+            Some(eq) => (&request[..eq], Some(&request[eq + 1..])),
+            None => (request, None),
+        };
+        let devices = format!(
+            "{{\"class\":\"DEVICES\",\"devices\":[{{\"class\":\"DEVICE\",\"path\":\"{}\"}}]}}\r\n",
+            device
+        );
+
+        let response = match command {
+            "?VERSION" => Client::banner(),
+            "?DEVICES" => devices,
+            "?WATCH" => {
+                if let Some(args) = args {
+                    let flag = |key| json_field(args, key).map(|v| v == "true");
+                    let raw = json_field(args, "raw").and_then(|v| v.parse().ok());
+                    let nmea = flag("nmea");
+                    self.watch.enable = flag("enable").unwrap_or(true);
+                    self.watch.nmea = nmea.unwrap_or(false);
+                    self.watch.raw = raw.unwrap_or(0);
+                    self.watch.json = flag("json").unwrap_or(nmea.is_none() && raw.is_none());
+                }
+                devices + &self.watch.to_json()
+            }
+            "?POLL" => format!(
+                "{{\"class\":\"POLL\",\"active\":1,\"tpv\":[{}],\"sky\":[]}}\r\n",
+                fix.to_json(device).unwrap_or_default()
+            ),
+            _ => format!(
+                "{{\"class\":\"ERROR\",\"message\":\"Unrecognized request '{}'\"}}\r\n",
+                command.trim_start_matches('?')
+            ),
+        };
+        self.queue(response.as_bytes());
+    }
+
+    fn banner() -> String {
+        format!(
+            "{{\"class\":\"VERSION\",\"release\":\"{}\",\"rev\":\"tokio-serial\",\"proto_major\":{},\"proto_minor\":{}}}\r\n",
+            env!("CARGO_PKG_VERSION"),
+            PROTO_MAJOR,
+            PROTO_MINOR
+        )
+    }
+}
+
+/// Find the raw value of `key` in a flat JSON object
+fn json_field<'a>(object: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("\"{}\"", key);
+    let start = object.find(&pattern)? + pattern.len();
+    let rest = object[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = rest.find([',', '}']).unwrap_or(rest.len());
+    Some(rest[..end].trim())
+}
+
+/// Position/velocity/time state assembled from NMEA sentences
+#[derive(Debug, Default)]
+struct Fix {
+    time: Option<String>,
+    mode: u8,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    alt: Option<f64>,
+    speed: Option<f64>,
+    track: Option<f64>,
+}
+
+impl Fix {
+    /// Update the fix from a sentence, returning a TPV report at the end of an epoch
+    fn update(&mut self, sentence: &Sentence, device: &str) -> Option<String> {
+        let fields: Vec<&str> = sentence.fields().collect();
+        match sentence.sentence_type() {
+            "GGA" if fields.len() >= 9 => {
+                let quality = fields[5].parse::<u8>().unwrap_or(0);
+                self.alt = fields[8].parse().ok().filter(|_| quality > 0);
+                if quality > 0 {
+                    self.lat = coordinate(fields[1], fields[2]);
+                    self.lon = coordinate(fields[3], fields[4]);
+                }
+                None
+            }
+            "RMC" if fields.len() >= 9 => {
+                let valid = fields[1] == "A";
+                self.time = timestamp(fields[0], fields[8]);
+                if valid {
+                    self.lat = coordinate(fields[2], fields[3]);
+                    self.lon = coordinate(fields[4], fields[5]);
+                    self.speed = fields[6].parse::<f64>().ok().map(|knots| knots * 0.514_444);
+                    self.track = fields[7].parse().ok();
+                    self.mode = if self.alt.is_some() { 3 } else { 2 };
+                } else {
+                    self.mode = 1;
+                }
+                self.to_json(device).map(|mut tpv| {
+                    tpv.push_str("\r\n");
+                    tpv
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn to_json(&self, device: &str) -> Option<String> {
+        let time = self.time.as_ref()?;
+        let mut tpv = format!(
+            "{{\"class\":\"TPV\",\"device\":\"{}\",\"mode\":{},\"time\":\"{}\"",
+            device, self.mode, time
+        );
+        if self.mode >= 2 {
+            let fields = [
+                ("lat", self.lat),
+                ("lon", self.lon),
+                ("alt", self.alt),
+                ("speed", self.speed),
+                ("track", self.track),
+            ];
+            for (name, value) in fields.iter() {
+                if let Some(value) = value {
+                    let _ = write!(tpv, ",\"{}\":{}", name, value);
+                }
+            }
+        }
+        tpv.push('}');
+        Some(tpv)
+    }
+}
+
+/// Convert an NMEA `(d)ddmm.mmmm` coordinate and hemisphere to signed decimal degrees
+fn coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let dot = value.find('.').unwrap_or(value.len());
+    if dot < 2 {
+        return None;
+    }
+    let degrees: f64 = value[..dot - 2].parse().ok()?;
+    let minutes: f64 = value[dot - 2..].parse().ok()?;
+    let magnitude = degrees + minutes / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(magnitude),
+        "S" | "W" => Some(-magnitude),
+        _ => None,
+    }
+}
+
+/// Convert NMEA `hhmmss.ss` time and `ddmmyy` date fields to an ISO 8601 timestamp
+fn timestamp(time: &str, date: &str) -> Option<String> {
+    if time.len() < 6 || date.len() != 6 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let seconds: f64 = time[4..].parse().ok()?;
+    // Two digit years pivot at 1980, the start of GPS time
+    let year: u32 = date[4..6].parse().ok()?;
+    let year = if year < 80 { 2000 + year } else { 1900 + year };
+    Some(format!(
+        "{}-{}-{}T{}:{}:{:06.3}Z",
+        year,
+        &date[2..4],
+        &date[0..2],
+        &time[0..2],
+        &time[2..4],
+        seconds
+    ))
+}
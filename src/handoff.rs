@@ -0,0 +1,313 @@
+//! Hand open ports to another process over a Unix domain socket
+//!
+//! Opening a serial device usually takes membership of `dialout` or root.  A small privileged
+//! broker can open the ports and pass them to unprivileged workers instead: [`send_port`]
+//! sends the descriptor of a [`SerialStream`] as `SCM_RIGHTS` ancillary data together with its
+//! path and line settings, [`recv_port`] turns them back into a `SerialStream`.
+//!
+//! ```no_run
+//! use tokio::net::UnixStream;
+//! use tokio_serial::handoff::{recv_port, send_port};
+//! use tokio_serial::SerialStream;
+//!
+//! // Broker
+//! async fn hand_out(worker: &UnixStream) -> tokio_serial::Result<()> {
+//!     let port = SerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//!     send_port(worker, &port).await?;
+//!     Ok(())
+//! }
+//!
+//! // Worker
+//! async fn take(broker: &UnixStream) -> tokio_serial::Result<SerialStream> {
+//!     let (port, builder) = recv_port(broker).await?;
+//!     Ok(port)
+//! }
+//! ```
+//!
+//! Both ends share the same open file description: the sender usually drops its
+//! `SerialStream` once the port was sent.  Only one port travels per message, several ports
+//! are sent one after the other over the same socket.
+use crate::settings::Settings;
+use crate::{DataBits, FlowControl, Parity, SerialPort, SerialPortBuilder, SerialStream, StopBits};
+
+use tokio::io::Interest;
+use tokio::net::UnixStream;
+
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// Format of the payload, bumped when its layout changes
+const VERSION: u8 = 1;
+
+/// Version, baud rate, data bits, parity, stop bits, flow control and length of the path
+const HEADER_LEN: usize = 1 + 4 + 4 + 2;
+
+/// Longest path sent along a port
+const MAX_PATH_LEN: usize = 4096;
+
+/// Send `port` and its settings to the process at the other end of `socket`
+///
+/// The port stays usable on this side, the receiver gets a duplicate of its descriptor.
+pub async fn send_port(socket: &UnixStream, port: &SerialStream) -> crate::Result<()> {
+    let settings = Settings {
+        path: port.name().unwrap_or_default(),
+        baud_rate: port.baud_rate()?,
+        data_bits: port.data_bits()?,
+        flow_control: port.flow_control()?,
+        parity: port.parity()?,
+        stop_bits: port.stop_bits()?,
+        dtr_on_open: None,
+    };
+    let payload = encode(&settings)?;
+
+    // The descriptor goes along with the first byte, the rest is plain stream data
+    let mut sent = loop {
+        socket.writable().await?;
+        match socket.try_io(Interest::WRITABLE, || {
+            send_with_fd(socket.as_raw_fd(), &payload, port.as_raw_fd())
+        }) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            result => break result?,
+        }
+    };
+    while sent < payload.len() {
+        socket.writable().await?;
+        match socket.try_write(&payload[sent..]) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            result => sent += result?,
+        }
+    }
+    Ok(())
+}
+
+/// Receive a port sent with [`send_port`]
+///
+/// The line settings are applied again to the received descriptor, see
+/// [`SerialStream::from_owned_fd`].  The returned builder holds the path and settings of the
+/// port at the sending end, the path is not known to the port itself.
+pub async fn recv_port(socket: &UnixStream) -> crate::Result<(SerialStream, SerialPortBuilder)> {
+    let mut payload = vec![0u8; HEADER_LEN + MAX_PATH_LEN];
+    let (mut received, fd) = loop {
+        socket.readable().await?;
+        match socket.try_io(Interest::READABLE, || {
+            recv_with_fd(socket.as_raw_fd(), &mut payload[..HEADER_LEN])
+        }) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            result => break result?,
+        }
+    };
+    let fd = match fd {
+        Some(fd) => fd,
+        None if received == 0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        None => return Err(invalid_data("no descriptor received along the port settings").into()),
+    };
+
+    // Reads never go past the message, the next one carries its own descriptor
+    let mut expected = HEADER_LEN;
+    loop {
+        if received >= HEADER_LEN {
+            expected = HEADER_LEN + path_len(&payload)?;
+        }
+        if received == expected {
+            break;
+        }
+        socket.readable().await?;
+        match socket.try_read(&mut payload[received..expected]) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            result => received += result?,
+        }
+    }
+
+    let settings = decode(&payload[..expected])?;
+    let builder = crate::new(settings.path, settings.baud_rate)
+        .data_bits(settings.data_bits)
+        .flow_control(settings.flow_control)
+        .parity(settings.parity)
+        .stop_bits(settings.stop_bits);
+    let port = SerialStream::from_owned_fd(fd, &builder)?;
+    Ok((port, builder))
+}
+
+fn encode(settings: &Settings) -> io::Result<Vec<u8>> {
+    let path = settings.path.as_bytes();
+    if path.len() > MAX_PATH_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "port path too long to be sent",
+        ));
+    }
+
+    let mut payload = Vec::with_capacity(HEADER_LEN + path.len());
+    payload.push(VERSION);
+    payload.extend_from_slice(&settings.baud_rate.to_le_bytes());
+    payload.push(match settings.data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    });
+    payload.push(match settings.parity {
+        Parity::None => b'N',
+        Parity::Odd => b'O',
+        Parity::Even => b'E',
+    });
+    payload.push(match settings.stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    });
+    payload.push(match settings.flow_control {
+        FlowControl::None => b'N',
+        FlowControl::Software => b'S',
+        FlowControl::Hardware => b'H',
+    });
+    payload.extend_from_slice(&(path.len() as u16).to_le_bytes());
+    payload.extend_from_slice(path);
+    Ok(payload)
+}
+
+fn path_len(payload: &[u8]) -> io::Result<usize> {
+    if payload[0] != VERSION {
+        return Err(invalid_data("unknown port handoff version"));
+    }
+    let len = u16::from_le_bytes([payload[9], payload[10]]) as usize;
+    if len > MAX_PATH_LEN {
+        return Err(invalid_data("port path too long"));
+    }
+    Ok(len)
+}
+
+fn decode(payload: &[u8]) -> io::Result<Settings> {
+    path_len(payload)?;
+    let baud_rate = u32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    let data_bits = match payload[5] {
+        5 => DataBits::Five,
+        6 => DataBits::Six,
+        7 => DataBits::Seven,
+        8 => DataBits::Eight,
+        _ => return Err(invalid_data("invalid data bits")),
+    };
+    let parity = match payload[6] {
+        b'N' => Parity::None,
+        b'O' => Parity::Odd,
+        b'E' => Parity::Even,
+        _ => return Err(invalid_data("invalid parity")),
+    };
+    let stop_bits = match payload[7] {
+        1 => StopBits::One,
+        2 => StopBits::Two,
+        _ => return Err(invalid_data("invalid stop bits")),
+    };
+    let flow_control = match payload[8] {
+        b'N' => FlowControl::None,
+        b'S' => FlowControl::Software,
+        b'H' => FlowControl::Hardware,
+        _ => return Err(invalid_data("invalid flow control")),
+    };
+    let path = String::from_utf8(payload[HEADER_LEN..].to_vec())
+        .map_err(|_| invalid_data("port path is not UTF-8"))?;
+    Ok(Settings {
+        path,
+        baud_rate,
+        data_bits,
+        flow_control,
+        parity,
+        stop_bits,
+        dtr_on_open: None,
+    })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Room for the control message carrying one descriptor
+fn cmsg_space() -> usize {
+    // SAFETY: only computes a size
+    unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) as usize }
+}
+
+/// `sendmsg` of `buf` with `fd` attached
+fn send_with_fd(socket: RawFd, buf: &[u8], fd: RawFd) -> io::Result<usize> {
+    let mut control = vec![0u8; cmsg_space()];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // SAFETY: msghdr is plain data, all pointers stay valid for the call
+    unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+
+        match libc::sendmsg(socket, &msg, SEND_FLAGS) {
+            -1 => Err(io::Error::last_os_error()),
+            n => Ok(n as usize),
+        }
+    }
+}
+
+/// `recvmsg` into `buf`, returning the descriptor received along
+fn recv_with_fd(socket: RawFd, buf: &mut [u8]) -> io::Result<(usize, Option<OwnedFd>)> {
+    let mut control = vec![0u8; cmsg_space()];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // SAFETY: msghdr is plain data, all pointers stay valid for the call and the control
+    // messages are walked within the length set by the kernel
+    unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+
+        let n = match libc::recvmsg(socket, &mut msg, RECV_FLAGS) {
+            -1 => return Err(io::Error::last_os_error()),
+            n => n as usize,
+        };
+
+        let mut received = None;
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg);
+                let count = ((*cmsg).cmsg_len as usize - (data as usize - cmsg as usize))
+                    / mem::size_of::<RawFd>();
+                for i in 0..count {
+                    let fd = std::ptr::read_unaligned((data as *const RawFd).add(i));
+                    // Extra descriptors are closed when dropped
+                    let fd = OwnedFd::from_raw_fd(fd);
+                    #[cfg(not(target_os = "linux"))]
+                    libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC);
+                    received.get_or_insert(fd);
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(invalid_data("too many descriptors received"));
+        }
+        Ok((n, received))
+    }
+}
+
+#[cfg(target_os = "linux")]
+const SEND_FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(target_os = "linux"))]
+const SEND_FLAGS: libc::c_int = 0;
+
+#[cfg(target_os = "linux")]
+const RECV_FLAGS: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(not(target_os = "linux"))]
+const RECV_FLAGS: libc::c_int = 0;
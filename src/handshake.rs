@@ -0,0 +1,279 @@
+//! ENQ/ACK/NAK handshakes over a framed port
+//!
+//! [`Handshake`] sends blocks with the [handshake codec](crate::codec::handshake) and waits
+//! for each to be acknowledged, sending it again on `NAK` or silence up to a number of
+//! retries.  [`Tap`] builds the Telocator Alphanumeric Protocol of paging terminals on it:
+//! log in, send pages, log out.
+//!
+//! ```no_run
+//! use tokio_serial::codec::handshake::HandshakeCodec;
+//! use tokio_serial::frame::SerialFramed;
+//! use tokio_serial::handshake::Tap;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut tap = Tap::new(SerialFramed::new(port, HandshakeCodec::tap()));
+//! tap.login("000000").await?;
+//! tap.send_page("5551234", "Pump 3 pressure low").await?;
+//! tap.logout().await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::codec::handshake::{Outgoing, Reply, EOT, ESC};
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use tokio::time::{timeout_at, Instant};
+
+use std::io;
+use std::time::Duration;
+
+/// Times a block is sent again after a `NAK` or no answer, by default
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Time the other end is given to answer, by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends blocks until they are acknowledged, see the [module](self) docs
+#[derive(Debug)]
+pub struct Handshake<F> {
+    framed: F,
+    retries: u32,
+    timeout: Duration,
+}
+
+impl<F> Handshake<F>
+where
+    F: Sink<Outgoing, Error = io::Error> + Stream<Item = io::Result<Reply>> + Unpin,
+{
+    /// Run handshakes over `framed`, using a
+    /// [`HandshakeCodec`](crate::codec::handshake::HandshakeCodec)
+    pub fn new(framed: F) -> Self {
+        Self {
+            framed,
+            retries: DEFAULT_RETRIES,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Send a block up to `retries` more times, [`DEFAULT_RETRIES`] by default
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// Returns the times a block is sent again
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Wait up to `timeout` for an answer, [`DEFAULT_TIMEOUT`] by default
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the time the other end is given to answer
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns a reference to the framed port
+    pub fn get_ref(&self) -> &F {
+        &self.framed
+    }
+
+    /// Returns a mutable reference to the framed port
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.framed
+    }
+
+    /// Consumes the handshake, returning the framed port
+    pub fn into_inner(self) -> F {
+        self.framed
+    }
+
+    /// Send a block of `fields` until it is acknowledged, returning the text received with
+    /// the `ACK`
+    ///
+    /// ## Errors
+    ///
+    /// As for [`confirm`](Self::confirm).
+    pub async fn send_block(&mut self, fields: Vec<String>) -> io::Result<Vec<String>> {
+        self.confirm(Outgoing::Block(fields)).await
+    }
+
+    /// Send `ENQ` until the other end acknowledges it is ready
+    ///
+    /// ## Errors
+    ///
+    /// As for [`confirm`](Self::confirm).
+    pub async fn enquire(&mut self, enq: u8) -> io::Result<()> {
+        self.confirm(Outgoing::Control(enq)).await.map(drop)
+    }
+
+    /// Send `item` until it is acknowledged, returning the text received with the `ACK`
+    ///
+    /// ## Errors
+    ///
+    /// * `TimedOut` if it was still refused or unanswered after the retries.
+    /// * `Other` if the other end abandoned it.
+    /// * `ConnectionAborted` if the other end ended the exchange.
+    /// * `UnexpectedEof` if the stream ended, and the errors of the framed port.
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn confirm(&mut self, item: Outgoing) -> io::Result<Vec<String>> {
+        for _ in 0..=self.retries {
+            self.framed.send(item.clone()).await?;
+            let mut text = Vec::new();
+            let deadline = Instant::now() + self.timeout;
+            loop {
+                match self.next_reply(deadline).await? {
+                    Some(Reply::Ack) => return Ok(text),
+                    Some(Reply::Text(line)) => text.push(line),
+                    Some(Reply::Abandon) => {
+                        return Err(io::Error::new(io::ErrorKind::Other, "block abandoned"))
+                    }
+                    Some(Reply::Eot | Reply::Disconnect) => return Err(aborted()),
+                    // Send again
+                    Some(Reply::Nak) | None => break,
+                    Some(Reply::Enq) => {}
+                }
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "block not acknowledged",
+        ))
+    }
+
+    /// Wait for a reply matching `expected`, returning the text received before it
+    ///
+    /// ## Errors
+    ///
+    /// * `TimedOut` if it didn't come within the timeout.
+    /// * `ConnectionAborted` if the other end ended the exchange.
+    /// * `UnexpectedEof` if the stream ended, and the errors of the framed port.
+    pub async fn wait_for(&mut self, expected: &Reply) -> io::Result<Vec<String>> {
+        let deadline = Instant::now() + self.timeout;
+        let mut text = Vec::new();
+        loop {
+            match self.next_reply(deadline).await? {
+                Some(reply) if &reply == expected => return Ok(text),
+                Some(Reply::Text(line)) => text.push(line),
+                Some(Reply::Eot | Reply::Disconnect) => return Err(aborted()),
+                Some(_) => {}
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "expected reply didn't come",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Returns the next reply, `None` at the deadline
+    async fn next_reply(&mut self, deadline: Instant) -> io::Result<Option<Reply>> {
+        match timeout_at(deadline, self.framed.next()).await {
+            Ok(Some(reply)) => reply.map(Some),
+            Ok(None) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+fn aborted() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "the other end ended the exchange",
+    )
+}
+
+/// A Telocator Alphanumeric Protocol session with a paging terminal
+///
+/// The framed port must use [`HandshakeCodec::tap`](crate::codec::handshake::HandshakeCodec::tap).
+#[derive(Debug)]
+pub struct Tap<F> {
+    handshake: Handshake<F>,
+}
+
+impl<F> Tap<F>
+where
+    F: Sink<Outgoing, Error = io::Error> + Stream<Item = io::Result<Reply>> + Unpin,
+{
+    /// Run a session over `framed`
+    pub fn new(framed: F) -> Self {
+        Self {
+            handshake: Handshake::new(framed),
+        }
+    }
+
+    /// Returns the handshake, to set its retries and timeout
+    pub fn handshake_mut(&mut self) -> &mut Handshake<F> {
+        &mut self.handshake
+    }
+
+    /// Consumes the session, returning the framed port
+    pub fn into_inner(self) -> F {
+        self.handshake.into_inner()
+    }
+
+    /// Wake the terminal up and log in with `password`, empty for none, in the automatic
+    /// mode `PG1`
+    ///
+    /// ## Errors
+    ///
+    /// As for [`Handshake::confirm`].
+    pub async fn login(&mut self, password: &str) -> io::Result<()> {
+        // Carriage returns until the terminal prompts
+        let prompt = Reply::Text("ID=".into());
+        let mut attempts = 0;
+        let timeout = self.handshake.timeout;
+        self.handshake.timeout = timeout.min(Duration::from_secs(2));
+        let woken = loop {
+            self.handshake
+                .framed
+                .send(Outgoing::Text(String::new()))
+                .await?;
+            match self.handshake.wait_for(&prompt).await {
+                Err(e)
+                    if e.kind() == io::ErrorKind::TimedOut && attempts < self.handshake.retries =>
+                {
+                    attempts += 1
+                }
+                result => break result,
+            }
+        };
+        self.handshake.timeout = timeout;
+        woken?;
+
+        let login = format!("{}PG1{}", char::from(ESC), password);
+        self.handshake.confirm(Outgoing::Text(login)).await?;
+        // Go ahead
+        let go_ahead = Reply::Text(format!("{}[p", char::from(ESC)));
+        self.handshake.wait_for(&go_ahead).await?;
+        Ok(())
+    }
+
+    /// Send `message` to the pager `pager_id`, returning the terminal's text
+    ///
+    /// ## Errors
+    ///
+    /// As for [`Handshake::confirm`], `Other` if the terminal rejected the page.
+    pub async fn send_page(&mut self, pager_id: &str, message: &str) -> io::Result<Vec<String>> {
+        self.handshake
+            .send_block(vec![pager_id.to_owned(), message.to_owned()])
+            .await
+    }
+
+    /// End the session
+    ///
+    /// ## Errors
+    ///
+    /// `TimedOut` if the terminal didn't hang up, and the errors of the framed port.
+    pub async fn logout(&mut self) -> io::Result<()> {
+        let eot = String::from(char::from(EOT));
+        self.handshake.framed.send(Outgoing::Text(eot)).await?;
+        match self.handshake.wait_for(&Reply::Disconnect).await {
+            Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => Ok(()),
+            result => result.map(drop),
+        }
+    }
+}
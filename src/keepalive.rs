@@ -0,0 +1,235 @@
+//! Keeping idle links up
+//!
+//! Radio modems, cellular links and some terminal servers drop a connection that carried
+//! nothing for a while.  [`Keepalive`] wraps a framed port and sends a keepalive frame
+//! whenever the link was idle in both directions for the configured time.  It may also
+//! [expect a reply](Keepalive::expect_reply) to each keepalive, in which case an unanswered
+//! one is reported as a `TimedOut` error from the stream: the cue to reopen or redial.
+//!
+//! Replies to keepalives are taken out of the stream, everything else is passed through.
+//! The keepalive goes out while the stream is being polled.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use futures::StreamExt;
+//! use tokio_serial::frame::SerialFramed;
+//! use tokio_serial::keepalive::Keepalive;
+//! use tokio_util::codec::LinesCodec;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> Result<(), Box<dyn std::error::Error>> {
+//! let framed = SerialFramed::new(port, LinesCodec::new());
+//! let mut link = Keepalive::new(framed, "AT".to_string(), Duration::from_secs(30));
+//! link.expect_reply(Duration::from_secs(2), |line: &String| line == "OK");
+//! while let Some(line) = link.next().await {
+//!     println!("{}", line?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use futures::{ready, Sink, Stream};
+use tokio::time::{sleep_until, Instant, Sleep};
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Matcher recognizing the reply to a keepalive
+type Matcher<Resp> = Box<dyn FnMut(&Resp) -> bool + Send>;
+
+enum State {
+    /// Waiting for the link to be idle long enough
+    Idle,
+    /// The keepalive is due
+    Send,
+    /// The keepalive was handed to the sink
+    Flush,
+    /// Waiting for the reply to the keepalive
+    Reply,
+}
+
+/// Sends a keepalive frame over an idle link, see the [module](self) docs
+pub struct Keepalive<F, Req, Resp> {
+    framed: F,
+    frame: Req,
+    idle: Duration,
+    reply: Option<(Duration, Matcher<Resp>)>,
+    state: State,
+    // When the keepalive or its reply is due, set on the first poll
+    deadline: Option<Instant>,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl<F, Req, Resp> Keepalive<F, Req, Resp> {
+    /// Send `frame` over `framed` whenever nothing was sent or received for `idle`
+    pub fn new(framed: F, frame: Req, idle: Duration) -> Self {
+        Self {
+            framed,
+            frame,
+            idle,
+            reply: None,
+            state: State::Idle,
+            deadline: None,
+            timer: None,
+        }
+    }
+
+    /// Expect a frame for which `matcher` returns `true` within `timeout` of each keepalive
+    ///
+    /// The stream fails with `TimedOut` if none came, and keeps going afterwards.
+    pub fn expect_reply<M>(&mut self, timeout: Duration, matcher: M)
+    where
+        M: FnMut(&Resp) -> bool + Send + 'static,
+    {
+        self.reply = Some((timeout, Box::new(matcher)));
+    }
+
+    /// Returns the idle time after which a keepalive is sent
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+
+    /// Returns a reference to the framed port
+    pub fn get_ref(&self) -> &F {
+        &self.framed
+    }
+
+    /// Returns a mutable reference to the framed port
+    ///
+    /// Frames sent or received through it don't count as activity.
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.framed
+    }
+
+    /// Consumes the wrapper, returning the framed port
+    pub fn into_inner(self) -> F {
+        self.framed
+    }
+
+    /// Count activity on the link, restarting the idle time
+    fn touch(&mut self) {
+        if let State::Idle = self.state {
+            self.deadline = Some(Instant::now() + self.idle);
+        }
+    }
+
+    fn poll_deadline(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let idle = self.idle;
+        let deadline = *self.deadline.get_or_insert_with(|| Instant::now() + idle);
+        let timer = self
+            .timer
+            .get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+        if timer.deadline() != deadline {
+            timer.as_mut().reset(deadline);
+        }
+        timer.as_mut().poll(cx)
+    }
+}
+
+impl<F, Req, Resp, E> Stream for Keepalive<F, Req, Resp>
+where
+    F: Sink<Req, Error = E> + Stream<Item = Result<Resp, E>> + Unpin,
+    Req: Clone + Unpin,
+    E: From<io::Error>,
+{
+    type Item = Result<Resp, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match Pin::new(&mut this.framed).poll_next(cx) {
+                Poll::Ready(Some(Ok(frame))) => {
+                    if let (State::Reply, Some((_, matcher))) = (&this.state, &mut this.reply) {
+                        if matcher(&frame) {
+                            this.state = State::Idle;
+                            this.touch();
+                            continue;
+                        }
+                    }
+                    this.touch();
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                Poll::Ready(other) => return Poll::Ready(other),
+                Poll::Pending => {}
+            }
+
+            match this.state {
+                State::Idle => {
+                    ready!(this.poll_deadline(cx));
+                    this.state = State::Send;
+                }
+                State::Send => {
+                    let framed = Pin::new(&mut this.framed);
+                    if let Err(e) = ready!(framed.poll_ready(cx)) {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    if let Err(e) = Pin::new(&mut this.framed).start_send(this.frame.clone()) {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    this.state = State::Flush;
+                }
+                State::Flush => {
+                    if let Err(e) = ready!(Pin::new(&mut this.framed).poll_flush(cx)) {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    log::debug!("sent keepalive");
+                    match &this.reply {
+                        Some((timeout, _)) => {
+                            this.deadline = Some(Instant::now() + *timeout);
+                            this.state = State::Reply;
+                        }
+                        None => {
+                            this.state = State::Idle;
+                            this.touch();
+                        }
+                    }
+                }
+                State::Reply => {
+                    ready!(this.poll_deadline(cx));
+                    this.state = State::Idle;
+                    this.touch();
+                    let e = io::Error::new(io::ErrorKind::TimedOut, "keepalive unanswered");
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+            }
+        }
+    }
+}
+
+impl<F, Req, Resp> Sink<Req> for Keepalive<F, Req, Resp>
+where
+    F: Sink<Req> + Unpin,
+    Req: Unpin,
+{
+    type Error = F::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Req) -> Result<(), Self::Error> {
+        self.touch();
+        Pin::new(&mut self.framed).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.framed).poll_close(cx)
+    }
+}
+
+impl<F: fmt::Debug, Req: fmt::Debug, Resp> fmt::Debug for Keepalive<F, Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keepalive")
+            .field("framed", &self.framed)
+            .field("frame", &self.frame)
+            .field("idle", &self.idle)
+            .field("expects_reply", &self.reply.is_some())
+            .finish()
+    }
+}
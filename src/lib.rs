@@ -7,27 +7,226 @@
 #![deny(missing_docs)]
 #![warn(rust_2018_idioms)]
 
-// Re-export serialport types and traits from mio_serial
-pub use mio_serial::{
-    available_ports, new, ClearBuffer, DataBits, Error, ErrorKind, FlowControl, Parity, SerialPort,
-    SerialPortBuilder, SerialPortInfo, SerialPortType, StopBits, UsbPortInfo,
+// Re-export serialport types and traits
+pub use serialport::{
+    new, ClearBuffer, DataBits, Error, ErrorKind, FlowControl, Parity, SerialPort,
+    SerialPortBuilder, StopBits,
 };
+// Port enumeration, left out of `minimal` builds
+#[cfg(not(feature = "minimal"))]
+pub use serialport::{available_ports, SerialPortInfo, SerialPortType, UsbPortInfo};
 
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+#[cfg(any(unix, windows))]
+use futures::ready;
+#[cfg(any(unix, windows))]
+use tokio::io::{AsyncBufRead, ReadBuf};
+use tokio::io::{AsyncRead, AsyncWrite};
 
 #[cfg(unix)]
 use std::convert::TryFrom;
+#[cfg(any(unix, windows))]
 use std::io::{Read, Result as IoResult, Write};
+#[cfg(any(unix, windows))]
 use std::pin::Pin;
+#[cfg(any(unix, windows))]
 use std::task::{Context, Poll};
-use std::time::Duration;
+#[cfg(any(unix, windows))]
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "codec")]
 pub mod frame;
 
+#[cfg(feature = "codec")]
+pub mod codec;
+
+#[cfg(feature = "codec")]
+pub mod transaction;
+
+#[cfg(feature = "codec")]
+pub mod detect;
+
+#[cfg(feature = "codec")]
+pub mod keepalive;
+
+#[cfg(feature = "codec")]
+pub mod modbus;
+
+#[cfg(feature = "codec")]
+pub mod poller;
+
+#[cfg(feature = "codec")]
+pub mod slcan;
+
+#[cfg(feature = "codec")]
+pub mod handshake;
+
+#[cfg(feature = "gpsd")]
+pub mod gpsd;
+
+#[cfg(feature = "rfc2217")]
+pub mod rfc2217;
+
+#[cfg(feature = "tcp")]
+mod tcp;
+#[cfg(feature = "tcp")]
+pub use tcp::TcpSerial;
+
+#[cfg(feature = "test-util")]
+pub mod test;
+
+#[cfg(feature = "throttle")]
+pub mod throttle;
+
+#[cfg(all(any(unix, windows), feature = "events"))]
+pub mod events;
+
+#[cfg(all(any(unix, windows), feature = "monitor"))]
+pub mod monitor;
+
+#[cfg(all(any(unix, windows), feature = "time"))]
+pub mod onewire;
+
+#[cfg(feature = "time")]
+pub mod probe;
+
+#[cfg(feature = "time")]
+pub mod recorder;
+
+#[cfg(feature = "time")]
+pub mod sbd;
+
+#[cfg(feature = "trigger")]
+pub mod trigger;
+
+#[cfg(all(any(unix, windows), feature = "time"))]
+pub mod sdi12;
+
+#[cfg(feature = "bench")]
+pub mod bench;
+
+#[cfg(all(target_os = "linux", feature = "uring"))]
+pub mod uring;
+
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub mod web;
+
+#[cfg(unix)]
+pub mod android;
+
+#[cfg(unix)]
+pub mod handoff;
+
+#[cfg(unix)]
+pub mod console;
+
+#[cfg(target_os = "linux")]
+pub mod pps;
+
+#[cfg(any(unix, windows))]
+pub mod port_set;
+
+#[cfg(any(unix, windows))]
+pub mod reload;
+
+#[cfg(any(unix, windows))]
+pub mod blocking;
+
+pub mod channel_reader;
+
+pub mod write_queue;
+
+#[cfg(any(
+    unix,
+    windows,
+    feature = "rfc2217",
+    feature = "tcp",
+    feature = "test-util",
+    feature = "wasm"
+))]
+mod settings;
+
+#[cfg(any(unix, windows))]
+pub mod config;
+
+mod error;
+pub use error::{ErrorClass, Operation, PortError};
+
+#[cfg(any(unix, windows))]
+mod builder;
+#[cfg(any(unix, windows))]
+pub use builder::{AsyncSerialPortBuilder, PlatformOptions};
+
+#[cfg(any(unix, windows))]
+mod serial_config;
+#[cfg(any(unix, windows))]
+pub use serial_config::{ConfigChange, ConfigDiff, SerialConfig};
+
+#[cfg(any(unix, windows))]
+mod batch;
+#[cfg(any(unix, windows))]
+pub use batch::{open_all, OpenReport, PortOutcome};
+
+#[cfg(all(any(unix, windows), not(feature = "minimal")))]
+mod port_info;
+#[cfg(all(any(unix, windows), not(feature = "minimal")))]
+pub use port_info::{available_ports_ext, PortInfoExt};
+
+#[cfg(any(unix, windows))]
+mod busy;
+#[cfg(any(unix, windows))]
+mod claim;
+#[cfg(target_os = "linux")]
+pub use busy::{port_holders, PortHolder};
+#[cfg(any(unix, windows))]
+pub use claim::is_open_in_process;
+
+#[cfg(any(unix, windows))]
+mod read_buffer;
+#[cfg(any(unix, windows))]
+use read_buffer::ReadBuffer;
+
+#[cfg(unix)]
+mod termios;
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod darwin;
+
+#[cfg(any(
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+mod bsd;
+#[cfg(any(
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+pub use bsd::callout_path;
+
+#[cfg(windows)]
+mod com;
+#[cfg(windows)]
+pub use com::{DtrControl, Purge, RtsControl};
+#[cfg(windows)]
+mod overlapped;
+#[cfg(windows)]
+pub use overlapped::CommTimeouts;
+
+#[cfg(windows)]
+mod comm_event;
+#[cfg(windows)]
+pub use comm_event::{CommEvent, CommEvents};
+
 #[cfg(unix)]
 mod os_prelude {
-    pub use futures::ready;
+    pub use std::mem::MaybeUninit;
+    pub use std::os::unix::io::AsRawFd;
     pub use tokio::io::unix::AsyncFd;
 }
 
@@ -36,14 +235,23 @@ mod os_prelude {
     pub use std::mem;
     pub use std::ops::{Deref, DerefMut};
     pub use std::os::windows::prelude::*;
-    pub use tokio::net::windows::named_pipe;
 }
 
+#[cfg(any(unix, windows))]
 use crate::os_prelude::*;
 
+/// Number of spurious readiness events the unix poll loops retry before yielding
+///
+/// Readiness can be reported while the read or write still fails with `WouldBlock`.  The
+/// loops clear it and retry, but a device that keeps reporting readiness without data would
+/// otherwise keep the task spinning inside a single `poll_*` call.
+#[cfg(unix)]
+const SPURIOUS_RETRIES: usize = 8;
+
 /// A type for results generated by interacting with serial ports.
-pub type Result<T> = mio_serial::Result<T>;
+pub type Result<T> = serialport::Result<T>;
 
+#[cfg(any(unix, windows))]
 /// Async serial port I/O
 ///
 /// Reading and writing to a `SerialStream` is usually done using the
@@ -57,46 +265,196 @@ pub type Result<T> = mio_serial::Result<T>;
 pub struct SerialStream {
     #[cfg(unix)]
     inner: AsyncFd<mio_serial::SerialStream>,
-    // Named pipes and COM ports are actually two entirely different things that hardly have anything in common.
-    // The only thing they share is the opaque `HANDLE` type that can be fed into `CreateFileW`, `ReadFile`, `WriteFile`, etc.
-    //
-    // Both `mio` and `tokio` don't yet have any code to work on arbitrary HANDLEs.
-    // But they have code for dealing with named pipes, and we (ab)use that here to work on COM ports.
+    // Neither `mio` nor `tokio` can wait on arbitrary HANDLEs, so reads and writes are issued
+    // as overlapped operations on the port handle, see the `overlapped` module.
     #[cfg(windows)]
-    inner: named_pipe::NamedPipeClient,
+    inner: overlapped::OverlappedIo,
     // The com port is kept around for serialport related methods
     #[cfg(windows)]
     com: mem::ManuallyDrop<mio_serial::SerialStream>,
+    #[cfg(windows)]
+    path: String,
+    #[cfg(windows)]
+    lock: Option<com::PortLock>,
+    // Released when the port is dropped, see the `claim` module
+    #[cfg_attr(windows, allow(dead_code))]
+    claim: Option<claim::Claim>,
+    read_buf: ReadBuffer,
+    drop_output: PendingOutput,
+    // Ports opening their descriptor themselves to save its settings have an unnamed
+    // `TTYPort`, the path is kept here instead
+    #[cfg(unix)]
+    name: Option<String>,
+    original_settings: Option<OriginalSettings>,
+    restore_settings: bool,
+    #[cfg(unix)]
+    read_low_watermark: usize,
+}
+
+/// Handling of output still waiting to be transmitted when a port is closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingOutput {
+    /// Leave it to the driver, which usually keeps transmitting for a while after the close
+    Keep,
+    /// Wait until it has been transmitted
+    Drain,
+    /// Discard it
+    Discard,
 }
 
+// The settings a port had before it was opened, see `SerialStream::set_restore_settings_on_close`
+#[cfg(unix)]
+type OriginalSettings = termios::Original;
+#[cfg(windows)]
+type OriginalSettings = com::Original;
+
+/// A port opened by [`SerialStream::open_detached`], not yet registered with the reactor
+#[cfg(unix)]
+pub(crate) struct Detached {
+    port: mio_serial::SerialStream,
+    name: Option<String>,
+    original: Option<termios::Original>,
+    claim: Option<claim::Claim>,
+}
+
+#[cfg(unix)]
+impl Detached {
+    /// Register the port with the reactor of the current runtime
+    pub(crate) fn attach(self) -> crate::Result<SerialStream> {
+        let mut port = SerialStream::from_mio(self.port)?;
+        port.name = self.name;
+        port.restore_settings = self.original.is_some();
+        port.original_settings = self.original;
+        port.claim = self.claim;
+        Ok(port)
+    }
+}
+
+#[cfg(any(unix, windows))]
 impl SerialStream {
     /// Open serial port from a provided path, using the default reactor.
+    ///
+    /// ## Errors
+    ///
+    /// * `NoDevice` if the device is in use.  The description says by whom where that can be
+    ///   found out: the processes holding it on Linux, see [`port_holders`], a sharing
+    ///   violation on Windows.
+    /// * `Io(AddrInUse)`, classified as [`ErrorClass::AlreadyOpen`], if a port of this
+    ///   process already has the device open, see [`is_open_in_process`] and
+    ///   [`SerialPortBuilderExt::allow_shared`].
     pub fn open(builder: &crate::SerialPortBuilder) -> crate::Result<Self> {
-        let port = mio_serial::SerialStream::open(builder)?;
-
         #[cfg(unix)]
         {
-            Ok(Self {
-                inner: AsyncFd::new(port)?,
-            })
+            Self::open_detached(builder, false, false)?.attach()
         }
 
         #[cfg(windows)]
         {
-            let handle = port.as_raw_handle();
-            // Keep the com port around to use for serialport related things
-            let com = mem::ManuallyDrop::new(port);
-            Ok(Self {
-                inner: unsafe { named_pipe::NamedPipeClient::from_raw_handle(handle)? },
-                com,
-            })
+            let path = settings::Settings::from_builder(builder).path;
+            Self::open_com(builder, &path, true, false, false)
         }
     }
 
+    /// The part of [`open`](Self::open) that blocks and doesn't need the reactor
+    ///
+    /// A `shared` port doesn't claim its device within the process.  With `restore`, the
+    /// settings the device had are saved to be restored on close.
+    #[cfg(unix)]
+    pub(crate) fn open_detached(
+        builder: &crate::SerialPortBuilder,
+        shared: bool,
+        restore: bool,
+    ) -> crate::Result<Detached> {
+        // Read back once, the checks and the open below all go by it
+        let path = settings::Settings::from_builder(builder).path;
+        if !shared {
+            // Any spelling of a claimed device would be refused by the exclusive lock
+            claim::Claim::check(&path)?;
+        }
+        let opened = if restore {
+            // The device is opened by path here, see `termios::open`
+            termios::open(&path, builder)
+                .map(|(port, original)| (port, Some(path.clone()), Some(original)))
+        } else {
+            mio_serial::SerialStream::open(builder).map(|port| (port, None, None))
+        };
+        let (port, name, original) = opened.map_err(|e| busy::explain(&path, e))?;
+        // `serialport` names the port by the path it was opened with, whatever its spelling
+        let claim = match (shared, name.clone().or_else(|| port.name())) {
+            (false, Some(path)) => Some(claim::Claim::acquire(&path)?),
+            _ => None,
+        };
+        Ok(Detached {
+            port,
+            name,
+            original,
+            claim,
+        })
+    }
+
+    /// Open the Windows port at `path`, with the sharing mode and lock of an `exclusive` one
+    /// or without
+    ///
+    /// `path` is any name of the device, see [`com::device_path`].  A `shared` port doesn't claim its device within the process.  With `restore`, the
+    /// settings the device had are saved to be restored on close.
+    #[cfg(windows)]
+    pub(crate) fn open_com(
+        builder: &crate::SerialPortBuilder,
+        path: &str,
+        exclusive: bool,
+        shared: bool,
+        restore: bool,
+    ) -> crate::Result<Self> {
+        let path = com::device_path(path);
+        let claim = match shared {
+            true => None,
+            false => Some(claim::Claim::acquire(&path)?),
+        };
+        let lock = if exclusive {
+            com::PortLock::acquire(&path)?
+        } else {
+            None
+        };
+        let (port, original) =
+            com::open(builder, &path, exclusive, restore).map_err(|e| busy::explain(&path, e))?;
+        let handle = port.as_raw_handle();
+        // SAFETY: the port is opened overlapped, and the com port below is never dropped once
+        // the handle is owned
+        let inner = unsafe { overlapped::OverlappedIo::from_raw_handle(handle)? };
+        // Keep the com port around to use for serialport related things
+        let com = mem::ManuallyDrop::new(port);
+        Ok(Self {
+            inner,
+            com,
+            path,
+            lock,
+            claim,
+            read_buf: ReadBuffer::default(),
+            drop_output: PendingOutput::Keep,
+            restore_settings: original.is_some(),
+            original_settings: original,
+        })
+    }
+
+    #[cfg(unix)]
+    fn from_mio(port: mio_serial::SerialStream) -> crate::Result<Self> {
+        Ok(Self {
+            inner: AsyncFd::new(port)?,
+            claim: None,
+            read_buf: ReadBuffer::default(),
+            drop_output: PendingOutput::Keep,
+            name: None,
+            original_settings: None,
+            restore_settings: false,
+            read_low_watermark: 0,
+        })
+    }
+
     /// Create a pair of pseudo serial terminals using the default reactor
     ///
     /// ## Returns
-    /// Two connected, unnamed `Serial` objects.
+    /// Two connected `Serial` objects: `(master, slave)`.  The master is unnamed, the slave's
+    /// `name()` is the path of the pseudo terminal.
     ///
     /// ## Errors
     /// Attempting any IO or parameter settings on the slave tty after the master
@@ -116,37 +474,662 @@ impl SerialStream {
     pub fn pair() -> crate::Result<(Self, Self)> {
         let (master, slave) = mio_serial::SerialStream::pair()?;
 
-        let master = SerialStream {
-            inner: AsyncFd::new(master)?,
-        };
-        let slave = SerialStream {
-            inner: AsyncFd::new(slave)?,
-        };
-        Ok((master, slave))
+        Ok((Self::from_mio(master)?, Self::from_mio(slave)?))
+    }
+
+    /// Create a pseudo serial terminal, returning the master and the path of the slave
+    ///
+    /// The slave is not kept open so the path can be handed to another process (a terminal
+    /// program, a device simulator, ...) which opens it itself.  Until the slave is opened,
+    /// reads on the master return end of file on Linux.
+    ///
+    /// ## Examples
+    ///
+    /// ```no_run
+    /// use tokio_serial::SerialStream;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (master, path) = SerialStream::pair_named().unwrap();
+    ///     let simulator = std::process::Command::new("picocom")
+    ///         .arg(&path)
+    ///         .spawn()
+    ///         .unwrap();
+    /// }
+    /// ```
+    #[cfg(unix)]
+    pub fn pair_named() -> crate::Result<(Self, std::path::PathBuf)> {
+        let (master, slave) = Self::pair()?;
+        let path = slave.name().ok_or_else(|| {
+            crate::Error::new(
+                crate::ErrorKind::Unknown,
+                "pseudo terminal slave has no name",
+            )
+        })?;
+        Ok((master, path.into()))
+    }
+
+    /// Wrap a terminal opened elsewhere, applying the settings of `builder`
+    ///
+    /// For descriptors inherited from a parent process (systemd socket activation, a sandbox
+    /// that only lets the process receive descriptors, ...): `open` is skipped and only the
+    /// line settings are applied through [`config::configure`], the path of the builder is
+    /// ignored.  The port takes an exclusive lock on the descriptor like an opened one.
+    ///
+    /// ## Examples
+    ///
+    /// ```no_run
+    /// use std::os::unix::io::{FromRawFd, OwnedFd};
+    /// use tokio_serial::SerialStream;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> tokio_serial::Result<()> {
+    ///     // SAFETY: systemd passes the first socket as descriptor 3
+    ///     let fd = unsafe { OwnedFd::from_raw_fd(3) };
+    ///     let port = SerialStream::from_owned_fd(fd, &tokio_serial::new("", 115200))?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(unix)]
+    pub fn from_owned_fd(
+        fd: std::os::unix::io::OwnedFd,
+        builder: &crate::SerialPortBuilder,
+    ) -> crate::Result<Self> {
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+
+        config::configure(&fd, builder)?;
+        // SAFETY: the descriptor is owned and handed over
+        let port = unsafe { serialport::TTYPort::from_raw_fd(fd.into_raw_fd()) };
+        Self::try_from(port)
+    }
+
+    /// Wrap the terminal `fd` opened elsewhere, see [`from_owned_fd`](Self::from_owned_fd)
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open descriptor owned by nothing else, the port closes it.
+    #[cfg(unix)]
+    pub unsafe fn from_raw_fd_configured(
+        fd: std::os::unix::io::RawFd,
+        builder: &crate::SerialPortBuilder,
+    ) -> crate::Result<Self> {
+        use std::os::unix::io::{FromRawFd, OwnedFd};
+
+        Self::from_owned_fd(OwnedFd::from_raw_fd(fd), builder)
+    }
+
+    /// Create a pair of connected serial ports using the default reactor
+    ///
+    /// Windows has no pseudo terminals, so this opens the first free pair of the
+    /// [com0com](https://com0com.sourceforge.net/) null-modem emulator, `\\.\CNCA<n>` and
+    /// `\\.\CNCB<n>`, configured for 9600 8N1.  com0com must be installed with at least one
+    /// pair using its default port names.
+    ///
+    /// ## Returns
+    /// Two connected `Serial` objects.
+    ///
+    /// ## Errors
+    /// `NoDevice` if no free com0com pair could be opened.
+    ///
+    /// ## Examples
+    ///
+    /// ```no_run
+    /// use tokio_serial::SerialStream;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (a, b) = SerialStream::pair().unwrap();
+    /// }
+    /// ```
+    #[cfg(windows)]
+    pub fn pair() -> crate::Result<(Self, Self)> {
+        // com0com's setup only creates a handful of pairs, don't probe forever
+        const MAX_PAIRS: u32 = 32;
+
+        // The device paths are known, there is nothing to read back from the builder
+        let open = |path: String| {
+            Self::open_com(&crate::new(path.as_str(), 9600), &path, true, false, false)
+        };
+        for n in 0..MAX_PAIRS {
+            let a = match open(format!(r"\\.\CNCA{}", n)) {
+                Ok(a) => a,
+                Err(e) => {
+                    log::trace!("com0com pair {} unavailable: {}", n, e);
+                    continue;
+                }
+            };
+            match open(format!(r"\\.\CNCB{}", n)) {
+                Ok(b) => return Ok((a, b)),
+                Err(e) => log::trace!("com0com pair {} unavailable: {}", n, e),
+            }
+        }
+        Err(crate::Error::new(
+            crate::ErrorKind::NoDevice,
+            "no free com0com port pair (CNCA<n>/CNCB<n>) found",
+        ))
+    }
+
+    /// Open a remote serial port exported by an RFC 2217 server (e.g. `ser2net`)
+    ///
+    /// The remote port is configured using the settings from `builder`, the builder path is
+    /// ignored.  The returned [`rfc2217::Client`] implements the same `AsyncRead`, `AsyncWrite`
+    /// and [`SerialPort`] traits as `SerialStream`.
+    ///
+    /// ## Examples
+    ///
+    /// ```no_run
+    /// use tokio_serial::SerialStream;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let builder = tokio_serial::new("", 9600);
+    ///     let port = SerialStream::open_rfc2217("localhost:2217", &builder).await.unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "rfc2217")]
+    pub async fn open_rfc2217<A: tokio::net::ToSocketAddrs>(
+        addr: A,
+        builder: &crate::SerialPortBuilder,
+    ) -> crate::Result<rfc2217::Client> {
+        rfc2217::Client::connect(addr, builder).await
+    }
+
+    /// Set the capacity of the internal read buffer
+    ///
+    /// With a non-zero capacity, reads smaller than the capacity are served from a buffer
+    /// that is refilled with a single read of up to `capacity` bytes, so a codec decoding a
+    /// few bytes at a time doesn't make a system call for each of them.  Reads at least as
+    /// large as the capacity bypass the buffer.  The buffered bytes are counted by
+    /// `bytes_to_read` and dropped by `clear(ClearBuffer::Input)` like the driver's own.
+    ///
+    /// The default capacity is zero: every read goes to the driver.  Bytes already buffered
+    /// when the capacity is changed are kept until read.
+    pub fn set_read_buffer_capacity(&mut self, capacity: usize) {
+        self.read_buf.set_capacity(capacity);
+    }
+
+    /// Returns the capacity of the internal read buffer
+    pub fn read_buffer_capacity(&self) -> usize {
+        self.read_buf.capacity()
+    }
+
+    /// Set how dropping the port handles output that hasn't been transmitted yet
+    ///
+    /// The default, [`PendingOutput::Keep`], leaves it to the driver, or what was chosen with
+    /// [`SerialPortBuilderExt::drop_output`].  Note that [`PendingOutput::Drain`] blocks the
+    /// dropping thread until the output is sent; prefer [`close`](Self::close) from async
+    /// code.
+    pub fn set_drop_output(&mut self, output: PendingOutput) {
+        self.drop_output = output;
+    }
+
+    /// Close the port
+    ///
+    /// Unlike dropping the port, this reports errors.  Pending output is handled according
+    /// to `output`, unread input is discarded, the original settings are restored if
+    /// [requested](Self::set_restore_settings_on_close), exclusive access is released and
+    /// the port is deregistered from the reactor.  The port is closed even if one of these steps fails,
+    /// the first error is returned.
+    ///
+    /// ## Examples
+    ///
+    /// ```no_run
+    /// use tokio::io::AsyncWriteExt;
+    /// use tokio_serial::{PendingOutput, SerialPortBuilderExt};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> tokio_serial::Result<()> {
+    ///     let mut port = tokio_serial::new("/dev/ttyUSB0", 9600).open_native_async()?;
+    ///     port.write_all(b"bye").await?;
+    ///     port.close(PendingOutput::Drain).await
+    /// }
+    /// ```
+    pub async fn close(mut self, output: PendingOutput) -> crate::Result<()> {
+        let mut result = match output {
+            PendingOutput::Keep => Ok(()),
+            PendingOutput::Drain => {
+                futures::future::poll_fn(|cx| Pin::new(&mut self).poll_flush(cx))
+                    .await
+                    .map_err(crate::Error::from)
+            }
+            PendingOutput::Discard => self.clear(crate::ClearBuffer::Output),
+        };
+        result = result.and(self.clear(crate::ClearBuffer::Input));
+        if let Some(original) = self.settings_to_restore() {
+            #[cfg(unix)]
+            let restored = original.restore(self.as_raw_fd());
+            #[cfg(windows)]
+            let restored = original.restore(self.as_raw_handle());
+            result = result.and(restored.map_err(Into::into));
+        }
+        #[cfg(unix)]
+        {
+            if self.exclusive() {
+                result = result.and(self.set_exclusive(false));
+            }
+        }
+        // Everything was handled here, dropping only deregisters and closes the port
+        self.drop_output = PendingOutput::Keep;
+        drop(self);
+        result
+    }
+
+    /// Whether to restore the settings the device had before it was opened when the port is
+    /// closed
+    ///
+    /// The settings are only saved by ports opened with
+    /// [`SerialPortBuilderExt::restore_settings_on_close`], which restore them by default;
+    /// this turns restoring off, or back on.  Other ports have nothing to restore and are
+    /// left alone.
+    pub fn set_restore_settings_on_close(&mut self, restore: bool) {
+        self.restore_settings = restore;
+    }
+
+    /// Only wake readers once at least `bytes` are pending
+    ///
+    /// A protocol with fixed size frames at a high baud rate otherwise gets a wakeup for
+    /// every few bytes the driver hands over.  With a low watermark, [`readable`](Self::readable)
+    /// and reads wait until the driver holds `bytes`, or the line hung up.  `VMIN` is set so
+    /// the driver itself only reports readiness past the watermark where it can (Linux, up
+    /// to 255 bytes); the pending bytes are checked again after every readiness event
+    /// otherwise.  Bytes already in the [read buffer](Self::set_read_buffer_capacity) are
+    /// returned at once.
+    ///
+    /// A frame shorter than the watermark, like an error reply, isn't delivered until more
+    /// bytes arrive: pair the watermark with a timeout.  0 or 1 wakes readers for every
+    /// byte, which is the default.
+    #[cfg(unix)]
+    pub fn set_read_low_watermark(&mut self, bytes: usize) -> crate::Result<()> {
+        let vmin = bytes.clamp(1, libc::cc_t::MAX as usize) as libc::cc_t;
+        config::modify_termios(self, |termios| termios.c_cc[libc::VMIN] = vmin)?;
+        self.read_low_watermark = bytes;
+        Ok(())
+    }
+
+    /// Returns the low watermark of reads
+    #[cfg(unix)]
+    pub fn read_low_watermark(&self) -> usize {
+        self.read_low_watermark
+    }
+
+    /// Run `f` with the descriptor of the port, for ioctls the crate doesn't cover
+    ///
+    /// The port is borrowed mutably for the duration, so no read or write of it is in
+    /// progress while `f` talks to the driver.  Afterwards the state the crate keeps about
+    /// the terminal settings is brought up to date: a `VMIN` changed by `f` becomes the
+    /// [read low watermark](Self::set_read_low_watermark).
+    ///
+    /// ```no_run
+    /// # fn run(mut port: tokio_serial::SerialStream) -> std::io::Result<()> {
+    /// use std::os::unix::io::AsRawFd;
+    ///
+    /// // Bytes still waiting in the output queue of the driver
+    /// let mut queued: libc::c_int = 0;
+    /// let result = port.with_raw_fd(|fd| unsafe {
+    ///     libc::ioctl(fd.as_raw_fd(), libc::TIOCOUTQ, &mut queued)
+    /// });
+    /// if result == -1 {
+    ///     return Err(std::io::Error::last_os_error());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub fn with_raw_fd<R>(&mut self, f: impl FnOnce(std::os::unix::io::BorrowedFd<'_>) -> R) -> R {
+        // SAFETY: the descriptor stays open for as long as `self` is borrowed
+        let fd = unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.inner.as_raw_fd()) };
+        let result = f(fd);
+        match config::termios(self) {
+            Ok(termios) => {
+                // 0 and 1 both wake readers for every byte
+                let vmin = usize::from(termios.c_cc[libc::VMIN]).max(1);
+                if vmin != self.read_low_watermark.clamp(1, libc::cc_t::MAX as usize) {
+                    self.read_low_watermark = vmin;
+                }
+            }
+            Err(e) => log::debug!("unable to read the terminal settings back: {}", e),
+        }
+        result
+    }
+
+    /// Run `f` with the handle of the port, for `DeviceIoControl` codes the crate doesn't
+    /// cover
+    ///
+    /// The port is borrowed mutably for the duration, so no read or write of it is started
+    /// while `f` talks to the driver.  The handle was opened for overlapped I/O: calls on it
+    /// need an `OVERLAPPED` structure and must wait for their completion before returning.
+    #[cfg(windows)]
+    pub fn with_raw_handle<R>(
+        &mut self,
+        f: impl FnOnce(std::os::windows::io::BorrowedHandle<'_>) -> R,
+    ) -> R {
+        // SAFETY: the handle stays open for as long as `self` is borrowed
+        let handle =
+            unsafe { std::os::windows::io::BorrowedHandle::borrow_raw(self.inner.as_raw_handle()) };
+        f(handle)
+    }
+
+    /// Take the saved settings if they are to be restored, so they are restored only once
+    fn settings_to_restore(&mut self) -> Option<OriginalSettings> {
+        if self.restore_settings {
+            self.original_settings.take()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the device path the port was opened with
+    ///
+    /// The path given to the builder is normalized: `COM10`, `COM10:` and `\\.\COM10` all
+    /// open `\\.\COM10`, device interface paths like `\\?\USB#VID_0403&PID_6001#...` are
+    /// used as they are.
+    #[cfg(windows)]
+    pub fn device_path(&self) -> &str {
+        &self.path
+    }
+
+    /// Sets how the driver controls the RTS line
+    ///
+    /// [`RtsControl::Toggle`] lets the driver switch the direction of an RS-485 adapter
+    /// around every transmission.  Except with [`RtsControl::Disable`] and
+    /// [`RtsControl::Enable`], `write_request_to_send` fails since the line belongs to the
+    /// driver.  Changing the flow control afterwards resets the mode.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the driver rejects the mode.
+    #[cfg(windows)]
+    pub fn set_rts_control(&mut self, control: RtsControl) -> crate::Result<()> {
+        Ok(com::set_rts_control(self.inner.as_raw_handle(), control)?)
+    }
+
+    /// Sets how the driver controls the DTR line
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the driver rejects the mode.
+    #[cfg(windows)]
+    pub fn set_dtr_control(&mut self, control: DtrControl) -> crate::Result<()> {
+        Ok(com::set_dtr_control(self.inner.as_raw_handle(), control)?)
+    }
+
+    /// Sets the recommended sizes of the driver's input and output queues, in bytes
+    ///
+    /// Drivers are free to round the sizes or to ignore them.  Ports receiving bursts faster
+    /// than the task reads them lose fewer bytes with a larger input queue.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the driver rejects the sizes.
+    #[cfg(windows)]
+    pub fn set_driver_buffer_sizes(&mut self, input: u32, output: u32) -> crate::Result<()> {
+        Ok(com::set_buffer_sizes(
+            self.inner.as_raw_handle(),
+            input,
+            output,
+        )?)
+    }
+
+    /// Purge the port, recovering it from a stuck transfer or an error without reopening it
+    ///
+    /// Unlike [`clear`](SerialPort::clear), which always aborts the transfers in flight along
+    /// with the buffers, each part of the purge is chosen separately.  Clearing the input also
+    /// drops the bytes buffered by the stream.  An aborted read is reissued by the next read
+    /// without losing data, an aborted write silently drops its unsent bytes.
+    ///
+    /// ```no_run
+    /// use tokio_serial::{Purge, SerialPortBuilderExt};
+    ///
+    /// # fn main() -> tokio_serial::Result<()> {
+    /// let port = tokio_serial::new("COM7", 115200).open_native_async()?;
+    /// port.purge(Purge {
+    ///     abort_write: true,
+    ///     clear_output: true,
+    ///     ..Purge::default()
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the driver fails the purge.
+    #[cfg(windows)]
+    pub fn purge(&self, purge: Purge) -> crate::Result<()> {
+        if purge.clear_input {
+            self.read_buf.discard();
+            self.inner.discard_buffered();
+        }
+        Ok(com::purge(self.inner.as_raw_handle(), purge)?)
+    }
+
+    /// Returns the `COMMTIMEOUTS` of the port
+    #[cfg(windows)]
+    pub fn comm_timeouts(&self) -> crate::Result<CommTimeouts> {
+        Ok(self.inner.timeouts()?)
+    }
+
+    /// Sets the `COMMTIMEOUTS` of the port
+    ///
+    /// Ports are opened with [`CommTimeouts::default`], which makes reads return as soon as
+    /// any data arrived.  The new timeouts apply to the reads and writes issued after this
+    /// returns.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the driver rejects the timeouts.
+    #[cfg(windows)]
+    pub fn set_comm_timeouts(&mut self, timeouts: CommTimeouts) -> crate::Result<()> {
+        Ok(self.inner.set_timeouts(timeouts)?)
+    }
+
+    /// Returns a stream of the communication `events` of the port
+    ///
+    /// The events are watched with `WaitCommEvent` on a duplicate of the port handle, so the
+    /// port can keep being read and written.  The events of a single wait are yielded in the
+    /// order of [`CommEvent::ALL`].  A port has a single event mask: a new stream replaces
+    /// it, which ends the previous one.
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use tokio_serial::{CommEvent, SerialPortBuilderExt};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> tokio_serial::Result<()> {
+    ///     let port = tokio_serial::new("COM1", 9600).open_native_async()?;
+    ///     let mut events = port.comm_events(&[CommEvent::Cts, CommEvent::Break])?;
+    ///     while let Some(event) = events.next().await {
+    ///         println!("{:?}", event?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the handle can't be duplicated or the driver rejects the mask.
+    #[cfg(windows)]
+    pub fn comm_events(&self, events: &[CommEvent]) -> crate::Result<CommEvents> {
+        Ok(CommEvents::new(self.inner.as_raw_handle(), events)?)
+    }
+
+    /// Returns a stream of everything happening on the port, see the [`events`] module
+    ///
+    /// The stream doesn't borrow the port, which keeps being read and written as usual.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the descriptor or handle of the port can't be duplicated.
+    #[cfg(feature = "events")]
+    pub fn events(&self) -> crate::Result<events::PortEvents> {
+        #[cfg(unix)]
+        return Ok(events::PortEvents::new(self.inner.as_raw_fd())?);
+        #[cfg(windows)]
+        return Ok(events::PortEvents::new(self.inner.as_raw_handle())?);
+    }
+
+    /// Change the line settings that differ from `config`, returning what changed
+    ///
+    /// The settings are compared with those read back from the driver, so the ones already
+    /// in place aren't written again.  When any differs, the output still pending is
+    /// transmitted first, with the settings it was written for.
+    ///
+    /// ```no_run
+    /// use tokio_serial::{SerialConfig, SerialPortBuilderExt};
+    ///
+    /// # async fn run() -> tokio_serial::Result<()> {
+    /// let mut port = tokio_serial::new("/dev/ttyUSB0", 9600).open_native_async()?;
+    /// let diff = port.apply(&SerialConfig::new(115_200)).await?;
+    /// println!("reconfigured: {}", diff);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * The errors of reading and changing the settings.  The changes made before one
+    ///   failed stay in place.
+    pub async fn apply(&mut self, config: &SerialConfig) -> crate::Result<ConfigDiff> {
+        let diff = SerialConfig::read(self)?.diff(config);
+        if !diff.is_empty() {
+            futures::future::poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await?;
+            diff.apply_to(self)?;
+        }
+        Ok(diff)
+    }
+
+    /// Set DTR and RTS together, leaving a line given `None` as it is
+    ///
+    /// On unix both lines change with a single `TIOCMSET`.  Windows has no such call: DTR is
+    /// set first and RTS follows within microseconds.  Reset sequences like those of
+    /// bootloaders that sample one line on the edge of the other rely on this order.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the driver has no modem lines, like a pseudo terminal.
+    pub fn set_modem_lines(&mut self, dtr: Option<bool>, rts: Option<bool>) -> crate::Result<()> {
+        Ok(config::write_modem_lines(self, dtr, rts)?)
+    }
+
+    /// [Set DTR and RTS](Self::set_modem_lines), then wait `settle` for the device to react
+    /// before returning
+    #[cfg(feature = "time")]
+    pub async fn set_modem_lines_settled(
+        &mut self,
+        dtr: Option<bool>,
+        rts: Option<bool>,
+        settle: Duration,
+    ) -> crate::Result<()> {
+        self.set_modem_lines(dtr, rts)?;
+        tokio::time::sleep(settle).await;
+        Ok(())
+    }
+
+    /// Returns the levels of CTS, DSR, RI and DCD read together
+    ///
+    /// A single `TIOCMGET` or `GetCommModemStatus` reads all four lines, so they are
+    /// consistent with each other, unlike the results of four `read_*` calls.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the driver has no modem lines, like a pseudo terminal.
+    pub fn modem_status(&self) -> crate::Result<config::ModemLines> {
+        Ok(config::read_modem_lines(self)?)
+    }
+
+    /// Returns a stream of the timestamped edges of a pulse-per-second `line`, see the
+    /// [`pps`] module
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the descriptor of the port can't be duplicated or the thread waiting for
+    ///   the edges can't be started.  A driver without modem lines, like a pseudo terminal,
+    ///   is reported by the stream.
+    #[cfg(target_os = "linux")]
+    pub fn pps(&self, line: pps::PpsLine) -> crate::Result<pps::Pps> {
+        Ok(pps::Pps::new(self.inner.as_raw_fd(), line)?)
+    }
+
+    /// Turn the port into a [`BlockingPort`](blocking::BlockingPort) for a synchronous
+    /// library, see the [`blocking`] module
+    ///
+    /// The port keeps its descriptor, settings and exclusivity, bytes already in the
+    /// [read buffer](Self::set_read_buffer_capacity) are read first.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` if the descriptor can't be duplicated or switched to blocking mode.
+    #[cfg(unix)]
+    pub fn into_blocking(mut self) -> crate::Result<blocking::BlockingPort> {
+        // SAFETY: the descriptor stays open for as long as `self` is borrowed
+        let fd = unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.inner.as_raw_fd()) }
+            .try_clone_to_owned()?;
+        let buffered = self.read_buf.take();
+        let original = self.settings_to_restore();
+        let exclusive = self.exclusive();
+        let claim = self.claim.take();
+        // The duplicate keeps the port open, pending output stays queued in the driver
+        self.drop_output = PendingOutput::Keep;
+        drop(self);
+        blocking::BlockingPort::new(fd, buffered, original, exclusive, claim)
+    }
+
+    /// Sets the exclusivity of the port
+    ///
+    /// If a port is exclusive, then trying to open the same device path again
+    /// will fail.
+    ///
+    /// See the man pages for the tiocexcl and tiocnxcl ioctl's for more details.  Ports are
+    /// opened exclusive on unix, which on macOS also keeps the USB CDC driver from handing
+    /// the device to a second `open` of its `cu.` or `tty.` node.
+    ///
+    /// On Windows the exclusivity is a named kernel object keyed by the device path, which
+    /// ports opened by this crate check.  Ports are exclusive by default there, see
+    /// [`SerialPortBuilderExt::exclusive`] for opening one shared.  The lock is best effort:
+    /// if the object can't be created at all, the port is left non-exclusive.
+    ///
+    /// ## Errors
+    ///
+    /// * `Io` for any error while setting exclusivity for the port.
+    /// * `NoDevice` on Windows if another port holds the lock.
+    pub fn set_exclusive(&mut self, exclusive: bool) -> crate::Result<()> {
+        #[cfg(unix)]
+        return self.inner.get_mut().set_exclusive(exclusive);
+
+        #[cfg(windows)]
+        {
+            if !exclusive {
+                self.lock = None;
+            } else if self.lock.is_none() {
+                self.lock = com::PortLock::acquire(&self.path)?;
+            }
+            Ok(())
+        }
     }
 
-    /// Sets the exclusivity of the port
-    ///
-    /// If a port is exclusive, then trying to open the same device path again
-    /// will fail.
+    /// Sets how long the driver holds received bytes before handing them to readers
     ///
-    /// See the man pages for the tiocexcl and tiocnxcl ioctl's for more details.
+    /// The macOS serial drivers batch received data for a few character times by default,
+    /// which adds that much latency to every reply of a request/response protocol.  A
+    /// latency of zero hands every byte over as soon as it arrives.
     ///
     /// ## Errors
     ///
-    /// * `Io` for any error while setting exclusivity for the port.
-    #[cfg(unix)]
-    pub fn set_exclusive(&mut self, exclusive: bool) -> crate::Result<()> {
-        self.inner.get_mut().set_exclusive(exclusive)
+    /// * `Io` if the driver doesn't support `IOSSDATALAT`.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn set_receive_latency(&mut self, latency: Duration) -> crate::Result<()> {
+        Ok(darwin::set_data_latency(self.as_raw_fd(), latency)?)
     }
 
     /// Returns the exclusivity of the port
     ///
     /// If a port is exclusive, then trying to open the same device path again
     /// will fail.
-    #[cfg(unix)]
     pub fn exclusive(&self) -> bool {
-        self.inner.get_ref().exclusive()
+        #[cfg(unix)]
+        return self.inner.get_ref().exclusive();
+        #[cfg(windows)]
+        return self.lock.is_some();
     }
 
     /// Borrow a reference to the underlying mio-serial::SerialStream object.
@@ -174,6 +1157,22 @@ impl SerialStream {
             self.com.deref_mut()
         }
     }
+    /// Attach the port name and `operation` to an I/O error
+    ///
+    /// `WouldBlock` is left alone, it is part of normal operation rather than a failure.
+    fn port_error(&self, operation: Operation, err: std::io::Error) -> std::io::Error {
+        if err.kind() == std::io::ErrorKind::WouldBlock {
+            return err;
+        }
+        // Windows reports a removed device with unrelated codes like `ERROR_ACCESS_DENIED`
+        #[cfg(windows)]
+        if is_hangup(&err) {
+            let err = PortError::new(self.name(), operation, err);
+            return std::io::Error::new(std::io::ErrorKind::NotConnected, err);
+        }
+        PortError::new(self.name(), operation, err).into()
+    }
+
     /// Try to read bytes on the serial port.  On success returns the number of bytes read.
     ///
     /// The function must be called with valid byte array `buf` of sufficient
@@ -182,14 +1181,24 @@ impl SerialStream {
     ///
     /// When there is no pending data, `Err(io::ErrorKind::WouldBlock)` is
     /// returned. This function is usually paired with `readable()`.
+    ///
+    /// A hangup is reported as end of file, see the [`AsyncRead`] implementation.
     pub fn try_read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if !self.read_buf.is_empty() {
+            return Ok(self.read_buf.copy_to(buf));
+        }
         #[cfg(unix)]
         {
-            self.inner.get_mut().read(buf)
+            match self.inner.get_mut().read(buf) {
+                Err(err) if is_hangup(&err) => Ok(0),
+                result => result.map_err(|e| self.port_error(Operation::Read, e)),
+            }
         }
         #[cfg(windows)]
         {
-            self.inner.try_read(buf)
+            self.inner
+                .try_read(buf)
+                .map_err(|e| self.port_error(Operation::Read, e))
         }
     }
 
@@ -201,8 +1210,152 @@ impl SerialStream {
     /// false-positive and attempting a `try_read()` will return with
     /// `io::ErrorKind::WouldBlock`.
     pub async fn readable(&self) -> IoResult<()> {
-        let _ = self.inner.readable().await?;
-        Ok(())
+        #[cfg(unix)]
+        loop {
+            let mut guard = self.inner.readable().await?;
+            if !below_watermark(guard.get_inner(), guard.ready(), self.read_low_watermark) {
+                return Ok(());
+            }
+            guard.clear_ready();
+        }
+        #[cfg(windows)]
+        return self.inner.readable().await;
+    }
+
+    /// Receive bytes without removing them from the queue, returning their number
+    ///
+    /// The bytes are kept in the internal read buffer and returned again by the next read or
+    /// peek, so a protocol can be recognized from its first bytes before the port is handed
+    /// to the matching codec.  Like [`TcpStream::peek`](tokio::net::TcpStream::peek), returns
+    /// as soon as any byte is available, the ones buffered by earlier peeks included: peeking
+    /// again returns the same bytes, along with those that arrived since.
+    ///
+    /// Returns 0 when the line hung up and nothing is left to read.
+    ///
+    /// ## Examples
+    ///
+    /// ```no_run
+    /// # async fn run(mut port: tokio_serial::SerialStream) -> std::io::Result<()> {
+    /// let mut start = [0u8; 2];
+    /// let n = port.peek(&mut start).await?;
+    /// if start[..n].starts_with(b"$") {
+    ///     // NMEA sentences
+    /// } else if start[..n] == [0xb5, 0x62] {
+    ///     // UBX frames
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn peek(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let mut buf = ReadBuf::new(buf);
+        futures::future::poll_fn(|cx| self.poll_peek(cx, &mut buf)).await
+    }
+
+    /// [Peek](Self::peek) once more than `seen` bytes are available, or `buf` is full
+    #[cfg(feature = "codec")]
+    pub(crate) async fn peek_beyond(&mut self, buf: &mut [u8], seen: usize) -> IoResult<usize> {
+        let mut buf = ReadBuf::new(buf);
+        futures::future::poll_fn(|cx| self.poll_peek_at_least(cx, &mut buf, seen + 1)).await
+    }
+
+    /// Attempts to receive bytes without removing them from the queue, see [`peek`](Self::peek)
+    ///
+    /// The bytes are put in `buf`, their number is returned.
+    pub fn poll_peek(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<usize>> {
+        self.poll_peek_at_least(cx, buf, 1)
+    }
+
+    /// Peek once `wanted` bytes are available, taking in what arrived without waiting for
+    /// more up to what `buf` holds
+    fn poll_peek_at_least(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+        wanted: usize,
+    ) -> Poll<IoResult<usize>> {
+        let wanted = wanted.min(buf.remaining());
+        while self.read_buf.len() < buf.remaining() {
+            let before = self.read_buf.len();
+            let inner = &mut self.inner;
+            match self
+                .read_buf
+                .poll_extend(buf.remaining(), |buf| poll_read_inner(inner, cx, buf))
+            {
+                Poll::Ready(result) => {
+                    result.map_err(|e| self.port_error(Operation::Read, e))?;
+                    // The line hung up
+                    if self.read_buf.len() == before {
+                        break;
+                    }
+                }
+                Poll::Pending if before >= wanted => break,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let available = self.read_buf.buffered();
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    /// Read bytes like [`AsyncReadExt::read`], along with the time they arrived
+    ///
+    /// The time is taken from the monotonic clock as soon as the driver handed the bytes
+    /// over, so the gaps between the chunks of a bus can be reconstructed.  Each chunk is
+    /// what a single read returned: bytes arriving while the previous chunk was processed
+    /// share the timestamp of the read that fetched them.  The read buffer is bypassed,
+    /// except for bytes already in it, from a [`peek`](Self::peek) for instance, which
+    /// carry the time of the read that buffered the last of them.
+    ///
+    /// ```no_run
+    /// # async fn run(mut port: tokio_serial::SerialStream) -> std::io::Result<()> {
+    /// let mut buf = [0u8; 256];
+    /// let (_, mut last) = port.read_timestamped(&mut buf).await?;
+    /// loop {
+    ///     let (n, at) = port.read_timestamped(&mut buf).await?;
+    ///     println!("{:?} after {:?}", &buf[..n], at - last);
+    ///     last = at;
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// [`AsyncReadExt::read`]: tokio::io::AsyncReadExt::read
+    pub async fn read_timestamped(&mut self, buf: &mut [u8]) -> IoResult<(usize, Instant)> {
+        let mut buf = ReadBuf::new(buf);
+        let at = futures::future::poll_fn(|cx| self.poll_read_timestamped(cx, &mut buf)).await?;
+        Ok((buf.filled().len(), at))
+    }
+
+    /// Attempts to read bytes along with the time they arrived, see
+    /// [`read_timestamped`](Self::read_timestamped)
+    pub fn poll_read_timestamped(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<Instant>> {
+        if !self.read_buf.is_empty() {
+            let available = self.read_buf.buffered();
+            let n = available.len().min(buf.remaining());
+            buf.put_slice(&available[..n]);
+            self.read_buf.consume(n);
+            let at = self.read_buf.filled_at().unwrap_or_else(Instant::now);
+            return Poll::Ready(Ok(at));
+        }
+
+        #[cfg(unix)]
+        ready!(poll_read_watermark(
+            &mut self.inner,
+            self.read_low_watermark,
+            cx
+        ))
+        .map_err(|e| self.port_error(Operation::Read, e))?;
+        ready!(poll_read_inner(&mut self.inner, cx, buf))
+            .map_err(|e| self.port_error(Operation::Read, e))?;
+        Poll::Ready(Ok(Instant::now()))
     }
 
     /// Try to write bytes on the serial port.  On success returns the number of bytes written.
@@ -211,13 +1364,10 @@ impl SerialStream {
     /// returned. This function is usually paired with `writable()`.
     pub fn try_write(&mut self, buf: &[u8]) -> IoResult<usize> {
         #[cfg(unix)]
-        {
-            self.inner.get_mut().write(buf)
-        }
+        let result = self.inner.get_mut().write(buf);
         #[cfg(windows)]
-        {
-            self.inner.try_write(buf)
-        }
+        let result = self.inner.try_write(buf);
+        result.map_err(|e| self.port_error(Operation::Write, e))
     }
 
     /// Wait for the port to become writable.
@@ -228,49 +1378,149 @@ impl SerialStream {
     /// false-positive and attempting a `try_write()` will return with
     /// `io::ErrorKind::WouldBlock`.
     pub async fn writable(&self) -> IoResult<()> {
-        let _ = self.inner.writable().await?;
-        Ok(())
+        #[cfg(unix)]
+        return self.inner.writable().await.map(|_| ());
+        #[cfg(windows)]
+        return self.inner.writable().await;
     }
 }
 
+/// Read from the driver, bypassing the read buffer
 #[cfg(unix)]
-impl AsyncRead for SerialStream {
-    /// Attempts to ready bytes on the serial port.
-    ///
-    /// Note that on multiple calls to a `poll_*` method in the read direction, only the
-    /// `Waker` from the `Context` passed to the most recent call will be scheduled to
-    /// receive a wakeup.
-    ///
-    /// # Return value
-    ///
-    /// The function returns:
-    ///
-    /// * `Poll::Pending` if the socket is not ready to read
-    /// * `Poll::Ready(Ok(()))` reads data `ReadBuf` if the socket is ready
-    /// * `Poll::Ready(Err(e))` if an error is encountered.
-    ///
-    /// # Errors
-    ///
-    /// This function may encounter any standard I/O error except `WouldBlock`.
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<IoResult<()>> {
-        loop {
-            let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+fn poll_read_inner(
+    inner: &mut AsyncFd<mio_serial::SerialStream>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+) -> Poll<IoResult<()>> {
+    if buf.remaining() == 0 {
+        return Poll::Ready(Ok(()));
+    }
 
-            match guard.try_io(|inner| inner.get_ref().read(buf.initialize_unfilled())) {
-                Ok(Ok(bytes_read)) => {
-                    buf.advance(bytes_read);
-                    return Poll::Ready(Ok(()));
-                }
-                Ok(Err(err)) => {
-                    return Poll::Ready(Err(err));
+    for _ in 0..SPURIOUS_RETRIES {
+        let mut guard = ready!(inner.poll_read_ready_mut(cx))?;
+
+        // SAFETY: `read_uninit` only writes to the unfilled part, never de-initializing it
+        let unfilled = unsafe { buf.unfilled_mut() };
+        let wanted = unfilled.len();
+        match guard.try_io(|inner| read_uninit(inner.get_ref(), unfilled)) {
+            Ok(Ok(bytes_read)) => {
+                // A short read usually drained the driver's buffer, so the next read would
+                // only fail with `WouldBlock`: wait for the next readiness event instead,
+                // unless `FIONREAD` shows bytes arrived meanwhile and can be read directly.
+                // After a full read more data is likely pending and readiness is kept.
+                // An empty read is a hangup, which stays readable.
+                if bytes_read > 0 && bytes_read < wanted && !has_queued(guard.get_inner()) {
+                    guard.clear_ready();
                 }
-                Err(_would_block) => continue,
+                // SAFETY: read(2) initialized the first `bytes_read` bytes
+                unsafe { buf.assume_init(bytes_read) };
+                buf.advance(bytes_read);
+                return Poll::Ready(Ok(()));
+            }
+            Ok(Err(err)) if is_hangup(&err) => {
+                log::debug!("serial port hung up: {}", err);
+                return Poll::Ready(Ok(()));
             }
+            Ok(Err(err)) => {
+                return Poll::Ready(Err(err));
+            }
+            Err(_would_block) => continue,
+        }
+    }
+    yield_now(cx)
+}
+
+/// Whether `FIONREAD` reports bytes waiting in the driver, `false` if it fails
+#[cfg(unix)]
+fn has_queued(port: &mio_serial::SerialStream) -> bool {
+    matches!(port.bytes_to_read(), Ok(n) if n > 0)
+}
+
+/// Wait for the driver to hold at least `watermark` bytes, see `set_read_low_watermark`
+#[cfg(unix)]
+fn poll_read_watermark(
+    inner: &mut AsyncFd<mio_serial::SerialStream>,
+    watermark: usize,
+    cx: &mut Context<'_>,
+) -> Poll<IoResult<()>> {
+    loop {
+        let mut guard = ready!(inner.poll_read_ready_mut(cx))?;
+        if !below_watermark(guard.get_inner(), guard.ready(), watermark) {
+            return Poll::Ready(Ok(()));
         }
+        guard.clear_ready();
+    }
+}
+
+/// Whether the port is readable but holds less than `watermark` bytes
+///
+/// A hangup or a failing `FIONREAD` lets the read go ahead and report it.
+#[cfg(unix)]
+fn below_watermark(
+    port: &mio_serial::SerialStream,
+    ready: tokio::io::Ready,
+    watermark: usize,
+) -> bool {
+    watermark > 1
+        && !ready.is_read_closed()
+        && matches!(port.bytes_to_read(), Ok(n) if (n as usize) < watermark)
+}
+
+/// Whether a read error means the other end of the line went away
+///
+/// Linux reports a closed pseudo terminal slave as `EIO` on the master, while the slave sees
+/// an empty read once the master is closed.  Unplugged USB adapters fail with `EIO`, `ENXIO`
+/// or `ENODEV` depending on the driver.
+#[cfg(unix)]
+pub(crate) fn is_hangup(err: &std::io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EIO) | Some(libc::ENXIO) | Some(libc::ENODEV)
+    )
+}
+
+/// Whether an I/O error means the device went away
+///
+/// Operations on a USB adapter pulled while the port is open fail with
+/// `ERROR_ACCESS_DENIED`, `ERROR_BAD_COMMAND` or `ERROR_DEVICE_REMOVED` depending on the
+/// driver, and the operations in flight at the time with `ERROR_OPERATION_ABORTED`.
+#[cfg(windows)]
+pub(crate) fn is_hangup(err: &std::io::Error) -> bool {
+    use windows_sys::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_BAD_COMMAND, ERROR_DEVICE_NOT_CONNECTED, ERROR_DEVICE_REMOVED,
+        ERROR_OPERATION_ABORTED,
+    };
+
+    matches!(
+        err.raw_os_error().map(|code| code as u32),
+        Some(ERROR_ACCESS_DENIED)
+            | Some(ERROR_BAD_COMMAND)
+            | Some(ERROR_DEVICE_NOT_CONNECTED)
+            | Some(ERROR_DEVICE_REMOVED)
+            | Some(ERROR_OPERATION_ABORTED)
+    )
+}
+
+/// Elsewhere errors carry no OS code to recognize a hangup by
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn is_hangup(_err: &std::io::Error) -> bool {
+    false
+}
+
+/// Read into a possibly uninitialized buffer
+///
+/// `Read::read` needs an initialized buffer, which would mean zeroing the unfilled part of
+/// every `ReadBuf` first.  `tokio_util::io::poll_read_buf`, and thus `Framed`, hands out the
+/// uninitialized spare capacity of its `BytesMut`, so data is read straight into the frame
+/// buffer instead.
+#[cfg(unix)]
+fn read_uninit(port: &mio_serial::SerialStream, buf: &mut [MaybeUninit<u8>]) -> IoResult<usize> {
+    // SAFETY: the pointer and length describe writable memory owned by `buf`
+    let n = unsafe { libc::read(port.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+    if n < 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(n as usize)
     }
 }
 
@@ -282,6 +1532,9 @@ impl AsyncWrite for SerialStream {
     /// only the `Waker` from the `Context` passed to the most recent call will
     /// be scheduled to receive a wakeup.
     ///
+    /// As with reads, every readiness check consumes a unit of the task's tokio cooperative
+    /// budget.
+    ///
     /// # Return value
     ///
     /// The function returns:
@@ -294,24 +1547,53 @@ impl AsyncWrite for SerialStream {
     ///
     /// This function may encounter any standard I/O error except `WouldBlock`.
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
-        loop {
+        for _ in 0..SPURIOUS_RETRIES {
             let mut guard = ready!(self.inner.poll_write_ready(cx))?;
 
             match guard.try_io(|inner| inner.get_ref().write(buf)) {
-                Ok(result) => return Poll::Ready(result),
+                Ok(result) => {
+                    return Poll::Ready(result.map_err(|e| self.port_error(Operation::Write, e)))
+                }
+                Err(_would_block) => continue,
+            }
+        }
+        yield_now(cx)
+    }
+
+    /// Attempts to send the data of several buffers with a single `writev`
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<IoResult<usize>> {
+        for _ in 0..SPURIOUS_RETRIES {
+            let mut guard = ready!(self.inner.poll_write_ready(cx))?;
+
+            match guard.try_io(|inner| writev(inner.as_raw_fd(), bufs)) {
+                Ok(result) => {
+                    return Poll::Ready(result.map_err(|e| self.port_error(Operation::Write, e)))
+                }
                 Err(_would_block) => continue,
             }
         }
+        yield_now(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
-        loop {
+        for _ in 0..SPURIOUS_RETRIES {
             let mut guard = ready!(self.inner.poll_write_ready(cx))?;
             match guard.try_io(|inner| inner.get_ref().flush()) {
-                Ok(_) => return Poll::Ready(Ok(())),
+                Ok(result) => {
+                    return Poll::Ready(result.map_err(|e| self.port_error(Operation::Flush, e)))
+                }
                 Err(_would_block) => continue,
             }
         }
+        yield_now(cx)
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
@@ -320,40 +1602,152 @@ impl AsyncWrite for SerialStream {
     }
 }
 
+/// Buffers handed to a single `writev`, well below any `IOV_MAX`
+#[cfg(unix)]
+const MAX_IOVECS: usize = 64;
+
+/// Write the buffers of `bufs` with one system call
+#[cfg(unix)]
+fn writev(fd: std::os::unix::io::RawFd, bufs: &[std::io::IoSlice<'_>]) -> IoResult<usize> {
+    let count = bufs.len().min(MAX_IOVECS) as libc::c_int;
+    // SAFETY: `IoSlice` is ABI compatible with `iovec`, `count` buffers are valid
+    let n = unsafe { libc::writev(fd, bufs.as_ptr() as *const libc::iovec, count) };
+    if n < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+/// Reschedule the task and return `Poll::Pending`, letting other tasks run first
+#[cfg(unix)]
+fn yield_now<T>(cx: &mut Context<'_>) -> Poll<T> {
+    cx.waker().wake_by_ref();
+    Poll::Pending
+}
+
+/// Read from the driver, bypassing the read buffer
 #[cfg(windows)]
+fn poll_read_inner(
+    inner: &mut overlapped::OverlappedIo,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+) -> Poll<IoResult<()>> {
+    let n = ready!(inner.poll_read(cx, buf.initialize_unfilled()))?;
+    buf.advance(n);
+    Poll::Ready(Ok(()))
+}
+
+#[cfg(any(unix, windows))]
 impl AsyncRead for SerialStream {
+    /// Attempts to ready bytes on the serial port.
+    ///
+    /// Note that on multiple calls to a `poll_*` method in the read direction, only the
+    /// `Waker` from the `Context` passed to the most recent call will be scheduled to
+    /// receive a wakeup.
+    ///
+    /// Every readiness check consumes a unit of the task's tokio cooperative budget, so a
+    /// task reading a port that always has data ready is made to yield back to the runtime
+    /// like one reading a tokio socket.
+    ///
+    /// # Return value
+    ///
+    /// The function returns:
+    ///
+    /// * `Poll::Pending` if the socket is not ready to read
+    /// * `Poll::Ready(Ok(()))` reads data `ReadBuf` if the socket is ready
+    /// * `Poll::Ready(Err(e))` if an error is encountered.
+    ///
+    /// # End of file
+    ///
+    /// A serial port has no graceful close, so end of file means the line hung up: the
+    /// other end of a pseudo terminal was closed or the device was unplugged.  On unix every
+    /// read after a hangup returns end of file, whether the driver reported it with an empty
+    /// read or with `EIO`, `ENXIO` or `ENODEV`.  Writes keep failing with the driver's error.
+    ///
+    /// # Errors
+    ///
+    /// This function may encounter any standard I/O error except `WouldBlock`.  The error
+    /// wraps a [`PortError`] naming the port and the operation.
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<IoResult<()>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_read(cx, buf)
+        let this = self.get_mut();
+        if this.read_buf.bypass(buf.remaining()) {
+            #[cfg(unix)]
+            ready!(poll_read_watermark(
+                &mut this.inner,
+                this.read_low_watermark,
+                cx
+            ))?;
+            return poll_read_inner(&mut this.inner, cx, buf)
+                .map_err(|e| this.port_error(Operation::Read, e));
+        }
+
+        let available = ready!(Pin::new(&mut *this).poll_fill_buf(cx))?;
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        this.read_buf.consume(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl AsyncBufRead for SerialStream {
+    /// Returns the contents of the internal read buffer, filling it if empty
+    ///
+    /// A port without a read buffer capacity uses a 1024 byte buffer.
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<&[u8]>> {
+        let this = self.get_mut();
+        if this.read_buf.is_empty() {
+            #[cfg(unix)]
+            ready!(poll_read_watermark(
+                &mut this.inner,
+                this.read_low_watermark,
+                cx
+            ))?;
+            let inner = &mut this.inner;
+            ready!(this
+                .read_buf
+                .poll_fill(|buf| poll_read_inner(inner, cx, buf)))
+            .map_err(|e| this.port_error(Operation::Read, e))?;
+        }
+        Poll::Ready(Ok(this.read_buf.buffered()))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().read_buf.consume(amt);
     }
 }
 
 #[cfg(windows)]
 impl AsyncWrite for SerialStream {
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_write(cx, buf)
+        self.inner
+            .poll_write(cx, buf)
+            .map_err(|e| self.port_error(Operation::Write, e))
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_flush(cx)
+        self.inner
+            .poll_flush(cx)
+            .map_err(|e| self.port_error(Operation::Flush, e))
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
-        let mut self_ = self;
-        Pin::new(&mut self_.inner).poll_shutdown(cx)
+        self.poll_flush(cx)
     }
 }
 
+#[cfg(any(unix, windows))]
 impl crate::SerialPort for SerialStream {
     #[inline(always)]
     fn name(&self) -> Option<String> {
-        self.borrow().name()
+        #[cfg(unix)]
+        return self.name.clone().or_else(|| self.borrow().name());
+        #[cfg(windows)]
+        return Some(self.path.clone());
     }
 
     #[inline(always)]
@@ -386,6 +1780,10 @@ impl crate::SerialPort for SerialStream {
         Duration::from_secs(0)
     }
 
+    /// Sets the baud rate
+    ///
+    /// On macOS rates without a `Bxxx` constant are set with the `IOSSIOSPEED` ioctl, and a
+    /// driver refusing the rate is reported as an error instead of keeping the old rate.
     #[inline(always)]
     fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
         self.borrow_mut().set_baud_rate(baud_rate)
@@ -448,7 +1846,11 @@ impl crate::SerialPort for SerialStream {
 
     #[inline(always)]
     fn bytes_to_read(&self) -> crate::Result<u32> {
-        self.borrow().bytes_to_read()
+        #[cfg(unix)]
+        let buffered = self.read_buf.len();
+        #[cfg(windows)]
+        let buffered = self.read_buf.len() + self.inner.buffered();
+        Ok(self.borrow().bytes_to_read()? + buffered as u32)
     }
 
     #[inline(always)]
@@ -458,6 +1860,11 @@ impl crate::SerialPort for SerialStream {
 
     #[inline(always)]
     fn clear(&self, buffer_to_clear: crate::ClearBuffer) -> crate::Result<()> {
+        if buffer_to_clear != crate::ClearBuffer::Output {
+            self.read_buf.discard();
+            #[cfg(windows)]
+            self.inner.discard_buffered();
+        }
         self.borrow().clear(buffer_to_clear)
     }
 
@@ -484,12 +1891,38 @@ impl crate::SerialPort for SerialStream {
     }
 }
 
+#[cfg(any(unix, windows))]
+impl Drop for SerialStream {
+    fn drop(&mut self) {
+        let result = match self.drop_output {
+            PendingOutput::Keep => Ok(()),
+            PendingOutput::Drain => self.borrow_mut().flush().map_err(crate::Error::from),
+            PendingOutput::Discard => self.borrow().clear(crate::ClearBuffer::Output),
+        };
+        if let Err(e) = result {
+            log::debug!("failed to handle pending output on drop: {}", e);
+        }
+
+        if let Some(original) = self.settings_to_restore() {
+            #[cfg(unix)]
+            let restored = original.restore(self.as_raw_fd());
+            #[cfg(windows)]
+            let restored = original.restore(self.as_raw_handle());
+            if let Err(e) = restored {
+                log::debug!("failed to restore settings on drop: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(any(unix, windows))]
 impl Read for SerialStream {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
         self.try_read(buf)
     }
 }
 
+#[cfg(any(unix, windows))]
 impl Write for SerialStream {
     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
         self.try_write(buf)
@@ -505,10 +1938,7 @@ impl TryFrom<serialport::TTYPort> for SerialStream {
     type Error = Error;
 
     fn try_from(value: serialport::TTYPort) -> std::result::Result<Self, Self::Error> {
-        let port = mio_serial::SerialStream::try_from(value)?;
-        Ok(Self {
-            inner: AsyncFd::new(port)?,
-        })
+        Self::from_mio(mio_serial::SerialStream::try_from(value)?)
     }
 }
 
@@ -534,21 +1964,162 @@ mod io {
     }
 }
 
+/// An asynchronous serial port
+///
+/// Object-safe combination of `AsyncRead`, `AsyncWrite` and [`SerialPort`] implemented for
+/// every type providing all three, including [`SerialStream`] and the network backends.
+/// Drivers written against `dyn AsyncSerialPort` (or a generic `P: AsyncSerialPort + ?Sized`)
+/// work unchanged on any of them.
+///
+/// [`SerialPort`] requires the blocking `Read` and `Write` traits, whose methods take
+/// precedence on a `dyn AsyncSerialPort`; call the async extension methods in their
+/// qualified form (or through a `Box`) instead.
+///
+/// ```no_run
+/// use tokio::io::AsyncWriteExt;
+/// use tokio_serial::{AsyncSerialPort, SerialPortBuilderExt};
+///
+/// async fn reset(port: &mut dyn AsyncSerialPort) -> tokio_serial::Result<()> {
+///     port.write_data_terminal_ready(false)?;
+///     AsyncWriteExt::write_all(port, b"ATZ\r").await?;
+///     Ok(())
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> tokio_serial::Result<()> {
+///     let mut port = tokio_serial::new("/dev/ttyUSB0", 9600).open_async()?;
+///     reset(&mut *port).await
+/// }
+/// ```
+pub trait AsyncSerialPort: AsyncRead + AsyncWrite + SerialPort + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + SerialPort + Unpin> AsyncSerialPort for T {}
+
+#[cfg(any(unix, windows))]
 /// An extension trait for serialport::SerialPortBuilder
 ///
-/// This trait adds one method to SerialPortBuilder:
+/// This trait adds two methods to SerialPortBuilder:
 ///
 /// - open_native_async
+/// - open_async
 ///
-/// This method mirrors the `open_native` method of SerialPortBuilder
+/// These methods mirror the `open_native` and `open` methods of SerialPortBuilder.  The
+/// remaining methods set options `SerialPortBuilder` has no room for, returning an
+/// [`AsyncSerialPortBuilder`] that implements this trait too.
 pub trait SerialPortBuilderExt {
     /// Open a platform-specific interface to the port with the specified settings
     fn open_native_async(self) -> Result<SerialStream>;
+
+    /// Open a cross-platform interface to the port with the specified settings
+    fn open_async(self) -> Result<Box<dyn AsyncSerialPort>>;
+
+    /// Set how the driver controls the RTS line, see [`SerialStream::set_rts_control`]
+    #[cfg(windows)]
+    fn rts_control(self, control: RtsControl) -> AsyncSerialPortBuilder;
+
+    /// Set how the driver controls the DTR line, see [`SerialStream::set_dtr_control`]
+    #[cfg(windows)]
+    fn dtr_control(self, control: DtrControl) -> AsyncSerialPortBuilder;
+
+    /// Open the port exclusively, the default, or shared with other handles
+    ///
+    /// An exclusive port is opened without sharing and takes the lock described in
+    /// [`SerialStream::set_exclusive`].  A shared port is opened with read and write sharing
+    /// and without the lock, which only some virtual port drivers accept.
+    #[cfg(windows)]
+    fn exclusive(self, exclusive: bool) -> AsyncSerialPortBuilder;
+
+    /// Set platform specific options with `f`, applied when the port is opened
+    ///
+    /// ```no_run
+    /// use tokio_serial::SerialPortBuilderExt;
+    ///
+    /// # fn main() -> tokio_serial::Result<()> {
+    /// let port = tokio_serial::new("/dev/ttyUSB0", 9600)
+    ///     .with_platform_options(|opts| {
+    ///         #[cfg(unix)]
+    ///         opts.vmin(8).modify_termios(|t| t.c_iflag |= libc::IGNPAR);
+    ///     })
+    ///     .open_native_async()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn with_platform_options(self, f: impl FnOnce(&mut PlatformOptions)) -> AsyncSerialPortBuilder;
+
+    /// Discard the bytes the driver buffered in `buffer` before the port was opened
+    ///
+    /// Without it, bytes received while no program had the port open, or output a previous
+    /// program left behind, come before the first frames.  Bytes a USB adapter still holds
+    /// arrive after the clear.
+    fn clear_buffers_on_open(self, buffer: ClearBuffer) -> AsyncSerialPortBuilder;
+
+    /// Open the port even if another port of this process has its device open
+    ///
+    /// Ports opened by path claim their device within the process, a second open fails with
+    /// [`ErrorClass::AlreadyOpen`], see [`is_open_in_process`].  A port opened with this
+    /// option neither checks nor takes the claim.  The OS may still refuse the second open,
+    /// see [`SerialStream::set_exclusive`].
+    fn allow_shared(self) -> AsyncSerialPortBuilder;
+
+    /// Handle output not transmitted yet as `output` when the port is dropped
+    ///
+    /// See [`SerialStream::set_drop_output`], which changes it once the port is open.
+    fn drop_output(self, output: PendingOutput) -> AsyncSerialPortBuilder;
+
+    /// Restore the settings the device had before it was opened when the port is closed
+    ///
+    /// The settings are saved from the port as it is opened, before those of the builder are
+    /// applied, and put back by [`SerialStream::close`] or when the port is dropped, so a tool
+    /// temporarily reconfiguring a console UART leaves it as it found it.  Needs the device
+    /// to be opened by a path the crate can tell, see
+    /// [`SerialStream::set_restore_settings_on_close`].
+    fn restore_settings_on_close(self, restore: bool) -> AsyncSerialPortBuilder;
 }
 
+#[cfg(any(unix, windows))]
 impl SerialPortBuilderExt for SerialPortBuilder {
     /// Open a platform-specific interface to the port with the specified settings
     fn open_native_async(self) -> Result<SerialStream> {
         SerialStream::open(&self)
     }
+
+    /// Open a cross-platform interface to the port with the specified settings
+    fn open_async(self) -> Result<Box<dyn AsyncSerialPort>> {
+        Ok(Box::new(SerialStream::open(&self)?))
+    }
+
+    #[cfg(windows)]
+    fn rts_control(self, control: RtsControl) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).rts_control(control)
+    }
+
+    #[cfg(windows)]
+    fn dtr_control(self, control: DtrControl) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).dtr_control(control)
+    }
+
+    #[cfg(windows)]
+    fn exclusive(self, exclusive: bool) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).exclusive(exclusive)
+    }
+
+    fn with_platform_options(self, f: impl FnOnce(&mut PlatformOptions)) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).with_platform_options(f)
+    }
+
+    fn clear_buffers_on_open(self, buffer: ClearBuffer) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).clear_buffers_on_open(buffer)
+    }
+
+    fn allow_shared(self) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).allow_shared()
+    }
+
+    fn drop_output(self, output: PendingOutput) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).drop_output(output)
+    }
+
+    fn restore_settings_on_close(self, restore: bool) -> AsyncSerialPortBuilder {
+        AsyncSerialPortBuilder::from(self).restore_settings_on_close(restore)
+    }
 }
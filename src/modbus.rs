@@ -0,0 +1,568 @@
+//! Modbus RTU master and slave
+//!
+//! [`ModbusRtuMaster`] polls slaves over a port with the [RTU codec](crate::codec::modbus):
+//! it frames and checks the requests and responses, keeps the line quiet for 3.5 character
+//! times between frames, waits a configurable time for each slave and turns exception
+//! responses into errors carrying their [`Exception`].
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tokio_serial::modbus::ModbusRtuMaster;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut master = ModbusRtuMaster::new(port);
+//! master.set_timeout(Duration::from_millis(200));
+//! // A slow gateway
+//! master.set_slave_timeout(17, Some(Duration::from_secs(1)));
+//!
+//! let temperatures = master.read_holding_registers(1, 0x0100, 4).await?;
+//! master.write_single_register(17, 0x0010, 1).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ModbusRtuSlave`] is the other end, emulating a device: it answers the requests to its
+//! address with a [`Handler`], like the [`Registers`] map, and takes care of the framing,
+//! the CRC and the silence before each response.
+//!
+//! ```no_run
+//! use tokio_serial::modbus::{ModbusRtuSlave, Registers};
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut registers = Registers::new(16, 4);
+//! registers.input_mut()[0] = 215;
+//! ModbusRtuSlave::new(port, 1).run(registers).await
+//! # }
+//! ```
+use crate::codec::modbus::{frame_silence, ModbusRtuCodec, RtuFrame};
+use crate::frame::TimedFramed;
+use crate::{SerialPort, SerialStream};
+
+pub use crate::codec::modbus::Exception;
+
+use bytes::{Buf, Bytes};
+use futures::{SinkExt, StreamExt};
+use tokio::time::{sleep_until, timeout_at, Instant};
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Response timeout used for slaves without one of their own
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time given to all slaves to process a broadcast before the next request
+pub const DEFAULT_TURNAROUND_DELAY: Duration = Duration::from_millis(100);
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// Most registers read by one request
+const MAX_READ: u16 = 125;
+/// Most registers written by one request
+const MAX_WRITE: u16 = 123;
+
+/// Returns the exception of an error returned for an exception response
+pub fn exception(err: &io::Error) -> Option<Exception> {
+    err.get_ref()?.downcast_ref::<Exception>().copied()
+}
+
+/// Runs Modbus RTU requests, one at a time, see the [module](self) docs
+#[derive(Debug)]
+pub struct ModbusRtuMaster {
+    framed: TimedFramed<ModbusRtuCodec>,
+    timeout: Duration,
+    slave_timeouts: HashMap<u8, Duration>,
+    turnaround_delay: Duration,
+    // When the line may carry the next request
+    quiet_until: Option<Instant>,
+}
+
+impl ModbusRtuMaster {
+    /// Poll slaves over `port`, configured with the baud rate and parity of the bus
+    pub fn new(port: SerialStream) -> Self {
+        Self {
+            framed: TimedFramed::new(port, ModbusRtuCodec::new()),
+            timeout: DEFAULT_TIMEOUT,
+            slave_timeouts: HashMap::new(),
+            turnaround_delay: DEFAULT_TURNAROUND_DELAY,
+            quiet_until: None,
+        }
+    }
+
+    /// Wait up to `timeout` for a response, [`DEFAULT_TIMEOUT`] by default
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the response timeout of slaves without one of their own
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Wait up to `timeout` for responses of `slave`, `None` for the default timeout
+    pub fn set_slave_timeout(&mut self, slave: u8, timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => self.slave_timeouts.insert(slave, timeout),
+            None => self.slave_timeouts.remove(&slave),
+        };
+    }
+
+    /// Returns the response timeout of `slave`
+    pub fn slave_timeout(&self, slave: u8) -> Duration {
+        self.slave_timeouts
+            .get(&slave)
+            .copied()
+            .unwrap_or(self.timeout)
+    }
+
+    /// Leave the slaves `delay` to process a broadcast, [`DEFAULT_TURNAROUND_DELAY`] by
+    /// default
+    pub fn set_turnaround_delay(&mut self, delay: Duration) {
+        self.turnaround_delay = delay;
+    }
+
+    /// Returns the time left to the slaves to process a broadcast
+    pub fn turnaround_delay(&self) -> Duration {
+        self.turnaround_delay
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        self.framed.get_ref().get_ref()
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        self.framed.get_mut().get_mut()
+    }
+
+    /// Consumes the master, returning the port
+    pub fn into_inner(self) -> SerialStream {
+        self.framed.into_inner().into_inner()
+    }
+
+    /// Send the request `function` with `data` to `slave` and return the data of its response
+    ///
+    /// A request to address 0 is a broadcast: nothing is awaited and empty data returned.
+    /// Responses from other slaves, late answers to earlier requests, are skipped.
+    ///
+    /// ## Errors
+    ///
+    /// * `Other` carrying the [`Exception`] of an exception response, see [`exception`].
+    /// * `TimedOut` if the slave didn't respond within its timeout.
+    /// * `InvalidData` if the response doesn't match the request.
+    /// * `UnexpectedEof` if the port hung up, and the errors of the port.
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn request(&mut self, slave: u8, function: u8, data: &[u8]) -> io::Result<Bytes> {
+        if let Some(quiet_until) = self.quiet_until {
+            sleep_until(quiet_until).await;
+        }
+        let request = RtuFrame::new(slave, function, data.to_vec());
+        self.framed.send(request).await?;
+        let silence = self.silence()?;
+
+        if slave == 0 {
+            self.quiet_until = Some(Instant::now() + self.turnaround_delay.max(silence));
+            return Ok(Bytes::new());
+        }
+
+        let deadline = Instant::now() + self.slave_timeout(slave);
+        let response = loop {
+            let next = match timeout_at(deadline, self.framed.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    self.quiet_until = Some(Instant::now() + silence);
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("no response from Modbus slave {}", slave),
+                    ));
+                }
+            };
+            match next {
+                Some(Ok(response)) if response.address == slave => break response,
+                Some(Ok(other)) => {
+                    log::debug!("skipping response of Modbus slave {}", other.address)
+                }
+                // A corrupt frame may be noise ahead of the response
+                Some(Err(e)) if e.kind() == io::ErrorKind::InvalidData => {
+                    log::debug!("skipping corrupt Modbus frame: {}", e)
+                }
+                Some(Err(e)) => return Err(e),
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        };
+        self.quiet_until = Some(Instant::now() + silence);
+
+        if response.function == function | 0x80 {
+            if let Some(exception) = response.exception() {
+                return Err(io::Error::new(io::ErrorKind::Other, exception));
+            }
+        }
+        if response.function != function {
+            return Err(mismatch());
+        }
+        Ok(response.data)
+    }
+
+    /// Read `count` holding registers of `slave` from `start`
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `count` is 0 or more than 125, or as for [`request`](Self::request).
+    pub async fn read_holding_registers(
+        &mut self,
+        slave: u8,
+        start: u16,
+        count: u16,
+    ) -> io::Result<Vec<u16>> {
+        self.read_registers(slave, READ_HOLDING_REGISTERS, start, count)
+            .await
+    }
+
+    /// Read `count` input registers of `slave` from `start`
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `count` is 0 or more than 125, or as for [`request`](Self::request).
+    pub async fn read_input_registers(
+        &mut self,
+        slave: u8,
+        start: u16,
+        count: u16,
+    ) -> io::Result<Vec<u16>> {
+        self.read_registers(slave, READ_INPUT_REGISTERS, start, count)
+            .await
+    }
+
+    /// Write `value` to the holding register `address` of `slave`
+    pub async fn write_single_register(
+        &mut self,
+        slave: u8,
+        address: u16,
+        value: u16,
+    ) -> io::Result<()> {
+        let mut data = [0; 4];
+        data[..2].copy_from_slice(&address.to_be_bytes());
+        data[2..].copy_from_slice(&value.to_be_bytes());
+        let response = self.request(slave, WRITE_SINGLE_REGISTER, &data).await?;
+        // The slave echoes the request
+        if slave != 0 && response[..] != data {
+            return Err(mismatch());
+        }
+        Ok(())
+    }
+
+    /// Write `values` to the holding registers of `slave` from `start`
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `values` is empty or longer than 123, or as for
+    ///   [`request`](Self::request).
+    pub async fn write_multiple_registers(
+        &mut self,
+        slave: u8,
+        start: u16,
+        values: &[u16],
+    ) -> io::Result<()> {
+        let count = register_count(values.len(), MAX_WRITE)?;
+        let mut data = Vec::with_capacity(5 + values.len() * 2);
+        data.extend_from_slice(&start.to_be_bytes());
+        data.extend_from_slice(&count.to_be_bytes());
+        data.push((count * 2) as u8);
+        for value in values {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        let response = self.request(slave, WRITE_MULTIPLE_REGISTERS, &data).await?;
+        if slave != 0 && response[..] != data[..4] {
+            return Err(mismatch());
+        }
+        Ok(())
+    }
+
+    async fn read_registers(
+        &mut self,
+        slave: u8,
+        function: u8,
+        start: u16,
+        count: u16,
+    ) -> io::Result<Vec<u16>> {
+        register_count(usize::from(count), MAX_READ)?;
+        let mut data = [0; 4];
+        data[..2].copy_from_slice(&start.to_be_bytes());
+        data[2..].copy_from_slice(&count.to_be_bytes());
+        let mut response = self.request(slave, function, &data).await?;
+        if response.len() != 1 + usize::from(count) * 2
+            || response[0] as usize != count as usize * 2
+        {
+            return Err(mismatch());
+        }
+        response.advance(1);
+        Ok((0..count).map(|_| response.get_u16()).collect())
+    }
+
+    fn silence(&self) -> io::Result<Duration> {
+        silence(self.get_ref())
+    }
+}
+
+/// Answers the requests received by a [`ModbusRtuSlave`]
+///
+/// Closures taking the function code and the request data are handlers too.
+pub trait Handler {
+    /// Handle the request `function` with `data`, returning the data of the response
+    ///
+    /// Broadcasts are handled too, their response is dropped.
+    fn handle(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception>;
+}
+
+impl<F> Handler for F
+where
+    F: FnMut(u8, &[u8]) -> Result<Vec<u8>, Exception>,
+{
+    fn handle(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        self(function, data)
+    }
+}
+
+/// A handler shared with the application, which may update it while the slave runs
+impl<H: Handler> Handler for Arc<Mutex<H>> {
+    fn handle(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        self.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .handle(function, data)
+    }
+}
+
+/// Holding and input registers from address 0, answering the register functions
+///
+/// Reading and writing holding registers, single or multiple, and reading input registers
+/// are supported.  Other functions are answered with [`Exception::IllegalFunction`],
+/// addresses beyond the registers with [`Exception::IllegalDataAddress`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Registers {
+    holding: Vec<u16>,
+    input: Vec<u16>,
+}
+
+impl Registers {
+    /// Create `holding` holding and `input` input registers, all 0
+    pub fn new(holding: usize, input: usize) -> Self {
+        Self {
+            holding: vec![0; holding],
+            input: vec![0; input],
+        }
+    }
+
+    /// Returns the holding registers
+    pub fn holding(&self) -> &[u16] {
+        &self.holding
+    }
+
+    /// Returns the holding registers to change them
+    pub fn holding_mut(&mut self) -> &mut [u16] {
+        &mut self.holding
+    }
+
+    /// Returns the input registers
+    pub fn input(&self) -> &[u16] {
+        &self.input
+    }
+
+    /// Returns the input registers to change them
+    pub fn input_mut(&mut self) -> &mut [u16] {
+        &mut self.input
+    }
+}
+
+impl Handler for Registers {
+    fn handle(&mut self, function: u8, mut data: &[u8]) -> Result<Vec<u8>, Exception> {
+        let supported = [
+            READ_HOLDING_REGISTERS,
+            READ_INPUT_REGISTERS,
+            WRITE_SINGLE_REGISTER,
+            WRITE_MULTIPLE_REGISTERS,
+        ];
+        if !supported.contains(&function) {
+            return Err(Exception::IllegalFunction);
+        }
+        if data.len() < 4 {
+            return Err(Exception::IllegalDataValue);
+        }
+        let request = data;
+        let start = usize::from(data.get_u16());
+        let count = usize::from(data.get_u16());
+        match function {
+            READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => {
+                let registers = match function {
+                    READ_HOLDING_REGISTERS => &self.holding,
+                    _ => &self.input,
+                };
+                if count == 0 || count > usize::from(MAX_READ) {
+                    return Err(Exception::IllegalDataValue);
+                }
+                let registers = registers
+                    .get(start..start + count)
+                    .ok_or(Exception::IllegalDataAddress)?;
+                let mut response = Vec::with_capacity(1 + count * 2);
+                response.push((count * 2) as u8);
+                for register in registers {
+                    response.extend_from_slice(&register.to_be_bytes());
+                }
+                Ok(response)
+            }
+            WRITE_SINGLE_REGISTER => {
+                // The second field is the value
+                let register = self
+                    .holding
+                    .get_mut(start)
+                    .ok_or(Exception::IllegalDataAddress)?;
+                *register = count as u16;
+                Ok(request.to_vec())
+            }
+            WRITE_MULTIPLE_REGISTERS => {
+                if count == 0
+                    || count > usize::from(MAX_WRITE)
+                    || data.len() != 1 + count * 2
+                    || usize::from(data[0]) != count * 2
+                {
+                    return Err(Exception::IllegalDataValue);
+                }
+                let registers = self
+                    .holding
+                    .get_mut(start..start + count)
+                    .ok_or(Exception::IllegalDataAddress)?;
+                for (register, value) in registers.iter_mut().zip(data[1..].chunks(2)) {
+                    *register = u16::from_be_bytes([value[0], value[1]]);
+                }
+                Ok(request[..4].to_vec())
+            }
+            _ => unreachable!("unsupported functions were answered above"),
+        }
+    }
+}
+
+/// Answers the Modbus RTU requests to one address, see the [module](self) docs
+#[derive(Debug)]
+pub struct ModbusRtuSlave {
+    framed: TimedFramed<ModbusRtuCodec>,
+    address: u8,
+    response_delay: Duration,
+}
+
+impl ModbusRtuSlave {
+    /// Answer the requests to `address` on `port`
+    ///
+    /// ## Panics
+    ///
+    /// This function panics if `address` is 0, the broadcast address, or above 247.
+    pub fn new(port: SerialStream, address: u8) -> Self {
+        assert!(
+            (1..=247).contains(&address),
+            "Modbus slave addresses are 1 to 247"
+        );
+        Self {
+            framed: TimedFramed::new(port, ModbusRtuCodec::new()),
+            address,
+            response_delay: Duration::from_secs(0),
+        }
+    }
+
+    /// Returns the address answered to
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Wait at least `delay` after a request before responding, for masters that need time
+    /// to turn their transceiver around
+    ///
+    /// The silence of 3.5 character times ending the request is always waited for.
+    pub fn set_response_delay(&mut self, delay: Duration) {
+        self.response_delay = delay;
+    }
+
+    /// Returns the least time waited after a request before responding
+    pub fn response_delay(&self) -> Duration {
+        self.response_delay
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        self.framed.get_ref().get_ref()
+    }
+
+    /// Consumes the slave, returning the port
+    pub fn into_inner(self) -> SerialStream {
+        self.framed.into_inner().into_inner()
+    }
+
+    /// Answer requests with `handler` until the port hangs up or fails
+    ///
+    /// Requests to other addresses and corrupt frames are ignored, broadcasts are handled
+    /// without a response.
+    pub async fn run<H: Handler>(mut self, mut handler: H) -> io::Result<()> {
+        while let Some(request) = self.framed.next().await {
+            let request = match request {
+                Ok(request) => request,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    log::debug!("ignoring corrupt Modbus frame: {}", e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let received = Instant::now();
+            if request.address != self.address && request.address != 0 {
+                continue;
+            }
+
+            let result = handler.handle(request.function, &request.data);
+            if request.address == 0 {
+                continue;
+            }
+            let response = match result {
+                Ok(data) => RtuFrame::new(self.address, request.function, data),
+                Err(exception) => RtuFrame::new(
+                    self.address,
+                    request.function | 0x80,
+                    vec![exception.code()],
+                ),
+            };
+            // The request was delimited by its silence already
+            sleep_until(received + self.response_delay).await;
+            self.framed.send(response).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the silence ending a frame at the current settings of `port`
+fn silence(port: &SerialStream) -> io::Result<Duration> {
+    let char_time = crate::settings::char_time(
+        port.baud_rate()?,
+        port.data_bits()?,
+        port.parity()?,
+        port.stop_bits()?,
+    );
+    Ok(frame_silence(char_time))
+}
+
+fn register_count(count: usize, max: u16) -> io::Result<u16> {
+    if count == 0 || count > usize::from(max) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a Modbus request covers 1 to {} registers", max),
+        ));
+    }
+    Ok(count as u16)
+}
+
+fn mismatch() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Modbus response doesn't match the request",
+    )
+}
@@ -0,0 +1,394 @@
+//! Passive listening on a tapped bus
+//!
+//! A sniffer hooked to an RS-485 bus or to an RS-232 line through a Y adapter must not
+//! disturb it: no bytes sent, no control line asserted, no flow control stalling the
+//! talkers.  [`Monitor`] opens a port that way and splits what it hears into frames at the
+//! silences between them, each stamped with the time its first and last bytes arrived.
+//! Which end sent a frame is not known, the bytes of both directions share the wire.
+//!
+//! On a full duplex line tapped with one adapter per direction, a [`Sniffer`] tags the frames
+//! of each adapter with their [`Direction`] and merges them in the order they started, so a
+//! request comes before its response.  [`Sniffer::into_split`] gives the directions as
+//! separate streams instead.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tokio_serial::monitor::Monitor;
+//!
+//! # async fn run() -> tokio_serial::Result<()> {
+//! let mut bus = Monitor::open(&tokio_serial::new("/dev/ttyUSB0", 19200))?;
+//! while let Some(frame) = bus.next().await {
+//!     let frame = frame?;
+//!     println!("{:?} {:02x?}", frame.start, frame.bytes);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::settings::{char_time, Settings};
+use crate::{FlowControl, SerialPort, SerialPortBuilder, SerialStream};
+
+use futures::Stream;
+use tokio::io::ReadBuf;
+use tokio::time::{sleep_until, Sleep};
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Length ending a frame by default, bounding the memory held by continuous traffic
+pub const DEFAULT_MAX_FRAME_LEN: usize = 4096;
+
+/// Bytes read from the driver at once
+const CHUNK_LEN: usize = 256;
+
+/// Bytes heard between two silences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorFrame {
+    /// The bytes in the order they arrived
+    pub bytes: Vec<u8>,
+    /// When the first bytes arrived
+    pub start: Instant,
+    /// When the last bytes arrived
+    pub end: Instant,
+}
+
+/// A port opened for listening only, see the [module](self) docs
+#[derive(Debug)]
+pub struct Monitor {
+    port: SerialStream,
+    gap: Duration,
+    max_frame_len: usize,
+    chunk: Box<[u8]>,
+    frame: Option<MonitorFrame>,
+    timer: Option<Pin<Box<Sleep>>>,
+    done: bool,
+}
+
+impl Monitor {
+    /// Open the port of `builder` for listening
+    ///
+    /// The line settings of `builder` are applied, except for flow control, which is
+    /// turned off, and DTR, which is left low like RTS.  On unix the port is opened read
+    /// only, the modem lines are ignored and nothing is changed on close; Linux raises DTR and
+    /// RTS for the moment between the open and lowering them again.
+    ///
+    /// Frames are split at silences of 3.5 character times, the Modbus RTU rule, see
+    /// [`set_gap`](Self::set_gap).
+    pub fn open(builder: &SerialPortBuilder) -> crate::Result<Self> {
+        let builder = builder
+            .clone()
+            .flow_control(FlowControl::None)
+            .preserve_dtr_on_open();
+        let mut port = open_read_only(&builder)?;
+        // Best effort, a line without modem control like a pty has nothing to lower
+        if let Err(e) = port.write_data_terminal_ready(false) {
+            log::debug!("unable to lower DTR: {}", e);
+        }
+        if let Err(e) = port.write_request_to_send(false) {
+            log::debug!("unable to lower RTS: {}", e);
+        }
+
+        let settings = Settings::from_builder(&builder);
+        let char_time = char_time(
+            settings.baud_rate,
+            settings.data_bits,
+            settings.parity,
+            settings.stop_bits,
+        );
+        Ok(Self {
+            port,
+            gap: char_time * 7 / 2,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            chunk: vec![0; CHUNK_LEN].into_boxed_slice(),
+            frame: None,
+            timer: None,
+            done: false,
+        })
+    }
+
+    /// End a frame after `gap` without bytes
+    ///
+    /// The driver hands bytes over in chunks, which blurs silences shorter than a few
+    /// milliseconds; a USB adapter may need a gap of its latency timer or more.
+    pub fn set_gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    /// Returns the silence ending a frame
+    pub fn gap(&self) -> Duration {
+        self.gap
+    }
+
+    /// End frames once they reach `len` bytes, [`DEFAULT_MAX_FRAME_LEN`] by default
+    ///
+    /// A frame may exceed `len` by the bytes read along with its last ones.
+    pub fn set_max_frame_len(&mut self, len: usize) {
+        self.max_frame_len = len.max(1);
+    }
+
+    /// Returns the longest frame
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Consumes the monitor, returning the port
+    ///
+    /// Bytes of a frame not yet ended are lost.
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+}
+
+impl Stream for Monitor {
+    type Item = io::Result<MonitorFrame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(this.frame.take().map(Ok));
+            }
+
+            let mut buf = ReadBuf::new(&mut this.chunk);
+            let at = match this.port.poll_read_timestamped(cx, &mut buf) {
+                Poll::Ready(Ok(at)) => at,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Pending => {
+                    if this.frame.is_none() {
+                        return Poll::Pending;
+                    }
+                    let timer = match this.timer.as_mut() {
+                        Some(timer) => timer,
+                        None => return Poll::Pending,
+                    };
+                    futures::ready!(timer.as_mut().poll(cx));
+                    this.timer = None;
+                    return Poll::Ready(this.frame.take().map(Ok));
+                }
+            };
+            let n = buf.filled().len();
+            if n == 0 {
+                // The line hung up
+                this.done = true;
+                continue;
+            }
+
+            let deadline = tokio::time::Instant::from_std(at + this.gap);
+            match this.timer.as_mut() {
+                Some(timer) => timer.as_mut().reset(deadline),
+                None => this.timer = Some(Box::pin(sleep_until(deadline))),
+            }
+
+            let chunk = &this.chunk[..n];
+            let ended = match this.frame.as_mut() {
+                Some(frame) if at.saturating_duration_since(frame.end) <= this.gap => {
+                    frame.bytes.extend_from_slice(chunk);
+                    frame.end = at;
+                    None
+                }
+                _ => this.frame.replace(MonitorFrame {
+                    bytes: chunk.to_vec(),
+                    start: at,
+                    end: at,
+                }),
+            };
+            if ended.is_some() {
+                return Poll::Ready(ended.map(Ok));
+            }
+            if this.frame.as_ref().map_or(0, |frame| frame.bytes.len()) >= this.max_frame_len {
+                this.timer = None;
+                return Poll::Ready(this.frame.take().map(Ok));
+            }
+        }
+    }
+}
+
+/// Which end sent a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent by the host, on its TX line
+    HostToDevice,
+    /// Sent by the device, on the host's RX line
+    DeviceToHost,
+}
+
+/// A frame tagged with its direction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectedFrame {
+    /// Which end sent it
+    pub direction: Direction,
+    /// The frame
+    pub frame: MonitorFrame,
+}
+
+/// The frames of one direction, tagged
+#[derive(Debug)]
+pub struct Directed {
+    monitor: Monitor,
+    direction: Direction,
+}
+
+impl Directed {
+    /// Tag the frames of `monitor` with `direction`
+    pub fn new(monitor: Monitor, direction: Direction) -> Self {
+        Self { monitor, direction }
+    }
+
+    /// Returns the direction
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Returns a reference to the monitor
+    pub fn get_ref(&self) -> &Monitor {
+        &self.monitor
+    }
+
+    /// Returns a mutable reference to the monitor
+    pub fn get_mut(&mut self) -> &mut Monitor {
+        &mut self.monitor
+    }
+
+    /// Consumes the stream, returning the monitor
+    pub fn into_inner(self) -> Monitor {
+        self.monitor
+    }
+}
+
+impl Stream for Directed {
+    type Item = io::Result<DirectedFrame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let direction = self.direction;
+        Pin::new(&mut self.monitor)
+            .poll_next(cx)
+            .map(|frame| frame.map(|frame| frame.map(|frame| DirectedFrame { direction, frame })))
+    }
+}
+
+/// Both directions of a full duplex line, see the [module](self) docs
+///
+/// A frame is held back while the other direction has a frame in progress that started
+/// earlier, so frames come out ordered by their start even when a long one ends after a
+/// short reply.
+#[derive(Debug)]
+pub struct Sniffer {
+    sides: [Directed; 2],
+    pending: [Option<DirectedFrame>; 2],
+    ended: [bool; 2],
+}
+
+impl Sniffer {
+    /// Merge the frames of `host`, hearing the host's TX line, and `device`, hearing its RX
+    /// line
+    pub fn new(host: Monitor, device: Monitor) -> Self {
+        Self {
+            sides: [
+                Directed::new(host, Direction::HostToDevice),
+                Directed::new(device, Direction::DeviceToHost),
+            ],
+            pending: [None, None],
+            ended: [false, false],
+        }
+    }
+
+    /// Open the ports of `host` and `device` for listening, see [`Monitor::open`]
+    pub fn open(host: &SerialPortBuilder, device: &SerialPortBuilder) -> crate::Result<Self> {
+        Ok(Self::new(Monitor::open(host)?, Monitor::open(device)?))
+    }
+
+    /// Set the silence ending a frame in both directions, see [`Monitor::set_gap`]
+    pub fn set_gap(&mut self, gap: Duration) {
+        for side in &mut self.sides {
+            side.monitor.set_gap(gap);
+        }
+    }
+
+    /// Returns the monitor of `direction`
+    pub fn get_ref(&self, direction: Direction) -> &Monitor {
+        &self.sides[direction as usize].monitor
+    }
+
+    /// Returns the monitor of `direction` mutably
+    pub fn get_mut(&mut self, direction: Direction) -> &mut Monitor {
+        &mut self.sides[direction as usize].monitor
+    }
+
+    /// Consumes the sniffer, returning a stream per direction, host to device first
+    ///
+    /// Frames read but held back for ordering are lost.
+    pub fn into_split(self) -> (Directed, Directed) {
+        let [host, device] = self.sides;
+        (host, device)
+    }
+}
+
+impl Stream for Sniffer {
+    type Item = io::Result<DirectedFrame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        for i in 0..2 {
+            if this.pending[i].is_some() || this.ended[i] {
+                continue;
+            }
+            match Pin::new(&mut this.sides[i]).poll_next(cx) {
+                Poll::Ready(Some(Ok(frame))) => this.pending[i] = Some(frame),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => this.ended[i] = true,
+                Poll::Pending => {}
+            }
+        }
+
+        let start = |i: usize| this.pending[i].as_ref().map(|pending| pending.frame.start);
+        let first = match (start(0), start(1)) {
+            (Some(host), Some(device)) if device < host => 1,
+            (Some(_), _) => 0,
+            (None, Some(_)) => 1,
+            (None, None) if this.ended == [true, true] => return Poll::Ready(None),
+            (None, None) => return Poll::Pending,
+        };
+        let other = 1 - first;
+        let earlier = this.sides[other]
+            .monitor
+            .frame
+            .as_ref()
+            .is_some_and(|frame| Some(frame.start) < start(first));
+        if this.pending[other].is_none() && !this.ended[other] && earlier {
+            // Woken by the other monitor's timer or bytes
+            return Poll::Pending;
+        }
+        Poll::Ready(this.pending[first].take().map(Ok))
+    }
+}
+
+#[cfg(unix)]
+fn open_read_only(builder: &SerialPortBuilder) -> crate::Result<SerialStream> {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let path = crate::settings::Settings::from_builder(builder).path;
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(&path)?;
+    let port = SerialStream::from_owned_fd(file.into(), builder)?;
+    // Ignore the modem lines and leave them alone on close
+    crate::config::modify_termios(&port, |t| {
+        t.c_cflag |= libc::CLOCAL;
+        t.c_cflag &= !libc::HUPCL;
+    })?;
+    Ok(port)
+}
+
+#[cfg(windows)]
+fn open_read_only(builder: &SerialPortBuilder) -> crate::Result<SerialStream> {
+    SerialStream::open(builder)
+}
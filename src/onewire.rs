@@ -0,0 +1,355 @@
+//! 1-Wire buses driven by a UART
+//!
+//! With its TX and RX joined to the bus through an open-drain buffer or a diode, a plain
+//! UART can generate the 1-Wire time slots, as described in Maxim's application note 214:
+//!
+//! * a reset is `0xF0` sent at 9600 baud: devices present pull the line low while it is
+//!   read back, so anything but `0xF0` coming back means presence,
+//! * a time slot is one character at 115200 baud: `0xFF` writes a 1 or reads a bit, `0x00`
+//!   writes a 0, and a device answering 0 pulls the character read back below `0xFF`.
+//!
+//! [`OneWire`] builds the ROM commands and the [search](OneWire::search) on these.  Reading
+//! a DS18B20 temperature sensor:
+//!
+//! ```no_run
+//! use tokio_serial::onewire::{OneWire, CONVERT_T, READ_SCRATCHPAD};
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut bus = OneWire::new(port)?;
+//! for rom in bus.search().await? {
+//!     bus.select(&rom).await?;
+//!     bus.write_bytes(&[CONVERT_T]).await?;
+//!     tokio::time::sleep(std::time::Duration::from_millis(750)).await;
+//!     bus.select(&rom).await?;
+//!     bus.write_bytes(&[READ_SCRATCHPAD]).await?;
+//!     let scratchpad = bus.read_bytes(9).await?;
+//!     let celsius = f32::from(i16::from_le_bytes([scratchpad[0], scratchpad[1]])) / 16.0;
+//!     println!("{}: {}°C", rom, celsius);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, SerialStream, StopBits};
+
+use futures::future::poll_fn;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{timeout, Duration};
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+
+/// Baud rate of the reset pulse
+pub const RESET_BAUD_RATE: u32 = 9600;
+/// Baud rate of the time slots
+pub const SLOT_BAUD_RATE: u32 = 115_200;
+
+/// Search for the ROMs of all devices
+pub const SEARCH_ROM: u8 = 0xf0;
+/// Read the ROM of the only device
+pub const READ_ROM: u8 = 0x33;
+/// Address the device with the ROM that follows
+pub const MATCH_ROM: u8 = 0x55;
+/// Address all devices
+pub const SKIP_ROM: u8 = 0xcc;
+/// DS18B20: start a temperature conversion
+pub const CONVERT_T: u8 = 0x44;
+/// DS18B20: read the scratchpad
+pub const READ_SCRATCHPAD: u8 = 0xbe;
+
+/// Longest wait for a character to come back, far above the slot time
+const ECHO_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Compute the Dallas/Maxim CRC-8 of `data`
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        let mut byte = byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 1;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8c;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+/// The 64 bit ROM code identifying a device: family code, serial number and CRC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rom(pub [u8; 8]);
+
+impl Rom {
+    /// Returns the family code, `0x28` for a DS18B20
+    pub fn family(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Returns `true` if the CRC of the code matches
+    pub fn is_valid(&self) -> bool {
+        crc8(&self.0[..7]) == self.0[7]
+    }
+}
+
+impl fmt::Display for Rom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// A 1-Wire bus on a UART, see the [module](self) docs
+#[derive(Debug)]
+pub struct OneWire {
+    port: SerialStream,
+}
+
+impl OneWire {
+    /// Drive the bus through `port`, switching it to 8N1 without flow control
+    pub fn new(mut port: SerialStream) -> io::Result<Self> {
+        port.set_data_bits(DataBits::Eight)?;
+        port.set_parity(Parity::None)?;
+        port.set_stop_bits(StopBits::One)?;
+        port.set_flow_control(FlowControl::None)?;
+        port.set_baud_rate(SLOT_BAUD_RATE)?;
+        Ok(Self { port })
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Consumes the bus, returning the port
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+
+    /// Reset the bus, returning `true` if a device answered with a presence pulse
+    ///
+    /// ## Errors
+    ///
+    /// * `TimedOut` if the pulse didn't come back, when RX isn't wired to the bus.
+    /// * `Other` if the bus is shorted to ground, and the errors of the port.
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn reset(&mut self) -> io::Result<bool> {
+        self.port.clear(ClearBuffer::Input)?;
+        self.port.set_baud_rate(RESET_BAUD_RATE)?;
+        let echo = self.exchange(&[0xf0]).await;
+        self.port.set_baud_rate(SLOT_BAUD_RATE)?;
+        match echo?[0] {
+            0xf0 => Ok(false),
+            0x00 => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "1-Wire bus is shorted to ground",
+            )),
+            _ => Ok(true),
+        }
+    }
+
+    /// Write the bits of `bytes`, least significant first
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let slots = bytes
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit| slot(byte >> bit & 1 != 0)))
+            .collect::<Vec<_>>();
+        let echo = self.exchange(&slots).await?;
+        if echo
+            .iter()
+            .zip(slots.iter())
+            .any(|(&echo, &slot)| echo != slot && slot == 0)
+        {
+            // A written 0 must come back 0, anything else means a wiring problem
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "1-Wire bus didn't follow a written 0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Read `len` bytes, least significant bit first
+    pub async fn read_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let echo = self.exchange(&vec![0xff; len * 8]).await?;
+        Ok(echo
+            .chunks(8)
+            .map(|slots| {
+                slots.iter().enumerate().fold(0, |byte, (bit, &echo)| {
+                    byte | (u8::from(echo == 0xff) << bit)
+                })
+            })
+            .collect())
+    }
+
+    /// Write a single bit
+    pub async fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        self.exchange(&[slot(bit)]).await.map(drop)
+    }
+
+    /// Read a single bit
+    pub async fn read_bit(&mut self) -> io::Result<bool> {
+        Ok(self.exchange(&[0xff]).await?[0] == 0xff)
+    }
+
+    /// Reset the bus and address the device with `rom`
+    ///
+    /// ## Errors
+    ///
+    /// * `NotFound` if no device answered the reset, or as for [`reset`](Self::reset).
+    pub async fn select(&mut self, rom: &Rom) -> io::Result<()> {
+        self.reset_present().await?;
+        self.write_bytes(&[MATCH_ROM]).await?;
+        self.write_bytes(&rom.0).await
+    }
+
+    /// Reset the bus and address all devices
+    pub async fn skip_rom(&mut self) -> io::Result<()> {
+        self.reset_present().await?;
+        self.write_bytes(&[SKIP_ROM]).await
+    }
+
+    /// Read the ROM of the only device on the bus
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if the CRC doesn't match, as when several devices answered.
+    pub async fn read_rom(&mut self) -> io::Result<Rom> {
+        self.reset_present().await?;
+        self.write_bytes(&[READ_ROM]).await?;
+        let mut rom = Rom([0; 8]);
+        rom.0.copy_from_slice(&self.read_bytes(8).await?);
+        if !rom.is_valid() {
+            return Err(invalid_rom());
+        }
+        Ok(rom)
+    }
+
+    /// Find the ROMs of all devices on the bus, in the order of the search
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if a ROM fails its CRC, or no device answered a step of the search.
+    pub async fn search(&mut self) -> io::Result<Vec<Rom>> {
+        let mut roms = Vec::new();
+        let mut rom = [0u8; 8];
+        // Bit position of the last branch where 0 was taken, 1-based, 0 for none
+        let mut last_discrepancy = 0;
+        loop {
+            if !self.reset().await? {
+                return Ok(roms);
+            }
+            self.write_bytes(&[SEARCH_ROM]).await?;
+
+            let mut discrepancy = 0;
+            for position in 1..=64 {
+                let (byte, mask) = ((position - 1) / 8, 1u8 << ((position - 1) % 8));
+                let bit = self.read_bit().await?;
+                let complement = self.read_bit().await?;
+                let direction = match (bit, complement) {
+                    (true, true) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "no 1-Wire device answered the search",
+                        ))
+                    }
+                    (true, false) => true,
+                    (false, true) => false,
+                    // Devices differ here
+                    (false, false) => {
+                        let direction = match position.cmp(&last_discrepancy) {
+                            std::cmp::Ordering::Less => rom[byte] & mask != 0,
+                            std::cmp::Ordering::Equal => true,
+                            std::cmp::Ordering::Greater => false,
+                        };
+                        if !direction {
+                            discrepancy = position;
+                        }
+                        direction
+                    }
+                };
+                if direction {
+                    rom[byte] |= mask;
+                } else {
+                    rom[byte] &= !mask;
+                }
+                self.write_bit(direction).await?;
+            }
+
+            let found = Rom(rom);
+            if !found.is_valid() {
+                return Err(invalid_rom());
+            }
+            roms.push(found);
+            if discrepancy == 0 {
+                return Ok(roms);
+            }
+            last_discrepancy = discrepancy;
+        }
+    }
+
+    async fn reset_present(&mut self) -> io::Result<()> {
+        if !self.reset().await? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no 1-Wire device present",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send `slots` and return what came back
+    async fn exchange(&mut self, slots: &[u8]) -> io::Result<Vec<u8>> {
+        let port = &mut self.port;
+        let mut sent = slots;
+        while !sent.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut *port).poll_write(cx, sent)).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            sent = &sent[n..];
+        }
+
+        let mut echo = vec![0; slots.len()];
+        let mut filled = 0;
+        while filled < echo.len() {
+            let read = poll_fn(|cx| {
+                let mut buf = ReadBuf::new(&mut echo[filled..]);
+                Pin::new(&mut *port)
+                    .poll_read(cx, &mut buf)
+                    .map_ok(|()| buf.filled().len())
+            });
+            match timeout(ECHO_TIMEOUT, read).await {
+                Ok(Ok(0)) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(Ok(n)) => filled += n,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "1-Wire time slots didn't come back, is RX wired to the bus?",
+                    ))
+                }
+            }
+        }
+        Ok(echo)
+    }
+}
+
+/// Returns the character generating a time slot writing `bit`
+fn slot(bit: bool) -> u8 {
+    if bit {
+        0xff
+    } else {
+        0x00
+    }
+}
+
+fn invalid_rom() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "1-Wire ROM CRC mismatch")
+}
@@ -0,0 +1,508 @@
+//! Overlapped I/O on Windows COM ports
+//!
+//! tokio has no equivalent of `AsyncFd` for arbitrary Windows handles, so reads and writes
+//! are issued as overlapped `ReadFile`/`WriteFile` calls, at most one per direction at a
+//! time.  Each direction has an auto-reset event that the system thread pool watches through
+//! `RegisterWaitForSingleObject`; its callback wakes the task waiting for the operation.
+//!
+//! Reads are only issued when data is asked for, sized to the request, and the port's
+//! `COMMTIMEOUTS` make them complete as soon as any data is available.  Nothing is read
+//! ahead of the caller beyond the bytes of a completed read that didn't fit in its buffer.
+//! Writes are written behind: `poll_write` hands a copy of the data to the driver and
+//! returns, the next write or flush waits for it and reports its errors.
+use futures::task::AtomicWaker;
+use windows_sys::Win32::Devices::Communication::{GetCommTimeouts, SetCommTimeouts, COMMTIMEOUTS};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_IO_INCOMPLETE, ERROR_IO_PENDING, ERROR_OPERATION_ABORTED,
+    HANDLE, INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{ReadFile, WriteFile};
+use windows_sys::Win32::System::Threading::{
+    CreateEventW, RegisterWaitForSingleObject, UnregisterWaitEx, INFINITE, WT_EXECUTEINWAITTHREAD,
+};
+use windows_sys::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
+
+use std::ffi::c_void;
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::os::windows::io::RawHandle;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Size of the reads issued on behalf of `readable` and `try_read`
+const READ_CHUNK: usize = 4096;
+
+/// Largest write handed to the driver at once
+const MAX_WRITE: usize = 64 * 1024;
+
+/// How long dropping the port waits for a write still in flight before cancelling it
+const DROP_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Timeouts of the reads and writes on a Windows COM port, in milliseconds
+///
+/// These are the fields of the Win32 `COMMTIMEOUTS` structure, see its documentation for
+/// how they combine.  The [`Default`] makes a read complete as soon as any data is
+/// available and wait for the first byte otherwise, and lets writes take as long as they
+/// need.  This is what ports use unless changed with
+/// [`SerialStream::set_comm_timeouts`](crate::SerialStream::set_comm_timeouts).
+///
+/// Timeouts never surface as errors of `AsyncRead`: a read expiring without data is
+/// reissued, so a short total timeout only costs wakeups.  A write expiring before all of
+/// its data was sent fails the next write or flush with `io::ErrorKind::TimedOut`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommTimeouts {
+    /// Longest gap between two bytes before a read completes with what it has
+    pub read_interval: u32,
+    /// Time per requested byte added to the total timeout of a read
+    pub read_total_multiplier: u32,
+    /// Time added to the total timeout of a read
+    pub read_total_constant: u32,
+    /// Time per written byte added to the total timeout of a write
+    pub write_total_multiplier: u32,
+    /// Time added to the total timeout of a write
+    pub write_total_constant: u32,
+}
+
+impl Default for CommTimeouts {
+    fn default() -> Self {
+        // With both read fields at MAXDWORD a read returns immediately with the buffered
+        // bytes, or with the first byte to arrive within the constant
+        Self {
+            read_interval: u32::MAX,
+            read_total_multiplier: u32::MAX,
+            read_total_constant: u32::MAX - 1,
+            write_total_multiplier: 0,
+            write_total_constant: 0,
+        }
+    }
+}
+
+impl From<CommTimeouts> for COMMTIMEOUTS {
+    fn from(timeouts: CommTimeouts) -> Self {
+        COMMTIMEOUTS {
+            ReadIntervalTimeout: timeouts.read_interval,
+            ReadTotalTimeoutMultiplier: timeouts.read_total_multiplier,
+            ReadTotalTimeoutConstant: timeouts.read_total_constant,
+            WriteTotalTimeoutMultiplier: timeouts.write_total_multiplier,
+            WriteTotalTimeoutConstant: timeouts.write_total_constant,
+        }
+    }
+}
+
+impl From<COMMTIMEOUTS> for CommTimeouts {
+    fn from(timeouts: COMMTIMEOUTS) -> Self {
+        CommTimeouts {
+            read_interval: timeouts.ReadIntervalTimeout,
+            read_total_multiplier: timeouts.ReadTotalTimeoutMultiplier,
+            read_total_constant: timeouts.ReadTotalTimeoutConstant,
+            write_total_multiplier: timeouts.WriteTotalTimeoutMultiplier,
+            write_total_constant: timeouts.WriteTotalTimeoutConstant,
+        }
+    }
+}
+
+/// Asynchronous reads and writes on a COM port handle opened with `FILE_FLAG_OVERLAPPED`
+#[derive(Debug)]
+pub(crate) struct OverlappedIo {
+    handle: HANDLE,
+    read: Mutex<ReadState>,
+    write: Mutex<WriteState>,
+    read_channel: Channel,
+    write_channel: Channel,
+}
+
+// SAFETY: the handle and the events may be used from any thread, the transfers are only
+// touched while holding their direction's lock
+unsafe impl Send for OverlappedIo {}
+unsafe impl Sync for OverlappedIo {}
+
+#[derive(Debug, Default)]
+struct ReadState {
+    transfer: Option<Box<Transfer>>,
+    /// Bytes of the last completed read not yet handed out
+    data: Vec<u8>,
+    pos: usize,
+}
+
+#[derive(Debug, Default)]
+struct WriteState {
+    transfer: Option<Box<Transfer>>,
+}
+
+/// An operation in flight, boxed so the kernel's pointers to it stay valid
+struct Transfer {
+    overlapped: OVERLAPPED,
+    buf: Vec<u8>,
+}
+
+impl fmt::Debug for Transfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transfer")
+            .field("len", &self.buf.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl OverlappedIo {
+    /// Take ownership of `handle`, which is closed on drop
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be a COM port handle opened with `FILE_FLAG_OVERLAPPED` that nothing
+    /// else closes.
+    pub(crate) unsafe fn from_raw_handle(handle: RawHandle) -> io::Result<Self> {
+        let handle = handle as HANDLE;
+        set_comm_timeouts(handle, CommTimeouts::default())?;
+        Ok(Self {
+            handle,
+            read: Mutex::default(),
+            write: Mutex::default(),
+            read_channel: Channel::new()?,
+            write_channel: Channel::new()?,
+        })
+    }
+
+    pub(crate) fn timeouts(&self) -> io::Result<CommTimeouts> {
+        let mut timeouts = COMMTIMEOUTS::default();
+        // SAFETY: GetCommTimeouts fills the struct on success
+        if unsafe { GetCommTimeouts(self.handle, &mut timeouts) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(timeouts.into())
+    }
+
+    /// Apply `timeouts` to the operations issued from now on
+    pub(crate) fn set_timeouts(&self, timeouts: CommTimeouts) -> io::Result<()> {
+        set_comm_timeouts(self.handle, timeouts)
+    }
+
+    pub(crate) fn as_raw_handle(&self) -> RawHandle {
+        self.handle as RawHandle
+    }
+
+    pub(crate) fn poll_read(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut state = lock(&self.read);
+        futures::ready!(self.poll_fill(&mut state, cx, buf.len()))?;
+        Poll::Ready(Ok(state.copy_to(buf)))
+    }
+
+    pub(crate) fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        would_block(self.poll_read(&mut noop_context(), buf))
+    }
+
+    pub(crate) async fn readable(&self) -> io::Result<()> {
+        poll_fn(|cx| self.poll_fill(&mut lock(&self.read), cx, READ_CHUNK)).await
+    }
+
+    /// Returns the number of bytes read from the driver but not handed out yet
+    pub(crate) fn buffered(&self) -> usize {
+        let state = lock(&self.read);
+        state.data.len() - state.pos
+    }
+
+    /// Drop the bytes read from the driver but not handed out yet
+    pub(crate) fn discard_buffered(&self) {
+        let mut state = lock(&self.read);
+        state.data.clear();
+        state.pos = 0;
+    }
+
+    pub(crate) fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let mut state = lock(&self.write);
+        futures::ready!(self.poll_write_idle(&mut state, cx))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let n = buf.len().min(MAX_WRITE);
+        let mut transfer = Transfer::new(self.write_channel.event, buf[..n].to_vec());
+        // SAFETY: the transfer is kept in the state until the operation completes
+        let started = unsafe {
+            WriteFile(
+                self.handle,
+                transfer.buf.as_ptr(),
+                n as u32,
+                ptr::null_mut(),
+                &mut transfer.overlapped,
+            )
+        };
+        check_started(started)?;
+        state.transfer = Some(transfer);
+        Poll::Ready(Ok(n))
+    }
+
+    pub(crate) fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        would_block(self.poll_write(&mut noop_context(), buf))
+    }
+
+    /// Wait for the write in flight to complete
+    pub(crate) fn poll_flush(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_write_idle(&mut lock(&self.write), cx)
+    }
+
+    pub(crate) async fn writable(&self) -> io::Result<()> {
+        poll_fn(|cx| self.poll_flush(cx)).await
+    }
+
+    /// Make sure `state` holds data, reading up to `len` bytes if it doesn't
+    fn poll_fill(
+        &self,
+        state: &mut ReadState,
+        cx: &mut Context<'_>,
+        len: usize,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            if state.pos < state.data.len() {
+                return Poll::Ready(Ok(()));
+            }
+
+            let transfer = match &state.transfer {
+                Some(transfer) => transfer,
+                None => {
+                    let mut transfer = Transfer::new(self.read_channel.event, vec![0; len]);
+                    // SAFETY: the transfer is kept in the state until the operation completes
+                    let started = unsafe {
+                        ReadFile(
+                            self.handle,
+                            transfer.buf.as_mut_ptr(),
+                            len as u32,
+                            ptr::null_mut(),
+                            &mut transfer.overlapped,
+                        )
+                    };
+                    check_started(started)?;
+                    state.transfer = Some(transfer);
+                    continue;
+                }
+            };
+
+            // Register before checking so a completion in between still wakes the task
+            self.read_channel.register(cx.waker());
+            let result = futures::ready!(self.poll_result(transfer));
+            let mut transfer = state.transfer.take().expect("read in flight");
+            match result {
+                Ok(0) => {
+                    // The read timed out without data, issue the next one on the next poll
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Ok(n) => {
+                    transfer.buf.truncate(n);
+                    state.data = transfer.buf;
+                    state.pos = 0;
+                }
+                // `clear(ClearBuffer::Input)` aborts the read in flight
+                Err(e) if e.raw_os_error() == Some(ERROR_OPERATION_ABORTED as i32) => {}
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+
+    /// Wait for the write in flight, if any, and report its result
+    fn poll_write_idle(
+        &self,
+        state: &mut WriteState,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let transfer = match &state.transfer {
+            Some(transfer) => transfer,
+            None => return Poll::Ready(Ok(())),
+        };
+        self.write_channel.register(cx.waker());
+        let result = futures::ready!(self.poll_result(transfer));
+        let transfer = state.transfer.take().expect("write in flight");
+        match result {
+            Ok(n) if n < transfer.buf.len() => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "write timed out before all data was sent",
+            ))),
+            Ok(_) => Poll::Ready(Ok(())),
+            // Discarded by `clear(ClearBuffer::Output)`, or by a removal the next write reports
+            Err(e) if e.raw_os_error() == Some(ERROR_OPERATION_ABORTED as i32) => {
+                Poll::Ready(Ok(()))
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    fn poll_result(&self, transfer: &Transfer) -> Poll<io::Result<usize>> {
+        poll_overlapped(self.handle, &transfer.overlapped)
+    }
+
+    fn wait_for(&self, transfer: &Transfer, timeout: Duration) {
+        wait_overlapped(self.handle, &transfer.overlapped, timeout)
+    }
+}
+
+/// Returns the number of bytes transferred by the operation `overlapped` on `handle`, or
+/// `Poll::Pending` if it's still in flight
+pub(crate) fn poll_overlapped(handle: HANDLE, overlapped: &OVERLAPPED) -> Poll<io::Result<usize>> {
+    let mut transferred = 0;
+    // SAFETY: the overlapped structure belongs to an operation issued on this handle
+    let done = unsafe { GetOverlappedResult(handle, overlapped, &mut transferred, 0) };
+    if done != 0 {
+        return Poll::Ready(Ok(transferred as usize));
+    }
+    match unsafe { GetLastError() } {
+        ERROR_IO_INCOMPLETE => Poll::Pending,
+        e => Poll::Ready(Err(io::Error::from_raw_os_error(e as i32))),
+    }
+}
+
+/// Block until the operation `overlapped` on `handle` completes, cancelling it if it takes
+/// longer than `timeout`
+pub(crate) fn wait_overlapped(handle: HANDLE, overlapped: &OVERLAPPED, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let mut cancelled = false;
+    while poll_overlapped(handle, overlapped).is_pending() {
+        if !cancelled && Instant::now() >= deadline {
+            // SAFETY: cancelling an operation issued on this handle
+            unsafe { CancelIoEx(handle, overlapped) };
+            cancelled = true;
+        }
+        std::thread::yield_now();
+    }
+}
+
+impl Drop for OverlappedIo {
+    fn drop(&mut self) {
+        // The kernel may write to the buffers until the operations complete
+        if let Some(transfer) = lock(&self.read).transfer.take() {
+            self.wait_for(&transfer, Duration::ZERO);
+        }
+        if let Some(transfer) = lock(&self.write).transfer.take() {
+            self.wait_for(&transfer, DROP_WRITE_TIMEOUT);
+        }
+        // SAFETY: the handle is owned and nothing uses it anymore
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+impl ReadState {
+    fn copy_to(&mut self, buf: &mut [u8]) -> usize {
+        let available = &self.data[self.pos..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.pos += n;
+        n
+    }
+}
+
+impl Transfer {
+    fn new(event: HANDLE, buf: Vec<u8>) -> Box<Self> {
+        let overlapped = OVERLAPPED {
+            hEvent: event,
+            ..Default::default()
+        };
+        Box::new(Self { overlapped, buf })
+    }
+}
+
+/// An event signalled when an operation completes, and the task to wake when it is
+#[derive(Debug)]
+pub(crate) struct Channel {
+    event: HANDLE,
+    wait: HANDLE,
+    // Boxed so the address handed to the wait callback stays valid
+    waker: Box<AtomicWaker>,
+}
+
+impl Channel {
+    pub(crate) fn new() -> io::Result<Self> {
+        // SAFETY: plain object creation, the results are checked
+        unsafe {
+            let event = CreateEventW(ptr::null(), 0, 0, ptr::null());
+            if event.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let waker = Box::new(AtomicWaker::new());
+            let mut wait = ptr::null_mut();
+            let registered = RegisterWaitForSingleObject(
+                &mut wait,
+                event,
+                Some(wake),
+                &*waker as *const AtomicWaker as *const c_void,
+                INFINITE,
+                WT_EXECUTEINWAITTHREAD,
+            );
+            if registered == 0 {
+                let e = io::Error::last_os_error();
+                CloseHandle(event);
+                return Err(e);
+            }
+            Ok(Self { event, wait, waker })
+        }
+    }
+
+    /// Returns the auto-reset event to signal when an operation completes
+    pub(crate) fn event(&self) -> HANDLE {
+        self.event
+    }
+
+    /// Wake `waker` the next time the event is signalled
+    pub(crate) fn register(&self, waker: &Waker) {
+        self.waker.register(waker);
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        // SAFETY: INVALID_HANDLE_VALUE makes UnregisterWaitEx wait for a running callback, so
+        // the waker outlives every use by the callback
+        unsafe {
+            UnregisterWaitEx(self.wait, INVALID_HANDLE_VALUE);
+            CloseHandle(self.event);
+        }
+    }
+}
+
+// A `WAITORTIMERCALLBACK`: its second parameter is a `BOOLEAN`, one byte holding 0 or 1,
+// which `windows-sys` binds as `bool` from 0.60 on.  0.59 binds it as `u8`, hence the
+// minimum version.
+unsafe extern "system" fn wake(context: *mut c_void, _timed_out: bool) {
+    let waker = &*(context as *const AtomicWaker);
+    waker.wake();
+}
+
+fn set_comm_timeouts(handle: HANDLE, timeouts: CommTimeouts) -> io::Result<()> {
+    // SAFETY: plain call, an invalid handle is reported as an error
+    if unsafe { SetCommTimeouts(handle, &timeouts.into()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn check_started(started: i32) -> io::Result<()> {
+    if started != 0 {
+        return Ok(());
+    }
+    match unsafe { GetLastError() } {
+        ERROR_IO_PENDING => Ok(()),
+        e => Err(io::Error::from_raw_os_error(e as i32)),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn noop_context() -> Context<'static> {
+    Context::from_waker(futures::task::noop_waker_ref())
+}
+
+fn would_block<T>(poll: Poll<io::Result<T>>) -> io::Result<T> {
+    match poll {
+        Poll::Ready(result) => result,
+        Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+    }
+}
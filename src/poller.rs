@@ -0,0 +1,285 @@
+//! Periodic polling of the devices on a bus
+//!
+//! SCADA-style masters read each device on a bus at its own rate: a level sensor every
+//! second, an energy meter every minute.  A [`Poller`] takes these as [`Poll`] entries, each
+//! a request, a period and a matcher recognizing its response, and sends them one at a time
+//! over a framed port, handing each response to the entry's callback or stream.
+//!
+//! Entries are scheduled at a fixed rate from their start, so the exchanges of other entries
+//! delay a poll but don't make its schedule drift.  A poll that fell behind by more than the
+//! [lateness limit](Poller::set_max_lateness) is skipped instead of bunching up with the next
+//! one, and the [offset](Poll::offset) of each entry staggers entries of the same period.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use futures::StreamExt;
+//! use tokio_serial::frame::SerialFramed;
+//! use tokio_serial::poller::{Poll, Poller};
+//! use tokio_util::codec::LinesCodec;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> Result<(), Box<dyn std::error::Error>> {
+//! let mut poller = Poller::new(SerialFramed::new(port, LinesCodec::new()));
+//! poller.set_timeout(Duration::from_millis(200));
+//! poller.on_response(
+//!     Poll::new("01LEVEL?".to_string(), Duration::from_secs(1))
+//!         .matching(|line: &String| line.starts_with("01")),
+//!     |level| println!("level: {:?}", level),
+//! );
+//! let mut energy = poller.subscribe(
+//!     Poll::new("02ENERGY?".to_string(), Duration::from_secs(60))
+//!         .matching(|line: &String| line.starts_with("02")),
+//! );
+//! tokio::spawn(poller.run());
+//! while let Some(reading) = energy.next().await {
+//!     println!("energy: {:?}", reading);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use futures::channel::mpsc;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use tokio::time::{sleep_until, timeout_at, Instant};
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// Stream of the responses to a [subscribed](Poller::subscribe) entry
+///
+/// Polls that got no matching response in time are `TimedOut` errors.
+pub type Responses<Resp> = mpsc::UnboundedReceiver<io::Result<Resp>>;
+
+/// Matcher recognizing the response to an entry
+type Matcher<Resp> = Box<dyn FnMut(&Resp) -> bool + Send>;
+
+/// A request sent periodically by a [`Poller`]
+pub struct Poll<Req, Resp> {
+    request: Req,
+    period: Duration,
+    offset: Duration,
+    matcher: Matcher<Resp>,
+}
+
+impl<Req, Resp> Poll<Req, Resp> {
+    /// Send `request` every `period`, taking the first response as its answer
+    pub fn new(request: Req, period: Duration) -> Self {
+        Self {
+            request,
+            period,
+            offset: Duration::from_secs(0),
+            matcher: Box::new(|_| true),
+        }
+    }
+
+    /// Take only responses for which `matcher` returns `true` as the answer, skipping others
+    ///
+    /// Late answers to a poll that timed out are skipped this way instead of being taken
+    /// for the answer to the next one.
+    pub fn matching<M>(mut self, matcher: M) -> Self
+    where
+        M: FnMut(&Resp) -> bool + Send + 'static,
+    {
+        self.matcher = Box::new(matcher);
+        self
+    }
+
+    /// Send the first request `offset` after the poller started, immediately by default
+    pub fn offset(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+impl<Req: fmt::Debug, Resp> fmt::Debug for Poll<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poll")
+            .field("request", &self.request)
+            .field("period", &self.period)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+/// Where the responses of an entry go
+enum Delivery<Resp> {
+    Callback(Box<dyn FnMut(io::Result<Resp>) + Send>),
+    Stream(mpsc::UnboundedSender<io::Result<Resp>>),
+}
+
+struct Entry<Req, Resp> {
+    poll: Poll<Req, Resp>,
+    delivery: Delivery<Resp>,
+    // When the next poll is due, set once the poller runs
+    next: Option<Instant>,
+}
+
+/// Sends periodic requests over a framed port, see the [module](self) docs
+pub struct Poller<F, Req, Resp> {
+    framed: F,
+    entries: Vec<Entry<Req, Resp>>,
+    timeout: Duration,
+    quiet_time: Duration,
+    max_lateness: Option<Duration>,
+}
+
+impl<F, Req, Resp> Poller<F, Req, Resp> {
+    /// Poll over `framed`, with a timeout of one second and no quiet time
+    pub fn new(framed: F) -> Self {
+        Self {
+            framed,
+            entries: Vec::new(),
+            timeout: Duration::from_secs(1),
+            quiet_time: Duration::from_secs(0),
+            max_lateness: None,
+        }
+    }
+
+    /// Give up on a response after `timeout`, counted from when the request was sent
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the response timeout
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Leave the line quiet for at least `quiet_time` between an exchange and the next request
+    pub fn set_quiet_time(&mut self, quiet_time: Duration) {
+        self.quiet_time = quiet_time;
+    }
+
+    /// Returns the quiet time between exchanges
+    pub fn quiet_time(&self) -> Duration {
+        self.quiet_time
+    }
+
+    /// Skip polls falling behind their schedule by more than `lateness`, or never with `None`
+    ///
+    /// Skipped polls aren't reported to their entry, the next one is sent on schedule.
+    pub fn set_max_lateness(&mut self, lateness: Option<Duration>) {
+        self.max_lateness = lateness;
+    }
+
+    /// Returns how late a poll may be sent
+    pub fn max_lateness(&self) -> Option<Duration> {
+        self.max_lateness
+    }
+
+    /// Add `poll`, handing each of its responses to `callback`
+    pub fn on_response<C>(&mut self, poll: Poll<Req, Resp>, callback: C)
+    where
+        C: FnMut(io::Result<Resp>) + Send + 'static,
+    {
+        self.add(poll, Delivery::Callback(Box::new(callback)));
+    }
+
+    /// Add `poll`, returning the stream of its responses
+    ///
+    /// Responses are dropped once the stream is.
+    pub fn subscribe(&mut self, poll: Poll<Req, Resp>) -> Responses<Resp> {
+        let (tx, rx) = mpsc::unbounded();
+        self.add(poll, Delivery::Stream(tx));
+        rx
+    }
+
+    fn add(&mut self, poll: Poll<Req, Resp>, delivery: Delivery<Resp>) {
+        self.entries.push(Entry {
+            poll,
+            delivery,
+            next: None,
+        });
+    }
+
+    /// Returns a reference to the framed port
+    pub fn get_ref(&self) -> &F {
+        &self.framed
+    }
+
+    /// Consumes the poller, returning the framed port
+    pub fn into_inner(self) -> F {
+        self.framed
+    }
+}
+
+impl<F, Req, Resp, E> Poller<F, Req, Resp>
+where
+    F: Sink<Req, Error = E> + Stream<Item = Result<Resp, E>> + Unpin,
+    Req: Clone,
+    E: From<io::Error>,
+{
+    /// Poll the entries until the stream ends
+    ///
+    /// Returns immediately without entries.
+    ///
+    /// # Errors
+    ///
+    /// The errors of the sink and stream, which end the polling.
+    pub async fn run(mut self) -> Result<(), E> {
+        let start = Instant::now();
+        for entry in self.entries.iter_mut() {
+            entry.next = Some(start + entry.poll.offset);
+        }
+        let mut quiet_until = start;
+        loop {
+            // Earliest due entry, the first added among those due at the same time
+            let index = match (0..self.entries.len()).min_by_key(|&i| self.entries[i].next) {
+                Some(index) => index,
+                None => return Ok(()),
+            };
+            let due = self.entries[index].next.unwrap_or(start);
+            sleep_until(due.max(quiet_until)).await;
+
+            let now = Instant::now();
+            let entry = &mut self.entries[index];
+            let period = entry.poll.period.max(Duration::from_millis(1));
+            if self.max_lateness.is_some_and(|max| now - due > max) {
+                // Skip to the first period that can still be sent in time
+                let missed = ((now - due).as_nanos() / period.as_nanos()) as u32 + 1;
+                entry.next = Some(due + period * missed);
+                log::debug!("skipped {} late polls", missed);
+                continue;
+            }
+            entry.next = Some(due + period);
+
+            self.framed.send(entry.poll.request.clone()).await?;
+            let deadline = Instant::now() + self.timeout;
+            let response = loop {
+                match timeout_at(deadline, self.framed.next()).await {
+                    Ok(Some(Ok(response))) if (entry.poll.matcher)(&response) => {
+                        break Ok(response)
+                    }
+                    Ok(Some(Ok(_))) => log::debug!("skipped unmatched response"),
+                    Ok(Some(Err(e))) => return Err(e),
+                    Ok(None) => return Ok(()),
+                    Err(_) => break Err(io::Error::new(io::ErrorKind::TimedOut, "no response")),
+                }
+            };
+            quiet_until = Instant::now() + self.quiet_time;
+
+            let closed = match &mut entry.delivery {
+                Delivery::Callback(callback) => {
+                    callback(response);
+                    false
+                }
+                Delivery::Stream(tx) => tx.unbounded_send(response).is_err(),
+            };
+            if closed {
+                // Nobody listens anymore, stop polling the entry
+                self.entries.swap_remove(index);
+            }
+        }
+    }
+}
+
+impl<F: fmt::Debug, Req, Resp> fmt::Debug for Poller<F, Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poller")
+            .field("framed", &self.framed)
+            .field("entries", &self.entries.len())
+            .field("timeout", &self.timeout)
+            .field("quiet_time", &self.quiet_time)
+            .field("max_lateness", &self.max_lateness)
+            .finish()
+    }
+}
@@ -0,0 +1,288 @@
+//! Port enumeration with the names shown to users
+//!
+//! [`available_ports_ext`] extends [`available_ports`](crate::available_ports) with the
+//! descriptions Windows keeps for each port in the device manager, so a UI can show
+//! "USB Serial Port (COM7) – FT232R USB UART" instead of just "COM7".  On other platforms
+//! the extra fields are `None`.
+use crate::SerialPortInfo;
+
+use std::fmt;
+
+/// A port with the descriptions Windows reports for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortInfoExt {
+    /// The port as returned by [`available_ports`](crate::available_ports)
+    pub info: SerialPortInfo,
+    /// The name shown by the device manager, like "USB Serial Port (COM7)"
+    pub friendly_name: Option<String>,
+    /// The product string reported by the bus, like "FT232R USB UART"
+    pub bus_description: Option<String>,
+    /// Where the device is attached, like "Port_#0002.Hub_#0004"
+    pub location: Option<String>,
+}
+
+impl PortInfoExt {
+    /// Returns the friendly name, or the port name without one, followed by the bus
+    /// reported description if there is one
+    pub fn display_name(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for PortInfoExt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            self.friendly_name
+                .as_deref()
+                .unwrap_or(&self.info.port_name),
+        )?;
+        match &self.bus_description {
+            Some(description) => write!(f, " – {}", description),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the available ports with their friendly name, bus reported description and
+/// location
+///
+/// ```no_run
+/// for port in tokio_serial::available_ports_ext().unwrap() {
+///     println!("{}", port);
+/// }
+/// ```
+///
+/// ## Errors
+///
+/// * The errors of [`available_ports`](crate::available_ports).  A port whose descriptions
+///   can't be read is listed without them.
+pub fn available_ports_ext() -> crate::Result<Vec<PortInfoExt>> {
+    #[cfg(windows)]
+    let mut details = sys::port_details();
+
+    Ok(crate::available_ports()?
+        .into_iter()
+        .map(|info| {
+            #[cfg(windows)]
+            if let Some(details) = details.remove(&info.port_name) {
+                return PortInfoExt {
+                    info,
+                    friendly_name: details.friendly_name,
+                    bus_description: details.bus_description,
+                    location: details.location,
+                };
+            }
+            PortInfoExt {
+                info,
+                friendly_name: None,
+                bus_description: None,
+                location: None,
+            }
+        })
+        .collect())
+}
+
+#[cfg(windows)]
+mod sys {
+    use windows_sys::core::GUID;
+    use windows_sys::Win32::Devices::DeviceAndDriverInstallation::{
+        CM_Get_DevNode_PropertyW, CM_Get_Parent, SetupDiDestroyDeviceInfoList,
+        SetupDiEnumDeviceInfo, SetupDiGetClassDevsW, SetupDiGetDevicePropertyW,
+        SetupDiGetDeviceRegistryPropertyW, SetupDiOpenDevRegKey, CR_SUCCESS, DICS_FLAG_GLOBAL,
+        DIGCF_PRESENT, DIREG_DEV, GUID_DEVCLASS_MODEM, GUID_DEVCLASS_PORTS, HDEVINFO,
+        SPDRP_FRIENDLYNAME, SPDRP_LOCATION_INFORMATION, SP_DEVINFO_DATA,
+    };
+    use windows_sys::Win32::Devices::Properties::{
+        DEVPKEY_Device_BusReportedDeviceDesc, DEVPROP_TYPE_STRING,
+    };
+    use windows_sys::Win32::Foundation::{DEVPROPKEY, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::System::Registry::{RegCloseKey, RegQueryValueExW, KEY_READ, REG_SZ};
+
+    use std::collections::HashMap;
+    use std::ptr;
+
+    /// Size in UTF-16 units of the buffers the properties are read into
+    const PROPERTY_LEN: usize = 256;
+
+    #[derive(Debug, Default)]
+    pub(super) struct Details {
+        pub(super) friendly_name: Option<String>,
+        pub(super) bus_description: Option<String>,
+        pub(super) location: Option<String>,
+    }
+
+    /// Returns the descriptions of the present ports and modems, by port name
+    pub(super) fn port_details() -> HashMap<String, Details> {
+        let mut details = HashMap::new();
+        for class in [GUID_DEVCLASS_PORTS, GUID_DEVCLASS_MODEM].iter() {
+            if let Some(devices) = DeviceSet::present(class) {
+                devices.collect_into(&mut details);
+            }
+        }
+        details
+    }
+
+    /// The present devices of a setup class
+    struct DeviceSet(HDEVINFO);
+
+    impl DeviceSet {
+        fn present(class: &GUID) -> Option<Self> {
+            // SAFETY: plain call, the result is checked
+            let set =
+                unsafe { SetupDiGetClassDevsW(class, ptr::null(), ptr::null_mut(), DIGCF_PRESENT) };
+            if set == INVALID_HANDLE_VALUE as HDEVINFO {
+                None
+            } else {
+                Some(Self(set))
+            }
+        }
+
+        fn collect_into(&self, details: &mut HashMap<String, Details>) {
+            for index in 0.. {
+                let mut device = SP_DEVINFO_DATA {
+                    cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+                    ..Default::default()
+                };
+                // SAFETY: the set is valid and `cbSize` is initialized
+                if unsafe { SetupDiEnumDeviceInfo(self.0, index, &mut device) } == 0 {
+                    break;
+                }
+                let port_name = match self.port_name(&device) {
+                    // Parallel ports share the class
+                    Some(name) if !name.starts_with("LPT") => name,
+                    _ => continue,
+                };
+                details.insert(
+                    port_name,
+                    Details {
+                        friendly_name: self.registry_property(&device, SPDRP_FRIENDLYNAME),
+                        bus_description: self.bus_description(&device),
+                        location: self.registry_property(&device, SPDRP_LOCATION_INFORMATION),
+                    },
+                );
+            }
+        }
+
+        /// Returns the `PortName` value of the device's hardware key
+        fn port_name(&self, device: &SP_DEVINFO_DATA) -> Option<String> {
+            let mut buf = [0u16; PROPERTY_LEN];
+            let mut size = std::mem::size_of_val(&buf) as u32;
+            let mut kind = 0;
+            let name: Vec<u16> = "PortName\0".encode_utf16().collect();
+            // SAFETY: the key is closed below, the buffer size is in bytes
+            unsafe {
+                let key =
+                    SetupDiOpenDevRegKey(self.0, device, DICS_FLAG_GLOBAL, 0, DIREG_DEV, KEY_READ);
+                if key == INVALID_HANDLE_VALUE {
+                    return None;
+                }
+                let result = RegQueryValueExW(
+                    key,
+                    name.as_ptr(),
+                    ptr::null(),
+                    &mut kind,
+                    buf.as_mut_ptr().cast(),
+                    &mut size,
+                );
+                RegCloseKey(key);
+                if result != 0 || kind != REG_SZ {
+                    return None;
+                }
+            }
+            from_wide(&buf[..size as usize / 2])
+        }
+
+        fn registry_property(&self, device: &SP_DEVINFO_DATA, property: u32) -> Option<String> {
+            let mut buf = [0u16; PROPERTY_LEN];
+            let mut size = 0;
+            // SAFETY: the buffer size is in bytes
+            let found = unsafe {
+                SetupDiGetDeviceRegistryPropertyW(
+                    self.0,
+                    device,
+                    property,
+                    ptr::null_mut(),
+                    buf.as_mut_ptr().cast(),
+                    std::mem::size_of_val(&buf) as u32,
+                    &mut size,
+                )
+            };
+            if found == 0 {
+                return None;
+            }
+            from_wide(&buf[..size as usize / 2])
+        }
+
+        /// Returns the bus reported description of the device, or of its parent
+        ///
+        /// Drivers like FTDI's create the port as a child of the USB device, which is the one
+        /// the bus described.
+        fn bus_description(&self, device: &SP_DEVINFO_DATA) -> Option<String> {
+            let key = &DEVPKEY_Device_BusReportedDeviceDesc;
+            let mut buf = [0u16; PROPERTY_LEN];
+            let mut kind = 0;
+            let mut size = 0;
+            // SAFETY: the buffer size is in bytes
+            let found = unsafe {
+                SetupDiGetDevicePropertyW(
+                    self.0,
+                    device,
+                    key,
+                    &mut kind,
+                    buf.as_mut_ptr().cast(),
+                    std::mem::size_of_val(&buf) as u32,
+                    &mut size,
+                    0,
+                )
+            };
+            if found != 0 && kind == DEVPROP_TYPE_STRING {
+                return from_wide(&buf[..size as usize / 2]);
+            }
+            parent_property(device.DevInst, key)
+        }
+    }
+
+    impl Drop for DeviceSet {
+        fn drop(&mut self) {
+            // SAFETY: the set is owned
+            unsafe { SetupDiDestroyDeviceInfoList(self.0) };
+        }
+    }
+
+    fn parent_property(device: u32, key: &DEVPROPKEY) -> Option<String> {
+        let mut parent = 0;
+        let mut buf = [0u16; PROPERTY_LEN];
+        let mut kind = 0;
+        let mut size = std::mem::size_of_val(&buf) as u32;
+        // SAFETY: the buffer size is in bytes
+        unsafe {
+            if CM_Get_Parent(&mut parent, device, 0) != CR_SUCCESS {
+                return None;
+            }
+            if CM_Get_DevNode_PropertyW(
+                parent,
+                key,
+                &mut kind,
+                buf.as_mut_ptr().cast(),
+                &mut size,
+                0,
+            ) != CR_SUCCESS
+                || kind != DEVPROP_TYPE_STRING
+            {
+                return None;
+            }
+        }
+        from_wide(&buf[..size as usize / 2])
+    }
+
+    /// Decode a NUL terminated UTF-16 string, `None` if it's empty
+    fn from_wide(wide: &[u16]) -> Option<String> {
+        let end = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+        let string = String::from_utf16_lossy(&wide[..end]);
+        if string.is_empty() {
+            None
+        } else {
+            Some(string)
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Reading many ports from a single task
+//!
+//! A concentrator with a hundred sensors doesn't need a hundred tasks: [`PortSet`] holds the
+//! ports and hands out the bytes of whichever port has some, tagged with the [`PortKey`] the
+//! port was [inserted](PortSet::insert) under.
+//!
+//! ```no_run
+//! use tokio_serial::port_set::{SetEvent, PortSet};
+//!
+//! # async fn run(ports: Vec<tokio_serial::SerialStream>) {
+//! let mut set = PortSet::new();
+//! for port in ports {
+//!     set.insert(port);
+//! }
+//! loop {
+//!     match set.next_event().await {
+//!         (key, SetEvent::Data(bytes)) => println!("{:?}: {:02x?}", key, bytes),
+//!         (key, SetEvent::Hangup(_port)) => println!("{:?} hung up", key),
+//!         (key, SetEvent::Error(_port, e)) => println!("{:?} failed: {}", key, e),
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Frames rather than bytes are obtained by feeding each port's bytes to its own decoder.
+use crate::SerialStream;
+
+use futures::future::poll_fn;
+use futures::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// Bytes read from a port at once, by default
+pub const DEFAULT_READ_LEN: usize = 1024;
+
+/// Identifies a port of a [`PortSet`]
+///
+/// Keys of removed ports are handed out again to the ports inserted later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PortKey(usize);
+
+impl PortKey {
+    /// Returns the position of the port in the set, for indexing tables of per port state
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// What happened on a port of a [`PortSet`]
+#[derive(Debug)]
+pub enum SetEvent {
+    /// Bytes arrived
+    Data(Vec<u8>),
+    /// The line hung up, the port was removed from the set
+    Hangup(SerialStream),
+    /// Reading failed, the port was removed from the set
+    Error(SerialStream, io::Error),
+}
+
+/// A set of ports read from one task, see the [module](self) docs
+#[derive(Debug)]
+pub struct PortSet {
+    ports: Vec<Option<SerialStream>>,
+    len: usize,
+    // Where the next poll starts, so a busy port can't starve the others
+    cursor: usize,
+    // Read into by every poll, sized to the read length
+    chunk: Vec<u8>,
+    waker: Option<Waker>,
+}
+
+impl PortSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self {
+            ports: Vec::new(),
+            len: 0,
+            cursor: 0,
+            chunk: vec![0; DEFAULT_READ_LEN],
+            waker: None,
+        }
+    }
+
+    /// Read up to `len` bytes of a port at once, [`DEFAULT_READ_LEN`] by default
+    pub fn set_read_len(&mut self, len: usize) {
+        self.chunk.resize(len.max(1), 0);
+    }
+
+    /// Returns the most bytes read from a port at once
+    pub fn read_len(&self) -> usize {
+        self.chunk.len()
+    }
+
+    /// Add `port` to the set, returning its key
+    ///
+    /// A task waiting on the set is woken to read the new port.
+    pub fn insert(&mut self, port: SerialStream) -> PortKey {
+        let index = match self.ports.iter().position(Option::is_none) {
+            Some(index) => {
+                self.ports[index] = Some(port);
+                index
+            }
+            None => {
+                self.ports.push(Some(port));
+                self.ports.len() - 1
+            }
+        };
+        self.len += 1;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+        PortKey(index)
+    }
+
+    /// Take the port of `key` out of the set
+    pub fn remove(&mut self, key: PortKey) -> Option<SerialStream> {
+        let port = self.ports.get_mut(key.0)?.take()?;
+        self.len -= 1;
+        Some(port)
+    }
+
+    /// Returns a reference to the port of `key`
+    pub fn get(&self, key: PortKey) -> Option<&SerialStream> {
+        self.ports.get(key.0)?.as_ref()
+    }
+
+    /// Returns a mutable reference to the port of `key`, to write to it
+    pub fn get_mut(&mut self, key: PortKey) -> Option<&mut SerialStream> {
+        self.ports.get_mut(key.0)?.as_mut()
+    }
+
+    /// Returns the number of ports in the set
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the set holds no port
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the keys of the ports in the set
+    pub fn keys(&self) -> impl Iterator<Item = PortKey> + '_ {
+        self.ports
+            .iter()
+            .enumerate()
+            .filter(|(_, port)| port.is_some())
+            .map(|(index, _)| PortKey(index))
+    }
+
+    /// Attempts to read the next port with bytes or a hangup
+    ///
+    /// The ports are polled in turn, starting after the one returned last.  An empty set
+    /// stays pending until a port is inserted.
+    pub fn poll_next_ready(&mut self, cx: &mut Context<'_>) -> Poll<(PortKey, SetEvent)> {
+        let count = self.ports.len();
+        for offset in 0..count {
+            let index = (self.cursor + offset) % count;
+            let port = match self.ports[index].as_mut() {
+                Some(port) => port,
+                None => continue,
+            };
+            let mut buf = ReadBuf::new(&mut self.chunk);
+            let event = match Pin::new(port).poll_read(cx, &mut buf) {
+                Poll::Pending => continue,
+                Poll::Ready(Ok(())) if !buf.filled().is_empty() => {
+                    SetEvent::Data(buf.filled().to_vec())
+                }
+                Poll::Ready(Ok(())) => SetEvent::Hangup(self.take(index)),
+                Poll::Ready(Err(e)) => SetEvent::Error(self.take(index), e),
+            };
+            self.cursor = index + 1;
+            return Poll::Ready((PortKey(index), event));
+        }
+        self.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Wait for the next port with bytes or a hangup, see
+    /// [`poll_next_ready`](Self::poll_next_ready)
+    pub async fn next_event(&mut self) -> (PortKey, SetEvent) {
+        poll_fn(|cx| self.poll_next_ready(cx)).await
+    }
+
+    fn take(&mut self, index: usize) -> SerialStream {
+        self.len -= 1;
+        self.ports[index].take().expect("port polled")
+    }
+}
+
+impl Default for PortSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stream for PortSet {
+    type Item = (PortKey, SetEvent);
+
+    /// Never ends, an empty set stays pending until a port is inserted
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_next_ready(cx).map(Some)
+    }
+}
@@ -0,0 +1,133 @@
+//! Timestamps of a pulse-per-second signal on a modem line
+//!
+//! GPS receivers used for timing mark the start of each second with a pulse, commonly wired
+//! to DCD (or CTS) of the serial port carrying their NMEA sentences.  [`Pps`] timestamps the
+//! edges of that line as close to the interrupt as user space gets: a thread blocks in the
+//! `TIOCMIWAIT` ioctl and reads both clocks the moment the driver wakes it.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tokio_serial::pps::PpsLine;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> tokio_serial::Result<()> {
+//! let mut pulses = port.pps(PpsLine::Dcd)?;
+//! while let Some(edge) = pulses.next().await {
+//!     let edge = edge?;
+//!     if edge.assert {
+//!         println!("second {} started at {:?}", edge.sequence, edge.realtime);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The wakeup latency of the thread, usually tens of microseconds, adds jitter to the
+//! timestamps.  For better precision the kernel's own PPS support timestamps the edges in the
+//! interrupt handler: attach the `pps-ldisc` line discipline with `ldattach PPS` and read
+//! `/dev/ppsN` instead.
+use futures::channel::mpsc;
+use futures::Stream;
+
+use std::io;
+use std::os::unix::io::{AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime};
+
+/// The modem line carrying the pulses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpsLine {
+    /// Data carrier detect, the usual choice
+    Dcd,
+    /// Clear to send
+    Cts,
+}
+
+impl PpsLine {
+    fn mask(self) -> libc::c_int {
+        match self {
+            PpsLine::Dcd => libc::TIOCM_CD,
+            PpsLine::Cts => libc::TIOCM_CTS,
+        }
+    }
+}
+
+/// A timestamped edge of the pulse line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PpsEdge {
+    /// Whether the line was asserted by the edge, the start of the pulse on most receivers
+    pub assert: bool,
+    /// Edges seen so far, starting at 1
+    pub sequence: u64,
+    /// `CLOCK_MONOTONIC` at the edge
+    pub monotonic: Instant,
+    /// `CLOCK_REALTIME` at the edge
+    pub realtime: SystemTime,
+}
+
+/// Stream of the edges of a pulse line, see the [module](self) docs
+///
+/// The thread waiting for the edges ends with the first edge after the stream is dropped,
+/// or when the port hangs up.
+#[derive(Debug)]
+pub struct Pps {
+    edges: mpsc::UnboundedReceiver<io::Result<PpsEdge>>,
+}
+
+impl Pps {
+    /// Watch `line` on a duplicate of `fd`
+    pub(crate) fn new(fd: RawFd, line: PpsLine) -> io::Result<Self> {
+        // SAFETY: `fd` is the open descriptor of the port, borrowed only to duplicate it
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+        let (tx, edges) = mpsc::unbounded();
+        std::thread::Builder::new()
+            .name("tokio-serial-pps".into())
+            .spawn(move || wait_edges(fd, line, tx))?;
+        Ok(Self { edges })
+    }
+}
+
+impl Stream for Pps {
+    type Item = io::Result<PpsEdge>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().edges).poll_next(cx)
+    }
+}
+
+fn wait_edges(fd: OwnedFd, line: PpsLine, tx: mpsc::UnboundedSender<io::Result<PpsEdge>>) {
+    let mut sequence = 0;
+    loop {
+        // SAFETY: the ioctl takes the mask of the lines to wait for by value
+        if unsafe { libc::ioctl(fd.as_raw_fd(), libc::TIOCMIWAIT, line.mask()) } == -1 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            // A hangup ends the stream without an error
+            if !crate::is_hangup(&e) {
+                let _ = tx.unbounded_send(Err(e));
+            }
+            return;
+        }
+        let monotonic = Instant::now();
+        let realtime = SystemTime::now();
+
+        let edge = crate::config::read_modem_lines(&fd).map(|lines| {
+            sequence += 1;
+            PpsEdge {
+                assert: match line {
+                    PpsLine::Dcd => lines.cd,
+                    PpsLine::Cts => lines.cts,
+                },
+                sequence,
+                monotonic,
+                realtime,
+            }
+        });
+        let failed = edge.is_err();
+        if tx.unbounded_send(edge).is_err() || failed {
+            return;
+        }
+    }
+}
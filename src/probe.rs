@@ -0,0 +1,143 @@
+//! Checking that a device still answers
+//!
+//! A serial link can be half open: the port opens and writes succeed, yet the device at the
+//! other end is off, unplugged behind a converter or hung.  A [`Probe`] writes a request the
+//! device always answers, like `AT\r` for a modem, and waits for the expected pattern in
+//! what comes back, returning the round trip time.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tokio_serial::probe::Probe;
+//!
+//! # async fn run(mut port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let probe = Probe::new(&b"AT\r"[..], &b"OK"[..]).timeout(Duration::from_millis(500));
+//! let rtt = probe.run(&mut port).await?;
+//! println!("modem answered in {:?}", rtt);
+//! # Ok(())
+//! # }
+//! ```
+use futures::future::poll_fn;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{timeout_at, Instant};
+
+use std::io;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Response timeout of a probe, by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A request and the pattern its response must contain, see the [module](self) docs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probe {
+    request: Vec<u8>,
+    expected: Vec<u8>,
+    timeout: Duration,
+}
+
+impl Probe {
+    /// Write `request` and expect `expected` somewhere in the response
+    pub fn new(request: impl Into<Vec<u8>>, expected: impl Into<Vec<u8>>) -> Self {
+        Self {
+            request: request.into(),
+            expected: expected.into(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Give up on the response after `timeout`, counted from when the request was written
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the request written
+    pub fn request(&self) -> &[u8] {
+        &self.request
+    }
+
+    /// Returns the pattern expected in the response
+    pub fn expected(&self) -> &[u8] {
+        &self.expected
+    }
+
+    /// Write the request to `port` and wait for the expected pattern, returning the time it
+    /// took to arrive
+    ///
+    /// Bytes up to the end of the pattern are consumed, the ones read past it are dropped.
+    ///
+    /// ## Errors
+    ///
+    /// * `TimedOut` if the pattern didn't come within the timeout.
+    /// * `UnexpectedEof` if the port was closed, and the errors of the port.
+    pub async fn run<P>(&self, port: &mut P) -> io::Result<Duration>
+    where
+        P: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut request = &self.request[..];
+        while !request.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut *port).poll_write(cx, request)).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            request = &request[n..];
+        }
+        poll_fn(|cx| Pin::new(&mut *port).poll_flush(cx)).await?;
+        let sent = Instant::now();
+
+        let deadline = sent + self.timeout;
+        let mut received = Vec::new();
+        loop {
+            if find(&received, &self.expected) {
+                return Ok(sent.elapsed());
+            }
+            // Only the tail that may start the pattern needs keeping
+            let keep = self.expected.len().saturating_sub(1);
+            if received.len() > keep {
+                received.drain(..received.len() - keep);
+            }
+
+            let mut buf = [0; 64];
+            let read = poll_fn(|cx| {
+                let mut buf = ReadBuf::new(&mut buf);
+                Pin::new(&mut *port)
+                    .poll_read(cx, &mut buf)
+                    .map_ok(|()| buf.filled().len())
+            });
+            match timeout_at(deadline, read).await {
+                Ok(Ok(0)) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(Ok(n)) => received.extend_from_slice(&buf[..n]),
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "device didn't answer the probe",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Write `request` to `port` and wait up to `timeout` for `expected`, see [`Probe::run`]
+pub async fn probe<P>(
+    port: &mut P,
+    request: &[u8],
+    expected: &[u8],
+    timeout: Duration,
+) -> io::Result<Duration>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    Probe::new(request, expected)
+        .timeout(timeout)
+        .run(port)
+        .await
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty()
+        || haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
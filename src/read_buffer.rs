@@ -0,0 +1,168 @@
+//! Optional read coalescing for `SerialStream`
+use futures::ready;
+use tokio::io::ReadBuf;
+
+use std::io::Result as IoResult;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Poll;
+use std::time::Instant;
+
+/// Size of the buffer filled by `poll_fill_buf` on a port without a read buffer capacity
+const DEFAULT_FILL_CAPACITY: usize = 1024;
+
+/// Bytes read from the driver ahead of the caller
+///
+/// A capacity of zero, the default, disables coalescing: reads go straight to the driver and
+/// the buffer is only used by `AsyncBufRead::poll_fill_buf`.
+#[derive(Debug, Default)]
+pub(crate) struct ReadBuffer {
+    storage: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    capacity: usize,
+    // Set by `SerialPort::clear`, which only has a shared reference
+    discard: AtomicBool,
+    // When the last bytes were read into the buffer
+    filled_at: Option<Instant>,
+}
+
+impl ReadBuffer {
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the capacity, bytes already buffered are kept until consumed
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        if self.is_empty() {
+            self.storage = Box::default();
+            self.pos = 0;
+            self.end = 0;
+        }
+    }
+
+    /// Returns the number of buffered bytes
+    pub(crate) fn len(&self) -> usize {
+        if self.discard.load(Ordering::Relaxed) {
+            0
+        } else {
+            self.end - self.pos
+        }
+    }
+
+    pub(crate) fn is_empty(&mut self) -> bool {
+        self.apply_discard();
+        self.pos == self.end
+    }
+
+    /// Drop the buffered bytes the next time the buffer is used
+    pub(crate) fn discard(&self) {
+        self.discard.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn buffered(&mut self) -> &[u8] {
+        self.apply_discard();
+        &self.storage[self.pos..self.end]
+    }
+
+    pub(crate) fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.end);
+    }
+
+    /// Take the buffered bytes out, leaving the buffer empty
+    #[cfg(unix)]
+    pub(crate) fn take(&mut self) -> Vec<u8> {
+        let bytes = self.buffered().to_vec();
+        self.pos = 0;
+        self.end = 0;
+        bytes
+    }
+
+    /// Put `bytes` in front of the buffered ones, to be read first
+    #[cfg(unix)]
+    pub(crate) fn unread(&mut self, bytes: &[u8]) {
+        let mut storage = bytes.to_vec();
+        storage.extend_from_slice(self.buffered());
+        self.end = storage.len();
+        self.pos = 0;
+        self.storage = storage.into_boxed_slice();
+    }
+
+    /// Copy as many buffered bytes as fit into `buf`, returning their number
+    pub(crate) fn copy_to(&mut self, buf: &mut [u8]) -> usize {
+        let available = self.buffered();
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        n
+    }
+
+    /// Returns when the last buffered bytes arrived
+    pub(crate) fn filled_at(&self) -> Option<Instant> {
+        self.filled_at
+    }
+
+    /// Whether a read of `wanted` bytes should bypass the buffer
+    pub(crate) fn bypass(&mut self, wanted: usize) -> bool {
+        self.is_empty() && wanted >= self.capacity
+    }
+
+    /// Refill the empty buffer using `read`
+    pub(crate) fn poll_fill<F>(&mut self, read: F) -> Poll<IoResult<()>>
+    where
+        F: FnOnce(&mut ReadBuf<'_>) -> Poll<IoResult<()>>,
+    {
+        debug_assert!(self.is_empty());
+        let size = match self.capacity {
+            0 => DEFAULT_FILL_CAPACITY,
+            n => n,
+        };
+        if self.storage.len() != size {
+            self.storage = vec![0; size].into_boxed_slice();
+        }
+
+        let mut buf = ReadBuf::new(&mut self.storage);
+        ready!(read(&mut buf))?;
+        self.end = buf.filled().len();
+        self.pos = 0;
+        self.filled_at = Some(Instant::now());
+        Poll::Ready(Ok(()))
+    }
+
+    /// Read more bytes behind the buffered ones using `read`, until `wanted` could be buffered
+    ///
+    /// Used to look ahead: the buffered bytes are kept and the storage grows as needed.
+    pub(crate) fn poll_extend<F>(&mut self, wanted: usize, read: F) -> Poll<IoResult<()>>
+    where
+        F: FnOnce(&mut ReadBuf<'_>) -> Poll<IoResult<()>>,
+    {
+        self.apply_discard();
+        self.storage.copy_within(self.pos..self.end, 0);
+        self.end -= self.pos;
+        self.pos = 0;
+
+        let size = wanted.max(match self.capacity {
+            0 => DEFAULT_FILL_CAPACITY,
+            n => n,
+        });
+        if self.storage.len() < size {
+            let mut storage = vec![0; size];
+            storage[..self.end].copy_from_slice(&self.storage[..self.end]);
+            self.storage = storage.into_boxed_slice();
+        }
+
+        let mut buf = ReadBuf::new(&mut self.storage[self.end..]);
+        ready!(read(&mut buf))?;
+        self.end += buf.filled().len();
+        self.filled_at = Some(Instant::now());
+        Poll::Ready(Ok(()))
+    }
+
+    fn apply_discard(&mut self) {
+        if *self.discard.get_mut() {
+            *self.discard.get_mut() = false;
+            self.pos = 0;
+            self.end = 0;
+        }
+    }
+}
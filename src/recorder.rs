@@ -0,0 +1,238 @@
+//! Recording console output with timestamps
+//!
+//! Boot time analysis reads a board's console and looks at when each line came, the way
+//! grabserial does.  A [`Recorder`] wraps the port and yields its lines as
+//! [`RecordedLine`]s, stamped with the wall clock time, the time since the recording started
+//! and the time since the previous line.  Lines may also be written to a log file as they
+//! arrive, formatted like grabserial's output:
+//!
+//! ```text
+//! [    0.000000  0.000000] U-Boot 2023.04
+//! [    1.204513  1.204513] Starting kernel ...
+//! ```
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tokio_serial::recorder::Recorder;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let log = std::fs::File::create("boot.log")?;
+//! let mut console = Recorder::new(port).record_to(log).reset_on("Starting kernel");
+//! while let Some(line) = console.next().await {
+//!     let line = line?;
+//!     if line.delta.as_secs() >= 1 {
+//!         println!("slow step: {}", line);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use futures::{ready, Stream};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::time::Instant;
+
+use std::fmt;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+/// Lines longer than this are split, by default
+pub const DEFAULT_MAX_LINE_LEN: usize = 4096;
+
+/// A line received by a [`Recorder`]
+///
+/// The times are those of the line's first byte.  `Display` formats it like grabserial,
+/// with the elapsed and delta times in seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedLine {
+    /// The line without its `\r\n` or `\n`, invalid UTF-8 replaced
+    pub text: String,
+    /// Wall clock time
+    pub time: SystemTime,
+    /// Time since the recording started, or since the last reset
+    pub elapsed: Duration,
+    /// Time since the previous line
+    pub delta: Duration,
+}
+
+impl fmt::Display for RecordedLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:5}.{:06} {:2}.{:06}] {}",
+            self.elapsed.as_secs(),
+            self.elapsed.subsec_micros(),
+            self.delta.as_secs(),
+            self.delta.subsec_micros(),
+            self.text
+        )
+    }
+}
+
+/// Yields the timestamped lines read from a port, see the [module](self) docs
+pub struct Recorder<R> {
+    reader: R,
+    buf: Vec<u8>,
+    line: Vec<u8>,
+    // Arrival of the first byte of `line`
+    line_start: Option<(Instant, SystemTime)>,
+    // Arrival of the bytes read last
+    last_read: Option<(Instant, SystemTime)>,
+    start: Instant,
+    previous: Option<Instant>,
+    reset_on: Option<String>,
+    log: Option<Box<dyn Write + Send>>,
+    max_line_len: usize,
+    eof: bool,
+}
+
+impl<R> Recorder<R> {
+    /// Record the lines read from `reader`, counting the elapsed time from now
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: vec![0; 256],
+            line: Vec::new(),
+            line_start: None,
+            last_read: None,
+            start: Instant::now(),
+            previous: None,
+            reset_on: None,
+            log: None,
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+            eof: false,
+        }
+    }
+
+    /// Also write each line to `log`, formatted as by `Display`, as soon as it is complete
+    ///
+    /// A failed write is reported by the stream.
+    pub fn record_to(mut self, log: impl Write + Send + 'static) -> Self {
+        self.log = Some(Box::new(log));
+        self
+    }
+
+    /// Restart the elapsed time at the first line containing `pattern`, like grabserial's
+    /// `--match`
+    ///
+    /// The matching line gets an elapsed time of zero, which takes out the time spent before
+    /// the interesting part of a boot, in the boot loader for instance.
+    pub fn reset_on(mut self, pattern: impl Into<String>) -> Self {
+        self.reset_on = Some(pattern.into());
+        self
+    }
+
+    /// Split lines longer than `len` bytes, [`DEFAULT_MAX_LINE_LEN`] by default
+    pub fn max_line_len(mut self, len: usize) -> Self {
+        self.max_line_len = len.max(1);
+        self
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Consumes the recorder, returning the port
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Complete the line of `len` bytes of `self.line`
+    fn take_line(&mut self, len: usize) -> io::Result<RecordedLine> {
+        let (arrived, time) = self
+            .line_start
+            .take()
+            .unwrap_or_else(|| (Instant::now(), SystemTime::now()));
+        let mut bytes: Vec<u8> = self.line.drain(..len).collect();
+        if bytes.last() == Some(&b'\n') {
+            bytes.pop();
+        }
+        if bytes.last() == Some(&b'\r') {
+            bytes.pop();
+        }
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+
+        if let Some(pattern) = &self.reset_on {
+            if text.contains(pattern.as_str()) {
+                self.start = arrived;
+                self.reset_on = None;
+            }
+        }
+        let line = RecordedLine {
+            text,
+            time,
+            elapsed: arrived.saturating_duration_since(self.start),
+            delta: self
+                .previous
+                .map_or(Duration::from_secs(0), |previous| arrived - previous),
+        };
+        self.previous = Some(arrived);
+        if !self.line.is_empty() {
+            // The bytes left over start the next line
+            self.line_start = self.last_read;
+        }
+
+        if let Some(log) = &mut self.log {
+            writeln!(log, "{}", line)?;
+            log.flush()?;
+        }
+        Ok(line)
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for Recorder<R> {
+    type Item = io::Result<RecordedLine>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let end = this
+                .line
+                .iter()
+                .position(|&b| b == b'\n')
+                .map(|end| end + 1);
+            match end {
+                Some(end) => return Poll::Ready(Some(this.take_line(end))),
+                None if this.line.len() >= this.max_line_len => {
+                    let len = this.max_line_len;
+                    return Poll::Ready(Some(this.take_line(len)));
+                }
+                None if this.eof && !this.line.is_empty() => {
+                    let len = this.line.len();
+                    return Poll::Ready(Some(this.take_line(len)));
+                }
+                None if this.eof => return Poll::Ready(None),
+                None => {}
+            }
+
+            let mut buf = ReadBuf::new(&mut this.buf);
+            if let Err(e) = ready!(Pin::new(&mut this.reader).poll_read(cx, &mut buf)) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            let read = buf.filled();
+            if read.is_empty() {
+                this.eof = true;
+                continue;
+            }
+            this.last_read = Some((Instant::now(), SystemTime::now()));
+            if this.line_start.is_none() {
+                this.line_start = this.last_read;
+            }
+            this.line.extend_from_slice(read);
+        }
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Recorder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("reader", &self.reader)
+            .field("start", &self.start)
+            .field("reset_on", &self.reset_on)
+            .field("logging", &self.log.is_some())
+            .field("max_line_len", &self.max_line_len)
+            .finish()
+    }
+}
@@ -0,0 +1,273 @@
+//! Reconfiguring ports while they are in use
+//!
+//! A headless gateway reloads its configuration without restarting.  A [`ManagedPort`] is
+//! read and written like the [`SerialStream`] it wraps, and picks up the settings sent
+//! through its [`ReloadHandle`] on its next read or write: changed line settings are
+//! [applied](SerialStream::apply) in place, a changed path reopens the port.  Each change is
+//! reported as a [`ReloadEvent`].
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use tokio_serial::reload::{ManagedPort, PortSettings};
+//! use tokio_serial::SerialConfig;
+//!
+//! # async fn run() -> tokio_serial::Result<()> {
+//! let settings = PortSettings::new("/dev/ttyUSB0", SerialConfig::new(9600));
+//! let mut port = ManagedPort::open(settings)?;
+//! let handle = port.handle();
+//! let mut events = port.events();
+//! tokio::spawn(async move {
+//!     while let Some(event) = events.next().await {
+//!         println!("{:?}", event);
+//!     }
+//! });
+//!
+//! // Typically sent by whatever watches and parses the configuration file
+//! handle.update(PortSettings::new("/dev/ttyUSB0", SerialConfig::new(115_200)));
+//! # Ok(())
+//! # }
+//! ```
+use crate::{ConfigDiff, SerialConfig, SerialStream};
+
+use futures::channel::mpsc;
+use futures::{ready, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Where a [`ManagedPort`] is and how it is configured
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortSettings {
+    /// Path of the port
+    pub path: String,
+    /// Line settings of the port
+    pub config: SerialConfig,
+}
+
+impl PortSettings {
+    /// Settings of the port at `path`
+    pub fn new(path: impl Into<String>, config: SerialConfig) -> Self {
+        Self {
+            path: path.into(),
+            config,
+        }
+    }
+
+    fn open(&self) -> crate::Result<SerialStream> {
+        SerialStream::open(&self.config.to_builder(&self.path))
+    }
+}
+
+/// A change made to a [`ManagedPort`]
+#[derive(Debug)]
+pub enum ReloadEvent {
+    /// Line settings were changed in place
+    Reconfigured {
+        /// The settings read back from the driver before the change
+        before: SerialConfig,
+        /// The settings applied
+        after: SerialConfig,
+        /// The settings that differed
+        diff: ConfigDiff,
+    },
+    /// The port was closed and the one at the new path opened
+    Reopened {
+        /// The settings of the closed port
+        before: PortSettings,
+        /// The settings of the opened port
+        after: PortSettings,
+    },
+    /// The settings couldn't be applied, the port keeps going with the previous ones
+    ///
+    /// Line settings changed before the failing one stay in place.
+    Failed {
+        /// The settings that failed
+        settings: PortSettings,
+        /// Why they failed
+        error: crate::Error,
+    },
+}
+
+/// Sends new settings to a [`ManagedPort`]
+#[derive(Debug, Clone)]
+pub struct ReloadHandle {
+    updates: mpsc::UnboundedSender<PortSettings>,
+}
+
+impl ReloadHandle {
+    /// Have the port switch to `settings` on its next read or write
+    ///
+    /// Returns `false` if the port was dropped.
+    pub fn update(&self, settings: PortSettings) -> bool {
+        self.updates.unbounded_send(settings).is_ok()
+    }
+}
+
+/// Stream of the [`ReloadEvent`]s of a [`ManagedPort`], see [`ManagedPort::events`]
+pub type ReloadEvents = mpsc::UnboundedReceiver<ReloadEvent>;
+
+/// A port reconfigured by the settings sent to it, see the [module](self) docs
+#[derive(Debug)]
+pub struct ManagedPort {
+    port: SerialStream,
+    settings: PortSettings,
+    updates: mpsc::UnboundedReceiver<PortSettings>,
+    handle: mpsc::UnboundedSender<PortSettings>,
+    // Taken while its pending output is flushed
+    pending: Option<PortSettings>,
+    events: Option<mpsc::UnboundedSender<ReloadEvent>>,
+}
+
+impl ManagedPort {
+    /// Open the port of `settings`
+    pub fn open(settings: PortSettings) -> crate::Result<Self> {
+        let port = settings.open()?;
+        let (handle, updates) = mpsc::unbounded();
+        Ok(Self {
+            port,
+            settings,
+            updates,
+            handle,
+            pending: None,
+            events: None,
+        })
+    }
+
+    /// Returns a handle sending new settings to the port
+    pub fn handle(&self) -> ReloadHandle {
+        ReloadHandle {
+            updates: self.handle.clone(),
+        }
+    }
+
+    /// Returns the stream of the changes made from now on
+    ///
+    /// Only the stream returned last receives the events.
+    pub fn events(&mut self) -> ReloadEvents {
+        let (tx, rx) = mpsc::unbounded();
+        self.events = Some(tx);
+        rx
+    }
+
+    /// Returns the settings the port was opened or last reconfigured with
+    pub fn settings(&self) -> &PortSettings {
+        &self.settings
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the port
+    ///
+    /// Settings changed through it are overridden by the next update.
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Apply the settings received so far
+    ///
+    /// Called by every read and write first, only pending while the output written with
+    /// the previous settings is transmitted.  Failures are reported as events.
+    pub fn poll_reload(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            let settings = match self.pending.take() {
+                Some(settings) => settings,
+                None => match Pin::new(&mut self.updates).poll_next(cx) {
+                    Poll::Ready(Some(settings)) => settings,
+                    // The receiver never ends, the port holds a sender itself
+                    Poll::Ready(None) | Poll::Pending => return Poll::Ready(()),
+                },
+            };
+            if settings == self.settings {
+                continue;
+            }
+
+            match Pin::new(&mut self.port).poll_flush(cx) {
+                Poll::Ready(Ok(())) => (),
+                // Output that can't be transmitted doesn't keep the settings from changing
+                Poll::Ready(Err(e)) => {
+                    log::debug!("unable to transmit before reconfiguring: {}", e)
+                }
+                Poll::Pending => {
+                    self.pending = Some(settings);
+                    return Poll::Pending;
+                }
+            }
+            let event = self.switch(settings);
+            if let Some(events) = &self.events {
+                let _ = events.unbounded_send(event);
+            }
+        }
+    }
+
+    fn switch(&mut self, settings: PortSettings) -> ReloadEvent {
+        if settings.path != self.settings.path {
+            return match settings.open() {
+                Ok(port) => {
+                    self.port = port;
+                    let before = std::mem::replace(&mut self.settings, settings.clone());
+                    ReloadEvent::Reopened {
+                        before,
+                        after: settings,
+                    }
+                }
+                Err(error) => ReloadEvent::Failed { settings, error },
+            };
+        }
+
+        let reconfigured = SerialConfig::read(&self.port).and_then(|before| {
+            let diff = before.diff(&settings.config);
+            diff.apply_to(&mut self.port)?;
+            Ok((before, diff))
+        });
+        match reconfigured {
+            Ok((before, diff)) => {
+                self.settings = settings;
+                ReloadEvent::Reconfigured {
+                    before,
+                    after: self.settings.config,
+                    diff,
+                }
+            }
+            Err(error) => ReloadEvent::Failed { settings, error },
+        }
+    }
+}
+
+impl AsyncRead for ManagedPort {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_reload(cx));
+        Pin::new(&mut this.port).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ManagedPort {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_reload(cx));
+        Pin::new(&mut this.port).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_reload(cx));
+        Pin::new(&mut this.port).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().port).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,714 @@
+//! Remote serial ports using the Telnet COM port control option (RFC 2217)
+//!
+//! [RFC 2217](https://datatracker.ietf.org/doc/html/rfc2217) extends the Telnet protocol with
+//! a COM-PORT-OPTION used to change line settings and control lines of a serial port exported
+//! by a network device server (e.g. `ser2net`, Moxa NPort, Lantronix, ...).
+//!
+//! [`Client`] implements the same `AsyncRead`, `AsyncWrite` and [`SerialPort`] surface as
+//! [`SerialStream`](crate::SerialStream) so applications can switch between local and remote
+//! ports without changes.  [`Server`] goes the other way and exports a local port to RFC 2217
+//! clients, much like `ser2net`.
+//!
+//! ```no_run
+//! use tokio::io::AsyncWriteExt;
+//! use tokio_serial::{SerialPort, SerialStream};
+//!
+//! #[tokio::main]
+//! async fn main() -> tokio_serial::Result<()> {
+//!     let builder = tokio_serial::new("", 115200);
+//!     let mut port = SerialStream::open_rfc2217("192.168.1.10:2217", &builder).await?;
+//!     port.write_data_terminal_ready(true)?;
+//!     port.write_all(b"AT\r").await?;
+//!     Ok(())
+//! }
+//! ```
+use crate::settings::Settings;
+use crate::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use futures::future::poll_fn;
+use futures::ready;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+use std::io::{self, Read, Result as IoResult, Write};
+use std::mem;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+mod server;
+
+pub use server::{BridgeStats, DirectionStats, Server};
+
+// Telnet commands
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+// Telnet options
+const BINARY: u8 = 0;
+const SGA: u8 = 3;
+const COM_PORT_OPTION: u8 = 44;
+
+// COM-PORT-OPTION commands, client to server.  Server responses add `SERVER_OFFSET`.
+const SIGNATURE: u8 = 0;
+const SET_BAUDRATE: u8 = 1;
+const SET_DATASIZE: u8 = 2;
+const SET_PARITY: u8 = 3;
+const SET_STOPSIZE: u8 = 4;
+const SET_CONTROL: u8 = 5;
+const NOTIFY_LINESTATE: u8 = 6;
+const NOTIFY_MODEMSTATE: u8 = 7;
+const FLOWCONTROL_SUSPEND: u8 = 8;
+const FLOWCONTROL_RESUME: u8 = 9;
+const SET_LINESTATE_MASK: u8 = 10;
+const SET_MODEMSTATE_MASK: u8 = 11;
+const PURGE_DATA: u8 = 12;
+const SERVER_OFFSET: u8 = 100;
+
+// SET-CONTROL values
+const CONTROL_FLOW_REQUEST: u8 = 0;
+const CONTROL_FLOW_NONE: u8 = 1;
+const CONTROL_FLOW_XONXOFF: u8 = 2;
+const CONTROL_FLOW_HARDWARE: u8 = 3;
+const CONTROL_BREAK_REQUEST: u8 = 4;
+const CONTROL_BREAK_ON: u8 = 5;
+const CONTROL_BREAK_OFF: u8 = 6;
+const CONTROL_DTR_REQUEST: u8 = 7;
+const CONTROL_DTR_ON: u8 = 8;
+const CONTROL_DTR_OFF: u8 = 9;
+const CONTROL_RTS_REQUEST: u8 = 10;
+const CONTROL_RTS_ON: u8 = 11;
+const CONTROL_RTS_OFF: u8 = 12;
+
+// PURGE-DATA values
+const PURGE_RX: u8 = 1;
+const PURGE_TX: u8 = 2;
+const PURGE_BOTH: u8 = 3;
+
+// NOTIFY-MODEMSTATE bits
+const MODEM_DELTA_CTS: u8 = 0x01;
+const MODEM_DELTA_DSR: u8 = 0x02;
+const MODEM_TRAILING_RI: u8 = 0x04;
+const MODEM_DELTA_CD: u8 = 0x08;
+const MODEM_CTS: u8 = 0x10;
+const MODEM_DSR: u8 = 0x20;
+const MODEM_RI: u8 = 0x40;
+const MODEM_CD: u8 = 0x80;
+
+/// Largest chunk of raw telnet data read from the socket at once
+const READ_CHUNK: usize = 4096;
+
+/// Telnet protocol elements other than plain data
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    /// `IAC <verb> <option>` option negotiation
+    Negotiate(u8, u8),
+    /// Payload of an `IAC SB ... IAC SE` sub-negotiation, starting with the option
+    Subnegotiation(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    Data,
+    Iac,
+    Verb(u8),
+    Sub,
+    SubIac,
+}
+
+/// Incremental telnet stream decoder
+#[derive(Debug)]
+struct Parser {
+    state: ParseState,
+    sub: Vec<u8>,
+}
+
+impl Parser {
+    fn new() -> Self {
+        Self {
+            state: ParseState::Data,
+            sub: Vec::new(),
+        }
+    }
+
+    /// Decode `src`, writing plain data into `data` and protocol elements into `events`.
+    ///
+    /// `data` must be at least as long as `src`.  Returns the number of data bytes written.
+    fn decode(&mut self, src: &[u8], data: &mut [u8], events: &mut Vec<Event>) -> usize {
+        let mut n = 0;
+        for &byte in src {
+            self.state = match self.state {
+                ParseState::Data if byte == IAC => ParseState::Iac,
+                ParseState::Data => {
+                    data[n] = byte;
+                    n += 1;
+                    ParseState::Data
+                }
+                ParseState::Iac => match byte {
+                    IAC => {
+                        data[n] = IAC;
+                        n += 1;
+                        ParseState::Data
+                    }
+                    WILL | WONT | DO | DONT => ParseState::Verb(byte),
+                    SB => {
+                        self.sub.clear();
+                        ParseState::Sub
+                    }
+                    // NOP, GA, AYT and friends carry no meaning for a serial link
+                    _ => ParseState::Data,
+                },
+                ParseState::Verb(verb) => {
+                    events.push(Event::Negotiate(verb, byte));
+                    ParseState::Data
+                }
+                ParseState::Sub if byte == IAC => ParseState::SubIac,
+                ParseState::Sub => {
+                    self.sub.push(byte);
+                    ParseState::Sub
+                }
+                ParseState::SubIac => match byte {
+                    SE => {
+                        events.push(Event::Subnegotiation(mem::take(&mut self.sub)));
+                        ParseState::Data
+                    }
+                    _ => {
+                        self.sub.push(byte);
+                        ParseState::Sub
+                    }
+                },
+            };
+        }
+        n
+    }
+}
+
+/// Append `src` to `dst`, doubling any `IAC` bytes
+fn escape(src: &[u8], dst: &mut Vec<u8>) {
+    dst.reserve(src.len());
+    for &byte in src {
+        if byte == IAC {
+            dst.push(IAC);
+        }
+        dst.push(byte);
+    }
+}
+
+/// Append an `IAC <verb> <option>` negotiation to `dst`
+fn negotiate(verb: u8, option: u8, dst: &mut Vec<u8>) {
+    dst.extend_from_slice(&[IAC, verb, option]);
+}
+
+/// Append a COM-PORT-OPTION sub-negotiation to `dst`
+fn com_port_command(command: u8, payload: &[u8], dst: &mut Vec<u8>) {
+    dst.extend_from_slice(&[IAC, SB, COM_PORT_OPTION, command]);
+    escape(payload, dst);
+    dst.extend_from_slice(&[IAC, SE]);
+}
+
+fn encode_data_bits(data_bits: DataBits) -> u8 {
+    match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    }
+}
+
+fn decode_data_bits(value: u8) -> Option<DataBits> {
+    match value {
+        5 => Some(DataBits::Five),
+        6 => Some(DataBits::Six),
+        7 => Some(DataBits::Seven),
+        8 => Some(DataBits::Eight),
+        _ => None,
+    }
+}
+
+fn encode_parity(parity: Parity) -> u8 {
+    match parity {
+        Parity::None => 1,
+        Parity::Odd => 2,
+        Parity::Even => 3,
+    }
+}
+
+fn decode_parity(value: u8) -> Option<Parity> {
+    match value {
+        1 => Some(Parity::None),
+        2 => Some(Parity::Odd),
+        3 => Some(Parity::Even),
+        _ => None,
+    }
+}
+
+fn encode_stop_bits(stop_bits: StopBits) -> u8 {
+    match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    }
+}
+
+fn decode_stop_bits(value: u8) -> Option<StopBits> {
+    match value {
+        1 => Some(StopBits::One),
+        2 => Some(StopBits::Two),
+        _ => None,
+    }
+}
+
+fn encode_flow_control(flow_control: FlowControl) -> u8 {
+    match flow_control {
+        FlowControl::None => CONTROL_FLOW_NONE,
+        FlowControl::Software => CONTROL_FLOW_XONXOFF,
+        FlowControl::Hardware => CONTROL_FLOW_HARDWARE,
+    }
+}
+
+fn decode_flow_control(value: u8) -> Option<FlowControl> {
+    match value {
+        CONTROL_FLOW_NONE => Some(FlowControl::None),
+        CONTROL_FLOW_XONXOFF => Some(FlowControl::Software),
+        CONTROL_FLOW_HARDWARE => Some(FlowControl::Hardware),
+        _ => None,
+    }
+}
+
+/// Async RFC 2217 client
+///
+/// Settings and control line changes made through the [`SerialPort`] trait are sent to the
+/// server immediately when the socket allows it, otherwise they are queued and sent ahead of
+/// the next write or flush.  Getters report the last value requested or confirmed by the
+/// server, and the modem status lines reflect the most recent NOTIFY-MODEMSTATE received.
+///
+/// Telnet negotiation from the server is processed while reading, so the port should be
+/// read from regularly even if no data is expected.
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream,
+    name: String,
+    parser: Parser,
+    scratch: Vec<u8>,
+    tx: Mutex<Vec<u8>>,
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+    modem_state: u8,
+    line_state: u8,
+}
+
+impl Client {
+    /// Connect to an RFC 2217 server and configure the remote port from `builder`
+    ///
+    /// The path of the builder is ignored.
+    pub async fn connect<A: ToSocketAddrs>(
+        addr: A,
+        builder: &crate::SerialPortBuilder,
+    ) -> crate::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let name = format!("rfc2217://{}", stream.peer_addr()?);
+        let settings = Settings::from_builder(builder);
+
+        let mut tx = Vec::new();
+        negotiate(WILL, BINARY, &mut tx);
+        negotiate(DO, BINARY, &mut tx);
+        negotiate(WILL, SGA, &mut tx);
+        negotiate(DO, SGA, &mut tx);
+        negotiate(WILL, COM_PORT_OPTION, &mut tx);
+        com_port_command(SET_BAUDRATE, &settings.baud_rate.to_be_bytes(), &mut tx);
+        com_port_command(
+            SET_DATASIZE,
+            &[encode_data_bits(settings.data_bits)],
+            &mut tx,
+        );
+        com_port_command(SET_PARITY, &[encode_parity(settings.parity)], &mut tx);
+        com_port_command(
+            SET_STOPSIZE,
+            &[encode_stop_bits(settings.stop_bits)],
+            &mut tx,
+        );
+        com_port_command(
+            SET_CONTROL,
+            &[encode_flow_control(settings.flow_control)],
+            &mut tx,
+        );
+        com_port_command(SET_MODEMSTATE_MASK, &[0xff], &mut tx);
+        match settings.dtr_on_open {
+            Some(true) => com_port_command(SET_CONTROL, &[CONTROL_DTR_ON], &mut tx),
+            Some(false) => com_port_command(SET_CONTROL, &[CONTROL_DTR_OFF], &mut tx),
+            None => {}
+        }
+
+        let client = Self {
+            stream,
+            name,
+            parser: Parser::new(),
+            scratch: vec![0; READ_CHUNK],
+            tx: Mutex::new(tx),
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            flow_control: settings.flow_control,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            modem_state: 0,
+            line_state: 0,
+        };
+
+        poll_fn(|cx| client.poll_drain(cx)).await?;
+        Ok(client)
+    }
+
+    /// Returns the last line state reported by the server through NOTIFY-LINESTATE
+    ///
+    /// See RFC 2217 for the meaning of the individual bits.
+    pub fn line_state(&self) -> u8 {
+        self.line_state
+    }
+
+    /// Returns the last modem state reported by the server through NOTIFY-MODEMSTATE
+    ///
+    /// See RFC 2217 for the meaning of the individual bits.
+    pub fn modem_state(&self) -> u8 {
+        self.modem_state
+    }
+
+    fn tx(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        self.tx.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue a COM-PORT-OPTION command and try to send it right away
+    fn send_command(&self, command: u8, payload: &[u8]) -> crate::Result<()> {
+        com_port_command(command, payload, &mut self.tx());
+        self.try_drain()?;
+        Ok(())
+    }
+
+    /// Write as much of the pending output as possible without blocking
+    fn try_drain(&self) -> IoResult<()> {
+        let mut tx = self.tx();
+        while !tx.is_empty() {
+            match self.stream.try_write(&tx) {
+                Ok(n) => {
+                    tx.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Write all of the pending output
+    fn poll_drain(&self, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let mut tx = self.tx();
+        while !tx.is_empty() {
+            ready!(self.stream.poll_write_ready(cx))?;
+            match self.stream.try_write(&tx) {
+                Ok(n) => {
+                    tx.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Decode raw socket data into `data`, handling any telnet protocol elements on the way
+    fn process(&mut self, raw_len: usize, data: &mut [u8]) -> IoResult<usize> {
+        let mut events = Vec::new();
+        let n = self
+            .parser
+            .decode(&self.scratch[..raw_len], data, &mut events);
+        for event in events {
+            self.handle(event);
+        }
+        self.try_drain()?;
+        Ok(n)
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Negotiate(DO, option) => {
+                if !matches!(option, BINARY | SGA | COM_PORT_OPTION) {
+                    negotiate(WONT, option, &mut self.tx());
+                }
+            }
+            Event::Negotiate(WILL, option) => {
+                if !matches!(option, BINARY | SGA) {
+                    negotiate(DONT, option, &mut self.tx());
+                }
+            }
+            Event::Negotiate(_, _) => {}
+            Event::Subnegotiation(payload) => {
+                if payload.len() < 3 || payload[0] != COM_PORT_OPTION {
+                    return;
+                }
+                let value = &payload[2..];
+                match payload[1].wrapping_sub(SERVER_OFFSET) {
+                    SET_BAUDRATE if value.len() == 4 => {
+                        let baud_rate =
+                            u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+                        if baud_rate != 0 {
+                            self.baud_rate = baud_rate;
+                        }
+                    }
+                    SET_DATASIZE => {
+                        if let Some(data_bits) = decode_data_bits(value[0]) {
+                            self.data_bits = data_bits;
+                        }
+                    }
+                    SET_PARITY => {
+                        if let Some(parity) = decode_parity(value[0]) {
+                            self.parity = parity;
+                        }
+                    }
+                    SET_STOPSIZE => {
+                        if let Some(stop_bits) = decode_stop_bits(value[0]) {
+                            self.stop_bits = stop_bits;
+                        }
+                    }
+                    SET_CONTROL => {
+                        if let Some(flow_control) = decode_flow_control(value[0]) {
+                            self.flow_control = flow_control;
+                        }
+                    }
+                    NOTIFY_LINESTATE => self.line_state = value[0],
+                    NOTIFY_MODEMSTATE => self.modem_state = value[0],
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl AsyncRead for Client {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        let len = buf.remaining().min(READ_CHUNK);
+
+        loop {
+            ready!(this.stream.poll_read_ready(cx))?;
+            let raw_len = match this.stream.try_read(&mut this.scratch[..len]) {
+                Ok(raw_len) => raw_len,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Poll::Ready(Err(e)),
+            };
+
+            // Zero bytes from the socket is EOF, pass it through
+            if raw_len == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            let n = this.process(raw_len, buf.initialize_unfilled_to(raw_len))?;
+            if n > 0 {
+                buf.advance(n);
+                return Poll::Ready(Ok(()));
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Client {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        ready!(self.poll_drain(cx))?;
+        escape(buf, &mut self.tx());
+        // The data is accepted, anything left over goes out on the next write or flush
+        if let Poll::Ready(Err(e)) = self.poll_drain(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        self.poll_drain(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+impl Read for Client {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let len = buf.len().min(READ_CHUNK);
+        loop {
+            let raw_len = self.stream.try_read(&mut self.scratch[..len])?;
+            if raw_len == 0 {
+                return Ok(0);
+            }
+            let n = self.process(raw_len, buf)?;
+            if n > 0 {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+impl Write for Client {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.flush()?;
+        escape(buf, &mut self.tx());
+        self.try_drain()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.try_drain()?;
+        if self.tx().is_empty() {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+}
+
+impl SerialPort for Client {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> crate::Result<u32> {
+        Ok(self.baud_rate)
+    }
+
+    fn data_bits(&self) -> crate::Result<DataBits> {
+        Ok(self.data_bits)
+    }
+
+    fn flow_control(&self) -> crate::Result<FlowControl> {
+        Ok(self.flow_control)
+    }
+
+    fn parity(&self) -> crate::Result<Parity> {
+        Ok(self.parity)
+    }
+
+    fn stop_bits(&self) -> crate::Result<StopBits> {
+        Ok(self.stop_bits)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
+        self.send_command(SET_BAUDRATE, &baud_rate.to_be_bytes())?;
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> crate::Result<()> {
+        self.send_command(SET_DATASIZE, &[encode_data_bits(data_bits)])?;
+        self.data_bits = data_bits;
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> crate::Result<()> {
+        self.send_command(SET_CONTROL, &[encode_flow_control(flow_control)])?;
+        self.flow_control = flow_control;
+        Ok(())
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> crate::Result<()> {
+        self.send_command(SET_PARITY, &[encode_parity(parity)])?;
+        self.parity = parity;
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> crate::Result<()> {
+        self.send_command(SET_STOPSIZE, &[encode_stop_bits(stop_bits)])?;
+        self.stop_bits = stop_bits;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, _: Duration) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
+        let value = if level {
+            CONTROL_RTS_ON
+        } else {
+            CONTROL_RTS_OFF
+        };
+        self.send_command(SET_CONTROL, &[value])
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
+        let value = if level {
+            CONTROL_DTR_ON
+        } else {
+            CONTROL_DTR_OFF
+        };
+        self.send_command(SET_CONTROL, &[value])
+    }
+
+    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+        Ok(self.modem_state & MODEM_CTS != 0)
+    }
+
+    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+        Ok(self.modem_state & MODEM_DSR != 0)
+    }
+
+    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+        Ok(self.modem_state & MODEM_RI != 0)
+    }
+
+    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+        Ok(self.modem_state & MODEM_CD != 0)
+    }
+
+    /// RFC 2217 has no way to query the remote receive buffer, always returns `0`.
+    fn bytes_to_read(&self) -> crate::Result<u32> {
+        Ok(0)
+    }
+
+    /// Returns the number of bytes queued locally that have not yet been sent to the server.
+    fn bytes_to_write(&self) -> crate::Result<u32> {
+        Ok(self.tx().len() as u32)
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> crate::Result<()> {
+        let value = match buffer_to_clear {
+            ClearBuffer::Input => PURGE_RX,
+            ClearBuffer::Output => PURGE_TX,
+            ClearBuffer::All => PURGE_BOTH,
+        };
+        self.send_command(PURGE_DATA, &[value])
+    }
+
+    /// Cloning an RFC 2217 client is not supported.
+    ///
+    /// # Errors
+    /// Always returns `ErrorKind::Other` with a message.
+    fn try_clone(&self) -> crate::Result<Box<dyn SerialPort>> {
+        Err(crate::Error::new(
+            crate::ErrorKind::Io(io::ErrorKind::Other),
+            "Cannot clone Tokio handles",
+        ))
+    }
+
+    fn set_break(&self) -> crate::Result<()> {
+        self.send_command(SET_CONTROL, &[CONTROL_BREAK_ON])
+    }
+
+    fn clear_break(&self) -> crate::Result<()> {
+        self.send_command(SET_CONTROL, &[CONTROL_BREAK_OFF])
+    }
+}
@@ -0,0 +1,530 @@
+//! RFC 2217 server exporting a local serial port over TCP
+use super::*;
+
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+
+/// How often the modem status lines are sampled for NOTIFY-MODEMSTATE
+const MODEM_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pending output to a client is capped at this size before the port is read from again
+const MAX_PENDING: usize = 16 * 1024;
+
+/// Signature reported to clients asking for one
+const SERVER_SIGNATURE: &[u8] = b"tokio-serial";
+
+/// How often the statistics of a session are published
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Traffic through a [`Server`] in one direction, see [`BridgeStats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DirectionStats {
+    /// Data bytes passed on, without the Telnet framing
+    pub bytes: u64,
+    /// Chunks of data passed on, one per read
+    pub frames: u64,
+    /// Sessions ended by an error in this direction
+    pub errors: u64,
+    /// Bytes per second over the last second, zero while no client is connected
+    pub bytes_per_sec: f64,
+}
+
+/// Link health of a [`Server`], published through [`Server::stats`]
+///
+/// The counters add up over every session of the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BridgeStats {
+    /// From the network client to the serial port
+    pub to_port: DirectionStats,
+    /// From the serial port to the network client
+    pub to_network: DirectionStats,
+    /// Clients served so far, the current one included
+    pub sessions: u64,
+    /// `true` while a client is connected
+    pub connected: bool,
+}
+
+/// RFC 2217 server exposing a local serial port to network clients
+///
+/// Clients are served one at a time.  Data is passed through in both directions while
+/// COM-PORT-OPTION commands are applied to the port, and modem status line changes are sent
+/// back as NOTIFY-MODEMSTATE notifications.  Clients that don't negotiate RFC 2217 still get a
+/// plain binary telnet connection to the port.
+///
+/// ```no_run
+/// use tokio_serial::{rfc2217::Server, SerialPortBuilderExt};
+///
+/// #[tokio::main]
+/// async fn main() -> std::io::Result<()> {
+///     let port = tokio_serial::new("/dev/ttyUSB0", 9600).open_native_async()?;
+///     Server::bind("0.0.0.0:2217", port).await?.run().await
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Server<P = crate::SerialStream> {
+    listener: TcpListener,
+    port: P,
+    stats: watch::Sender<BridgeStats>,
+}
+
+impl<P> Server<P>
+where
+    P: AsyncRead + AsyncWrite + SerialPort + Unpin,
+{
+    /// Bind a server to `addr` serving `port`
+    pub async fn bind<A: ToSocketAddrs>(addr: A, port: P) -> IoResult<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let (stats, _) = watch::channel(BridgeStats::default());
+        Ok(Self {
+            listener,
+            port,
+            stats,
+        })
+    }
+
+    /// Returns the local address the server is listening on
+    pub fn local_addr(&self) -> IoResult<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns a receiver of the statistics of the server
+    ///
+    /// They are published every second while a client is connected and when it disconnects,
+    /// so a monitoring task can show the health of the link without touching the data path.
+    pub fn stats(&self) -> watch::Receiver<BridgeStats> {
+        self.stats.subscribe()
+    }
+
+    /// Returns a reference to the served port
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the served port
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.port
+    }
+
+    /// Consumes the server, returning the served port
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    /// Accept and serve clients, one at a time, until an error occurs on the port
+    ///
+    /// Network errors only end the session with the affected client.
+    pub async fn run(mut self) -> IoResult<()> {
+        loop {
+            self.serve_one().await?;
+        }
+    }
+
+    /// Accept and serve a single client until it disconnects
+    ///
+    /// Errors on the port are returned, network errors are logged and end the session.
+    pub async fn serve_one(&mut self) -> IoResult<()> {
+        let (socket, peer) = self.listener.accept().await?;
+        log::debug!("rfc2217 client connected: {}", peer);
+        socket.set_nodelay(true)?;
+
+        let mut stats = *self.stats.borrow();
+        stats.sessions += 1;
+        stats.connected = true;
+        self.stats.send_replace(stats);
+
+        let mut session = Session::new(socket, &mut self.port, &self.stats);
+        let result = poll_fn(|cx| session.poll(cx)).await;
+        let mut stats = session.stats;
+        stats.connected = false;
+        stats.to_port.bytes_per_sec = 0.0;
+        stats.to_network.bytes_per_sec = 0.0;
+        let result = match result {
+            Ok(()) => {
+                log::debug!("rfc2217 client disconnected: {}", peer);
+                Ok(())
+            }
+            Err(SessionError::Network(direction, e)) => {
+                stats.direction(direction).errors += 1;
+                log::debug!("rfc2217 client {} dropped: {}", peer, e);
+                Ok(())
+            }
+            Err(SessionError::Port(direction, e)) => {
+                stats.direction(direction).errors += 1;
+                Err(e)
+            }
+        };
+        self.stats.send_replace(stats);
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    ToPort,
+    ToNetwork,
+}
+
+impl BridgeStats {
+    fn direction(&mut self, direction: Direction) -> &mut DirectionStats {
+        match direction {
+            Direction::ToPort => &mut self.to_port,
+            Direction::ToNetwork => &mut self.to_network,
+        }
+    }
+}
+
+/// Why a session ended, and in which direction the data was going
+#[derive(Debug)]
+enum SessionError {
+    Network(Direction, io::Error),
+    Port(Direction, io::Error),
+}
+
+/// A single client connection
+struct Session<'a, P> {
+    socket: TcpStream,
+    port: &'a mut P,
+    parser: Parser,
+    to_port: Vec<u8>,
+    to_socket: Vec<u8>,
+    net_buf: Vec<u8>,
+    port_buf: Vec<u8>,
+    modem_poll: Interval,
+    modem_mask: u8,
+    modem_state: Option<u8>,
+    line_mask: u8,
+    suspended: bool,
+    break_state: bool,
+    dtr: bool,
+    rts: bool,
+    stats: BridgeStats,
+    stats_tx: &'a watch::Sender<BridgeStats>,
+    stats_poll: Interval,
+    // Time and byte counts of the last publication, for the throughput
+    published: (Instant, u64, u64),
+}
+
+impl<'a, P> Session<'a, P>
+where
+    P: AsyncRead + AsyncWrite + SerialPort + Unpin,
+{
+    fn new(socket: TcpStream, port: &'a mut P, stats_tx: &'a watch::Sender<BridgeStats>) -> Self {
+        let mut to_socket = Vec::new();
+        negotiate(WILL, BINARY, &mut to_socket);
+        negotiate(DO, BINARY, &mut to_socket);
+        negotiate(WILL, SGA, &mut to_socket);
+        negotiate(DO, SGA, &mut to_socket);
+        negotiate(DO, COM_PORT_OPTION, &mut to_socket);
+
+        let mut modem_poll = time::interval(MODEM_POLL_INTERVAL);
+        modem_poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let start = Instant::now();
+        let mut stats_poll = time::interval_at(start + STATS_INTERVAL, STATS_INTERVAL);
+        stats_poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let stats = *stats_tx.borrow();
+
+        Self {
+            socket,
+            port,
+            parser: Parser::new(),
+            to_port: Vec::new(),
+            to_socket,
+            net_buf: vec![0; READ_CHUNK],
+            port_buf: vec![0; READ_CHUNK],
+            modem_poll,
+            modem_mask: 0xff,
+            modem_state: None,
+            line_mask: 0,
+            suspended: false,
+            break_state: false,
+            dtr: true,
+            rts: true,
+            stats,
+            stats_tx,
+            stats_poll,
+            published: (start, stats.to_port.bytes, stats.to_network.bytes),
+        }
+    }
+
+    fn publish_stats(&mut self) {
+        let now = Instant::now();
+        let (then, to_port, to_network) = self.published;
+        let elapsed = now.saturating_duration_since(then).as_secs_f64();
+        if elapsed > 0.0 {
+            self.stats.to_port.bytes_per_sec =
+                (self.stats.to_port.bytes - to_port) as f64 / elapsed;
+            self.stats.to_network.bytes_per_sec =
+                (self.stats.to_network.bytes - to_network) as f64 / elapsed;
+        }
+        self.published = (now, self.stats.to_port.bytes, self.stats.to_network.bytes);
+        self.stats_tx.send_replace(self.stats);
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SessionError>> {
+        loop {
+            let mut progress = false;
+
+            // Network to port
+            if self.to_port.is_empty() {
+                let mut buf = ReadBuf::new(&mut self.net_buf);
+                if let Poll::Ready(result) = Pin::new(&mut self.socket).poll_read(cx, &mut buf) {
+                    result.map_err(|e| SessionError::Network(Direction::ToPort, e))?;
+                    let len = buf.filled().len();
+                    if len == 0 {
+                        return Poll::Ready(Ok(()));
+                    }
+                    let mut events = Vec::new();
+                    self.to_port.resize(len, 0);
+                    let n =
+                        self.parser
+                            .decode(&self.net_buf[..len], &mut self.to_port, &mut events);
+                    self.to_port.truncate(n);
+                    if n > 0 {
+                        self.stats.to_port.bytes += n as u64;
+                        self.stats.to_port.frames += 1;
+                    }
+                    for event in events {
+                        self.handle(event)
+                            .map_err(|e| SessionError::Port(Direction::ToPort, e))?;
+                    }
+                    progress = true;
+                }
+            }
+            if !self.to_port.is_empty() {
+                if let Poll::Ready(result) = Pin::new(&mut *self.port).poll_write(cx, &self.to_port)
+                {
+                    let n = result.map_err(|e| SessionError::Port(Direction::ToPort, e))?;
+                    self.to_port.drain(..n);
+                    progress = true;
+                }
+            }
+
+            // Port to network
+            if !self.suspended && self.to_socket.len() < MAX_PENDING {
+                let mut buf = ReadBuf::new(&mut self.port_buf);
+                if let Poll::Ready(result) = Pin::new(&mut *self.port).poll_read(cx, &mut buf) {
+                    result.map_err(|e| SessionError::Port(Direction::ToNetwork, e))?;
+                    if buf.filled().is_empty() {
+                        return Poll::Ready(Err(SessionError::Port(
+                            Direction::ToNetwork,
+                            io::ErrorKind::UnexpectedEof.into(),
+                        )));
+                    }
+                    self.stats.to_network.bytes += buf.filled().len() as u64;
+                    self.stats.to_network.frames += 1;
+                    escape(buf.filled(), &mut self.to_socket);
+                    progress = true;
+                }
+            }
+            if !self.to_socket.is_empty() {
+                if let Poll::Ready(result) =
+                    Pin::new(&mut self.socket).poll_write(cx, &self.to_socket)
+                {
+                    let n = result.map_err(|e| SessionError::Network(Direction::ToNetwork, e))?;
+                    self.to_socket.drain(..n);
+                    progress = true;
+                }
+            }
+
+            // Modem status lines
+            if self.modem_poll.poll_tick(cx).is_ready() {
+                self.notify_modem_state()
+                    .map_err(|e| SessionError::Port(Direction::ToNetwork, e.into()))?;
+                progress = true;
+            }
+
+            if self.stats_poll.poll_tick(cx).is_ready() {
+                self.publish_stats();
+                progress = true;
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    fn reply(&mut self, command: u8, value: &[u8]) {
+        com_port_command(command + SERVER_OFFSET, value, &mut self.to_socket);
+    }
+
+    fn notify_modem_state(&mut self) -> crate::Result<()> {
+        let mut state = 0;
+        if self.port.read_clear_to_send()? {
+            state |= MODEM_CTS;
+        }
+        if self.port.read_data_set_ready()? {
+            state |= MODEM_DSR;
+        }
+        if self.port.read_ring_indicator()? {
+            state |= MODEM_RI;
+        }
+        if self.port.read_carrier_detect()? {
+            state |= MODEM_CD;
+        }
+
+        let previous = self.modem_state.replace(state);
+        let changed = previous.map(|previous| previous ^ state).unwrap_or(0xf0);
+        if changed == 0 {
+            return Ok(());
+        }
+
+        let mut deltas = 0;
+        if changed & MODEM_CTS != 0 {
+            deltas |= MODEM_DELTA_CTS;
+        }
+        if changed & MODEM_DSR != 0 {
+            deltas |= MODEM_DELTA_DSR;
+        }
+        if changed & MODEM_RI != 0 && state & MODEM_RI == 0 {
+            deltas |= MODEM_TRAILING_RI;
+        }
+        if changed & MODEM_CD != 0 {
+            deltas |= MODEM_DELTA_CD;
+        }
+
+        let notification = (state | deltas) & self.modem_mask;
+        if notification != 0 || previous.is_none() {
+            self.reply(NOTIFY_MODEMSTATE, &[notification]);
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, event: Event) -> IoResult<()> {
+        match event {
+            Event::Negotiate(DO, option) => {
+                if !matches!(option, BINARY | SGA) {
+                    negotiate(WONT, option, &mut self.to_socket);
+                }
+            }
+            Event::Negotiate(WILL, option) => {
+                if !matches!(option, BINARY | SGA | COM_PORT_OPTION) {
+                    negotiate(DONT, option, &mut self.to_socket);
+                }
+            }
+            Event::Negotiate(_, _) => {}
+            Event::Subnegotiation(payload) => {
+                if payload.len() >= 2 && payload[0] == COM_PORT_OPTION {
+                    self.handle_command(payload[1], &payload[2..])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_command(&mut self, command: u8, value: &[u8]) -> crate::Result<()> {
+        match command {
+            SIGNATURE => {
+                if value.is_empty() {
+                    self.reply(SIGNATURE, SERVER_SIGNATURE);
+                } else {
+                    log::debug!(
+                        "rfc2217 client signature: {}",
+                        String::from_utf8_lossy(value)
+                    );
+                }
+            }
+            SET_BAUDRATE if value.len() == 4 => {
+                let baud_rate = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+                if baud_rate != 0 {
+                    self.port.set_baud_rate(baud_rate)?;
+                }
+                let current = self.port.baud_rate()?;
+                self.reply(SET_BAUDRATE, &current.to_be_bytes());
+            }
+            SET_DATASIZE if !value.is_empty() => {
+                if let Some(data_bits) = decode_data_bits(value[0]) {
+                    self.port.set_data_bits(data_bits)?;
+                }
+                let current = encode_data_bits(self.port.data_bits()?);
+                self.reply(SET_DATASIZE, &[current]);
+            }
+            SET_PARITY if !value.is_empty() => {
+                if let Some(parity) = decode_parity(value[0]) {
+                    self.port.set_parity(parity)?;
+                }
+                let current = encode_parity(self.port.parity()?);
+                self.reply(SET_PARITY, &[current]);
+            }
+            SET_STOPSIZE if !value.is_empty() => {
+                if let Some(stop_bits) = decode_stop_bits(value[0]) {
+                    self.port.set_stop_bits(stop_bits)?;
+                }
+                let current = encode_stop_bits(self.port.stop_bits()?);
+                self.reply(SET_STOPSIZE, &[current]);
+            }
+            SET_CONTROL if !value.is_empty() => {
+                let reply = match value[0] {
+                    CONTROL_FLOW_REQUEST => encode_flow_control(self.port.flow_control()?),
+                    CONTROL_FLOW_NONE | CONTROL_FLOW_XONXOFF | CONTROL_FLOW_HARDWARE => {
+                        if let Some(flow_control) = decode_flow_control(value[0]) {
+                            self.port.set_flow_control(flow_control)?;
+                        }
+                        encode_flow_control(self.port.flow_control()?)
+                    }
+                    CONTROL_BREAK_ON => {
+                        self.port.set_break()?;
+                        self.break_state = true;
+                        CONTROL_BREAK_ON
+                    }
+                    CONTROL_BREAK_OFF => {
+                        self.port.clear_break()?;
+                        self.break_state = false;
+                        CONTROL_BREAK_OFF
+                    }
+                    CONTROL_BREAK_REQUEST if self.break_state => CONTROL_BREAK_ON,
+                    CONTROL_BREAK_REQUEST => CONTROL_BREAK_OFF,
+                    CONTROL_DTR_ON | CONTROL_DTR_OFF => {
+                        self.dtr = value[0] == CONTROL_DTR_ON;
+                        self.port.write_data_terminal_ready(self.dtr)?;
+                        value[0]
+                    }
+                    CONTROL_DTR_REQUEST if self.dtr => CONTROL_DTR_ON,
+                    CONTROL_DTR_REQUEST => CONTROL_DTR_OFF,
+                    CONTROL_RTS_ON | CONTROL_RTS_OFF => {
+                        self.rts = value[0] == CONTROL_RTS_ON;
+                        self.port.write_request_to_send(self.rts)?;
+                        value[0]
+                    }
+                    CONTROL_RTS_REQUEST if self.rts => CONTROL_RTS_ON,
+                    CONTROL_RTS_REQUEST => CONTROL_RTS_OFF,
+                    // Inbound flow control and DCD/DSR flow control are not supported
+                    _ => encode_flow_control(self.port.flow_control()?),
+                };
+                self.reply(SET_CONTROL, &[reply]);
+            }
+            FLOWCONTROL_SUSPEND => {
+                self.suspended = true;
+                self.reply(FLOWCONTROL_SUSPEND, &[]);
+            }
+            FLOWCONTROL_RESUME => {
+                self.suspended = false;
+                self.reply(FLOWCONTROL_RESUME, &[]);
+            }
+            SET_LINESTATE_MASK if !value.is_empty() => {
+                self.line_mask = value[0];
+                self.reply(SET_LINESTATE_MASK, &[self.line_mask]);
+            }
+            SET_MODEMSTATE_MASK if !value.is_empty() => {
+                self.modem_mask = value[0];
+                self.reply(SET_MODEMSTATE_MASK, &[self.modem_mask]);
+            }
+            PURGE_DATA if !value.is_empty() => {
+                let buffer = match value[0] {
+                    PURGE_RX => Some(ClearBuffer::Input),
+                    PURGE_TX => Some(ClearBuffer::Output),
+                    PURGE_BOTH => Some(ClearBuffer::All),
+                    _ => None,
+                };
+                if let Some(buffer) = buffer {
+                    self.port.clear(buffer)?;
+                }
+                self.reply(PURGE_DATA, &[value[0]]);
+            }
+            _ => log::debug!("ignoring unsupported rfc2217 command {}", command),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,442 @@
+//! Iridium Short Burst Data modems
+//!
+//! Iridium 9602 and 9603 modems, and the boards built around them like the RockBLOCK, send
+//! and receive short messages over the satellite network with AT commands.  A message to
+//! send, mobile originated (MO), is written to the modem's MO buffer, then an SBD session
+//! with the satellite transfers it and brings back the next mobile terminated (MT) message
+//! waiting at the gateway, if any, into the MT buffer to read.
+//!
+//! [`SbdModem`] runs these steps: [`write_mo`](SbdModem::write_mo) writes a binary message
+//! with its checksum, [`session`](SbdModem::session) runs `AT+SBDIX` and parses its
+//! [`SessionStatus`], [`read_mt`](SbdModem::read_mt) reads the binary message received.
+//! [`exchange`](SbdModem::exchange) does the three.
+//!
+//! ```no_run
+//! use tokio_serial::sbd::SbdModem;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut modem = SbdModem::new(port);
+//! modem.init().await?;
+//! loop {
+//!     let (status, received) = modem.exchange(b"\x01\x17\x2a").await?;
+//!     if let Some(message) = received {
+//!         println!("received {:02x?}", message);
+//!     }
+//!     if status.mo_sent() {
+//!         break;
+//!     }
+//!     // No satellite in view, try again later
+//!     tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use futures::future::poll_fn;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{timeout_at, Instant};
+
+use std::convert::TryFrom;
+use std::io;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Time the modem is given to answer a command, by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time an SBD session is given to complete, by default
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Longest mobile originated message of the 9602 and 9603
+pub const MAX_MO_LEN: usize = 340;
+
+/// Longest mobile terminated message of the 9602 and 9603
+pub const MAX_MT_LEN: usize = 270;
+
+/// Compute the SBD checksum of `message`, the low 16 bits of the sum of its bytes
+pub fn checksum(message: &[u8]) -> u16 {
+    message
+        .iter()
+        .fold(0u16, |sum, &b| sum.wrapping_add(u16::from(b)))
+}
+
+/// Outcome of an SBD session, as reported by `+SBDIX`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionStatus {
+    /// Disposition of the MO message, 0 to 4 when it was sent
+    pub mo_status: u8,
+    /// Sequence number of the MO message
+    pub momsn: u16,
+    /// 0 if there was no MT message, 1 if one was received, 2 if checking failed
+    pub mt_status: u8,
+    /// Sequence number of the MT message
+    pub mtmsn: u16,
+    /// Length of the MT message received
+    pub mt_length: u16,
+    /// MT messages still waiting at the gateway
+    pub mt_queued: u16,
+}
+
+impl SessionStatus {
+    /// Parse the `+SBDIX: ...` line
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields = line.strip_prefix("+SBDIX:")?;
+        let mut fields = fields.split(',').map(|field| field.trim().parse::<u16>());
+        let mut next = || fields.next()?.ok();
+        let status = Self {
+            mo_status: u8::try_from(next()?).ok()?,
+            momsn: next()?,
+            mt_status: u8::try_from(next()?).ok()?,
+            mtmsn: next()?,
+            mt_length: next()?,
+            mt_queued: next()?,
+        };
+        Some(status)
+    }
+
+    /// Returns `true` if the MO message was transferred
+    pub fn mo_sent(&self) -> bool {
+        self.mo_status <= 4
+    }
+
+    /// Returns `true` if an MT message was received into the MT buffer
+    pub fn mt_received(&self) -> bool {
+        self.mt_status == 1
+    }
+}
+
+/// Buffers of the modem to clear
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffer {
+    /// The mobile originated buffer
+    Mo,
+    /// The mobile terminated buffer
+    Mt,
+    /// Both buffers
+    Both,
+}
+
+/// Runs the SBD workflow of an Iridium modem, see the [module](self) docs
+#[derive(Debug)]
+pub struct SbdModem<P> {
+    port: P,
+    buf: Vec<u8>,
+    timeout: Duration,
+    session_timeout: Duration,
+}
+
+impl<P: AsyncRead + AsyncWrite + Unpin> SbdModem<P> {
+    /// Drive the modem behind `port`, 19200 baud for the 9602 and 9603
+    pub fn new(port: P) -> Self {
+        Self {
+            port,
+            buf: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+        }
+    }
+
+    /// Wait up to `timeout` for the answer to a command, [`DEFAULT_TIMEOUT`] by default
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the time the modem is given to answer a command
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Wait up to `timeout` for an SBD session, [`DEFAULT_SESSION_TIMEOUT`] by default
+    pub fn set_session_timeout(&mut self, timeout: Duration) {
+        self.session_timeout = timeout;
+    }
+
+    /// Returns the time an SBD session is given
+    pub fn session_timeout(&self) -> Duration {
+        self.session_timeout
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Consumes the helper, returning the port
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+
+    /// Check that the modem answers and turn off its echo and flow control
+    ///
+    /// Flow control is off for boards like the RockBLOCK that don't wire RTS and CTS.
+    ///
+    /// ## Errors
+    ///
+    /// * `Other` if the modem answered `ERROR`.
+    /// * `TimedOut` if it didn't answer, and the errors of the port.
+    pub async fn init(&mut self) -> io::Result<()> {
+        self.command("AT", self.timeout).await?;
+        self.command("ATE0", self.timeout).await?;
+        self.command("AT&K0", self.timeout).await?;
+        Ok(())
+    }
+
+    /// Send the command `command`, without its `\r`, and return the lines answered before
+    /// `OK`
+    ///
+    /// ## Errors
+    ///
+    /// As for [`init`](Self::init).
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn command(&mut self, command: &str, timeout: Duration) -> io::Result<Vec<String>> {
+        self.write_all(format!("{}\r", command).as_bytes()).await?;
+        let deadline = Instant::now() + timeout;
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line(deadline).await?;
+            match line.as_str() {
+                "OK" => return Ok(lines),
+                "ERROR" => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("modem answered ERROR to {}", command),
+                    ))
+                }
+                echo if echo == command => {}
+                _ => lines.push(line),
+            }
+        }
+    }
+
+    /// Returns the signal strength from 0 to 5 bars
+    ///
+    /// ## Errors
+    ///
+    /// As for [`init`](Self::init), and `InvalidData` for an unexpected answer.  Taking the
+    /// measure may take several seconds.
+    pub async fn signal_quality(&mut self) -> io::Result<u8> {
+        let lines = self.command("AT+CSQ", self.session_timeout).await?;
+        lines
+            .iter()
+            .find_map(|line| line.strip_prefix("+CSQ:")?.trim().parse().ok())
+            .ok_or_else(|| invalid("unexpected answer to AT+CSQ"))
+    }
+
+    /// Write `message` to the MO buffer
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if `message` is empty or longer than [`MAX_MO_LEN`].
+    /// * `InvalidData` if the modem reported a checksum mismatch, and the errors of
+    ///   [`init`](Self::init).
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn write_mo(&mut self, message: &[u8]) -> io::Result<()> {
+        if message.is_empty() || message.len() > MAX_MO_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SBD messages are 1 to 340 bytes",
+            ));
+        }
+        let command = format!("AT+SBDWB={}", message.len());
+        self.write_all(format!("{}\r", command).as_bytes()).await?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match self.read_line(deadline).await?.as_str() {
+                "READY" => break,
+                "ERROR" => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "modem answered ERROR to AT+SBDWB",
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        let mut data = message.to_vec();
+        data.extend_from_slice(&checksum(message).to_be_bytes());
+        self.write_all(&data).await?;
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            let line = self.read_line(deadline).await?;
+            if let Ok(status) = line.parse::<u8>() {
+                break status;
+            }
+        };
+        while self.read_line(deadline).await? != "OK" {}
+        match status {
+            0 => Ok(()),
+            1 => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "modem timed out receiving the message",
+            )),
+            2 => Err(invalid("modem reported an SBD checksum mismatch")),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "modem refused the SBD message size",
+            )),
+        }
+    }
+
+    /// Run an SBD session, sending the MO buffer and receiving the next MT message
+    ///
+    /// A failed transfer, for lack of satellite in view for instance, is reported in the
+    /// status and not as an error.
+    ///
+    /// ## Errors
+    ///
+    /// As for [`init`](Self::init), and `InvalidData` for an unexpected answer.
+    pub async fn session(&mut self) -> io::Result<SessionStatus> {
+        let lines = self.command("AT+SBDIX", self.session_timeout).await?;
+        lines
+            .iter()
+            .find_map(|line| SessionStatus::parse(line))
+            .ok_or_else(|| invalid("unexpected answer to AT+SBDIX"))
+    }
+
+    /// Read the message in the MT buffer
+    ///
+    /// ## Errors
+    ///
+    /// As for [`init`](Self::init), and `InvalidData` if the message fails its checksum.
+    pub async fn read_mt(&mut self) -> io::Result<Vec<u8>> {
+        self.write_all(b"AT+SBDRB\r").await?;
+        let deadline = Instant::now() + self.timeout;
+        // A blank line from the previous answer, or the echo of the command.  The lengths
+        // they would make are past the longest message.
+        loop {
+            self.fill(2, deadline).await?;
+            match self.buf[0] {
+                b'\r' | b'\n' => {
+                    self.buf.remove(0);
+                }
+                b'A' => {
+                    let line = self.read_line(deadline).await?;
+                    if line != "AT+SBDRB" {
+                        return Err(invalid("unexpected answer to AT+SBDRB"));
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let len = usize::from(u16::from_be_bytes([self.buf[0], self.buf[1]]));
+        if len > MAX_MT_LEN {
+            return Err(invalid("unexpected answer to AT+SBDRB"));
+        }
+        self.fill(2 + len + 2, deadline).await?;
+        let data: Vec<u8> = self.buf.drain(..2 + len + 2).collect();
+        let message = data[2..2 + len].to_vec();
+        while self.read_line(deadline).await? != "OK" {}
+        if checksum(&message).to_be_bytes() != data[2 + len..] {
+            return Err(invalid("SBD message checksum mismatch"));
+        }
+        Ok(message)
+    }
+
+    /// Clear the MO buffer, the MT buffer or both
+    ///
+    /// ## Errors
+    ///
+    /// As for [`init`](Self::init).
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn clear(&mut self, buffer: Buffer) -> io::Result<()> {
+        let command = match buffer {
+            Buffer::Mo => "AT+SBDD0",
+            Buffer::Mt => "AT+SBDD1",
+            Buffer::Both => "AT+SBDD2",
+        };
+        let lines = self.command(command, self.timeout).await?;
+        match lines.first().map(String::as_str) {
+            Some("0") => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "modem failed to clear its buffer",
+            )),
+        }
+    }
+
+    /// Write `message` to the MO buffer, run a session and read the MT message it brought
+    ///
+    /// ## Errors
+    ///
+    /// As for [`write_mo`](Self::write_mo), [`session`](Self::session) and
+    /// [`read_mt`](Self::read_mt).
+    pub async fn exchange(
+        &mut self,
+        message: &[u8],
+    ) -> io::Result<(SessionStatus, Option<Vec<u8>>)> {
+        self.write_mo(message).await?;
+        let status = self.session().await?;
+        let received = match status.mt_received() {
+            true => Some(self.read_mt().await?),
+            false => None,
+        };
+        Ok((status, received))
+    }
+
+    async fn write_all(&mut self, mut data: &[u8]) -> io::Result<()> {
+        // Answers to anything before are stale
+        self.buf.clear();
+        while !data.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut self.port).poll_write(cx, data)).await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            data = &data[n..];
+        }
+        poll_fn(|cx| Pin::new(&mut self.port).poll_flush(cx)).await
+    }
+
+    /// Read until the buffer holds `len` bytes
+    async fn fill(&mut self, len: usize, deadline: Instant) -> io::Result<()> {
+        while self.buf.len() < len {
+            self.read_more(deadline).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the next non-empty line, without its `\r` or `\n`
+    async fn read_line(&mut self, deadline: Instant) -> io::Result<String> {
+        loop {
+            if let Some(end) = self.buf.iter().position(|&b| b == b'\r' || b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line[..end]).trim().to_owned();
+                if !line.is_empty() {
+                    return Ok(line);
+                }
+                continue;
+            }
+            self.read_more(deadline).await?;
+        }
+    }
+
+    async fn read_more(&mut self, deadline: Instant) -> io::Result<()> {
+        let mut chunk = [0; 64];
+        let port = &mut self.port;
+        let read = poll_fn(|cx| {
+            let mut buf = ReadBuf::new(&mut chunk);
+            Pin::new(&mut *port)
+                .poll_read(cx, &mut buf)
+                .map_ok(|()| buf.filled().len())
+        });
+        match timeout_at(deadline, read).await {
+            Ok(Ok(0)) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(Ok(n)) => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "modem didn't answer",
+            )),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
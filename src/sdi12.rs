@@ -0,0 +1,329 @@
+//! SDI-12 environmental sensor buses
+//!
+//! SDI-12 sensors share one data line at 1200 baud, 7E1.  A recorder wakes them with a
+//! break of at least 12ms followed by 8.33ms of marking, sends a command like `0M!` and
+//! the addressed sensor must start answering within 15ms.  Sensors go back to sleep after
+//! 100ms of marking, after which the next command needs a new break, and a command that got
+//! no answer is retried.  [`Sdi12`] takes care of this timing, with some slack for the
+//! latency of USB adapters.
+//!
+//! ```no_run
+//! use tokio_serial::sdi12::{self, Sdi12};
+//! use tokio_serial::SerialPortBuilderExt;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let port = sdi12::builder("/dev/ttyUSB0").open_native_async()?;
+//! let mut bus = Sdi12::new(port);
+//! println!("{}", bus.command("0I!").await?);
+//! let values = bus.measure('0').await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{DataBits, Parity, SerialPort, SerialPortBuilder, SerialStream, StopBits};
+
+use futures::future::poll_fn;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, timeout_at, Duration, Instant};
+
+use std::borrow::Cow;
+use std::io;
+use std::pin::Pin;
+
+/// Baud rate of SDI-12
+pub const BAUD_RATE: u32 = 1200;
+/// Least break waking the sensors
+pub const BREAK: Duration = Duration::from_millis(12);
+/// Least marking after the break before a command
+pub const MARKING: Duration = Duration::from_micros(8_333);
+/// Marking after which sensors may have gone back to sleep and need a new break
+pub const SLEEP_AFTER: Duration = Duration::from_millis(87);
+/// Time a sensor has to start answering once a command was sent
+pub const RESPONSE_START: Duration = Duration::from_millis(15);
+
+/// Longest response, `aD0!` values included
+const MAX_RESPONSE: usize = 96;
+
+/// Returns a builder for the port at `path`, with the 1200 baud 7E1 settings of SDI-12
+pub fn builder<'a>(path: impl Into<Cow<'a, str>>) -> SerialPortBuilder {
+    crate::new(path, BAUD_RATE)
+        .data_bits(DataBits::Seven)
+        .parity(Parity::Even)
+        .stop_bits(StopBits::One)
+}
+
+/// Runs SDI-12 commands, see the [module](self) docs
+#[derive(Debug)]
+pub struct Sdi12 {
+    port: SerialStream,
+    retries: u32,
+    slack: Duration,
+    echo: bool,
+    // When the line last carried something
+    last_activity: Option<Instant>,
+}
+
+impl Sdi12 {
+    /// Run commands over `port`, which must be configured as by [`builder`]
+    pub fn new(port: SerialStream) -> Self {
+        Self {
+            port,
+            retries: 3,
+            slack: Duration::from_millis(20),
+            echo: false,
+            last_activity: None,
+        }
+    }
+
+    /// Send an unanswered command up to `retries` more times, 3 by default
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// Returns how often an unanswered command is sent again
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Wait `slack` longer than the specification for responses, 20ms by default
+    ///
+    /// USB adapters deliver bytes in packets, some milliseconds after they arrived.
+    pub fn set_slack(&mut self, slack: Duration) {
+        self.slack = slack;
+    }
+
+    /// Returns the extra time responses are waited for
+    pub fn slack(&self) -> Duration {
+        self.slack
+    }
+
+    /// Drop the echo of each command read back before the response
+    ///
+    /// Interfaces driving the single data line from a UART's TX and RX pins receive what
+    /// they send.
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Returns `true` if the echo of commands is dropped
+    pub fn echo(&self) -> bool {
+        self.echo
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        &self.port
+    }
+
+    /// Returns a mutable reference to the port
+    pub fn get_mut(&mut self) -> &mut SerialStream {
+        &mut self.port
+    }
+
+    /// Consumes the bus, returning the port
+    pub fn into_inner(self) -> SerialStream {
+        self.port
+    }
+
+    /// Wake all sensors with a break followed by marking
+    pub async fn wake(&mut self) -> io::Result<()> {
+        self.port.set_break()?;
+        sleep(BREAK).await;
+        self.port.clear_break()?;
+        sleep(MARKING).await;
+        self.last_activity = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Send `command`, like `0M!`, and return the response without its `<CR><LF>`
+    ///
+    /// A break wakes the sensors first if the line was quiet long enough for them to sleep.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` if the command doesn't end with `!`.
+    /// * `TimedOut` if no complete response came after the last retry.
+    /// * `InvalidData` if the response is too long, and the errors of the port.
+    pub async fn command(&mut self, command: &str) -> io::Result<String> {
+        if !command.ends_with('!') || !command.is_ascii() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SDI-12 commands are ASCII and end with '!'",
+            ));
+        }
+        for attempt in 0..=self.retries {
+            let asleep = match self.last_activity {
+                Some(last) => last.elapsed() >= SLEEP_AFTER,
+                None => true,
+            };
+            if asleep {
+                self.wake().await?;
+            }
+            self.port.clear(crate::ClearBuffer::Input)?;
+            write_all(&mut self.port, command.as_bytes()).await?;
+            let sent = Instant::now();
+            self.last_activity = Some(sent);
+
+            let start = sent + char_time() * command.len() as u32 + RESPONSE_START + self.slack;
+            match self.read_response(command, start).await? {
+                Some(response) => return Ok(response),
+                None => log::debug!(
+                    "no SDI-12 response to {} (attempt {})",
+                    command,
+                    attempt + 1
+                ),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no SDI-12 response to {}", command),
+        ))
+    }
+
+    /// Start a measurement on the sensor at `address` and return its values
+    ///
+    /// Sends `aM!`, waits for the measurement as announced, or for the sensor's service
+    /// request, then collects the values with `aD0!`, `aD1!`... until all were received.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if a response isn't from the sensor or can't be parsed, or as for
+    ///   [`command`](Self::command).
+    pub async fn measure(&mut self, address: char) -> io::Result<Vec<f64>> {
+        let response = self.command(&format!("{}M!", address)).await?;
+        // atttn: seconds until the values are ready and their count
+        let fields = response
+            .strip_prefix(address)
+            .filter(|fields| fields.len() == 4)
+            .ok_or_else(|| invalid("unexpected SDI-12 measurement response"))?;
+        let seconds: u64 = fields[..3]
+            .parse()
+            .map_err(|_| invalid("bad SDI-12 measurement time"))?;
+        let count: usize = fields[3..]
+            .parse()
+            .map_err(|_| invalid("bad SDI-12 value count"))?;
+
+        if seconds > 0 {
+            // The sensor announces early results with a service request, `a<CR><LF>`
+            let ready = Instant::now() + Duration::from_secs(seconds) + self.slack;
+            let _ = self.read_line(ready).await?;
+            self.last_activity = Some(Instant::now());
+        }
+
+        let mut values = Vec::with_capacity(count);
+        for page in 0..10 {
+            if values.len() >= count {
+                break;
+            }
+            let response = self.command(&format!("{}D{}!", address, page)).await?;
+            let data = response
+                .strip_prefix(address)
+                .ok_or_else(|| invalid("SDI-12 response of another sensor"))?;
+            let before = values.len();
+            values.extend(parse_values(data)?);
+            if values.len() == before {
+                break;
+            }
+        }
+        Ok(values)
+    }
+
+    /// Read the response to `command`, `None` if it didn't start by `start`
+    async fn read_response(&mut self, command: &str, start: Instant) -> io::Result<Option<String>> {
+        let mut line = match self.read_line(start).await? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        if self.echo {
+            line = match line.strip_prefix(command) {
+                Some(rest) => rest.to_owned(),
+                None => line,
+            };
+        }
+        self.last_activity = Some(Instant::now());
+        Ok(Some(line))
+    }
+
+    /// Read a line, `None` if nothing came by `start`
+    ///
+    /// Once bytes arrive, the line may take as long as its characters need, plus the slack.
+    async fn read_line(&mut self, start: Instant) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+        let mut deadline = start;
+        loop {
+            let mut buf = [0; 32];
+            let n = match timeout_at(deadline, read(&mut self.port, &mut buf)).await {
+                Ok(n) => n?,
+                Err(_) if line.is_empty() => return Ok(None),
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "SDI-12 response cut short",
+                    ))
+                }
+            };
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            line.extend_from_slice(&buf[..n]);
+            if let Some(end) = line.windows(2).position(|w| w == b"\r\n") {
+                line.truncate(end);
+                // Sensors send 7 bit characters, the parity bit is stripped by the driver
+                return String::from_utf8(line)
+                    .map(Some)
+                    .map_err(|_| invalid("SDI-12 response is not ASCII"));
+            }
+            if line.len() > MAX_RESPONSE {
+                return Err(invalid("SDI-12 response too long"));
+            }
+            deadline = Instant::now() + char_time() * 2 + self.slack;
+        }
+    }
+}
+
+/// Parse SDI-12 values, each starting with its sign, like `+7.25-2+25`
+pub fn parse_values(data: &str) -> io::Result<Vec<f64>> {
+    let mut values = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        if !rest.starts_with(['+', '-']) {
+            return Err(invalid("SDI-12 value without sign"));
+        }
+        let end = rest[1..].find(['+', '-']).map_or(rest.len(), |end| end + 1);
+        let value = rest[..end]
+            .parse()
+            .map_err(|_| invalid("bad SDI-12 value"))?;
+        values.push(value);
+        rest = &rest[end..];
+    }
+    Ok(values)
+}
+
+/// Time to transmit one 7E1 character at 1200 baud
+fn char_time() -> Duration {
+    crate::settings::char_time(BAUD_RATE, DataBits::Seven, Parity::Even, StopBits::One)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+async fn read(port: &mut SerialStream, buf: &mut [u8]) -> io::Result<usize> {
+    poll_fn(|cx| {
+        let mut buf = ReadBuf::new(buf);
+        Pin::new(&mut *port)
+            .poll_read(cx, &mut buf)
+            .map_ok(|()| buf.filled().len())
+    })
+    .await
+}
+
+async fn write_all(port: &mut SerialStream, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *port).poll_write(cx, buf)).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[n..];
+    }
+    poll_fn(|cx| Pin::new(&mut *port).poll_flush(cx)).await
+}
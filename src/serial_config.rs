@@ -0,0 +1,169 @@
+//! Line settings as plain data, compared and applied with [`SerialStream::apply`]
+use crate::settings::Settings;
+use crate::{DataBits, FlowControl, Parity, SerialPort, SerialPortBuilder, StopBits};
+
+#[cfg(doc)]
+use crate::SerialStream;
+
+use std::fmt;
+
+/// The line settings of a port
+///
+/// Unlike `SerialPortBuilder`, the fields can be read and compared, which suits settings
+/// loaded from a configuration file and reloaded while the port stays open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// Bits per second
+    pub baud_rate: u32,
+    /// Data bits per character
+    pub data_bits: DataBits,
+    /// Parity checking mode
+    pub parity: Parity,
+    /// Stop bits per character
+    pub stop_bits: StopBits,
+    /// Flow control mode
+    pub flow_control: FlowControl,
+}
+
+impl SerialConfig {
+    /// Settings of `baud_rate`, eight data bits, no parity, one stop bit and no flow control
+    pub fn new(baud_rate: u32) -> Self {
+        Self {
+            baud_rate,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+
+    /// Returns the line settings of `builder`
+    pub fn from_builder(builder: &SerialPortBuilder) -> Self {
+        let settings = Settings::from_builder(builder);
+        Self {
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            flow_control: settings.flow_control,
+        }
+    }
+
+    /// Returns a builder for the port at `path` with these settings
+    pub fn to_builder(&self, path: &str) -> SerialPortBuilder {
+        crate::new(path, self.baud_rate)
+            .data_bits(self.data_bits)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .flow_control(self.flow_control)
+    }
+
+    /// Read the current settings of `port`
+    pub fn read(port: &dyn SerialPort) -> crate::Result<Self> {
+        Ok(Self {
+            baud_rate: port.baud_rate()?,
+            data_bits: port.data_bits()?,
+            parity: port.parity()?,
+            stop_bits: port.stop_bits()?,
+            flow_control: port.flow_control()?,
+        })
+    }
+
+    /// Returns the changes turning `self` into `target`
+    pub fn diff(&self, target: &SerialConfig) -> ConfigDiff {
+        let mut changes = Vec::new();
+        if self.baud_rate != target.baud_rate {
+            changes.push(ConfigChange::BaudRate(self.baud_rate, target.baud_rate));
+        }
+        if self.data_bits != target.data_bits {
+            changes.push(ConfigChange::DataBits(self.data_bits, target.data_bits));
+        }
+        if self.parity != target.parity {
+            changes.push(ConfigChange::Parity(self.parity, target.parity));
+        }
+        if self.stop_bits != target.stop_bits {
+            changes.push(ConfigChange::StopBits(self.stop_bits, target.stop_bits));
+        }
+        if self.flow_control != target.flow_control {
+            changes.push(ConfigChange::FlowControl(
+                self.flow_control,
+                target.flow_control,
+            ));
+        }
+        ConfigDiff { changes }
+    }
+}
+
+/// A setting changed from its first value to its second
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigChange {
+    /// Bits per second
+    BaudRate(u32, u32),
+    /// Data bits per character
+    DataBits(DataBits, DataBits),
+    /// Parity checking mode
+    Parity(Parity, Parity),
+    /// Stop bits per character
+    StopBits(StopBits, StopBits),
+    /// Flow control mode
+    FlowControl(FlowControl, FlowControl),
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigChange::BaudRate(from, to) => write!(f, "baud rate {} -> {}", from, to),
+            ConfigChange::DataBits(from, to) => write!(f, "data bits {} -> {}", from, to),
+            ConfigChange::Parity(from, to) => write!(f, "parity {} -> {}", from, to),
+            ConfigChange::StopBits(from, to) => write!(f, "stop bits {} -> {}", from, to),
+            ConfigChange::FlowControl(from, to) => write!(f, "flow control {} -> {}", from, to),
+        }
+    }
+}
+
+/// The settings that differ between two [`SerialConfig`]s
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    changes: Vec<ConfigChange>,
+}
+
+impl ConfigDiff {
+    /// Returns the changed settings
+    pub fn changes(&self) -> &[ConfigChange] {
+        &self.changes
+    }
+
+    /// Returns `true` if no setting changed
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Apply the changes to `port`, in the order of the fields of [`SerialConfig`]
+    pub(crate) fn apply_to(&self, port: &mut dyn SerialPort) -> crate::Result<()> {
+        for change in &self.changes {
+            match *change {
+                ConfigChange::BaudRate(_, to) => port.set_baud_rate(to)?,
+                ConfigChange::DataBits(_, to) => port.set_data_bits(to)?,
+                ConfigChange::Parity(_, to) => port.set_parity(to)?,
+                ConfigChange::StopBits(_, to) => port.set_stop_bits(to)?,
+                ConfigChange::FlowControl(_, to) => port.set_flow_control(to)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return f.write_str("no change");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
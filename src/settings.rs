@@ -0,0 +1,172 @@
+//! Read-only view of the settings held by a `SerialPortBuilder`
+//!
+//! `serialport::SerialPortBuilder` does not expose getters for its fields.  Backends that
+//! don't go through `serialport` to open a device (network ports, mocks, ...) still need to
+//! know what was asked for, so the values are recovered here.  Enumerated settings are
+//! found by probing the builder's `PartialEq` implementation, free-form values (path and baud
+//! rate) are read back out of its `Debug` representation.  For these backends the path is
+//! only a name, reading it back can't go wrong in a way that matters.
+//!
+//! Ports opened from a local device read the path back once and go by it from there on:
+//! the claim of the device, the errors naming it and, when the settings are saved, the
+//! open itself.
+use crate::{DataBits, FlowControl, Parity, SerialPortBuilder, StopBits};
+
+/// Snapshot of the settings held by a `SerialPortBuilder`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Settings {
+    pub(crate) path: String,
+    pub(crate) baud_rate: u32,
+    pub(crate) data_bits: DataBits,
+    pub(crate) flow_control: FlowControl,
+    pub(crate) parity: Parity,
+    pub(crate) stop_bits: StopBits,
+    pub(crate) dtr_on_open: Option<bool>,
+}
+
+impl Settings {
+    /// Extract the settings from a builder
+    pub(crate) fn from_builder(builder: &SerialPortBuilder) -> Self {
+        let matches = |candidate: SerialPortBuilder| candidate == *builder;
+
+        let data_bits = [
+            DataBits::Five,
+            DataBits::Six,
+            DataBits::Seven,
+            DataBits::Eight,
+        ]
+        .iter()
+        .copied()
+        .find(|&value| matches(builder.clone().data_bits(value)))
+        .unwrap_or(DataBits::Eight);
+
+        let flow_control = [
+            FlowControl::None,
+            FlowControl::Software,
+            FlowControl::Hardware,
+        ]
+        .iter()
+        .copied()
+        .find(|&value| matches(builder.clone().flow_control(value)))
+        .unwrap_or(FlowControl::None);
+
+        let parity = [Parity::None, Parity::Odd, Parity::Even]
+            .iter()
+            .copied()
+            .find(|&value| matches(builder.clone().parity(value)))
+            .unwrap_or(Parity::None);
+
+        let stop_bits = [StopBits::One, StopBits::Two]
+            .iter()
+            .copied()
+            .find(|&value| matches(builder.clone().stop_bits(value)))
+            .unwrap_or(StopBits::One);
+
+        let dtr_on_open = if matches(builder.clone().preserve_dtr_on_open()) {
+            None
+        } else {
+            Some(matches(builder.clone().dtr_on_open(true)))
+        };
+
+        let debug = format!("{:?}", builder);
+        let path = debug_field(&debug, "path")
+            .and_then(unquote)
+            .unwrap_or_default();
+        let baud_rate = debug_field(&debug, "baud_rate")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+
+        Self {
+            path,
+            baud_rate,
+            data_bits,
+            flow_control,
+            parity,
+            stop_bits,
+            dtr_on_open,
+        }
+    }
+}
+
+/// Time taken to transmit one character, zero for a baud rate of zero
+#[cfg(any(
+    feature = "codec",
+    feature = "monitor",
+    feature = "test-util",
+    feature = "time"
+))]
+pub(crate) fn char_time(
+    baud_rate: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+) -> std::time::Duration {
+    if baud_rate == 0 {
+        return std::time::Duration::from_secs(0);
+    }
+    let data_bits = match data_bits {
+        DataBits::Five => 5,
+        DataBits::Six => 6,
+        DataBits::Seven => 7,
+        DataBits::Eight => 8,
+    };
+    let parity_bits = match parity {
+        Parity::None => 0,
+        Parity::Odd | Parity::Even => 1,
+    };
+    let stop_bits = match stop_bits {
+        StopBits::One => 1,
+        StopBits::Two => 2,
+    };
+    let bits = 1 + data_bits + parity_bits + stop_bits;
+    std::time::Duration::from_nanos(bits * 1_000_000_000 / u64::from(baud_rate))
+}
+
+/// Find the raw text of `name` in a derived `Debug` struct representation
+fn debug_field<'a>(debug: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}: ", name);
+    let start = debug.find(&pattern)? + pattern.len();
+    let rest = &debug[start..];
+
+    if rest.starts_with('"') {
+        // Quoted string, find the closing quote while skipping escapes
+        let mut escaped = false;
+        for (i, c) in rest.char_indices().skip(1) {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => return Some(&rest[..=i]),
+                _ => escaped = false,
+            }
+        }
+        None
+    } else {
+        let end = rest.find([',', ' ']).unwrap_or(rest.len());
+        Some(&rest[..end])
+    }
+}
+
+/// Reverse the escaping applied by `str`'s `Debug` implementation
+fn unquote(quoted: &str) -> Option<String> {
+    let inner = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '0' => out.push('\0'),
+            'u' => {
+                // \u{XXXX}
+                let code: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                out.push(std::char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            other => out.push(other),
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,220 @@
+//! CAN bus access through SLCAN adapters
+//!
+//! [`Slcan`] drives a USB-CAN adapter speaking the LAWICEL protocol over its virtual serial
+//! port with the [SLCAN codec](crate::codec::slcan): it sets the bit rate, opens and closes
+//! the channel, waiting for the adapter to acknowledge each command, and sends and receives
+//! frames.
+//!
+//! ```no_run
+//! use tokio_serial::slcan::{Bitrate, CanFrame, CanId, Slcan};
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! let mut can = Slcan::new(port);
+//! can.open(Bitrate::Kbps500).await?;
+//! can.send(&CanFrame::new(CanId::Standard(0x7df), &[0x02, 0x01, 0x0c])?).await?;
+//! let reply = can.recv().await?;
+//! println!("{} {:02x?}", reply.frame.id(), reply.frame.data());
+//! can.close().await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::codec::slcan::{Command, Response, SlcanCodec};
+use crate::frame::SerialFramed;
+use crate::SerialStream;
+
+pub use crate::codec::slcan::{Bitrate, CanFrame, CanId, Received};
+
+use futures::{SinkExt, StreamExt};
+use tokio::time::{timeout_at, Instant};
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+
+/// Time the adapter is given to answer a command, by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A CAN interface behind an SLCAN adapter, see the [module](self) docs
+#[derive(Debug)]
+pub struct Slcan {
+    framed: SerialFramed<SlcanCodec>,
+    timeout: Duration,
+    // Frames received while waiting for the answer to a command
+    received: VecDeque<Received>,
+}
+
+impl Slcan {
+    /// Drive the adapter behind `port`
+    ///
+    /// The baud rate of a USB adapter's virtual port doesn't matter, that of a serial one
+    /// must match its setting.
+    pub fn new(port: SerialStream) -> Self {
+        Self {
+            framed: SerialFramed::new(port, SlcanCodec::new()),
+            timeout: DEFAULT_TIMEOUT,
+            received: VecDeque::new(),
+        }
+    }
+
+    /// Wait up to `timeout` for the answer to a command, [`DEFAULT_TIMEOUT`] by default
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the time the adapter is given to answer a command
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns a reference to the port
+    pub fn get_ref(&self) -> &SerialStream {
+        self.framed.get_ref()
+    }
+
+    /// Consumes the interface, returning the port
+    pub fn into_inner(self) -> SerialStream {
+        self.framed.into_inner()
+    }
+
+    /// Set the bit rate and open the channel
+    ///
+    /// The channel is closed first, in case a previous session left it open.
+    ///
+    /// ## Errors
+    ///
+    /// * `Other` if the adapter refused a command.
+    /// * `TimedOut` if it didn't answer, and the errors of the port.
+    pub async fn open(&mut self, bitrate: Bitrate) -> io::Result<()> {
+        self.reopen(bitrate, Command::Open).await
+    }
+
+    /// Set the bit rate and open the channel without taking part in the bus: frames aren't
+    /// acknowledged and none can be sent
+    ///
+    /// ## Errors
+    ///
+    /// As for [`open`](Self::open).
+    pub async fn open_listen_only(&mut self, bitrate: Bitrate) -> io::Result<()> {
+        self.reopen(bitrate, Command::ListenOnly).await
+    }
+
+    async fn reopen(&mut self, bitrate: Bitrate, open: Command) -> io::Result<()> {
+        // Closing a closed channel fails, which is fine
+        self.command(Command::Close).await?;
+        self.expect_ok(Command::Bitrate(bitrate)).await?;
+        self.expect_ok(open).await
+    }
+
+    /// Close the channel
+    ///
+    /// ## Errors
+    ///
+    /// As for [`open`](Self::open).
+    pub async fn close(&mut self) -> io::Result<()> {
+        self.expect_ok(Command::Close).await
+    }
+
+    /// Turn the timestamps of received frames on or off, with the channel closed
+    ///
+    /// ## Errors
+    ///
+    /// As for [`open`](Self::open).
+    pub async fn set_timestamps(&mut self, on: bool) -> io::Result<()> {
+        self.expect_ok(Command::Timestamps(on)).await
+    }
+
+    /// Returns the hardware and firmware versions, four hex digits like `1013`
+    ///
+    /// ## Errors
+    ///
+    /// As for [`open`](Self::open), and `InvalidData` if the reply isn't a version.
+    pub async fn version(&mut self) -> io::Result<String> {
+        match self.command(Command::Version).await? {
+            Response::Reply(reply) if reply.starts_with('V') => Ok(reply[1..].to_owned()),
+            Response::Error => Err(refused()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected answer to a version query",
+            )),
+        }
+    }
+
+    /// Send `frame` on the bus
+    ///
+    /// The frame is handed to the adapter without waiting for its answer, which
+    /// [`recv`](Self::recv) skips, or reports if the adapter refused the frame.
+    pub async fn send(&mut self, frame: &CanFrame) -> io::Result<()> {
+        self.framed.send(Command::Transmit(*frame)).await
+    }
+
+    /// Returns the next frame received from the bus
+    ///
+    /// ## Errors
+    ///
+    /// * `Other` if the adapter refused a frame sent, its buffer being full or the channel
+    ///   closed.
+    /// * `InvalidData` for a malformed frame.
+    /// * `UnexpectedEof` if the port hung up, and the errors of the port.
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    pub async fn recv(&mut self) -> io::Result<Received> {
+        if let Some(received) = self.received.pop_front() {
+            return Ok(received);
+        }
+        loop {
+            match self.framed.next().await {
+                Some(Ok(Response::Frame(received))) => return Ok(received),
+                Some(Ok(Response::Error)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        "SLCAN adapter refused a frame",
+                    ))
+                }
+                Some(Ok(other)) => log::debug!("skipping SLCAN answer {:?}", other),
+                Some(Err(e)) => return Err(e),
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+    }
+
+    async fn expect_ok(&mut self, command: Command) -> io::Result<()> {
+        match self.command(command).await? {
+            Response::Ok => Ok(()),
+            _ => Err(refused()),
+        }
+    }
+
+    /// Send `command` and return the adapter's answer, keeping the frames received meanwhile
+    async fn command(&mut self, command: Command) -> io::Result<Response> {
+        self.framed.send(command).await?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let next = match timeout_at(deadline, self.framed.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "SLCAN adapter didn't answer",
+                    ))
+                }
+            };
+            match next {
+                Some(Ok(Response::Frame(received))) => self.received.push_back(received),
+                // Answers to frames sent before
+                Some(Ok(Response::Sent)) => {}
+                Some(Ok(response)) => return Ok(response),
+                Some(Err(e)) if e.kind() == io::ErrorKind::InvalidData => {
+                    log::debug!("skipping malformed SLCAN frame: {}", e)
+                }
+                Some(Err(e)) => return Err(e),
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+    }
+}
+
+// `io::Error::other` needs a newer compiler than the MSRV
+#[allow(clippy::io_other_error)]
+fn refused() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "SLCAN adapter refused the command")
+}
@@ -0,0 +1,244 @@
+//! Raw TCP transport for network serial device servers
+//!
+//! Device servers (ser2net in "raw" mode, Moxa NPort "TCP server" mode, ...) expose a serial
+//! port as a plain TCP socket without any in-band control protocol.  [`TcpSerial`] wraps such a
+//! socket so code written against the port traits of this crate can use it unchanged.
+use crate::settings::Settings;
+use crate::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+use std::io::{self, Read, Result as IoResult, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Serial port reached over a raw TCP connection
+///
+/// There is no way to configure the remote port in raw mode, so settings changes are only
+/// recorded locally and reported back by the getters.  Control line methods are no-ops; the
+/// CTS, DSR and CD inputs read as asserted and RI as not asserted.
+///
+/// ```no_run
+/// use tokio::io::AsyncWriteExt;
+/// use tokio_serial::TcpSerial;
+///
+/// #[tokio::main]
+/// async fn main() -> tokio_serial::Result<()> {
+///     let builder = tokio_serial::new("", 9600);
+///     let mut port = TcpSerial::connect("192.168.1.10:4001", &builder).await?;
+///     port.write_all(b"hello").await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TcpSerial {
+    stream: TcpStream,
+    name: String,
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+}
+
+impl TcpSerial {
+    /// Connect to a device server at `addr`
+    ///
+    /// The settings in `builder` are only recorded, the builder path is ignored.
+    pub async fn connect<A: ToSocketAddrs>(
+        addr: A,
+        builder: &crate::SerialPortBuilder,
+    ) -> crate::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        Self::from_stream(stream, builder)
+    }
+
+    /// Wrap an already connected `TcpStream`
+    pub fn from_stream(
+        stream: TcpStream,
+        builder: &crate::SerialPortBuilder,
+    ) -> crate::Result<Self> {
+        stream.set_nodelay(true)?;
+        let name = format!("tcp://{}", stream.peer_addr()?);
+        let settings = Settings::from_builder(builder);
+        Ok(Self {
+            stream,
+            name,
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            flow_control: settings.flow_control,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+        })
+    }
+
+    /// Returns a reference to the underlying `TcpStream`
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Consumes the port, returning the underlying `TcpStream`
+    pub fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+}
+
+impl AsyncRead for TcpSerial {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpSerial {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+impl Read for TcpSerial {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.stream.try_read(buf)
+    }
+}
+
+impl Write for TcpSerial {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.stream.try_write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+impl SerialPort for TcpSerial {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> crate::Result<u32> {
+        Ok(self.baud_rate)
+    }
+
+    fn data_bits(&self) -> crate::Result<DataBits> {
+        Ok(self.data_bits)
+    }
+
+    fn flow_control(&self) -> crate::Result<FlowControl> {
+        Ok(self.flow_control)
+    }
+
+    fn parity(&self) -> crate::Result<Parity> {
+        Ok(self.parity)
+    }
+
+    fn stop_bits(&self) -> crate::Result<StopBits> {
+        Ok(self.stop_bits)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> crate::Result<()> {
+        self.data_bits = data_bits;
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> crate::Result<()> {
+        self.flow_control = flow_control;
+        Ok(())
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> crate::Result<()> {
+        self.parity = parity;
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> crate::Result<()> {
+        self.stop_bits = stop_bits;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, _: Duration) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, _: bool) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, _: bool) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+        Ok(true)
+    }
+
+    fn bytes_to_read(&self) -> crate::Result<u32> {
+        Ok(0)
+    }
+
+    fn bytes_to_write(&self) -> crate::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, _: ClearBuffer) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Cloning TcpSerial is not supported.
+    ///
+    /// # Errors
+    /// Always returns `ErrorKind::Other` with a message.
+    fn try_clone(&self) -> crate::Result<Box<dyn SerialPort>> {
+        Err(crate::Error::new(
+            crate::ErrorKind::Io(io::ErrorKind::Other),
+            "Cannot clone Tokio handles",
+        ))
+    }
+
+    fn set_break(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn clear_break(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
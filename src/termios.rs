@@ -0,0 +1,51 @@
+//! Saving and restoring the terminal settings of a port
+use crate::config;
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+
+/// Terminal settings of a device before it was opened
+#[derive(Clone, Copy)]
+pub(crate) struct Original(libc::termios);
+
+impl Original {
+    /// Apply the saved settings to `fd`
+    pub(crate) fn restore(&self, fd: RawFd) -> io::Result<()> {
+        config::put_termios(fd, &self.0)
+    }
+}
+
+impl fmt::Debug for Original {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Original").finish()
+    }
+}
+
+/// Open the device at `path` with the settings of `builder`, returning the ones it had
+///
+/// `serialport` changes the settings as part of opening, so the device is opened here
+/// instead: its settings are read from the descriptor before [`config::configure`] applies
+/// those of the builder to it.  The port is locked like `serialport` does.
+pub(crate) fn open(
+    path: &str,
+    builder: &crate::SerialPortBuilder,
+) -> crate::Result<(mio_serial::SerialStream, Original)> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+        .open(path)?;
+    let original = Original(config::termios(&file)?);
+    config::configure(&file, builder)?;
+
+    // SAFETY: the descriptor is owned and handed over, the port takes the exclusive lock
+    let mut port = unsafe { serialport::TTYPort::from_raw_fd(file.into_raw_fd()) };
+    if builder.clone().exclusive(false) == *builder {
+        port.set_exclusive(false)?;
+    }
+    Ok((mio_serial::SerialStream::try_from(port)?, original))
+}
@@ -0,0 +1,601 @@
+//! In-memory serial ports for unit tests
+//!
+//! [`MockSerial`] implements the same traits as [`SerialStream`](crate::SerialStream) without
+//! touching the operating system, so it is available on every platform.  It comes in two
+//! flavours:
+//!
+//! * [`MockSerial::pair`] returns two connected ports, like `SerialStream::pair` does with a
+//!   pseudo terminal on Unix
+//! * [`MockSerial::builder`] returns a [`Builder`] scripting the exchange a driver is expected
+//!   to perform, in the style of `tokio_test::io::Builder`
+//!
+//! [`SimulatedSerial`] goes further and reproduces the timing of a real link: data flows at
+//! the configured baud rate through driver buffers of limited size.
+//!
+//! On Unix, [`PtyPair`] provides two linked pseudo terminals for integration tests of code
+//! that opens ports by path.
+//!
+//! All timing is based on `tokio::time`.  With the clock paused (`tokio::time::pause()` or
+//! `#[tokio::test(start_paused = true)]`) tests of timeouts and watchdogs run instantly and
+//! deterministically; [`advance_chars`] and [`SimulatedSerial::advance_until_idle`] move the
+//! clock in units of line time.
+//!
+//! ```
+//! use std::time::Duration;
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//! use tokio_serial::test::MockSerial;
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() {
+//!     let mut port = MockSerial::builder()
+//!         .write(b"AT\r")
+//!         .wait(Duration::from_millis(10))
+//!         .read(b"OK\r\n")
+//!         .build();
+//!
+//!     port.write_all(b"AT\r").await.unwrap();
+//!     let mut reply = [0u8; 4];
+//!     port.read_exact(&mut reply).await.unwrap();
+//!     assert_eq!(&reply, b"OK\r\n");
+//! }
+//! ```
+mod capture;
+pub use capture::{Capture, CaptureChunk, Direction};
+
+mod simulated;
+pub use simulated::{Overflow, SimulatedBuilder, SimulatedSerial, DEFAULT_DRIVER_BUFFER};
+
+#[cfg(unix)]
+mod pty;
+#[cfg(unix)]
+pub use pty::PtyPair;
+
+use crate::settings::Settings;
+use crate::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use futures::task::noop_waker_ref;
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::time::Sleep;
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, Read, Result as IoResult, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Buffer size of each direction of a [`MockSerial::pair`]
+const PAIR_BUFFER: usize = 4096;
+
+/// In-memory serial port
+///
+/// Settings changes are recorded and reported back by the getters.  The control lines of a
+/// pair are cross-connected like a null-modem cable: RTS drives the peer's CTS and DTR drives
+/// the peer's DSR and CD.  Scripted ports read CTS, DSR and CD as asserted.
+#[derive(Debug)]
+pub struct MockSerial {
+    io: Io,
+    lines: Lines,
+    name: String,
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+    timeout: Duration,
+}
+
+#[derive(Debug)]
+enum Io {
+    Duplex(DuplexStream),
+    Script(Script),
+}
+
+impl MockSerial {
+    /// Create a pair of connected ports using 9600 8N1 settings
+    pub fn pair() -> (Self, Self) {
+        Self::pair_with(&crate::new("mock", 9600))
+    }
+
+    /// Create a pair of connected ports using the settings in `builder`
+    pub fn pair_with(builder: &crate::SerialPortBuilder) -> (Self, Self) {
+        let (a, b) = tokio::io::duplex(PAIR_BUFFER);
+        let state = Arc::new(Mutex::new([LineState::default(); 2]));
+        let a = Self::new(
+            Io::Duplex(a),
+            Lines {
+                state: state.clone(),
+                side: 0,
+            },
+            builder,
+        );
+        let b = Self::new(Io::Duplex(b), Lines { state, side: 1 }, builder);
+        (a, b)
+    }
+
+    /// Start scripting a port
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    fn new(io: Io, lines: Lines, builder: &crate::SerialPortBuilder) -> Self {
+        let settings = Settings::from_builder(builder);
+        Self {
+            io,
+            lines,
+            name: settings.path,
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            flow_control: settings.flow_control,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            timeout: Duration::from_secs(0),
+        }
+    }
+}
+
+/// Returns the time taken to transmit a single character with the given settings
+///
+/// A character is made of a start bit, the data bits, the parity bit if any and the stop
+/// bits.  A baud rate of zero is treated as infinitely fast.
+pub fn char_time(
+    baud_rate: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+) -> Duration {
+    crate::settings::char_time(baud_rate, data_bits, parity, stop_bits)
+}
+
+/// Advance the paused clock by the time `port` takes to transmit `chars` characters
+///
+/// Returns the duration the clock was advanced by.  The clock must have been paused with
+/// `tokio::time::pause()`.
+pub async fn advance_chars<P: SerialPort + ?Sized>(
+    port: &P,
+    chars: u32,
+) -> crate::Result<Duration> {
+    let duration = char_time(
+        port.baud_rate()?,
+        port.data_bits()?,
+        port.parity()?,
+        port.stop_bits()?,
+    ) * chars;
+    tokio::time::advance(duration).await;
+    Ok(duration)
+}
+
+/// Poll an async operation once, mapping `Pending` to `WouldBlock`
+fn poll_now<T>(f: impl FnOnce(&mut Context<'_>) -> Poll<IoResult<T>>) -> IoResult<T> {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    match f(&mut cx) {
+        Poll::Ready(result) => result,
+        Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+    }
+}
+
+/// Builder for a scripted [`MockSerial`]
+///
+/// Actions are performed in order.  Writes are compared against the expected data and panic
+/// on a mismatch; reads and writes attempted while a different action is pending wait for it.
+/// Once the script is exhausted reads return EOF and writes panic.  Dropping the port before
+/// the script completes panics as well.
+#[derive(Debug)]
+pub struct Builder {
+    actions: VecDeque<Action>,
+    settings: crate::SerialPortBuilder,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            actions: VecDeque::new(),
+            settings: crate::new("mock", 9600),
+        }
+    }
+}
+
+impl Builder {
+    /// Expect the driver to read `data`
+    pub fn read(&mut self, data: &[u8]) -> &mut Self {
+        if !data.is_empty() {
+            self.actions.push_back(Action::Read(data.to_vec()));
+        }
+        self
+    }
+
+    /// Expect the driver to write `data`
+    pub fn write(&mut self, data: &[u8]) -> &mut Self {
+        if !data.is_empty() {
+            self.actions.push_back(Action::Write(data.to_vec()));
+        }
+        self
+    }
+
+    /// Stall reads and writes for `duration`
+    pub fn wait(&mut self, duration: Duration) -> &mut Self {
+        self.actions.push_back(Action::Wait(duration));
+        self
+    }
+
+    /// Fail the next read with `error`
+    pub fn read_error(&mut self, error: io::Error) -> &mut Self {
+        self.actions.push_back(Action::ReadError(Some(error)));
+        self
+    }
+
+    /// Fail the next write with `error`
+    pub fn write_error(&mut self, error: io::Error) -> &mut Self {
+        self.actions.push_back(Action::WriteError(Some(error)));
+        self
+    }
+
+    /// Expect the exchange of `capture`, waiting before each read as long as the device took
+    /// to answer
+    ///
+    /// The wait before a read is the time between the start of the chunk written before it
+    /// and its own.
+    pub fn capture(&mut self, capture: &Capture) -> &mut Self {
+        let mut previous = None;
+        for chunk in capture.chunks() {
+            match chunk.direction {
+                Direction::HostToDevice => self.write(&chunk.data),
+                Direction::DeviceToHost => {
+                    if let Some(previous) = previous {
+                        let gap = chunk.at.saturating_sub(previous);
+                        if gap > Duration::from_secs(0) {
+                            self.wait(gap);
+                        }
+                    }
+                    self.read(&chunk.data)
+                }
+            };
+            previous = Some(chunk.at);
+        }
+        self
+    }
+
+    /// Use the settings in `builder` instead of 9600 8N1
+    pub fn settings(&mut self, builder: &crate::SerialPortBuilder) -> &mut Self {
+        self.settings = builder.clone();
+        self
+    }
+
+    /// Build the scripted port
+    pub fn build(&mut self) -> MockSerial {
+        let script = Script {
+            actions: std::mem::take(&mut self.actions),
+            sleep: None,
+            wakers: [None, None],
+        };
+        let lines = Lines {
+            state: Arc::new(Mutex::new([LineState::default(); 2])),
+            side: 0,
+        };
+        MockSerial::new(Io::Script(script), lines, &self.settings)
+    }
+}
+
+#[derive(Debug)]
+enum Action {
+    Read(Vec<u8>),
+    Write(Vec<u8>),
+    Wait(Duration),
+    ReadError(Option<io::Error>),
+    WriteError(Option<io::Error>),
+}
+
+/// Indices into `Script::wakers`
+const READ: usize = 0;
+const WRITE: usize = 1;
+
+#[derive(Debug)]
+struct Script {
+    actions: VecDeque<Action>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    /// Reader and writer tasks waiting for the current action to complete
+    wakers: [Option<Waker>; 2],
+}
+
+impl Script {
+    fn advance(&mut self) {
+        self.actions.pop_front();
+        for waker in self.wakers.iter_mut().filter_map(Option::take) {
+            waker.wake();
+        }
+    }
+
+    /// Register the task performing `direction` to be woken when the current action completes
+    fn park(&mut self, cx: &mut Context<'_>, direction: usize) {
+        self.wakers[direction] = Some(cx.waker().clone());
+    }
+
+    /// Complete a leading `Wait` action
+    fn poll_wait(&mut self, cx: &mut Context<'_>, direction: usize) -> Poll<()> {
+        while let Some(Action::Wait(duration)) = self.actions.front() {
+            let duration = *duration;
+            let sleep = self
+                .sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(duration)));
+            if sleep.as_mut().poll(cx).is_pending() {
+                // Only the last task polling the timer is woken by it
+                self.park(cx, direction);
+                return Poll::Pending;
+            }
+            self.sleep = None;
+            self.advance();
+        }
+        Poll::Ready(())
+    }
+
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        futures::ready!(self.poll_wait(cx, READ));
+        match self.actions.front_mut() {
+            None => Poll::Ready(Ok(())),
+            Some(Action::Read(data)) => {
+                let n = data.len().min(buf.remaining());
+                buf.put_slice(&data[..n]);
+                data.drain(..n);
+                if data.is_empty() {
+                    self.advance();
+                }
+                Poll::Ready(Ok(()))
+            }
+            Some(Action::ReadError(error)) => {
+                let error = error.take().expect("error already returned");
+                self.advance();
+                Poll::Ready(Err(error))
+            }
+            Some(_) => {
+                self.park(cx, READ);
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        futures::ready!(self.poll_wait(cx, WRITE));
+        match self.actions.front_mut() {
+            None => panic!("unexpected write of {:?}, script is complete", buf),
+            Some(Action::Write(expected)) => {
+                let n = expected.len().min(buf.len());
+                assert_eq!(&buf[..n], &expected[..n], "write does not match the script");
+                expected.drain(..n);
+                if expected.is_empty() {
+                    self.advance();
+                }
+                Poll::Ready(Ok(n))
+            }
+            Some(Action::WriteError(error)) => {
+                let error = error.take().expect("error already returned");
+                self.advance();
+                Poll::Ready(Err(error))
+            }
+            Some(_) => {
+                self.park(cx, WRITE);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Script {
+    fn drop(&mut self) {
+        if !std::thread::panicking() && !self.actions.is_empty() {
+            panic!(
+                "MockSerial dropped with unfinished script: {:?}",
+                self.actions
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LineState {
+    rts: bool,
+    dtr: bool,
+}
+
+impl Default for LineState {
+    fn default() -> Self {
+        Self {
+            rts: true,
+            dtr: true,
+        }
+    }
+}
+
+/// One side of a shared set of control lines
+#[derive(Debug)]
+struct Lines {
+    state: Arc<Mutex<[LineState; 2]>>,
+    side: usize,
+}
+
+impl Lines {
+    fn set(&self, f: impl FnOnce(&mut LineState)) {
+        f(&mut self.state.lock().unwrap()[self.side]);
+    }
+
+    fn peer(&self) -> LineState {
+        self.state.lock().unwrap()[1 - self.side]
+    }
+}
+
+impl AsyncRead for MockSerial {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        match self.io {
+            Io::Duplex(ref mut io) => Pin::new(io).poll_read(cx, buf),
+            Io::Script(ref mut script) => script.poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for MockSerial {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        match self.io {
+            Io::Duplex(ref mut io) => Pin::new(io).poll_write(cx, buf),
+            Io::Script(ref mut script) => script.poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.io {
+            Io::Duplex(ref mut io) => Pin::new(io).poll_flush(cx),
+            Io::Script(_) => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.io {
+            Io::Duplex(ref mut io) => Pin::new(io).poll_shutdown(cx),
+            Io::Script(_) => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl Read for MockSerial {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        poll_now(|cx| {
+            let mut buf = ReadBuf::new(buf);
+            futures::ready!(Pin::new(&mut *self).poll_read(cx, &mut buf))?;
+            Poll::Ready(Ok(buf.filled().len()))
+        })
+    }
+}
+
+impl Write for MockSerial {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        poll_now(|cx| Pin::new(&mut *self).poll_write(cx, buf))
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        poll_now(|cx| Pin::new(&mut *self).poll_flush(cx))
+    }
+}
+
+impl SerialPort for MockSerial {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> crate::Result<u32> {
+        Ok(self.baud_rate)
+    }
+
+    fn data_bits(&self) -> crate::Result<DataBits> {
+        Ok(self.data_bits)
+    }
+
+    fn flow_control(&self) -> crate::Result<FlowControl> {
+        Ok(self.flow_control)
+    }
+
+    fn parity(&self) -> crate::Result<Parity> {
+        Ok(self.parity)
+    }
+
+    fn stop_bits(&self) -> crate::Result<StopBits> {
+        Ok(self.stop_bits)
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
+        self.baud_rate = baud_rate;
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> crate::Result<()> {
+        self.data_bits = data_bits;
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> crate::Result<()> {
+        self.flow_control = flow_control;
+        Ok(())
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> crate::Result<()> {
+        self.parity = parity;
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> crate::Result<()> {
+        self.stop_bits = stop_bits;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> crate::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
+        self.lines.set(|lines| lines.rts = level);
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
+        self.lines.set(|lines| lines.dtr = level);
+        Ok(())
+    }
+
+    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+        Ok(self.lines.peer().rts)
+    }
+
+    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+        Ok(self.lines.peer().dtr)
+    }
+
+    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+        Ok(self.lines.peer().dtr)
+    }
+
+    fn bytes_to_read(&self) -> crate::Result<u32> {
+        Ok(0)
+    }
+
+    fn bytes_to_write(&self) -> crate::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, _: ClearBuffer) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Cloning MockSerial is not supported.
+    ///
+    /// # Errors
+    /// Always returns `ErrorKind::Other` with a message.
+    fn try_clone(&self) -> crate::Result<Box<dyn SerialPort>> {
+        Err(crate::Error::new(
+            crate::ErrorKind::Io(io::ErrorKind::Other),
+            "Cannot clone MockSerial",
+        ))
+    }
+
+    fn set_break(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn clear_break(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+//! Serial captures loaded from files, to script mock ports with
+use std::convert::TryInto;
+use std::io::{self, Read};
+use std::time::Duration;
+
+/// Which end sent the bytes of a capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Written by the host, the code under test
+    HostToDevice,
+    /// Sent by the device, read by the host
+    DeviceToHost,
+}
+
+/// Bytes sent one way without the other end sending in between
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureChunk {
+    /// Time of the first byte since the start of the capture
+    pub at: Duration,
+    /// Which end sent the bytes
+    pub direction: Direction,
+    /// The bytes
+    pub data: Vec<u8>,
+}
+
+/// An exchange captured on a line, in the order it happened
+///
+/// Loaded from a pcapng file with [`from_pcapng`](Self::from_pcapng) or from the CSV export
+/// of a Saleae logic analyzer with [`from_saleae_csv`](Self::from_saleae_csv), it scripts a
+/// [`MockSerial`](super::MockSerial) through [`Builder::capture`](super::Builder::capture), so
+/// regression tests replay what was recorded in the field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capture {
+    chunks: Vec<CaptureChunk>,
+}
+
+impl Capture {
+    /// Create an empty capture
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `data` sent at `at`, joining it to the last chunk if that went the same way
+    pub fn push(&mut self, at: Duration, direction: Direction, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        match self.chunks.last_mut() {
+            Some(last) if last.direction == direction => last.data.extend_from_slice(data),
+            _ => self.chunks.push(CaptureChunk {
+                at,
+                direction,
+                data: data.to_vec(),
+            }),
+        }
+    }
+
+    /// Returns the chunks, alternating directions
+    pub fn chunks(&self) -> &[CaptureChunk] {
+        &self.chunks
+    }
+
+    /// Returns all the bytes sent in `direction`
+    pub fn bytes(&self, direction: Direction) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.direction == direction)
+            .flat_map(|chunk| chunk.data.iter().copied())
+            .collect()
+    }
+
+    /// Load a pcapng capture
+    ///
+    /// The data of each packet is taken as the serial bytes, as written with the user link
+    /// types 147 to 162.  Packets flagged outbound in their `epb_flags` went to the device,
+    /// inbound ones came from it; without the flag, packets of the first interface went to
+    /// the device and those of the others came from it.  Times are relative to the first
+    /// packet.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if the file isn't a pcapng capture or is cut short.
+    /// * The errors of `reader`.
+    pub fn from_pcapng(mut reader: impl Read) -> io::Result<Self> {
+        let mut file = Vec::new();
+        reader.read_to_end(&mut file)?;
+        let mut pcapng = Pcapng::default();
+        let mut rest = &file[..];
+        if !rest.starts_with(&SECTION_HEADER.to_le_bytes()) {
+            return Err(invalid("not a pcapng file"));
+        }
+        while !rest.is_empty() {
+            let len = pcapng.block(rest)?;
+            rest = &rest[len..];
+        }
+        Ok(pcapng.capture)
+    }
+
+    /// Load the CSV export of a Saleae Logic async serial analyzer
+    ///
+    /// Both the Logic 2 export (`name,type,start_time,duration,data`) and the Logic 1 one
+    /// (`Time [s],Analyzer Name,Decoded Protocol Result`) are read, with the values in hex
+    /// like `0x41` or in ASCII.  The rows of the analyzer named `host`, decoding the host's TX
+    /// line, went to the device, those of the other analyzers came from it.  Rows whose type
+    /// isn't `data`, like framing errors, are skipped.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` if a column is missing or a row can't be read.
+    /// * The errors of `reader`.
+    pub fn from_saleae_csv(mut reader: impl Read, host: &str) -> io::Result<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = csv_fields(lines.next().ok_or_else(|| invalid("empty CSV capture"))?);
+        let column = |names: &[&str]| {
+            header
+                .iter()
+                .position(|field| names.iter().any(|name| field.eq_ignore_ascii_case(name)))
+        };
+        let time = column(&["start_time", "Time [s]", "Time"]);
+        let name = column(&["name", "Analyzer Name"]);
+        let data = column(&["data", "Decoded Protocol Result", "Value"]);
+        let kind = column(&["type"]);
+        let (time, name, data) = match (time, name, data) {
+            (Some(time), Some(name), Some(data)) => (time, name, data),
+            _ => return Err(invalid("CSV capture without time, name or data column")),
+        };
+
+        let mut capture = Self::new();
+        let mut start = None;
+        for line in lines {
+            let fields = csv_fields(line);
+            let field = |i: usize| {
+                fields
+                    .get(i)
+                    .map(String::as_str)
+                    .ok_or_else(|| invalid("short CSV row"))
+            };
+            if let Some(kind) = kind {
+                if !field(kind)?.eq_ignore_ascii_case("data") {
+                    continue;
+                }
+            }
+            let seconds: f64 = field(time)?
+                .trim()
+                .parse()
+                .map_err(|_| invalid("bad CSV time"))?;
+            let start = *start.get_or_insert(seconds);
+            let at = Duration::from_secs_f64((seconds - start).max(0.0));
+            let byte = saleae_value(field(data)?).ok_or_else(|| invalid("bad CSV value"))?;
+            let direction = if field(name)? == host {
+                Direction::HostToDevice
+            } else {
+                Direction::DeviceToHost
+            };
+            capture.push(at, direction, &[byte]);
+        }
+        Ok(capture)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+const SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION: u32 = 1;
+const SIMPLE_PACKET: u32 = 3;
+const ENHANCED_PACKET: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const OPT_END: u16 = 0;
+const IF_TSRESOL: u16 = 9;
+const EPB_FLAGS: u16 = 2;
+
+#[derive(Debug, Default)]
+struct Pcapng {
+    big_endian: bool,
+    // Timestamp resolution of each interface of the section
+    resolutions: Vec<u8>,
+    // Nanoseconds of the first packet
+    origin: Option<u128>,
+    capture: Capture,
+}
+
+impl Pcapng {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = bytes[..4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    /// Read the block starting `rest`, returning its length
+    // `usize::div_ceil` needs a newer compiler than the MSRV
+    #[allow(clippy::manual_div_ceil)]
+    fn block(&mut self, rest: &[u8]) -> io::Result<usize> {
+        let truncated = || invalid("pcapng block cut short");
+        if rest.len() < 12 {
+            return Err(truncated());
+        }
+        // The byte order of a section is known from its header
+        if rest[..4] == SECTION_HEADER.to_le_bytes() {
+            let magic = rest.get(8..12).ok_or_else(truncated)?;
+            self.big_endian = match magic.try_into().unwrap() {
+                m if u32::from_be_bytes(m) == BYTE_ORDER_MAGIC => true,
+                m if u32::from_le_bytes(m) == BYTE_ORDER_MAGIC => false,
+                _ => return Err(invalid("bad pcapng byte order magic")),
+            };
+            self.resolutions.clear();
+        }
+        let kind = self.u32(rest);
+        let len = self.u32(&rest[4..]) as usize;
+        // Blocks are padded to 32 bits
+        if len < 12 || len & 0b11 != 0 {
+            return Err(invalid("bad pcapng block length"));
+        }
+        let body = rest.get(8..len - 4).ok_or_else(truncated)?;
+
+        match kind {
+            INTERFACE_DESCRIPTION => {
+                let options = body.get(8..).ok_or_else(truncated)?;
+                let resolution = self
+                    .options(options)
+                    .find(|(code, value)| *code == IF_TSRESOL && !value.is_empty())
+                    .map_or(6, |(_, value)| value[0]);
+                self.resolutions.push(resolution);
+            }
+            ENHANCED_PACKET => {
+                if body.len() < 20 {
+                    return Err(truncated());
+                }
+                let interface = self.u32(body) as usize;
+                let ticks = u64::from(self.u32(&body[4..])) << 32 | u64::from(self.u32(&body[8..]));
+                let captured = self.u32(&body[12..]) as usize;
+                let data = body.get(20..20 + captured).ok_or_else(truncated)?;
+                let options = body.get(20 + (captured + 3) / 4 * 4..).unwrap_or(&[]);
+                let flags = self
+                    .options(options)
+                    .find(|(code, value)| *code == EPB_FLAGS && value.len() == 4)
+                    .map(|(_, value)| self.u32(value) & 0b11);
+                let direction = match flags {
+                    Some(0b01) => Direction::DeviceToHost,
+                    Some(0b10) => Direction::HostToDevice,
+                    _ if interface == 0 => Direction::HostToDevice,
+                    _ => Direction::DeviceToHost,
+                };
+                let resolution = self.resolutions.get(interface).copied().unwrap_or(6);
+                let at = self.elapsed(nanos(ticks, resolution));
+                self.capture.push(at, direction, data);
+            }
+            SIMPLE_PACKET => {
+                let original = self.u32(body.get(..4).ok_or_else(truncated)?) as usize;
+                let data = &body[4..];
+                let data = &data[..original.min(data.len())];
+                // No timestamp, taken as following the previous packet
+                let at = self.capture.chunks.last().map_or(Duration::ZERO, |c| c.at);
+                self.capture.push(at, Direction::HostToDevice, data);
+            }
+            _ => {}
+        }
+        Ok(len)
+    }
+
+    /// Returns the code and value of the options in `options`
+    // `usize::div_ceil` needs a newer compiler than the MSRV
+    #[allow(clippy::manual_div_ceil)]
+    fn options<'a>(&'a self, mut options: &'a [u8]) -> impl Iterator<Item = (u16, &'a [u8])> + 'a {
+        std::iter::from_fn(move || {
+            if options.len() < 4 {
+                return None;
+            }
+            let code = self.u16(options);
+            let len = usize::from(self.u16(&options[2..]));
+            let value = options.get(4..4 + len)?;
+            options = options.get(4 + (len + 3) / 4 * 4..).unwrap_or(&[]);
+            Some((code, value)).filter(|(code, _)| *code != OPT_END)
+        })
+    }
+
+    fn elapsed(&mut self, nanos: u128) -> Duration {
+        let origin = *self.origin.get_or_insert(nanos);
+        let elapsed = nanos.saturating_sub(origin);
+        Duration::new(
+            (elapsed / 1_000_000_000) as u64,
+            (elapsed % 1_000_000_000) as u32,
+        )
+    }
+}
+
+/// Convert `ticks` of the resolution of `if_tsresol` to nanoseconds
+fn nanos(ticks: u64, resolution: u8) -> u128 {
+    let ticks = u128::from(ticks);
+    let exponent = u32::from(resolution & 0x7f);
+    if resolution & 0x80 != 0 {
+        (ticks * 1_000_000_000) >> exponent.min(127)
+    } else if exponent <= 9 {
+        ticks * 10u128.pow(9 - exponent)
+    } else {
+        ticks / 10u128.pow(exponent.min(38) - 9)
+    }
+}
+
+/// Split a CSV line, unquoting its fields
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Parse a byte exported by Saleae Logic: `0x41`, `A`, `'A'` or an escape like `\r`
+fn saleae_value(value: &str) -> Option<u8> {
+    let value = match value {
+        v if v.len() >= 3 && v.starts_with('\'') && v.ends_with('\'') => &v[1..v.len() - 1],
+        // A space alone
+        v if v.len() == 1 => v,
+        v => v.trim(),
+    };
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return u8::from_str_radix(hex, 16).ok();
+    }
+    if let Some(hex) = value.strip_prefix("\\x") {
+        return u8::from_str_radix(hex, 16).ok();
+    }
+    match value {
+        "\\r" => Some(b'\r'),
+        "\\n" => Some(b'\n'),
+        "\\t" => Some(b'\t'),
+        "\\0" => Some(0),
+        "\\\\" => Some(b'\\'),
+        v if v.len() == 1 => Some(v.as_bytes()[0]),
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+//! Linked pseudo terminals for integration tests
+use serialport::{SerialPort, TTYPort};
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often the relay threads check whether the pair was dropped
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Two pseudo terminals whose slaves are linked like a null-modem cable
+///
+/// This replaces the `socat PTY,link=... PTY,link=...` setup commonly used for serial
+/// integration tests without requiring socat: two PTYs are opened natively and data is
+/// relayed between their masters by background threads.  The slave devices can be opened by
+/// path, by this process or any other, for as long as the pair is alive.  Dropping the pair
+/// stops the relay and removes the symbolic links created by [`PtyPair::with_links`].
+///
+/// ```no_run
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+/// use tokio_serial::test::PtyPair;
+/// use tokio_serial::SerialPortBuilderExt;
+///
+/// #[tokio::main]
+/// async fn main() -> tokio_serial::Result<()> {
+///     let pair = PtyPair::new()?;
+///     let mut a = tokio_serial::new(pair.port_a().to_string_lossy(), 9600).open_native_async()?;
+///     let mut b = tokio_serial::new(pair.port_b().to_string_lossy(), 9600).open_native_async()?;
+///
+///     a.write_all(b"ping").await?;
+///     let mut buf = [0u8; 4];
+///     b.read_exact(&mut buf).await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PtyPair {
+    port_a: PathBuf,
+    port_b: PathBuf,
+    links: Vec<PathBuf>,
+    stop: Arc<AtomicBool>,
+    relays: Vec<JoinHandle<()>>,
+    // Keeping the slaves open keeps the masters usable while nobody else has them open
+    _slaves: [TTYPort; 2],
+}
+
+impl PtyPair {
+    /// Create a linked pair, its ports are named by the pseudo terminal slave paths
+    pub fn new() -> crate::Result<Self> {
+        let (master_a, slave_a) = TTYPort::pair()?;
+        let (master_b, slave_b) = TTYPort::pair()?;
+        let port_a = slave_path(&slave_a)?;
+        let port_b = slave_path(&slave_b)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let relays = vec![
+            relay(
+                master_a.try_clone_native()?,
+                master_b.try_clone_native()?,
+                &stop,
+            )?,
+            relay(master_b, master_a, &stop)?,
+        ];
+
+        Ok(Self {
+            port_a,
+            port_b,
+            links: Vec::new(),
+            stop,
+            relays,
+            _slaves: [slave_a, slave_b],
+        })
+    }
+
+    /// Create a linked pair reachable through symbolic links at `port_a` and `port_b`
+    ///
+    /// Existing files at either path are replaced.
+    pub fn with_links(port_a: impl AsRef<Path>, port_b: impl AsRef<Path>) -> crate::Result<Self> {
+        let mut pair = Self::new()?;
+        for (target, link) in [
+            (pair.port_a.clone(), port_a.as_ref()),
+            (pair.port_b.clone(), port_b.as_ref()),
+        ]
+        .iter()
+        {
+            match std::fs::remove_file(link) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            std::os::unix::fs::symlink(target, link)?;
+            pair.links.push(link.to_path_buf());
+        }
+        pair.port_a = port_a.as_ref().to_path_buf();
+        pair.port_b = port_b.as_ref().to_path_buf();
+        Ok(pair)
+    }
+
+    /// Returns the path of the first port
+    pub fn port_a(&self) -> &Path {
+        &self.port_a
+    }
+
+    /// Returns the path of the second port
+    pub fn port_b(&self) -> &Path {
+        &self.port_b
+    }
+}
+
+impl Drop for PtyPair {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for relay in self.relays.drain(..) {
+            let _ = relay.join();
+        }
+        for link in self.links.iter() {
+            log::trace!("removing link: {}", link.display());
+            let _ = std::fs::remove_file(link);
+        }
+    }
+}
+
+fn slave_path(slave: &TTYPort) -> crate::Result<PathBuf> {
+    slave.name().map(PathBuf::from).ok_or_else(|| {
+        crate::Error::new(
+            crate::ErrorKind::Unknown,
+            "pseudo terminal slave has no name",
+        )
+    })
+}
+
+/// Copy everything read from `from` to `to` until `stop` is set
+fn relay(
+    mut from: TTYPort,
+    mut to: TTYPort,
+    stop: &Arc<AtomicBool>,
+) -> crate::Result<JoinHandle<()>> {
+    from.set_timeout(POLL_INTERVAL)?;
+    let stop = stop.clone();
+    let handle = std::thread::Builder::new()
+        .name("tokio-serial-pty-relay".into())
+        .spawn(move || {
+            let mut buf = [0u8; 1024];
+            while !stop.load(Ordering::Relaxed) {
+                match from.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if let Err(e) = to.write_all(&buf[..n]) {
+                            log::debug!("pty relay write failed: {}", e);
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        log::debug!("pty relay read failed: {}", e);
+                        break;
+                    }
+                }
+            }
+        })?;
+    Ok(handle)
+}
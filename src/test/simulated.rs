@@ -0,0 +1,545 @@
+//! Serial link simulated at the configured baud rate
+use super::{char_time, poll_now, LineState, Lines};
+use crate::settings::Settings;
+use crate::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, Read, Result as IoResult, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Default size of the transmit and receive buffers of each end
+pub const DEFAULT_DRIVER_BUFFER: usize = 4096;
+
+/// Handling of writes that don't fit in the transmit buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait until the line drains enough of the buffer
+    Block,
+    /// Report the data as written and discard it
+    Drop,
+    /// Fail the write with `io::ErrorKind::Other`
+    Error,
+}
+
+/// Builder for a [`SimulatedSerial`] pair
+#[derive(Debug, Clone)]
+pub struct SimulatedBuilder {
+    settings: crate::SerialPortBuilder,
+    driver_buffer: usize,
+    overflow: Overflow,
+}
+
+impl SimulatedBuilder {
+    /// Set the size of the transmit and receive buffers of each end
+    pub fn driver_buffer(mut self, size: usize) -> Self {
+        self.driver_buffer = size;
+        self
+    }
+
+    /// Set the handling of writes that don't fit in the transmit buffer
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Create the pair of connected ports
+    pub fn pair(self) -> (SimulatedSerial, SimulatedSerial) {
+        let settings = Settings::from_builder(&self.settings);
+        let end = End {
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            flow_control: settings.flow_control,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            closed: false,
+        };
+        let shared = Arc::new(Mutex::new(Shared {
+            ends: [end.clone(), end],
+            links: [Link::default(), Link::default()],
+            driver_buffer: self.driver_buffer,
+            overflow: self.overflow,
+        }));
+        let lines = Arc::new(Mutex::new([LineState::default(); 2]));
+        let port = |side| SimulatedSerial {
+            shared: shared.clone(),
+            side,
+            lines: Lines {
+                state: lines.clone(),
+                side,
+            },
+            name: settings.path.clone(),
+            timeout: Duration::from_secs(0),
+            read_sleep: None,
+            write_sleep: None,
+        };
+        (port(0), port(1))
+    }
+}
+
+/// One end of a simulated serial link
+///
+/// Data written to one end reaches the other one character time at a time, a character
+/// taking a start bit, the data bits, the parity bit and the stop bits at the writer's baud
+/// rate.  Each end has a transmit and a receive buffer of the same size: writes exceeding
+/// the transmit buffer are handled according to [`Overflow`], characters arriving at a full
+/// receive buffer are lost and counted by [`overruns`](SimulatedSerial::overruns).  If the
+/// two ends disagree on the baud rate or the character format, the received data is
+/// garbled.
+///
+/// Line time is measured with `tokio::time`, so tests using `tokio::time::pause()` run at
+/// full speed.  Control lines are connected like [`MockSerial::pair`](super::MockSerial::pair).
+///
+/// ```
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+/// use tokio_serial::test::SimulatedSerial;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let (mut a, mut b) = SimulatedSerial::pair(&tokio_serial::new("sim", 115_200));
+///     a.write_all(b"hello").await.unwrap();
+///     let mut buf = [0u8; 5];
+///     b.read_exact(&mut buf).await.unwrap();
+///     assert_eq!(&buf, b"hello");
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SimulatedSerial {
+    shared: Arc<Mutex<Shared>>,
+    side: usize,
+    lines: Lines,
+    name: String,
+    timeout: Duration,
+    read_sleep: Option<Pin<Box<Sleep>>>,
+    write_sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl SimulatedSerial {
+    /// Create a pair of connected ports using the settings in `builder`
+    ///
+    /// The ports use [`DEFAULT_DRIVER_BUFFER`] sized buffers and block on overflow.
+    pub fn pair(builder: &crate::SerialPortBuilder) -> (Self, Self) {
+        Self::builder(builder).pair()
+    }
+
+    /// Start configuring a pair of ports using the settings in `builder`
+    pub fn builder(builder: &crate::SerialPortBuilder) -> SimulatedBuilder {
+        SimulatedBuilder {
+            settings: builder.clone(),
+            driver_buffer: DEFAULT_DRIVER_BUFFER,
+            overflow: Overflow::Block,
+        }
+    }
+
+    /// Returns the number of characters lost because the receive buffer of this end was full
+    pub fn overruns(&self) -> u64 {
+        let mut shared = self.lock();
+        shared.advance(1 - self.side, Instant::now());
+        shared.links[1 - self.side].overruns
+    }
+
+    /// Advance the paused clock until both directions of the link have drained
+    ///
+    /// Returns the simulated line time that elapsed.  The clock must have been paused with
+    /// `tokio::time::pause()`.
+    pub async fn advance_until_idle(&self) -> Duration {
+        let now = Instant::now();
+        let remaining = {
+            let shared = self.lock();
+            (0..2)
+                .filter_map(|n| {
+                    let link = &shared.links[n];
+                    let due = link.next_due?;
+                    let queued = link.tx.len().saturating_sub(1) as u32;
+                    Some(due.saturating_duration_since(now) + shared.ends[n].char_time() * queued)
+                })
+                .max()
+                .unwrap_or_default()
+        };
+        tokio::time::advance(remaining).await;
+        remaining
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap()
+    }
+}
+
+/// Poll a timer for `deadline`, creating or resetting it as needed
+fn poll_deadline(
+    sleep: &mut Option<Pin<Box<Sleep>>>,
+    deadline: Instant,
+    cx: &mut Context<'_>,
+) -> Poll<()> {
+    let sleep = sleep.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+    if sleep.deadline() != deadline {
+        sleep.as_mut().reset(deadline);
+    }
+    sleep.as_mut().poll(cx)
+}
+
+#[derive(Debug)]
+struct Shared {
+    ends: [End; 2],
+    /// `links[n]` carries the data written by end `n`
+    links: [Link; 2],
+    driver_buffer: usize,
+    overflow: Overflow,
+}
+
+impl Shared {
+    /// Move the characters transmitted on link `n` by `now` to the receiving end
+    fn advance(&mut self, n: usize, now: Instant) {
+        let char_time = self.ends[n].char_time();
+        let garble = !self.ends[n].same_framing(&self.ends[1 - n]);
+        let capacity = self.driver_buffer;
+        let link = &mut self.links[n];
+
+        let mut moved = false;
+        while let Some(due) = link.next_due {
+            if due > now {
+                break;
+            }
+            let byte = link.tx.pop_front().expect("character in flight");
+            if link.rx.len() < capacity {
+                link.rx
+                    .push_back(if garble { !byte.rotate_left(3) } else { byte });
+            } else {
+                link.overruns += 1;
+            }
+            link.next_due = if link.tx.is_empty() {
+                None
+            } else {
+                Some(due + char_time)
+            };
+            moved = true;
+        }
+        if moved {
+            link.wake();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct End {
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+    closed: bool,
+}
+
+impl End {
+    fn char_time(&self) -> Duration {
+        char_time(self.baud_rate, self.data_bits, self.parity, self.stop_bits)
+    }
+
+    fn same_framing(&self, other: &End) -> bool {
+        self.baud_rate == other.baud_rate
+            && self.data_bits == other.data_bits
+            && self.parity == other.parity
+            && self.stop_bits == other.stop_bits
+    }
+}
+
+#[derive(Debug, Default)]
+struct Link {
+    /// Transmit buffer of the writing end, the first character is on the wire
+    tx: VecDeque<u8>,
+    /// Receive buffer of the reading end
+    rx: VecDeque<u8>,
+    /// When the character on the wire has been fully transmitted
+    next_due: Option<Instant>,
+    overruns: u64,
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+}
+
+impl Link {
+    fn wake(&mut self) {
+        for waker in [self.reader.take(), self.writer.take()].iter_mut() {
+            if let Some(waker) = waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for SimulatedSerial {
+    fn drop(&mut self) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.ends[self.side].closed = true;
+            shared.links[self.side].wake();
+        }
+    }
+}
+
+impl AsyncRead for SimulatedSerial {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        let peer = 1 - self.side;
+        loop {
+            let deadline = {
+                let mut shared = self.lock();
+                shared.advance(peer, Instant::now());
+                let closed = shared.ends[peer].closed;
+                let link = &mut shared.links[peer];
+                if !link.rx.is_empty() {
+                    let n = link.rx.len().min(buf.remaining());
+                    let data: Vec<u8> = link.rx.drain(..n).collect();
+                    buf.put_slice(&data);
+                    return Poll::Ready(Ok(()));
+                }
+                if closed && link.tx.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                link.reader = Some(cx.waker().clone());
+                match link.next_due {
+                    Some(deadline) => deadline,
+                    None => return Poll::Pending,
+                }
+            };
+            if poll_deadline(&mut self.read_sleep, deadline, cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl AsyncWrite for SimulatedSerial {
+    // `io::Error::other` needs a newer compiler than the MSRV
+    #[allow(clippy::io_other_error)]
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let side = self.side;
+        loop {
+            let deadline = {
+                let mut shared = self.lock();
+                let now = Instant::now();
+                shared.advance(side, now);
+                let char_time = shared.ends[side].char_time();
+                let free = shared.driver_buffer - shared.links[side].tx.len();
+                let overflow = shared.overflow;
+                let link = &mut shared.links[side];
+                if free > 0 {
+                    let n = free.min(buf.len());
+                    link.tx.extend(&buf[..n]);
+                    if link.next_due.is_none() {
+                        link.next_due = Some(now + char_time);
+                    }
+                    link.wake();
+                    return Poll::Ready(Ok(n));
+                }
+                match overflow {
+                    Overflow::Drop => return Poll::Ready(Ok(buf.len())),
+                    Overflow::Error => {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            "transmit buffer overflow",
+                        )))
+                    }
+                    Overflow::Block => {
+                        link.writer = Some(cx.waker().clone());
+                        link.next_due.expect("full buffer is transmitting")
+                    }
+                }
+            };
+            if poll_deadline(&mut self.write_sleep, deadline, cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    /// Wait until the transmit buffer has drained, like `tcdrain`
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let side = self.side;
+        loop {
+            let deadline = {
+                let mut shared = self.lock();
+                shared.advance(side, Instant::now());
+                let link = &mut shared.links[side];
+                match link.next_due {
+                    None => return Poll::Ready(Ok(())),
+                    Some(deadline) => {
+                        link.writer = Some(cx.waker().clone());
+                        deadline
+                    }
+                }
+            };
+            if poll_deadline(&mut self.write_sleep, deadline, cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl Read for SimulatedSerial {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        poll_now(|cx| {
+            let mut buf = ReadBuf::new(buf);
+            futures::ready!(Pin::new(&mut *self).poll_read(cx, &mut buf))?;
+            Poll::Ready(Ok(buf.filled().len()))
+        })
+    }
+}
+
+impl Write for SimulatedSerial {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        poll_now(|cx| Pin::new(&mut *self).poll_write(cx, buf))
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        poll_now(|cx| Pin::new(&mut *self).poll_flush(cx))
+    }
+}
+
+impl SerialPort for SimulatedSerial {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> crate::Result<u32> {
+        Ok(self.lock().ends[self.side].baud_rate)
+    }
+
+    fn data_bits(&self) -> crate::Result<DataBits> {
+        Ok(self.lock().ends[self.side].data_bits)
+    }
+
+    fn flow_control(&self) -> crate::Result<FlowControl> {
+        Ok(self.lock().ends[self.side].flow_control)
+    }
+
+    fn parity(&self) -> crate::Result<Parity> {
+        Ok(self.lock().ends[self.side].parity)
+    }
+
+    fn stop_bits(&self) -> crate::Result<StopBits> {
+        Ok(self.lock().ends[self.side].stop_bits)
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> crate::Result<()> {
+        self.lock().ends[self.side].baud_rate = baud_rate;
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> crate::Result<()> {
+        self.lock().ends[self.side].data_bits = data_bits;
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> crate::Result<()> {
+        self.lock().ends[self.side].flow_control = flow_control;
+        Ok(())
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> crate::Result<()> {
+        self.lock().ends[self.side].parity = parity;
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> crate::Result<()> {
+        self.lock().ends[self.side].stop_bits = stop_bits;
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> crate::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> crate::Result<()> {
+        self.lines.set(|lines| lines.rts = level);
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> crate::Result<()> {
+        self.lines.set(|lines| lines.dtr = level);
+        Ok(())
+    }
+
+    fn read_clear_to_send(&mut self) -> crate::Result<bool> {
+        Ok(self.lines.peer().rts)
+    }
+
+    fn read_data_set_ready(&mut self) -> crate::Result<bool> {
+        Ok(self.lines.peer().dtr)
+    }
+
+    fn read_ring_indicator(&mut self) -> crate::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_carrier_detect(&mut self) -> crate::Result<bool> {
+        Ok(self.lines.peer().dtr)
+    }
+
+    fn bytes_to_read(&self) -> crate::Result<u32> {
+        let mut shared = self.lock();
+        shared.advance(1 - self.side, Instant::now());
+        Ok(shared.links[1 - self.side].rx.len() as u32)
+    }
+
+    fn bytes_to_write(&self) -> crate::Result<u32> {
+        let mut shared = self.lock();
+        shared.advance(self.side, Instant::now());
+        Ok(shared.links[self.side].tx.len() as u32)
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> crate::Result<()> {
+        let mut shared = self.lock();
+        if let ClearBuffer::Input | ClearBuffer::All = buffer_to_clear {
+            shared.links[1 - self.side].rx.clear();
+        }
+        if let ClearBuffer::Output | ClearBuffer::All = buffer_to_clear {
+            let link = &mut shared.links[self.side];
+            link.tx.clear();
+            link.next_due = None;
+            link.wake();
+        }
+        Ok(())
+    }
+
+    /// Cloning SimulatedSerial is not supported.
+    ///
+    /// # Errors
+    /// Always returns `ErrorKind::Other` with a message.
+    fn try_clone(&self) -> crate::Result<Box<dyn SerialPort>> {
+        Err(crate::Error::new(
+            crate::ErrorKind::Io(io::ErrorKind::Other),
+            "Cannot clone SimulatedSerial",
+        ))
+    }
+
+    fn set_break(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    fn clear_break(&self) -> crate::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! Rate limiting for devices that choke on traffic sent back to back
+//!
+//! Some firmwares poll their UART between other work and lose commands arriving faster than
+//! they handle them, whatever the baud rate.  [`Throttle`] wraps a raw stream or a framed sink
+//! and holds writes back with token buckets:
+//!
+//! * bytes per second, for writes through `AsyncWrite`;
+//! * frames per second, counting each `AsyncWrite` call or each item sent to a `Sink`;
+//! * [chunks](Throttle::chunked) of a fixed size and spacing, for writes through
+//!   `AsyncWrite`.
+//!
+//! Each bucket starts full and holds up to its burst, so a quiet link may send a burst at
+//! once before the rate applies.  Reads and received frames go through untouched.
+//!
+//! ```no_run
+//! use tokio::io::AsyncWriteExt;
+//! use tokio_serial::throttle::Throttle;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> std::io::Result<()> {
+//! // At most 5 commands per second, one at a time, and no more than 100 bytes per second
+//! let mut port = Throttle::new(port).frames_per_sec(5, 1).bytes_per_sec(100, 16);
+//! port.write_all(b"MEAS:VOLT?\n").await?;
+//! port.write_all(b"MEAS:CURR?\n").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A `write_all` may take several writes when the byte rate cuts them short, each counting as
+//! a frame.  Wrapping a `SerialFramed` instead limits whole frames.
+use futures::{ready, Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, Instant, Sleep};
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Rate limited writes to `T`, see the [module](self) docs
+#[derive(Debug)]
+pub struct Throttle<T> {
+    inner: T,
+    bytes: Option<Bucket>,
+    frames: Option<Bucket>,
+    chunks: Option<Chunks>,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+/// Writes cut to `max` bytes, `delay` apart
+#[derive(Debug, Clone, Copy)]
+struct Chunks {
+    max: usize,
+    delay: Duration,
+    last: Option<Instant>,
+}
+
+/// Token bucket refilled at `rate` tokens per second up to `burst`
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    rate: u32,
+    burst: u32,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u32, burst: u32) -> Self {
+        assert!(rate > 0, "a throttle rate must not be zero");
+        let burst = burst.max(1);
+        Self {
+            rate,
+            burst,
+            tokens: f64::from(burst),
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Whole tokens available now
+    fn available(&mut self, now: Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(self.rate)).min(f64::from(self.burst));
+        self.refilled = now;
+        self.tokens as u32
+    }
+
+    fn take(&mut self, tokens: u32) {
+        self.tokens -= f64::from(tokens);
+    }
+
+    /// Time until the next whole token, once [`available`](Self::available) returned 0
+    fn wait(&self) -> Duration {
+        Duration::from_secs_f64((1.0 - self.tokens).max(0.0) / f64::from(self.rate))
+    }
+}
+
+impl<T> Throttle<T> {
+    /// Wrap `inner` without any limit yet
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            bytes: None,
+            frames: None,
+            chunks: None,
+            timer: None,
+        }
+    }
+
+    /// Limit writes to `rate` bytes per second, with bursts of up to `burst` bytes
+    ///
+    /// A single write is cut down to the bytes available, so `burst` is also the largest
+    /// write passed on at once.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is zero.
+    pub fn bytes_per_sec(mut self, rate: u32, burst: u32) -> Self {
+        self.bytes = Some(Bucket::new(rate, burst));
+        self
+    }
+
+    /// Limit writes or sent items to `rate` per second, with bursts of up to `burst`
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is zero.
+    pub fn frames_per_sec(mut self, rate: u32, burst: u32) -> Self {
+        self.frames = Some(Bucket::new(rate, burst));
+        self
+    }
+
+    /// Cut writes to at most `max_chunk` bytes, each written `inter_chunk_delay` after the
+    /// previous one
+    ///
+    /// Some USB bootloaders lose data handed to them more than a USB packet, often 64 bytes,
+    /// at a time.  Only writes through `AsyncWrite` are cut, items sent to a `Sink` are left
+    /// whole.
+    ///
+    /// # Panics
+    ///
+    /// If `max_chunk` is zero.
+    pub fn chunked(mut self, max_chunk: usize, inter_chunk_delay: Duration) -> Self {
+        assert!(max_chunk > 0, "a chunk must hold at least one byte");
+        self.chunks = Some(Chunks {
+            max: max_chunk,
+            delay: inter_chunk_delay,
+            last: None,
+        });
+        self
+    }
+
+    /// Returns a reference to the throttled stream or sink
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the throttled stream or sink
+    ///
+    /// Writes made directly to it are not counted.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consumes the throttle, returning the stream or sink
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Wait for a frame token and up to `len` byte tokens, returning the bytes allowed
+    fn poll_tokens(&mut self, cx: &mut Context<'_>, len: usize) -> Poll<usize> {
+        loop {
+            if let Some(timer) = self.timer.as_mut() {
+                ready!(timer.as_mut().poll(cx));
+                self.timer = None;
+            }
+
+            let now = Instant::now();
+            let mut wait = Duration::from_secs(0);
+            if let Some(frames) = self.frames.as_mut() {
+                if frames.available(now) == 0 {
+                    wait = wait.max(frames.wait());
+                }
+            }
+            let mut allowed = len;
+            if let Some(chunks) = self.chunks {
+                if let Some(last) = chunks.last {
+                    wait = wait.max((last + chunks.delay).saturating_duration_since(now));
+                }
+                allowed = allowed.min(chunks.max);
+            }
+            if let Some(bytes) = self.bytes.as_mut() {
+                match bytes.available(now) {
+                    0 if len > 0 => wait = wait.max(bytes.wait()),
+                    available => allowed = allowed.min(available as usize),
+                }
+            }
+            if wait == Duration::from_secs(0) {
+                return Poll::Ready(allowed);
+            }
+            self.timer = Some(Box::pin(sleep(wait)));
+        }
+    }
+
+    /// Account for a write of `len` bytes or an item sent
+    fn spend(&mut self, len: usize) {
+        if let Some(frames) = self.frames.as_mut() {
+            frames.take(1);
+        }
+        if let Some(bytes) = self.bytes.as_mut() {
+            bytes.take(len as u32);
+        }
+        if let Some(chunks) = self.chunks.as_mut() {
+            chunks.last = Some(Instant::now());
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Throttle<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Throttle<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let allowed = ready!(this.poll_tokens(cx, buf.len()));
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..allowed]))?;
+        this.spend(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T: Stream + Unpin> Stream for Throttle<T> {
+    type Item = T::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner).poll_next(cx)
+    }
+}
+
+/// Items sent count against the frame rate only, their size is not known here
+impl<I, T: Sink<I> + Unpin> Sink<I> for Throttle<T> {
+    type Error = T::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(Pin::new(&mut this.inner).poll_ready(cx))?;
+        if let Some(frames) = this.frames.as_mut() {
+            while frames.available(Instant::now()) == 0 {
+                let timer = this
+                    .timer
+                    .get_or_insert_with(|| Box::pin(sleep(frames.wait())));
+                ready!(timer.as_mut().poll(cx));
+                this.timer = None;
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).start_send(item)?;
+        if let Some(frames) = this.frames.as_mut() {
+            frames.take(1);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
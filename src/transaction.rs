@@ -0,0 +1,397 @@
+//! Request/response exchanges over a framed port
+//!
+//! Most serial devices answer one request at a time: a request is sent, the reply is awaited
+//! and only then may the next request go out.  [`Transactor`] runs these exchanges over
+//! anything that is both a `Sink` of requests and a `Stream` of responses, typically a
+//! [`SerialFramed`](crate::frame::SerialFramed).
+//!
+//! Many devices also need the line to stay quiet for a while after they answered before they
+//! listen again: Modbus RTU slaves need 3.5 character times, legacy PLCs often several
+//! milliseconds.  The transactor enforces this [quiet time](Transactor::set_quiet_time) for
+//! every request it sends, so callers don't have to sprinkle sleeps around.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tokio_serial::frame::SerialFramed;
+//! use tokio_serial::transaction::Transactor;
+//! use tokio_util::codec::LinesCodec;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> Result<(), Box<dyn std::error::Error>> {
+//! let mut plc = Transactor::new(SerialFramed::new(port, LinesCodec::new()));
+//! plc.set_quiet_time(Duration::from_millis(20));
+//! plc.set_timeout(Some(Duration::from_millis(500)));
+//! let status = plc.request("STATUS?").await?;
+//! let level = plc.request("LEVEL?").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Pipelining
+//!
+//! Protocols tagging each frame with a sequence number or transaction id allow several
+//! requests to be outstanding at once, their responses coming back in any order.  A
+//! [`Pipeline`] extracts this correlation key from requests and responses and routes each
+//! response to the task awaiting it through the cloneable [`PipelineHandle`]:
+//!
+//! ```no_run
+//! use tokio_serial::frame::SerialFramed;
+//! use tokio_serial::transaction::Pipeline;
+//! use tokio_util::codec::LinesCodec;
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> Result<(), Box<dyn std::error::Error>> {
+//! // Requests like `17 READ 4` are answered by `17 <value>`
+//! let key = |frame: &String| frame.split(' ').next().map(str::to_owned);
+//! let (pipeline, handle) = Pipeline::new(
+//!     SerialFramed::new(port, LinesCodec::new()),
+//!     move |request: &String| key(request).unwrap_or_default(),
+//!     key,
+//! );
+//! tokio::spawn(pipeline.run());
+//! let (a, b) = futures::join!(
+//!     handle.request("17 READ 4".to_string()),
+//!     handle.request("18 READ 5".to_string()),
+//! );
+//! # Ok(())
+//! # }
+//! ```
+use futures::channel::{mpsc, oneshot};
+use futures::future::poll_fn;
+use futures::{Future, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use tokio::time::{sleep_until, timeout, Instant};
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::task::Poll;
+use std::time::Duration;
+use std::{fmt, io};
+
+/// Runs one request/response exchange at a time, see the [module](self) docs
+#[derive(Debug)]
+pub struct Transactor<F> {
+    framed: F,
+    quiet_time: Duration,
+    timeout: Option<Duration>,
+    // When the line went quiet after the last exchange
+    quiet_since: Option<Instant>,
+    // The last request sent got no response, it may still come
+    unanswered: bool,
+}
+
+impl<F> Transactor<F> {
+    /// Run exchanges over `framed`, with no quiet time and no timeout
+    pub fn new(framed: F) -> Self {
+        Self {
+            framed,
+            quiet_time: Duration::from_secs(0),
+            timeout: None,
+            quiet_since: None,
+            unanswered: false,
+        }
+    }
+
+    /// Leave the line quiet for at least `quiet_time` after each response
+    ///
+    /// The quiet time is counted from the moment a response was received, or from the timeout
+    /// of an exchange that got no response.  A request made earlier waits for it to pass.
+    pub fn set_quiet_time(&mut self, quiet_time: Duration) {
+        self.quiet_time = quiet_time;
+    }
+
+    /// Returns the quiet time after each response
+    pub fn quiet_time(&self) -> Duration {
+        self.quiet_time
+    }
+
+    /// Give up on a response after `timeout`, or wait forever with `None`
+    ///
+    /// The timeout starts once the request was sent.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the response timeout
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns a reference to the framed port
+    pub fn get_ref(&self) -> &F {
+        &self.framed
+    }
+
+    /// Returns a mutable reference to the framed port
+    ///
+    /// Frames sent through it don't wait for the quiet time.
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.framed
+    }
+
+    /// Consumes the transactor, returning the framed port
+    pub fn into_inner(self) -> F {
+        self.framed
+    }
+
+    /// Send `request` once the quiet time passed and wait for its response
+    ///
+    /// A response coming after its request [timed out](Self::set_timeout), or after the
+    /// future of its request was dropped, is discarded if it arrived before the next request
+    /// is sent, the quiet time included.  Later, it can't be told from the response to the
+    /// next request.
+    ///
+    /// # Errors
+    ///
+    /// The errors of the sink and stream, `TimedOut` when no response came within the
+    /// [timeout](Self::set_timeout) and `UnexpectedEof` when the stream ended.
+    pub async fn request<Req, Resp, E>(&mut self, request: Req) -> Result<Resp, E>
+    where
+        F: Sink<Req, Error = E> + Stream<Item = Result<Resp, E>> + Unpin,
+        E: From<io::Error>,
+    {
+        if let Some(quiet_since) = self.quiet_since {
+            sleep_until(quiet_since + self.quiet_time).await;
+        }
+        if self.unanswered {
+            self.discard_received();
+        }
+        self.framed.send(request).await?;
+        self.unanswered = true;
+
+        let response = match self.timeout {
+            Some(limit) => match timeout(limit, self.framed.next()).await {
+                Ok(response) => response,
+                Err(_) => {
+                    self.quiet_since = Some(Instant::now());
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "no response").into());
+                }
+            },
+            None => self.framed.next().await,
+        };
+        self.quiet_since = Some(Instant::now());
+        self.unanswered = false;
+        response.unwrap_or_else(|| Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()))
+    }
+
+    /// Drop the frames already received, late responses to an unanswered request
+    fn discard_received<Resp, E>(&mut self)
+    where
+        F: Stream<Item = Result<Resp, E>> + Unpin,
+    {
+        while let Some(Some(_)) = self.framed.next().now_or_never() {
+            log::debug!("discarding a late response");
+        }
+        self.unanswered = false;
+    }
+}
+
+type Pending<Req, Resp> = (Req, oneshot::Sender<io::Result<Resp>>);
+/// Extracts the key of a response, `None` for frames answering no request
+type ResponseKey<Resp, K> = Box<dyn Fn(&Resp) -> Option<K> + Send>;
+
+/// Sends requests to a [`Pipeline`]
+pub struct PipelineHandle<Req, Resp> {
+    requests: mpsc::UnboundedSender<Pending<Req, Resp>>,
+}
+
+impl<Req, Resp> PipelineHandle<Req, Resp> {
+    /// Send `request` and wait for the response with the same key
+    ///
+    /// # Errors
+    ///
+    /// * `TimedOut` when no response came within the [timeout](Pipeline::set_timeout).
+    /// * `AlreadyExists` if a request with the same key is outstanding.
+    /// * `BrokenPipe` if the pipeline stopped before the response came.
+    pub async fn request(&self, request: Req) -> io::Result<Resp> {
+        let (tx, rx) = oneshot::channel();
+        // A pipeline that is gone drops the sender, reported below
+        let _ = self.requests.unbounded_send((request, tx));
+        rx.await.unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "pipeline stopped before the response came",
+            ))
+        })
+    }
+}
+
+impl<Req, Resp> Clone for PipelineHandle<Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+        }
+    }
+}
+
+impl<Req, Resp> fmt::Debug for PipelineHandle<Req, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipelineHandle").finish_non_exhaustive()
+    }
+}
+
+/// Runs concurrent exchanges matched by a correlation key, see the [module](self) docs
+pub struct Pipeline<F, Req, Resp, K> {
+    framed: F,
+    requests: mpsc::UnboundedReceiver<Pending<Req, Resp>>,
+    request_key: Box<dyn Fn(&Req) -> K + Send>,
+    response_key: ResponseKey<Resp, K>,
+    timeout: Option<Duration>,
+    max_in_flight: usize,
+}
+
+impl<F, Req, Resp, K> Pipeline<F, Req, Resp, K> {
+    /// Run exchanges over `framed`, returning the pipeline and a handle sending requests
+    ///
+    /// `request_key` returns the key of a request, `response_key` the key of a response or
+    /// `None` for frames that answer no request, which are dropped.
+    pub fn new<RK, SK>(
+        framed: F,
+        request_key: RK,
+        response_key: SK,
+    ) -> (Self, PipelineHandle<Req, Resp>)
+    where
+        RK: Fn(&Req) -> K + Send + 'static,
+        SK: Fn(&Resp) -> Option<K> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded();
+        let pipeline = Self {
+            framed,
+            requests: rx,
+            request_key: Box::new(request_key),
+            response_key: Box::new(response_key),
+            timeout: None,
+            max_in_flight: usize::MAX,
+        };
+        (pipeline, PipelineHandle { requests: tx })
+    }
+
+    /// Give up on a response after `timeout`, or wait forever with `None`, the default
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the response timeout
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Hold back further requests while `max` are outstanding, unlimited by default
+    ///
+    /// Devices often buffer only a few requests.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max` is 0.
+    pub fn set_max_in_flight(&mut self, max: usize) {
+        assert!(max > 0, "at least one request must be allowed in flight");
+        self.max_in_flight = max;
+    }
+
+    /// Returns how many requests may be outstanding
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+}
+
+enum Event<Req, Resp, E> {
+    Request(Option<Pending<Req, Resp>>),
+    Response(Option<Result<Resp, E>>),
+    Timeout,
+}
+
+impl<F, Req, Resp, K, E> Pipeline<F, Req, Resp, K>
+where
+    F: Sink<Req, Error = E> + Stream<Item = Result<Resp, E>> + Unpin,
+    K: Eq + Hash,
+{
+    /// Run the exchanges until every handle was dropped and no request is outstanding
+    ///
+    /// # Errors
+    ///
+    /// The errors of the sink and stream, which end the pipeline.  Outstanding requests then
+    /// fail with `BrokenPipe`, as they do when the stream ends.
+    pub async fn run(mut self) -> Result<(), E> {
+        let mut in_flight: HashMap<K, (Option<Instant>, oneshot::Sender<io::Result<Resp>>)> =
+            HashMap::new();
+        let mut handles_gone = false;
+        let mut sleep = Box::pin(sleep_until(Instant::now()));
+        loop {
+            if handles_gone && in_flight.is_empty() {
+                return Ok(());
+            }
+            let deadline = in_flight
+                .values()
+                .filter_map(|(deadline, _)| *deadline)
+                .min();
+            if let Some(deadline) = deadline {
+                sleep.as_mut().reset(deadline);
+            }
+            let accepting = !handles_gone && in_flight.len() < self.max_in_flight;
+
+            let event = poll_fn(|cx| {
+                if let Poll::Ready(response) = self.framed.poll_next_unpin(cx) {
+                    return Poll::Ready(Event::Response(response));
+                }
+                if accepting {
+                    if let Poll::Ready(request) = self.requests.poll_next_unpin(cx) {
+                        return Poll::Ready(Event::Request(request));
+                    }
+                }
+                if deadline.is_some() && sleep.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(Event::Timeout);
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match event {
+                Event::Request(None) => handles_gone = true,
+                Event::Request(Some((request, tx))) => {
+                    let key = (self.request_key)(&request);
+                    if in_flight.contains_key(&key) {
+                        let _ = tx.send(Err(io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            "a request with the same key is outstanding",
+                        )));
+                        continue;
+                    }
+                    self.framed.send(request).await?;
+                    let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+                    in_flight.insert(key, (deadline, tx));
+                }
+                Event::Response(None) => return Ok(()),
+                Event::Response(Some(response)) => {
+                    let response = response?;
+                    let waiting =
+                        (self.response_key)(&response).and_then(|key| in_flight.remove(&key));
+                    match waiting {
+                        Some((_, tx)) => {
+                            let _ = tx.send(Ok(response));
+                        }
+                        None => log::debug!("dropped a response answering no outstanding request"),
+                    }
+                }
+                Event::Timeout => {
+                    let now = Instant::now();
+                    let (expired, outstanding): (Vec<_>, Vec<_>) =
+                        in_flight.drain().partition(|(_, (deadline, _))| {
+                            deadline.is_some_and(|deadline| deadline <= now)
+                        });
+                    in_flight.extend(outstanding);
+                    for (_, (_, tx)) in expired {
+                        let _ =
+                            tx.send(Err(io::Error::new(io::ErrorKind::TimedOut, "no response")));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<F: fmt::Debug, Req, Resp, K> fmt::Debug for Pipeline<F, Req, Resp, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("framed", &self.framed)
+            .field("timeout", &self.timeout)
+            .field("max_in_flight", &self.max_in_flight)
+            .finish_non_exhaustive()
+    }
+}
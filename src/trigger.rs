@@ -0,0 +1,246 @@
+//! Acting on patterns seen in a decoded stream
+//!
+//! Watching a device's log output for `Error`, a boot banner or a test verdict and doing
+//! something about it is a common use of a serial port.  A [`Trigger`] holds rules, each a
+//! byte string or regular expression [`Pattern`] with a debounce time, that either emit an
+//! event of the caller's type or call an async callback.  Matches of a rule within its
+//! debounce time of the last one it fired for are ignored, so a device repeating an error
+//! in a tight loop doesn't flood the actions.
+//!
+//! Items are checked as bytes, so the stream may be of `String` lines as well as of
+//! `Bytes` frames.  [`feed`](Trigger::feed) checks one item for a loop that also processes
+//! the items itself, [`run`](Trigger::run) consumes a whole stream.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use std::time::Duration;
+//! use tokio_serial::frame::SerialFramed;
+//! use tokio_serial::trigger::{Pattern, Trigger};
+//! use tokio_util::codec::LinesCodec;
+//!
+//! #[derive(Debug, Clone)]
+//! enum Alert {
+//!     Booted,
+//!     Failure,
+//! }
+//!
+//! # async fn run(port: tokio_serial::SerialStream) -> Result<(), Box<dyn std::error::Error>> {
+//! let mut trigger = Trigger::new();
+//! trigger.emit(Pattern::bytes("Using Zephyr OS"), Duration::from_secs(0), Alert::Booted);
+//! trigger.emit(Pattern::regex(r"(?i)error|panic")?, Duration::from_secs(5), Alert::Failure);
+//! trigger.call(Pattern::bytes("Warning"), Duration::from_secs(1), |m| async move {
+//!     println!("warning at {:?}", m.at);
+//! });
+//!
+//! let mut lines = SerialFramed::new(port, LinesCodec::new());
+//! while let Some(line) = lines.next().await {
+//!     let line = line?;
+//!     println!("{}", line);
+//!     for alert in trigger.feed(&line).await {
+//!         println!("{:?}", alert);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::{Future, FutureExt, Stream, StreamExt};
+use tokio::time::Instant;
+
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
+
+/// What a rule looks for in each item
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// A byte string found anywhere in the item
+    Bytes(Vec<u8>),
+    /// A regular expression matched against the item's bytes
+    Regex(regex::bytes::Regex),
+}
+
+impl Pattern {
+    /// Look for `bytes`, a `&str` or byte string
+    pub fn bytes(bytes: impl AsRef<[u8]>) -> Self {
+        Pattern::Bytes(bytes.as_ref().to_vec())
+    }
+
+    /// Look for matches of the regular expression `regex`
+    pub fn regex(regex: &str) -> Result<Self, regex::Error> {
+        regex::bytes::Regex::new(regex).map(Pattern::Regex)
+    }
+
+    /// Returns the range of the first match in `haystack`
+    pub fn find(&self, haystack: &[u8]) -> Option<Range<usize>> {
+        match self {
+            Pattern::Bytes(needle) if needle.is_empty() => Some(0..0),
+            Pattern::Bytes(needle) => haystack
+                .windows(needle.len())
+                .position(|window| window == &needle[..])
+                .map(|start| start..start + needle.len()),
+            Pattern::Regex(regex) => regex.find(haystack).map(|m| m.range()),
+        }
+    }
+}
+
+/// An item matching a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// The bytes of the whole item
+    pub item: Vec<u8>,
+    /// Where the pattern matched in the item
+    pub range: Range<usize>,
+    /// When the item was checked
+    pub at: Instant,
+}
+
+impl Match {
+    /// Returns the matched bytes
+    pub fn matched(&self) -> &[u8] {
+        &self.item[self.range.clone()]
+    }
+}
+
+type Callback = Box<dyn FnMut(Match) -> BoxFuture<'static, ()> + Send>;
+
+enum Action<K> {
+    Emit(K),
+    Call(Callback),
+}
+
+struct Rule<K> {
+    pattern: Pattern,
+    debounce: Duration,
+    last_fired: Option<Instant>,
+    action: Action<K>,
+}
+
+/// Emits events and calls callbacks on pattern matches, see the [module](self) docs
+pub struct Trigger<K> {
+    rules: Vec<Rule<K>>,
+}
+
+impl<K> Trigger<K> {
+    /// Create a trigger without rules
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Emit `event` for items matching `pattern`, at most once per `debounce`
+    pub fn emit(&mut self, pattern: Pattern, debounce: Duration, event: K) -> &mut Self {
+        self.add(pattern, debounce, Action::Emit(event))
+    }
+
+    /// Call `callback` for items matching `pattern`, at most once per `debounce`
+    ///
+    /// The callback's future is awaited before the next item is checked.  Spawn a task from
+    /// it for actions that take long.
+    pub fn call<F, Fut>(
+        &mut self,
+        pattern: Pattern,
+        debounce: Duration,
+        mut callback: F,
+    ) -> &mut Self
+    where
+        F: FnMut(Match) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let callback: Callback = Box::new(move |m| callback(m).boxed());
+        self.add(pattern, debounce, Action::Call(callback))
+    }
+
+    fn add(&mut self, pattern: Pattern, debounce: Duration, action: Action<K>) -> &mut Self {
+        self.rules.push(Rule {
+            pattern,
+            debounce,
+            last_fired: None,
+            action,
+        });
+        self
+    }
+
+    /// Returns the number of rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns `true` if there is no rule
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+impl<K: Clone> Trigger<K> {
+    /// Check `item` against every rule, in the order they were added, returning the events
+    /// emitted once the callbacks called are done
+    pub async fn feed(&mut self, item: impl AsRef<[u8]>) -> Vec<K> {
+        let item = item.as_ref();
+        let now = Instant::now();
+        let mut events = Vec::new();
+        for rule in self.rules.iter_mut() {
+            let range = match rule.pattern.find(item) {
+                Some(range) => range,
+                None => continue,
+            };
+            if rule
+                .last_fired
+                .is_some_and(|last| now.duration_since(last) < rule.debounce)
+            {
+                continue;
+            }
+            rule.last_fired = Some(now);
+            match &mut rule.action {
+                Action::Emit(event) => events.push(event.clone()),
+                Action::Call(callback) => {
+                    let m = Match {
+                        item: item.to_vec(),
+                        range,
+                        at: now,
+                    };
+                    callback(m).await;
+                }
+            }
+        }
+        events
+    }
+
+    /// Check every item of `stream` until it ends, sending the events emitted to `events`
+    ///
+    /// Events are dropped once `events`' receiver is.
+    ///
+    /// # Errors
+    ///
+    /// The first error of the stream, which ends the run.
+    pub async fn run<S, T, E>(
+        mut self,
+        mut stream: S,
+        events: mpsc::UnboundedSender<K>,
+    ) -> Result<(), E>
+    where
+        S: Stream<Item = Result<T, E>> + Unpin,
+        T: AsRef<[u8]>,
+    {
+        while let Some(item) = stream.next().await {
+            for event in self.feed(item?).await {
+                let _ = events.unbounded_send(event);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K> Default for Trigger<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> fmt::Debug for Trigger<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.rules.iter().map(|rule| (&rule.pattern, rule.debounce)))
+            .finish()
+    }
+}
@@ -0,0 +1,129 @@
+//! Serial ports driven by io_uring
+//!
+//! [`UringSerialStream`] submits reads and writes on the port to io_uring through
+//! `tokio-uring`, instead of waiting for readiness with epoll and then issuing a `read`.  On
+//! gateways moving data on many ports at high rates this halves the number of system calls
+//! per transfer and removes the wakeup between readiness and data.
+//!
+//! Like every `tokio-uring` resource, the stream must be used inside `tokio_uring::start`,
+//! and its operations take ownership of the buffer and hand it back with the result:
+//!
+//! ```no_run
+//! use tokio_serial::uring::UringSerialStream;
+//!
+//! fn main() -> tokio_serial::Result<()> {
+//!     tokio_uring::start(async {
+//!         let port = UringSerialStream::open(&tokio_serial::new("/dev/ttyUSB0", 115200))?;
+//!         let (result, buf) = port.read(vec![0u8; 256]).await;
+//!         println!("{:?}", &buf[..result?]);
+//!         Ok(())
+//!     })
+//! }
+//! ```
+use crate::SerialPortBuilder;
+
+use tokio_uring::buf::{IoBuf, IoBufMut};
+use tokio_uring::fs::File;
+use tokio_uring::BufResult;
+
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+/// A serial port whose reads and writes are submitted to io_uring
+///
+/// Settings are changed through [`port`](Self::port) and [`port_mut`](Self::port_mut).  The
+/// file descriptor is in blocking mode: the reads in flight wait in the kernel, not in the
+/// runtime.
+#[derive(Debug)]
+pub struct UringSerialStream {
+    file: File,
+    port: serialport::TTYPort,
+}
+
+impl UringSerialStream {
+    /// Open the port described by `builder`
+    ///
+    /// Must be called inside `tokio_uring::start`.
+    pub fn open(builder: &SerialPortBuilder) -> crate::Result<Self> {
+        Self::from_port(serialport::TTYPort::open(builder)?)
+    }
+
+    /// Create a pair of pseudo terminals, see [`SerialStream::pair`](crate::SerialStream::pair)
+    ///
+    /// Must be called inside `tokio_uring::start`.
+    pub fn pair() -> crate::Result<(Self, Self)> {
+        let (master, slave) = serialport::TTYPort::pair()?;
+        Ok((Self::from_port(master)?, Self::from_port(slave)?))
+    }
+
+    fn from_port(port: serialport::TTYPort) -> crate::Result<Self> {
+        // The descriptor stays owned by the `TTYPort`, which applies the settings
+        // SAFETY: plain call, the result is checked
+        let fd = unsafe { libc::dup(port.as_raw_fd()) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: the duplicate is owned by nothing else
+        let file = File::from_std(unsafe { std::fs::File::from_raw_fd(fd) });
+        Ok(Self { file, port })
+    }
+
+    /// Read into `buf`, returning the number of bytes read with the buffer
+    ///
+    /// A hangup is reported as end of file, like the reads of
+    /// [`SerialStream`](crate::SerialStream).
+    pub async fn read<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        // Terminals ignore the offset, zero is what non-seekable files require
+        let (result, buf) = self.file.read_at(buf, 0).await;
+        match result {
+            Err(e) if crate::is_hangup(&e) => (Ok(0), buf),
+            result => (result, buf),
+        }
+    }
+
+    /// Write from `buf`, returning the number of bytes written with the buffer
+    pub async fn write<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        self.file.write_at(buf, 0).await
+    }
+
+    /// Write all of `buf`
+    pub async fn write_all<T: IoBuf>(&self, buf: T) -> BufResult<(), T> {
+        let mut written = 0;
+        let mut buf = buf;
+        while written < buf.bytes_init() {
+            let (result, slice) = self.file.write_at(buf.slice(written..), 0).await;
+            buf = slice.into_inner();
+            match result {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => written += n,
+                Err(e) => return (Err(e), buf),
+            }
+        }
+        (Ok(()), buf)
+    }
+
+    /// Returns the port, for reading its settings
+    pub fn port(&self) -> &serialport::TTYPort {
+        &self.port
+    }
+
+    /// Returns the port, for changing its settings
+    pub fn port_mut(&mut self) -> &mut serialport::TTYPort {
+        &mut self.port
+    }
+
+    /// Close the port, waiting for the descriptor used by io_uring to be closed
+    pub async fn close(self) -> io::Result<()> {
+        self.file.close().await
+    }
+}
+
+impl AsRawFd for UringSerialStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.port.as_raw_fd()
+    }
+}
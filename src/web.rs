@@ -0,0 +1,249 @@
+//! Serial ports in the browser through the Web Serial API
+//!
+//! [`WebSerialStream`] implements `AsyncRead` and `AsyncWrite` on top of the readable and
+//! writable streams of a Web Serial `SerialPort`, so codecs and protocol layers written
+//! against `tokio-serial` run unchanged in a browser based device configurator.
+//!
+//! The bindings of `web-sys` for Web Serial are unstable: build with
+//! `RUSTFLAGS=--cfg=web_sys_unstable_apis`.  Browsers only hand out ports in response to a
+//! user gesture, through [`request_port`]:
+//!
+//! ```ignore
+//! use tokio::io::AsyncWriteExt;
+//! use tokio_serial::web::{request_port, WebSerialStream};
+//!
+//! // Called from a click handler
+//! async fn connect() -> std::io::Result<WebSerialStream> {
+//!     let port = request_port().await?;
+//!     let mut port = WebSerialStream::open(port, &tokio_serial::new("", 115200)).await?;
+//!     port.write_all(b"AT\r").await?;
+//!     Ok(port)
+//! }
+//! ```
+//!
+//! The stream lives on the browser's event loop, so unlike `SerialStream` it is neither
+//! `Send` nor a [`SerialPort`](crate::SerialPort).
+use crate::settings::Settings;
+use crate::{DataBits, FlowControl, Parity, SerialPortBuilder, StopBits};
+
+use futures::ready;
+use js_sys::{Reflect, Uint8Array};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    FlowControlType, ParityType, ReadableStreamDefaultReader, SerialOptions, SerialPort,
+    WritableStreamDefaultWriter,
+};
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Ask the user to pick a serial port
+///
+/// Must be called while handling a user gesture like a click, the browser refuses otherwise.
+pub async fn request_port() -> io::Result<SerialPort> {
+    let window = web_sys::window()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "Web Serial needs a window"))?;
+    let serial = window.navigator().serial();
+    JsFuture::from(serial.request_port())
+        .await
+        .map_err(js_error)
+}
+
+/// A Web Serial port, see the [module](self) docs
+pub struct WebSerialStream {
+    port: SerialPort,
+    reader: ReadableStreamDefaultReader,
+    writer: WritableStreamDefaultWriter,
+    // The chunk being read and what is left of the last one
+    read: Option<JsFuture>,
+    chunk: Vec<u8>,
+    consumed: usize,
+    eof: bool,
+    // Writes are queued by the stream, only the last one is waited for
+    write: Option<JsFuture>,
+}
+
+impl WebSerialStream {
+    /// Open `port` with the line settings of `builder`
+    ///
+    /// Web Serial has no software flow control, asking for it is an error.  The path and
+    /// timeout of the builder are ignored.
+    pub async fn open(port: SerialPort, builder: &SerialPortBuilder) -> io::Result<Self> {
+        let settings = Settings::from_builder(builder);
+        let options = SerialOptions::new(settings.baud_rate);
+        options.set_data_bits(match settings.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        });
+        options.set_parity(match settings.parity {
+            Parity::None => ParityType::None,
+            Parity::Odd => ParityType::Odd,
+            Parity::Even => ParityType::Even,
+        });
+        options.set_stop_bits(match settings.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        });
+        options.set_flow_control(match settings.flow_control {
+            FlowControl::None => FlowControlType::None,
+            FlowControl::Hardware => FlowControlType::Hardware,
+            FlowControl::Software => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Web Serial has no software flow control",
+                ))
+            }
+        });
+        JsFuture::from(port.open(&options))
+            .await
+            .map_err(js_error)?;
+
+        let reader = ReadableStreamDefaultReader::new(&port.readable()).map_err(js_error)?;
+        let writer = WritableStreamDefaultWriter::new(&port.writable()).map_err(js_error)?;
+        Ok(Self {
+            port,
+            reader,
+            writer,
+            read: None,
+            chunk: Vec::new(),
+            consumed: 0,
+            eof: false,
+            write: None,
+        })
+    }
+
+    /// Returns the Web Serial port, for its signals and information
+    pub fn port(&self) -> &SerialPort {
+        &self.port
+    }
+
+    /// Close the port after the queued writes went out
+    pub async fn close(self) -> io::Result<()> {
+        if let Some(write) = self.write {
+            write.await.map_err(js_error)?;
+        }
+        JsFuture::from(self.reader.cancel())
+            .await
+            .map_err(js_error)?;
+        JsFuture::from(self.writer.close())
+            .await
+            .map_err(js_error)?;
+        self.reader.release_lock();
+        self.writer.release_lock();
+        JsFuture::from(self.port.close()).await.map_err(js_error)?;
+        Ok(())
+    }
+
+    /// Wait for the next chunk, returning `false` at the end of the stream
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        let reader = &self.reader;
+        let read = self
+            .read
+            .get_or_insert_with(|| JsFuture::from(reader.read()));
+        let result = ready!(Pin::new(read).poll(cx));
+        self.read = None;
+        let result = result.map_err(js_error)?;
+
+        let done = Reflect::get(&result, &JsValue::from_str("done")).map_err(js_error)?;
+        if done.as_bool().unwrap_or(false) {
+            return Poll::Ready(Ok(false));
+        }
+        let value = Reflect::get(&result, &JsValue::from_str("value")).map_err(js_error)?;
+        let value: Uint8Array = value.dyn_into().map_err(js_error)?;
+        self.chunk = value.to_vec();
+        self.consumed = 0;
+        Poll::Ready(Ok(true))
+    }
+}
+
+impl AsyncRead for WebSerialStream {
+    /// Attempts to read bytes from the port
+    ///
+    /// End of file means the port went away, like an unplugged adapter.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while this.consumed == this.chunk.len() {
+            if this.eof || !ready!(this.poll_chunk(cx))? {
+                this.eof = true;
+                return Poll::Ready(Ok(()));
+            }
+        }
+        let available = &this.chunk[this.consumed..];
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        this.consumed += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for WebSerialStream {
+    /// Queue `buf` on the port
+    ///
+    /// The bytes are copied into the stream's queue at once, an error of the write shows up
+    /// in the next write or flush.
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        let chunk = Uint8Array::from(buf);
+        let write = JsFuture::from(self.writer.write_with_chunk(&chunk));
+        self.write = Some(write);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(write) = self.write.as_mut() {
+            let result = ready!(Pin::new(write).poll(cx));
+            self.write = None;
+            result.map_err(js_error)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl fmt::Debug for WebSerialStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSerialStream")
+            .field("buffered", &(self.chunk.len() - self.consumed))
+            .field("eof", &self.eof)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Turn a rejected promise or exception into an I/O error
+///
+/// A `NetworkError` is what Web Serial throws once the device is gone.
+fn js_error(value: JsValue) -> io::Error {
+    let name = Reflect::get(&value, &JsValue::from_str("name"))
+        .ok()
+        .and_then(|name| name.as_string());
+    let kind = match name.as_deref() {
+        Some("NetworkError") => io::ErrorKind::NotConnected,
+        Some("NotFoundError") => io::ErrorKind::NotFound,
+        Some("SecurityError") | Some("NotAllowedError") => io::ErrorKind::PermissionDenied,
+        Some("InvalidStateError") => io::ErrorKind::AddrInUse,
+        _ => io::ErrorKind::Other,
+    };
+    let message = Reflect::get(&value, &JsValue::from_str("message"))
+        .ok()
+        .and_then(|message| message.as_string())
+        .unwrap_or_else(|| format!("{:?}", value));
+    io::Error::new(kind, message)
+}
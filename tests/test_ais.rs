@@ -0,0 +1,73 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::ais::{AisAssembler, AisCodec, AisPayload};
+use tokio_serial::codec::nmea::Sentence;
+use tokio_util::codec::{Decoder, Encoder};
+
+const FRAGMENTS: [&str; 2] = [
+    "!AIVDM,2,1,3,B,55P5TL01VIaAL@7WKO@mBplU@<PDhh000000001S;AJ::4A80?4i@E53,0*3E\r\n",
+    "!AIVDM,2,2,3,B,1@0000000000000,2*55\r\n",
+];
+
+#[test]
+fn position_report_decodes() {
+    let sentence = Sentence::parse("!AIVDM,1,1,,B,177KQJ5000G?tO`K>RA1wUbN0TKH,0*5C").unwrap();
+    let payload = AisAssembler::new().push(&sentence).unwrap().unwrap();
+    assert_eq!(payload.len(), 168);
+    assert_eq!(payload.channel(), Some('B'));
+    assert!(!payload.is_own_vessel());
+    assert_eq!(payload.message_type(), 1);
+    assert_eq!(payload.mmsi(), 477553000);
+    // Longitude and latitude in 1/10000 minutes
+    assert_eq!(payload.signed(61, 28), Some(-73407500));
+    assert_eq!(payload.signed(89, 27), Some(28549700));
+    assert_eq!(payload.unsigned(160, 9), None);
+}
+
+#[test]
+fn fragments_reassemble() {
+    let mut codec = AisCodec::new();
+    let mut src = BytesMut::from(&b"$GPGLL,5300.97914,N,00259.98174,E,125926,A*28\r\n"[..]);
+    src.extend_from_slice(FRAGMENTS[0].as_bytes());
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    src.extend_from_slice(FRAGMENTS[1].as_bytes());
+    let payload = codec.decode(&mut src).unwrap().unwrap();
+    assert_eq!(payload.len(), 424);
+    assert_eq!(payload.message_type(), 5);
+    assert_eq!(payload.mmsi(), 369190000);
+    assert_eq!(payload.text(112, 120).as_deref(), Some("MT.MITCHELL"));
+    assert_eq!(codec.dropped(), 0);
+}
+
+#[test]
+fn incomplete_messages_are_dropped() {
+    let mut assembler = AisAssembler::new();
+    let first = Sentence::parse(FRAGMENTS[0]).unwrap();
+    let second = Sentence::parse(FRAGMENTS[1]).unwrap();
+    assert_eq!(assembler.push(&first).unwrap(), None);
+    assert_eq!(assembler.push(&first).unwrap(), None);
+    assert_eq!(assembler.dropped(), 1);
+    assert!(assembler.push(&second).unwrap().is_some());
+    // The second fragment alone
+    assert_eq!(assembler.push(&second).unwrap(), None);
+
+    let bad = Sentence::encapsulated("AIVDM,1,1,,A,1~,0").unwrap();
+    let err = assembler.push(&bad).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn payloads_round_trip() {
+    let bits: Vec<u8> = (0..100).map(|i| (i * 37) as u8).collect();
+    let payload = AisPayload::from_bits(&bits, 797);
+    let mut codec = AisCodec::new();
+    let mut dst = BytesMut::new();
+    codec.encode(payload.clone(), &mut dst).unwrap();
+    assert_eq!(dst.iter().filter(|&&b| b == b'\n').count(), 3);
+    assert!(dst.starts_with(b"!AIVDM,3,1,1,,"));
+
+    let decoded = codec.decode(&mut dst).unwrap().unwrap();
+    assert_eq!(decoded, payload);
+    assert_eq!(decoded.armor(), payload.armor());
+    assert!(dst.is_empty());
+}
@@ -0,0 +1,77 @@
+#![cfg(unix)]
+use std::os::unix::io::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::android::{ExternalSerialStream, LineControl};
+use tokio_serial::{DataBits, Parity, SerialPort, StopBits};
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl LineControl for Recorder {
+    fn set_parameters(
+        &mut self,
+        baud_rate: u32,
+        data_bits: DataBits,
+        parity: Parity,
+        stop_bits: StopBits,
+    ) -> tokio_serial::Result<()> {
+        self.0.lock().unwrap().push(format!(
+            "{} {:?} {:?} {:?}",
+            baud_rate, data_bits, parity, stop_bits
+        ));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn settings_go_through_the_control() {
+    let (ours, mut theirs) = tokio::net::UnixStream::pair().unwrap();
+    let ours = ours.into_std().unwrap();
+    let recorder = Recorder::default();
+
+    let builder = tokio_serial::new("usb:1234", 19200).parity(Parity::Even);
+    let mut port = ExternalSerialStream::new(OwnedFd::from(ours), recorder.clone(), &builder)
+        .expect("Unable to wrap descriptor");
+    assert_eq!(port.name().as_deref(), Some("usb:1234"));
+
+    port.set_baud_rate(115200).unwrap();
+    assert_eq!(port.baud_rate().unwrap(), 115200);
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        ["19200 Eight Even One", "115200 Eight Even One"]
+    );
+    assert!(port.write_request_to_send(true).is_err());
+
+    port.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    theirs.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+
+    theirs.write_all(b"pong").await.unwrap();
+    port.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"pong");
+
+    drop(theirs);
+    assert_eq!(port.read(&mut buf).await.unwrap(), 0);
+}
+
+#[test]
+fn std_socket_pair_is_accepted() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap();
+    let (ours, _theirs) = UnixStream::pair().unwrap();
+    let _guard = rt.enter();
+    let port = ExternalSerialStream::new(
+        OwnedFd::from(ours),
+        tokio_serial::android::Preconfigured,
+        &tokio_serial::new("", 9600),
+    )
+    .unwrap();
+    assert_eq!(port.name(), None);
+    assert_eq!(port.bytes_to_read().unwrap(), 0);
+}
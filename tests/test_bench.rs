@@ -0,0 +1,24 @@
+#![cfg(feature = "bench")]
+
+use tokio_serial::bench::{self, Options};
+use tokio_serial::test::MockSerial;
+
+#[tokio::test]
+async fn bench_reports_latency_throughput_and_wakeups() {
+    let (mut tx, mut rx) = MockSerial::pair();
+    let options = Options {
+        latency_samples: 10,
+        throughput_bytes: 16 * 1024,
+        chunk_size: 512,
+    };
+    let report = bench::run(&mut tx, &mut rx, &options).await.unwrap();
+
+    assert_eq!(report.latency.samples, 10);
+    assert!(report.latency.min <= report.latency.median);
+    assert!(report.latency.median <= report.latency.max);
+    assert_eq!(report.throughput.bytes, 16 * 1024);
+    assert!(report.throughput.bytes_per_second() > 0.0);
+    let reads = report.throughput.reads;
+    assert_eq!(reads.polls, reads.pending + reads.ready);
+    assert!(reads.ready > 0);
+}
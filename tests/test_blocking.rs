@@ -0,0 +1,57 @@
+#![cfg(unix)]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::blocking::BlockingBridge;
+use tokio_serial::SerialStream;
+
+#[tokio::test]
+async fn blocking_port_round_trips() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to open pty");
+    slave.set_read_buffer_capacity(64);
+    master.write_all(b"abcdef").await.unwrap();
+    let mut buf = [0; 2];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ab");
+
+    let mut port = slave.into_blocking().unwrap();
+    let port = tokio::task::spawn_blocking(move || {
+        use std::io::{Read, Write};
+
+        let mut buf = [0; 2];
+        port.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"cd");
+        port.write_all(b"sync").unwrap();
+        port
+    })
+    .await
+    .unwrap();
+
+    let mut buf = [0; 4];
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"sync");
+
+    let mut slave = port.into_async().unwrap();
+    let mut buf = [0; 2];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ef");
+}
+
+#[tokio::test]
+async fn bridge_blocks_on_the_async_port() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to open pty");
+    let mut bridge = BlockingBridge::new(slave);
+    let reader = tokio::task::spawn_blocking(move || {
+        use std::io::{Read, Write};
+
+        let mut buf = [0; 5];
+        bridge.read_exact(&mut buf).unwrap();
+        bridge.write_all(&buf).unwrap();
+        bridge.flush().unwrap();
+        bridge.into_inner()
+    });
+
+    master.write_all(b"hello").await.unwrap();
+    let mut buf = [0; 5];
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"hello");
+    reader.await.unwrap();
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "test-util")]
+
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::Instant;
+use tokio_serial::test::{Capture, Direction, MockSerial};
+
+// `usize::div_ceil` needs a newer compiler than the MSRV
+#[allow(clippy::manual_div_ceil)]
+fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+    let padded = (body.len() + 3) / 4 * 4;
+    let len = (12 + padded) as u32;
+    let mut block = kind.to_le_bytes().to_vec();
+    block.extend_from_slice(&len.to_le_bytes());
+    block.extend_from_slice(body);
+    block.resize(8 + padded, 0);
+    block.extend_from_slice(&len.to_le_bytes());
+    block
+}
+
+/// An enhanced packet at `micros`, with `flags` as its `epb_flags` if any
+// `usize::div_ceil` needs a newer compiler than the MSRV
+#[allow(clippy::manual_div_ceil)]
+fn packet(interface: u32, micros: u64, data: &[u8], flags: Option<u32>) -> Vec<u8> {
+    let mut body = interface.to_le_bytes().to_vec();
+    body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+    body.extend_from_slice(&(micros as u32).to_le_bytes());
+    body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    body.extend_from_slice(data);
+    body.resize((body.len() + 3) / 4 * 4, 0);
+    if let Some(flags) = flags {
+        body.extend_from_slice(&[2, 0, 4, 0]);
+        body.extend_from_slice(&flags.to_le_bytes());
+        body.extend_from_slice(&[0; 4]);
+    }
+    block(6, &body)
+}
+
+fn pcapng() -> Vec<u8> {
+    let mut file = block(
+        0x0a0d_0d0a,
+        &[
+            0x4d, 0x3c, 0x2b, 0x1a, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ],
+    );
+    // User link type 147
+    file.extend(block(1, &[147, 0, 0, 0, 0, 0, 0, 0]));
+    file.extend(packet(0, 1_000_000, b"AT\r", Some(2)));
+    file.extend(packet(0, 1_250_000, b"OK", Some(1)));
+    file.extend(packet(0, 1_260_000, b"\r\n", Some(1)));
+    file.extend(packet(0, 2_000_000, b"ATI\r", None));
+    file
+}
+
+#[test]
+fn pcapng_loads() {
+    let capture = Capture::from_pcapng(&pcapng()[..]).unwrap();
+    let chunks = capture.chunks();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[1].direction, Direction::DeviceToHost);
+    assert_eq!(chunks[1].data, b"OK\r\n");
+    assert_eq!(chunks[1].at, Duration::from_millis(250));
+    assert_eq!(chunks[2].at, Duration::from_secs(1));
+    assert_eq!(capture.bytes(Direction::HostToDevice), b"AT\rATI\r");
+
+    let mut cut = pcapng();
+    cut.truncate(cut.len() - 6);
+    let err = Capture::from_pcapng(&cut[..]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(Capture::from_pcapng(&b"\xd4\xc3\xb2\xa1"[..]).is_err());
+}
+
+#[test]
+fn saleae_csv_loads() {
+    let logic2 = "name,type,start_time,duration,data\n\
+        TX,data,0.5,0.0001,0x41\n\
+        TX,data,0.5001,0.0001,T\n\
+        TX,error,0.5002,0.0001,\n\
+        RX,data,0.6,0.0001,\"O\"\n\
+        RX,data,0.6001,0.0001,\\r\n";
+    let capture = Capture::from_saleae_csv(logic2.as_bytes(), "TX").unwrap();
+    assert_eq!(capture.bytes(Direction::HostToDevice), b"AT");
+    assert_eq!(capture.bytes(Direction::DeviceToHost), b"O\r");
+    assert_eq!(capture.chunks()[1].at.as_millis(), 100);
+
+    let logic1 = "Time [s],Analyzer Name,Decoded Protocol Result\n\
+        -0.01,Async Serial,'A'\n\
+        0.02,Async Serial,' '\n";
+    let capture = Capture::from_saleae_csv(logic1.as_bytes(), "Async Serial").unwrap();
+    assert_eq!(capture.bytes(Direction::HostToDevice), b"A ");
+    assert!(Capture::from_saleae_csv("a,b\n1,2\n".as_bytes(), "TX").is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn capture_scripts_mock() {
+    let capture = Capture::from_pcapng(&pcapng()[..]).unwrap();
+    let mut port = MockSerial::builder().capture(&capture).build();
+
+    let start = Instant::now();
+    port.write_all(b"AT\r").await.unwrap();
+    let mut reply = [0; 4];
+    port.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"OK\r\n");
+    assert_eq!(start.elapsed(), Duration::from_millis(250));
+    port.write_all(b"ATI\r").await.unwrap();
+}
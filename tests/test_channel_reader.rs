@@ -0,0 +1,65 @@
+#![cfg(unix)]
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::time::timeout;
+use tokio_serial::channel_reader::{ChannelReader, OverflowPolicy};
+use tokio_serial::SerialStream;
+
+async fn fill(
+    policy: OverflowPolicy,
+) -> (tokio_serial::channel_reader::ChunkReceiver, SerialStream) {
+    let (mut master, slave) = SerialStream::pair().expect("unable to open pty");
+    let (mut reader, chunks) = ChannelReader::new(2, policy);
+    reader.set_read_len(1);
+    tokio::spawn(reader.run(slave));
+    master.write_all(b"abcd").await.unwrap();
+    // Let the reader take in every byte it is going to
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    (chunks, master)
+}
+
+#[tokio::test]
+async fn drop_oldest_keeps_the_latest_chunks() {
+    let (mut chunks, _master) = fill(OverflowPolicy::DropOldest).await;
+    assert_eq!(chunks.recv().await.unwrap(), b"c");
+    assert_eq!(chunks.recv().await.unwrap(), b"d");
+    assert_eq!(chunks.dropped(), 2);
+}
+
+#[tokio::test]
+async fn drop_newest_keeps_the_first_chunks() {
+    let (mut chunks, _master) = fill(OverflowPolicy::DropNewest).await;
+    assert_eq!(chunks.recv().await.unwrap(), b"a");
+    assert_eq!(chunks.recv().await.unwrap(), b"b");
+    assert_eq!(chunks.dropped(), 2);
+}
+
+#[tokio::test]
+async fn backpressure_loses_nothing() {
+    let (mut chunks, _master) = fill(OverflowPolicy::Backpressure).await;
+    assert_eq!(chunks.len(), 2);
+    let mut received = Vec::new();
+    for _ in 0..4 {
+        let chunk = timeout(Duration::from_secs(1), chunks.recv())
+            .await
+            .unwrap();
+        received.extend(chunk.unwrap());
+    }
+    assert_eq!(received, b"abcd");
+    assert_eq!(chunks.dropped(), 0);
+}
+
+#[tokio::test]
+async fn receiver_ends_when_the_port_hangs_up() {
+    let (master, slave) = SerialStream::pair().expect("unable to open pty");
+    let (reader, mut chunks) = ChannelReader::new(4, OverflowPolicy::default());
+    let pump = tokio::spawn(reader.run(slave));
+    drop(master);
+    assert_eq!(
+        timeout(Duration::from_secs(1), chunks.recv())
+            .await
+            .unwrap(),
+        None
+    );
+    pump.await.unwrap().unwrap();
+}
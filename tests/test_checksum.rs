@@ -0,0 +1,72 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::nmea::{NmeaCodec, Sentence};
+use tokio_serial::codec::ubx::{UbxCodec, UbxFrame};
+use tokio_serial::codec::{Checked, ChecksumPolicy, Frame};
+use tokio_util::codec::{Decoder, Encoder};
+
+const GOOD: &str = "$GPGLL,5300.97914,N,00259.98174,E,125926,A*28\r\n";
+const BAD: &str = "$GPGLL,5300.97914,N,00259.98174,E,125926,A*00\r\n";
+
+fn lines(lines: &[&str]) -> BytesMut {
+    BytesMut::from(lines.concat().as_bytes())
+}
+
+#[test]
+fn corrupt_sentence_is_an_error_by_default() {
+    let mut codec = NmeaCodec::new();
+    let mut src = lines(&[BAD, GOOD]);
+    let err = codec.decode(&mut src).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Sentence::parse(GOOD).unwrap())
+    );
+}
+
+#[test]
+fn corrupt_sentence_is_dropped() {
+    let mut codec = NmeaCodec::new();
+    codec.set_checksum_policy(ChecksumPolicy::Drop);
+    let stats = codec.resync_stats();
+    let mut src = lines(&[BAD, BAD, GOOD]);
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Sentence::parse(GOOD).unwrap())
+    );
+    assert_eq!(stats.corrupt_frames(), 2);
+}
+
+#[test]
+fn corrupt_sentence_is_emitted() {
+    let mut codec = Checked::new(NmeaCodec::new());
+    let mut src = lines(&[BAD, GOOD]);
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Frame::Corrupt(BAD.as_bytes().to_vec().into()))
+    );
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Frame::Valid(Sentence::parse(GOOD).unwrap()))
+    );
+}
+
+#[test]
+fn corrupt_ubx_frame_is_emitted() {
+    let frame = UbxFrame::new(0x01, 0x07, vec![1, 2, 3, 4]);
+    let mut src = BytesMut::new();
+    let mut codec = Checked::new(UbxCodec::new());
+    codec.encode(frame.clone(), &mut src).unwrap();
+    let corrupt = {
+        let mut bytes = src.clone();
+        bytes[6] ^= 0xff;
+        bytes
+    };
+    let mut src = [&corrupt[..], &src[..]].concat()[..].into();
+
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Frame::Corrupt(corrupt.freeze()))
+    );
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(Frame::Valid(frame)));
+}
@@ -0,0 +1,130 @@
+#![cfg(target_os = "linux")]
+use serialport::TTYPort;
+use tokio_serial::{config, SerialPort, StopBits};
+
+// Pseudo terminals force eight data bits without parity, so only the other settings are
+// checked here
+#[test]
+fn configure_applies_builder_settings() {
+    let (_master, slave) = TTYPort::pair().expect("Unable to create ptty pair");
+
+    let builder = tokio_serial::new("", 250_000).stop_bits(StopBits::Two);
+    config::configure(&slave, &builder).expect("Unable to configure");
+    assert_eq!(slave.baud_rate().unwrap(), 250_000);
+    assert_eq!(slave.stop_bits().unwrap(), StopBits::Two);
+
+    config::set_baud_rate(&slave, 9600).expect("Unable to set baud rate");
+    assert_eq!(slave.baud_rate().unwrap(), 9600);
+
+    config::modify_termios(&slave, |t| t.c_iflag |= libc::IGNBRK).expect("Unable to modify");
+    assert_ne!(config::termios(&slave).unwrap().c_iflag & libc::IGNBRK, 0);
+}
+
+#[tokio::test]
+async fn raw_fd_changes_are_picked_up() {
+    use std::os::unix::io::AsRawFd;
+
+    let (_master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let watermark = slave.read_low_watermark();
+    let mut queued: libc::c_int = -1;
+    let result =
+        slave.with_raw_fd(|fd| unsafe { libc::ioctl(fd.as_raw_fd(), libc::TIOCOUTQ, &mut queued) });
+    assert_eq!(result, 0);
+    assert_eq!(queued, 0);
+    assert_eq!(slave.read_low_watermark(), watermark);
+
+    slave.with_raw_fd(|fd| {
+        config::modify_termios(&fd, |t| t.c_cc[libc::VMIN] = 4).unwrap();
+    });
+    assert_eq!(slave.read_low_watermark(), 4);
+}
+
+#[test]
+fn divisor_needs_the_legacy_serial_interface() {
+    use tokio_serial::config::BaudMethod;
+
+    let (_master, slave) = TTYPort::pair().expect("Unable to create ptty pair");
+    let err = config::set_baud_rate_with(&slave, 0, BaudMethod::Divisor).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    // Pseudo terminals have no UART behind them
+    assert!(config::set_baud_rate_with(&slave, 31_250, BaudMethod::Divisor).is_err());
+
+    config::set_baud_rate_with(&slave, 31_250, BaudMethod::Termios2).unwrap();
+    assert_eq!(slave.baud_rate().unwrap(), 31_250);
+}
+
+#[tokio::test]
+async fn platform_options_apply_at_open() {
+    use tokio_serial::SerialPortBuilderExt;
+
+    let (_master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let port = tokio_serial::new(path.to_str().unwrap(), 9600)
+        .with_platform_options(|opts| {
+            opts.vmin(4)
+                .vtime(2)
+                .modify_termios(|t| t.c_iflag |= libc::IGNPAR);
+        })
+        .open_native_async()
+        .unwrap();
+    let termios = config::termios(&port).unwrap();
+    assert_eq!(termios.c_cc[libc::VMIN], 4);
+    assert_eq!(termios.c_cc[libc::VTIME], 2);
+    assert_ne!(termios.c_iflag & libc::IGNPAR, 0);
+    assert_eq!(port.read_low_watermark(), 4);
+}
+
+#[tokio::test]
+async fn stale_input_is_cleared_on_open() {
+    use std::io::Write;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+    use tokio_serial::{ClearBuffer, SerialPortBuilderExt};
+
+    let (mut master, slave) = TTYPort::pair().expect("Unable to create ptty pair");
+    let path = slave.name().unwrap();
+    master.write_all(b"stale").unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+
+    let mut port = tokio_serial::new(path, 9600)
+        .clear_buffers_on_open(ClearBuffer::Input)
+        .open_native_async()
+        .unwrap();
+    master.write_all(b"fresh").unwrap();
+    let mut buf = [0; 5];
+    port.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"fresh");
+}
+
+#[tokio::test]
+async fn modem_status_needs_modem_lines() {
+    let (_master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    assert!(slave.modem_status().is_err());
+}
+
+#[tokio::test]
+async fn modem_lines_need_a_modem() {
+    let (_master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    assert!(slave.set_modem_lines(Some(true), Some(false)).is_err());
+}
+
+#[tokio::test]
+async fn apply_changes_only_what_differs() {
+    use tokio_serial::{ConfigChange, SerialConfig};
+
+    let (_master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut target = SerialConfig::read(&slave).unwrap();
+    let baud_rate = target.baud_rate;
+    target.baud_rate = 19200;
+    target.stop_bits = StopBits::Two;
+
+    let diff = slave.apply(&target).await.unwrap();
+    assert_eq!(
+        diff.changes(),
+        [
+            ConfigChange::BaudRate(baud_rate, 19200),
+            ConfigChange::StopBits(StopBits::One, StopBits::Two),
+        ]
+    );
+    assert_eq!(slave.baud_rate().unwrap(), 19200);
+    assert!(slave.apply(&target).await.unwrap().is_empty());
+}
@@ -0,0 +1,62 @@
+#![cfg(unix)]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::console::{Console, Exit, Newline};
+
+#[tokio::test]
+async fn keys_and_output_are_mapped() {
+    let (mut input, mut keyboard) = tokio::io::duplex(64);
+    let (mut output, mut screen) = tokio::io::duplex(64);
+    let (mut port, mut device) = tokio::io::duplex(64);
+
+    let mut console = Console::new();
+    console.set_send_newline(Newline::CrLf);
+    console.set_local_echo(true);
+    let session = tokio::spawn(async move {
+        console
+            .bridge(&mut input, &mut output, &mut port)
+            .await
+            .unwrap()
+    });
+
+    keyboard.write_all(b"AT\r").await.unwrap();
+    let mut sent = [0; 4];
+    device.read_exact(&mut sent).await.unwrap();
+    assert_eq!(&sent, b"AT\r\n");
+    let mut echo = [0; 4];
+    screen.read_exact(&mut echo).await.unwrap();
+    assert_eq!(&echo, b"AT\r\n");
+
+    device.write_all(b"OK\n").await.unwrap();
+    let mut shown = [0; 4];
+    screen.read_exact(&mut shown).await.unwrap();
+    assert_eq!(&shown, b"OK\r\n");
+
+    // Keys typed before the escape still go out
+    keyboard.write_all(b"x\x1dy").await.unwrap();
+    assert_eq!(session.await.unwrap(), Exit::Escape);
+    let mut rest = Vec::new();
+    device.read_to_end(&mut rest).await.unwrap();
+    assert_eq!(rest, b"x");
+}
+
+#[tokio::test]
+async fn session_ends_with_port() {
+    let (mut input, _keyboard) = tokio::io::duplex(64);
+    let (mut output, mut screen) = tokio::io::duplex(64);
+    let (mut port, mut device) = tokio::io::duplex(64);
+
+    let mut console = Console::new();
+    console.set_escape(None);
+    console.set_receive_newline(None);
+    device.write_all(b"bye\n").await.unwrap();
+    drop(device);
+    let exit = console
+        .bridge(&mut input, &mut output, &mut port)
+        .await
+        .unwrap();
+    assert_eq!(exit, Exit::PortClosed);
+    drop(output);
+    let mut shown = Vec::new();
+    screen.read_to_end(&mut shown).await.unwrap();
+    assert_eq!(shown, b"bye\n");
+}
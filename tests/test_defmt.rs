@@ -0,0 +1,91 @@
+#![cfg(feature = "defmt")]
+use bytes::{Bytes, BytesMut};
+use tokio_serial::codec::defmt::{rzcobs_decode, rzcobs_encode, DefmtCodec};
+use tokio_serial::codec::{Checked, ChecksumPolicy, Frame};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn encoded(data: &[u8]) -> BytesMut {
+    let mut dst = BytesMut::new();
+    rzcobs_encode(data, &mut dst);
+    dst
+}
+
+#[test]
+fn rzcobs_round_trips() {
+    let long: Vec<u8> = (1..=255).chain(1..=40).collect();
+    let mixed: Vec<u8> = (0..300).map(|i| (i % 5) as u8).collect();
+    for data in [
+        &b""[..],
+        b"\x00",
+        b"\x01\x02\x03",
+        b"abcdefg",
+        b"abcdefg\x00h",
+        &long,
+        &mixed,
+    ] {
+        let wire = encoded(data);
+        assert!(!wire.contains(&0), "{:?}", data);
+        let decoded = rzcobs_decode(&wire).unwrap();
+        assert!(decoded.starts_with(data), "{:?}", data);
+        assert!(decoded[data.len()..].iter().all(|&b| b == 0));
+        assert!(decoded.len() - data.len() < 8);
+    }
+}
+
+#[test]
+fn known_encoding() {
+    assert_eq!(&encoded(b"\x01\x02\x03")[..], b"\x01\x02\x03\x78");
+    assert_eq!(&encoded(b"abcdefgh\x00")[..], b"abcdefgh\x81");
+    assert_eq!(rzcobs_decode(b"\x01\x02"), None);
+}
+
+#[test]
+fn frames_decode_after_partial_first() {
+    let mut codec = DefmtCodec::new();
+    let mut src = BytesMut::from(&b"\x05\x05\x05\x00"[..]);
+    src.extend_from_slice(&encoded(b"\x02\x00\x10"));
+    src.extend_from_slice(b"\x00\x00");
+    src.extend_from_slice(&encoded(b"\x03hello"));
+    src.extend_from_slice(b"\x00");
+
+    let first = codec.decode(&mut src).unwrap().unwrap();
+    assert!(first.starts_with(b"\x02\x00\x10"));
+    let second = codec.decode(&mut src).unwrap().unwrap();
+    assert!(second.starts_with(b"\x03hello"));
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    assert!(src.is_empty());
+    assert_eq!(codec.resync_stats().skipped_bytes(), 4);
+    assert_eq!(codec.resync_stats().corrupt_frames(), 0);
+}
+
+#[test]
+fn malformed_and_long_frames_follow_policy() {
+    let mut codec = Checked::new(DefmtCodec::with_max_length(16));
+    let mut src = BytesMut::new();
+    codec.encode(Bytes::from_static(b"\x01"), &mut src).unwrap();
+    src.extend_from_slice(b"\x01\x02\x00");
+    src.extend_from_slice(&[0x11; 20]);
+    src.extend_from_slice(b"\x00");
+    codec.encode(Bytes::from_static(b"\x04"), &mut src).unwrap();
+
+    assert!(matches!(codec.decode(&mut src), Ok(Some(Frame::Valid(_)))));
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Frame::Corrupt(Bytes::from_static(b"\x01\x02")))
+    );
+    assert!(matches!(
+        codec.decode(&mut src),
+        Ok(Some(Frame::Corrupt(_)))
+    ));
+    match codec.decode(&mut src).unwrap() {
+        Some(Frame::Valid(frame)) => assert_eq!(frame[0], 4),
+        other => panic!("{:?}", other),
+    }
+
+    let mut codec = codec.into_inner();
+    codec.set_checksum_policy(ChecksumPolicy::Drop);
+    src.extend_from_slice(b"\x01\x02\x00");
+    codec.encode(Bytes::from_static(b"\x05"), &mut src).unwrap();
+    assert_eq!(codec.decode(&mut src).unwrap().unwrap()[0], 5);
+    assert_eq!(codec.resync_stats().corrupt_frames(), 3);
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::demux::{DemuxCodec, GnssFrame};
+use tokio_serial::codec::nmea::Sentence;
+use tokio_serial::codec::ubx::{UbxCodec, UbxFrame};
+use tokio_util::codec::{Decoder, Encoder};
+
+const GGA: &str = "$GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*76";
+const RMC: &str = "$GPRMC,092750.000,A,5321.6802,N,00630.3372,W,0.02,31.66,280511,,,A*43";
+
+fn ubx(frame: &UbxFrame) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    UbxCodec::new().encode(frame.clone(), &mut bytes).unwrap();
+    bytes
+}
+
+fn decode_all(codec: &mut DemuxCodec<GnssFrame>, src: &mut BytesMut) -> Vec<Option<GnssFrame>> {
+    let mut frames = Vec::new();
+    loop {
+        match codec.decode(src) {
+            Ok(Some(frame)) => frames.push(Some(frame)),
+            Ok(None) => return frames,
+            Err(_) => frames.push(None),
+        }
+    }
+}
+
+#[test]
+fn interleaved_frames_are_tagged() {
+    let frame = UbxFrame::new(0x01, 0x07, vec![b'$', b'\n', 3, 4]);
+    let mut src = BytesMut::new();
+    src.extend_from_slice(format!("{}\r\n", GGA).as_bytes());
+    src.extend_from_slice(b"\x00\xff");
+    src.extend_from_slice(&ubx(&frame));
+    src.extend_from_slice(format!("{}\r\n", RMC).as_bytes());
+
+    let frames = decode_all(&mut DemuxCodec::gnss(), &mut src);
+    assert_eq!(
+        frames,
+        vec![
+            Some(GnssFrame::Nmea(Sentence::parse(GGA).unwrap())),
+            Some(GnssFrame::Ubx(frame)),
+            Some(GnssFrame::Nmea(Sentence::parse(RMC).unwrap())),
+        ]
+    );
+    assert!(src.is_empty());
+}
+
+#[test]
+fn truncated_sentence_keeps_the_next_ubx_frame() {
+    let frame = UbxFrame::new(0x01, 0x07, vec![1, 2, 3, 4]);
+    let mut src = BytesMut::new();
+    src.extend_from_slice(&GGA.as_bytes()[..30]);
+    src.extend_from_slice(&ubx(&frame));
+    src.extend_from_slice(format!("{}\r\n", RMC).as_bytes());
+
+    let frames = decode_all(&mut DemuxCodec::gnss(), &mut src);
+    assert_eq!(
+        frames,
+        vec![
+            None,
+            Some(GnssFrame::Ubx(frame)),
+            Some(GnssFrame::Nmea(Sentence::parse(RMC).unwrap())),
+        ]
+    );
+}
+
+#[test]
+fn sync_split_across_reads_waits() {
+    let frame = UbxFrame::new(0x05, 0x01, vec![0x06, 0x8a]);
+    let bytes = ubx(&frame);
+    let mut codec = DemuxCodec::gnss();
+    let mut src = BytesMut::from(&b"noise"[..]);
+    src.extend_from_slice(&bytes[..1]);
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    assert_eq!(&src[..], &bytes[..1]);
+
+    src.extend_from_slice(&bytes[1..]);
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(GnssFrame::Ubx(frame)));
+}
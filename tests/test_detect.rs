@@ -0,0 +1,59 @@
+#![cfg(all(unix, feature = "codec"))]
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio_serial::codec::ubx::{UbxCodec, UbxFrame};
+use tokio_serial::detect::{Detected, Detector, Protocol};
+use tokio_serial::SerialStream;
+use tokio_util::codec::Encoder;
+
+#[tokio::test]
+async fn ubx_after_line_noise_is_dispatched() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to open pty");
+    let mut bytes = bytes::BytesMut::from(&[0x00, 0xff, 0x13][..]);
+    let frame = UbxFrame::new(0x01, 0x07, vec![1, 2, 3, 4]);
+    UbxCodec::new().encode(frame.clone(), &mut bytes).unwrap();
+    master.write_all(&bytes).await.unwrap();
+
+    match Detector::new().dispatch(slave).await.unwrap() {
+        Detected::Ubx(mut frames) => assert_eq!(frames.next().await.unwrap().unwrap(), frame),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn nmea_arriving_in_pieces_is_detected() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to open pty");
+    let detector = Detector::new();
+    let detection = tokio::spawn(async move {
+        let protocol = detector.detect(&mut slave).await.unwrap();
+        (protocol, slave)
+    });
+
+    master.write_all(b"$GP").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    master
+        .write_all(b"GGA,123519,4807.038,N*00\r\n")
+        .await
+        .unwrap();
+    let (protocol, mut slave) = detection.await.unwrap();
+    assert_eq!(protocol, Protocol::Nmea);
+
+    // Nothing was consumed
+    let mut start = [0u8; 3];
+    tokio::io::AsyncReadExt::read_exact(&mut slave, &mut start)
+        .await
+        .unwrap();
+    assert_eq!(&start, b"$GP");
+}
+
+#[test]
+fn modbus_request_and_custom_signatures_are_classified() {
+    let detector = Detector::new().register("at", |bytes| bytes.starts_with(b"AT"));
+
+    // Read holding registers 0x006b..0x006d of slave 0x11
+    let request = [0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87];
+    assert_eq!(detector.classify(&request), Some(Protocol::ModbusRtu));
+    assert_eq!(detector.classify(&request[..7]), None);
+    assert_eq!(detector.classify(b"AT+CSQ\r"), Some(Protocol::Custom("at")));
+    assert_eq!(detector.classify(b"hello"), None);
+}
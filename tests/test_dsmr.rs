@@ -0,0 +1,91 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::dsmr::{crc16, DsmrCodec, Telegram};
+use tokio_serial::codec::ChecksumPolicy;
+use tokio_util::codec::{Decoder, Encoder};
+
+const BODY: &str = "/ISk5\\2MT382-1000\r\n\r\n\
+    1-3:0.2.8(50)\r\n\
+    0-0:1.0.0(101209113020W)\r\n\
+    1-0:1.8.1(123456.789*kWh)\r\n\
+    1-0:1.7.0(01.193*kW)\r\n\
+    0-1:24.2.1(101209112500W)(12785.123*m3)\r\n\
+    !";
+
+fn telegram() -> String {
+    format!("{}{:04X}\r\n", BODY, crc16(BODY.as_bytes()))
+}
+
+#[test]
+fn crc_matches_reference() {
+    assert_eq!(crc16(b"123456789"), 0xbb3d);
+}
+
+#[test]
+fn telegrams_decode_through_noise() {
+    let mut codec = DsmrCodec::new();
+    let mut src = BytesMut::from(&b"\x00garbage"[..]);
+    src.extend_from_slice(telegram().as_bytes());
+    let telegram = codec.decode(&mut src).unwrap().unwrap();
+    assert!(src.is_empty());
+    assert_eq!(telegram.header(), "ISk5\\2MT382-1000");
+    assert_eq!(telegram.crc(), Some(crc16(BODY.as_bytes())));
+    assert_eq!(telegram.objects().len(), 5);
+    assert_eq!(
+        telegram.get("1-0:1.8.1").unwrap().quantity(),
+        Some((123456.789, "kWh"))
+    );
+    let gas = telegram.get("0-1:24.2.1").unwrap();
+    assert_eq!(gas.values, ["101209112500W", "12785.123*m3"]);
+    assert_eq!(gas.value(), Some("12785.123*m3"));
+    assert_eq!(codec.resync_stats().skipped_bytes(), 8);
+
+    let mut dst = BytesMut::new();
+    codec.encode(telegram.clone(), &mut dst).unwrap();
+    assert_eq!(codec.decode(&mut dst).unwrap(), Some(telegram));
+}
+
+#[test]
+fn telegrams_decode_in_pieces() {
+    let mut codec = DsmrCodec::new();
+    let mut src = BytesMut::new();
+    let text = telegram();
+    let (first, second) = text.as_bytes().split_at(text.len() - 4);
+    src.extend_from_slice(first);
+    assert!(codec.decode(&mut src).unwrap().is_none());
+    src.extend_from_slice(second);
+    assert!(codec.decode(&mut src).unwrap().is_some());
+}
+
+#[test]
+fn old_telegrams_have_no_crc_and_split_gas_readings() {
+    let text = "/KMP5 KA6U001585575011\r\n\r\n\
+        0-0:96.1.1(204B413655373430363835)\r\n\
+        0-1:24.3.0(121030140000)(00)(60)(1)(0-1:24.2.1)(m3)\r\n\
+        (00001.001)\r\n\
+        !\r\n";
+    let telegram = DsmrCodec::new()
+        .decode(&mut BytesMut::from(text.as_bytes()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(telegram.crc(), None);
+    let gas = telegram.get("0-1:24.3.0").unwrap();
+    assert_eq!(gas.values.len(), 7);
+    assert_eq!(gas.quantity(), Some((1.001, "")));
+}
+
+#[test]
+fn crc_mismatch_follows_policy() {
+    let text = telegram().replace("01.193", "01.194");
+    let mut codec = DsmrCodec::new();
+    let err = codec
+        .decode(&mut BytesMut::from(text.as_bytes()))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    codec.set_checksum_policy(ChecksumPolicy::Drop);
+    let mut src = BytesMut::from(text.as_bytes());
+    src.extend_from_slice(telegram().as_bytes());
+    assert!(codec.decode(&mut src).unwrap().is_some());
+    assert!(Telegram::parse(&text).is_err());
+}
@@ -0,0 +1,139 @@
+use std::io;
+use tokio_serial::{ErrorClass, Operation, PortError};
+
+#[test]
+fn io_errors_are_classified_by_kind() {
+    let class = |kind| ErrorClass::of_io(&io::Error::from(kind));
+    assert_eq!(class(io::ErrorKind::Interrupted), ErrorClass::Transient);
+    assert_eq!(class(io::ErrorKind::TimedOut), ErrorClass::Transient);
+    assert_eq!(class(io::ErrorKind::BrokenPipe), ErrorClass::Disconnected);
+    assert_eq!(class(io::ErrorKind::NotFound), ErrorClass::Disconnected);
+    assert_eq!(
+        class(io::ErrorKind::PermissionDenied),
+        ErrorClass::Permission
+    );
+    assert_eq!(class(io::ErrorKind::InvalidInput), ErrorClass::Other);
+}
+
+#[cfg(windows)]
+#[test]
+fn removed_device_errors_are_disconnected() {
+    // ERROR_ACCESS_DENIED, ERROR_DEVICE_REMOVED, ERROR_OPERATION_ABORTED
+    for code in [5, 1617, 995] {
+        let err = io::Error::from_raw_os_error(code);
+        assert_eq!(ErrorClass::of_io(&err), ErrorClass::Disconnected);
+    }
+}
+
+#[test]
+fn serial_errors_are_classified() {
+    let busy = tokio_serial::Error::new(tokio_serial::ErrorKind::NoDevice, "busy");
+    assert_eq!(ErrorClass::of(&busy), ErrorClass::Disconnected);
+    let denied = tokio_serial::Error::new(
+        tokio_serial::ErrorKind::Io(io::ErrorKind::PermissionDenied),
+        "denied",
+    );
+    assert_eq!(ErrorClass::of(&denied), ErrorClass::Permission);
+}
+
+#[test]
+fn port_error_survives_io_error_round_trip() {
+    let err: io::Error = PortError::new(
+        Some("/dev/ttyS0".into()),
+        Operation::Read,
+        io::Error::from(io::ErrorKind::Interrupted),
+    )
+    .into();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    assert_eq!(
+        err.to_string(),
+        "read on /dev/ttyS0 failed: operation interrupted"
+    );
+
+    let port_error = PortError::from_io(&err).expect("wrapped PortError");
+    assert_eq!(port_error.port(), Some("/dev/ttyS0"));
+    assert_eq!(port_error.operation(), Operation::Read);
+    assert!(port_error.is_transient());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn write_to_hung_up_port_reports_disconnected() {
+    use tokio::io::AsyncWriteExt;
+
+    let (master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    drop(master);
+
+    let err = slave.write_all(b"anyone?").await.unwrap_err();
+    let port_error = PortError::from_io(&err).expect("wrapped PortError");
+    assert_eq!(port_error.operation(), Operation::Write);
+    assert_eq!(port_error.class(), ErrorClass::Disconnected);
+    assert_eq!(ErrorClass::of_io(&err), ErrorClass::Disconnected);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn busy_port_names_its_holder() {
+    use tokio_serial::SerialPortBuilderExt;
+
+    let (_master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let builder = tokio_serial::new(path.to_str().unwrap(), 9600);
+    let _port = tokio_serial::SerialStream::open(&builder).unwrap();
+
+    let holders = tokio_serial::port_holders(&path).unwrap();
+    assert!(holders.iter().any(|h| h.pid == std::process::id()));
+    let err = builder
+        .clone()
+        .allow_shared()
+        .open_native_async()
+        .unwrap_err();
+    assert_eq!(err.kind, tokio_serial::ErrorKind::NoDevice);
+    assert!(
+        err.description.contains("(this process)"),
+        "{}",
+        err.description
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn second_open_in_process_is_refused() {
+    let (_master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let path = path.to_str().unwrap();
+    assert!(!tokio_serial::is_open_in_process(path));
+    let port = tokio_serial::SerialStream::open(&tokio_serial::new(path, 9600)).unwrap();
+    assert!(tokio_serial::is_open_in_process(path));
+
+    let err = tokio_serial::SerialStream::open(&tokio_serial::new(path, 9600)).unwrap_err();
+    assert_eq!(ErrorClass::of(&err), ErrorClass::AlreadyOpen);
+    assert!(err.description.contains("already open in this process"));
+    let err = io::Error::from(err);
+    assert_eq!(ErrorClass::of_io(&err), ErrorClass::AlreadyOpen);
+
+    drop(port);
+    assert!(!tokio_serial::is_open_in_process(path));
+    tokio_serial::SerialStream::open(&tokio_serial::new(path, 9600)).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn port_opened_through_a_link_claims_its_device() {
+    use tokio_serial::SerialPortBuilderExt;
+
+    let (_master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let link = std::env::temp_dir().join(format!("tokio-serial-claim-{}", std::process::id()));
+    let _ = std::fs::remove_file(&link);
+    std::os::unix::fs::symlink(&path, &link).unwrap();
+    let link = link.to_str().unwrap().to_owned();
+
+    let port = tokio_serial::SerialStream::open(&tokio_serial::new(link.as_str(), 9600)).unwrap();
+    assert!(tokio_serial::is_open_in_process(path.to_str().unwrap()));
+    let err = tokio_serial::new(path.to_str().unwrap(), 9600)
+        .preserve_dtr_on_open()
+        .open_native_async()
+        .unwrap_err();
+    assert_eq!(ErrorClass::of(&err), ErrorClass::AlreadyOpen);
+
+    drop(port);
+    std::fs::remove_file(&link).unwrap();
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::escpos::{
+    Align, Command, Cut, EscPosCodec, Raster, StatusKind, Symbology,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn encode(commands: Vec<Command>) -> BytesMut {
+    let mut codec = EscPosCodec::new();
+    let mut dst = BytesMut::new();
+    for command in commands {
+        codec.encode(command, &mut dst).unwrap();
+    }
+    dst
+}
+
+#[test]
+fn commands_encode() {
+    let dst = encode(vec![
+        Command::Init,
+        Command::Align(Align::Center),
+        Command::Bold(true),
+        Command::Size {
+            width: 2,
+            height: 1,
+        },
+        Command::Text("Café\n".into()),
+        Command::Feed(3),
+        Command::Cut(Cut::Partial),
+    ]);
+    assert_eq!(
+        &dst[..],
+        b"\x1b@\x1ba\x01\x1bE\x01\x1d!\x10Caf?\n\x1bd\x03\x1dV\x01"
+    );
+}
+
+#[test]
+fn barcodes_encode() {
+    let dst = encode(vec![
+        Command::BarcodeHeight(80),
+        Command::Barcode {
+            symbology: Symbology::Ean13,
+            data: "400638133393".into(),
+        },
+    ]);
+    assert_eq!(&dst[..], b"\x1dh\x50\x1dk\x43\x0c400638133393");
+
+    let mut codec = EscPosCodec::new();
+    let barcode = Command::Barcode {
+        symbology: Symbology::Code39,
+        data: String::new(),
+    };
+    assert!(codec.encode(barcode, &mut BytesMut::new()).is_err());
+}
+
+#[test]
+fn images_pack_rows() {
+    // 10 x 2 dots: a black first row, a white second one
+    let mut pixels = vec![0u8; 10];
+    pixels.extend_from_slice(&[255; 10]);
+    let raster = Raster::from_luma(10, 2, &pixels, 128).unwrap();
+    let dst = encode(vec![Command::Image(raster)]);
+    assert_eq!(
+        &dst[..],
+        [0x1d, b'v', b'0', 0, 2, 0, 2, 0, 0xff, 0xc0, 0x00, 0x00]
+    );
+    assert!(Raster::new(10, 2, vec![0; 3]).is_err());
+}
+
+#[test]
+fn status_answers_match_requests() {
+    let mut codec = EscPosCodec::new();
+    let mut dst = BytesMut::new();
+    codec
+        .encode(Command::Status(StatusKind::Offline), &mut dst)
+        .unwrap();
+    codec
+        .encode(Command::Status(StatusKind::Paper), &mut dst)
+        .unwrap();
+    assert_eq!(&dst[..], b"\x10\x04\x02\x10\x04\x04");
+    assert_eq!(codec.pending_status(), 2);
+
+    // Noise, the cover open, then paper near its end
+    let mut src = BytesMut::from(&[0xff, 0x16, 0x1e][..]);
+    let offline = codec.decode(&mut src).unwrap().unwrap();
+    assert!(offline.cover_open());
+    assert!(!offline.paper_end());
+    let paper = codec.decode(&mut src).unwrap().unwrap();
+    assert_eq!(paper.kind, StatusKind::Paper);
+    assert!(paper.paper_near_end());
+    assert!(!paper.cover_open());
+    assert_eq!(codec.pending_status(), 0);
+    assert_eq!(codec.resync_stats().skipped_bytes(), 1);
+
+    // Unsolicited bytes are skipped
+    assert!(codec
+        .decode(&mut BytesMut::from(&[0x12][..]))
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,53 @@
+#![cfg(all(target_os = "linux", feature = "events"))]
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+use tokio_serial::events::PortEvent;
+use tokio_serial::SerialStream;
+
+async fn next(events: &mut tokio_serial::events::PortEvents) -> Option<PortEvent> {
+    timeout(Duration::from_secs(1), events.next())
+        .await
+        .expect("no event")
+}
+
+/// Skips the writability the driver signals again after a read
+async fn next_input(events: &mut tokio_serial::events::PortEvents) -> Option<PortEvent> {
+    loop {
+        match next(events).await {
+            Some(PortEvent::Writable) => continue,
+            event => return event,
+        }
+    }
+}
+
+#[tokio::test]
+async fn readiness_and_hangup_are_reported() {
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to open pty");
+    let mut events = slave.events().unwrap();
+
+    assert!(matches!(next(&mut events).await, Some(PortEvent::Writable)));
+    assert!(timeout(Duration::from_millis(100), events.next())
+        .await
+        .is_err());
+
+    master.write_all(b"ping").await.unwrap();
+    assert!(matches!(next(&mut events).await, Some(PortEvent::Readable)));
+    let mut buf = [0u8; 4];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+
+    master.write_all(b"pong").await.unwrap();
+    assert!(matches!(
+        next_input(&mut events).await,
+        Some(PortEvent::Readable)
+    ));
+
+    drop(master);
+    assert!(matches!(
+        next_input(&mut events).await,
+        Some(PortEvent::Disconnected)
+    ));
+    assert!(next(&mut events).await.is_none());
+}
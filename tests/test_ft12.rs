@@ -0,0 +1,78 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::ft12::{Ft12Codec, LinkFrame};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn frames_round_trip() {
+    let frames = [
+        LinkFrame::Fixed {
+            control: 0x49,
+            address: 1,
+        },
+        LinkFrame::SingleChar,
+        LinkFrame::Variable {
+            control: 0x08,
+            address: 1,
+            data: vec![0x64, 0x01, 0x07, 0x01].into(),
+        },
+    ];
+    let mut codec = Ft12Codec::new();
+    let mut src = BytesMut::from(&[0x00, 0xff][..]);
+    for frame in frames.iter() {
+        codec.encode(frame.clone(), &mut src).unwrap();
+    }
+    // Request status of link, primary to station 1
+    assert_eq!(&src[2..7], [0x10, 0x49, 0x01, 0x4a, 0x16]);
+
+    for frame in frames.iter() {
+        assert_eq!(codec.decode(&mut src).unwrap().as_ref(), Some(frame));
+    }
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    assert_eq!(codec.resync_stats().skipped_bytes(), 2);
+
+    assert!(frames[0].is_primary());
+    assert!(frames[1].is_confirm());
+    assert!(!frames[2].is_confirm());
+    let ack = LinkFrame::Fixed {
+        control: 0x00,
+        address: 1,
+    };
+    assert!(ack.is_confirm());
+}
+
+#[test]
+fn corrupt_frame_is_an_error() {
+    let mut codec = Ft12Codec::with_address_len(2);
+    let mut src = BytesMut::new();
+    let frame = LinkFrame::Variable {
+        control: 0x53,
+        address: 0x0102,
+        data: vec![1, 2, 3].into(),
+    };
+    codec.encode(frame.clone(), &mut src).unwrap();
+    assert_eq!(&src[..4], [0x68, 6, 6, 0x68]);
+    let len = src.len();
+    src[len - 2] ^= 0xff;
+    codec.encode(frame.clone(), &mut src).unwrap();
+
+    let err = codec.decode(&mut src).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(frame));
+}
+
+#[test]
+fn partial_frame_waits() {
+    let mut codec = Ft12Codec::new();
+    let mut src = BytesMut::from(&[0x68, 0x03, 0x03, 0x68, 0x08][..]);
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    src.extend_from_slice(&[0x01, 0xaa, 0xb3, 0x16]);
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(LinkFrame::Variable {
+            control: 0x08,
+            address: 1,
+            data: vec![0xaa].into(),
+        })
+    );
+}
@@ -0,0 +1,17 @@
+#![cfg(all(unix, feature = "futures-io"))]
+use futures::executor::block_on;
+use futures::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::futures_io::SerialStream;
+
+#[test]
+fn futures_io_pair_without_tokio() {
+    block_on(async {
+        let (mut master, mut slave) = SerialStream::pair().expect("Unable to create ptty pair");
+
+        master.write_all(b"hello").await.expect("Unable to write");
+
+        let mut buf = [0u8; 5];
+        slave.read_exact(&mut buf).await.expect("Unable to read");
+        assert_eq!(&buf, b"hello");
+    });
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "gpsd")]
+use bytes::BytesMut;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_serial::codec::nmea::{NmeaCodec, Sentence};
+use tokio_serial::codec::ubx::{UbxCodec, UbxFrame};
+use tokio_serial::gpsd;
+use tokio_util::codec::{Decoder, Encoder};
+
+const RMC: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+
+#[test]
+fn codecs_skip_interleaved_frames() {
+    let ubx = UbxFrame::new(0x01, 0x07, vec![1, 2, 3, 4]);
+    let mut buf = BytesMut::from(&b"garbage"[..]);
+    UbxCodec::new().encode(ubx.clone(), &mut buf).unwrap();
+    buf.extend_from_slice(RMC.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+
+    let mut nmea_buf = buf.clone();
+    let sentence = NmeaCodec::new().decode(&mut nmea_buf).unwrap().unwrap();
+    assert_eq!(sentence.as_str(), RMC);
+    assert_eq!(sentence.talker(), "GP");
+    assert_eq!(sentence.sentence_type(), "RMC");
+    assert_eq!(sentence.fields().nth(2), Some("4807.038"));
+
+    assert_eq!(UbxCodec::new().decode(&mut buf).unwrap(), Some(ubx));
+
+    let corrupt = RMC.replace("*6A", "*6B");
+    assert!(Sentence::parse(&corrupt).is_err());
+}
+
+#[tokio::test]
+async fn gpsd_relays_nmea_and_reports_tpv() {
+    let server = gpsd::Server::bind("127.0.0.1:0", "/dev/ttyTEST")
+        .await
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    let (mut device, port) = tokio::io::duplex(1024);
+    let feed = tokio::spawn(server.run(port));
+
+    let mut client = BufReader::new(TcpStream::connect(addr).await.unwrap());
+    let mut line = String::new();
+    client.read_line(&mut line).await.unwrap();
+    assert!(line.contains("\"class\":\"VERSION\""), "{}", line);
+
+    client
+        .get_mut()
+        .write_all(b"?WATCH={\"enable\":true,\"json\":true,\"nmea\":true};\n")
+        .await
+        .unwrap();
+    line.clear();
+    client.read_line(&mut line).await.unwrap();
+    assert!(line.contains("\"path\":\"/dev/ttyTEST\""), "{}", line);
+    line.clear();
+    client.read_line(&mut line).await.unwrap();
+    assert!(line.contains("\"class\":\"WATCH\""), "{}", line);
+
+    let mut frames = BytesMut::new();
+    UbxCodec::new()
+        .encode(UbxFrame::new(0x01, 0x07, vec![0; 8]), &mut frames)
+        .unwrap();
+    frames.extend_from_slice(RMC.as_bytes());
+    frames.extend_from_slice(b"\r\n");
+    device.write_all(&frames).await.unwrap();
+
+    line.clear();
+    client.read_line(&mut line).await.unwrap();
+    assert_eq!(line.trim_end(), RMC);
+    line.clear();
+    client.read_line(&mut line).await.unwrap();
+    assert!(line.contains("\"class\":\"TPV\""), "{}", line);
+    assert!(line.contains("\"mode\":2"), "{}", line);
+    assert!(
+        line.contains("\"time\":\"1994-03-23T12:35:19.000Z\""),
+        "{}",
+        line
+    );
+    assert!(line.contains("\"lat\":48.1173"), "{}", line);
+
+    drop(device);
+    feed.await.unwrap().unwrap();
+}
@@ -0,0 +1,63 @@
+#![cfg(target_os = "linux")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio_serial::handoff::{recv_port, send_port};
+use tokio_serial::{SerialPort, SerialStream, StopBits};
+
+#[tokio::test]
+async fn port_survives_the_trip() {
+    let (broker, worker) = UnixStream::pair().unwrap();
+    let (mut master, mut slave) = SerialStream::pair().expect("unable to open pty");
+    slave.set_baud_rate(57600).unwrap();
+    slave.set_stop_bits(StopBits::Two).unwrap();
+    let path = slave.name().unwrap();
+
+    send_port(&broker, &slave)
+        .await
+        .expect("unable to send port");
+    drop(slave);
+    let (mut port, builder) = recv_port(&worker).await.expect("unable to receive port");
+
+    assert_eq!(
+        builder,
+        tokio_serial::new(path, 57600).stop_bits(StopBits::Two)
+    );
+    assert_eq!(port.baud_rate().unwrap(), 57600);
+    assert_eq!(port.stop_bits().unwrap(), StopBits::Two);
+
+    port.write_all(b"handed over").await.unwrap();
+    let mut buf = [0u8; 11];
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"handed over");
+}
+
+#[tokio::test]
+async fn ports_arrive_in_order() {
+    let (broker, worker) = UnixStream::pair().unwrap();
+    let (_first_master, first) = SerialStream::pair().expect("unable to open pty");
+    let (_second_master, second) = SerialStream::pair().expect("unable to open pty");
+
+    send_port(&broker, &first).await.unwrap();
+    send_port(&broker, &second).await.unwrap();
+    let (_, builder) = recv_port(&worker).await.unwrap();
+    assert_eq!(
+        builder,
+        tokio_serial::new(first.name().unwrap(), first.baud_rate().unwrap())
+    );
+    let (_, builder) = recv_port(&worker).await.unwrap();
+    assert_eq!(
+        builder,
+        tokio_serial::new(second.name().unwrap(), second.baud_rate().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn closed_socket_is_end_of_file() {
+    let (broker, worker) = UnixStream::pair().unwrap();
+    drop(broker);
+    let err = recv_port(&worker).await.unwrap_err();
+    assert_eq!(
+        err.kind(),
+        tokio_serial::ErrorKind::Io(std::io::ErrorKind::UnexpectedEof)
+    );
+}
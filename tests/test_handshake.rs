@@ -0,0 +1,113 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio_serial::codec::handshake::{
+    ChecksumStyle, Controls, HandshakeCodec, Outgoing, Reply, ETX, STX,
+};
+use tokio_serial::handshake::{Handshake, Tap};
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+/// Reads a line, or a block through its checksum
+async fn read_message(port: &mut DuplexStream) -> Vec<u8> {
+    let mut message = Vec::new();
+    loop {
+        let byte = port.read_u8().await.unwrap();
+        message.push(byte);
+        if message[0] == STX {
+            if message.len() > 4 && message[message.len() - 5] == ETX {
+                return message;
+            }
+        } else if byte == b'\r' {
+            return message;
+        }
+    }
+}
+
+/// Answers like a paging terminal, asking for the first page again
+async fn terminal(mut port: DuplexStream) {
+    let mut refused = false;
+    loop {
+        let message = read_message(&mut port).await;
+        let answer: &[u8] = match &message[..] {
+            b"\r" => b"ID=",
+            b"\x1bPG1000000\r" => b"110 1.8\r\x06\r\x1b[p\r",
+            b"\x04\r" => b"115 Goodbye\r\x1e\r\x1b\x04\r",
+            block if block[0] == STX && !refused => {
+                refused = true;
+                b"\x15\r"
+            }
+            b"\x025551234\rPump 3 low\r\x034>?\r" => b"211 Page(s) Sent OK\r\x06\r",
+            _ => b"\x1e\r",
+        };
+        port.write_all(answer).await.unwrap();
+    }
+}
+
+#[test]
+fn checksum_styles() {
+    let block = b"\x02123\rABC\r\x03";
+    assert_eq!(ChecksumStyle::Tap.checksum(block), b"17;");
+    assert_eq!(ChecksumStyle::Sum8.checksum(block), b"7B");
+    assert_eq!(ChecksumStyle::Xor.checksum(block), b"73");
+    assert!(ChecksumStyle::None.checksum(block).is_empty());
+}
+
+#[test]
+fn custom_controls_decode() {
+    let controls = Controls {
+        ack: b'Y',
+        nak: b'N',
+        ..Controls::default()
+    };
+    let mut codec = HandshakeCodec::new(controls, ChecksumStyle::Xor);
+    let mut dst = BytesMut::new();
+    codec
+        .encode(Outgoing::Block(vec!["R".into()]), &mut dst)
+        .unwrap();
+    assert_eq!(&dst[..], b"\x02R\r\x035C\r");
+
+    let mut src = BytesMut::from(&b"busy\nN\r\nY\x1b"[..]);
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Reply::Text("busy".into()))
+    );
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(Reply::Nak));
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(Reply::Ack));
+    // ESC waits for the next byte
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    src.extend_from_slice(b"\x04");
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(Reply::Disconnect));
+}
+
+#[tokio::test(start_paused = true)]
+async fn tap_session_sends_page() {
+    let (ours, theirs) = tokio::io::duplex(256);
+    tokio::spawn(terminal(theirs));
+
+    let mut tap = Tap::new(Framed::new(ours, HandshakeCodec::tap()));
+    tap.login("000000").await.unwrap();
+    let text = tap.send_page("5551234", "Pump 3 low").await.unwrap();
+    assert_eq!(text, ["211 Page(s) Sent OK"]);
+    tap.logout().await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn unacknowledged_block_times_out() {
+    let (ours, mut theirs) = tokio::io::duplex(256);
+    let mut handshake = Handshake::new(Framed::new(ours, HandshakeCodec::default()));
+    handshake.set_retries(1);
+    handshake.set_timeout(Duration::from_secs(1));
+    let err = handshake.send_block(vec!["X".into()]).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    // Sent twice
+    let mut sent = [0; 10];
+    theirs.read_exact(&mut sent).await.unwrap();
+    assert_eq!(&sent, b"\x02X\r\x03\r\x02X\r\x03\r");
+
+    // Abandoned blocks aren't sent again
+    theirs.write_all(b"\x1e").await.unwrap();
+    let err = handshake.send_block(Vec::new()).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+}
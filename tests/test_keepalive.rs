@@ -0,0 +1,47 @@
+#![cfg(feature = "codec")]
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_serial::keepalive::Keepalive;
+use tokio_util::codec::{Framed, LinesCodec, LinesCodecError};
+
+#[tokio::test(start_paused = true)]
+async fn idle_links_get_keepalives() {
+    let (ours, theirs) = tokio::io::duplex(1024);
+    let start = Instant::now();
+    let device = tokio::spawn(async move {
+        let mut device = Framed::new(theirs, LinesCodec::new());
+        let mut arrivals = Vec::new();
+        while let Some(Ok(line)) = device.next().await {
+            arrivals.push((start.elapsed().as_millis(), line.clone()));
+            match line.as_str() {
+                "AT" if arrivals.len() < 4 => device.send("OK").await.unwrap(),
+                "AT" => {}
+                _ => device.send(format!("re: {}", line)).await.unwrap(),
+            }
+        }
+        arrivals
+    });
+
+    let mut link = Keepalive::new(
+        Framed::new(ours, LinesCodec::new()),
+        "AT".to_string(),
+        Duration::from_millis(100),
+    );
+    link.expect_reply(Duration::from_millis(50), |line: &String| line == "OK");
+
+    link.send("data".to_string()).await.unwrap();
+    assert_eq!(link.next().await.unwrap().unwrap(), "re: data");
+
+    // Replies to the keepalives are taken out of the stream, until one goes unanswered
+    match link.next().await.unwrap() {
+        Err(LinesCodecError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(start.elapsed(), Duration::from_millis(350));
+    drop(link);
+
+    let arrivals = device.await.unwrap();
+    let times: Vec<_> = arrivals.iter().map(|(at, _)| *at).collect();
+    assert_eq!(times, [0, 100, 200, 300]);
+}
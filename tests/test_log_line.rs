@@ -0,0 +1,95 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::log_line::{Level, LogFormat, LogLineCodec, LogRecord};
+use tokio_util::codec::Decoder;
+
+#[test]
+fn zephyr_lines_parse() {
+    let record = LogRecord::parse("[00:00:01.234,567] <err> i2c_nrfx: Error on I2C line");
+    assert_eq!(record.format, LogFormat::Zephyr);
+    assert_eq!(record.level, Some(Level::Error));
+    assert_eq!(record.timestamp.as_deref(), Some("00:00:01.234,567"));
+    assert_eq!(record.module.as_deref(), Some("i2c_nrfx"));
+    assert_eq!(record.message, "Error on I2C line");
+
+    let record = LogRecord::parse("\x1b[1;33m<wrn> main: low battery\x1b[0m");
+    assert_eq!(record.level, Some(Level::Warning));
+    assert_eq!(record.timestamp, None);
+    assert_eq!(record.message, "low battery");
+}
+
+#[test]
+fn esp_idf_lines_parse() {
+    let record = LogRecord::parse("\x1b[0;32mI (318) cpu_start: Starting scheduler.\x1b[0m");
+    assert_eq!(record.format, LogFormat::EspIdf);
+    assert_eq!(record.level, Some(Level::Info));
+    assert_eq!(record.timestamp.as_deref(), Some("318"));
+    assert_eq!(record.module.as_deref(), Some("cpu_start"));
+    assert_eq!(record.message, "Starting scheduler.");
+    assert_eq!(LogRecord::parse("V (1) x: y").level, Some(Level::Verbose));
+}
+
+#[test]
+fn syslog_lines_parse() {
+    let record = LogRecord::parse("<11>Oct 16 12:00:00 gateway dhcpd[42]: lease expired: 10.0.0.7");
+    assert_eq!(record.format, LogFormat::Syslog);
+    assert_eq!(record.level, Some(Level::Error));
+    assert_eq!(record.timestamp.as_deref(), Some("Oct 16 12:00:00"));
+    assert_eq!(record.module.as_deref(), Some("dhcpd"));
+    assert_eq!(record.message, "lease expired: 10.0.0.7");
+
+    let record =
+        LogRecord::parse("<165>1 2024-10-16T12:00:00Z host app 7 ID47 [a b=\"c\"] started");
+    assert_eq!(record.level, Some(Level::Info));
+    assert_eq!(record.timestamp.as_deref(), Some("2024-10-16T12:00:00Z"));
+    assert_eq!(record.module.as_deref(), Some("app"));
+    assert_eq!(record.message, "started");
+
+    let record = LogRecord::parse("<15>1 - - - - - - hello");
+    assert_eq!(record.level, Some(Level::Debug));
+    assert_eq!(record.timestamp, None);
+    assert_eq!(record.module, None);
+    assert_eq!(record.message, "hello");
+}
+
+#[test]
+fn unknown_lines_are_kept() {
+    let record = LogRecord::parse("Booting...");
+    assert_eq!(record.format, LogFormat::Unknown);
+    assert_eq!(record.level, None);
+    assert_eq!(record.message, "Booting...");
+    assert!(!record.is_at_least(Level::Verbose));
+}
+
+#[test]
+fn codec_splits_and_filters() {
+    let mut codec = LogLineCodec::new();
+    let mut src =
+        BytesMut::from(&b"I (10) boot: ok\r\n\r\nE (20) boot: flash read failed\r\nW (30) bo"[..]);
+    let mut records = Vec::new();
+    while let Some(record) = codec.decode(&mut src).unwrap() {
+        records.push(record);
+    }
+    assert_eq!(records.len(), 2);
+    let severe: Vec<_> = records
+        .iter()
+        .filter(|r| r.is_at_least(Level::Warning))
+        .map(|r| r.message.as_str())
+        .collect();
+    assert_eq!(severe, ["flash read failed"]);
+
+    src.extend_from_slice(b"ot: slow");
+    let last = codec.decode_eof(&mut src).unwrap().unwrap();
+    assert_eq!(last.level, Some(Level::Warning));
+    assert_eq!(last.message, "slow");
+    assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+}
+
+#[test]
+fn long_lines_are_split() {
+    let mut codec = LogLineCodec::with_max_length(8);
+    let mut src = BytesMut::from(&b"0123456789abc\n"[..]);
+    assert_eq!(codec.decode(&mut src).unwrap().unwrap().message, "01234567");
+    assert_eq!(codec.decode(&mut src).unwrap().unwrap().message, "89abc");
+    assert!(src.is_empty());
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::test::MockSerial;
+use tokio_serial::SerialPort;
+
+#[tokio::test]
+async fn mock_pair_passes_data_and_control_lines() {
+    let (mut a, mut b) = MockSerial::pair();
+
+    a.write_all(b"hello").await.unwrap();
+    let mut buf = [0u8; 5];
+    b.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"hello");
+
+    assert!(b.read_clear_to_send().unwrap());
+    a.write_request_to_send(false).unwrap();
+    a.write_data_terminal_ready(false).unwrap();
+    assert!(!b.read_clear_to_send().unwrap());
+    assert!(!b.read_data_set_ready().unwrap());
+    assert!(!b.read_carrier_detect().unwrap());
+    assert!(a.read_data_set_ready().unwrap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn mock_script_runs_in_order() {
+    let mut port = MockSerial::builder()
+        .write(b"AT\r")
+        .wait(Duration::from_secs(5))
+        .read(b"OK\r\n")
+        .read_error(std::io::ErrorKind::BrokenPipe.into())
+        .build();
+
+    port.write_all(b"AT\r").await.unwrap();
+    let start = tokio::time::Instant::now();
+    let mut reply = [0u8; 4];
+    port.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"OK\r\n");
+    assert!(start.elapsed() >= Duration::from_secs(5));
+
+    let err = port.read(&mut reply).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+    assert_eq!(port.read(&mut reply).await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[should_panic(expected = "write does not match the script")]
+async fn mock_script_rejects_unexpected_writes() {
+    let mut port = MockSerial::builder().write(b"AT\r").build();
+    port.write_all(b"ATZ").await.unwrap();
+}
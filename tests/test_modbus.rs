@@ -0,0 +1,135 @@
+#![cfg(all(unix, feature = "codec"))]
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use tokio_serial::codec::modbus::{crc16, lrc, ModbusAsciiCodec, ModbusRtuCodec, RtuFrame};
+use tokio_serial::codec::ChecksumPolicy;
+use tokio_serial::frame::TimedFramed;
+use tokio_serial::modbus::{self, Exception, ModbusRtuMaster};
+use tokio_serial::SerialStream;
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::time::Duration;
+
+#[test]
+fn crc_matches_reference() {
+    // Read 2 holding registers of slave 1 from 0
+    assert_eq!(
+        crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]).to_le_bytes(),
+        [0xc4, 0x0b]
+    );
+
+    let mut dst = BytesMut::new();
+    ModbusRtuCodec::new()
+        .encode(RtuFrame::new(1, 3, vec![0, 0, 0, 2]), &mut dst)
+        .unwrap();
+    assert_eq!(&dst[..], [0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0xc4, 0x0b]);
+}
+
+#[test]
+fn ascii_frames_round_trip() {
+    assert_eq!(lrc(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]), 0xfa);
+
+    let mut codec = ModbusAsciiCodec::new();
+    let frame = RtuFrame::new(1, 3, vec![0, 0, 0, 2]);
+    let mut dst = BytesMut::new();
+    codec.encode(frame.clone(), &mut dst).unwrap();
+    assert_eq!(&dst[..], b":010300000002FA\r\n");
+
+    // Noise, and a frame cut short by the next one
+    let mut src = BytesMut::from(&b"\x00:0103:0103"[..]);
+    src.extend_from_slice(&dst[5..]);
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(frame));
+    assert!(src.is_empty());
+    assert_eq!(codec.resync_stats().skipped_bytes(), 6);
+}
+
+#[test]
+fn ascii_lrc_mismatch_follows_policy() {
+    let mut codec = ModbusAsciiCodec::new();
+    let mut src = BytesMut::from(&b":010300000002FB\r\n"[..]);
+    let err = codec.decode(&mut src).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    codec.set_checksum_policy(ChecksumPolicy::Drop);
+    let mut src = BytesMut::from(&b":0103ZZ\r\n:0106000100037\r\n:010600010003F5\r\n"[..]);
+    let frame = codec.decode(&mut src).unwrap().unwrap();
+    assert_eq!(frame, RtuFrame::new(1, 6, vec![0, 1, 0, 3]));
+}
+
+#[tokio::test]
+async fn master_reads_and_writes_registers() {
+    let (port, slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut slave = TimedFramed::new(slave, ModbusRtuCodec::new());
+    tokio::spawn(async move {
+        while let Some(Ok(request)) = slave.next().await {
+            let response = match (request.address, request.function) {
+                // Late answer of another slave, skipped by the master
+                (2, 0x03) => RtuFrame::new(9, 0x03, vec![2, 0, 0]),
+                (2, 0x06) => RtuFrame::new(2, 0x06, request.data.clone()),
+                (2, 0x04) => RtuFrame::new(2, 0x84, vec![0x02]),
+                _ => continue,
+            };
+            slave.send(response.clone()).await.unwrap();
+            if response.address == 9 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let data = vec![4, 0x12, 0x34, 0xab, 0xcd];
+                slave.send(RtuFrame::new(2, 0x03, data)).await.unwrap();
+            }
+        }
+    });
+
+    let mut master = ModbusRtuMaster::new(port);
+    master.set_timeout(Duration::from_millis(200));
+    assert_eq!(
+        master.read_holding_registers(2, 0, 2).await.unwrap(),
+        [0x1234, 0xabcd]
+    );
+    master.write_single_register(2, 0x10, 7).await.unwrap();
+
+    let err = master.read_input_registers(2, 0xffff, 1).await.unwrap_err();
+    assert_eq!(modbus::exception(&err), Some(Exception::IllegalDataAddress));
+
+    let err = master.read_holding_registers(3, 0, 1).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    let err = master.read_holding_registers(2, 0, 0).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn slave_answers_master() {
+    use std::sync::{Arc, Mutex};
+    use tokio_serial::modbus::{ModbusRtuSlave, Registers};
+
+    let (port, device) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut registers = Registers::new(8, 2);
+    registers.input_mut().copy_from_slice(&[215, 1013]);
+    let registers = Arc::new(Mutex::new(registers));
+    tokio::spawn(ModbusRtuSlave::new(device, 5).run(registers.clone()));
+
+    let mut master = ModbusRtuMaster::new(port);
+    master.set_timeout(Duration::from_millis(200));
+    assert_eq!(
+        master.read_input_registers(5, 0, 2).await.unwrap(),
+        [215, 1013]
+    );
+    master
+        .write_multiple_registers(5, 2, &[1, 2, 3])
+        .await
+        .unwrap();
+    master.write_single_register(0, 7, 42).await.unwrap();
+    assert_eq!(
+        master.read_holding_registers(5, 1, 7).await.unwrap(),
+        [0, 1, 2, 3, 0, 0, 42]
+    );
+
+    let err = master.read_holding_registers(5, 6, 4).await.unwrap_err();
+    assert_eq!(modbus::exception(&err), Some(Exception::IllegalDataAddress));
+    let err = master.request(5, 0x2b, &[0x0e]).await.unwrap_err();
+    assert_eq!(modbus::exception(&err), Some(Exception::IllegalFunction));
+    // Other addresses are ignored
+    let err = master.read_input_registers(6, 0, 1).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+    assert_eq!(registers.lock().unwrap().holding()[2..5], [1, 2, 3]);
+}
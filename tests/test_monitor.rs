@@ -0,0 +1,91 @@
+#![cfg(all(target_os = "linux", feature = "monitor"))]
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::time::{sleep, timeout};
+use tokio_serial::monitor::{Direction, Monitor, Sniffer};
+use tokio_serial::SerialStream;
+
+#[tokio::test]
+async fn bursts_split_at_silences() {
+    let (mut master, path) = SerialStream::pair_named().expect("unable to open pty");
+    let mut bus = Monitor::open(&tokio_serial::new(path.to_str().unwrap(), 38400)).unwrap();
+    bus.set_gap(Duration::from_millis(50));
+
+    // The bursts must arrive while the monitor listens, not wait in the pty together
+    tokio::spawn(async move {
+        master.write_all(b"\x01\x03\x00\x00").await.unwrap();
+        master.write_all(b"\x00\x01").await.unwrap();
+        sleep(Duration::from_millis(200)).await;
+        master.write_all(b"\x01\x03\x02\x00\x2a").await.unwrap();
+        sleep(Duration::from_secs(1)).await;
+    });
+
+    let first = timeout(Duration::from_secs(1), bus.next())
+        .await
+        .expect("no frame")
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.bytes, b"\x01\x03\x00\x00\x00\x01");
+    let second = timeout(Duration::from_secs(1), bus.next())
+        .await
+        .expect("no frame")
+        .unwrap()
+        .unwrap();
+    assert_eq!(second.bytes, b"\x01\x03\x02\x00\x2a");
+    assert!(second.start - first.end >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn long_traffic_is_cut() {
+    let (mut master, path) = SerialStream::pair_named().expect("unable to open pty");
+    let mut bus = Monitor::open(&tokio_serial::new(path.to_str().unwrap(), 38400)).unwrap();
+    bus.set_gap(Duration::from_secs(1));
+    bus.set_max_frame_len(4);
+
+    master.write_all(b"abcdef").await.unwrap();
+    let frame = timeout(Duration::from_millis(500), bus.next())
+        .await
+        .expect("frame not cut")
+        .unwrap()
+        .unwrap();
+    assert_eq!(frame.bytes, b"abcdef");
+}
+
+#[tokio::test]
+async fn sniffer_orders_directions() {
+    let (mut host, host_path) = SerialStream::pair_named().expect("unable to open pty");
+    let (mut device, device_path) = SerialStream::pair_named().expect("unable to open pty");
+    let mut sniffer = Sniffer::open(
+        &tokio_serial::new(host_path.to_str().unwrap(), 38400),
+        &tokio_serial::new(device_path.to_str().unwrap(), 38400),
+    )
+    .unwrap();
+    sniffer.set_gap(Duration::from_millis(100));
+
+    // The device answers while the host's frame is still going, which then ends last
+    tokio::spawn(async move {
+        host.write_all(b"req").await.unwrap();
+        sleep(Duration::from_millis(20)).await;
+        device.write_all(b"ack").await.unwrap();
+        sleep(Duration::from_millis(40)).await;
+        host.write_all(b"uest").await.unwrap();
+        sleep(Duration::from_secs(1)).await;
+    });
+
+    let mut frames = Vec::new();
+    for _ in 0..2 {
+        let frame = timeout(Duration::from_secs(1), sniffer.next())
+            .await
+            .expect("no frame")
+            .unwrap()
+            .unwrap();
+        frames.push(frame);
+    }
+    let (first, second) = (&frames[0], &frames[1]);
+    assert_eq!(first.direction, Direction::HostToDevice);
+    assert_eq!(first.frame.bytes, b"request");
+    assert_eq!(second.direction, Direction::DeviceToHost);
+    assert_eq!(second.frame.bytes, b"ack");
+    assert!(second.frame.end < first.frame.end);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::mstp::{data_crc, FrameType, MacTiming, MstpCodec, MstpFrame};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::time::Duration;
+
+#[test]
+fn token_frame_matches_reference() {
+    let mut dst = BytesMut::new();
+    MstpCodec::new()
+        .encode(MstpFrame::token(0x10, 0x05), &mut dst)
+        .unwrap();
+    assert_eq!(&dst[..], [0x55, 0xff, 0x00, 0x10, 0x05, 0x00, 0x00, 0x8c]);
+    assert_eq!(data_crc(&[0x01, 0x22, 0x30]), 0xbd10);
+}
+
+#[test]
+fn frames_round_trip_through_noise() {
+    let frames = [
+        MstpFrame::new(FrameType::DataExpectingReply, 3, 1, vec![0x01, 0x22, 0x30]),
+        MstpFrame::token(0xff, 3),
+    ];
+    let mut codec = MstpCodec::new();
+    let mut src = BytesMut::from(&[0x55, 0x00, 0x55][..]);
+    for frame in frames.iter() {
+        codec.encode(frame.clone(), &mut src).unwrap();
+    }
+    assert_eq!(src[src.len() - 10..src.len() - 8], [0x10, 0xbd]);
+
+    for frame in frames.iter() {
+        assert_eq!(codec.decode(&mut src).unwrap().as_ref(), Some(frame));
+    }
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    assert_eq!(codec.resync_stats().skipped_bytes(), 3);
+}
+
+#[test]
+fn corrupt_header_resyncs() {
+    let mut codec = MstpCodec::new();
+    let mut src = BytesMut::new();
+    codec.encode(MstpFrame::token(1, 2), &mut src).unwrap();
+    src[7] ^= 1;
+    codec.encode(MstpFrame::token(2, 1), &mut src).unwrap();
+    let err = codec.decode(&mut src).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(MstpFrame::token(2, 1))
+    );
+}
+
+#[test]
+fn timing_follows_baud_rate() {
+    let timing = MacTiming::new(38400);
+    assert_eq!(timing.turnaround, Duration::from_nanos(26_041 * 40));
+    assert_eq!(timing.token_regeneration(3), Duration::from_millis(530));
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::nmea::{NmeaCodec, NmeaEncoder, Sentence};
+use tokio_util::codec::{Decoder, Encoder};
+
+use std::time::Duration;
+
+#[test]
+fn encoded_body_decodes() {
+    let mut encoder = NmeaEncoder::new();
+    let mut dst = BytesMut::new();
+    encoder
+        .encode("GPGLL,5300.97914,N,00259.98174,E,125926,A", &mut dst)
+        .unwrap();
+    assert_eq!(
+        &dst[..],
+        b"$GPGLL,5300.97914,N,00259.98174,E,125926,A*28\r\n"
+    );
+
+    let sentence = NmeaCodec::new().decode(&mut dst).unwrap().unwrap();
+    assert_eq!(sentence.address(), "GPGLL");
+}
+
+#[test]
+fn invalid_body_is_rejected() {
+    let mut encoder = NmeaEncoder::new();
+    let mut dst = BytesMut::new();
+    let err = encoder.encode("GPGLL,1*00", &mut dst).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(dst.is_empty());
+}
+
+#[test]
+fn proprietary_sentences_are_rate_limited() {
+    let mut encoder = NmeaEncoder::new();
+    encoder.limit_proprietary(Some(Duration::from_secs(60)));
+    let mut dst = BytesMut::new();
+    encoder.encode("PUBX,40,GSV,0,0,0,0,0,0", &mut dst).unwrap();
+    encoder.encode("PUBX,40,GSV,0,0,0,0,0,0", &mut dst).unwrap();
+    encoder.encode("PMTK220,1000", &mut dst).unwrap();
+    encoder.encode("GPTXT,01,01,02,hello", &mut dst).unwrap();
+    encoder.encode("GPTXT,01,01,02,hello", &mut dst).unwrap();
+    encoder
+        .encode(
+            Sentence::encapsulated("PUBX,40,GSV,0,0,0,0,0,0").unwrap(),
+            &mut dst,
+        )
+        .unwrap();
+
+    assert_eq!(encoder.suppressed(), 2);
+    assert_eq!(dst.iter().filter(|&&b| b == b'\n').count(), 4);
+}
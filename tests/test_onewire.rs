@@ -0,0 +1,123 @@
+#![cfg(all(unix, feature = "time"))]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::onewire::{crc8, OneWire, Rom, READ_ROM, SEARCH_ROM};
+use tokio_serial::SerialStream;
+
+fn rom(family: u8, serial: u8) -> Rom {
+    let mut code = [family, serial, 0, 0, 0, 0, serial, 0];
+    code[7] = crc8(&code[..7]);
+    Rom(code)
+}
+
+fn bit(rom: &Rom, position: usize) -> bool {
+    rom.0[position / 8] >> (position % 8) & 1 != 0
+}
+
+/// Answers the time slots of a bus with `devices`, as the UART would read them back
+async fn emulate(mut line: SerialStream, devices: Vec<Rom>) {
+    let mut command = Vec::new();
+    // Search: devices still taking part, bit position and slot within it
+    let mut search: Option<(Vec<Rom>, usize, usize)> = None;
+    let mut reading: Option<(Rom, usize)> = None;
+    loop {
+        let mut slot = [0];
+        if line.read(&mut slot).await.unwrap_or(0) == 0 {
+            return;
+        }
+        let echo = match slot[0] {
+            0xf0 => {
+                command.clear();
+                search = None;
+                reading = None;
+                if devices.is_empty() {
+                    0xf0
+                } else {
+                    0xe0
+                }
+            }
+            slot if command.len() < 8 => {
+                command.push(slot == 0xff);
+                if command.len() == 8 {
+                    let byte = command
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |byte, (bit, &set)| byte | (u8::from(set) << bit));
+                    match byte {
+                        SEARCH_ROM => search = Some((devices.clone(), 0, 0)),
+                        READ_ROM => reading = Some((devices[0], 0)),
+                        _ => {}
+                    }
+                }
+                slot
+            }
+            slot => match (search.as_mut(), reading.as_mut()) {
+                (Some((active, position, phase)), _) => {
+                    let answer = match *phase {
+                        0 => active.iter().all(|rom| bit(rom, *position)),
+                        1 => active.iter().all(|rom| !bit(rom, *position)),
+                        _ => {
+                            let direction = slot == 0xff;
+                            let at = *position;
+                            active.retain(|rom| bit(rom, at) == direction);
+                            *position += 1;
+                            slot == 0xff
+                        }
+                    };
+                    *phase = (*phase + 1) % 3;
+                    if answer {
+                        0xff
+                    } else {
+                        0x00
+                    }
+                }
+                (None, Some((rom, position))) => {
+                    *position += 1;
+                    if bit(rom, *position - 1) {
+                        0xff
+                    } else {
+                        0x00
+                    }
+                }
+                (None, None) => slot,
+            },
+        };
+        line.write_all(&[echo]).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn search_finds_every_device() {
+    let devices = vec![rom(0x28, 0x11), rom(0x28, 0x12), rom(0x10, 0x80)];
+    let (port, line) = SerialStream::pair().expect("unable to create ptty pair");
+    tokio::spawn(emulate(line, devices.clone()));
+
+    let mut bus = OneWire::new(port).unwrap();
+    let mut found = bus.search().await.unwrap();
+    found.sort();
+    let mut expected = devices;
+    expected.sort();
+    assert_eq!(found, expected);
+    assert!(found.iter().all(Rom::is_valid));
+}
+
+#[tokio::test]
+async fn single_device_rom_is_read() {
+    let device = rom(0x28, 0x42);
+    let (port, line) = SerialStream::pair().expect("unable to create ptty pair");
+    tokio::spawn(emulate(line, vec![device]));
+
+    let mut bus = OneWire::new(port).unwrap();
+    assert!(bus.reset().await.unwrap());
+    assert_eq!(bus.read_rom().await.unwrap(), device);
+    assert_eq!(device.family(), 0x28);
+}
+
+#[tokio::test]
+async fn empty_bus_has_no_presence() {
+    let (port, line) = SerialStream::pair().expect("unable to create ptty pair");
+    tokio::spawn(emulate(line, Vec::new()));
+
+    let mut bus = OneWire::new(port).unwrap();
+    assert!(!bus.reset().await.unwrap());
+    assert!(bus.search().await.unwrap().is_empty());
+}
@@ -0,0 +1,32 @@
+#![cfg(unix)]
+use tokio_serial::SerialStream;
+
+#[tokio::test]
+async fn all_ports_open_in_order() {
+    let (_a, a_path) = SerialStream::pair_named().expect("unable to open pty");
+    let (_b, b_path) = SerialStream::pair_named().expect("unable to open pty");
+    let ports = tokio_serial::open_all(vec![
+        tokio_serial::new(a_path.to_str().unwrap(), 9600),
+        tokio_serial::new(b_path.to_str().unwrap(), 9600),
+    ])
+    .await
+    .unwrap();
+    assert_eq!(ports.len(), 2);
+}
+
+#[tokio::test]
+async fn failures_are_reported_with_their_path() {
+    let (_a, a_path) = SerialStream::pair_named().expect("unable to open pty");
+    let report = tokio_serial::open_all(vec![
+        tokio_serial::new(a_path.to_str().unwrap(), 9600),
+        tokio_serial::new("/dev/does-not-exist", 9600),
+    ])
+    .await
+    .unwrap_err();
+    let failures: Vec<_> = report.failures().map(|(path, _)| path).collect();
+    assert_eq!(failures, ["/dev/does-not-exist"]);
+    assert!(report
+        .to_string()
+        .starts_with("1 of 2 ports failed to open"));
+    assert_eq!(report.into_opened().len(), 1);
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "codec")]
+use futures::{SinkExt, StreamExt};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_serial::poller::{Poll, Poller};
+use tokio_util::codec::{Framed, LinesCodec};
+
+type Port = Framed<tokio::io::DuplexStream, LinesCodec>;
+
+/// A device answering `re: <request>`, except to `mute`, returning when each request came
+fn device(theirs: tokio::io::DuplexStream) -> tokio::task::JoinHandle<Vec<(Duration, String)>> {
+    let start = Instant::now();
+    tokio::spawn(async move {
+        let mut device = Framed::new(theirs, LinesCodec::new());
+        let mut arrivals = Vec::new();
+        while let Some(Ok(request)) = device.next().await {
+            arrivals.push((start.elapsed(), request.clone()));
+            if request != "mute" {
+                device.send(format!("re: {}", request)).await.unwrap();
+            }
+        }
+        arrivals
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn entries_are_polled_at_their_rates() {
+    let (ours, theirs) = tokio::io::duplex(1024);
+    let device = device(theirs);
+
+    let mut poller: Poller<Port, String, String> =
+        Poller::new(Framed::new(ours, LinesCodec::new()));
+    let fast = Arc::new(Mutex::new(Vec::new()));
+    let seen = fast.clone();
+    poller.on_response(
+        Poll::new("fast".to_string(), Duration::from_millis(100)),
+        move |response| seen.lock().unwrap().push(response.unwrap()),
+    );
+    let mut slow = poller.subscribe(
+        Poll::new("slow".to_string(), Duration::from_millis(250)).offset(Duration::from_millis(10)),
+    );
+    let poller = tokio::spawn(poller.run());
+
+    assert_eq!(slow.next().await.unwrap().unwrap(), "re: slow");
+    assert_eq!(slow.next().await.unwrap().unwrap(), "re: slow");
+    drop(slow);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    poller.abort();
+    let _ = poller.await;
+
+    let arrivals = device.await.unwrap();
+    let at = |request: &str| {
+        arrivals
+            .iter()
+            .filter(|(_, r)| r == request)
+            .map(|(at, _)| at.as_millis())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(at("fast"), [0, 100, 200, 300, 400, 500, 600, 700]);
+    // Stopped once its stream was dropped
+    assert_eq!(at("slow"), [10, 260, 510]);
+    assert_eq!(fast.lock().unwrap().len(), 8);
+}
+
+#[tokio::test(start_paused = true)]
+async fn unanswered_and_late_polls() {
+    let (ours, theirs) = tokio::io::duplex(1024);
+    let device = device(theirs);
+
+    let mut poller: Poller<Port, String, String> =
+        Poller::new(Framed::new(ours, LinesCodec::new()));
+    poller.set_timeout(Duration::from_millis(150));
+    poller.set_max_lateness(Some(Duration::from_millis(20)));
+    let mut mute = poller.subscribe(Poll::new("mute".to_string(), Duration::from_millis(500)));
+    // Due while the mute poll times out, so late by 50ms and skipped once
+    let mut other = poller.subscribe(
+        Poll::new("other".to_string(), Duration::from_millis(100))
+            .offset(Duration::from_millis(100))
+            .matching(|response: &String| response.ends_with("other")),
+    );
+    let poller = tokio::spawn(poller.run());
+
+    let err = mute.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(other.next().await.unwrap().unwrap(), "re: other");
+    poller.abort();
+    let _ = poller.await;
+
+    let arrivals = device.await.unwrap();
+    let times: Vec<_> = arrivals.iter().map(|(at, _)| at.as_millis()).collect();
+    assert_eq!(times, [0, 200]);
+}
@@ -0,0 +1,71 @@
+#![cfg(unix)]
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::time::timeout;
+use tokio_serial::port_set::{PortSet, SetEvent};
+use tokio_serial::SerialStream;
+
+#[tokio::test]
+async fn bytes_come_tagged_with_their_port() {
+    let (mut a, a_slave) = SerialStream::pair().expect("unable to open pty");
+    let (mut b, b_slave) = SerialStream::pair().expect("unable to open pty");
+    let mut set = PortSet::new();
+    let a_key = set.insert(a_slave);
+    let b_key = set.insert(b_slave);
+    assert_eq!(set.len(), 2);
+
+    b.write_all(b"from b").await.unwrap();
+    match timeout(Duration::from_secs(1), set.next_event())
+        .await
+        .unwrap()
+    {
+        (key, SetEvent::Data(bytes)) => {
+            assert_eq!(key, b_key);
+            assert_eq!(bytes, b"from b");
+        }
+        (_, other) => panic!("unexpected {:?}", other),
+    }
+
+    a.write_all(b"from a").await.unwrap();
+    match timeout(Duration::from_secs(1), set.next_event())
+        .await
+        .unwrap()
+    {
+        (key, SetEvent::Data(bytes)) => {
+            assert_eq!(key, a_key);
+            assert_eq!(bytes, b"from a");
+        }
+        (_, other) => panic!("unexpected {:?}", other),
+    }
+
+    drop(a);
+    match timeout(Duration::from_secs(1), set.next_event())
+        .await
+        .unwrap()
+    {
+        (key, SetEvent::Hangup(_)) => assert_eq!(key, a_key),
+        (_, other) => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(set.keys().collect::<Vec<_>>(), vec![b_key]);
+}
+
+#[tokio::test]
+async fn empty_set_wakes_on_insert() {
+    let (mut master, slave) = SerialStream::pair().expect("unable to open pty");
+    master.write_all(b"x").await.unwrap();
+    let mut set = PortSet::new();
+    assert!(timeout(Duration::from_millis(50), set.next_event())
+        .await
+        .is_err());
+    let key = set.insert(slave);
+    match timeout(Duration::from_secs(1), set.next_event())
+        .await
+        .unwrap()
+    {
+        (got, SetEvent::Data(bytes)) => {
+            assert_eq!(got, key);
+            assert_eq!(bytes, b"x");
+        }
+        (_, other) => panic!("unexpected {:?}", other),
+    }
+}
@@ -0,0 +1,17 @@
+#![cfg(target_os = "linux")]
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_serial::pps::PpsLine;
+use tokio_serial::SerialStream;
+
+#[tokio::test]
+async fn line_without_modem_lines_is_an_error() {
+    let (_master, slave) = SerialStream::pair().expect("unable to open pty");
+    let mut pulses = slave.pps(PpsLine::Dcd).unwrap();
+    let first = timeout(Duration::from_secs(1), pulses.next())
+        .await
+        .expect("no edge or error");
+    assert!(first.expect("stream ended").is_err());
+    assert!(pulses.next().await.is_none());
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "time")]
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::probe::{probe, Probe};
+
+#[tokio::test(start_paused = true)]
+async fn probe_waits_for_the_pattern() {
+    let (mut ours, mut theirs) = tokio::io::duplex(64);
+    tokio::spawn(async move {
+        let mut request = [0; 3];
+        theirs.read_exact(&mut request).await.unwrap();
+        assert_eq!(&request, b"AT\r");
+        // Echo first, the answer split in two
+        theirs.write_all(b"AT\r\r\nO").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        theirs.write_all(b"K\r\n").await.unwrap();
+        theirs
+    });
+
+    let rtt = Probe::new(&b"AT\r"[..], &b"OK"[..])
+        .run(&mut ours)
+        .await
+        .unwrap();
+    assert_eq!(rtt, Duration::from_millis(30));
+}
+
+#[tokio::test(start_paused = true)]
+async fn silent_device_fails_the_probe() {
+    let (mut ours, _theirs) = tokio::io::duplex(64);
+    let err = probe(&mut ours, b"ping", b"pong", Duration::from_millis(100))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+}
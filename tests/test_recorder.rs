@@ -0,0 +1,75 @@
+#![cfg(feature = "time")]
+use futures::StreamExt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio_serial::recorder::Recorder;
+
+/// A log file shared with the test
+#[derive(Clone, Default)]
+struct Log(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Log {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        std::io::Write::write(&mut *self.0.lock().unwrap(), buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn lines_are_stamped_and_logged() {
+    let (ours, mut theirs) = tokio::io::duplex(64);
+    tokio::spawn(async move {
+        theirs.write_all(b"U-Boot\r\nDRAM: ").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        theirs.write_all(b"512 MiB\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        theirs
+            .write_all(b"Starting kernel\n\xffpartial")
+            .await
+            .unwrap();
+    });
+
+    let log = Log::default();
+    let lines: Vec<_> = Recorder::new(ours)
+        .record_to(log.clone())
+        .reset_on("Starting kernel")
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    let texts: Vec<_> = lines.iter().map(|line| line.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        [
+            "U-Boot",
+            "DRAM: 512 MiB",
+            "Starting kernel",
+            "\u{fffd}partial"
+        ]
+    );
+    // Lines are stamped with the arrival of their first byte
+    let times: Vec<_> = lines
+        .iter()
+        .map(|line| (line.elapsed, line.delta))
+        .collect();
+    assert_eq!(
+        times,
+        [
+            (Duration::from_millis(0), Duration::from_millis(0)),
+            (Duration::from_millis(0), Duration::from_millis(0)),
+            (Duration::from_millis(0), Duration::from_millis(1750)),
+            (Duration::from_millis(0), Duration::from_millis(0)),
+        ]
+    );
+
+    let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        log.lines().nth(2),
+        Some("[    0.000000  1.750000] Starting kernel")
+    );
+}
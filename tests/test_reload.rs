@@ -0,0 +1,69 @@
+#![cfg(unix)]
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+use tokio_serial::reload::{ManagedPort, PortSettings, ReloadEvent};
+use tokio_serial::{ConfigChange, SerialConfig, SerialPort, SerialStream};
+
+#[tokio::test]
+async fn settings_change_on_the_next_write() {
+    let (mut master, path) = SerialStream::pair_named().expect("unable to open pty");
+    let path = path.to_str().unwrap().to_owned();
+    let mut port = ManagedPort::open(PortSettings::new(&path, SerialConfig::new(9600))).unwrap();
+    let handle = port.handle();
+    let mut events = port.events();
+
+    assert!(handle.update(PortSettings::new(&path, SerialConfig::new(19200))));
+    port.write_all(b"ping").await.unwrap();
+    assert_eq!(port.get_ref().baud_rate().unwrap(), 19200);
+    match timeout(Duration::from_secs(1), events.next())
+        .await
+        .unwrap()
+    {
+        Some(ReloadEvent::Reconfigured { diff, .. }) => {
+            assert_eq!(diff.changes(), [ConfigChange::BaudRate(9600, 19200)])
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let mut buf = [0; 4];
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+}
+
+#[tokio::test]
+async fn new_path_reopens() {
+    let (_first, first_path) = SerialStream::pair_named().expect("unable to open pty");
+    let (mut second, second_path) = SerialStream::pair_named().expect("unable to open pty");
+    let settings = PortSettings::new(first_path.to_str().unwrap(), SerialConfig::new(9600));
+    let mut port = ManagedPort::open(settings).unwrap();
+    let mut events = port.events();
+
+    port.handle().update(PortSettings::new(
+        second_path.to_str().unwrap(),
+        SerialConfig::new(9600),
+    ));
+    port.write_all(b"moved").await.unwrap();
+    assert!(matches!(
+        events.next().await,
+        Some(ReloadEvent::Reopened { .. })
+    ));
+    let mut buf = [0; 5];
+    timeout(Duration::from_secs(1), second.read_exact(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&buf, b"moved");
+
+    port.handle().update(PortSettings::new(
+        "/dev/does-not-exist",
+        SerialConfig::new(9600),
+    ));
+    port.write_all(b"x").await.unwrap();
+    assert!(matches!(
+        events.next().await,
+        Some(ReloadEvent::Failed { .. })
+    ));
+    assert_eq!(port.settings().path, second_path.to_str().unwrap());
+}
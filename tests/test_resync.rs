@@ -0,0 +1,69 @@
+#![cfg(feature = "codec")]
+use bytes::BytesMut;
+use tokio_serial::codec::demux::DemuxCodec;
+use tokio_serial::codec::nmea::NmeaCodec;
+use tokio_serial::codec::ubx::{UbxCodec, UbxFrame};
+use tokio_util::codec::{Decoder, Encoder};
+
+const GGA: &str = "$GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*76\r\n";
+
+#[test]
+fn clean_line_skips_nothing() {
+    let mut codec = NmeaCodec::new();
+    let stats = codec.resync_stats();
+    let mut src = BytesMut::from(GGA.repeat(3).as_bytes());
+    while codec.decode(&mut src).unwrap().is_some() {}
+    assert_eq!(stats.skipped_bytes(), 0);
+    assert_eq!(stats.resyncs(), 0);
+}
+
+#[test]
+fn noise_run_counts_once_across_reads() {
+    let mut codec = NmeaCodec::new();
+    let stats = codec.resync_stats();
+    let mut src = BytesMut::from(&b"\x00\x13"[..]);
+    assert!(codec.decode(&mut src).unwrap().is_none());
+    src.extend_from_slice(b"\xff\xfe\xfd");
+    assert!(codec.decode(&mut src).unwrap().is_none());
+    src.extend_from_slice(GGA.as_bytes());
+    assert!(codec.decode(&mut src).unwrap().is_some());
+    assert_eq!(stats.skipped_bytes(), 5);
+    assert_eq!(stats.resyncs(), 1);
+
+    src.extend_from_slice(b"\x00");
+    src.extend_from_slice(GGA.as_bytes());
+    assert!(codec.decode(&mut src).unwrap().is_some());
+    assert_eq!(stats.skipped_bytes(), 6);
+    assert_eq!(stats.resyncs(), 2);
+    assert_eq!(stats.corrupt_frames(), 0);
+
+    stats.reset();
+    assert_eq!(stats.skipped_bytes(), 0);
+}
+
+#[test]
+fn corrupt_ubx_frame_is_counted() {
+    let mut src = BytesMut::new();
+    UbxCodec::new()
+        .encode(UbxFrame::new(0x01, 0x07, vec![1, 2, 3, 4]), &mut src)
+        .unwrap();
+    let len = src.len();
+    src[7] ^= 0xff;
+
+    let mut codec = UbxCodec::new();
+    let stats = codec.resync_stats();
+    assert!(codec.decode(&mut src).is_err());
+    assert_eq!(stats.corrupt_frames(), 1);
+    assert_eq!(stats.skipped_bytes(), len as u64);
+}
+
+#[test]
+fn demux_counts_garbage_between_protocols() {
+    let mut codec = DemuxCodec::gnss();
+    let stats = codec.resync_stats();
+    let mut src = BytesMut::from(&b"garbage"[..]);
+    src.extend_from_slice(GGA.as_bytes());
+    assert!(codec.decode(&mut src).unwrap().is_some());
+    assert_eq!(stats.skipped_bytes(), 7);
+    assert_eq!(stats.resyncs(), 1);
+}
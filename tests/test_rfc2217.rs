@@ -0,0 +1,120 @@
+#![cfg(feature = "rfc2217")]
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_serial::{SerialPort, SerialStream};
+
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const COM_PORT_OPTION: u8 = 44;
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[tokio::test]
+async fn client_configures_remote_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        let mut buf = [0u8; 256];
+
+        // baud rate of 115200 and escaped data
+        let baud = [IAC, SB, COM_PORT_OPTION, 1, 0x00, 0x01, 0xc2, 0x00, IAC, SE];
+        let data = [b'a', IAC, IAC, b'b'];
+        while !(contains(&received, &baud) && contains(&received, &data)) {
+            let n = socket.read(&mut buf).await.unwrap();
+            assert_ne!(n, 0, "client closed before sending settings");
+            received.extend_from_slice(&buf[..n]);
+        }
+
+        // Report CTS asserted, then send data containing an escaped IAC
+        socket
+            .write_all(&[IAC, SB, COM_PORT_OPTION, 107, 0x10, IAC, SE])
+            .await
+            .unwrap();
+        socket.write_all(&[b'x', IAC, IAC, b'y']).await.unwrap();
+        socket
+    });
+
+    let builder = tokio_serial::new("", 115200);
+    let mut client = SerialStream::open_rfc2217(addr, &builder)
+        .await
+        .expect("unable to connect");
+    assert_eq!(client.baud_rate().unwrap(), 115200);
+
+    client.write_all(&[b'a', IAC, b'b']).await.unwrap();
+    client.flush().await.unwrap();
+
+    let mut buf = [0u8; 3];
+    client.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, [b'x', IAC, b'y']);
+    assert!(client.read_clear_to_send().unwrap());
+
+    drop(server.await.unwrap());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_bridges_local_port() {
+    use tokio_serial::rfc2217::Server;
+
+    let (master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let server = Server::bind("127.0.0.1:0", master).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.run());
+
+    let builder = tokio_serial::new("", 9600);
+    let mut client = SerialStream::open_rfc2217(addr, &builder)
+        .await
+        .expect("unable to connect");
+
+    let message = [b'p', b'i', IAC, b'n', b'g'];
+    client.write_all(&message).await.unwrap();
+    let mut buf = [0u8; 5];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, message);
+
+    slave.write_all(&message).await.unwrap();
+    client.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, message);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_reports_stats() {
+    use tokio_serial::rfc2217::Server;
+
+    let (master, mut slave) = SerialStream::pair().expect("unable to create ptty pair");
+    let server = Server::bind("127.0.0.1:0", master).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let mut stats = server.stats();
+    tokio::spawn(server.run());
+
+    let builder = tokio_serial::new("", 9600);
+    let mut client = SerialStream::open_rfc2217(addr, &builder)
+        .await
+        .expect("unable to connect");
+
+    let message = [b'p', b'i', IAC, b'n', b'g'];
+    client.write_all(&message).await.unwrap();
+    let mut buf = [0u8; 5];
+    slave.read_exact(&mut buf).await.unwrap();
+    slave.write_all(b"pong!").await.unwrap();
+    client.read_exact(&mut buf).await.unwrap();
+    drop(client);
+
+    let stats = stats
+        .wait_for(|stats| stats.sessions == 1 && !stats.connected)
+        .await
+        .unwrap();
+    assert_eq!(stats.to_port.bytes, 5);
+    assert_eq!(stats.to_network.bytes, 5);
+    assert!(stats.to_port.frames >= 1);
+    assert!(stats.to_network.frames >= 1);
+    assert_eq!(stats.to_port.errors + stats.to_network.errors, 0);
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "time")]
+use std::io::ErrorKind;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio_serial::sbd::{checksum, Buffer, SbdModem, SessionStatus};
+
+async fn read_command(port: &mut DuplexStream) -> String {
+    let mut line = Vec::new();
+    loop {
+        let byte = port.read_u8().await.unwrap();
+        if byte == b'\r' {
+            return String::from_utf8(line).unwrap();
+        }
+        line.push(byte);
+    }
+}
+
+/// Answers like a 9603 with echo on, holding `mt` for the first session
+async fn modem(mut port: DuplexStream, mt: &'static [u8]) {
+    let mut mo = Vec::new();
+    loop {
+        let command = read_command(&mut port).await;
+        port.write_all(format!("{}\r", command).as_bytes())
+            .await
+            .unwrap();
+        if let Some(len) = command.strip_prefix("AT+SBDWB=") {
+            let len: usize = len.parse().unwrap();
+            port.write_all(b"READY\r\n").await.unwrap();
+            let mut data = vec![0; len + 2];
+            port.read_exact(&mut data).await.unwrap();
+            let sum = checksum(&data[..len]).to_be_bytes();
+            let status = if data[len..] == sum { 0 } else { 2 };
+            mo = data[..len].to_vec();
+            port.write_all(format!("\r\n{}\r\n\r\nOK\r\n", status).as_bytes())
+                .await
+                .unwrap();
+            continue;
+        }
+        let answer = match command.as_str() {
+            "AT" | "AT&K0" => "\r\nOK\r\n".to_string(),
+            "AT+CSQ" => "\r\n+CSQ:4\r\n\r\nOK\r\n".to_string(),
+            "AT+SBDIX" => {
+                tokio::time::sleep(Duration::from_secs(20)).await;
+                format!("\r\n+SBDIX: 0, 12, 1, 5, {}, 2\r\n\r\nOK\r\n", mt.len())
+            }
+            "AT+SBDRB" => {
+                let mut data = (mt.len() as u16).to_be_bytes().to_vec();
+                data.extend_from_slice(mt);
+                data.extend_from_slice(&checksum(mt).to_be_bytes());
+                data.extend_from_slice(b"\r\nOK\r\n");
+                port.write_all(&data).await.unwrap();
+                continue;
+            }
+            "AT+SBDD2" => {
+                mo.clear();
+                "\r\n0\r\n\r\nOK\r\n".to_string()
+            }
+            _ => "\r\nERROR\r\n".to_string(),
+        };
+        port.write_all(answer.as_bytes()).await.unwrap();
+    }
+}
+
+#[test]
+fn session_status_parses() {
+    let status = SessionStatus::parse("+SBDIX: 32, 7, 0, 0, 0, 0").unwrap();
+    assert!(!status.mo_sent());
+    assert!(!status.mt_received());
+    assert_eq!(status.momsn, 7);
+    assert_eq!(SessionStatus::parse("+SBDIX: 0, 1, 0"), None);
+    assert_eq!(checksum(b"hello"), 0x0214);
+}
+
+#[tokio::test(start_paused = true)]
+async fn exchange_sends_and_receives() {
+    let (ours, theirs) = tokio::io::duplex(1024);
+    tokio::spawn(modem(theirs, b"\x00\r\nAT"));
+
+    let mut sbd = SbdModem::new(ours);
+    sbd.command("AT", sbd.timeout()).await.unwrap();
+    assert_eq!(sbd.signal_quality().await.unwrap(), 4);
+
+    let (status, received) = sbd.exchange(b"\x01\x17\x2a").await.unwrap();
+    assert!(status.mo_sent());
+    assert_eq!(status.mtmsn, 5);
+    assert_eq!(status.mt_queued, 2);
+    assert_eq!(received.as_deref(), Some(&b"\x00\r\nAT"[..]));
+
+    sbd.clear(Buffer::Both).await.unwrap();
+    let err = sbd.write_mo(&[0; 341]).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let err = sbd.command("AT+SBDMTA=1", sbd.timeout()).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+}
+
+#[tokio::test(start_paused = true)]
+async fn silent_modem_times_out() {
+    let (ours, _theirs) = tokio::io::duplex(64);
+    let mut sbd = SbdModem::new(ours);
+    sbd.set_session_timeout(Duration::from_secs(10));
+    let err = sbd.session().await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+}
@@ -0,0 +1,60 @@
+#![cfg(all(unix, feature = "time"))]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::sdi12::{parse_values, Sdi12};
+use tokio_serial::SerialStream;
+
+use std::time::Duration;
+
+#[test]
+fn values_are_split_at_signs() {
+    assert_eq!(parse_values("+7.25-2+25.0").unwrap(), [7.25, -2.0, 25.0]);
+    assert!(parse_values("7.25").is_err());
+}
+
+#[tokio::test]
+async fn measurement_collects_values() {
+    let (port, mut sensor) = SerialStream::pair().expect("unable to create ptty pair");
+    tokio::spawn(async move {
+        let mut command = Vec::new();
+        let mut pages = 0;
+        loop {
+            let mut byte = [0];
+            if sensor.read(&mut byte).await.unwrap() == 0 {
+                return;
+            }
+            command.push(byte[0]);
+            if byte[0] != b'!' {
+                continue;
+            }
+            let response: &[u8] = match &command[..] {
+                b"0M!" => b"00012\r\n",
+                // The first data request is missed and retried
+                b"0D0!" if pages == 0 => {
+                    pages += 1;
+                    command.clear();
+                    continue;
+                }
+                b"0D0!" => b"0+21.5\r\n",
+                b"0D1!" => b"0-3\r\n",
+                _ => b"",
+            };
+            command.clear();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            sensor.write_all(response).await.unwrap();
+            if response == b"00012\r\n" {
+                // Service request once the measurement is done
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                sensor.write_all(b"0\r\n").await.unwrap();
+            }
+        }
+    });
+
+    let mut bus = Sdi12::new(port);
+    assert_eq!(bus.measure('0').await.unwrap(), [21.5, -3.0]);
+
+    bus.set_retries(0);
+    let err = bus.command("1I!").await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    let err = bus.command("1I").await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
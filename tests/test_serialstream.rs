@@ -1,83 +1,50 @@
-use std::time::Duration;
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    process, time,
-};
+use std::borrow::Cow;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_serial::SerialPortBuilderExt;
 
-#[cfg(unix)]
-const DEFAULT_TEST_PORT_NAMES: &str = concat!(
-    env!("CARGO_TARGET_TMPDIR"),
-    "/ttyUSB0;",
-    env!("CARGO_TARGET_TMPDIR"),
-    "/ttyUSB1"
-);
 #[cfg(not(unix))]
 const DEFAULT_TEST_PORT_NAMES: &str = "COM10;COM11";
 
 struct Fixture {
     #[cfg(unix)]
-    process: process::Child,
-    pub port_a: &'static str,
-    pub port_b: &'static str,
-}
-
-#[cfg(unix)]
-impl Drop for Fixture {
-    fn drop(&mut self) {
-        if let Some(id) = self.process.id() {
-            log::trace!("stopping socat process (id: {})...", id);
-
-            self.process.start_kill().ok();
-            std::thread::sleep(Duration::from_millis(1000));
-            log::trace!("removing link: {}", self.port_a);
-            std::fs::remove_file(self.port_a).ok();
-            log::trace!("removing link: {}", self.port_b);
-            std::fs::remove_file(self.port_b).ok();
-        }
-    }
+    _pair: tokio_serial::test::PtyPair,
+    pub port_a: Cow<'static, str>,
+    pub port_b: Cow<'static, str>,
 }
 
 impl Fixture {
     #[cfg(unix)]
-    pub async fn new(port_a: &'static str, port_b: &'static str) -> Self {
-        use std::sync::atomic::{AtomicUsize, Ordering};
-        static N: AtomicUsize = AtomicUsize::new(0);
-        let n = N.fetch_add(1, Ordering::Relaxed);
-        let port_a = format!("{}{}", port_a, n).leak();
-        let port_b = format!("{}{}", port_b, n).leak();
-        let args = [
-            format!("PTY,link={}", port_a),
-            format!("PTY,link={}", port_b),
-        ];
-        log::trace!("starting process: socat {} {}", args[0], args[1]);
-        let process = process::Command::new("socat")
-            .args(&args)
-            .spawn()
-            .expect("unable to spawn socat process");
-        log::trace!(".... done! (pid: {:?})", process.id().unwrap());
-        time::sleep(Duration::from_millis(1000)).await;
+    pub fn new() -> Self {
+        let pair = tokio_serial::test::PtyPair::new().expect("unable to create pty pair");
+        log::trace!(
+            "created pty pair: {} <-> {}",
+            pair.port_a().display(),
+            pair.port_b().display()
+        );
         Self {
-            process,
-            port_a,
-            port_b,
+            port_a: pair.port_a().to_string_lossy().into_owned().into(),
+            port_b: pair.port_b().to_string_lossy().into_owned().into(),
+            _pair: pair,
         }
     }
 
     #[cfg(not(unix))]
-    pub async fn new(port_a: &'static str, port_b: &'static str) -> Self {
-        Self { port_a, port_b }
+    pub fn new() -> Self {
+        let port_names: Vec<&str> = std::option_env!("TEST_PORT_NAMES")
+            .unwrap_or(DEFAULT_TEST_PORT_NAMES)
+            .split(';')
+            .collect();
+
+        assert_eq!(port_names.len(), 2);
+        Self {
+            port_a: port_names[0].into(),
+            port_b: port_names[1].into(),
+        }
     }
 }
 
 async fn setup_virtual_serial_ports() -> Fixture {
-    let port_names: Vec<&str> = std::option_env!("TEST_PORT_NAMES")
-        .unwrap_or(DEFAULT_TEST_PORT_NAMES)
-        .split(';')
-        .collect();
-
-    assert_eq!(port_names.len(), 2);
-    Fixture::new(port_names[0], port_names[1]).await
+    Fixture::new()
 }
 
 #[tokio::test]
@@ -86,10 +53,10 @@ async fn send_recv() {
 
     let fixture = setup_virtual_serial_ports().await;
 
-    let mut sender = tokio_serial::new(fixture.port_a, 9600)
+    let mut sender = tokio_serial::new(fixture.port_a.clone(), 9600)
         .open_native_async()
         .expect("unable to open serial port");
-    let mut receiver = tokio_serial::new(fixture.port_b, 9600)
+    let mut receiver = tokio_serial::new(fixture.port_b.clone(), 9600)
         .open_native_async()
         .expect("unable to open serial port");
 
@@ -110,3 +77,598 @@ async fn send_recv() {
     log::trace!("checking test message");
     assert_eq!(&buf[..n], message);
 }
+
+#[cfg(unix)]
+#[tokio::test]
+async fn pair_named_slave_can_be_opened_by_path() {
+    let (mut master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let mut slave = tokio_serial::new(path.to_string_lossy(), 9600)
+        .open_native_async()
+        .expect("unable to open slave by path");
+
+    master.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+}
+
+#[cfg(windows)]
+#[tokio::test]
+async fn com0com_pair_is_connected() {
+    let (mut a, mut b) = match tokio_serial::SerialStream::pair() {
+        Ok(pair) => pair,
+        // com0com isn't installed
+        Err(e) if e.kind() == tokio_serial::ErrorKind::NoDevice => return,
+        Err(e) => panic!("unable to open com0com pair: {}", e),
+    };
+
+    a.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    b.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "current_thread")]
+async fn streaming_reader_yields_to_other_tasks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio_serial::SerialPort;
+
+    const LEN: usize = 1024;
+
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    master.write_all(&[0x55; LEN]).await.unwrap();
+    while (slave.bytes_to_read().unwrap() as usize) < LEN {
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let ticker = tokio::spawn({
+        let ticks = ticks.clone();
+        async move {
+            loop {
+                ticks.fetch_add(1, Ordering::Relaxed);
+                tokio::task::yield_now().await;
+            }
+        }
+    });
+
+    // Every byte is already buffered, so only the cooperative budget can make these reads
+    // return to the runtime
+    let mut byte = [0u8; 1];
+    for _ in 0..LEN {
+        slave.read_exact(&mut byte).await.unwrap();
+    }
+    let ticked = ticks.load(Ordering::Relaxed);
+    ticker.abort();
+
+    assert!(ticked > 0, "reader starved the other task");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn data_arriving_after_a_short_read_wakes_the_reader() {
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut buf = [0u8; 64];
+
+    for chunk in [&b"first"[..], b"second", b"third"] {
+        master.write_all(chunk).await.unwrap();
+        let n = tokio::time::timeout(std::time::Duration::from_secs(5), slave.read(&mut buf))
+            .await
+            .expect("reader was not woken")
+            .unwrap();
+        assert_eq!(&buf[..n], chunk);
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn burst_arriving_during_short_reads_is_read_in_order() {
+    const LEN: usize = 64 * 1024;
+
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let sent: Vec<u8> = (0..LEN).map(|i| (i % 251) as u8).collect();
+    let writer = tokio::spawn({
+        let sent = sent.clone();
+        async move { master.write_all(&sent).await.map(|_| master) }
+    });
+
+    // The pty hands the data over in pieces smaller than the buffer, with more queued behind
+    let mut received = Vec::with_capacity(LEN);
+    let mut buf = [0u8; 8192];
+    while received.len() < LEN {
+        let n = tokio::time::timeout(std::time::Duration::from_secs(5), slave.read(&mut buf))
+            .await
+            .expect("reader was not woken")
+            .unwrap();
+        received.extend_from_slice(&buf[..n]);
+    }
+    let _master = writer.await.unwrap().unwrap();
+    assert!(received == sent);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn read_buffer_coalesces_small_reads() {
+    use tokio::io::AsyncBufReadExt;
+    use tokio_serial::{ClearBuffer, SerialPort};
+
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    slave.set_read_buffer_capacity(64);
+    assert_eq!(slave.read_buffer_capacity(), 64);
+
+    master.write_all(b"hello world").await.unwrap();
+    while slave.bytes_to_read().unwrap() < 11 {
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+
+    // The first small read pulls everything into the buffer
+    let mut byte = [0u8; 1];
+    slave.read_exact(&mut byte).await.unwrap();
+    assert_eq!(&byte, b"h");
+    assert_eq!(slave.bytes_to_read().unwrap(), 10);
+
+    assert_eq!(slave.fill_buf().await.unwrap(), b"ello world");
+    slave.consume(5);
+    let mut rest = [0u8; 2];
+    slave.read_exact(&mut rest).await.unwrap();
+    assert_eq!(&rest, b"wo");
+
+    slave.clear(ClearBuffer::Input).unwrap();
+    assert_eq!(slave.bytes_to_read().unwrap(), 0);
+    master.write_all(b"!").await.unwrap();
+    slave.read_exact(&mut byte).await.unwrap();
+    assert_eq!(&byte, b"!");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn framed_reads_into_uninitialized_frame_buffer() {
+    use futures::StreamExt;
+    use tokio_util::codec::{FramedRead, LinesCodec};
+
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    // A large initial capacity leaves plenty of uninitialized spare capacity to read into
+    let mut lines = FramedRead::with_capacity(slave, LinesCodec::new(), 64 * 1024);
+
+    master.write_all(b"first\nsecond\n").await.unwrap();
+    assert_eq!(lines.next().await.unwrap().unwrap(), "first");
+    assert_eq!(lines.next().await.unwrap().unwrap(), "second");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn closed_pty_master_reads_as_eof() {
+    let (master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    drop(master);
+
+    let mut buf = [0u8; 8];
+    assert_eq!(slave.read(&mut buf).await.unwrap(), 0);
+    assert_eq!(slave.read(&mut buf).await.unwrap(), 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn closed_pty_slave_reads_as_eof() {
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    drop(slave);
+
+    // Linux reports this hangup as EIO
+    let mut buf = [0u8; 8];
+    assert_eq!(master.read(&mut buf).await.unwrap(), 0);
+    assert_eq!(master.read(&mut buf).await.unwrap(), 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn vanished_device_reads_as_eof() {
+    // Dropping the pair closes the pseudo terminals behind a port opened by path, the same
+    // thing an unplugged adapter looks like to the reader
+    let fixture = Fixture::new();
+    let mut port = tokio_serial::new(fixture.port_a.clone(), 9600)
+        .open_native_async()
+        .expect("unable to open serial port");
+    drop(fixture);
+
+    let mut buf = [0u8; 8];
+    let n = tokio::time::timeout(std::time::Duration::from_secs(5), port.read(&mut buf))
+        .await
+        .expect("hangup was not reported")
+        .unwrap();
+    assert_eq!(n, 0);
+}
+
+#[cfg(all(unix, feature = "codec"))]
+#[tokio::test]
+async fn serial_framed_decodes_remainder_at_eof() {
+    use futures::StreamExt;
+    use tokio_serial::frame::SerialFramed;
+    use tokio_util::codec::LinesCodec;
+
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut lines = SerialFramed::new(slave, LinesCodec::new());
+
+    master.write_all(b"first\nsec").await.unwrap();
+    assert_eq!(lines.next().await.unwrap().unwrap(), "first");
+    master.write_all(b"ond\nlast").await.unwrap();
+    assert_eq!(lines.next().await.unwrap().unwrap(), "second");
+
+    // Give the last bytes time to reach the slave before hanging up
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    drop(master);
+    assert_eq!(lines.next().await.unwrap().unwrap(), "last");
+    assert!(lines.next().await.is_none());
+}
+
+#[cfg(all(unix, feature = "codec"))]
+#[tokio::test]
+async fn serial_framed_paces_frames() {
+    use futures::SinkExt;
+    use std::time::{Duration, Instant};
+    use tokio_serial::frame::{Pacing, SerialFramed};
+    use tokio_util::codec::BytesCodec;
+
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut framed = SerialFramed::new(slave, BytesCodec::new());
+    framed.set_pacing(Some(Pacing::new(Duration::from_millis(40))));
+
+    let start = Instant::now();
+    for frame in [&b"one"[..], b"two", b"six"] {
+        framed.send(bytes::Bytes::from_static(frame)).await.unwrap();
+    }
+    // Every frame after the first waits out the gap
+    assert!(start.elapsed() >= Duration::from_millis(80));
+
+    let mut buf = [0u8; 9];
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"onetwosix");
+}
+
+#[cfg(all(unix, feature = "codec"))]
+#[tokio::test]
+async fn serial_framed_finishes_partly_written_frame() {
+    use futures::SinkExt;
+    use tokio_serial::frame::SerialFramed;
+    use tokio_util::codec::BytesCodec;
+
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut framed = SerialFramed::new(slave, BytesCodec::new());
+
+    // Far more than the pty takes in one write, the rest goes out as the master reads
+    let frame: Vec<u8> = (0..256 * 1024).map(|i| i as u8).collect();
+    let len = frame.len();
+    let reader = tokio::spawn(async move {
+        let mut received = vec![0u8; len];
+        master.read_exact(&mut received).await.unwrap();
+        received
+    });
+    framed
+        .send(bytes::Bytes::from(frame.clone()))
+        .await
+        .unwrap();
+    assert!(framed.write_buffer().is_empty());
+    assert_eq!(reader.await.unwrap(), frame);
+}
+
+#[cfg(all(unix, feature = "codec"))]
+#[tokio::test]
+async fn serial_framed_bounds_bytes_in_flight() {
+    use futures::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_serial::frame::SerialFramed;
+    use tokio_util::codec::BytesCodec;
+
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut framed = SerialFramed::new(slave, BytesCodec::new());
+    framed.set_max_in_flight(Some(256));
+
+    // Nobody reads the master, the sink stalls once the pty is full
+    let frame = bytes::Bytes::from(vec![b'x'; 100]);
+    let mut frames = futures::stream::repeat(frame).take(10_000).map(Ok);
+    let sent = tokio::time::timeout(Duration::from_millis(200), framed.send_all(&mut frames));
+    assert!(sent.await.is_err());
+    assert!(framed.write_buffer().len() < 256 + 100);
+
+    // Everything accepted arrives once the master reads
+    let reader = tokio::spawn(async move {
+        let mut received = 0usize;
+        let mut buf = [0u8; 4096];
+        while let Ok(Ok(n)) =
+            tokio::time::timeout(Duration::from_millis(200), master.read(&mut buf)).await
+        {
+            assert!(buf[..n].iter().all(|&b| b == b'x'));
+            received += n;
+        }
+        received
+    });
+    SinkExt::<bytes::Bytes>::flush(&mut framed).await.unwrap();
+    assert_eq!(reader.await.unwrap() % 100, 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn read_low_watermark_holds_reads_back() {
+    use std::time::Duration;
+
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    slave.set_read_low_watermark(8).unwrap();
+
+    master.write_all(b"abc").await.unwrap();
+    let mut buf = [0u8; 16];
+    let early = tokio::time::timeout(Duration::from_millis(100), slave.read(&mut buf)).await;
+    assert!(early.is_err(), "read completed below the watermark");
+
+    master.write_all(b"defgh").await.unwrap();
+    let n = tokio::time::timeout(Duration::from_secs(5), slave.read(&mut buf))
+        .await
+        .expect("read didn't complete at the watermark")
+        .unwrap();
+    assert_eq!(&buf[..n], b"abcdefgh");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn peek_leaves_bytes_for_the_next_read() {
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+
+    master.write_all(b"$G").await.unwrap();
+    let mut start = [0u8; 1];
+    assert_eq!(slave.peek(&mut start).await.unwrap(), 1);
+    assert_eq!(&start, b"$");
+
+    // The bytes peeked already are returned right away, without waiting for a full buffer
+    let mut more = [0u8; 6];
+    let n = tokio::time::timeout(std::time::Duration::from_secs(5), slave.peek(&mut more))
+        .await
+        .expect("peek waited for a full buffer")
+        .unwrap();
+    assert_eq!(&more[..n], &b"$G"[..n]);
+
+    master.write_all(b"PGGA").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(slave.peek(&mut more).await.unwrap(), 6);
+    assert_eq!(&more, b"$GPGGA");
+
+    let mut buf = [0u8; 6];
+    slave.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"$GPGGA");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn read_timestamps_show_the_gaps() {
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut buf = [0u8; 16];
+
+    master.write_all(b"a").await.unwrap();
+    let (n, first) = slave.read_timestamped(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"a");
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    master.write_all(b"b").await.unwrap();
+    let (n, second) = slave.read_timestamped(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"b");
+    assert!(second - first >= std::time::Duration::from_millis(50));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn drop_output_chosen_on_the_builder_drains_on_drop() {
+    use tokio_serial::PendingOutput;
+
+    let (mut master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let mut port = tokio_serial::new(path.to_str().unwrap(), 9600)
+        .drop_output(PendingOutput::Drain)
+        .open_native_async()
+        .unwrap();
+    port.write_all(b"goodbye").await.unwrap();
+    drop(port);
+
+    let mut buf = [0u8; 7];
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"goodbye");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn close_drains_output_and_hangs_up() {
+    use tokio_serial::PendingOutput;
+
+    let (mut master, mut slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    slave.write_all(b"goodbye").await.unwrap();
+    slave.close(PendingOutput::Drain).await.unwrap();
+
+    let mut received = Vec::new();
+    master.read_to_end(&mut received).await.unwrap();
+    assert_eq!(received, b"goodbye");
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn close_restores_original_settings() {
+    use tokio_serial::{PendingOutput, SerialPort};
+
+    // Linux applies termios requests on a pty master to its slave, so the master reads back
+    // the settings of the device opened by path
+    let (master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let path = path.to_string_lossy().into_owned();
+    let original = master.baud_rate().unwrap();
+    assert_ne!(original, 115_200);
+
+    let port = tokio_serial::new(path.clone(), 115_200)
+        .restore_settings_on_close(true)
+        .open_native_async()
+        .expect("unable to open serial port");
+    assert_eq!(master.baud_rate().unwrap(), 115_200);
+    port.close(PendingOutput::Keep).await.unwrap();
+    assert_eq!(master.baud_rate().unwrap(), original);
+
+    // Without opting in, the new settings stay
+    let port = tokio_serial::new(path, 115_200)
+        .open_native_async()
+        .expect("unable to open serial port");
+    drop(port);
+    assert_eq!(master.baud_rate().unwrap(), 115_200);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn from_owned_fd_configures_inherited_descriptor() {
+    use std::fs::OpenOptions;
+    use std::os::unix::io::OwnedFd;
+    use tokio_serial::SerialPort;
+
+    let (mut master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .expect("unable to open pty slave");
+
+    let mut port = tokio_serial::SerialStream::from_owned_fd(
+        OwnedFd::from(file),
+        &tokio_serial::new("", 57600),
+    )
+    .expect("unable to wrap descriptor");
+    assert_eq!(port.baud_rate().unwrap(), 57600);
+
+    port.write_all(b"inherited").await.unwrap();
+    let mut buf = [0u8; 9];
+    master.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"inherited");
+}
+
+#[cfg(all(unix, feature = "codec"))]
+#[tokio::test]
+async fn serial_framed_keeps_partial_frames_across_map_port() {
+    use futures::StreamExt;
+    use tokio_serial::frame::SerialFramed;
+    use tokio_serial::SerialPort;
+    use tokio_util::codec::LinesCodec;
+
+    let (mut master, path) = tokio_serial::SerialStream::pair_named().expect("unable to open pty");
+    let path = path.to_str().unwrap().to_owned();
+    let port = tokio_serial::new(&path, 9600).open_native_async().unwrap();
+    let mut lines = SerialFramed::new(port, LinesCodec::new());
+
+    master.write_all(b"first\nsec").await.unwrap();
+    assert_eq!(lines.next().await.unwrap().unwrap(), "first");
+    lines.get_mut().set_baud_rate(19200).unwrap();
+
+    let mut lines = lines
+        .try_map_port(|port| {
+            drop(port);
+            tokio_serial::new(&path, 19200).open_native_async()
+        })
+        .unwrap();
+    assert_eq!(lines.get_ref().baud_rate().unwrap(), 19200);
+    master.write_all(b"ond\n").await.unwrap();
+    assert_eq!(lines.next().await.unwrap().unwrap(), "second");
+}
+
+#[cfg(all(unix, feature = "codec"))]
+#[tokio::test]
+async fn timed_framed_ends_frames_at_silences() {
+    use bytes::BytesMut;
+    use futures::StreamExt;
+    use std::time::Duration;
+    use tokio_serial::codec::{TimedDecoder, Timing};
+    use tokio_serial::frame::TimedFramed;
+    use tokio_util::codec::Decoder;
+
+    /// Frames are whatever arrived between two silences of 20 ms
+    struct Bursts;
+
+    const SILENCE: Duration = Duration::from_millis(20);
+
+    impl Decoder for Bursts {
+        type Item = Vec<u8>;
+        type Error = std::io::Error;
+
+        fn decode(&mut self, _src: &mut BytesMut) -> std::io::Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+    }
+
+    impl TimedDecoder for Bursts {
+        fn decode_timed(
+            &mut self,
+            src: &mut BytesMut,
+            timing: &Timing,
+        ) -> std::io::Result<Option<Vec<u8>>> {
+            assert!(timing.char_time > Duration::from_secs(0));
+            let end = match timing.gap {
+                Some(gap) if gap >= SILENCE => src.len() - timing.new_bytes,
+                _ => return Ok(None),
+            };
+            if end == 0 {
+                return Ok(None);
+            }
+            Ok(Some(src.split_to(end).to_vec()))
+        }
+
+        fn idle_timeout(&self, _char_time: Duration) -> Option<Duration> {
+            Some(SILENCE)
+        }
+    }
+
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut frames = TimedFramed::new(slave, Bursts);
+    tokio::spawn(async move {
+        master.write_all(b"abc").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        master.write_all(b"de").await.unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    });
+    assert_eq!(frames.next().await.unwrap().unwrap(), b"abc");
+    assert_eq!(frames.next().await.unwrap().unwrap(), b"de");
+}
+
+#[cfg(all(unix, feature = "codec"))]
+#[tokio::test]
+async fn send_vectored_writes_frames_in_order() {
+    use bytes::Bytes;
+    use futures::SinkExt;
+    use tokio_serial::frame::{EncoderVectored, SerialFramed};
+    use tokio_util::codec::LinesCodec;
+
+    struct Framing(LinesCodec);
+
+    impl tokio_util::codec::Encoder<String> for Framing {
+        type Error = tokio_util::codec::LinesCodecError;
+
+        fn encode(&mut self, line: String, dst: &mut bytes::BytesMut) -> Result<(), Self::Error> {
+            self.0.encode(line, dst)
+        }
+    }
+
+    impl EncoderVectored<Bytes> for Framing {
+        type Error = tokio_util::codec::LinesCodecError;
+
+        fn encode_vectored(
+            &mut self,
+            payload: Bytes,
+            dst: &mut Vec<Bytes>,
+        ) -> Result<(), Self::Error> {
+            dst.push(Bytes::from_static(b"<"));
+            dst.push(payload);
+            dst.push(Bytes::new());
+            dst.push(Bytes::from_static(b">"));
+            Ok(())
+        }
+    }
+
+    let (mut master, slave) = tokio_serial::SerialStream::pair().expect("unable to open pty");
+    let mut framed = SerialFramed::new(slave, Framing(LinesCodec::new()));
+    framed.feed("buffered".to_owned()).await.unwrap();
+    let payload = Bytes::from(vec![b'x'; 4096]);
+    framed.send_vectored(payload.clone()).await.unwrap();
+
+    let mut expected = b"buffered\n<".to_vec();
+    expected.extend_from_slice(&payload);
+    expected.push(b'>');
+    let mut received = vec![0; expected.len()];
+    master.read_exact(&mut received).await.unwrap();
+    assert_eq!(received, expected);
+}
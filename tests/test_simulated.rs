@@ -0,0 +1,88 @@
+#![cfg(feature = "test-util")]
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::Instant;
+use tokio_serial::test::{advance_chars, Overflow, SimulatedSerial};
+use tokio_serial::SerialPort;
+
+#[tokio::test(start_paused = true)]
+async fn simulated_link_runs_at_baud_rate() {
+    // 9600 8N1 carries 960 characters per second
+    let (mut a, mut b) = SimulatedSerial::pair(&tokio_serial::new("sim", 9600));
+
+    let start = Instant::now();
+    a.write_all(&[0x55; 96]).await.unwrap();
+    let mut buf = [0u8; 96];
+    b.read_exact(&mut buf).await.unwrap();
+    let elapsed = start.elapsed();
+
+    assert_eq!(buf, [0x55; 96]);
+    assert!(elapsed >= Duration::from_millis(100), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(102), "{:?}", elapsed);
+}
+
+#[tokio::test(start_paused = true)]
+async fn simulated_link_drops_on_overflow() {
+    let (mut a, mut b) = SimulatedSerial::builder(&tokio_serial::new("sim", 115_200))
+        .driver_buffer(16)
+        .overflow(Overflow::Drop)
+        .pair();
+
+    // Half of the write does not fit in the transmit buffer
+    a.write_all(&[1; 32]).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(b.bytes_to_read().unwrap(), 16);
+
+    // The receive buffer is full, everything sent now is overrun
+    a.write_all(&[2; 16]).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(b.overruns(), 16);
+
+    let mut buf = [0u8; 16];
+    b.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, [1; 16]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn simulated_link_reports_overflow_errors_and_garbles_mismatched_settings() {
+    let (mut a, mut b) = SimulatedSerial::builder(&tokio_serial::new("sim", 9600))
+        .driver_buffer(4)
+        .overflow(Overflow::Error)
+        .pair();
+
+    assert_eq!(a.write(b"abcdef").await.unwrap(), 4);
+    assert!(a.write(b"ef").await.is_err());
+
+    let mut buf = [0u8; 4];
+    b.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"abcd");
+
+    b.set_baud_rate(19200).unwrap();
+    a.write_all(b"wxyz").await.unwrap();
+    b.read_exact(&mut buf).await.unwrap();
+    assert_ne!(&buf, b"wxyz");
+}
+
+#[tokio::test(start_paused = true)]
+async fn line_time_can_be_advanced() {
+    let (mut a, b) = SimulatedSerial::pair(&tokio_serial::new("sim", 1200));
+
+    a.write_all(&[0; 20]).await.unwrap();
+    let elapsed = advance_chars(&a, 10).await.unwrap();
+    // 10 bits per character at 1200 baud
+    assert_eq!(elapsed.as_micros(), 83_333);
+    assert_eq!(b.bytes_to_read().unwrap(), 10);
+
+    a.advance_until_idle().await;
+    assert_eq!(b.bytes_to_read().unwrap(), 20);
+    assert_eq!(a.bytes_to_write().unwrap(), 0);
+
+    // A read timeout measured in line time fires deterministically
+    let mut b = b;
+    let mut buf = [0u8; 32];
+    let n = b.read(&mut buf).await.unwrap();
+    assert_eq!(n, 20);
+    let idle = tokio::time::timeout(Duration::from_millis(50), b.read(&mut buf)).await;
+    assert!(idle.is_err());
+}
@@ -0,0 +1,136 @@
+#![cfg(all(unix, feature = "codec"))]
+use bytes::BytesMut;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_serial::codec::slcan::{Command, Response, SlcanCodec};
+use tokio_serial::codec::ChecksumPolicy;
+use tokio_serial::slcan::{Bitrate, CanFrame, CanId, Slcan};
+use tokio_serial::SerialStream;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn commands_encode() {
+    let mut codec = SlcanCodec::new();
+    let mut dst = BytesMut::new();
+    for command in [
+        Command::Close,
+        Command::Bitrate(Bitrate::Kbps500),
+        Command::Timestamps(true),
+        Command::Open,
+        Command::Transmit(CanFrame::new(CanId::Extended(0x18daf110), &[]).unwrap()),
+        Command::Transmit(CanFrame::remote(CanId::Standard(0x7ff), 8).unwrap()),
+    ] {
+        codec.encode(command, &mut dst).unwrap();
+    }
+    assert_eq!(&dst[..], b"C\rS6\rZ1\rO\rT18DAF1100\rr7FF8\r");
+
+    assert!(CanFrame::new(CanId::Standard(0x800), &[]).is_err());
+    assert!(CanFrame::new(CanId::Extended(0), &[0; 9]).is_err());
+    assert_eq!(Bitrate::from_bps(125_000), Some(Bitrate::Kbps125));
+}
+
+#[test]
+fn responses_decode() {
+    let mut codec = SlcanCodec::new();
+    let mut src = BytesMut::from(&b"\r\x07zV1013\rt12320102EA60\rR000000014\rt1"[..]);
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(Response::Ok));
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(Response::Error));
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Response::Reply("zV1013".into()))
+    );
+    match codec.decode(&mut src).unwrap() {
+        Some(Response::Frame(received)) => {
+            assert_eq!(received.frame.id(), CanId::Standard(0x123));
+            assert_eq!(received.frame.data(), [0x01, 0x02]);
+            assert_eq!(received.timestamp, Some(60000));
+        }
+        other => panic!("{:?}", other),
+    }
+    match codec.decode(&mut src).unwrap() {
+        Some(Response::Frame(received)) => {
+            assert!(received.frame.is_remote());
+            assert_eq!(received.frame.id(), CanId::Extended(1));
+            assert_eq!(received.frame.dlc(), 4);
+            assert_eq!(received.timestamp, None);
+        }
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+    src.extend_from_slice(b"23901\rt1231AB\r");
+    let err = codec.decode(&mut src).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    codec.set_checksum_policy(ChecksumPolicy::Drop);
+    src.extend_from_slice(b"z\r");
+    assert!(matches!(
+        codec.decode(&mut src),
+        Ok(Some(Response::Frame(_)))
+    ));
+    assert_eq!(codec.decode(&mut src).unwrap(), Some(Response::Sent));
+}
+
+/// Answers like a LAWICEL adapter, sending a frame on the bus for every frame received
+async fn adapter(mut port: SerialStream) {
+    let mut open = false;
+    let mut line = Vec::new();
+    let mut buf = [0; 64];
+    loop {
+        let n = match port.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        for &byte in &buf[..n] {
+            if byte != b'\r' {
+                line.push(byte);
+                continue;
+            }
+            let answer: &[u8] = match (line.first(), open) {
+                (Some(b'C'), true) => {
+                    open = false;
+                    b"\r"
+                }
+                (Some(b'O'), false) => {
+                    open = true;
+                    // A frame arriving before the answer
+                    b"t0011FF\r\r"
+                }
+                (Some(b'S'), false) => b"\r",
+                (Some(b'V'), _) => b"V1013\r",
+                (Some(b't'), true) => b"z\rt7E83410C00\r",
+                _ => b"\x07",
+            };
+            port.write_all(answer).await.unwrap();
+            line.clear();
+        }
+    }
+}
+
+#[tokio::test]
+async fn interface_drives_adapter() {
+    let (port, device) = SerialStream::pair().expect("unable to create ptty pair");
+    tokio::spawn(adapter(device));
+
+    let mut can = Slcan::new(port);
+    assert_eq!(can.version().await.unwrap(), "1013");
+    can.open(Bitrate::Kbps500).await.unwrap();
+    can.send(&CanFrame::new(CanId::Standard(0x7df), &[0x02, 0x01, 0x0c]).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(can.recv().await.unwrap().frame.id(), CanId::Standard(0x001));
+    let reply = can.recv().await.unwrap().frame;
+    assert_eq!(reply.id(), CanId::Standard(0x7e8));
+    assert_eq!(reply.data(), [0x41, 0x0c, 0x00]);
+
+    assert_eq!(
+        can.set_timestamps(true).await.unwrap_err().kind(),
+        std::io::ErrorKind::Other
+    );
+    can.close().await.unwrap();
+    can.send(&CanFrame::new(CanId::Standard(1), &[]).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        can.recv().await.unwrap_err().kind(),
+        std::io::ErrorKind::Other
+    );
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "tcp")]
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_serial::{AsyncSerialPort, DataBits, Parity, SerialPort, TcpSerial};
+
+#[tokio::test]
+async fn tcp_serial_passes_data_and_records_settings() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let builder = tokio_serial::new("", 19200)
+        .data_bits(DataBits::Seven)
+        .parity(Parity::Even);
+    let (port, accepted) = tokio::join!(TcpSerial::connect(addr, &builder), listener.accept());
+    let mut port = port.expect("unable to connect");
+    let (mut remote, _) = accepted.unwrap();
+
+    assert_eq!(port.baud_rate().unwrap(), 19200);
+    assert_eq!(port.data_bits().unwrap(), DataBits::Seven);
+    assert_eq!(port.parity().unwrap(), Parity::Even);
+
+    port.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    remote.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+}
+
+#[tokio::test]
+async fn tcp_serial_is_an_async_serial_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let builder = tokio_serial::new("", 9600);
+    let (port, accepted) = tokio::join!(TcpSerial::connect(addr, &builder), listener.accept());
+    let mut port: Box<dyn AsyncSerialPort> = Box::new(port.unwrap());
+    let (mut remote, _) = accepted.unwrap();
+
+    port.set_baud_rate(115_200).unwrap();
+    assert_eq!(port.baud_rate().unwrap(), 115_200);
+
+    remote.write_all(b"pong").await.unwrap();
+    let mut buf = [0u8; 4];
+    port.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"pong");
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "throttle")]
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::Instant;
+use tokio_serial::throttle::Throttle;
+
+#[tokio::test(start_paused = true)]
+async fn bytes_per_sec_spreads_writes() {
+    let (ours, mut theirs) = tokio::io::duplex(1024);
+    let mut port = Throttle::new(ours).bytes_per_sec(100, 10);
+
+    let start = Instant::now();
+    port.write_all(&[0u8; 60]).await.unwrap();
+    // The first 10 bytes go out at once, the other 50 at 100 bytes per second
+    assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+    let mut buf = [0u8; 60];
+    theirs.read_exact(&mut buf).await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn frames_per_sec_spaces_sent_items() {
+    let (ours, theirs) = futures::channel::mpsc::unbounded::<u8>();
+    let mut sink = Throttle::new(ours).frames_per_sec(10, 1);
+
+    let start = Instant::now();
+    let mut sent = Vec::new();
+    for frame in 0..4u8 {
+        sink.send(frame).await.unwrap();
+        sent.push(start.elapsed());
+    }
+    assert_eq!(sent, [0, 100, 200, 300].map(Duration::from_millis).to_vec());
+    drop(sink);
+    assert_eq!(theirs.collect::<Vec<_>>().await, [0, 1, 2, 3]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn quiet_link_allows_a_burst() {
+    let (ours, _theirs) = tokio::io::duplex(1024);
+    let mut port = Throttle::new(ours).frames_per_sec(1, 3);
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        port.write_all(b"x").await.unwrap();
+    }
+    assert_eq!(start.elapsed(), Duration::from_secs(0));
+    port.write_all(b"x").await.unwrap();
+    assert_eq!(start.elapsed(), Duration::from_secs(1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn chunked_splits_and_spaces_writes() {
+    let (ours, mut theirs) = tokio::io::duplex(1024);
+    let mut port = Throttle::new(ours).chunked(64, Duration::from_millis(10));
+
+    let start = Instant::now();
+    assert_eq!(port.write(&[0u8; 100]).await.unwrap(), 64);
+    port.write_all(&[0u8; 136]).await.unwrap();
+    // Three chunks follow the first, 10ms apart
+    assert_eq!(start.elapsed(), Duration::from_millis(30));
+
+    let mut buf = [0u8; 200];
+    theirs.read_exact(&mut buf).await.unwrap();
+}
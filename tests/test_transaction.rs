@@ -0,0 +1,128 @@
+#![cfg(feature = "codec")]
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::time::Instant;
+use tokio_serial::transaction::{Pipeline, Transactor};
+use tokio_util::codec::{Framed, LinesCodec, LinesCodecError};
+
+#[tokio::test(start_paused = true)]
+async fn requests_wait_for_the_quiet_time() {
+    let (ours, theirs) = tokio::io::duplex(1024);
+    let device = tokio::spawn(async move {
+        let mut device = Framed::new(theirs, LinesCodec::new());
+        let mut arrivals = Vec::new();
+        while let Some(Ok(request)) = device.next().await {
+            arrivals.push(Instant::now());
+            device.send(format!("re: {}", request)).await.unwrap();
+        }
+        arrivals
+    });
+
+    let mut plc = Transactor::new(Framed::new(ours, LinesCodec::new()));
+    plc.set_quiet_time(Duration::from_millis(20));
+    let start = Instant::now();
+    assert_eq!(plc.request("first").await.unwrap(), "re: first");
+    assert_eq!(plc.request("second").await.unwrap(), "re: second");
+    drop(plc);
+
+    let arrivals = device.await.unwrap();
+    assert_eq!(arrivals[0] - start, Duration::from_secs(0));
+    assert_eq!(arrivals[1] - start, Duration::from_millis(20));
+}
+
+#[tokio::test(start_paused = true)]
+async fn silent_device_times_out() {
+    let (ours, _theirs) = tokio::io::duplex(1024);
+    let mut plc = Transactor::new(Framed::new(ours, LinesCodec::new()));
+    plc.set_timeout(Some(Duration::from_millis(100)));
+
+    match plc.request("anyone?").await {
+        Err(LinesCodecError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::TimedOut),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn late_response_is_not_taken_for_the_next_one() {
+    let (ours, theirs) = tokio::io::duplex(1024);
+    // Answers `slow` only after the transactor gave up on it
+    tokio::spawn(async move {
+        let mut device = Framed::new(theirs, LinesCodec::new());
+        while let Some(Ok(request)) = device.next().await {
+            if request == "slow" {
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+            device.send(format!("re: {}", request)).await.unwrap();
+        }
+    });
+
+    let mut plc = Transactor::new(Framed::new(ours, LinesCodec::new()));
+    plc.set_quiet_time(Duration::from_millis(100));
+    plc.set_timeout(Some(Duration::from_millis(100)));
+    match plc.request("slow").await {
+        Err(LinesCodecError::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::TimedOut),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(plc.request("fast").await.unwrap(), "re: fast");
+    assert_eq!(plc.request("again").await.unwrap(), "re: again");
+}
+
+#[tokio::test(start_paused = true)]
+async fn pipelined_responses_reach_their_requests() {
+    let (ours, theirs) = tokio::io::duplex(1024);
+    // Answers requests in pairs, the second first, and never `3`
+    tokio::spawn(async move {
+        let mut device = Framed::new(theirs, LinesCodec::new());
+        let mut held = None;
+        while let Some(Ok(request)) = device.next().await {
+            if request.starts_with("3 ") {
+                continue;
+            }
+            match held.take() {
+                None => held = Some(request),
+                Some(first) => {
+                    device.send("9 unsolicited").await.unwrap();
+                    device.send(format!("re {}", request)).await.unwrap();
+                    device.send(format!("re {}", first)).await.unwrap();
+                }
+            }
+        }
+    });
+
+    let key = |frame: &String| {
+        frame
+            .trim_start_matches("re ")
+            .split(' ')
+            .next()
+            .map(str::to_owned)
+    };
+    let (mut pipeline, handle) = Pipeline::new(
+        Framed::new(ours, LinesCodec::new()),
+        move |request: &String| key(request).unwrap(),
+        key,
+    );
+    pipeline.set_timeout(Some(Duration::from_millis(100)));
+    let pipeline = tokio::spawn(pipeline.run());
+
+    let (one, two, three) = futures::join!(
+        handle.request("1 a".to_string()),
+        handle.request("2 b".to_string()),
+        handle.request("3 c".to_string()),
+    );
+    assert_eq!(one.unwrap(), "re 1 a");
+    assert_eq!(two.unwrap(), "re 2 b");
+    assert_eq!(three.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+
+    let (first, duplicate) = futures::join!(
+        handle.request("4 d".to_string()),
+        handle.request("4 e".to_string()),
+    );
+    assert_eq!(
+        duplicate.unwrap_err().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+    assert_eq!(first.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+
+    drop(handle);
+    assert!(pipeline.await.unwrap().is_ok());
+}
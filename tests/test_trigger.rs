@@ -0,0 +1,72 @@
+#![cfg(feature = "trigger")]
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_serial::trigger::{Pattern, Trigger};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Alert {
+    Booted,
+    Failure,
+}
+
+#[tokio::test(start_paused = true)]
+async fn matches_fire_once_per_debounce() {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let seen = warnings.clone();
+    let mut trigger = Trigger::new();
+    trigger
+        .emit(
+            Pattern::bytes("Using Zephyr OS"),
+            Duration::from_secs(0),
+            Alert::Booted,
+        )
+        .emit(
+            Pattern::regex(r"(?i)error|panic").unwrap(),
+            Duration::from_secs(5),
+            Alert::Failure,
+        )
+        .call(
+            Pattern::bytes("Warning"),
+            Duration::from_secs(0),
+            move |m| {
+                let seen = seen.clone();
+                async move { seen.lock().unwrap().push(m.matched().to_vec()) }
+            },
+        );
+    assert_eq!(trigger.len(), 3);
+
+    assert_eq!(
+        trigger.feed("*** Using Zephyr OS ***").await,
+        [Alert::Booted]
+    );
+    assert_eq!(trigger.feed("ERROR: sensor").await, [Alert::Failure]);
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    // Within the debounce time of the first error
+    assert!(trigger.feed("kernel panic").await.is_empty());
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(trigger.feed(b"Error again").await, [Alert::Failure]);
+    assert!(trigger.feed("all good").await.is_empty());
+
+    assert!(trigger.feed("Warning: low battery").await.is_empty());
+    assert_eq!(*warnings.lock().unwrap(), [b"Warning".to_vec()]);
+}
+
+#[tokio::test]
+async fn run_sends_events_of_a_stream() {
+    let mut trigger = Trigger::new();
+    trigger.emit(
+        Pattern::bytes("boot"),
+        Duration::from_secs(0),
+        Alert::Booted,
+    );
+    let lines = futures::stream::iter(vec![
+        Ok::<_, std::io::Error>("boot 1".to_string()),
+        Ok("noise".to_string()),
+        Ok("boot 2".to_string()),
+    ]);
+    let (tx, rx) = mpsc::unbounded();
+    trigger.run(lines, tx).await.unwrap();
+    assert_eq!(rx.collect::<Vec<_>>().await, [Alert::Booted, Alert::Booted]);
+}
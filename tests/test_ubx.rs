@@ -0,0 +1,73 @@
+#![cfg(all(unix, feature = "codec"))]
+use futures::{SinkExt, StreamExt};
+use tokio_serial::codec::ubx::{Ack, Configurator, NmeaMessage, UbxCodec, UbxFrame};
+use tokio_serial::frame::SerialFramed;
+use tokio_serial::SerialStream;
+
+use std::time::Duration;
+
+fn ack(frame: &UbxFrame, accept: bool) -> UbxFrame {
+    UbxFrame::new(0x05, u8::from(accept), vec![frame.class, frame.id])
+}
+
+#[tokio::test]
+async fn configuration_is_acknowledged() {
+    let (host, receiver) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut receiver = SerialFramed::new(receiver, UbxCodec::new());
+    let device = tokio::spawn(async move {
+        let mut received = Vec::new();
+        while let Some(Ok(frame)) = receiver.next().await {
+            // Navigation output ahead of the answer is skipped by the configurator
+            receiver
+                .send(UbxFrame::new(0x01, 0x07, vec![0; 4]))
+                .await
+                .unwrap();
+            let accept = frame.id != 0x09;
+            receiver.send(ack(&frame, accept)).await.unwrap();
+            received.push(frame);
+        }
+        received
+    });
+
+    let mut gnss = Configurator::new(SerialFramed::new(host, UbxCodec::new()));
+    gnss.set_rate(Duration::from_millis(200)).await.unwrap();
+    gnss.set_nmea_rate(NmeaMessage::Gsv, 0).await.unwrap();
+    let err = gnss.save().await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    drop(gnss);
+
+    let received = device.await.unwrap();
+    assert_eq!(received[0], UbxFrame::cfg_rate(200));
+    assert_eq!(&received[1].payload[..], [0xf0, 0x03, 0x00]);
+    assert_eq!(received.len(), 3);
+}
+
+#[tokio::test]
+async fn unanswered_configuration_is_retried() {
+    let (host, receiver) = SerialStream::pair().expect("unable to create ptty pair");
+    let mut receiver = SerialFramed::new(receiver, UbxCodec::new());
+    let device = tokio::spawn(async move {
+        // Miss the first message, answer the second
+        let first = receiver.next().await.unwrap().unwrap();
+        let second = receiver.next().await.unwrap().unwrap();
+        assert_eq!(first, second);
+        receiver.send(ack(&second, true)).await.unwrap();
+        receiver
+    });
+
+    let mut gnss = Configurator::new(SerialFramed::new(host, UbxCodec::new()));
+    gnss.set_timeout(Duration::from_millis(100));
+    gnss.set_message_rate(0x01, 0x07, 1).await.unwrap();
+    let _receiver = device.await.unwrap();
+
+    gnss.set_retries(0);
+    let err = gnss.save().await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    assert_eq!(
+        UbxFrame::new(0x05, 0x01, vec![0x06, 0x09]).ack(),
+        Some(Ack::Ack {
+            class: 0x06,
+            id: 0x09
+        })
+    );
+}
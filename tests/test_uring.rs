@@ -0,0 +1,16 @@
+#![cfg(all(target_os = "linux", feature = "uring"))]
+use tokio_serial::uring::UringSerialStream;
+
+#[test]
+fn uring_pair_transfers_data() {
+    tokio_uring::start(async {
+        let (master, slave) = UringSerialStream::pair().expect("Unable to create ptty pair");
+
+        let (result, _) = master.write_all(b"hello".to_vec()).await;
+        result.expect("Unable to write");
+
+        let (result, buf) = slave.read(vec![0u8; 16]).await;
+        let n = result.expect("Unable to read");
+        assert_eq!(&buf[..n], b"hello");
+    });
+}
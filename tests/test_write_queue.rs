@@ -0,0 +1,80 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+use tokio_serial::write_queue::WriteQueue;
+
+/// Records every write it is handed
+#[derive(Clone, Default)]
+struct Recorder {
+    writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    fail: bool,
+}
+
+impl AsyncWrite for Recorder {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.fail {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        self.writes.lock().unwrap().push(buf.to_vec());
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn waiting_frames_are_coalesced_up_to_the_mtu() {
+    let (mut queue, handle) = WriteQueue::new();
+    queue.set_coalescing(Some(6));
+    let first = handle.send(b"abc".to_vec());
+    let second = handle.send(b"de".to_vec());
+    let third = handle.send(b"fgh".to_vec());
+    drop(handle);
+
+    let port = Recorder::default();
+    queue.run(port.clone()).await.unwrap();
+    first.await.unwrap();
+    second.await.unwrap();
+    third.await.unwrap();
+    assert_eq!(*port.writes.lock().unwrap(), [&b"abcde"[..], b"fgh"]);
+}
+
+#[tokio::test]
+async fn frames_are_written_alone_by_default() {
+    let (queue, handle) = WriteQueue::new();
+    let completions = vec![handle.send(b"a".to_vec()), handle.send(b"b".to_vec())];
+    drop(handle);
+
+    let port = Recorder::default();
+    queue.run(port.clone()).await.unwrap();
+    for completion in completions {
+        completion.await.unwrap();
+    }
+    assert_eq!(*port.writes.lock().unwrap(), [b"a", b"b"]);
+}
+
+#[tokio::test]
+async fn failed_write_fails_its_frames() {
+    let (queue, handle) = WriteQueue::new();
+    let frame = handle.send(b"a".to_vec());
+    let port = Recorder {
+        fail: true,
+        ..Recorder::default()
+    };
+    assert!(queue.run(port).await.is_err());
+    assert_eq!(frame.await.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    let late = handle.send(b"b".to_vec());
+    assert_eq!(late.await.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "codec")]
+use bytes::{Bytes, BytesMut};
+use tokio_serial::codec::xbee::{ApiFrame, AtCommand, ReceivePacket, XBeeCodec};
+use tokio_serial::codec::{Checked, ChecksumPolicy, Frame};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn at_command_matches_reference() {
+    let mut codec = XBeeCodec::new();
+    let mut dst = BytesMut::new();
+    let command = AtCommand::new(1, *b"NI", Bytes::new());
+    codec
+        .encode(ApiFrame::AtCommand(command.clone()), &mut dst)
+        .unwrap();
+    assert_eq!(&dst[..], b"\x7e\x00\x04\x08\x01\x4e\x49\x5f");
+    assert_eq!(
+        codec.decode(&mut dst).unwrap(),
+        Some(ApiFrame::AtCommand(command))
+    );
+}
+
+#[test]
+fn escaped_frames_decode() {
+    let mut codec = XBeeCodec::escaped();
+    let mut src = BytesMut::from(&b"\x00\x11\x7e\x00\x02\x23\x7d"[..]);
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    src.extend_from_slice(b"\x31\xcb");
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(ApiFrame::Other {
+            frame_type: 0x23,
+            data: Bytes::from_static(b"\x11"),
+        })
+    );
+    assert!(src.is_empty());
+    assert_eq!(codec.resync_stats().skipped_bytes(), 2);
+
+    // The same frame unescaped
+    let mut codec = XBeeCodec::new();
+    let mut src = BytesMut::from(&b"\x7e\x00\x02\x23\x11\xcb"[..]);
+    assert!(codec.decode(&mut src).unwrap().is_some());
+}
+
+#[test]
+fn receive_packet_decodes() {
+    let mut src = BytesMut::from(
+        &b"\x7e\x00\x12\x90\x00\x13\xa2\x00\x40\x52\x2b\xaa\x7d\x84\x01\x52\x78\x44\x61\x74\x61\x0d"[..],
+    );
+    match XBeeCodec::new().decode(&mut src).unwrap() {
+        Some(ApiFrame::Receive(ReceivePacket {
+            source,
+            network_address,
+            options,
+            data,
+        })) => {
+            assert_eq!(source, 0x0013_a200_4052_2baa);
+            assert_eq!(network_address, 0x7d84);
+            assert_eq!(options, 0x01);
+            assert_eq!(&data[..], b"RxData");
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn bad_frames_follow_policy() {
+    let mut codec = Checked::new(XBeeCodec::escaped());
+    let mut src = BytesMut::from(&b"\x7e\x00\x04\x08\x01\x4e\x49\x00"[..]);
+    // Cut short by the next frame
+    src.extend_from_slice(b"\x7e\x00\x05\x08");
+    src.extend_from_slice(b"\x7e\x00\x02\x8a\x00\x75");
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Frame::Corrupt(Bytes::from_static(
+            b"\x7e\x00\x04\x08\x01\x4e\x49\x00"
+        )))
+    );
+    assert!(matches!(
+        codec.decode(&mut src),
+        Ok(Some(Frame::Corrupt(_)))
+    ));
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Frame::Valid(ApiFrame::ModemStatus(0)))
+    );
+
+    let mut codec = codec.into_inner();
+    src.extend_from_slice(b"\x7e\x00\x02\x8a\x00\x00");
+    assert!(codec.decode(&mut src).is_err());
+    codec.set_checksum_policy(ChecksumPolicy::Drop);
+    src.extend_from_slice(b"\x7e\x00\x02\x8a\x00\x00");
+    assert_eq!(codec.decode(&mut src).unwrap(), None);
+    assert_eq!(codec.resync_stats().corrupt_frames(), 4);
+}